@@ -37,3 +37,26 @@ pub async fn is_auto_launch_enabled(app: tauri::AppHandle) -> Result<bool, Strin
     let manager = app.autolaunch();
     manager.is_enabled().map_err(|e| e.to_string())
 }
+
+/// 获取开机自启方式 (off / app / headless)
+#[tauri::command]
+pub async fn get_launch_mode(
+    app: tauri::AppHandle,
+) -> Result<crate::modules::background_service::LaunchMode, String> {
+    Ok(crate::modules::background_service::get_launch_mode(&app))
+}
+
+/// 设置开机自启方式: app 为最小化启动桌面端，headless 为仅运行反代服务 (无窗口)
+#[tauri::command]
+pub async fn set_launch_mode(
+    app: tauri::AppHandle,
+    mode: crate::modules::background_service::LaunchMode,
+) -> Result<crate::modules::background_service::LaunchMode, String> {
+    crate::modules::background_service::set_launch_mode(&app, mode)
+}
+
+/// 本次是否由开机自启以最小化方式启动 (前端据此保持窗口隐藏)
+#[tauri::command]
+pub fn is_launched_minimized() -> bool {
+    crate::modules::background_service::launched_minimized()
+}
@@ -54,6 +54,41 @@ pub async fn add_account(
     )
     .await;
 
+    // 7. 后台探测该账号等级实际支持的模型特性
+    let probe_on_add = modules::config::load_app_config()
+        .map(|c| c.proxy.experimental.capability_probe_on_add)
+        .unwrap_or(false);
+    if probe_on_add {
+        let account_id = account.id.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = modules::capability_probe::probe_accounts(vec![account_id], Vec::new()).await {
+                modules::logger::log_warn(&format!("[Capability Probe] 新账号探测失败: {}", e));
+            }
+        });
+    }
+
+    Ok(account)
+}
+
+/// 添加 AI Studio API Key 账号 (与 OAuth 账号一起参与反代调度)
+#[tauri::command]
+pub async fn add_api_key_account(
+    app: tauri::AppHandle,
+    api_key: String,
+    name: Option<String>,
+) -> Result<Account, String> {
+    let upstream_proxy = modules::config::load_app_config()
+        .map(|c| c.proxy.upstream_proxy)
+        .unwrap_or_default();
+    crate::proxy::upstream::aistudio::verify_key(&api_key, &upstream_proxy).await?;
+    let account = modules::add_api_key_account(name, api_key)?;
+    modules::logger::log_info(&format!("添加 API Key 账号成功: {}", account.email));
+
+    let _ = crate::commands::proxy::reload_proxy_accounts(
+        app.state::<crate::commands::proxy::ProxyServiceState>(),
+    )
+    .await;
+
     Ok(account)
 }
 
@@ -305,27 +340,15 @@ pub async fn save_config(
     config: AppConfig,
 ) -> Result<(), String> {
     modules::save_app_config(&config)?;
+    modules::notifications::update_config(config.notifications.clone());
+    modules::logger::set_log_format(config.proxy.log_format);
 
     // 通知托盘配置已更新
     let _ = app.emit("config://updated", ());
 
     // 热更新正在运行的服务
-    let instance_lock = proxy_state.instance.read().await;
-    if let Some(instance) = instance_lock.as_ref() {
-        // 更新模型映射
-        instance.axum_server.update_mapping(&config.proxy).await;
-        // 更新上游代理
-        instance
-            .axum_server
-            .update_proxy(config.proxy.upstream_proxy.clone())
-            .await;
-        // 更新安全策略 (auth)
-        instance.axum_server.update_security(&config.proxy).await;
-        // 更新 z.ai 配置
-        instance.axum_server.update_zai(&config.proxy).await;
-        // 更新实验性配置
-        instance.axum_server.update_experimental(&config.proxy).await;
-        tracing::debug!("已同步热更新反代服务配置");
+    if let Some(instance) = proxy_state.instance.read().await.as_ref() {
+        crate::commands::proxy::hot_reload_proxy_config(instance, &config.proxy).await;
     }
 
     Ok(())
@@ -690,6 +713,24 @@ pub async fn save_update_settings(
     crate::modules::update_checker::save_update_settings(&settings)
 }
 
+/// 获取更新状态 (通道、下载中、已下载待安装的版本)
+#[tauri::command]
+pub async fn get_update_status() -> Result<crate::modules::update_checker::UpdateStatus, String> {
+    Ok(crate::modules::update_checker::get_update_status())
+}
+
+/// 下载并校验所选通道的最新版本，安装推迟到 install_pending_update 或退出时
+#[tauri::command]
+pub async fn download_update(app: tauri::AppHandle) -> Result<crate::modules::update_checker::UpdateStatus, String> {
+    crate::modules::update_checker::download_update(&app).await
+}
+
+/// 安装已下载的更新，restart 为 true 时立即重启
+#[tauri::command]
+pub async fn install_pending_update(app: tauri::AppHandle, restart: bool) -> Result<(), String> {
+    crate::modules::update_checker::install_pending_update(&app, restart)
+}
+
 
 
 /// 切换账号的反代禁用状态
@@ -756,6 +797,122 @@ pub async fn toggle_proxy_status(
     Ok(())
 }
 
+/// 临时暂停账号 (维护模式): 不参与反代调度与保活，账号保留；minutes 为空表示直到手动恢复
+#[tauri::command]
+pub async fn pause_account(
+    app: tauri::AppHandle,
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+    minutes: Option<u32>,
+    reason: Option<String>,
+) -> Result<(), String> {
+    if minutes == Some(0) {
+        return Err("暂停时长必须大于 0".to_string());
+    }
+    let mut account = modules::account::load_account(&account_id)?;
+    account.pause(chrono::Utc::now().timestamp(), minutes, reason);
+    modules::account::save_account(&account)?;
+
+    modules::logger::log_info(&format!(
+        "账号已暂停: {} ({})",
+        account.email,
+        minutes.map_or("直到手动恢复".to_string(), |m| format!("{} 分钟", m))
+    ));
+
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    crate::modules::tray::update_tray_menus(&app);
+    Ok(())
+}
+
+/// 恢复暂停的账号
+#[tauri::command]
+pub async fn resume_account(
+    app: tauri::AppHandle,
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+) -> Result<(), String> {
+    let mut account = modules::account::load_account(&account_id)?;
+    account.resume();
+    modules::account::save_account(&account)?;
+    modules::logger::log_info(&format!("账号已恢复: {}", account.email));
+
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    crate::modules::tray::update_tray_menus(&app);
+    Ok(())
+}
+
+/// 获取全部账号的当前状态 (反代未运行时不含限流冷却)
+#[tauri::command]
+pub async fn get_account_states(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+) -> Result<Vec<crate::models::AccountStatus>, String> {
+    let accounts = modules::account::list_accounts()?;
+    let instance = proxy_state.instance.read().await;
+    let now = chrono::Utc::now().timestamp();
+    let breakers = crate::proxy::circuit_breaker::CircuitBreakers::global();
+    Ok(accounts
+        .iter()
+        .map(|a| match instance.as_ref() {
+            Some(instance) => instance.token_manager.account_status(a),
+            None => a.status(now, None, breakers.is_account_open(&a.email)),
+        })
+        .collect())
+}
+
+/// 设置账号标签 (去除空白与重复)
+#[tauri::command]
+pub async fn set_account_tags(account_id: String, tags: Vec<String>) -> Result<(), String> {
+    let mut account = modules::account::load_account(&account_id)?;
+    let mut cleaned: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        if !cleaned.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            cleaned.push(tag.to_string());
+        }
+    }
+    account.tags = cleaned;
+    modules::account::save_account(&account)
+}
+
+/// 账号批量操作: 刷新全部 Token / 校验全部 Project ID / 按标签暂停或恢复 / 清理长期失效账号
+#[tauri::command]
+pub async fn bulk_account_action(
+    app: tauri::AppHandle,
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    action: modules::bulk_ops::BulkAction,
+) -> Result<modules::bulk_ops::BulkReport, String> {
+    let report = modules::bulk_ops::run(action).await?;
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    crate::modules::tray::update_tray_menus(&app);
+    Ok(report)
+}
+
+/// 设置账号的反代可用时间窗口 (schedule 为 None 或空窗口表示不限制)
+#[tauri::command]
+pub async fn set_account_schedule(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+    schedule: Option<crate::models::UsageSchedule>,
+) -> Result<(), String> {
+    let schedule = schedule.filter(|s| !s.windows.is_empty());
+    if let Some(s) = &schedule {
+        s.validate()?;
+    }
+
+    let mut account = modules::account::load_account(&account_id)?;
+    account.usage_schedule = schedule;
+    modules::account::save_account(&account)?;
+
+    modules::logger::log_info(&format!(
+        "账号可用时间窗口已更新: {} ({})",
+        account.email,
+        account.usage_schedule.as_ref().map_or(0, |s| s.windows.len())
+    ));
+
+    // 如果反代服务正在运行,重新加载账号池
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    Ok(())
+}
+
 /// 预热所有可用账号
 #[tauri::command]
 pub async fn warm_up_all_accounts() -> Result<String, String> {
@@ -768,6 +925,177 @@ pub async fn warm_up_account(account_id: String) -> Result<String, String> {
     modules::quota::warm_up_account(&account_id).await
 }
 
+/// 运行延迟/吞吐基准测试
+#[tauri::command]
+pub async fn run_benchmark(
+    request: crate::modules::benchmark::BenchmarkRequest,
+) -> Result<crate::modules::benchmark::BenchmarkReport, String> {
+    modules::benchmark::run_benchmark(request).await
+}
+
+/// 探测账号各模型实际支持的特性 (工具/图片/系统指令/思维/JSON)，结果并入能力表
+#[tauri::command]
+pub async fn probe_model_capabilities(
+    account_ids: Vec<String>,
+    models: Vec<String>,
+) -> Result<modules::capability_probe::ProbeReport, String> {
+    modules::capability_probe::probe_accounts(account_ids, models).await
+}
+
+/// 获取已保存的能力探测结果
+#[tauri::command]
+pub async fn get_capability_probes() -> Result<modules::capability_probe::ProbeReport, String> {
+    Ok(modules::capability_probe::get_report())
+}
+
+/// 获取模型上下文窗口 / 最大输出登记表 (内置表、远程清单与覆盖规则)
+#[tauri::command]
+pub async fn get_model_limits() -> Result<modules::model_limits::ModelLimitsReport, String> {
+    Ok(modules::model_limits::get_report())
+}
+
+/// 保存模型上限覆盖规则，立即对运行中的反代生效
+#[tauri::command]
+pub async fn set_model_limit_overrides(
+    overrides: Vec<crate::proxy::config::ModelLimitRule>,
+) -> Result<modules::model_limits::ModelLimitsReport, String> {
+    modules::model_limits::set_overrides(overrides)
+}
+
+/// 下载远程模型上限清单 (url 为空时使用配置中的地址)
+#[tauri::command]
+pub async fn refresh_model_limits_manifest(
+    url: Option<String>,
+) -> Result<modules::model_limits::ModelLimitsReport, String> {
+    modules::model_limits::refresh_manifest(url).await
+}
+
+/// 获取当前生效的 quirk 清单与本地覆盖
+#[tauri::command]
+pub async fn get_quirk_manifest() -> Result<modules::quirk_manifest::QuirkManifestReport, String> {
+    Ok(modules::quirk_manifest::get_report())
+}
+
+/// 立即下载并校验 quirk 清单
+#[tauri::command]
+pub async fn refresh_quirk_manifest() -> Result<modules::quirk_manifest::QuirkManifestReport, String> {
+    modules::quirk_manifest::refresh_manifest().await
+}
+
+/// 运行自检 (端口/DNS/TLS/Token/时钟偏差/端到端生成)，可选生成脱敏诊断包
+#[tauri::command]
+pub async fn run_diagnostics(
+    create_bundle: bool,
+) -> Result<crate::modules::diagnostics::DiagnosticsReport, String> {
+    modules::diagnostics::run_diagnostics(create_bundle).await
+}
+
+/// 首次启动向导: 检测端口是否可用 (被占用时给出建议端口)
+#[tauri::command]
+pub async fn check_setup_port(port: u16) -> Result<crate::modules::setup_wizard::PortCheck, String> {
+    Ok(modules::setup_wizard::check_port(port).await)
+}
+
+/// 首次启动向导: 端口 → API Key → 测试生成，进度通过 setup://progress 推送
+#[tauri::command]
+pub async fn run_setup_wizard(
+    app: tauri::AppHandle,
+    options: Option<crate::modules::setup_wizard::SetupOptions>,
+) -> Result<crate::modules::setup_wizard::SetupResult, String> {
+    modules::setup_wizard::run(Some(&app), options.unwrap_or_default()).await
+}
+
+/// 生成常用客户端的配置 (Cline / Codex / LiteLLM / OpenAI SDK 环境变量)
+#[tauri::command]
+pub async fn generate_client_configs(
+    options: Option<crate::modules::client_config::ClientConfigOptions>,
+) -> Result<Vec<crate::modules::client_config::GeneratedClientConfig>, String> {
+    modules::client_config::generate_all(options.unwrap_or_default())
+}
+
+/// 将单个客户端的配置写入文件
+#[tauri::command]
+pub async fn export_client_config(
+    kind: crate::modules::client_config::ClientConfigKind,
+    options: Option<crate::modules::client_config::ClientConfigOptions>,
+    file_path: String,
+) -> Result<(), String> {
+    modules::client_config::export(kind, options.unwrap_or_default(), &file_path)
+}
+
+/// 托盘: 获取反代状态 (运行状态 / 每分钟请求数 / 调度模式 / 账号暂停状态)
+#[tauri::command]
+pub async fn get_tray_proxy_state(app: tauri::AppHandle) -> Result<modules::tray::TrayProxyState, String> {
+    Ok(modules::tray::get_proxy_state(&app).await)
+}
+
+/// 托盘: 启动或停止反代服务
+#[tauri::command]
+pub async fn set_tray_proxy_running(
+    app: tauri::AppHandle,
+    running: bool,
+) -> Result<modules::tray::TrayProxyState, String> {
+    modules::tray::set_proxy_running(&app, running).await
+}
+
+/// 托盘: 切换调度模式 (写入配置并立即生效)
+#[tauri::command]
+pub async fn set_tray_scheduling_mode(
+    app: tauri::AppHandle,
+    mode: crate::proxy::sticky_config::SchedulingMode,
+) -> Result<modules::tray::TrayProxyState, String> {
+    modules::tray::set_scheduling_mode(&app, mode).await
+}
+
+/// 发送测试通知 (使用设置页当前未保存的通知配置)
+#[tauri::command]
+pub async fn send_test_notification(config: crate::models::NotificationConfig) -> Result<usize, String> {
+    modules::notifications::send_test(config).await
+}
+
+/// 立即生成日报 (默认昨天，date 格式 YYYY-MM-DD)；deliver 为 true 时同时推送桌面通知 / 写入报告文件
+#[tauri::command]
+pub async fn generate_daily_report(
+    config: crate::models::DailyReportConfig,
+    date: Option<String>,
+    deliver: bool,
+) -> Result<String, String> {
+    let date = match date {
+        Some(d) => chrono::NaiveDate::parse_from_str(&d, "%Y-%m-%d").map_err(|e| format!("日期格式无效: {}", e))?,
+        None => chrono::Local::now().date_naive() - chrono::Duration::days(1),
+    };
+    tokio::task::spawn_blocking(move || {
+        if deliver {
+            modules::daily_report::deliver(&config, date)
+        } else {
+            modules::daily_report::build_report(date).map(|r| modules::daily_report::render_markdown(&r))
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// 执行一次云同步 (WebDAV/S3)，direction 为空时自动判断方向
+#[tauri::command]
+pub async fn cloud_sync_now(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    direction: Option<crate::modules::cloud_sync::SyncDirection>,
+) -> Result<crate::modules::cloud_sync::CloudSyncResult, String> {
+    let result = modules::cloud_sync::sync_now(direction).await?;
+
+    // 拉取后刷新运行中的账号池
+    if result.action == crate::modules::cloud_sync::SyncAction::Pulled {
+        let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    }
+    Ok(result)
+}
+
+/// 获取云同步状态 (上次同步时间等)
+#[tauri::command]
+pub async fn get_cloud_sync_status() -> Result<crate::modules::cloud_sync::CloudSyncState, String> {
+    Ok(modules::cloud_sync::load_state())
+}
+
 // ============================================================================
 // HTTP API 设置命令
 // ============================================================================
@@ -814,6 +1142,11 @@ pub async fn get_token_stats_by_account(hours: i64) -> Result<Vec<AccountTokenSt
     crate::modules::token_stats::get_account_stats(hours)
 }
 
+#[tauri::command]
+pub async fn get_token_stats_by_tag(hours: i64, key: Option<String>) -> Result<Vec<crate::modules::token_stats::TagTokenStats>, String> {
+    crate::modules::token_stats::get_tag_stats(hours, key.as_deref())
+}
+
 #[tauri::command]
 pub async fn get_token_stats_summary(hours: i64) -> Result<TokenStatsSummary, String> {
     crate::modules::token_stats::get_summary_stats(hours)
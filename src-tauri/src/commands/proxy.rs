@@ -17,6 +17,7 @@ pub struct ProxyStatus {
 }
 
 /// 反代服务全局状态
+#[derive(Clone)]
 pub struct ProxyServiceState {
     pub instance: Arc<RwLock<Option<ProxyServiceInstance>>>,
     pub monitor: Arc<RwLock<Option<Arc<ProxyMonitor>>>>,
@@ -28,6 +29,8 @@ pub struct ProxyServiceInstance {
     pub token_manager: Arc<TokenManager>,
     pub axum_server: crate::proxy::AxumServer,
     pub server_handle: tokio::task::JoinHandle<()>,
+    /// 局域网 mDNS 广播 (Drop 时注销)
+    pub mdns: Option<crate::proxy::mdns::MdnsAdvertiser>,
 }
 
 impl ProxyServiceState {
@@ -45,6 +48,16 @@ pub async fn start_proxy_service(
     config: ProxyConfig,
     state: State<'_, ProxyServiceState>,
     app_handle: tauri::AppHandle,
+) -> Result<ProxyStatus, String> {
+    internal_start_proxy_service(config, &state, Some(app_handle)).await
+}
+
+/// 启动反代服务 (内部实现)
+/// app_handle 为 None 时运行于无界面模式 (headless)，不向前端推送事件
+pub async fn internal_start_proxy_service(
+    config: ProxyConfig,
+    state: &ProxyServiceState,
+    app_handle: Option<tauri::AppHandle>,
 ) -> Result<ProxyStatus, String> {
     let mut instance_lock = state.instance.write().await;
     
@@ -57,13 +70,15 @@ pub async fn start_proxy_service(
     {
         let mut monitor_lock = state.monitor.write().await;
         if monitor_lock.is_none() {
-            *monitor_lock = Some(Arc::new(ProxyMonitor::new(1000, Some(app_handle.clone()))));
+            *monitor_lock = Some(Arc::new(ProxyMonitor::new(1000, app_handle.clone())));
         }
         // Sync enabled state from config
         if let Some(monitor) = monitor_lock.as_ref() {
             monitor.set_enabled(config.enable_logging);
         }
     }
+    crate::modules::logger::set_log_format(config.log_format);
+    crate::proxy::telemetry::configure(&config.telemetry);
     
     let monitor = state.monitor.read().await.as_ref().unwrap().clone();
     
@@ -78,14 +93,22 @@ pub async fn start_proxy_service(
     // 同步 UI 传递的调度配置
     token_manager.update_sticky_config(config.scheduling.clone()).await;
     
+    // 多副本部署: 先从共享存储拉取账号，再加载
+    let state_sync = crate::proxy::state_store::prepare(&config.state_store).await?;
+
     // 3. 加载账号
     let active_accounts = token_manager.load_accounts().await
         .map_err(|e| format!("加载账号失败: {}", e))?;
+
+    // 恢复崩溃/重启前的限流冷却、估算用量与签名缓存
+    crate::proxy::state_journal::open_and_restore(&app_data_dir, &token_manager);
     
     if active_accounts == 0 {
         let zai_enabled = config.zai.enabled
             && !matches!(config.zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
-        if !zai_enabled {
+        let has_backend = config.openai_backends.iter().any(|b| b.enabled) || config.vertex.enabled
+            || !token_manager.api_key_accounts().is_empty();
+        if !zai_enabled && !has_backend {
             return Err("没有可用账号，请先添加账号".to_string());
         }
     }
@@ -93,7 +116,7 @@ pub async fn start_proxy_service(
     // 启动 Axum 服务器
     let (axum_server, server_handle) =
         match crate::proxy::AxumServer::start(
-            config.get_bind_address().to_string(),
+            config.listen_addresses(),
             config.port,
             token_manager.clone(),
             config.custom_mapping.clone(),
@@ -101,29 +124,78 @@ pub async fn start_proxy_service(
             config.upstream_proxy.clone(),
             crate::proxy::ProxySecurityConfig::from_proxy_config(&config),
             config.zai.clone(),
+            config.openai_backends.clone(),
+            config.vertex.clone(),
+            config.ai_studio.clone(),
             monitor.clone(),
             config.experimental.clone(),
-
+            config.upstream_endpoints.clone(),
+            config.upstream_pool.clone(),
+            config.max_request_body_bytes(),
+            config.cors.clone(),
+            config.hooks.clone(),
+            config.pii_scrub.clone(),
+            config.stream_tee.clone(),
+            config.token_budget.clone(),
+            config.model_fallback.clone(),
+            config.ab_test.clone(),
+            config.latency_budget.clone(),
+            config.racing.clone(),
+            config.account_pinning.clone(),
+            config.local_socket.clone(),
+            config.clone(),
+            app_handle.is_some(),
         ).await {
             Ok((server, handle)) => (server, handle),
             Err(e) => return Err(format!("启动 Axum 服务器失败: {}", e)),
         };
     
+    // 局域网模式下广播服务
+    let mdns = if config.allow_lan_access && config.mdns_advertise {
+        let mut models = crate::proxy::common::model_mapping::get_all_dynamic_models(
+            &RwLock::new(config.custom_mapping.clone()),
+        )
+        .await;
+        models.extend(crate::proxy::providers::openai_compat::listed_models(&config.openai_backends));
+        let auth_required = !matches!(
+            crate::proxy::ProxySecurityConfig::from_proxy_config(&config).effective_auth_mode(),
+            crate::proxy::ProxyAuthMode::Off
+        );
+        match crate::proxy::mdns::MdnsAdvertiser::start(config.port, &models, auth_required) {
+            Ok(advertiser) => Some(advertiser),
+            Err(e) => {
+                tracing::warn!("mDNS 广播失败: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     // 创建服务实例
     let instance = ProxyServiceInstance {
         config: config.clone(),
         token_manager: token_manager.clone(), // Clone for ProxyServiceInstance
         axum_server,
         server_handle,
+        mdns,
     };
     
     *instance_lock = Some(instance);
+    // 服务启动成功后再开始与共享存储同步
+    if let Some(sync) = state_sync {
+        sync.start(token_manager.clone());
+    }
+    crate::modules::watchdog::set_desired(Some(config.clone()));
     
 
     // 保存配置到全局 AppConfig
-    let mut app_config = crate::modules::config::load_app_config().map_err(|e| e)?;
-    app_config.proxy = config.clone();
-    crate::modules::config::save_app_config(&app_config).map_err(|e| e)?;
+    // headless (无 AppHandle) 的配置可能来自 --config 或环境变量/命令行覆盖，不写回桌面端设置
+    if app_handle.is_some() {
+        let mut app_config = crate::modules::config::load_app_config().map_err(|e| e)?;
+        app_config.proxy = config.clone();
+        crate::modules::config::save_app_config(&app_config).map_err(|e| e)?;
+    }
     
     Ok(ProxyStatus {
         running: true,
@@ -138,20 +210,96 @@ pub async fn start_proxy_service(
 pub async fn stop_proxy_service(
     state: State<'_, ProxyServiceState>,
 ) -> Result<(), String> {
-    let mut instance_lock = state.instance.write().await;
-    
-    if instance_lock.is_none() {
+    if state.instance.read().await.is_none() {
         return Err("服务未运行".to_string());
     }
-    
+
+    // 用户主动停止，看门狗不再拉起
+    crate::modules::watchdog::set_desired(None);
+    shutdown_proxy_gracefully(&state).await;
+    Ok(())
+}
+
+/// 优雅停机: 停止监听 -> 排空在途请求/流 -> 刷新日志库 -> 持久化调度器状态
+/// 供 stop/restart 命令与应用退出时共用
+pub async fn shutdown_proxy_gracefully(state: &ProxyServiceState) {
+    let mut instance_lock = state.instance.write().await;
+
+    crate::proxy::state_store::stop();
+
     // 停止 Axum 服务器
     if let Some(instance) = instance_lock.take() {
-        instance.axum_server.stop();
-        // 等待服务器任务完成
+        let drain_timeout = Duration::from_secs(instance.config.shutdown_drain_timeout);
+        // 先撤销广播，避免客户端在排空期间发现即将关闭的服务
+        drop(instance.mdns);
+        instance.axum_server.stop(drain_timeout);
+        // 等待服务器任务完成 (包含在途连接排空)
         instance.server_handle.await.ok();
     }
-    
-    Ok(())
+    drop(instance_lock);
+
+    // 给监控中间件的异步日志写入留出落盘时间
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    if let Err(e) = crate::modules::proxy_db::checkpoint() {
+        tracing::warn!("刷新请求日志数据库失败: {}", e);
+    }
+    if let Err(e) = crate::modules::token_stats::checkpoint() {
+        tracing::warn!("刷新 Token 统计数据库失败: {}", e);
+    }
+    crate::modules::scheduler::persist_state();
+    crate::proxy::state_journal::StateJournal::global().compact();
+}
+
+/// 将配置热更新到正在运行的服务 (端口/监听地址等需要重启的设置除外)
+/// 供保存配置与切换档案时共用
+pub async fn hot_reload_proxy_config(instance: &ProxyServiceInstance, config: &ProxyConfig) {
+    // 更新模型映射
+    instance.axum_server.update_mapping(config).await;
+    // 更新上游代理
+    instance
+        .axum_server
+        .update_proxy(config.upstream_proxy.clone())
+        .await;
+    // 更新安全策略 (auth)
+    instance.axum_server.update_security(config).await;
+    // 更新 z.ai 配置
+    instance.axum_server.update_zai(config).await;
+    // 更新 OpenAI 兼容上游
+    instance.axum_server.update_openai_backends(config).await;
+    // 更新 Vertex AI 配置
+    instance.axum_server.update_vertex(config).await;
+    instance.axum_server.update_ai_studio(config).await;
+    // 更新实验性配置
+    instance.axum_server.update_experimental(config).await;
+    // 更新扩展钩子
+    instance.axum_server.update_hooks(config).await;
+    instance.axum_server.update_pii_scrub(config).await;
+    instance.axum_server.update_stream_tee(config).await;
+    instance.axum_server.update_token_budget(config).await;
+    instance.axum_server.update_model_fallback(config).await;
+    instance.axum_server.update_ab_test(config).await;
+    instance.axum_server.update_latency_budget(config).await;
+    instance.axum_server.update_racing(config).await;
+    instance.axum_server.update_account_pinning(config).await;
+    // 更新上游端点
+    instance.axum_server.update_upstream_endpoints(config);
+    instance.axum_server.update_proxy_config(config).await;
+    // 更新链路追踪导出
+    crate::proxy::telemetry::configure(&config.telemetry);
+    tracing::debug!("已同步热更新反代服务配置");
+}
+
+/// 重启反代服务 (优雅排空后使用最新保存的配置重新启动)
+#[tauri::command]
+pub async fn restart_proxy_service(
+    state: State<'_, ProxyServiceState>,
+    app_handle: tauri::AppHandle,
+) -> Result<ProxyStatus, String> {
+    let config = crate::modules::config::load_app_config()?.proxy;
+
+    shutdown_proxy_gracefully(&state).await;
+    internal_start_proxy_service(config, &state, Some(app_handle)).await
 }
 
 /// 获取反代服务状态
@@ -293,6 +441,172 @@ pub async fn export_proxy_logs_json(
     Ok(count)
 }
 
+/// [NEW] 按会话指纹导出完整对话 (Markdown 或 OpenAI JSONL), 返回消息条数
+#[tauri::command]
+pub async fn export_conversation_transcript(
+    session_id: String,
+    format: crate::modules::transcript::TranscriptFormat,
+    file_path: String,
+) -> Result<usize, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::modules::transcript::export_session(&session_id, format, &file_path)
+    })
+    .await
+    .map_err(|e| format!("Export task failed: {}", e))?
+}
+
+/// 列出会话的全部分支 (每个叶节点一条，根 -> 叶)
+#[tauri::command]
+pub async fn list_conversation_branches(
+    session_id: String,
+) -> Result<Vec<crate::modules::conversation_tree::ConversationBranch>, String> {
+    tokio::task::spawn_blocking(move || crate::modules::conversation_tree::list_branches(&session_id))
+        .await
+        .map_err(|e| format!("Query task failed: {}", e))?
+}
+
+/// 裁剪分支: 删除该叶节点到最近分叉点之间的日志，返回删除条数
+#[tauri::command]
+pub async fn prune_conversation_branch(
+    session_id: String,
+    leaf_message_id: String,
+) -> Result<usize, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::modules::conversation_tree::prune_branch(&session_id, &leaf_message_id)
+    })
+    .await
+    .map_err(|e| format!("Prune task failed: {}", e))?
+}
+
+/// A/B 实验对比汇总 (experiment 为空时汇总全部实验)
+#[tauri::command]
+pub async fn get_ab_experiment_summary(
+    experiment: Option<String>,
+) -> Result<Vec<crate::proxy::ab_test::ExperimentSummary>, String> {
+    let results = tokio::task::spawn_blocking(move || {
+        crate::modules::proxy_db::get_ab_results(experiment.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Query task failed: {}", e))??;
+    Ok(crate::proxy::ab_test::summarize(&results))
+}
+
+/// 清除 A/B 实验结果，返回删除条数
+#[tauri::command]
+pub async fn clear_ab_results(experiment: Option<String>) -> Result<usize, String> {
+    crate::modules::proxy_db::clear_ab_results(experiment.as_deref())
+}
+
+/// 获取当前在途请求 (排队 / 等待上游 / 输出中)
+#[tauri::command]
+pub async fn get_inflight_requests() -> Result<Vec<crate::proxy::inflight::InflightInfo>, String> {
+    Ok(crate::proxy::inflight::InflightRegistry::global().snapshot())
+}
+
+/// 获取各 (账号, 端点) 熔断器的状态
+#[tauri::command]
+pub async fn get_circuit_breakers() -> Result<Vec<crate::proxy::circuit_breaker::CircuitInfo>, String> {
+    Ok(crate::proxy::circuit_breaker::CircuitBreakers::global().snapshot())
+}
+
+/// 复位熔断器 (指定账号或全部)，返回复位数量
+#[tauri::command]
+pub async fn reset_circuit_breakers(account: Option<String>) -> Result<usize, String> {
+    Ok(crate::proxy::circuit_breaker::CircuitBreakers::global().reset(account.as_deref()))
+}
+
+/// 取消指定 X-AG-Request-Id 的在途生成请求，返回 false 表示请求不存在或已结束
+#[tauri::command]
+pub async fn cancel_proxy_request(request_id: String) -> Result<bool, String> {
+    Ok(crate::proxy::inflight::InflightRegistry::global().cancel(&request_id))
+}
+
+/// 列出提示词预设
+#[tauri::command]
+pub async fn list_prompt_presets() -> Result<Vec<crate::proxy::presets::PromptPreset>, String> {
+    Ok(crate::proxy::presets::PresetStore::global().list())
+}
+
+/// 新增或覆盖提示词预设 (客户端以 `preset:<name>` 作为模型名选用)
+#[tauri::command]
+pub async fn save_prompt_preset(preset: crate::proxy::presets::PromptPreset) -> Result<(), String> {
+    crate::proxy::presets::PresetStore::global().save(preset)
+}
+
+/// 删除提示词预设，返回 false 表示不存在
+#[tauri::command]
+pub async fn delete_prompt_preset(name: String) -> Result<bool, String> {
+    crate::proxy::presets::PresetStore::global().delete(&name)
+}
+
+/// 列出配置档案及当前激活的档案
+#[tauri::command]
+pub async fn list_config_profiles() -> Result<crate::proxy::profiles::ProfileList, String> {
+    Ok(crate::proxy::profiles::ProfileStore::global().list())
+}
+
+/// 新增或覆盖配置档案
+#[tauri::command]
+pub async fn save_config_profile(profile: crate::proxy::profiles::ConfigProfile) -> Result<(), String> {
+    crate::proxy::profiles::ProfileStore::global().save(profile)
+}
+
+/// 删除配置档案，返回 false 表示不存在 (删除激活的档案时仅取消激活，不回滚当前配置)
+#[tauri::command]
+pub async fn delete_config_profile(name: String) -> Result<bool, String> {
+    crate::proxy::profiles::ProfileStore::global().delete(&name)
+}
+
+/// 激活配置档案: 用档案快照替换反代配置并立即生效；name 为空时仅取消激活 (保留当前配置)
+#[tauri::command]
+pub async fn activate_config_profile(
+    state: State<'_, ProxyServiceState>,
+    app_handle: tauri::AppHandle,
+    name: Option<String>,
+) -> Result<(), String> {
+    activate_profile(&state, Some(app_handle), name.as_deref()).await
+}
+
+pub async fn activate_profile(
+    state: &ProxyServiceState,
+    app_handle: Option<tauri::AppHandle>,
+    name: Option<&str>,
+) -> Result<(), String> {
+    use crate::proxy::profiles::{self, ProfileStore};
+
+    let store = ProfileStore::global();
+    let Some(name) = name else {
+        store.set_active(None)?;
+        tracing::info!("[Profiles] 已取消激活档案");
+        return Ok(());
+    };
+    let profile = store.get(name).ok_or_else(|| format!("profile_not_found: {}", name))?;
+
+    let mut app_config = crate::modules::config::load_app_config()?;
+    let previous = app_config.proxy.clone();
+    app_config.proxy = profiles::apply_to(&previous, &profile);
+    crate::modules::config::save_app_config(&app_config)?;
+    store.set_active(Some(name))?;
+
+    let running = state.instance.read().await.as_ref().map(|i| i.config.clone());
+    if let Some(running) = running {
+        if profiles::needs_restart(&running, &app_config.proxy) {
+            shutdown_proxy_gracefully(state).await;
+            internal_start_proxy_service(app_config.proxy.clone(), state, app_handle.clone()).await?;
+        } else if let Some(instance) = state.instance.read().await.as_ref() {
+            hot_reload_proxy_config(instance, &app_config.proxy).await;
+            instance.token_manager.update_sticky_config(app_config.proxy.scheduling.clone()).await;
+        }
+    }
+
+    if let Some(app) = app_handle {
+        use tauri::Emitter;
+        let _ = app.emit("config://updated", ());
+    }
+    tracing::info!("[Profiles] 已切换到档案 {}", name);
+    Ok(())
+}
+
 /// 获取带搜索条件的日志数量
 #[tauri::command]
 pub async fn get_proxy_logs_count_filtered(
@@ -313,6 +627,62 @@ pub async fn get_proxy_logs_filtered(
     crate::modules::proxy_db::get_logs_filtered(&filter, errors_only, limit, offset)
 }
 
+/// 日志查看器查询: 全文检索 + 模型/账号/状态/标签/时间过滤 + 分页 + 聚合计数
+#[tauri::command]
+pub async fn query_proxy_logs(
+    query: crate::modules::proxy_db::LogQuery,
+) -> Result<crate::modules::proxy_db::LogQueryResult, String> {
+    tokio::task::spawn_blocking(move || crate::modules::proxy_db::query_logs(&query))
+        .await
+        .map_err(|e| format!("Query task failed: {}", e))?
+}
+
+/// 立即按保留策略清理一次请求历史与流式落盘文件
+#[tauri::command]
+pub async fn run_log_retention() -> Result<crate::modules::retention::RetentionReport, String> {
+    let app_config = crate::modules::config::load_app_config()?;
+    let retention = app_config.proxy.retention;
+    let tee_dir = app_config.proxy.stream_tee.directory;
+    tokio::task::spawn_blocking(move || crate::modules::retention::apply(&retention, &tee_dir))
+        .await
+        .map_err(|e| format!("Retention task failed: {}", e))?
+}
+
+/// 紧急擦除: 安全删除全部请求历史、流式落盘文件与签名缓存
+#[tauri::command]
+pub async fn panic_wipe_history(
+    state: State<'_, ProxyServiceState>,
+) -> Result<crate::modules::retention::WipeReport, String> {
+    // 反代运行中以其生效配置为准 (headless 的 --config / 环境变量覆盖)
+    let running = match state.instance.read().await.as_ref() {
+        Some(instance) => Some(instance.axum_server.proxy_config().await),
+        None => None,
+    };
+    let proxy_config = match running {
+        Some(config) => config,
+        None => crate::modules::config::load_app_config().map(|c| c.proxy).unwrap_or_default(),
+    };
+    let tee_dir = proxy_config.stream_tee.directory.clone();
+    let image_dir = proxy_config.experimental.image_output.save_dir.clone();
+    let local = tokio::task::spawn_blocking(move || crate::modules::retention::panic_wipe(&tee_dir, &image_dir))
+        .await
+        .map_err(|e| format!("Wipe task failed: {}", e))?;
+    // 本地擦除失败时同样删除共享存储中的镜像
+    let remote = crate::proxy::state_store::wipe_history(&proxy_config.state_store).await;
+
+    // 内存中的最近日志与统计一并清空
+    let monitor_lock = state.monitor.read().await;
+    if let Some(monitor) = monitor_lock.as_ref() {
+        monitor.clear().await;
+    }
+    match (local, remote) {
+        (Ok(report), Ok(n)) => Ok(crate::modules::retention::WipeReport { deleted_remote_history: n, ..report }),
+        (Ok(_), Err(e)) => Err(format!("Wipe incomplete: state store history: {}", e)),
+        (Err(e), Ok(_)) => Err(e),
+        (Err(e), Err(remote)) => Err(format!("{}; state store history: {}", e, remote)),
+    }
+}
+
 /// 生成 API Key
 #[tauri::command]
 pub fn generate_api_key() -> String {
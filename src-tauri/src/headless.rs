@@ -0,0 +1,180 @@
+// Headless 模式: 仅运行反代核心 (无 Tauri 界面)
+// 用法: antigravity-manager serve [--config <path>] [overrides...]
+// 适合以 systemd / Windows 服务方式常驻在家庭服务器上，与桌面端共用同一账号目录
+// 配置按 配置文件 < AG_PROXY_* 环境变量 < 命令行参数 叠加 (见 modules::config_overrides)
+
+use crate::commands::proxy::{internal_start_proxy_service, shutdown_proxy_gracefully, ProxyServiceState};
+use crate::models::AppConfig;
+use crate::modules::{self, config_overrides, logger};
+use crate::proxy::ProxyConfig;
+use std::path::PathBuf;
+use tracing::{error, info};
+
+const USAGE: &str = "Usage: antigravity-manager serve [--config <path>] [options]\n\n\
+Runs the API proxy without the desktop UI, using the shared account store.\n\n\
+Options:\n  \
+  --config <path>        Config file (gui_config.json format, or a bare proxy config).\n                         \
+Defaults to the desktop app's gui_config.json.\n  \
+  --data-dir <dir>       Data directory (accounts, logs, config). Env: AG_DATA_DIR\n  \
+  --port <port>          Listen port\n  \
+  --api-key <key>        API key required from clients\n  \
+  --allow-lan            Listen on all interfaces instead of 127.0.0.1\n  \
+  --auth-mode <mode>     off | strict | all_except_health | auto\n  \
+  --log-format <format>  text | json (one JSON event per line, for Loki/Vector)\n  \
+  --set <path>=<value>   Override any proxy setting, e.g. --set upstream_proxy.url=http://10.0.0.1:7890\n                         \
+(repeatable; nested keys use '.')\n  \
+  --print-config         Print the effective proxy config as JSON and exit\n  \
+  -h, --help             Show this help\n\n\
+Every proxy setting can also be set with an environment variable: AG_PROXY_<KEY>,\n\
+nested keys separated by '__' (AG_PROXY_PORT=8045, AG_PROXY_UPSTREAM_PROXY__URL=...).\n\
+Precedence: config file < environment < command line.";
+
+/// 加载配置: 支持完整 AppConfig 或仅包含 proxy 部分的 JSON
+fn load_config(path: Option<PathBuf>) -> Result<AppConfig, String> {
+    let Some(path) = path else {
+        return modules::config::load_app_config();
+    };
+
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+
+    if let Ok(config) = serde_json::from_str::<AppConfig>(&content) {
+        return Ok(config);
+    }
+    let proxy: ProxyConfig = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse config {}: {}", path.display(), e))?;
+    let mut config = AppConfig::new();
+    config.proxy = proxy;
+    Ok(config)
+}
+
+/// 命令行参数
+#[derive(Debug, Default)]
+struct ServeArgs {
+    config_path: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    overrides: Vec<config_overrides::Override>,
+    print_config: bool,
+    help: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<ServeArgs, String> {
+    let mut parsed = ServeArgs::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        // 同时支持 `--flag value` 与 `--flag=value`
+        let (flag, inline) = match arg.split_once('=') {
+            Some((f, v)) if f.starts_with("--") => (f, Some(v.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = |name: &str| -> Result<String, String> {
+            match &inline {
+                Some(v) => Ok(v.clone()),
+                None => iter.next().cloned().ok_or_else(|| format!("{} requires a value", name)),
+            }
+        };
+        match flag {
+            "--config" | "-c" => parsed.config_path = Some(PathBuf::from(value("--config")?)),
+            "--data-dir" => parsed.data_dir = Some(PathBuf::from(value("--data-dir")?)),
+            "--port" => parsed.overrides.push(("port".to_string(), value("--port")?)),
+            "--api-key" => parsed.overrides.push(("api_key".to_string(), value("--api-key")?)),
+            "--auth-mode" => parsed.overrides.push(("auth_mode".to_string(), value("--auth-mode")?)),
+            "--log-format" => parsed.overrides.push(("log_format".to_string(), value("--log-format")?)),
+            "--allow-lan" => parsed
+                .overrides
+                .push(("allow_lan_access".to_string(), inline.clone().unwrap_or_else(|| "true".to_string()))),
+            "--set" => parsed.overrides.push(config_overrides::parse_assignment(&value("--set")?)?),
+            "--print-config" => parsed.print_config = true,
+            "-h" | "--help" => parsed.help = true,
+            other => return Err(format!("Unknown argument: {}\n\n{}", other, USAGE)),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Headless 入口，`args` 为 `serve` 之后的参数
+pub fn run_headless(args: &[String]) -> Result<(), String> {
+    let args = parse_args(args)?;
+    if args.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    // 在初始化日志/数据库之前设置，后续所有数据目录访问都使用该目录
+    if let Some(dir) = &args.data_dir {
+        std::env::set_var(modules::account::DATA_DIR_ENV, dir);
+    }
+
+    let app_config = load_config(args.config_path)?;
+    let mut overrides = config_overrides::env_overrides();
+    overrides.extend(args.overrides);
+    let mut proxy_config = config_overrides::apply(&app_config.proxy, &overrides)?;
+    proxy_config.enabled = true;
+
+    if args.print_config {
+        let json = serde_json::to_string_pretty(&proxy_config).map_err(|e| e.to_string())?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    // 在初始化前设置，启动阶段的日志也使用目标格式
+    logger::set_log_format(proxy_config.log_format);
+    logger::init_logger();
+    if let Err(e) = modules::token_stats::init_db() {
+        error!("Failed to initialize token stats database: {}", e);
+    }
+    if !overrides.is_empty() {
+        // 只记录键名，避免 API Key 等敏感值进入日志
+        let keys: Vec<&str> = overrides.iter().map(|(k, _)| k.as_str()).collect();
+        info!("[Headless] Config overrides applied: {}", keys.join(", "));
+    }
+
+    tauri::async_runtime::block_on(async move {
+        let state = ProxyServiceState::new();
+        let status = internal_start_proxy_service(proxy_config, &state, None).await?;
+        info!(
+            "[Headless] Proxy listening on port {} with {} active accounts",
+            status.port, status.active_accounts
+        );
+
+        // HTTP API (供桌面端/插件远程管理)
+        if let Ok(settings) = modules::http_api::load_settings() {
+            if settings.enabled {
+                modules::http_api::spawn_server(settings.port);
+            }
+        }
+
+        modules::scheduler::start_keep_warm_loop();
+        modules::retention::start_retention_loop(Some(state.clone()));
+        modules::watchdog::start(state.clone(), None);
+
+        wait_for_shutdown_signal().await;
+        info!("[Headless] Shutdown signal received, draining...");
+        modules::watchdog::set_desired(None);
+        shutdown_proxy_gracefully(&state).await;
+        info!("[Headless] Stopped");
+        Ok::<(), String>(())
+    })
+}
+
+/// 等待 Ctrl+C (所有平台) 或 SIGTERM (Unix, systemd stop)
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
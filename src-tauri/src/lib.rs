@@ -3,8 +3,11 @@ mod modules;
 mod commands;
 mod utils;
 mod proxy;  // Proxy service module
+mod headless; // Headless (no UI) serve mode
 pub mod error;
 
+pub use headless::run_headless;
+
 use tauri::Manager;
 use modules::logger;
 use tracing::{info, warn, error};
@@ -65,6 +68,7 @@ pub fn run() {
         ))
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
             let _ = app.get_webview_window("main")
                 .map(|window| {
@@ -101,7 +105,17 @@ pub fn run() {
             }
 
             modules::tray::create_tray(app.handle())?;
+            // 开机自启 (--minimized) 时只保留托盘
+            #[cfg(target_os = "macos")]
+            if modules::background_service::launched_minimized() {
+                app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+            }
+            modules::tray::start_state_loop(app.handle().clone());
             info!("Tray created");
+            modules::notifications::init(app.handle().clone());
+            modules::capability_probe::init();
+            modules::model_limits::init();
+            modules::quirk_manifest::init();
             
             // Auto-start proxy service
             let handle = app.handle().clone();
@@ -109,6 +123,8 @@ pub fn run() {
                 // Load config
                 if let Ok(config) = modules::config::load_app_config() {
                     if config.proxy.auto_start {
+                        // 启动失败 (如开机时端口暂被占用) 时由看门狗继续重试
+                        modules::watchdog::set_desired(Some(config.proxy.clone()));
                         let state = handle.state::<commands::proxy::ProxyServiceState>();
                         // Attempt to start service
                         if let Err(e) = commands::proxy::start_proxy_service(
@@ -124,8 +140,14 @@ pub fn run() {
                 }
             });
             
+            modules::watchdog::start(
+                app.state::<commands::proxy::ProxyServiceState>().inner().clone(),
+                Some(app.handle().clone()),
+            );
+
             // Start smart scheduler
             modules::scheduler::start_scheduler(app.handle().clone());
+            modules::update_checker::start_update_loop(app.handle().clone());
             
             // Start HTTP API server (for external calls, e.g. VS Code plugin)
             match modules::http_api::load_settings() {
@@ -162,6 +184,7 @@ pub fn run() {
             // Account management commands
             commands::list_accounts,
             commands::add_account,
+            commands::add_api_key_account,
             commands::delete_account,
             commands::delete_accounts,
             commands::reorder_accounts,
@@ -205,11 +228,21 @@ pub fn run() {
             commands::get_update_settings,
             commands::save_update_settings,
             commands::should_check_updates,
+            commands::get_update_status,
+            commands::download_update,
+            commands::install_pending_update,
             commands::update_last_check_time,
             commands::toggle_proxy_status,
+            commands::pause_account,
+            commands::resume_account,
+            commands::get_account_states,
+            commands::set_account_tags,
+            commands::bulk_account_action,
+            commands::set_account_schedule,
             // Proxy service commands
             commands::proxy::start_proxy_service,
             commands::proxy::stop_proxy_service,
+            commands::proxy::restart_proxy_service,
             commands::proxy::get_proxy_status,
             commands::proxy::get_proxy_stats,
             commands::proxy::get_proxy_logs,
@@ -218,8 +251,27 @@ pub fn run() {
             commands::proxy::get_proxy_logs_count,
             commands::proxy::export_proxy_logs,
             commands::proxy::export_proxy_logs_json,
+            commands::proxy::export_conversation_transcript,
+            commands::proxy::list_conversation_branches,
+            commands::proxy::prune_conversation_branch,
+            commands::proxy::get_ab_experiment_summary,
+            commands::proxy::clear_ab_results,
+            commands::proxy::get_inflight_requests,
+            commands::proxy::cancel_proxy_request,
+            commands::proxy::get_circuit_breakers,
+            commands::proxy::reset_circuit_breakers,
+            commands::proxy::list_prompt_presets,
+            commands::proxy::save_prompt_preset,
+            commands::proxy::delete_prompt_preset,
+            commands::proxy::list_config_profiles,
+            commands::proxy::save_config_profile,
+            commands::proxy::delete_config_profile,
+            commands::proxy::activate_config_profile,
             commands::proxy::get_proxy_logs_count_filtered,
             commands::proxy::get_proxy_logs_filtered,
+            commands::proxy::query_proxy_logs,
+            commands::proxy::run_log_retention,
+            commands::proxy::panic_wipe_history,
             commands::proxy::set_proxy_monitor_enabled,
             commands::proxy::clear_proxy_logs,
             commands::proxy::generate_api_key,
@@ -234,9 +286,32 @@ pub fn run() {
             // Autostart commands
             commands::autostart::toggle_auto_launch,
             commands::autostart::is_auto_launch_enabled,
+            commands::autostart::get_launch_mode,
+            commands::autostart::set_launch_mode,
+            commands::autostart::is_launched_minimized,
             // Warmup commands
             commands::warm_up_all_accounts,
             commands::warm_up_account,
+            commands::run_benchmark,
+            commands::probe_model_capabilities,
+            commands::get_capability_probes,
+            commands::get_model_limits,
+            commands::set_model_limit_overrides,
+            commands::refresh_model_limits_manifest,
+            commands::get_quirk_manifest,
+            commands::refresh_quirk_manifest,
+            commands::run_diagnostics,
+            commands::check_setup_port,
+            commands::run_setup_wizard,
+            commands::generate_client_configs,
+            commands::export_client_config,
+            commands::get_tray_proxy_state,
+            commands::set_tray_proxy_running,
+            commands::set_tray_scheduling_mode,
+            commands::send_test_notification,
+            commands::generate_daily_report,
+            commands::cloud_sync_now,
+            commands::get_cloud_sync_status,
             // HTTP API settings commands
             commands::get_http_api_settings,
             commands::save_http_api_settings,
@@ -245,6 +320,7 @@ pub fn run() {
             commands::get_token_stats_daily,
             commands::get_token_stats_weekly,
             commands::get_token_stats_by_account,
+            commands::get_token_stats_by_tag,
             commands::get_token_stats_summary,
             proxy::cli_sync::get_cli_sync_status,
             proxy::cli_sync::execute_cli_sync,
@@ -264,8 +340,13 @@ pub fn run() {
                     app_handle.set_activation_policy(tauri::ActivationPolicy::Regular).unwrap_or(());
                 }
             }
-            // Suppress unused variable warnings on non-macOS platforms
-            #[cfg(not(target_os = "macos"))]
-            let _ = (app_handle, event);
+            // Graceful shutdown on app exit: drain in-flight streams and flush state
+            if let tauri::RunEvent::Exit = event {
+                let state = app_handle.state::<commands::proxy::ProxyServiceState>();
+                modules::watchdog::set_desired(None);
+                tauri::async_runtime::block_on(commands::proxy::shutdown_proxy_gracefully(&state));
+                // 推迟安装的更新在退出时安装
+                modules::update_checker::install_on_exit(app_handle);
+            }
         });
 }
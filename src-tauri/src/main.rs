@@ -9,5 +9,15 @@ fn main() {
         std::env::set_var("WEBKIT_DISABLE_DMABUF_RENDERER", "1");
     }
 
+    // Headless mode: `antigravity-manager serve --config <path>`
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(|a| a.as_str()) == Some("serve") {
+        if let Err(e) = antigravity_tools_lib::run_headless(&args[2..]) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     antigravity_tools_lib::run()
 }
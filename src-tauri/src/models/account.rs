@@ -8,6 +8,9 @@ pub struct Account {
     pub id: String,
     pub email: String,
     pub name: Option<String>,
+    /// 账号类型: OAuth 账号或 AI Studio API Key (Key 存于 token.access_token)
+    #[serde(default, skip_serializing_if = "AccountKind::is_oauth")]
+    pub kind: AccountKind,
     pub token: TokenData,
     /// 可选的设备指纹，用于切换账号时固定机器信息
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -37,6 +40,23 @@ pub struct Account {
     /// 受配额保护禁用的模型列表 [NEW #621]
     #[serde(default, skip_serializing_if = "HashSet::is_empty")]
     pub protected_models: HashSet<String>,
+    /// Unix timestamp of the last successful keep-warm ping
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_warm_at: Option<i64>,
+    /// 反代可使用该账号的时间窗口 (为空表示不限制)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_schedule: Option<UsageSchedule>,
+    /// 账号标签 (按标签批量操作)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// 临时暂停 (维护模式) 的开始时间；暂停期间不参与反代调度与保活，账号本身保留
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused_at: Option<i64>,
+    /// 暂停到期时间 (Unix 秒)，到期后自动恢复；为空表示直到手动恢复
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused_until: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused_reason: Option<String>,
     pub created_at: i64,
     pub last_used: i64,
 }
@@ -48,6 +68,7 @@ impl Account {
             id,
             email,
             name: None,
+            kind: AccountKind::OAuth,
             token,
             device_profile: None,
             device_history: Vec::new(),
@@ -59,11 +80,21 @@ impl Account {
             proxy_disabled_reason: None,
             proxy_disabled_at: None,
             protected_models: HashSet::new(),
+            last_warm_at: None,
+            usage_schedule: None,
+            tags: Vec::new(),
+            paused_at: None,
+            paused_until: None,
+            paused_reason: None,
             created_at: now,
             last_used: now,
         }
     }
 
+    pub fn is_api_key(&self) -> bool {
+        self.kind == AccountKind::ApiKey
+    }
+
     pub fn update_last_used(&mut self) {
         self.last_used = chrono::Utc::now().timestamp();
     }
@@ -71,6 +102,167 @@ impl Account {
     pub fn update_quota(&mut self, quota: QuotaData) {
         self.quota = Some(quota);
     }
+
+    /// 标签匹配不区分大小写
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag.trim()))
+    }
+
+    /// 是否处于临时暂停中 (到期的暂停视为已恢复)
+    pub fn is_paused(&self, now: i64) -> bool {
+        self.paused_at.is_some() && self.paused_until.is_none_or(|until| now < until)
+    }
+
+    /// 暂停账号；minutes 为空表示直到手动恢复
+    pub fn pause(&mut self, now: i64, minutes: Option<u32>, reason: Option<String>) {
+        self.paused_at = Some(now);
+        self.paused_until = minutes.map(|m| now + m as i64 * 60);
+        self.paused_reason = reason.filter(|r| !r.trim().is_empty());
+    }
+
+    pub fn resume(&mut self) {
+        self.paused_at = None;
+        self.paused_until = None;
+        self.paused_reason = None;
+    }
+
+    /// 推导账号状态，优先级: 凭据失效 > 反代禁用 > 暂停 / 时间窗口外 > 限流冷却 > 熔断 > 可用
+    /// cooldown_until 与 circuit_open 为反代运行时状态 (限流到期时间、全部端点熔断)
+    pub fn status(&self, now: i64, cooldown_until: Option<i64>, circuit_open: bool) -> AccountStatus {
+        let (state, reason, until) = if self.disabled {
+            (AccountState::Error, self.disabled_reason.clone().or(Some("invalid_grant".to_string())), None)
+        } else if self.proxy_disabled {
+            (AccountState::Disabled, self.proxy_disabled_reason.clone(), None)
+        } else if self.is_paused(now) {
+            (AccountState::Paused, self.paused_reason.clone(), self.paused_until)
+        } else if self.usage_schedule.as_ref().is_some_and(|s| !s.allows_now()) {
+            (AccountState::Paused, Some("usage_schedule".to_string()), None)
+        } else if let Some(until) = cooldown_until.filter(|u| *u > now) {
+            (AccountState::Cooldown, Some("rate_limited".to_string()), Some(until))
+        } else if circuit_open {
+            (AccountState::Error, Some("circuit_open".to_string()), None)
+        } else {
+            (AccountState::Active, None, None)
+        };
+        AccountStatus { account_id: self.id.clone(), email: self.email.clone(), state, reason, until }
+    }
+}
+
+/// 账号状态 (由持久化字段与反代运行时状态推导，调度、保活、托盘与界面共用)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountState {
+    /// 可参与调度
+    Active,
+    /// 临时暂停或不在可用时间窗口内，到期 / 进入窗口后自动恢复
+    Paused,
+    /// 限流冷却中
+    Cooldown,
+    /// 手动或配额保护禁用反代
+    Disabled,
+    /// 凭据失效 (需重新登录) 或全部端点熔断
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountStatus {
+    pub account_id: String,
+    pub email: String,
+    pub state: AccountState,
+    pub reason: Option<String>,
+    /// 状态预计结束时间 (Unix 秒)
+    pub until: Option<i64>,
+}
+
+/// 账号类型
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountKind {
+    #[default]
+    #[serde(rename = "oauth")]
+    OAuth,
+    /// AI Studio (generativelanguage.googleapis.com) API Key，不走 v1internal，不参与 OAuth 账号轮换
+    ApiKey,
+}
+
+impl AccountKind {
+    pub fn is_oauth(&self) -> bool {
+        *self == AccountKind::OAuth
+    }
+}
+
+/// 账号使用时间窗口 (本地时间)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UsageSchedule {
+    #[serde(default)]
+    pub windows: Vec<ScheduleWindow>,
+}
+
+/// 单个时间窗口；start > end 表示跨午夜 (如 22:00-06:00)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduleWindow {
+    /// 适用的星期 (1=周一 ... 7=周日)，为空表示每天；跨午夜窗口按开始当天计算
+    #[serde(default)]
+    pub days: Vec<u8>,
+    /// "HH:MM"
+    pub start: String,
+    /// "HH:MM"，"24:00" 表示当天结束
+    pub end: String,
+}
+
+fn parse_hhmm(s: &str) -> Option<u32> {
+    let (h, m) = s.trim().split_once(':')?;
+    let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+    if m >= 60 || h > 24 || (h == 24 && m > 0) {
+        return None;
+    }
+    Some(h * 60 + m)
+}
+
+impl ScheduleWindow {
+    fn applies_to(&self, weekday: u8) -> bool {
+        self.days.is_empty() || self.days.contains(&weekday)
+    }
+
+    /// weekday: 1=周一 ... 7=周日；minute: 当天零点起的分钟数
+    fn contains(&self, weekday: u8, minute: u32) -> bool {
+        let (Some(start), Some(end)) = (parse_hhmm(&self.start), parse_hhmm(&self.end)) else {
+            return false;
+        };
+        if start <= end {
+            return self.applies_to(weekday) && minute >= start && minute < end;
+        }
+        // 跨午夜: 今天的前半段，或昨天开始的后半段
+        let yesterday = if weekday == 1 { 7 } else { weekday - 1 };
+        (self.applies_to(weekday) && minute >= start) || (self.applies_to(yesterday) && minute < end)
+    }
+}
+
+impl UsageSchedule {
+    pub fn validate(&self) -> Result<(), String> {
+        for w in &self.windows {
+            if parse_hhmm(&w.start).is_none() || parse_hhmm(&w.end).is_none() {
+                return Err(format!("时间格式无效 (应为 HH:MM): {}-{}", w.start, w.end));
+            }
+            if let Some(d) = w.days.iter().find(|d| !(1..=7).contains(*d)) {
+                return Err(format!("星期取值无效 (应为 1-7): {}", d));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn allows_at<T: chrono::Datelike + chrono::Timelike>(&self, at: &T) -> bool {
+        if self.windows.is_empty() {
+            return true;
+        }
+        let weekday = at.weekday().number_from_monday() as u8;
+        let minute = at.hour() * 60 + at.minute();
+        self.windows.iter().any(|w| w.contains(weekday, minute))
+    }
+
+    pub fn allows_now(&self) -> bool {
+        self.allows_at(&chrono::Local::now())
+    }
 }
 
 /// 账号索引数据（accounts.json）
@@ -126,3 +318,71 @@ pub struct DeviceProfileVersion {
     #[serde(default)]
     pub is_current: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_account_status_priority() {
+        let token = TokenData::new("at".to_string(), "rt".to_string(), 3600, None, None, None);
+        let mut acc = Account::new("id-1".to_string(), "a@example.com".to_string(), token);
+        let now = 1_700_000_000;
+        assert_eq!(acc.status(now, None, false).state, AccountState::Active);
+        assert_eq!(acc.status(now, Some(now + 60), false).state, AccountState::Cooldown);
+        assert_eq!(acc.status(now, Some(now - 1), true).reason.as_deref(), Some("circuit_open"));
+
+        acc.pause(now, Some(30), Some("maintenance".to_string()));
+        let status = acc.status(now, Some(now + 60), true);
+        assert_eq!(status.state, AccountState::Paused);
+        assert_eq!(status.until, Some(now + 1800));
+        // 到期自动恢复
+        assert!(!acc.is_paused(now + 1800));
+        assert_eq!(acc.status(now + 1800, None, false).state, AccountState::Active);
+
+        acc.pause(now, None, None);
+        assert!(acc.is_paused(i64::MAX));
+        acc.proxy_disabled = true;
+        assert_eq!(acc.status(now, None, false).state, AccountState::Disabled);
+        acc.disabled = true;
+        assert_eq!(acc.status(now, None, false).state, AccountState::Error);
+
+        acc.disabled = false;
+        acc.proxy_disabled = false;
+        acc.resume();
+        assert_eq!(acc.status(now, None, false).state, AccountState::Active);
+    }
+
+    fn window(days: &[u8], start: &str, end: &str) -> ScheduleWindow {
+        ScheduleWindow { days: days.to_vec(), start: start.to_string(), end: end.to_string() }
+    }
+
+    #[test]
+    fn test_usage_schedule_windows() {
+        // 2024-01-01 是周一
+        let at = |d: u32, h: u32, m: u32| NaiveDate::from_ymd_opt(2024, 1, d).unwrap().and_hms_opt(h, m, 0).unwrap();
+
+        let evenings = UsageSchedule { windows: vec![window(&[1, 2, 3, 4, 5], "18:00", "24:00"), window(&[6, 7], "00:00", "24:00")] };
+        assert!(!evenings.allows_at(&at(1, 10, 0)));
+        assert!(evenings.allows_at(&at(1, 18, 0)));
+        assert!(evenings.allows_at(&at(6, 10, 0)));
+
+        // 跨午夜窗口按开始当天计算: 周五 22:00 - 周六 06:00
+        let overnight = UsageSchedule { windows: vec![window(&[5], "22:00", "06:00")] };
+        assert!(overnight.allows_at(&at(5, 23, 0)));
+        assert!(overnight.allows_at(&at(6, 5, 59)));
+        assert!(!overnight.allows_at(&at(6, 6, 0)));
+        assert!(!overnight.allows_at(&at(5, 5, 0)));
+
+        assert!(UsageSchedule::default().allows_at(&at(1, 3, 0)));
+    }
+
+    #[test]
+    fn test_usage_schedule_validate() {
+        assert!(UsageSchedule { windows: vec![window(&[], "09:00", "24:00")] }.validate().is_ok());
+        assert!(UsageSchedule { windows: vec![window(&[], "9am", "17:00")] }.validate().is_err());
+        assert!(UsageSchedule { windows: vec![window(&[8], "09:00", "17:00")] }.validate().is_err());
+        assert!(UsageSchedule { windows: vec![window(&[], "24:30", "17:00")] }.validate().is_err());
+    }
+}
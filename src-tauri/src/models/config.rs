@@ -23,6 +23,12 @@ pub struct AppConfig {
     pub quota_protection: QuotaProtectionConfig, // [NEW] Quota protection configuration
     #[serde(default)]
     pub pinned_quota_models: PinnedQuotaModelsConfig, // [NEW] Pinned quota models list
+    #[serde(default)]
+    pub keep_warm: KeepWarmConfig, // [NEW] Keep-warm ping configuration
+    #[serde(default)]
+    pub cloud_sync: CloudSyncConfig, // [NEW] Encrypted WebDAV/S3 sync between devices
+    #[serde(default)]
+    pub notifications: NotificationConfig, // [NEW] Webhook / desktop notifications for account events
 }
 
 /// Scheduled warmup configuration
@@ -60,6 +66,192 @@ impl Default for ScheduledWarmupConfig {
     }
 }
 
+/// Keep-warm configuration
+/// Periodically sends a tiny request per account so the first real request
+/// of a session doesn't pay token-refresh / cold-start latency.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeepWarmConfig {
+    /// Whether keep-warm pings are enabled
+    pub enabled: bool,
+
+    /// Ping interval in minutes
+    #[serde(default = "default_keep_warm_interval")]
+    pub interval_minutes: u32,
+
+    /// Model used for the ping request (cheapest model recommended)
+    #[serde(default = "default_keep_warm_model")]
+    pub model: String,
+}
+
+fn default_keep_warm_interval() -> u32 {
+    30
+}
+
+fn default_keep_warm_model() -> String {
+    "gemini-3-flash".to_string()
+}
+
+impl KeepWarmConfig {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: default_keep_warm_interval(),
+            model: default_keep_warm_model(),
+        }
+    }
+}
+
+impl Default for KeepWarmConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cloud sync backend type
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CloudSyncBackend {
+    Webdav,
+    S3,
+}
+
+impl Default for CloudSyncBackend {
+    fn default() -> Self {
+        Self::Webdav
+    }
+}
+
+/// Encrypted sync of the account store and settings via WebDAV or S3-compatible storage
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CloudSyncConfig {
+    /// Whether cloud sync is enabled
+    #[serde(default)]
+    pub enabled: bool,
+
+    #[serde(default)]
+    pub backend: CloudSyncBackend,
+
+    /// WebDAV folder URL (e.g. https://dav.example.com/remote.php/dav/files/me/antigravity/)
+    #[serde(default)]
+    pub webdav_url: String,
+    #[serde(default)]
+    pub webdav_username: String,
+    #[serde(default)]
+    pub webdav_password: String,
+
+    /// S3-compatible endpoint (e.g. https://s3.amazonaws.com or https://<id>.r2.cloudflarestorage.com)
+    #[serde(default)]
+    pub s3_endpoint: String,
+    #[serde(default)]
+    pub s3_region: String,
+    #[serde(default)]
+    pub s3_bucket: String,
+    /// Optional key prefix inside the bucket
+    #[serde(default)]
+    pub s3_prefix: String,
+    #[serde(default)]
+    pub s3_access_key: String,
+    #[serde(default)]
+    pub s3_secret_key: String,
+
+    /// Passphrase used to encrypt the sync bundle (never uploaded)
+    #[serde(default)]
+    pub passphrase: String,
+}
+
+/// Account event notifications
+/// Events: `quota_exhausted`, `token_refresh_failed`, `all_accounts_unhealthy`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    /// Show native desktop notifications
+    #[serde(default)]
+    pub desktop: bool,
+
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// Minimum interval between two notifications of the same event for the same account
+    #[serde(default = "default_notification_cooldown")]
+    pub cooldown_secs: u64,
+
+    /// Daily usage summary (tokens per account, errors, top models)
+    #[serde(default)]
+    pub daily_report: DailyReportConfig,
+}
+
+fn default_notification_cooldown() -> u64 {
+    600
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            desktop: false,
+            webhooks: Vec::new(),
+            cooldown_secs: default_notification_cooldown(),
+            daily_report: DailyReportConfig::default(),
+        }
+    }
+}
+
+/// Daily report covering the previous local day, generated once the configured hour has passed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyReportConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Local hour (0-23) after which yesterday's report is generated
+    #[serde(default = "default_daily_report_hour")]
+    pub hour: u8,
+
+    /// Deliver the summary as a desktop notification
+    #[serde(default = "default_daily_report_desktop")]
+    pub desktop: bool,
+
+    /// Markdown file the full report is appended to; empty = don't write
+    #[serde(default)]
+    pub report_path: String,
+}
+
+fn default_daily_report_hour() -> u8 {
+    9
+}
+
+fn default_daily_report_desktop() -> bool {
+    true
+}
+
+impl Default for DailyReportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hour: default_daily_report_hour(),
+            desktop: default_daily_report_desktop(),
+            report_path: String::new(),
+        }
+    }
+}
+
+/// A single webhook target (ntfy / Discord / Slack / custom)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WebhookConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub name: String,
+    pub url: String,
+    /// Events to send; empty = all events
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// JSON payload template with `{{event}}`, `{{title}}`, `{{message}}`, `{{account}}`, `{{timestamp}}` placeholders;
+    /// empty = default payload
+    #[serde(default)]
+    pub template: String,
+    /// Extra request headers (e.g. ntfy `Authorization`)
+    #[serde(default)]
+    pub headers: std::collections::HashMap<String, String>,
+}
+
 /// Quota protection configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QuotaProtectionConfig {
@@ -146,6 +338,9 @@ impl AppConfig {
             scheduled_warmup: ScheduledWarmupConfig::default(),
             quota_protection: QuotaProtectionConfig::default(),
             pinned_quota_models: PinnedQuotaModelsConfig::default(),
+            keep_warm: KeepWarmConfig::default(),
+            cloud_sync: CloudSyncConfig::default(),
+            notifications: NotificationConfig::default(),
         }
     }
 }
@@ -3,8 +3,8 @@ pub mod token;
 pub mod quota;
 pub mod config;
 
-pub use account::{Account, AccountIndex, AccountSummary, DeviceProfile, DeviceProfileVersion};
+pub use account::{Account, AccountIndex, AccountKind, AccountState, AccountStatus, AccountSummary, DeviceProfile, DeviceProfileVersion, ScheduleWindow, UsageSchedule};
 pub use token::TokenData;
 pub use quota::QuotaData;
-pub use config::{AppConfig, QuotaProtectionConfig};
+pub use config::{AppConfig, DailyReportConfig, NotificationConfig, QuotaProtectionConfig, WebhookConfig};
 
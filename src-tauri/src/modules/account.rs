@@ -4,7 +4,7 @@ use serde_json;
 use uuid::Uuid;
 use serde::Serialize;
 
-use crate::models::{Account, AccountIndex, AccountSummary, TokenData, QuotaData, DeviceProfile, DeviceProfileVersion,};
+use crate::models::{Account, AccountIndex, AccountKind, AccountSummary, TokenData, QuotaData, DeviceProfile, DeviceProfileVersion,};
 use crate::modules;
 use once_cell::sync::Lazy;
 use std::sync::Mutex;
@@ -16,12 +16,16 @@ static ACCOUNT_INDEX_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 const DATA_DIR: &str = ".antigravity_tools";
 const ACCOUNTS_INDEX: &str = "accounts.json";
 const ACCOUNTS_DIR: &str = "accounts";
+/// 覆盖数据目录的环境变量 (容器部署挂载卷时使用)
+pub const DATA_DIR_ENV: &str = "AG_DATA_DIR";
 
 // ... existing functions get_data_dir, get_accounts_dir, load_account_index, save_account_index ...
 /// Get data directory path
 pub fn get_data_dir() -> Result<PathBuf, String> {
-    let home = dirs::home_dir().ok_or("failed_to_get_home_dir")?;
-    let data_dir = home.join(DATA_DIR);
+    let data_dir = match std::env::var_os(DATA_DIR_ENV).filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::home_dir().ok_or("failed_to_get_home_dir")?.join(DATA_DIR),
+    };
     
     // Ensure directory exists
     if !data_dir.exists() {
@@ -197,6 +201,30 @@ pub fn add_account(email: String, name: Option<String>, token: TokenData) -> Res
     Ok(account)
 }
 
+/// Add an AI Studio API key account (the key is stored in token.access_token)
+pub fn add_api_key_account(name: Option<String>, api_key: String) -> Result<Account, String> {
+    let api_key = api_key.trim().to_string();
+    if !api_key.starts_with("AIza") || api_key.len() < 30 {
+        return Err("Invalid AI Studio API key (expected AIza...)".to_string());
+    }
+    // 以 Key 末 6 位生成唯一标识，避免在列表中暴露完整 Key
+    let email = format!("aistudio-{}@api-key", &api_key[api_key.len() - 6..]);
+    let token = TokenData {
+        access_token: api_key,
+        refresh_token: String::new(),
+        expires_in: 0,
+        expiry_timestamp: i64::MAX,
+        token_type: "ApiKey".to_string(),
+        email: None,
+        project_id: None,
+        session_id: None,
+    };
+    let mut account = add_account(email, name, token)?;
+    account.kind = AccountKind::ApiKey;
+    save_account(&account)?;
+    Ok(account)
+}
+
 /// Add or update account
 pub fn upsert_account(email: String, name: Option<String>, token: TokenData) -> Result<Account, String> {
     let _lock = ACCOUNT_INDEX_LOCK.lock().map_err(|e| format!("failed_to_acquire_lock: {}", e))?;
@@ -291,10 +319,34 @@ pub fn delete_account(account_id: &str) -> Result<(), String> {
         fs::remove_file(&account_path)
             .map_err(|e| format!("failed_to_delete_account_file: {}", e))?;
     }
+    crate::modules::capability_probe::forget_account(account_id);
     
     Ok(())
 }
 
+/// 写入从其他副本同步来的账号 (覆盖账号文件并补全索引)
+pub fn import_replicated_account(account: &Account) -> Result<(), String> {
+    let _lock = ACCOUNT_INDEX_LOCK.lock().map_err(|e| format!("failed_to_acquire_lock: {}", e))?;
+    let mut index = load_account_index()?;
+    save_account(account)?;
+
+    let summary = AccountSummary {
+        id: account.id.clone(),
+        email: account.email.clone(),
+        name: account.name.clone(),
+        created_at: account.created_at,
+        last_used: account.last_used,
+    };
+    match index.accounts.iter_mut().find(|s| s.id == account.id) {
+        Some(existing) => *existing = summary,
+        None => index.accounts.push(summary),
+    }
+    if index.current_account_id.is_none() {
+        index.current_account_id = Some(account.id.clone());
+    }
+    save_account_index(&index)
+}
+
 /// Batch delete accounts (atomic index operation)
 pub fn delete_accounts(account_ids: &[String]) -> Result<(), String> {
     let _lock = ACCOUNT_INDEX_LOCK.lock().map_err(|e| format!("failed_to_acquire_lock: {}", e))?;
@@ -316,6 +368,7 @@ pub fn delete_accounts(account_ids: &[String]) -> Result<(), String> {
         if account_path.exists() {
             let _ = fs::remove_file(&account_path);
         }
+        crate::modules::capability_probe::forget_account(account_id);
     }
     
     // If current account is empty, use first one as default
@@ -375,6 +428,9 @@ pub async fn switch_account(account_id: &str) -> Result<(), String> {
     }
     
     let mut account = load_account(account_id)?;
+    if account.is_api_key() {
+        return Err("API key accounts cannot be used to sign in to Antigravity".to_string());
+    }
     crate::modules::logger::log_info(&format!("Switching to account: {} (ID: {})", account.email, account.id));
     
     // 2. Ensure Token is valid (auto-refresh)
@@ -705,6 +761,14 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
     use crate::modules::oauth;
     use crate::error::AppError;
     use reqwest::StatusCode;
+
+    // API Key 账号没有远端配额接口，使用反代本地统计的当日请求数
+    if account.is_api_key() {
+        let limit = crate::modules::config::load_app_config()
+            .map(|c| c.proxy.ai_studio.daily_request_limit)
+            .unwrap_or(0);
+        return Ok(crate::proxy::upstream::aistudio::quota_snapshot(&account.id, limit));
+    }
     
     // 1. Time-based check - ensure Token is valid first
     let token = match oauth::ensure_fresh_token(&account.token).await {
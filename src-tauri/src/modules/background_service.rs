@@ -0,0 +1,199 @@
+// 开机自启 / 后台常驻
+// 两种自启方式互斥 (同时启用会争抢端口):
+// - app: 通过 autostart 插件启动桌面端，附带 --minimized，窗口保持隐藏只留托盘
+// - headless: 登录时直接运行 `antigravity-manager serve`，无窗口
+//   Windows: HKCU\...\Run 注册表项; macOS: LaunchAgent (崩溃后由 launchd 拉起); Linux: XDG autostart
+// 反代监听器本身由看门狗 (watchdog.rs) 守护。
+
+use serde::{Deserialize, Serialize};
+use tauri_plugin_autostart::ManagerExt;
+
+use crate::modules::logger;
+
+/// 桌面端自启时附带的参数 (与 autostart 插件注册的参数一致)
+pub const MINIMIZED_ARGS: [&str; 2] = ["--minimized", "--hidden"];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LaunchMode {
+    Off,
+    App,
+    Headless,
+}
+
+/// 本次是否以最小化方式启动 (开机自启)
+pub fn launched_minimized() -> bool {
+    std::env::args().any(|a| MINIMIZED_ARGS.contains(&a.as_str()))
+}
+
+fn current_exe() -> Result<String, String> {
+    std::env::current_exe()
+        .map(|p| p.to_string_lossy().into_owned())
+        .map_err(|e| format!("Failed to locate executable: {}", e))
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+    const ENTRY_NAME: &str = "AntigravityManagerProxy";
+
+    fn reg(args: &[&str]) -> Result<std::process::Output, String> {
+        Command::new("reg")
+            .args(args)
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .map_err(|e| format!("Failed to run reg.exe: {}", e))
+    }
+
+    pub fn is_enabled() -> bool {
+        reg(&["query", RUN_KEY, "/v", ENTRY_NAME]).map(|o| o.status.success()).unwrap_or(false)
+    }
+
+    pub fn enable(exe: &str) -> Result<(), String> {
+        let command = format!("\"{}\" serve", exe);
+        let output = reg(&["add", RUN_KEY, "/v", ENTRY_NAME, "/t", "REG_SZ", "/d", &command, "/f"])?;
+        if !output.status.success() {
+            return Err(format!("reg add failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(())
+    }
+
+    pub fn disable() -> Result<(), String> {
+        // 项不存在时同样视为成功
+        if is_enabled() {
+            let output = reg(&["delete", RUN_KEY, "/v", ENTRY_NAME, "/f"])?;
+            if !output.status.success() {
+                return Err(format!("reg delete failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod platform {
+    use std::path::PathBuf;
+
+    #[cfg(target_os = "macos")]
+    fn entry_path() -> Result<PathBuf, String> {
+        let home = dirs::home_dir().ok_or("failed_to_get_home_dir")?;
+        Ok(home.join("Library/LaunchAgents/com.lbjlaq.antigravity-tools.proxy.plist"))
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn entry_path() -> Result<PathBuf, String> {
+        let config = dirs::config_dir().ok_or("failed_to_get_config_dir")?;
+        Ok(config.join("autostart/antigravity-tools-proxy.desktop"))
+    }
+
+    #[cfg(target_os = "macos")]
+    pub(super) fn render_entry(exe: &str) -> String {
+        let exe = exe.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>com.lbjlaq.antigravity-tools.proxy</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>serve</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+</dict>
+</plist>
+"#,
+            exe
+        )
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub(super) fn render_entry(exe: &str) -> String {
+        format!(
+            "[Desktop Entry]\nType=Application\nName=Antigravity Tools Proxy\nExec=\"{}\" serve\nNoDisplay=true\nX-GNOME-Autostart-enabled=true\n",
+            exe.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+    }
+
+    pub fn is_enabled() -> bool {
+        entry_path().map(|p| p.exists()).unwrap_or(false)
+    }
+
+    pub fn enable(exe: &str) -> Result<(), String> {
+        let path = entry_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::write(&path, render_entry(exe)).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn disable() -> Result<(), String> {
+        let path = entry_path()?;
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// 当前生效的自启方式
+pub fn get_launch_mode(app: &tauri::AppHandle) -> LaunchMode {
+    if platform::is_enabled() {
+        LaunchMode::Headless
+    } else if app.autolaunch().is_enabled().unwrap_or(false) {
+        LaunchMode::App
+    } else {
+        LaunchMode::Off
+    }
+}
+
+fn disable_app_autostart(app: &tauri::AppHandle) -> Result<(), String> {
+    if app.autolaunch().is_enabled().unwrap_or(false) {
+        app.autolaunch().disable().map_err(|e| format!("禁用自动启动失败: {}", e))?;
+    }
+    Ok(())
+}
+
+/// 切换自启方式，同时关闭另一种方式
+pub fn set_launch_mode(app: &tauri::AppHandle, mode: LaunchMode) -> Result<LaunchMode, String> {
+    match mode {
+        LaunchMode::Off => {
+            disable_app_autostart(app)?;
+            platform::disable()?;
+        }
+        LaunchMode::App => {
+            platform::disable()?;
+            app.autolaunch().enable().map_err(|e| format!("启用自动启动失败: {}", e))?;
+        }
+        LaunchMode::Headless => {
+            disable_app_autostart(app)?;
+            platform::enable(&current_exe()?)?;
+        }
+    }
+    logger::log_info(&format!("[Launch] 开机自启方式已切换为 {:?}", mode));
+    Ok(get_launch_mode(app))
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_entry_runs_serve() {
+        let entry = platform::render_entry("/opt/Antigravity Tools/antigravity-tools");
+        assert!(entry.contains("serve"));
+        assert!(entry.contains("/opt/Antigravity Tools/antigravity-tools"));
+    }
+}
@@ -0,0 +1,439 @@
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::modules::{account, config, logger, quota};
+use crate::proxy::upstream::client::UpstreamClient;
+
+/// 1x1 transparent PNG used by the image benchmark case
+pub(crate) const TINY_PNG_BASE64: &str =
+    "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYAAAAAYAAjCB0C8AAAAASUVORK5CYII=";
+
+/// Benchmark scenario
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum BenchmarkCase {
+    ShortPrompt,
+    LongPrompt,
+    ToolCall,
+    Image,
+}
+
+impl BenchmarkCase {
+    fn all() -> Vec<Self> {
+        vec![Self::ShortPrompt, Self::LongPrompt, Self::ToolCall, Self::Image]
+    }
+}
+
+/// Benchmark request issued from the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkRequest {
+    /// Account IDs to benchmark (empty = all enabled accounts)
+    #[serde(default)]
+    pub account_ids: Vec<String>,
+    /// Upstream model IDs to benchmark
+    pub models: Vec<String>,
+    /// Scenarios to run (empty = all)
+    #[serde(default)]
+    pub cases: Vec<BenchmarkCase>,
+    /// Repetitions per (account, model, case)
+    #[serde(default = "default_iterations")]
+    pub iterations: u32,
+}
+
+fn default_iterations() -> u32 {
+    1
+}
+
+/// Single benchmark run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkSample {
+    pub account_email: String,
+    pub model: String,
+    pub case: BenchmarkCase,
+    /// Time to first token (ms)
+    pub ttft_ms: Option<u64>,
+    pub duration_ms: u64,
+    pub output_tokens: u32,
+    pub tokens_per_sec: Option<f64>,
+    pub error: Option<String>,
+}
+
+/// Aggregated result per (account, model)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkSummary {
+    pub account_email: String,
+    pub model: String,
+    pub runs: u32,
+    pub errors: u32,
+    pub error_rate: f64,
+    pub avg_ttft_ms: Option<f64>,
+    pub avg_tokens_per_sec: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub samples: Vec<BenchmarkSample>,
+    pub summaries: Vec<BenchmarkSummary>,
+}
+
+/// Build the v1internal request body for a scenario
+fn build_case_body(case: BenchmarkCase, model: &str, project_id: &str) -> Value {
+    let (contents, tools) = match case {
+        BenchmarkCase::ShortPrompt => (
+            json!([{ "role": "user", "parts": [{ "text": "Reply with a single short sentence about the sea." }] }]),
+            None,
+        ),
+        BenchmarkCase::LongPrompt => {
+            let filler = "The quick brown fox jumps over the lazy dog. ".repeat(400);
+            (
+                json!([{ "role": "user", "parts": [{ "text": format!("{}\n\nSummarize the text above in three paragraphs.", filler) }] }]),
+                None,
+            )
+        }
+        BenchmarkCase::ToolCall => (
+            json!([{ "role": "user", "parts": [{ "text": "What is the weather in Paris right now? Use the tool." }] }]),
+            Some(json!([{
+                "functionDeclarations": [{
+                    "name": "get_weather",
+                    "description": "Get current weather for a city",
+                    "parameters": {
+                        "type": "OBJECT",
+                        "properties": { "city": { "type": "STRING" } },
+                        "required": ["city"]
+                    }
+                }]
+            }])),
+        ),
+        BenchmarkCase::Image => (
+            json!([{ "role": "user", "parts": [
+                { "inlineData": { "mimeType": "image/png", "data": TINY_PNG_BASE64 } },
+                { "text": "Describe this image in one sentence." }
+            ]}]),
+            None,
+        ),
+    };
+
+    let mut request = json!({
+        "contents": contents,
+        "generationConfig": { "maxOutputTokens": 1024 }
+    });
+    if let Some(tools) = tools {
+        request["tools"] = tools;
+    }
+
+    json!({
+        "project": project_id,
+        "requestId": format!("bench-{}", uuid::Uuid::new_v4()),
+        "request": request,
+        "model": model,
+        "userAgent": "antigravity",
+        "requestType": "agent"
+    })
+}
+
+/// Returns true when an SSE chunk carries generated content (text or functionCall)
+fn chunk_has_output(chunk: &Value) -> bool {
+    let root = chunk.get("response").unwrap_or(chunk);
+    root.get("candidates")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("content"))
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .map(|parts| parts.iter().any(|p| p.get("text").is_some() || p.get("functionCall").is_some()))
+        .unwrap_or(false)
+}
+
+fn chunk_output_tokens(chunk: &Value) -> Option<u32> {
+    let root = chunk.get("response").unwrap_or(chunk);
+    root.get("usageMetadata")
+        .and_then(|u| u.get("candidatesTokenCount"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32)
+}
+
+async fn run_single(
+    client: &UpstreamClient,
+    access_token: &str,
+    project_id: &str,
+    email: &str,
+    model: &str,
+    case: BenchmarkCase,
+) -> BenchmarkSample {
+    let body = build_case_body(case, model, project_id);
+    let start = Instant::now();
+    let mut sample = BenchmarkSample {
+        account_email: email.to_string(),
+        model: model.to_string(),
+        case,
+        ttft_ms: None,
+        duration_ms: 0,
+        output_tokens: 0,
+        tokens_per_sec: None,
+        error: None,
+    };
+
+    let response = match client
+        .call_v1_internal("streamGenerateContent", access_token, body, Some("alt=sse"))
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            sample.duration_ms = start.elapsed().as_millis() as u64;
+            sample.error = Some(e);
+            return sample;
+        }
+    };
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        sample.duration_ms = start.elapsed().as_millis() as u64;
+        sample.error = Some(format!("HTTP {}: {}", status, text.chars().take(300).collect::<String>()));
+        return sample;
+    }
+
+    let mut stream = response.bytes_stream();
+    let mut buffer = String::new();
+    while let Some(item) = stream.next().await {
+        let bytes = match item {
+            Ok(b) => b,
+            Err(e) => {
+                sample.error = Some(format!("Stream error: {}", e));
+                break;
+            }
+        };
+        buffer.push_str(&String::from_utf8_lossy(&bytes));
+        while let Some(pos) = buffer.find('\n') {
+            let line: String = buffer.drain(..=pos).collect();
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            let Ok(chunk) = serde_json::from_str::<Value>(data.trim()) else {
+                continue;
+            };
+            if sample.ttft_ms.is_none() && chunk_has_output(&chunk) {
+                sample.ttft_ms = Some(start.elapsed().as_millis() as u64);
+            }
+            if let Some(tokens) = chunk_output_tokens(&chunk) {
+                sample.output_tokens = tokens;
+            }
+        }
+    }
+
+    sample.duration_ms = start.elapsed().as_millis() as u64;
+    if sample.error.is_none() && sample.ttft_ms.is_none() {
+        sample.error = Some("Empty response".to_string());
+    }
+
+    // Throughput is measured over the generation window (after first token)
+    if let Some(ttft) = sample.ttft_ms {
+        let gen_ms = sample.duration_ms.saturating_sub(ttft).max(1);
+        if sample.output_tokens > 0 {
+            sample.tokens_per_sec = Some(sample.output_tokens as f64 * 1000.0 / gen_ms as f64);
+        }
+    }
+
+    sample
+}
+
+fn summarize(samples: &[BenchmarkSample]) -> Vec<BenchmarkSummary> {
+    let mut groups: HashMap<(String, String), Vec<&BenchmarkSample>> = HashMap::new();
+    for s in samples {
+        groups
+            .entry((s.account_email.clone(), s.model.clone()))
+            .or_default()
+            .push(s);
+    }
+
+    let mut summaries: Vec<BenchmarkSummary> = groups
+        .into_iter()
+        .map(|((account_email, model), runs)| {
+            let total = runs.len() as u32;
+            let errors = runs.iter().filter(|s| s.error.is_some()).count() as u32;
+            let ttfts: Vec<f64> = runs.iter().filter_map(|s| s.ttft_ms).map(|v| v as f64).collect();
+            let tps: Vec<f64> = runs.iter().filter_map(|s| s.tokens_per_sec).collect();
+            let avg = |v: &[f64]| if v.is_empty() { None } else { Some(v.iter().sum::<f64>() / v.len() as f64) };
+            BenchmarkSummary {
+                account_email,
+                model,
+                runs: total,
+                errors,
+                error_rate: if total == 0 { 0.0 } else { errors as f64 / total as f64 },
+                avg_ttft_ms: avg(&ttfts),
+                avg_tokens_per_sec: avg(&tps),
+            }
+        })
+        .collect();
+
+    summaries.sort_by(|a, b| a.account_email.cmp(&b.account_email).then(a.model.cmp(&b.model)));
+    summaries
+}
+
+/// Run the benchmark suite sequentially against the selected accounts/models
+pub async fn run_benchmark(req: BenchmarkRequest) -> Result<BenchmarkReport, String> {
+    if req.models.is_empty() {
+        return Err("No models selected".to_string());
+    }
+
+    let started_at = chrono::Utc::now().timestamp();
+    let accounts: Vec<_> = account::list_accounts()?
+        .into_iter()
+        .filter(|a| !a.disabled)
+        .filter(|a| req.account_ids.is_empty() || req.account_ids.contains(&a.id))
+        .collect();
+    if accounts.is_empty() {
+        return Err("No accounts available".to_string());
+    }
+
+    let cases = if req.cases.is_empty() { BenchmarkCase::all() } else { req.cases.clone() };
+    let iterations = req.iterations.clamp(1, 10);
+
+    let proxy_config = config::load_app_config().ok().map(|c| c.proxy);
+    let pool = proxy_config.as_ref().map(|p| p.upstream_pool.clone()).unwrap_or_default();
+    let client = UpstreamClient::new(proxy_config.as_ref().map(|p| p.upstream_proxy.clone()), &pool);
+    if let Some(p) = &proxy_config {
+        client.set_endpoints(p.upstream_endpoints.clone());
+    }
+
+    logger::log_info(&format!(
+        "[Benchmark] Starting: {} accounts x {} models x {} cases x {} iterations",
+        accounts.len(), req.models.len(), cases.len(), iterations
+    ));
+
+    let mut samples = Vec::new();
+    for acc in &accounts {
+        let (token, project_id) = match quota::get_valid_token_for_warmup(acc).await {
+            Ok(t) => t,
+            Err(e) => {
+                logger::log_warn(&format!("[Benchmark] Skipping {}: {}", acc.email, e));
+                continue;
+            }
+        };
+
+        for model in &req.models {
+            for case in &cases {
+                for _ in 0..iterations {
+                    let sample = run_single(&client, &token, &project_id, &acc.email, model, *case).await;
+                    if let Some(err) = &sample.error {
+                        logger::log_warn(&format!("[Benchmark] {} / {} / {:?} failed: {}", acc.email, model, case, err));
+                    }
+                    samples.push(sample);
+                }
+            }
+        }
+    }
+
+    let summaries = summarize(&samples);
+    logger::log_info(&format!("[Benchmark] Finished with {} samples", samples.len()));
+
+    Ok(BenchmarkReport {
+        started_at,
+        finished_at: chrono::Utc::now().timestamp(),
+        samples,
+        summaries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(email: &str, model: &str, ttft_ms: Option<u64>, tokens_per_sec: Option<f64>, error: Option<&str>) -> BenchmarkSample {
+        BenchmarkSample {
+            account_email: email.to_string(),
+            model: model.to_string(),
+            case: BenchmarkCase::ShortPrompt,
+            ttft_ms,
+            duration_ms: 1000,
+            output_tokens: 0,
+            tokens_per_sec,
+            error: error.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_summarize_groups_by_account_and_model() {
+        assert!(summarize(&[]).is_empty());
+
+        let samples = vec![
+            sample("b@example.com", "gemini-3-pro", Some(100), Some(20.0), None),
+            sample("a@example.com", "gemini-3-pro", Some(300), Some(40.0), None),
+            sample("a@example.com", "gemini-3-pro", Some(500), None, None),
+            sample("a@example.com", "gemini-3-pro", None, None, Some("HTTP 429")),
+            sample("a@example.com", "gemini-2.5-flash", None, None, Some("Empty response")),
+            sample("a@example.com", "gemini-2.5-flash", None, None, Some("HTTP 500")),
+        ];
+        let summaries = summarize(&samples);
+        let keys: Vec<(&str, &str)> =
+            summaries.iter().map(|s| (s.account_email.as_str(), s.model.as_str())).collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("a@example.com", "gemini-2.5-flash"),
+                ("a@example.com", "gemini-3-pro"),
+                ("b@example.com", "gemini-3-pro"),
+            ]
+        );
+
+        // 全部失败的分组: 错误率 100%，没有平均值
+        let failed = &summaries[0];
+        assert_eq!((failed.runs, failed.errors), (2, 2));
+        assert_eq!(failed.error_rate, 1.0);
+        assert!(failed.avg_ttft_ms.is_none() && failed.avg_tokens_per_sec.is_none());
+
+        // 平均值只统计有数据的样本
+        let mixed = &summaries[1];
+        assert_eq!((mixed.runs, mixed.errors), (3, 1));
+        assert!((mixed.error_rate - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(mixed.avg_ttft_ms, Some(400.0));
+        assert_eq!(mixed.avg_tokens_per_sec, Some(40.0));
+
+        let single = &summaries[2];
+        assert_eq!((single.runs, single.errors, single.error_rate), (1, 0, 0.0));
+        assert_eq!(single.avg_ttft_ms, Some(100.0));
+    }
+
+    #[test]
+    fn test_chunk_output_detection() {
+        let text = json!({ "response": { "candidates": [{ "content": { "parts": [{ "text": "hi" }] } }] } });
+        let call = json!({ "candidates": [{ "content": { "parts": [{ "functionCall": { "name": "f" } }] } }] });
+        let thought_sig = json!({ "response": { "candidates": [{ "content": { "parts": [{ "thoughtSignature": "x" }] } }] } });
+        let usage_only = json!({ "response": { "usageMetadata": { "candidatesTokenCount": 42 } } });
+        assert!(chunk_has_output(&text));
+        assert!(chunk_has_output(&call));
+        assert!(!chunk_has_output(&thought_sig));
+        assert!(!chunk_has_output(&usage_only));
+        assert!(!chunk_has_output(&json!({})));
+
+        assert_eq!(chunk_output_tokens(&usage_only), Some(42));
+        assert_eq!(chunk_output_tokens(&json!({ "usageMetadata": { "candidatesTokenCount": 7 } })), Some(7));
+        assert_eq!(chunk_output_tokens(&text), None);
+    }
+
+    #[test]
+    fn test_build_case_body() {
+        for case in BenchmarkCase::all() {
+            let body = build_case_body(case, "gemini-3-pro", "proj-1");
+            assert_eq!(body["model"], "gemini-3-pro");
+            assert_eq!(body["project"], "proj-1");
+            assert!(body["requestId"].as_str().unwrap().starts_with("bench-"));
+            assert_eq!(body["request"]["generationConfig"]["maxOutputTokens"], 1024);
+            assert_eq!(body["request"]["contents"][0]["role"], "user");
+            assert_eq!(body["request"].get("tools").is_some(), case == BenchmarkCase::ToolCall);
+        }
+
+        let tool = build_case_body(BenchmarkCase::ToolCall, "m", "p");
+        assert_eq!(tool["request"]["tools"][0]["functionDeclarations"][0]["name"], "get_weather");
+        let image = build_case_body(BenchmarkCase::Image, "m", "p");
+        assert_eq!(image["request"]["contents"][0]["parts"][0]["inlineData"]["data"], TINY_PNG_BASE64);
+        let long = build_case_body(BenchmarkCase::LongPrompt, "m", "p");
+        let short = build_case_body(BenchmarkCase::ShortPrompt, "m", "p");
+        let text_len = |b: &Value| b["request"]["contents"][0]["parts"][0]["text"].as_str().unwrap().len();
+        assert!(text_len(&long) > 10 * text_len(&short));
+    }
+}
@@ -0,0 +1,280 @@
+// 账号批量操作
+// 立即刷新全部 Token、重新校验全部 Project ID、按标签暂停 / 恢复、清理凭据长期失效的账号。
+// Tauri 命令与 Admin API (POST /admin/accounts/bulk) 共用同一入口，调用方负责在完成后重新加载账号池。
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::models::{Account, TokenData};
+use crate::modules::{account, logger, oauth};
+use crate::proxy::project_resolver;
+
+/// 并发访问上游的账号数
+const MAX_CONCURRENT: usize = 5;
+
+fn default_purge_days() -> u32 {
+    7
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BulkAction {
+    /// 立即刷新全部 OAuth 账号的 access_token
+    RefreshTokens,
+    /// 重新查询全部 OAuth 账号的 Project ID 并修正已变化的
+    VerifyProjects,
+    /// 暂停带有指定标签的账号；minutes 为空表示直到手动恢复
+    PauseTag {
+        tag: String,
+        #[serde(default)]
+        minutes: Option<u32>,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    ResumeTag {
+        tag: String,
+    },
+    /// 删除凭据失效 (disabled) 超过指定天数的账号
+    PurgeFailed {
+        #[serde(default = "default_purge_days")]
+        older_than_days: u32,
+        #[serde(default)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BulkReport {
+    /// 参与操作的账号数
+    pub total: usize,
+    pub success: usize,
+    pub failed: usize,
+    /// 成功处理 (或 dry_run 时将被处理) 的账号 ID
+    pub account_ids: Vec<String>,
+    pub details: Vec<String>,
+}
+
+impl BulkReport {
+    fn from_results(total: usize, results: Vec<(String, Result<Option<String>, String>)>) -> Self {
+        let mut report = BulkReport { total, ..Default::default() };
+        for (id, result) in results {
+            match result {
+                Ok(detail) => {
+                    report.success += 1;
+                    report.account_ids.push(id);
+                    report.details.extend(detail);
+                }
+                Err(msg) => {
+                    report.failed += 1;
+                    report.details.push(msg);
+                }
+            }
+        }
+        report
+    }
+}
+
+/// 可访问上游的账号: OAuth 且未因凭据失效被禁用
+fn oauth_accounts(accounts: Vec<Account>) -> Vec<Account> {
+    accounts.into_iter().filter(|a| !a.disabled && !a.is_api_key()).collect()
+}
+
+fn tagged<'a>(accounts: &'a [Account], tag: &str) -> Vec<&'a Account> {
+    accounts.iter().filter(|a| a.has_tag(tag)).collect()
+}
+
+/// 凭据失效超过 days 天的账号 (缺少 disabled_at 的不处理)
+fn purge_candidates(accounts: &[Account], now: i64, days: u32) -> Vec<&Account> {
+    let cutoff = now - days as i64 * 86400;
+    accounts
+        .iter()
+        .filter(|a| a.disabled && a.disabled_at.is_some_and(|at| at <= cutoff))
+        .collect()
+}
+
+/// 刷新失败且为 invalid_grant 时与配额刷新一致: 标记账号为凭据失效
+fn disable_on_invalid_grant(account: &mut Account, error: &str) {
+    if error.contains("invalid_grant") {
+        logger::log_error(&format!("Disabling account {} due to invalid_grant during bulk refresh", account.email));
+        account.disabled = true;
+        account.disabled_at = Some(chrono::Utc::now().timestamp());
+        account.disabled_reason = Some(format!("invalid_grant: {}", error));
+        let _ = account::save_account(account);
+    }
+}
+
+async fn refresh_token(mut account: Account) -> Result<Option<String>, String> {
+    let token_res = match oauth::refresh_access_token(&account.token.refresh_token).await {
+        Ok(t) => t,
+        Err(e) => {
+            disable_on_invalid_grant(&mut account, &e);
+            return Err(format!("Account {}: {}", account.email, e));
+        }
+    };
+    account.token = TokenData::new(
+        token_res.access_token,
+        account.token.refresh_token.clone(),
+        token_res.expires_in,
+        account.token.email.clone(),
+        account.token.project_id.clone(),
+        None,
+    );
+    account::save_account(&account).map_err(|e| format!("Account {}: {}", account.email, e))?;
+    Ok(None)
+}
+
+async fn verify_project(mut account: Account) -> Result<Option<String>, String> {
+    let token = match oauth::ensure_fresh_token(&account.token).await {
+        Ok(t) => t,
+        Err(e) => {
+            disable_on_invalid_grant(&mut account, &e);
+            return Err(format!("Account {}: {}", account.email, e));
+        }
+    };
+    let project_id = project_resolver::fetch_official_project_id(&token.access_token)
+        .await
+        .map_err(|e| format!("Account {}: {}", account.email, e))?;
+    let mut changed = token.access_token != account.token.access_token;
+    account.token = token;
+
+    let detail = match project_id {
+        Some(id) if account.token.project_id.as_deref() != Some(id.as_str()) => {
+            changed = true;
+            let detail = format!(
+                "Account {}: project {} -> {}",
+                account.email,
+                account.token.project_id.as_deref().unwrap_or("<none>"),
+                id
+            );
+            account.token.project_id = Some(id);
+            Some(detail)
+        }
+        Some(_) => None,
+        // 无资格获取官方项目时保留现有 (兜底) ID
+        None => Some(format!("Account {}: no official project assigned", account.email)),
+    };
+    if changed {
+        account::save_account(&account).map_err(|e| format!("Account {}: {}", account.email, e))?;
+    }
+    Ok(detail)
+}
+
+/// 以有限并发对每个账号执行 op，返回 (账号 ID, 结果)
+async fn run_concurrent<F, Fut>(accounts: Vec<Account>, op: F) -> Vec<(String, Result<Option<String>, String>)>
+where
+    F: Fn(Account) -> Fut,
+    Fut: std::future::Future<Output = Result<Option<String>, String>>,
+{
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT));
+    let tasks = accounts.into_iter().map(|acc| {
+        let permit = semaphore.clone();
+        let id = acc.id.clone();
+        let fut = op(acc);
+        async move {
+            let _guard = permit.acquire().await;
+            (id, fut.await)
+        }
+    });
+    join_all(tasks).await
+}
+
+/// 执行批量操作
+pub async fn run(action: BulkAction) -> Result<BulkReport, String> {
+    if let BulkAction::PauseTag { tag, .. } | BulkAction::ResumeTag { tag } = &action {
+        if tag.trim().is_empty() {
+            return Err("tag must not be empty".to_string());
+        }
+    }
+    let accounts = account::list_accounts()?;
+    let now = chrono::Utc::now().timestamp();
+
+    let report = match action {
+        BulkAction::RefreshTokens => {
+            let targets = oauth_accounts(accounts);
+            let total = targets.len();
+            BulkReport::from_results(total, run_concurrent(targets, refresh_token).await)
+        }
+        BulkAction::VerifyProjects => {
+            let targets = oauth_accounts(accounts);
+            let total = targets.len();
+            BulkReport::from_results(total, run_concurrent(targets, verify_project).await)
+        }
+        BulkAction::PauseTag { tag, minutes, reason } => {
+            if minutes == Some(0) {
+                return Err("minutes must be greater than 0".to_string());
+            }
+            let targets = tagged(&accounts, &tag);
+            let results = targets
+                .iter()
+                .map(|a| {
+                    let mut a = (*a).clone();
+                    a.pause(now, minutes, reason.clone());
+                    (a.id.clone(), account::save_account(&a).map(|_| None))
+                })
+                .collect();
+            BulkReport::from_results(targets.len(), results)
+        }
+        BulkAction::ResumeTag { tag } => {
+            let targets = tagged(&accounts, &tag);
+            let results = targets
+                .iter()
+                .map(|a| {
+                    let mut a = (*a).clone();
+                    a.resume();
+                    (a.id.clone(), account::save_account(&a).map(|_| None))
+                })
+                .collect();
+            BulkReport::from_results(targets.len(), results)
+        }
+        BulkAction::PurgeFailed { older_than_days, dry_run } => {
+            let targets = purge_candidates(&accounts, now, older_than_days);
+            let ids: Vec<String> = targets.iter().map(|a| a.id.clone()).collect();
+            let details = targets.iter().map(|a| format!("Account {}: {}", a.email, a.disabled_reason.as_deref().unwrap_or("disabled"))).collect();
+            if !dry_run && !ids.is_empty() {
+                account::delete_accounts(&ids)?;
+            }
+            BulkReport { total: ids.len(), success: ids.len(), failed: 0, account_ids: ids, details }
+        }
+    };
+
+    logger::log_info(&format!(
+        "[Bulk] {} accounts processed: {} success, {} failed",
+        report.total, report.success, report.failed
+    ));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(id: &str, tags: &[&str]) -> Account {
+        let token = TokenData::new("at".to_string(), "rt".to_string(), 3600, None, None, None);
+        let mut a = Account::new(id.to_string(), format!("{}@example.com", id), token);
+        a.tags = tags.iter().map(|t| t.to_string()).collect();
+        a
+    }
+
+    #[test]
+    fn test_action_parsing_and_selection() {
+        let action: BulkAction = serde_json::from_str(r#"{"action":"purge_failed"}"#).unwrap();
+        assert!(matches!(action, BulkAction::PurgeFailed { older_than_days: 7, dry_run: false }));
+        let action: BulkAction = serde_json::from_str(r#"{"action":"pause_tag","tag":"team-b","minutes":30}"#).unwrap();
+        assert!(matches!(action, BulkAction::PauseTag { minutes: Some(30), .. }));
+
+        let now = 1_700_000_000;
+        let mut accounts = vec![account("a", &["Team-B"]), account("b", &["team-a"]), account("c", &[]), account("d", &[])];
+        accounts[2].disabled = true;
+        accounts[2].disabled_at = Some(now - 8 * 86400);
+        accounts[3].disabled = true;
+        accounts[3].disabled_at = Some(now - 86400);
+
+        let ids = |list: Vec<&Account>| list.iter().map(|a| a.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(tagged(&accounts, " team-b")), vec!["a"]);
+        assert_eq!(ids(purge_candidates(&accounts, now, 7)), vec!["c"]);
+        assert_eq!(ids(purge_candidates(&accounts, now, 1)), vec!["c", "d"]);
+        assert_eq!(oauth_accounts(accounts).len(), 2);
+    }
+}
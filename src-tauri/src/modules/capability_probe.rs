@@ -0,0 +1,342 @@
+// 模型能力探测
+// 不同等级的 Antigravity 账号允许的特性不一样 (工具、图片输入、思维配置等)。
+// 用极小的请求逐项试探每个模型，把结果记录到 capability_probes.json 并并入能力表，
+// 让特性检查依据账号池的实际情况而不是只靠内置表。
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use crate::models::Account;
+use crate::modules::{account, benchmark::TINY_PNG_BASE64, config, logger, quota};
+use crate::proxy::mappers::capabilities::{self, ProbedCapabilities};
+use crate::proxy::upstream::client::UpstreamClient;
+
+/// 账号没有配额数据时探测的模型
+const DEFAULT_PROBE_MODELS: &[&str] = &["gemini-2.5-flash", "gemini-3-flash", "gemini-3-pro-high", "claude-sonnet-4-5"];
+
+/// 单个账号 / 模型的探测结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeResult {
+    pub account_id: String,
+    pub email: String,
+    #[serde(default)]
+    pub tier: Option<String>,
+    pub model: String,
+    pub capabilities: ProbedCapabilities,
+    /// 基础请求失败时的错误 (模型对该账号不可用)
+    #[serde(default)]
+    pub error: Option<String>,
+    pub probed_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeReport {
+    pub results: Vec<ProbeResult>,
+    /// 按模型合并后的能力 (任一账号支持即视为支持)
+    pub merged: BTreeMap<String, ProbedCapabilities>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Feature {
+    Tools,
+    Images,
+    SystemInstruction,
+    Thinking,
+    JsonMode,
+}
+
+const FEATURES: [Feature; 5] =
+    [Feature::Tools, Feature::Images, Feature::SystemInstruction, Feature::Thinking, Feature::JsonMode];
+
+fn set_feature(caps: &mut ProbedCapabilities, feature: Feature, value: Option<bool>) {
+    match feature {
+        Feature::Tools => caps.tools = value,
+        Feature::Images => caps.images = value,
+        Feature::SystemInstruction => caps.system_instruction = value,
+        Feature::Thinking => caps.thinking = value,
+        Feature::JsonMode => caps.json_mode = value,
+    }
+}
+
+fn get_feature(caps: &ProbedCapabilities, feature: Feature) -> Option<bool> {
+    match feature {
+        Feature::Tools => caps.tools,
+        Feature::Images => caps.images,
+        Feature::SystemInstruction => caps.system_instruction,
+        Feature::Thinking => caps.thinking,
+        Feature::JsonMode => caps.json_mode,
+    }
+}
+
+fn store_path() -> Result<PathBuf, String> {
+    Ok(account::get_data_dir()?.join("capability_probes.json"))
+}
+
+pub fn load_results() -> Vec<ProbeResult> {
+    store_path()
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+fn save_results(results: &[ProbeResult]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(results).map_err(|e| format!("序列化探测结果失败: {}", e))?;
+    std::fs::write(store_path()?, content).map_err(|e| format!("保存探测结果失败: {}", e))
+}
+
+/// 按模型合并: 任一账号实测支持 → 支持；所有确定的结果都不支持 → 不支持；否则未确定
+fn merge(results: &[ProbeResult]) -> BTreeMap<String, ProbedCapabilities> {
+    let mut merged: BTreeMap<String, ProbedCapabilities> = BTreeMap::new();
+    for result in results.iter().filter(|r| r.error.is_none()) {
+        let entry = merged.entry(result.model.clone()).or_default();
+        for feature in FEATURES {
+            let value = match (get_feature(entry, feature), get_feature(&result.capabilities, feature)) {
+                (Some(a), Some(b)) => Some(a || b),
+                (a, b) => a.or(b),
+            };
+            set_feature(entry, feature, value);
+        }
+    }
+    merged
+}
+
+fn apply(results: &[ProbeResult]) -> BTreeMap<String, ProbedCapabilities> {
+    let merged = merge(results);
+    capabilities::set_probed(merged.iter().map(|(k, v)| (k.clone(), *v)).collect::<HashMap<_, _>>());
+    merged
+}
+
+/// 启动时加载已保存的探测结果
+pub fn init() {
+    let results = load_results();
+    if !results.is_empty() {
+        apply(&results);
+        logger::log_info(&format!("[Capability Probe] 已加载 {} 条探测结果", results.len()));
+    }
+}
+
+pub fn get_report() -> ProbeReport {
+    let results = load_results();
+    let merged = merge(&results);
+    ProbeReport { results, merged }
+}
+
+/// 构造探测请求 (None = 基础请求)
+fn build_probe_body(feature: Option<Feature>, model: &str, project_id: &str) -> Value {
+    let mut request = json!({
+        "contents": [{ "role": "user", "parts": [{ "text": "Reply with OK." }] }],
+        "generationConfig": { "maxOutputTokens": 256 }
+    });
+    match feature {
+        None => {}
+        Some(Feature::Tools) => {
+            request["tools"] = json!([{
+                "functionDeclarations": [{
+                    "name": "noop",
+                    "description": "Does nothing",
+                    "parameters": { "type": "OBJECT", "properties": { "value": { "type": "STRING" } } }
+                }]
+            }]);
+        }
+        Some(Feature::Images) => {
+            request["contents"][0]["parts"] = json!([
+                { "inlineData": { "mimeType": "image/png", "data": TINY_PNG_BASE64 } },
+                { "text": "Reply with OK." }
+            ]);
+        }
+        Some(Feature::SystemInstruction) => {
+            request["systemInstruction"] = json!({ "parts": [{ "text": "Answer tersely." }] });
+        }
+        Some(Feature::Thinking) => {
+            request["generationConfig"]["thinkingConfig"] = json!({ "includeThoughts": true, "thinkingBudget": 128 });
+        }
+        Some(Feature::JsonMode) => {
+            request["generationConfig"]["responseMimeType"] = json!("application/json");
+        }
+    }
+
+    json!({
+        "project": project_id,
+        "requestId": format!("probe-{}", uuid::Uuid::new_v4()),
+        "request": request,
+        "model": model,
+        "userAgent": "antigravity",
+        "requestType": "agent"
+    })
+}
+
+/// 2xx → 支持；400 → 不支持；其他 (限流、5xx、网络错误) → 无法确定
+async fn send_probe(
+    client: &UpstreamClient,
+    access_token: &str,
+    project_id: &str,
+    model: &str,
+    feature: Option<Feature>,
+) -> Result<Option<bool>, String> {
+    let body = build_probe_body(feature, model, project_id);
+    let response = client.call_v1_internal("generateContent", access_token, body, None).await?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(Some(true));
+    }
+    let text = response.text().await.unwrap_or_default();
+    let message = format!("HTTP {}: {}", status, text.chars().take(200).collect::<String>());
+    match status.as_u16() {
+        400 if feature.is_some() => Ok(Some(false)),
+        429 | 500..=599 if feature.is_some() => Ok(None),
+        _ => Err(message),
+    }
+}
+
+async fn probe_model(
+    client: &UpstreamClient,
+    access_token: &str,
+    project_id: &str,
+    acc: &Account,
+    model: &str,
+) -> ProbeResult {
+    let mut result = ProbeResult {
+        account_id: acc.id.clone(),
+        email: acc.email.clone(),
+        tier: acc.quota.as_ref().and_then(|q| q.subscription_tier.clone()),
+        model: model.to_string(),
+        capabilities: ProbedCapabilities::default(),
+        error: None,
+        probed_at: chrono::Utc::now().timestamp(),
+    };
+
+    // 基础请求失败说明模型对该账号不可用，其余特性不再探测
+    if let Err(e) = send_probe(client, access_token, project_id, model, None).await {
+        result.error = Some(e);
+        return result;
+    }
+    for feature in FEATURES {
+        let value = send_probe(client, access_token, project_id, model, Some(feature))
+            .await
+            .unwrap_or(None);
+        set_feature(&mut result.capabilities, feature, value);
+    }
+    result
+}
+
+fn models_for(acc: &Account, requested: &[String]) -> Vec<String> {
+    if !requested.is_empty() {
+        return requested.to_vec();
+    }
+    let from_quota: Vec<String> = acc
+        .quota
+        .as_ref()
+        .map(|q| q.models.iter().map(|m| m.name.clone()).collect())
+        .unwrap_or_default();
+    if from_quota.is_empty() {
+        DEFAULT_PROBE_MODELS.iter().map(|m| m.to_string()).collect()
+    } else {
+        from_quota
+    }
+}
+
+/// 探测指定账号 (为空 = 所有启用的 OAuth 账号) 的模型能力 (为空 = 账号配额中的模型)，
+/// 覆盖这些账号 / 模型的旧结果并刷新能力表
+pub async fn probe_accounts(account_ids: Vec<String>, models: Vec<String>) -> Result<ProbeReport, String> {
+    let accounts: Vec<Account> = account::list_accounts()?
+        .into_iter()
+        .filter(|a| !a.disabled && !a.is_api_key())
+        .filter(|a| account_ids.is_empty() || account_ids.contains(&a.id))
+        .collect();
+    if accounts.is_empty() {
+        return Err("No accounts available".to_string());
+    }
+
+    let proxy_config = config::load_app_config().ok().map(|c| c.proxy);
+    let pool = proxy_config.as_ref().map(|p| p.upstream_pool.clone()).unwrap_or_default();
+    let client = UpstreamClient::new(proxy_config.as_ref().map(|p| p.upstream_proxy.clone()), &pool);
+    if let Some(p) = &proxy_config {
+        client.set_endpoints(p.upstream_endpoints.clone());
+    }
+
+    let mut fresh = Vec::new();
+    for acc in &accounts {
+        let (token, project_id) = match quota::get_valid_token_for_warmup(acc).await {
+            Ok(t) => t,
+            Err(e) => {
+                logger::log_warn(&format!("[Capability Probe] 跳过 {}: {}", acc.email, e));
+                continue;
+            }
+        };
+        for model in models_for(acc, &models) {
+            let result = probe_model(&client, &token, &project_id, acc, &model).await;
+            if let Some(err) = &result.error {
+                logger::log_warn(&format!("[Capability Probe] {} / {} 不可用: {}", acc.email, model, err));
+            }
+            fresh.push(result);
+        }
+    }
+    logger::log_info(&format!("[Capability Probe] 完成 {} 项探测", fresh.len()));
+
+    let mut results: Vec<ProbeResult> = load_results()
+        .into_iter()
+        .filter(|old| !fresh.iter().any(|r| r.account_id == old.account_id && r.model == old.model))
+        .collect();
+    results.extend(fresh);
+    save_results(&results)?;
+    let merged = apply(&results);
+    Ok(ProbeReport { results, merged })
+}
+
+/// 删除账号后移除其探测结果
+pub fn forget_account(account_id: &str) {
+    let results = load_results();
+    if !results.iter().any(|r| r.account_id == account_id) {
+        return;
+    }
+    let kept: Vec<ProbeResult> = results.into_iter().filter(|r| r.account_id != account_id).collect();
+    if save_results(&kept).is_ok() {
+        apply(&kept);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(account_id: &str, model: &str, caps: ProbedCapabilities, error: Option<&str>) -> ProbeResult {
+        ProbeResult {
+            account_id: account_id.to_string(),
+            email: format!("{}@example.com", account_id),
+            tier: None,
+            model: model.to_string(),
+            capabilities: caps,
+            error: error.map(|e| e.to_string()),
+            probed_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_merge_any_account_supporting_wins() {
+        let free = ProbedCapabilities { tools: Some(false), images: Some(false), thinking: None, ..Default::default() };
+        let pro = ProbedCapabilities { tools: Some(true), images: Some(false), thinking: Some(false), ..Default::default() };
+        let merged = merge(&[
+            result("a", "gemini-3-pro-high", free, None),
+            result("b", "gemini-3-pro-high", pro, None),
+            result("c", "gemini-3-pro-high", ProbedCapabilities { images: Some(true), ..Default::default() }, Some("HTTP 403")),
+        ]);
+        let caps = merged["gemini-3-pro-high"];
+        assert_eq!(caps.tools, Some(true));
+        assert_eq!(caps.images, Some(false));
+        assert_eq!(caps.thinking, Some(false));
+        assert_eq!(caps.json_mode, None);
+    }
+
+    #[test]
+    fn test_probe_body_sets_single_feature() {
+        let body = build_probe_body(Some(Feature::Thinking), "gemini-3-flash", "proj");
+        assert_eq!(body["model"], "gemini-3-flash");
+        assert_eq!(body["request"]["generationConfig"]["thinkingConfig"]["thinkingBudget"], 128);
+        assert!(body["request"].get("tools").is_none());
+        let base = build_probe_body(None, "gemini-3-flash", "proj");
+        assert!(base["request"]["generationConfig"].get("thinkingConfig").is_none());
+    }
+}
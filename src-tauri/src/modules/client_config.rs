@@ -0,0 +1,196 @@
+// 一键生成常用客户端的配置片段 / 文件
+// 预填反代的 Base URL、API Key 与选定的模型别名: Cline 设置 JSON、Codex config.toml、LiteLLM yaml、OpenAI SDK 环境变量。
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::modules::config;
+
+/// 未选择模型且没有自定义映射时使用的模型
+const DEFAULT_MODEL: &str = "gemini-2.5-flash";
+/// Codex 读取 API Key 的环境变量名
+const CODEX_ENV_KEY: &str = "ANTIGRAVITY_API_KEY";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientConfigKind {
+    Cline,
+    Codex,
+    Litellm,
+    OpenaiEnv,
+}
+
+impl ClientConfigKind {
+    pub const ALL: [ClientConfigKind; 4] = [Self::Cline, Self::Codex, Self::Litellm, Self::OpenaiEnv];
+
+    fn file_name(self) -> &'static str {
+        match self {
+            Self::Cline => "cline-settings.json",
+            Self::Codex => "config.toml",
+            Self::Litellm => "litellm_config.yaml",
+            Self::OpenaiEnv => ".env",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ClientConfigOptions {
+    /// 写入配置的模型别名，第一个为默认模型 (为空时使用自定义映射中的别名)
+    pub models: Vec<String>,
+    /// 覆盖 Base URL (如局域网地址)，为空时使用 http://127.0.0.1:<port>
+    pub base_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct GeneratedClientConfig {
+    pub kind: ClientConfigKind,
+    /// 建议的文件名
+    pub file_name: String,
+    pub content: String,
+}
+
+/// 生成所需的反代参数
+#[derive(Debug, Clone)]
+struct Target {
+    /// 不带 /v1 的根地址
+    base_url: String,
+    api_key: String,
+    models: Vec<String>,
+}
+
+fn resolve_target(options: ClientConfigOptions) -> Result<Target, String> {
+    let proxy = config::load_app_config()?.proxy;
+    let base_url = options
+        .base_url
+        .map(|u| u.trim().trim_end_matches('/').trim_end_matches("/v1").to_string())
+        .filter(|u| !u.is_empty())
+        .unwrap_or_else(|| format!("http://127.0.0.1:{}", proxy.port));
+
+    let mut models: Vec<String> = Vec::new();
+    for model in options.models.iter().map(|m| m.trim()).filter(|m| !m.is_empty()) {
+        if !models.iter().any(|m| m == model) {
+            models.push(model.to_string());
+        }
+    }
+    if models.is_empty() {
+        // 自定义映射中的精确别名 (通配符规则无法直接作为模型名)
+        let mut aliases: Vec<String> = proxy.custom_mapping.keys().filter(|k| !k.contains('*')).cloned().collect();
+        aliases.sort();
+        models = aliases;
+    }
+    if models.is_empty() {
+        models.push(DEFAULT_MODEL.to_string());
+    }
+    Ok(Target { base_url, api_key: proxy.api_key, models })
+}
+
+/// 双引号字符串 (同时是合法的 TOML / YAML 标量)
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn render_cline(target: &Target) -> String {
+    let settings = json!({
+        "apiProvider": "openai",
+        "openAiBaseUrl": format!("{}/v1", target.base_url),
+        "openAiApiKey": target.api_key,
+        "openAiModelId": target.models[0],
+    });
+    serde_json::to_string_pretty(&settings).unwrap_or_default()
+}
+
+fn render_codex(target: &Target) -> String {
+    let mut out = format!(
+        "# ~/.codex/config.toml\n# export {}={}\n\nmodel = {}\nmodel_provider = \"antigravity\"\n\n\
+         [model_providers.antigravity]\nname = \"Antigravity Manager\"\nbase_url = {}\nenv_key = {}\nwire_api = \"chat\"\n",
+        CODEX_ENV_KEY,
+        target.api_key,
+        quote(&target.models[0]),
+        quote(&format!("{}/v1", target.base_url)),
+        quote(CODEX_ENV_KEY),
+    );
+    // 其余模型生成 profile，使用 `codex --profile <model>` 切换
+    for model in &target.models[1..] {
+        out.push_str(&format!(
+            "\n[profiles.{}]\nmodel = {}\nmodel_provider = \"antigravity\"\n",
+            quote(model),
+            quote(model)
+        ));
+    }
+    out
+}
+
+fn render_litellm(target: &Target) -> String {
+    let mut out = String::from("model_list:\n");
+    for model in &target.models {
+        out.push_str(&format!(
+            "  - model_name: {}\n    litellm_params:\n      model: {}\n      api_base: {}\n      api_key: {}\n",
+            quote(model),
+            quote(&format!("openai/{}", model)),
+            quote(&format!("{}/v1", target.base_url)),
+            quote(&target.api_key),
+        ));
+    }
+    out
+}
+
+fn render_openai_env(target: &Target) -> String {
+    let base = format!("{}/v1", target.base_url);
+    // OPENAI_API_BASE 供旧版 SDK / LangChain 使用
+    format!(
+        "OPENAI_BASE_URL={}\nOPENAI_API_BASE={}\nOPENAI_API_KEY={}\nOPENAI_MODEL={}\n",
+        base, base, target.api_key, target.models[0]
+    )
+}
+
+fn render(kind: ClientConfigKind, target: &Target) -> GeneratedClientConfig {
+    let content = match kind {
+        ClientConfigKind::Cline => render_cline(target),
+        ClientConfigKind::Codex => render_codex(target),
+        ClientConfigKind::Litellm => render_litellm(target),
+        ClientConfigKind::OpenaiEnv => render_openai_env(target),
+    };
+    GeneratedClientConfig { kind, file_name: kind.file_name().to_string(), content }
+}
+
+/// 生成全部客户端的配置
+pub fn generate_all(options: ClientConfigOptions) -> Result<Vec<GeneratedClientConfig>, String> {
+    let target = resolve_target(options)?;
+    Ok(ClientConfigKind::ALL.iter().map(|kind| render(*kind, &target)).collect())
+}
+
+/// 生成单个客户端的配置并写入文件
+pub fn export(kind: ClientConfigKind, options: ClientConfigOptions, file_path: &str) -> Result<(), String> {
+    let generated = render(kind, &resolve_target(options)?);
+    std::fs::write(file_path, generated.content).map_err(|e| format!("Failed to write file: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rendered_configs_parse() {
+        let target = Target {
+            base_url: "http://127.0.0.1:8045".to_string(),
+            api_key: "sk-test".to_string(),
+            models: vec!["gemini-2.5-flash".to_string(), "claude-sonnet-4-5".to_string()],
+        };
+
+        let cline: serde_json::Value = serde_json::from_str(&render_cline(&target)).unwrap();
+        assert_eq!(cline["openAiBaseUrl"], "http://127.0.0.1:8045/v1");
+        assert_eq!(cline["openAiModelId"], "gemini-2.5-flash");
+
+        let codex: toml::Value = toml::from_str(&render_codex(&target)).unwrap();
+        assert_eq!(codex["model"].as_str(), Some("gemini-2.5-flash"));
+        assert_eq!(codex["model_providers"]["antigravity"]["base_url"].as_str(), Some("http://127.0.0.1:8045/v1"));
+        assert_eq!(codex["profiles"]["claude-sonnet-4-5"]["model"].as_str(), Some("claude-sonnet-4-5"));
+
+        let litellm = render_litellm(&target);
+        assert_eq!(litellm.matches("model_name:").count(), 2);
+        assert!(litellm.contains("model: \"openai/claude-sonnet-4-5\""));
+
+        assert!(render_openai_env(&target).contains("OPENAI_API_KEY=sk-test\n"));
+    }
+}
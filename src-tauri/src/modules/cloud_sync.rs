@@ -0,0 +1,672 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+use crate::models::config::{CloudSyncBackend, CloudSyncConfig};
+use crate::models::{Account, AccountIndex, AccountSummary, AppConfig};
+use crate::modules::{account, config, logger};
+
+const SYNC_OBJECT_NAME: &str = "antigravity-sync.bin";
+const SYNC_STATE_FILE: &str = "cloud_sync_state.json";
+/// v2 header: MAGIC | kdf id(1) | rounds(u32 LE) | salt(16) | nonce(12)
+const BUNDLE_MAGIC: &[u8] = b"AGSYNC2";
+/// Bundles written before the KDF parameters were recorded
+const LEGACY_BUNDLE_MAGIC: &[u8] = b"AGSYNC1";
+const BUNDLE_VERSION: u32 = 1;
+const KDF_PBKDF2_SHA256: u8 = 1;
+const PBKDF2_ROUNDS: u32 = 600_000;
+/// Upper bound accepted from a remote header, so a tampered file can't stall the app
+const MAX_PBKDF2_ROUNDS: u32 = 10_000_000;
+const LEGACY_KDF_ROUNDS: u32 = 100_000;
+
+/// Sync direction override (None = automatic)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncDirection {
+    Push,
+    Pull,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncAction {
+    Pushed,
+    Pulled,
+    UpToDate,
+    /// Both sides changed since the last sync; user must choose push or pull
+    Conflict,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloudSyncResult {
+    pub action: SyncAction,
+    pub message: String,
+    pub remote_updated_at: Option<i64>,
+    pub remote_device: Option<String>,
+}
+
+/// Local bookkeeping for conflict detection
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CloudSyncState {
+    /// Content hash of the bundle at the last successful sync
+    pub base_hash: Option<String>,
+    pub last_sync_at: Option<i64>,
+}
+
+/// Plaintext sync payload (encrypted before upload)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SyncBundle {
+    version: u32,
+    device: String,
+    updated_at: i64,
+    accounts: Vec<Account>,
+    /// AppConfig without the `cloud_sync` section (credentials stay local)
+    settings: Value,
+}
+
+// ===== State =====
+
+fn get_state_path() -> Result<PathBuf, String> {
+    Ok(account::get_data_dir()?.join(SYNC_STATE_FILE))
+}
+
+pub fn load_state() -> CloudSyncState {
+    get_state_path()
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &CloudSyncState) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+    std::fs::write(get_state_path()?, content).map_err(|e| format!("failed_to_save_sync_state: {}", e))
+}
+
+// ===== Bundle =====
+
+fn device_name() -> String {
+    sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string())
+}
+
+fn build_local_bundle(app_config: &AppConfig) -> Result<SyncBundle, String> {
+    let mut settings = serde_json::to_value(app_config).map_err(|e| e.to_string())?;
+    if let Some(obj) = settings.as_object_mut() {
+        obj.remove("cloud_sync");
+    }
+    Ok(SyncBundle {
+        version: BUNDLE_VERSION,
+        device: device_name(),
+        updated_at: chrono::Utc::now().timestamp(),
+        accounts: account::list_accounts()?,
+        settings,
+    })
+}
+
+/// Hash of the meaningful content only. Volatile fields (quota, access tokens,
+/// usage timestamps, local IDs) are excluded so routine refreshes don't look like edits.
+fn content_hash(bundle: &SyncBundle) -> String {
+    let mut accounts: Vec<Value> = bundle
+        .accounts
+        .iter()
+        .filter_map(|a| serde_json::to_value(a).ok())
+        .map(|mut v| {
+            if let Some(obj) = v.as_object_mut() {
+                for key in ["id", "quota", "last_used", "last_warm_at", "created_at", "device_history"] {
+                    obj.remove(key);
+                }
+                if let Some(token) = obj.get_mut("token").and_then(|t| t.as_object_mut()) {
+                    for key in ["access_token", "expires_in", "expiry_timestamp"] {
+                        token.remove(key);
+                    }
+                }
+            }
+            v
+        })
+        .collect();
+    accounts.sort_by(|a, b| a["email"].as_str().cmp(&b["email"].as_str()));
+
+    let canonical = serde_json::json!({ "accounts": accounts, "settings": bundle.settings });
+    let digest = Sha256::digest(canonical.to_string().as_bytes());
+    to_hex(&digest)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// ===== Encryption =====
+
+fn derive_key(passphrase: &str, salt: &[u8], rounds: u32) -> [u8; 32] {
+    pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(passphrase.as_bytes(), salt, rounds)
+}
+
+/// Key derivation used by `AGSYNC1` bundles; kept only to read old remote files
+fn derive_key_legacy(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(passphrase.as_bytes());
+    let mut key: [u8; 32] = hasher.finalize().into();
+    for _ in 0..LEGACY_KDF_ROUNDS {
+        let mut hasher = Sha256::new();
+        hasher.update(key);
+        hasher.update(passphrase.as_bytes());
+        key = hasher.finalize().into();
+    }
+    key
+}
+
+fn encrypt_bundle(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    encrypt_bundle_with(plaintext, passphrase, PBKDF2_ROUNDS)
+}
+
+/// Layout: header | AES-256-GCM ciphertext. The header is bound as associated data,
+/// so changing the recorded KDF parameters also fails authentication.
+fn encrypt_bundle_with(plaintext: &[u8], passphrase: &str, rounds: u32) -> Result<Vec<u8>, String> {
+    let salt: [u8; 16] = rand::random();
+    let nonce: [u8; 12] = rand::random();
+    let mut out = Vec::with_capacity(BUNDLE_MAGIC.len() + 33 + plaintext.len() + 16);
+    out.extend_from_slice(BUNDLE_MAGIC);
+    out.push(KDF_PBKDF2_SHA256);
+    out.extend_from_slice(&rounds.to_le_bytes());
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+
+    let key = derive_key(passphrase, &salt, rounds);
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &out })
+        .map_err(|_| "Encryption failed".to_string())?;
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+fn decrypt_bundle(data: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    if data.starts_with(LEGACY_BUNDLE_MAGIC) {
+        return decrypt_legacy_bundle(data, passphrase);
+    }
+    let header = BUNDLE_MAGIC.len() + 1 + 4 + 16 + 12;
+    if data.len() < header || !data.starts_with(BUNDLE_MAGIC) {
+        return Err("Remote sync file is not a valid Antigravity sync bundle".to_string());
+    }
+    let mut pos = BUNDLE_MAGIC.len();
+    let kdf = data[pos];
+    pos += 1;
+    let rounds = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap_or_default());
+    pos += 4;
+    if kdf != KDF_PBKDF2_SHA256 {
+        return Err(format!("Unsupported sync bundle key derivation ({})", kdf));
+    }
+    if rounds == 0 || rounds > MAX_PBKDF2_ROUNDS {
+        return Err(format!("Invalid sync bundle key derivation rounds ({})", rounds));
+    }
+    let salt = &data[pos..pos + 16];
+    let nonce = &data[pos + 16..header];
+
+    let key = derive_key(passphrase, salt, rounds);
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())?;
+    cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: &data[header..], aad: &data[..header] })
+        .map_err(|_| "Failed to decrypt sync bundle (wrong passphrase?)".to_string())
+}
+
+/// Layout: MAGIC | salt(16) | nonce(12) | AES-256-GCM ciphertext
+fn decrypt_legacy_bundle(data: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    let header = LEGACY_BUNDLE_MAGIC.len() + 16 + 12;
+    if data.len() < header {
+        return Err("Remote sync file is not a valid Antigravity sync bundle".to_string());
+    }
+    let salt = &data[LEGACY_BUNDLE_MAGIC.len()..LEGACY_BUNDLE_MAGIC.len() + 16];
+    let nonce = &data[LEGACY_BUNDLE_MAGIC.len() + 16..header];
+    let key = derive_key_legacy(passphrase, salt);
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())?;
+    cipher
+        .decrypt(Nonce::from_slice(nonce), &data[header..])
+        .map_err(|_| "Failed to decrypt sync bundle (wrong passphrase?)".to_string())
+}
+
+// ===== Backends =====
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(60))
+        .build()
+        .unwrap_or_default()
+}
+
+fn webdav_object_url(cfg: &CloudSyncConfig) -> String {
+    format!("{}/{}", cfg.webdav_url.trim_end_matches('/'), SYNC_OBJECT_NAME)
+}
+
+fn s3_object_key(cfg: &CloudSyncConfig) -> String {
+    let prefix = cfg.s3_prefix.trim_matches('/');
+    if prefix.is_empty() {
+        SYNC_OBJECT_NAME.to_string()
+    } else {
+        format!("{}/{}", prefix, SYNC_OBJECT_NAME)
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Build a path-style S3 request signed with AWS SigV4
+fn s3_request(cfg: &CloudSyncConfig, method: reqwest::Method, body: Vec<u8>) -> Result<reqwest::RequestBuilder, String> {
+    let endpoint = url::Url::parse(cfg.s3_endpoint.trim_end_matches('/'))
+        .map_err(|e| format!("Invalid S3 endpoint: {}", e))?;
+    let host = match (endpoint.host_str(), endpoint.port()) {
+        (Some(h), Some(p)) => format!("{}:{}", h, p),
+        (Some(h), None) => h.to_string(),
+        _ => return Err("Invalid S3 endpoint host".to_string()),
+    };
+    let region = if cfg.s3_region.is_empty() { "us-east-1" } else { cfg.s3_region.as_str() };
+    let canonical_uri = format!("/{}/{}", cfg.s3_bucket, s3_object_key(cfg));
+    let url = format!("{}://{}{}", endpoint.scheme(), host, canonical_uri);
+
+    let now = chrono::Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = to_hex(&Sha256::digest(&body));
+
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        method.as_str(), canonical_uri, host, payload_hash, amz_date, signed_headers, payload_hash
+    );
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        to_hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let k_date = hmac_sha256(format!("AWS4{}", cfg.s3_secret_key).as_bytes(), &date);
+    let k_region = hmac_sha256(&k_date, region);
+    let k_service = hmac_sha256(&k_region, "s3");
+    let k_signing = hmac_sha256(&k_service, "aws4_request");
+    let signature = to_hex(&hmac_sha256(&k_signing, &string_to_sign));
+
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        cfg.s3_access_key, scope, signed_headers, signature
+    );
+
+    Ok(http_client()
+        .request(method, url)
+        .header("x-amz-date", amz_date)
+        .header("x-amz-content-sha256", payload_hash)
+        .header("Authorization", authorization)
+        .body(body))
+}
+
+/// Download the remote bundle (None if it doesn't exist yet)
+async fn fetch_remote(cfg: &CloudSyncConfig) -> Result<Option<Vec<u8>>, String> {
+    let req = match cfg.backend {
+        CloudSyncBackend::Webdav => http_client()
+            .get(webdav_object_url(cfg))
+            .basic_auth(&cfg.webdav_username, Some(&cfg.webdav_password)),
+        CloudSyncBackend::S3 => s3_request(cfg, reqwest::Method::GET, Vec::new())?,
+    };
+    let resp = req.send().await.map_err(|e| format!("Sync download failed: {}", e))?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        return Err(format!("Sync download failed: HTTP {}", status));
+    }
+    let bytes = resp.bytes().await.map_err(|e| format!("Sync download failed: {}", e))?;
+    Ok(Some(bytes.to_vec()))
+}
+
+async fn upload_remote(cfg: &CloudSyncConfig, data: Vec<u8>) -> Result<(), String> {
+    let req = match cfg.backend {
+        CloudSyncBackend::Webdav => http_client()
+            .put(webdav_object_url(cfg))
+            .basic_auth(&cfg.webdav_username, Some(&cfg.webdav_password))
+            .header("Content-Type", "application/octet-stream")
+            .body(data),
+        CloudSyncBackend::S3 => s3_request(cfg, reqwest::Method::PUT, data)?,
+    };
+    let resp = req.send().await.map_err(|e| format!("Sync upload failed: {}", e))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("Sync upload failed: HTTP {} {}", status, text));
+    }
+    Ok(())
+}
+
+// ===== Apply =====
+
+/// Merge remote accounts (matched by email) and settings into the local store.
+/// Local accounts missing from the remote bundle are kept.
+fn apply_bundle(bundle: &SyncBundle, local_sync: &CloudSyncConfig) -> Result<(), String> {
+    let local_accounts = account::list_accounts()?;
+    let mut index = account::load_account_index()?;
+    let new_config = merge_settings(&bundle.settings, local_sync)?;
+
+    for merged in merge_accounts(&bundle.accounts, &local_accounts, &mut index) {
+        account::save_account(&merged)?;
+    }
+    account::save_account_index(&index)?;
+    config::save_app_config(&new_config)
+}
+
+/// Remote accounts keep the local ID (and quota, if the remote has none) of the
+/// matching local account; new accounts are appended to `index`.
+fn merge_accounts(remote_accounts: &[Account], local_accounts: &[Account], index: &mut AccountIndex) -> Vec<Account> {
+    let mut merged_accounts = Vec::with_capacity(remote_accounts.len());
+    for remote in remote_accounts {
+        let mut merged = remote.clone();
+        if let Some(local) = local_accounts.iter().find(|a| a.email == remote.email) {
+            merged.id = local.id.clone();
+            if merged.quota.is_none() {
+                merged.quota = local.quota.clone();
+            }
+        } else {
+            if local_accounts.iter().any(|a| a.id == merged.id) {
+                merged.id = uuid::Uuid::new_v4().to_string();
+            }
+            index.accounts.push(AccountSummary {
+                id: merged.id.clone(),
+                email: merged.email.clone(),
+                name: merged.name.clone(),
+                created_at: merged.created_at,
+                last_used: merged.last_used,
+            });
+        }
+        merged_accounts.push(merged);
+    }
+    merged_accounts
+}
+
+/// Remote settings with the local `cloud_sync` section put back in place
+fn merge_settings(settings: &Value, local_sync: &CloudSyncConfig) -> Result<AppConfig, String> {
+    let mut settings = settings.clone();
+    if let Some(obj) = settings.as_object_mut() {
+        obj.insert(
+            "cloud_sync".to_string(),
+            serde_json::to_value(local_sync).map_err(|e| e.to_string())?,
+        );
+    }
+    serde_json::from_value(settings).map_err(|e| format!("Invalid remote settings: {}", e))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SyncPlan {
+    Run(SyncDirection),
+    UpToDate,
+    Conflict,
+}
+
+/// Decide what to do from the hash recorded at the last sync. An explicit
+/// direction always wins; otherwise only the side that changed is propagated.
+fn plan_sync(
+    direction: Option<SyncDirection>,
+    base_hash: Option<&str>,
+    local_hash: &str,
+    remote_hash: Option<&str>,
+) -> SyncPlan {
+    match (direction, remote_hash) {
+        (Some(d), _) => SyncPlan::Run(d),
+        (None, None) => SyncPlan::Run(SyncDirection::Push),
+        (None, Some(rh)) if rh == local_hash => SyncPlan::UpToDate,
+        (None, Some(rh)) => {
+            let local_changed = base_hash != Some(local_hash);
+            let remote_changed = base_hash != Some(rh);
+            match (local_changed, remote_changed) {
+                (false, true) => SyncPlan::Run(SyncDirection::Pull),
+                (true, false) => SyncPlan::Run(SyncDirection::Push),
+                _ => SyncPlan::Conflict,
+            }
+        }
+    }
+}
+
+// ===== Entry =====
+
+/// Run one sync round. With `direction` = None the direction is chosen from
+/// the last-synced hash; if both sides changed a `Conflict` is reported instead.
+pub async fn sync_now(direction: Option<SyncDirection>) -> Result<CloudSyncResult, String> {
+    let app_config = config::load_app_config()?;
+    let cfg = app_config.cloud_sync.clone();
+    if !cfg.enabled {
+        return Err("Cloud sync is not enabled".to_string());
+    }
+    if cfg.passphrase.is_empty() {
+        return Err("Cloud sync passphrase is not set".to_string());
+    }
+
+    let local = build_local_bundle(&app_config)?;
+    let local_hash = content_hash(&local);
+    let mut state = load_state();
+
+    let remote = match fetch_remote(&cfg).await? {
+        Some(data) => {
+            let plain = decrypt_bundle(&data, &cfg.passphrase)?;
+            let bundle: SyncBundle =
+                serde_json::from_slice(&plain).map_err(|e| format!("Invalid sync bundle: {}", e))?;
+            Some(bundle)
+        }
+        None => None,
+    };
+    let remote_hash = remote.as_ref().map(content_hash);
+    let remote_updated_at = remote.as_ref().map(|r| r.updated_at);
+    let remote_device = remote.as_ref().map(|r| r.device.clone());
+
+    let action = match plan_sync(direction, state.base_hash.as_deref(), &local_hash, remote_hash.as_deref()) {
+        SyncPlan::Run(d) => d,
+        SyncPlan::UpToDate => {
+            state.base_hash = Some(local_hash);
+            state.last_sync_at = Some(chrono::Utc::now().timestamp());
+            save_state(&state)?;
+            return Ok(CloudSyncResult {
+                action: SyncAction::UpToDate,
+                message: "Already in sync".to_string(),
+                remote_updated_at,
+                remote_device,
+            });
+        }
+        SyncPlan::Conflict => {
+            logger::log_warn("[CloudSync] Conflict: both local and remote changed since last sync");
+            return Ok(CloudSyncResult {
+                action: SyncAction::Conflict,
+                message: "Both this device and the remote copy changed since the last sync".to_string(),
+                remote_updated_at,
+                remote_device,
+            });
+        }
+    };
+
+    let result = match action {
+        SyncDirection::Push => {
+            let plain = serde_json::to_vec(&local).map_err(|e| e.to_string())?;
+            upload_remote(&cfg, encrypt_bundle(&plain, &cfg.passphrase)?).await?;
+            state.base_hash = Some(local_hash);
+            logger::log_info(&format!("[CloudSync] Pushed {} accounts", local.accounts.len()));
+            CloudSyncResult {
+                action: SyncAction::Pushed,
+                message: format!("Uploaded {} accounts", local.accounts.len()),
+                remote_updated_at: Some(local.updated_at),
+                remote_device: Some(local.device.clone()),
+            }
+        }
+        SyncDirection::Pull => {
+            let Some(bundle) = remote else {
+                return Err("No remote sync data found".to_string());
+            };
+            apply_bundle(&bundle, &cfg)?;
+            state.base_hash = remote_hash;
+            logger::log_info(&format!(
+                "[CloudSync] Pulled {} accounts from {}",
+                bundle.accounts.len(),
+                bundle.device
+            ));
+            CloudSyncResult {
+                action: SyncAction::Pulled,
+                message: format!("Downloaded {} accounts from {}", bundle.accounts.len(), bundle.device),
+                remote_updated_at,
+                remote_device,
+            }
+        }
+    };
+
+    state.last_sync_at = Some(chrono::Utc::now().timestamp());
+    save_state(&state)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{QuotaData, TokenData};
+
+    // 测试中使用较少的迭代次数，避免 debug 构建下 PBKDF2 过慢
+    const TEST_ROUNDS: u32 = 1_000;
+
+    fn test_account(id: &str, email: &str) -> Account {
+        let token = TokenData::new("access".into(), "refresh".into(), 3600, Some(email.into()), None, None);
+        Account::new(id.to_string(), email.to_string(), token)
+    }
+
+    #[test]
+    fn test_encrypt_roundtrip() {
+        let data = br#"{"hello":"world"}"#;
+        let enc = encrypt_bundle_with(data, "secret", TEST_ROUNDS).unwrap();
+        assert!(enc.starts_with(BUNDLE_MAGIC));
+        assert_eq!(enc[BUNDLE_MAGIC.len()], KDF_PBKDF2_SHA256);
+        assert_eq!(&enc[BUNDLE_MAGIC.len() + 1..BUNDLE_MAGIC.len() + 5], &TEST_ROUNDS.to_le_bytes());
+        assert_eq!(decrypt_bundle(&enc, "secret").unwrap(), data.to_vec());
+    }
+
+    #[test]
+    fn test_decrypt_rejects_wrong_passphrase_and_tampering() {
+        let data = br#"{"hello":"world"}"#;
+        let enc = encrypt_bundle_with(data, "secret", TEST_ROUNDS).unwrap();
+        assert!(decrypt_bundle(&enc, "wrong").is_err());
+
+        // 密文被篡改
+        let mut tampered = enc.clone();
+        *tampered.last_mut().unwrap() ^= 0x01;
+        assert!(decrypt_bundle(&tampered, "secret").is_err());
+
+        // 头部参数属于关联数据: 修改迭代次数或 nonce 均无法通过认证
+        let mut tampered = enc.clone();
+        tampered[BUNDLE_MAGIC.len() + 1] ^= 0x01;
+        assert!(decrypt_bundle(&tampered, "secret").is_err());
+        let mut tampered = enc.clone();
+        tampered[BUNDLE_MAGIC.len() + 5 + 16] ^= 0x01;
+        assert!(decrypt_bundle(&tampered, "secret").is_err());
+
+        // 未知算法与超出上限的迭代次数在派生密钥前即被拒绝
+        let mut tampered = enc.clone();
+        tampered[BUNDLE_MAGIC.len()] = 9;
+        assert!(decrypt_bundle(&tampered, "secret").unwrap_err().contains("Unsupported"));
+        let mut tampered = enc.clone();
+        tampered[BUNDLE_MAGIC.len() + 1..BUNDLE_MAGIC.len() + 5].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decrypt_bundle(&tampered, "secret").unwrap_err().contains("rounds"));
+
+        assert!(decrypt_bundle(&enc[..BUNDLE_MAGIC.len() + 10], "secret").is_err());
+        assert!(decrypt_bundle(b"not a bundle", "secret").is_err());
+    }
+
+    #[test]
+    fn test_legacy_bundles_still_decrypt() {
+        let data = br#"{"hello":"world"}"#;
+        let salt = [7u8; 16];
+        let nonce = [9u8; 12];
+        let cipher = Aes256Gcm::new_from_slice(&derive_key_legacy("secret", &salt)).unwrap();
+        let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), data.as_slice()).unwrap();
+        let legacy = [LEGACY_BUNDLE_MAGIC, &salt, &nonce, &ciphertext].concat();
+
+        assert_eq!(decrypt_bundle(&legacy, "secret").unwrap(), data.to_vec());
+        assert!(decrypt_bundle(&legacy, "wrong").is_err());
+    }
+
+    #[test]
+    fn test_plan_sync_detects_conflicts() {
+        use SyncDirection::*;
+        // 无远端数据时推送
+        assert_eq!(plan_sync(None, None, "a", None), SyncPlan::Run(Push));
+        // 内容一致
+        assert_eq!(plan_sync(None, Some("old"), "a", Some("a")), SyncPlan::UpToDate);
+        // 只有一侧发生变化
+        assert_eq!(plan_sync(None, Some("base"), "local", Some("base")), SyncPlan::Run(Push));
+        assert_eq!(plan_sync(None, Some("base"), "base", Some("remote")), SyncPlan::Run(Pull));
+        // 两侧都变化，或从未同步过且内容不同
+        assert_eq!(plan_sync(None, Some("base"), "local", Some("remote")), SyncPlan::Conflict);
+        assert_eq!(plan_sync(None, None, "local", Some("remote")), SyncPlan::Conflict);
+        // 显式指定方向时不做冲突检测
+        assert_eq!(plan_sync(Some(Pull), Some("base"), "local", Some("remote")), SyncPlan::Run(Pull));
+        assert_eq!(plan_sync(Some(Push), None, "local", Some("remote")), SyncPlan::Run(Push));
+    }
+
+    #[test]
+    fn test_apply_bundle_merges_accounts_by_email() {
+        let mut local_a = test_account("local-a", "a@example.com");
+        local_a.quota = Some(QuotaData::new());
+        let local_b = test_account("local-b", "b@example.com");
+        let local = vec![local_a, local_b];
+        let mut index = AccountIndex::new();
+        for a in &local {
+            index.accounts.push(AccountSummary {
+                id: a.id.clone(),
+                email: a.email.clone(),
+                name: None,
+                created_at: a.created_at,
+                last_used: a.last_used,
+            });
+        }
+
+        let mut remote_a = test_account("remote-a", "a@example.com");
+        remote_a.name = Some("Renamed".into());
+        // 新账号的 ID 与本地账号冲突时重新分配
+        let remote_c = test_account("local-b", "c@example.com");
+        let merged = merge_accounts(&[remote_a, remote_c], &local, &mut index);
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].id, "local-a");
+        assert_eq!(merged[0].name.as_deref(), Some("Renamed"));
+        assert!(merged[0].quota.is_some());
+        assert_eq!(merged[1].email, "c@example.com");
+        assert_ne!(merged[1].id, "local-b");
+
+        // 本地独有的账号保留，新账号追加到索引
+        let emails: Vec<&str> = index.accounts.iter().map(|s| s.email.as_str()).collect();
+        assert_eq!(emails, vec!["a@example.com", "b@example.com", "c@example.com"]);
+        assert_eq!(index.accounts[2].id, merged[1].id);
+    }
+
+    #[test]
+    fn test_apply_bundle_keeps_local_sync_credentials() {
+        let mut remote_config = AppConfig::new();
+        remote_config.language = "ja".to_string();
+        let mut settings = serde_json::to_value(&remote_config).unwrap();
+        settings.as_object_mut().unwrap().remove("cloud_sync");
+
+        let local_sync = CloudSyncConfig {
+            enabled: true,
+            webdav_password: "local-password".to_string(),
+            passphrase: "local-passphrase".to_string(),
+            ..Default::default()
+        };
+        let merged = merge_settings(&settings, &local_sync).unwrap();
+        assert_eq!(merged.language, "ja");
+        assert!(merged.cloud_sync.enabled);
+        assert_eq!(merged.cloud_sync.webdav_password, "local-password");
+        assert_eq!(merged.cloud_sync.passphrase, "local-passphrase");
+
+        assert!(merge_settings(&serde_json::json!({ "language": 42 }), &local_sync).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+// 环境变量 / 命令行覆盖层
+// 按 配置文件 < 环境变量 < 命令行参数 的顺序叠加到反代配置上，容器/headless 部署无需挂载桌面端的设置文件。
+// - 环境变量: `AG_PROXY_<FIELD>`，嵌套字段用双下划线分隔，如 AG_PROXY_UPSTREAM_PROXY__URL
+// - 命令行: `--set <path>=<value>`，路径用点分隔，如 --set upstream_proxy.url=http://...
+// 值按原字段类型解析: 字符串字段原样使用，其余 (数字/布尔/数组/对象) 按 JSON 解析。
+// 覆盖只作用于本次运行，不会写回配置文件。
+
+use serde_json::Value;
+
+use crate::proxy::ProxyConfig;
+
+/// 反代配置环境变量前缀
+pub const ENV_PREFIX: &str = "AG_PROXY_";
+
+/// 覆盖项: (点分路径, 原始值)
+pub type Override = (String, String);
+
+/// 解析 `path=value`
+pub fn parse_assignment(raw: &str) -> Result<Override, String> {
+    let (path, value) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid override '{}', expected <path>=<value>", raw))?;
+    let path = path.trim();
+    if path.is_empty() {
+        return Err(format!("Invalid override '{}': empty path", raw));
+    }
+    Ok((path.to_string(), value.to_string()))
+}
+
+/// `AG_PROXY_UPSTREAM_PROXY__URL` -> `upstream_proxy.url`
+fn env_key_to_path(key: &str) -> Option<String> {
+    let rest = key.strip_prefix(ENV_PREFIX)?;
+    if rest.is_empty() {
+        return None;
+    }
+    Some(rest.split("__").map(|s| s.to_ascii_lowercase()).collect::<Vec<_>>().join("."))
+}
+
+/// 从进程环境变量收集覆盖项 (按变量名排序，保证应用顺序稳定)
+pub fn env_overrides() -> Vec<Override> {
+    let mut overrides: Vec<Override> = std::env::vars()
+        .filter_map(|(k, v)| env_key_to_path(&k).map(|path| (path, v)))
+        .collect();
+    overrides.sort();
+    overrides
+}
+
+/// 按原字段类型解析值
+fn parse_value(existing: Option<&Value>, raw: &str, path: &str) -> Result<Value, String> {
+    match existing {
+        Some(Value::String(_)) => Ok(Value::String(raw.to_string())),
+        // 可选字段 (null) 与新建的映射键: 合法 JSON 按 JSON，否则视为字符串
+        None | Some(Value::Null) => Ok(serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))),
+        Some(_) => serde_json::from_str(raw).map_err(|e| format!("Invalid value for {}: {} ({})", path, raw, e)),
+    }
+}
+
+fn set_path(root: &mut Value, path: &str, raw: &str) -> Result<(), String> {
+    let segments: Vec<&str> = path.split('.').collect();
+    let (last, parents) = segments.split_last().ok_or_else(|| format!("Invalid path: {}", path))?;
+    let mut node = root;
+    for segment in parents {
+        node = node
+            .get_mut(*segment)
+            .filter(|n| n.is_object())
+            .ok_or_else(|| format!("Unknown config key: {}", path))?;
+    }
+    let object = node.as_object_mut().ok_or_else(|| format!("Unknown config key: {}", path))?;
+    // 顶层只允许已有字段 (拼写错误直接报错)；嵌套对象允许新增键 (如 custom_mapping.<alias>)
+    if parents.is_empty() && !object.contains_key(*last) {
+        return Err(format!("Unknown config key: {}", path));
+    }
+    let value = parse_value(object.get(*last), raw, path)?;
+    object.insert(last.to_string(), value);
+    Ok(())
+}
+
+/// 依次应用覆盖项，返回新的配置
+pub fn apply(config: &ProxyConfig, overrides: &[Override]) -> Result<ProxyConfig, String> {
+    if overrides.is_empty() {
+        return Ok(config.clone());
+    }
+    let mut value = serde_json::to_value(config).map_err(|e| format!("Failed to serialize config: {}", e))?;
+    for (path, raw) in overrides {
+        set_path(&mut value, path, raw)?;
+    }
+    serde_json::from_value(value).map_err(|e| format!("Invalid config after overrides: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_key_to_path() {
+        assert_eq!(env_key_to_path("AG_PROXY_PORT").as_deref(), Some("port"));
+        assert_eq!(env_key_to_path("AG_PROXY_UPSTREAM_PROXY__URL").as_deref(), Some("upstream_proxy.url"));
+        assert_eq!(env_key_to_path("AG_PROXY_"), None);
+        assert_eq!(env_key_to_path("PATH"), None);
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let base = ProxyConfig::default();
+        let overrides = vec![
+            ("port".to_string(), "9100".to_string()),
+            // 字符串字段即使形如数字也保持字符串
+            ("api_key".to_string(), "12345".to_string()),
+            ("allow_lan_access".to_string(), "true".to_string()),
+            ("upstream_proxy.url".to_string(), "socks5://127.0.0.1:1080".to_string()),
+            ("custom_mapping.gpt-4o".to_string(), "gemini-2.5-flash".to_string()),
+            ("log_format".to_string(), "json".to_string()),
+        ];
+        let config = apply(&base, &overrides).unwrap();
+        assert_eq!(config.port, 9100);
+        assert_eq!(config.api_key, "12345");
+        assert!(config.allow_lan_access);
+        assert_eq!(config.upstream_proxy.url, "socks5://127.0.0.1:1080");
+        assert_eq!(config.custom_mapping.get("gpt-4o").map(|s| s.as_str()), Some("gemini-2.5-flash"));
+        assert_eq!(config.log_format, crate::proxy::config::LogFormat::Json);
+
+        assert!(apply(&base, &[("prot".to_string(), "1".to_string())]).is_err());
+        assert!(apply(&base, &[("port".to_string(), "abc".to_string())]).is_err());
+        assert!(parse_assignment("port").is_err());
+        assert_eq!(parse_assignment("api_key=a=b").unwrap(), ("api_key".to_string(), "a=b".to_string()));
+    }
+}
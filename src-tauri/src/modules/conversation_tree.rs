@@ -0,0 +1,309 @@
+// 会话树 (对话分支)
+// 每条请求日志对应树上的一个节点: message_id = 截至最后一条消息的历史哈希，
+// parent_message_id = 最后一条助手回复之前的历史哈希 (即产生该回复的上一轮请求的 message_id)。
+// 重放同一提示词得到同一节点 (多次尝试)，编辑历史后重发得到兄弟节点，从而形成分支而非重复的平铺日志。
+
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+
+/// 节点查询行 (按时间正序)
+#[derive(Debug, Clone)]
+pub struct NodeRow {
+    pub log_id: String,
+    pub timestamp: i64,
+    pub model: Option<String>,
+    pub status: u16,
+    pub message_id: String,
+    pub parent_message_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ConversationNode {
+    pub message_id: String,
+    pub parent_message_id: Option<String>,
+    /// 最近一次尝试的日志 ID
+    pub log_id: String,
+    pub timestamp: i64,
+    pub model: Option<String>,
+    pub status: u16,
+    /// 同一节点的请求次数 (重放 / 重试)
+    pub attempts: usize,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ConversationBranch {
+    pub leaf_message_id: String,
+    /// 根 -> 叶
+    pub nodes: Vec<ConversationNode>,
+    pub last_timestamp: i64,
+}
+
+fn node_id(hasher: Sha256) -> String {
+    let hash = format!("{:x}", hasher.finalize());
+    format!("msg-{}", &hash[..16])
+}
+
+fn is_assistant(message: &Value) -> bool {
+    matches!(message.get("role").and_then(|r| r.as_str()), Some("assistant") | Some("model"))
+}
+
+/// 参与哈希的内容: 文本 / 工具调用 / 工具结果；思维内容与签名不参与 (往返时可能变化)
+fn part_text(part: &Value, out: &mut String) {
+    if part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false) {
+        return;
+    }
+    match part.get("type").and_then(|t| t.as_str()) {
+        Some("thinking") | Some("redacted_thinking") => return,
+        Some("tool_use") => {
+            out.push_str(part.get("name").and_then(|n| n.as_str()).unwrap_or_default());
+            out.push_str(&part.get("input").map(|i| i.to_string()).unwrap_or_default());
+            return;
+        }
+        Some("tool_result") => {
+            out.push_str(part.get("tool_use_id").and_then(|n| n.as_str()).unwrap_or_default());
+            out.push_str(&part.get("content").map(|c| c.to_string()).unwrap_or_default());
+            return;
+        }
+        _ => {}
+    }
+    if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+        out.push_str(text);
+    } else if let Some(call) = part.get("functionCall").or_else(|| part.get("functionResponse")) {
+        out.push_str(&call.to_string());
+    } else {
+        out.push_str(&part.to_string());
+    }
+}
+
+fn normalize(message: &Value) -> String {
+    let mut out = String::new();
+    out.push_str(if is_assistant(message) {
+        "assistant"
+    } else {
+        message.get("role").and_then(|r| r.as_str()).unwrap_or("user")
+    });
+    out.push('\u{1f}');
+    match message.get("content").or_else(|| message.get("parts")) {
+        Some(Value::String(s)) => out.push_str(s),
+        Some(Value::Array(parts)) => parts.iter().for_each(|p| part_text(p, &mut out)),
+        _ => {}
+    }
+    if let Some(calls) = message.get("tool_calls").and_then(|c| c.as_array()) {
+        for call in calls {
+            out.push_str(&call.get("function").map(|f| f.to_string()).unwrap_or_default());
+        }
+    }
+    if let Some(id) = message.get("tool_call_id").and_then(|i| i.as_str()) {
+        out.push_str(id);
+    }
+    out
+}
+
+/// 计算请求的 (message_id, parent_message_id)；非对话类端点返回 None
+pub fn message_ids(path: &str, body: &[u8]) -> Option<(String, Option<String>)> {
+    let list_key = match path {
+        "/v1/chat/completions" | "/v1/messages" => "messages",
+        p if p.starts_with("/v1beta/models/")
+            && (p.ends_with(":generateContent") || p.ends_with(":streamGenerateContent")) =>
+        {
+            "contents"
+        }
+        _ => return None,
+    };
+    let value: Value = serde_json::from_slice(body).ok()?;
+    let messages = value.get(list_key)?.as_array()?;
+    if messages.is_empty() {
+        return None;
+    }
+
+    let last_assistant = messages.iter().rposition(is_assistant);
+    let mut hasher = Sha256::new();
+    let mut parent = None;
+    for (i, message) in messages.iter().enumerate() {
+        if i > 0 && Some(i) == last_assistant {
+            parent = Some(node_id(hasher.clone()));
+        }
+        hasher.update(normalize(message).as_bytes());
+        hasher.update([0x1e]);
+    }
+    Some((node_id(hasher), parent))
+}
+
+/// 按 message_id 合并重复尝试 (保留最近一次)
+fn collect_nodes(rows: &[NodeRow]) -> HashMap<String, ConversationNode> {
+    let mut nodes: HashMap<String, ConversationNode> = HashMap::new();
+    for row in rows {
+        let node = nodes.entry(row.message_id.clone()).or_insert_with(|| ConversationNode {
+            message_id: row.message_id.clone(),
+            parent_message_id: row.parent_message_id.clone(),
+            log_id: row.log_id.clone(),
+            timestamp: row.timestamp,
+            model: row.model.clone(),
+            status: row.status,
+            attempts: 0,
+        });
+        node.attempts += 1;
+        if row.timestamp >= node.timestamp {
+            node.log_id = row.log_id.clone();
+            node.timestamp = row.timestamp;
+            node.model = row.model.clone();
+            node.status = row.status;
+        }
+    }
+    nodes
+}
+
+/// 父节点不在本会话日志中时视为根
+fn parent_of<'a>(nodes: &'a HashMap<String, ConversationNode>, node: &ConversationNode) -> Option<&'a ConversationNode> {
+    node.parent_message_id.as_ref().and_then(|p| nodes.get(p))
+}
+
+fn child_counts(nodes: &HashMap<String, ConversationNode>) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for node in nodes.values() {
+        if let Some(parent) = parent_of(nodes, node) {
+            *counts.entry(parent.message_id.as_str()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// 每个叶节点对应一条分支，按最近活动时间倒序
+pub fn build_branches(rows: &[NodeRow]) -> Vec<ConversationBranch> {
+    let nodes = collect_nodes(rows);
+    let children = child_counts(&nodes);
+    let mut branches: Vec<ConversationBranch> = nodes
+        .values()
+        .filter(|n| !children.contains_key(n.message_id.as_str()))
+        .map(|leaf| {
+            let mut path = vec![leaf.clone()];
+            let mut seen = HashSet::from([leaf.message_id.as_str()]);
+            let mut current = leaf;
+            while let Some(parent) = parent_of(&nodes, current) {
+                if !seen.insert(parent.message_id.as_str()) {
+                    break;
+                }
+                path.push(parent.clone());
+                current = parent;
+            }
+            path.reverse();
+            ConversationBranch {
+                leaf_message_id: leaf.message_id.clone(),
+                last_timestamp: path.iter().map(|n| n.timestamp).max().unwrap_or(leaf.timestamp),
+                nodes: path,
+            }
+        })
+        .collect();
+    let leaf_ts = |b: &ConversationBranch| b.nodes.last().map(|n| n.timestamp).unwrap_or(0);
+    branches.sort_by(|a, b| b.last_timestamp.cmp(&a.last_timestamp).then_with(|| leaf_ts(b).cmp(&leaf_ts(a))));
+    branches
+}
+
+/// 裁剪分支时可删除的节点: 从叶节点向上，直到遇到仍有其他子节点的分叉点
+pub fn exclusive_nodes(rows: &[NodeRow], leaf_message_id: &str) -> Result<Vec<String>, String> {
+    let nodes = collect_nodes(rows);
+    let children = child_counts(&nodes);
+    let leaf = nodes.get(leaf_message_id).ok_or_else(|| format!("Unknown message id: {}", leaf_message_id))?;
+    if children.contains_key(leaf_message_id) {
+        return Err(format!("{} is not a branch leaf", leaf_message_id));
+    }
+
+    let mut removed = vec![leaf.message_id.clone()];
+    let mut current = leaf;
+    while let Some(parent) = parent_of(&nodes, current) {
+        if children.get(parent.message_id.as_str()).copied().unwrap_or(0) > 1 || removed.contains(&parent.message_id) {
+            break;
+        }
+        removed.push(parent.message_id.clone());
+        current = parent;
+    }
+    Ok(removed)
+}
+
+pub fn list_branches(session_id: &str) -> Result<Vec<ConversationBranch>, String> {
+    let rows = crate::modules::proxy_db::get_session_nodes(session_id)?;
+    Ok(build_branches(&rows))
+}
+
+/// 删除分支独有节点的全部日志，返回删除的日志条数
+pub fn prune_branch(session_id: &str, leaf_message_id: &str) -> Result<usize, String> {
+    let rows = crate::modules::proxy_db::get_session_nodes(session_id)?;
+    let removed = exclusive_nodes(&rows, leaf_message_id)?;
+    crate::modules::proxy_db::delete_logs_by_messages(session_id, &removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ids(body: Value) -> (String, Option<String>) {
+        message_ids("/v1/chat/completions", body.to_string().as_bytes()).unwrap()
+    }
+
+    fn row(log_id: &str, ts: i64, ids: &(String, Option<String>)) -> NodeRow {
+        NodeRow {
+            log_id: log_id.to_string(),
+            timestamp: ts,
+            model: None,
+            status: 200,
+            message_id: ids.0.clone(),
+            parent_message_id: ids.1.clone(),
+        }
+    }
+
+    #[test]
+    fn test_turns_chain_and_edits_branch() {
+        let first = ids(json!({ "messages": [{ "role": "user", "content": "hi" }] }));
+        assert_eq!(first.1, None);
+        let replay = ids(json!({ "messages": [{ "role": "user", "content": "hi" }], "temperature": 1 }));
+        assert_eq!(replay, first);
+
+        let second = ids(json!({ "messages": [
+            { "role": "user", "content": "hi" },
+            { "role": "assistant", "content": "hello" },
+            { "role": "user", "content": "tell me a joke" }
+        ]}));
+        assert_eq!(second.1.as_deref(), Some(first.0.as_str()));
+
+        let edited = ids(json!({ "messages": [
+            { "role": "user", "content": "hi" },
+            { "role": "assistant", "content": "hello" },
+            { "role": "user", "content": "tell me a poem" }
+        ]}));
+        assert_eq!(edited.1, second.1);
+        assert_ne!(edited.0, second.0);
+
+        // Gemini 原生格式同样适用，思维内容不影响节点
+        let gemini = |thought: &str| {
+            message_ids(
+                "/v1beta/models/gemini-2.5-pro:generateContent",
+                json!({ "contents": [
+                    { "role": "user", "parts": [{ "text": "hi" }] },
+                    { "role": "model", "parts": [{ "text": thought, "thought": true }, { "text": "hello" }] },
+                    { "role": "user", "parts": [{ "text": "again" }] }
+                ]})
+                .to_string()
+                .as_bytes(),
+            )
+            .unwrap()
+        };
+        assert_eq!(gemini("a"), gemini("b"));
+        assert!(message_ids("/v1/embeddings", b"{}").is_none());
+
+        let rows = vec![row("l1", 1, &first), row("l2", 2, &second), row("l3", 3, &edited), row("l4", 4, &first)];
+        let branches = build_branches(&rows);
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].leaf_message_id, edited.0);
+        assert_eq!(branches[0].nodes.len(), 2);
+        assert_eq!(branches[0].nodes[0].attempts, 2);
+        assert_eq!(branches[0].nodes[0].log_id, "l4");
+
+        assert_eq!(exclusive_nodes(&rows, &second.0).unwrap(), vec![second.0.clone()]);
+        assert!(exclusive_nodes(&rows, &first.0).is_err());
+        let linear = vec![row("l1", 1, &first), row("l2", 2, &second)];
+        assert_eq!(exclusive_nodes(&linear, &second.0).unwrap(), vec![second.0.clone(), first.0.clone()]);
+    }
+}
@@ -0,0 +1,310 @@
+// 每日用量报告
+// 每天在设定时刻之后汇总前一天 (本地时区) 的各账号 Token 用量、错误数与常用模型，
+// 以桌面通知推送摘要，并可追加完整 Markdown 报告到指定文件。
+
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDate, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use tokio::time::{self, Duration};
+
+use crate::models::DailyReportConfig;
+use crate::modules::{account, config, proxy_db, token_stats};
+
+const TOP_MODELS: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountDailyUsage {
+    pub account_email: String,
+    pub request_count: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    pub errors: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyReport {
+    pub date: String,
+    pub accounts: Vec<AccountDailyUsage>,
+    pub top_models: Vec<token_stats::ModelTokenStats>,
+    pub total_requests: u64,
+    pub total_tokens: u64,
+    pub total_errors: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ReportState {
+    last_date: Option<String>,
+}
+
+fn state_path() -> Result<PathBuf, String> {
+    Ok(account::get_data_dir()?.join("daily_report_state.json"))
+}
+
+fn load_state() -> ReportState {
+    state_path()
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &ReportState) {
+    if let Ok(path) = state_path() {
+        if let Ok(content) = serde_json::to_string_pretty(state) {
+            let _ = std::fs::write(path, content);
+        }
+    }
+}
+
+/// 本地日期对应的 [开始, 结束) 时间范围
+fn day_bounds(date: NaiveDate) -> Result<(DateTime<Local>, DateTime<Local>), String> {
+    let to_local = |d: NaiveDate| {
+        d.and_hms_opt(0, 0, 0)
+            .and_then(|t| t.and_local_timezone(Local).earliest())
+            .ok_or_else(|| format!("无效的本地日期: {}", d))
+    };
+    let next = date.succ_opt().ok_or_else(|| format!("无效的日期: {}", date))?;
+    Ok((to_local(date)?, to_local(next)?))
+}
+
+/// 从历史数据库生成指定日期的报告
+pub fn build_report(date: NaiveDate) -> Result<DailyReport, String> {
+    let (start, end) = day_bounds(date)?;
+    let usage = token_stats::get_account_stats_between(start.timestamp(), end.timestamp())?;
+    let errors = proxy_db::get_error_counts_between(start.timestamp_millis(), end.timestamp_millis())?;
+    let top_models = token_stats::get_model_stats_between(start.timestamp(), end.timestamp(), TOP_MODELS)?;
+    Ok(assemble(date, usage, errors, top_models))
+}
+
+fn assemble(
+    date: NaiveDate,
+    usage: Vec<token_stats::AccountTokenStats>,
+    errors: Vec<(String, u64)>,
+    top_models: Vec<token_stats::ModelTokenStats>,
+) -> DailyReport {
+    let mut error_map: HashMap<String, u64> = errors.into_iter().collect();
+    let mut accounts: Vec<AccountDailyUsage> = usage
+        .into_iter()
+        .map(|u| AccountDailyUsage {
+            errors: error_map.remove(&u.account_email).unwrap_or(0),
+            account_email: u.account_email,
+            request_count: u.request_count,
+            input_tokens: u.total_input_tokens,
+            output_tokens: u.total_output_tokens,
+            total_tokens: u.total_tokens,
+        })
+        .collect();
+    // 只有失败请求的账号 (或未分配账号的错误) 也要出现在报告中
+    for (email, count) in error_map {
+        accounts.push(AccountDailyUsage {
+            account_email: email,
+            request_count: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            total_tokens: 0,
+            errors: count,
+        });
+    }
+    accounts.sort_by(|a, b| b.total_tokens.cmp(&a.total_tokens).then(b.errors.cmp(&a.errors)));
+
+    DailyReport {
+        date: date.format("%Y-%m-%d").to_string(),
+        total_requests: accounts.iter().map(|a| a.request_count).sum(),
+        total_tokens: accounts.iter().map(|a| a.total_tokens).sum(),
+        total_errors: accounts.iter().map(|a| a.errors).sum(),
+        accounts,
+        top_models,
+    }
+}
+
+fn display_account(email: &str) -> &str {
+    if email.is_empty() {
+        "(no account)"
+    } else {
+        email
+    }
+}
+
+/// 桌面通知用的一行摘要
+pub fn summary_line(report: &DailyReport) -> String {
+    if report.total_requests == 0 && report.total_errors == 0 {
+        return "No proxy traffic.".to_string();
+    }
+    let mut line = format!(
+        "{} tokens across {} requests, {} errors.",
+        report.total_tokens, report.total_requests, report.total_errors
+    );
+    if let Some(model) = report.top_models.first() {
+        line.push_str(&format!(" Top model: {}.", model.model));
+    }
+    if let Some(acc) = report.accounts.iter().find(|a| a.total_tokens > 0) {
+        line.push_str(&format!(" Busiest account: {}.", display_account(&acc.account_email)));
+    }
+    line
+}
+
+pub fn render_markdown(report: &DailyReport) -> String {
+    let mut md = format!("## Daily report — {}\n\n", report.date);
+    md.push_str(&format!(
+        "**Requests:** {} · **Tokens:** {} · **Errors:** {}\n\n",
+        report.total_requests, report.total_tokens, report.total_errors
+    ));
+
+    if !report.accounts.is_empty() {
+        md.push_str("### Tokens per account\n\n");
+        md.push_str("| Account | Requests | Input | Output | Total | Errors |\n");
+        md.push_str("|---|---:|---:|---:|---:|---:|\n");
+        for acc in &report.accounts {
+            md.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} |\n",
+                display_account(&acc.account_email),
+                acc.request_count,
+                acc.input_tokens,
+                acc.output_tokens,
+                acc.total_tokens,
+                acc.errors
+            ));
+        }
+        md.push('\n');
+    }
+
+    if !report.top_models.is_empty() {
+        md.push_str("### Most used models\n\n");
+        md.push_str("| Model | Requests | Tokens |\n");
+        md.push_str("|---|---:|---:|\n");
+        for model in &report.top_models {
+            md.push_str(&format!("| {} | {} | {} |\n", model.model, model.request_count, model.total_tokens));
+        }
+        md.push('\n');
+    }
+    md
+}
+
+fn append_to_file(path: &str, markdown: &str) -> Result<(), String> {
+    let path = PathBuf::from(path.trim());
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| format!("无法创建目录 {}: {}", parent.display(), e))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("无法打开报告文件 {}: {}", path.display(), e))?;
+    file.write_all(markdown.as_bytes())
+        .map_err(|e| format!("写入报告文件失败: {}", e))
+}
+
+/// 生成并投递报告 (桌面通知 + 可选文件)，返回 Markdown 内容
+pub fn deliver(config: &DailyReportConfig, date: NaiveDate) -> Result<String, String> {
+    let report = build_report(date)?;
+    let markdown = render_markdown(&report);
+    if config.desktop {
+        crate::modules::notifications::show_desktop_message(
+            &format!("Antigravity daily report ({})", report.date),
+            &summary_line(&report),
+        );
+    }
+    if !config.report_path.trim().is_empty() {
+        append_to_file(&config.report_path, &markdown)?;
+    }
+    Ok(markdown)
+}
+
+/// 已到达设定时刻且昨天的报告尚未生成时，返回需要报告的日期
+fn due_date(now: DateTime<Local>, hour: u8, last_date: Option<&str>) -> Option<NaiveDate> {
+    if now.hour() < hour.min(23) as u32 {
+        return None;
+    }
+    let yesterday = now.date_naive() - ChronoDuration::days(1);
+    let key = yesterday.format("%Y-%m-%d").to_string();
+    if last_date == Some(key.as_str()) {
+        return None;
+    }
+    Some(yesterday)
+}
+
+/// 后台任务: 每 5 分钟检查一次是否需要生成日报
+pub fn start_daily_report_loop() {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(300));
+        loop {
+            interval.tick().await;
+
+            let Ok(app_config) = config::load_app_config() else {
+                continue;
+            };
+            let report_config = app_config.notifications.daily_report;
+            if !report_config.enabled {
+                continue;
+            }
+            let mut state = load_state();
+            let Some(date) = due_date(Local::now(), report_config.hour, state.last_date.as_deref()) else {
+                continue;
+            };
+            match tokio::task::spawn_blocking(move || deliver(&report_config, date)).await {
+                Ok(Ok(_)) => tracing::info!("[DailyReport] 已生成 {} 的日报", date),
+                Ok(Err(e)) => tracing::warn!("[DailyReport] 生成 {} 的日报失败: {}", date, e),
+                Err(e) => tracing::warn!("[DailyReport] 任务异常: {}", e),
+            }
+            // 失败也记为已处理，避免每 5 分钟重复弹出错误
+            state.last_date = Some(date.format("%Y-%m-%d").to_string());
+            save_state(&state);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn usage(email: &str, requests: u64, total: u64) -> token_stats::AccountTokenStats {
+        token_stats::AccountTokenStats {
+            account_email: email.to_string(),
+            total_input_tokens: total / 2,
+            total_output_tokens: total - total / 2,
+            total_tokens: total,
+            request_count: requests,
+        }
+    }
+
+    #[test]
+    fn test_assemble_merges_errors_and_renders() {
+        let date = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        let report = assemble(
+            date,
+            vec![usage("a@x.com", 10, 5000), usage("b@x.com", 3, 800)],
+            vec![("b@x.com".to_string(), 2), ("c@x.com".to_string(), 4)],
+            vec![token_stats::ModelTokenStats { model: "gemini-3-flash".to_string(), total_tokens: 5800, request_count: 13 }],
+        );
+        assert_eq!(report.total_requests, 13);
+        assert_eq!(report.total_errors, 6);
+        assert_eq!(report.accounts[0].account_email, "a@x.com");
+        assert_eq!(report.accounts[1].errors, 2);
+        assert_eq!(report.accounts[2].account_email, "c@x.com");
+
+        let md = render_markdown(&report);
+        assert!(md.starts_with("## Daily report — 2026-03-01"));
+        assert!(md.contains("| b@x.com | 3 | 400 | 400 | 800 | 2 |"));
+        assert!(md.contains("| gemini-3-flash | 13 | 5800 |"));
+        assert_eq!(
+            summary_line(&report),
+            "5800 tokens across 13 requests, 6 errors. Top model: gemini-3-flash. Busiest account: a@x.com."
+        );
+    }
+
+    #[test]
+    fn test_due_date_waits_for_hour_and_runs_once() {
+        let now = Local.with_ymd_and_hms(2026, 3, 2, 8, 30, 0).unwrap();
+        assert_eq!(due_date(now, 9, None), None);
+
+        let now = Local.with_ymd_and_hms(2026, 3, 2, 9, 5, 0).unwrap();
+        let expected = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        assert_eq!(due_date(now, 9, None), Some(expected));
+        assert_eq!(due_date(now, 9, Some("2026-03-01")), None);
+        assert_eq!(due_date(now, 9, Some("2026-02-28")), Some(expected));
+    }
+}
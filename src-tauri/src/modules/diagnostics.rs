@@ -0,0 +1,504 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::modules::{account, config, logger, quota};
+use crate::proxy::upstream::client::UpstreamClient;
+
+/// End-to-end generation check model
+const DIAGNOSTIC_MODEL: &str = "gemini-2.5-flash";
+/// Clock skew thresholds (seconds): warn / fail
+const CLOCK_SKEW_WARN_SECS: i64 = 30;
+const CLOCK_SKEW_FAIL_SECS: i64 = 300;
+/// Number of recent log files included in the bundle, and bytes kept from the end of each
+const BUNDLE_LOG_FILES: usize = 3;
+const BUNDLE_LOG_TAIL_BYTES: u64 = 2 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+    Skipped,
+}
+
+/// Single diagnostic check result
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Value::is_null")]
+    pub details: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+    pub generated_at: i64,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub checks: Vec<DiagnosticCheck>,
+    /// Path of the redacted diagnostics zip (when requested)
+    pub bundle_path: Option<String>,
+}
+
+fn check(name: &str, started: Instant, status: CheckStatus, message: impl Into<String>, details: Value) -> DiagnosticCheck {
+    DiagnosticCheck {
+        name: name.to_string(),
+        status,
+        message: message.into(),
+        duration_ms: started.elapsed().as_millis() as u64,
+        details,
+    }
+}
+
+// ===== Redaction =====
+
+fn secret_patterns() -> &'static [(Regex, &'static str)] {
+    static PATTERNS: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        vec![
+            // Google OAuth access / refresh tokens
+            (Regex::new(r"ya29\.[A-Za-z0-9_\-\.]+").unwrap(), "ya29.[REDACTED]"),
+            (Regex::new(r"1//[A-Za-z0-9_\-]{20,}").unwrap(), "1//[REDACTED]"),
+            // Proxy / provider API keys
+            (Regex::new(r"sk-[A-Za-z0-9_\-]{8,}").unwrap(), "sk-[REDACTED]"),
+            (Regex::new(r"AIza[0-9A-Za-z_\-]{20,}").unwrap(), "AIza[REDACTED]"),
+            (Regex::new(r"(?i)(bearer\s+)[A-Za-z0-9_\-\.=]+").unwrap(), "${1}[REDACTED]"),
+            (
+                Regex::new(r#"(?i)("?(?:access_token|refresh_token|api_key|x-api-key|x-goog-api-key|password|secret)"?\s*[:=]\s*"?)[^"\s,}]+"#).unwrap(),
+                "${1}[REDACTED]",
+            ),
+            // 邮箱只保留首字母与域名
+            (Regex::new(r"\b([A-Za-z0-9])[A-Za-z0-9._%+\-]*@([A-Za-z0-9.\-]+\.[A-Za-z]{2,})\b").unwrap(), "${1}***@${2}"),
+        ]
+    })
+}
+
+/// Mask tokens, API keys and email addresses in free text (logs)
+pub fn redact_text(text: &str) -> String {
+    secret_patterns()
+        .iter()
+        .fold(text.to_string(), |acc, (re, replacement)| re.replace_all(&acc, *replacement).into_owned())
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    ["key", "token", "secret", "password", "credential"].iter().any(|k| key.contains(k))
+}
+
+/// Redact configuration JSON: secret-looking fields are replaced, other strings are text-redacted
+pub fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                let is_secret = is_secret_key(key) && !matches!(v, Value::Bool(_) | Value::Number(_) | Value::Null);
+                match v {
+                    Value::String(s) if is_secret && !s.is_empty() => *v = json!("[REDACTED]"),
+                    Value::Array(items) if is_secret && items.iter().all(|i| i.is_string()) => {
+                        *v = json!(items.iter().map(|_| "[REDACTED]").collect::<Vec<_>>())
+                    }
+                    // 以 API Key 为键的映射 (如按 Key 限额)，键名本身即密钥
+                    Value::Object(inner) if is_secret && key.to_ascii_lowercase().contains("key") => {
+                        let renamed = std::mem::take(inner)
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, (_, mut item))| {
+                                redact_json(&mut item);
+                                (format!("[REDACTED-{}]", i + 1), item)
+                            })
+                            .collect();
+                        *inner = renamed;
+                    }
+                    _ => redact_json(v),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        Value::String(s) => *s = redact_text(s),
+        _ => {}
+    }
+}
+
+// ===== Checks =====
+
+async fn check_port(port: u16) -> DiagnosticCheck {
+    let started = Instant::now();
+    let addr = format!("127.0.0.1:{}", port);
+    let connect = tokio::time::timeout(Duration::from_secs(2), tokio::net::TcpStream::connect(&addr)).await;
+    if !matches!(connect, Ok(Ok(_))) {
+        // 无法连接: 区分 "服务未启动 (端口空闲)" 与 "端口被其他进程占用"
+        return match std::net::TcpListener::bind(&addr) {
+            Ok(_) => check("port", started, CheckStatus::Warn, format!("Proxy is not running; port {} is free", port), Value::Null),
+            Err(e) => check("port", started, CheckStatus::Fail, format!("Port {} is not accepting connections and cannot be bound: {}", port, e), Value::Null),
+        };
+    }
+
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(5)).build();
+    let health = match client {
+        Ok(c) => c.get(format!("http://{}/healthz", addr)).send().await,
+        Err(e) => return check("port", started, CheckStatus::Warn, format!("Port {} is open but the HTTP client failed: {}", port, e), Value::Null),
+    };
+    match health {
+        Ok(resp) if resp.status().is_success() => {
+            let body = resp.json::<Value>().await.unwrap_or(Value::Null);
+            check("port", started, CheckStatus::Pass, format!("Proxy is listening on port {}", port), body)
+        }
+        Ok(resp) => check(
+            "port",
+            started,
+            CheckStatus::Warn,
+            format!("Port {} is open but /healthz returned HTTP {} (another service, or auth is required)", port, resp.status()),
+            Value::Null,
+        ),
+        Err(e) => check("port", started, CheckStatus::Fail, format!("Port {} is open but not responding to HTTP: {}", port, e), Value::Null),
+    }
+}
+
+async fn check_dns(hosts: &[String]) -> DiagnosticCheck {
+    let started = Instant::now();
+    let mut details = serde_json::Map::new();
+    let mut failed = 0;
+    for host in hosts {
+        match tokio::time::timeout(Duration::from_secs(5), tokio::net::lookup_host((host.as_str(), 443))).await {
+            Ok(Ok(addrs)) => {
+                let addrs: Vec<String> = addrs.map(|a| a.ip().to_string()).collect();
+                details.insert(host.clone(), json!(addrs));
+            }
+            Ok(Err(e)) => {
+                failed += 1;
+                details.insert(host.clone(), json!({ "error": e.to_string() }));
+            }
+            Err(_) => {
+                failed += 1;
+                details.insert(host.clone(), json!({ "error": "timeout" }));
+            }
+        }
+    }
+    let (status, message) = match failed {
+        0 => (CheckStatus::Pass, format!("Resolved {} upstream host(s)", hosts.len())),
+        n if n < hosts.len() => (CheckStatus::Warn, format!("{} of {} upstream host(s) failed to resolve", n, hosts.len())),
+        _ => (CheckStatus::Fail, "No upstream host could be resolved".to_string()),
+    };
+    check("dns", started, status, message, Value::Object(details))
+}
+
+/// HTTPS 请求成功即说明 TCP + TLS (及代理) 可用
+async fn check_tls(client: &UpstreamClient) -> DiagnosticCheck {
+    let started = Instant::now();
+    let probes = client.probe_reachability().await;
+    let reachable = probes.iter().filter(|p| p.reachable).count();
+    let status = match reachable {
+        0 => CheckStatus::Fail,
+        n if n < probes.len() => CheckStatus::Warn,
+        _ => CheckStatus::Pass,
+    };
+    check(
+        "tls",
+        started,
+        status,
+        format!("{} of {} upstream endpoint(s) reachable over HTTPS", reachable, probes.len()),
+        json!(probes),
+    )
+}
+
+/// Compare the local clock with the upstream `Date` header
+async fn check_clock_skew(url: &str, upstream_proxy: Option<&crate::proxy::config::UpstreamProxyConfig>) -> DiagnosticCheck {
+    let started = Instant::now();
+    let mut builder = reqwest::Client::builder().timeout(Duration::from_secs(10));
+    if let Some(p) = upstream_proxy.filter(|p| p.enabled && !p.url.is_empty()) {
+        if let Ok(proxy) = reqwest::Proxy::all(&p.url) {
+            builder = builder.proxy(proxy);
+        }
+    }
+    let response = match builder.build().map_err(|e| e.to_string()) {
+        Ok(c) => c.get(url).send().await.map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+    let date = match response {
+        Ok(resp) => resp
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok()),
+        Err(e) => return check("clock_skew", started, CheckStatus::Skipped, format!("Upstream not reachable: {}", e), Value::Null),
+    };
+    let Some(remote) = date else {
+        return check("clock_skew", started, CheckStatus::Skipped, "Upstream response has no Date header", Value::Null);
+    };
+    // Date 头精度为秒，扣除一半往返时间
+    let local = chrono::Utc::now() - chrono::Duration::milliseconds(started.elapsed().as_millis() as i64 / 2);
+    let skew = local.timestamp() - remote.timestamp();
+    let status = match skew.abs() {
+        s if s >= CLOCK_SKEW_FAIL_SECS => CheckStatus::Fail,
+        s if s >= CLOCK_SKEW_WARN_SECS => CheckStatus::Warn,
+        _ => CheckStatus::Pass,
+    };
+    check(
+        "clock_skew",
+        started,
+        status,
+        format!("Local clock is {}s {} upstream time", skew.abs(), if skew >= 0 { "ahead of" } else { "behind" }),
+        json!({ "skew_secs": skew }),
+    )
+}
+
+/// 返回检查结果与第一个可用的 (access_token, project_id)
+async fn check_tokens() -> (DiagnosticCheck, Option<(String, String)>) {
+    let started = Instant::now();
+    let accounts = match account::list_accounts() {
+        Ok(a) => a,
+        Err(e) => return (check("tokens", started, CheckStatus::Fail, format!("Failed to load accounts: {}", e), Value::Null), None),
+    };
+    let mut results = Vec::new();
+    let mut usable = None;
+    let mut valid = 0;
+    let enabled: Vec<_> = accounts.iter().filter(|a| !a.disabled).collect();
+    for acc in &enabled {
+        match quota::get_valid_token_for_warmup(acc).await {
+            Ok((token, project_id)) => {
+                valid += 1;
+                usable.get_or_insert((token, project_id));
+                results.push(json!({ "account": redact_text(&acc.email), "valid": true }));
+            }
+            Err(e) => results.push(json!({ "account": redact_text(&acc.email), "valid": false, "error": redact_text(&e) })),
+        }
+    }
+    let disabled = accounts.len() - enabled.len();
+    let status = match (enabled.len(), valid) {
+        (0, _) => CheckStatus::Fail,
+        (n, v) if v == n => CheckStatus::Pass,
+        (_, 0) => CheckStatus::Fail,
+        _ => CheckStatus::Warn,
+    };
+    let result = check(
+        "tokens",
+        started,
+        status,
+        format!("{} of {} enabled account(s) have a valid token ({} disabled)", valid, enabled.len(), disabled),
+        json!(results),
+    );
+    (result, usable)
+}
+
+async fn check_generation(client: &UpstreamClient, credentials: Option<(String, String)>) -> DiagnosticCheck {
+    let started = Instant::now();
+    let Some((token, project_id)) = credentials else {
+        return check("generation", started, CheckStatus::Skipped, "No account with a valid token", Value::Null);
+    };
+    let body = json!({
+        "project": project_id,
+        "requestId": format!("diag-{}", uuid::Uuid::new_v4()),
+        "request": {
+            "contents": [{ "role": "user", "parts": [{ "text": "Reply with the single word OK." }] }],
+            "generationConfig": { "maxOutputTokens": 16 }
+        },
+        "model": DIAGNOSTIC_MODEL,
+        "userAgent": "antigravity",
+        "requestType": "agent"
+    });
+    let response = match client.call_v1_internal("generateContent", &token, body, None).await {
+        Ok(r) => r,
+        Err(e) => return check("generation", started, CheckStatus::Fail, format!("Request failed: {}", redact_text(&e)), Value::Null),
+    };
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        let snippet: String = redact_text(&text).chars().take(300).collect();
+        return check("generation", started, CheckStatus::Fail, format!("HTTP {}: {}", status, snippet), Value::Null);
+    }
+    let value: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+    let root = value.get("response").unwrap_or(&value);
+    let reply = root
+        .pointer("/candidates/0/content/parts/0/text")
+        .and_then(|t| t.as_str())
+        .unwrap_or("")
+        .trim()
+        .to_string();
+    check(
+        "generation",
+        started,
+        CheckStatus::Pass,
+        format!("{} responded in {}ms", DIAGNOSTIC_MODEL, started.elapsed().as_millis()),
+        json!({ "model": DIAGNOSTIC_MODEL, "reply": reply.chars().take(50).collect::<String>() }),
+    )
+}
+
+// ===== Bundle =====
+
+fn read_tail(path: &Path, max_bytes: u64) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    if len > max_bytes {
+        file.seek(SeekFrom::Start(len - max_bytes))?;
+    }
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn recent_log_files(dir: &Path, count: usize) -> Vec<PathBuf> {
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().is_file())
+                .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort_by(|a, b| b.0.cmp(&a.0));
+    files.into_iter().take(count).map(|(_, p)| p).collect()
+}
+
+fn write_bundle(report: &DiagnosticsReport) -> Result<PathBuf, String> {
+    let dir = account::get_data_dir()?.join("diagnostics");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create diagnostics directory: {}", e))?;
+    let path = dir.join(format!("diagnostics-{}.zip", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    let file = std::fs::File::create(&path).map_err(|e| format!("Failed to create bundle: {}", e))?;
+
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut add = |name: &str, content: &str| -> Result<(), String> {
+        zip.start_file(name, options).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+        zip.write_all(content.as_bytes()).map_err(|e| format!("Failed to write {}: {}", name, e))
+    };
+
+    add("report.json", &serde_json::to_string_pretty(report).unwrap_or_default())?;
+
+    let mut config = config::load_app_config()
+        .ok()
+        .and_then(|c| serde_json::to_value(c).ok())
+        .unwrap_or(Value::Null);
+    redact_json(&mut config);
+    add("config.redacted.json", &serde_json::to_string_pretty(&config).unwrap_or_default())?;
+
+    if let Ok(log_dir) = logger::get_log_dir() {
+        for log in recent_log_files(&log_dir, BUNDLE_LOG_FILES) {
+            let Some(name) = log.file_name().and_then(|n| n.to_str()) else { continue };
+            match read_tail(&log, BUNDLE_LOG_TAIL_BYTES) {
+                Ok(content) => add(&format!("logs/{}", name), &redact_text(&content))?,
+                Err(e) => logger::log_warn(&format!("[Diagnostics] Skipping log {}: {}", name, e)),
+            }
+        }
+    }
+
+    zip.finish().map_err(|e| format!("Failed to finalize bundle: {}", e))?;
+    Ok(path)
+}
+
+/// Run the diagnostic suite; optionally write a redacted zip bundle for bug reports
+/// 按当前反代配置 (上游代理 / 出口池 / 端点) 构造上游客户端
+fn upstream_client(proxy_config: Option<&crate::proxy::config::ProxyConfig>) -> UpstreamClient {
+    let pool = proxy_config.map(|p| p.upstream_pool.clone()).unwrap_or_default();
+    let client = UpstreamClient::new(proxy_config.map(|p| p.upstream_proxy.clone()), &pool);
+    if let Some(p) = proxy_config {
+        client.set_endpoints(p.upstream_endpoints.clone());
+    }
+    client
+}
+
+/// 仅运行 Token 检查 + 端到端生成 (供首次启动向导使用)。没有可用账号时返回 Token 检查的失败结果
+pub async fn run_generation_test() -> DiagnosticCheck {
+    let proxy_config = config::load_app_config().ok().map(|c| c.proxy);
+    let client = upstream_client(proxy_config.as_ref());
+    match check_tokens().await {
+        (_, Some(credentials)) => check_generation(&client, Some(credentials)).await,
+        (tokens, None) => DiagnosticCheck { name: "generation".to_string(), ..tokens },
+    }
+}
+
+pub async fn run_diagnostics(create_bundle: bool) -> Result<DiagnosticsReport, String> {
+    logger::log_info("[Diagnostics] Running self-test");
+    let proxy_config = config::load_app_config().ok().map(|c| c.proxy);
+    let client = upstream_client(proxy_config.as_ref());
+
+    let mut checks = Vec::new();
+    checks.push(check_port(proxy_config.as_ref().map(|p| p.port).unwrap_or(8045)).await);
+
+    let tls = check_tls(&client).await;
+    let endpoints: Vec<String> = tls.details.as_array().map(|probes| {
+        probes.iter().filter_map(|p| p.get("url").and_then(|u| u.as_str()).map(|u| u.to_string())).collect()
+    }).unwrap_or_default();
+    let mut hosts: Vec<String> = endpoints
+        .iter()
+        .filter_map(|u| url::Url::parse(u).ok()?.host_str().map(|h| h.to_string()))
+        .collect();
+    hosts.sort();
+    hosts.dedup();
+    checks.push(check_dns(&hosts).await);
+    checks.push(tls);
+
+    match endpoints.first() {
+        Some(url) => checks.push(check_clock_skew(url, proxy_config.as_ref().map(|p| &p.upstream_proxy)).await),
+        None => checks.push(check("clock_skew", Instant::now(), CheckStatus::Skipped, "No upstream endpoint configured", Value::Null)),
+    }
+
+    let (tokens, credentials) = check_tokens().await;
+    checks.push(tokens);
+    checks.push(check_generation(&client, credentials).await);
+
+    for c in checks.iter().filter(|c| matches!(c.status, CheckStatus::Warn | CheckStatus::Fail)) {
+        logger::log_warn(&format!("[Diagnostics] {} {:?}: {}", c.name, c.status, c.message));
+    }
+
+    let mut report = DiagnosticsReport {
+        generated_at: chrono::Utc::now().timestamp(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        checks,
+        bundle_path: None,
+    };
+    if create_bundle {
+        let path = write_bundle(&report)?;
+        logger::log_info(&format!("[Diagnostics] Bundle written to {}", path.display()));
+        report.bundle_path = Some(path.to_string_lossy().into_owned());
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_text_masks_tokens_and_emails() {
+        let line = "refresh for alice.smith@gmail.com token=ya29.a0AfB_xyz Authorization: Bearer sk-abcdef123456 rt 1//0gABCDEFGHIJKLMNOPQRSTUV";
+        let redacted = redact_text(line);
+        assert!(redacted.contains("a***@gmail.com"));
+        assert!(!redacted.contains("alice.smith"));
+        assert!(!redacted.contains("a0AfB_xyz"));
+        assert!(!redacted.contains("abcdef123456"));
+        assert!(!redacted.contains("0gABCDEFGHIJKLMNOPQRSTUV"));
+    }
+
+    #[test]
+    fn test_redact_json_replaces_secret_fields() {
+        let mut config = json!({
+            "proxy": {
+                "api_key": "sk-local",
+                "port": 8045,
+                "auth_mode": "off",
+                "zai": { "api_key": "", "enabled": false },
+                "token_budget": { "key_limits": { "sk-team": 100 } },
+                "allowed_keys": ["sk-a", "sk-b"]
+            },
+            "note": "owner bob@example.com"
+        });
+        redact_json(&mut config);
+        assert_eq!(config["proxy"]["api_key"], "[REDACTED]");
+        assert_eq!(config["proxy"]["port"], 8045);
+        assert_eq!(config["proxy"]["zai"]["api_key"], "");
+        assert_eq!(config["proxy"]["token_budget"]["key_limits"], json!({ "[REDACTED-1]": 100 }));
+        assert_eq!(config["proxy"]["allowed_keys"], json!(["[REDACTED]", "[REDACTED]"]));
+        assert_eq!(config["note"], "owner b***@example.com");
+    }
+}
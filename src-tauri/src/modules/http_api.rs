@@ -21,6 +21,7 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tower_http::cors::{Any, CorsLayer};
 
+use crate::models::{Account, AccountState};
 use crate::modules::{account, logger, proxy_db};
 
 /// Default port for HTTP API server
@@ -120,6 +121,8 @@ struct AccountResponse {
     name: Option<String>,
     is_current: bool,
     disabled: bool,
+    /// active / paused / cooldown / disabled / error (不含反代运行时的限流冷却)
+    state: AccountState,
     quota: Option<QuotaResponse>,
     device_bound: bool,
     last_used: i64,
@@ -232,6 +235,11 @@ async fn health() -> impl IntoResponse {
     })
 }
 
+fn account_state(acc: &Account) -> AccountState {
+    let circuit_open = crate::proxy::circuit_breaker::CircuitBreakers::global().is_account_open(&acc.email);
+    acc.status(chrono::Utc::now().timestamp(), None, circuit_open).state
+}
+
 /// GET /accounts - Get all accounts
 async fn list_accounts() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let accounts = account::list_accounts().map_err(|e| {
@@ -249,6 +257,7 @@ async fn list_accounts() -> Result<impl IntoResponse, (StatusCode, Json<ErrorRes
         .into_iter()
         .map(|acc| {
             let is_current = current_id.as_ref().map(|id| id == &acc.id).unwrap_or(false);
+            let state = account_state(&acc);
             let quota = acc.quota.map(|q| QuotaResponse {
                 models: q.models.into_iter().map(|m| ModelQuota {
                     name: m.name,
@@ -265,6 +274,7 @@ async fn list_accounts() -> Result<impl IntoResponse, (StatusCode, Json<ErrorRes
                 name: acc.name,
                 is_current,
                 disabled: acc.disabled,
+                state,
                 quota,
                 device_bound: acc.device_profile.is_some(),
                 last_used: acc.last_used,
@@ -288,6 +298,7 @@ async fn get_current_account() -> Result<impl IntoResponse, (StatusCode, Json<Er
     })?;
 
     let response = current.map(|acc| {
+        let state = account_state(&acc);
         let quota = acc.quota.map(|q| QuotaResponse {
             models: q.models.into_iter().map(|m| ModelQuota {
                 name: m.name,
@@ -304,6 +315,7 @@ async fn get_current_account() -> Result<impl IntoResponse, (StatusCode, Json<Er
             name: acc.name,
             is_current: true,
             disabled: acc.disabled,
+            state,
             quota,
             device_bound: acc.device_profile.is_some(),
             last_used: acc.last_used,
@@ -444,6 +456,19 @@ async fn get_logs(
     }))
 }
 
+/// GET /logs/search - Full-text log search with filters and aggregate counts
+async fn search_logs(
+    Query(query): Query<proxy_db::LogQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let result = tokio::task::spawn_blocking(move || proxy_db::query_logs(&query))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|r| r)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
+
+    Ok(Json(result))
+}
+
 // ============================================================================
 // Server
 // ============================================================================
@@ -466,6 +491,7 @@ pub async fn start_server(port: u16) -> Result<(), String> {
         .route("/accounts/refresh", post(refresh_all_quotas))
         .route("/accounts/{id}/bind-device", post(bind_device))
         .route("/logs", get(get_logs))
+        .route("/logs/search", get(search_logs))
         .layer(cors)
         .with_state(state);
 
@@ -13,6 +13,17 @@ pub struct TrayTexts {
     pub no_account: String,
     pub unknown_quota: String,
     pub forbidden: String,
+    pub proxy_running: String,
+    pub proxy_stopped: String,
+    pub start_proxy: String,
+    pub stop_proxy: String,
+    pub routing_mode: String,
+    pub mode_cache_first: String,
+    pub mode_balance: String,
+    pub mode_performance_first: String,
+    pub mode_fairness: String,
+    pub pause_account: String,
+    pub requests_per_minute: String,
 }
 
 /// Load translations from JSON
@@ -52,5 +63,16 @@ pub fn get_tray_texts(lang: &str) -> TrayTexts {
         no_account: t.get("no_account").cloned().unwrap_or_else(|| "No Account".to_string()),
         unknown_quota: t.get("unknown_quota").cloned().unwrap_or_else(|| "Unknown".to_string()),
         forbidden: t.get("forbidden").cloned().unwrap_or_else(|| "Account Forbidden".to_string()),
+        proxy_running: t.get("proxy_running").cloned().unwrap_or_else(|| "Proxy: Running (:{port})".to_string()),
+        proxy_stopped: t.get("proxy_stopped").cloned().unwrap_or_else(|| "Proxy: Stopped".to_string()),
+        start_proxy: t.get("start_proxy").cloned().unwrap_or_else(|| "Start Proxy".to_string()),
+        stop_proxy: t.get("stop_proxy").cloned().unwrap_or_else(|| "Stop Proxy".to_string()),
+        routing_mode: t.get("routing_mode").cloned().unwrap_or_else(|| "Routing Mode".to_string()),
+        mode_cache_first: t.get("mode_cache_first").cloned().unwrap_or_else(|| "Cache First".to_string()),
+        mode_balance: t.get("mode_balance").cloned().unwrap_or_else(|| "Balance".to_string()),
+        mode_performance_first: t.get("mode_performance_first").cloned().unwrap_or_else(|| "Performance First".to_string()),
+        mode_fairness: t.get("mode_fairness").cloned().unwrap_or_else(|| "Fairness".to_string()),
+        pause_account: t.get("pause_account").cloned().unwrap_or_else(|| "Pause Account".to_string()),
+        requests_per_minute: t.get("requests_per_minute").cloned().unwrap_or_else(|| "{count} req/min".to_string()),
     }
 }
@@ -1,8 +1,18 @@
 use tracing::{info, warn, error};
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::{Format, Full, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use crate::modules::account::get_data_dir;
+use crate::proxy::config::LogFormat;
+
+/// 是否输出 JSON 格式 (运行时可切换，见 set_log_format)
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
 
 // Custom local timezone time formatter
 struct LocalTimer;
@@ -14,6 +24,106 @@ impl tracing_subscriber::fmt::time::FormatTime for LocalTimer {
     }
 }
 
+/// 切换运行日志格式，立即作用于控制台与文件输出
+pub fn set_log_format(format: LogFormat) {
+    let json = format == LogFormat::Json;
+    if JSON_OUTPUT.swap(json, Ordering::Relaxed) != json {
+        info!("Log format switched to {:?}", format);
+    }
+}
+
+pub fn log_format() -> LogFormat {
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        LogFormat::Json
+    } else {
+        LogFormat::Text
+    }
+}
+
+/// 收集事件字段
+struct JsonVisitor<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
+
+/// 单行 JSON 事件: timestamp / level / target / message + 事件字段，
+/// 在反代请求内输出时附加 request_id / account / model / latency_ms
+fn json_event(event: &Event<'_>, ctx: Option<crate::proxy::inflight::LogContext>) -> serde_json::Value {
+    let meta = event.metadata();
+    let mut map = serde_json::Map::new();
+    map.insert("timestamp".to_string(), chrono::Local::now().to_rfc3339().into());
+    map.insert("level".to_string(), meta.level().as_str().into());
+    map.insert("target".to_string(), meta.target().into());
+    event.record(&mut JsonVisitor(&mut map));
+    // log crate 转发的事件带有 log.* 元数据字段，对采集端无意义
+    map.retain(|k, _| !k.starts_with("log."));
+    if let Some(ctx) = ctx {
+        map.entry("request_id").or_insert(ctx.request_id.into());
+        if let Some(account) = ctx.account {
+            map.entry("account").or_insert(account.into());
+        }
+        if let Some(model) = ctx.model {
+            map.entry("model").or_insert(model.into());
+        }
+        map.entry("latency_ms").or_insert(ctx.latency_ms.into());
+    }
+    serde_json::Value::Object(map)
+}
+
+/// 按 JSON_OUTPUT 在文本与 JSON 之间切换的事件格式
+struct SwitchableFormat {
+    text: Format<Full, LocalTimer>,
+}
+
+impl SwitchableFormat {
+    fn new(with_target: bool) -> Self {
+        Self {
+            text: fmt::format()
+                .with_target(with_target)
+                .with_thread_ids(false)
+                .with_level(true)
+                .with_timer(LocalTimer),
+        }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for SwitchableFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> std::fmt::Result {
+        if !JSON_OUTPUT.load(Ordering::Relaxed) {
+            return self.text.format_event(ctx, writer, event);
+        }
+        let line = json_event(event, crate::proxy::inflight::current_log_context());
+        writeln!(writer, "{}", line)
+    }
+}
+
 pub fn get_log_dir() -> Result<PathBuf, String> {
     let data_dir = get_data_dir()?;
     let log_dir = data_dir.join("logs");
@@ -43,30 +153,29 @@ pub fn init_logger() {
     let file_appender = tracing_appender::rolling::daily(log_dir, "app.log");
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
     
-    // 2. Console output layer (using local timezone)
+    // 2. Console output layer (using local timezone; text or JSON, switchable at runtime)
     let console_layer = fmt::Layer::new()
-        .with_target(false)
-        .with_thread_ids(false)
-        .with_level(true)
-        .with_timer(LocalTimer);
+        .event_format(SwitchableFormat::new(false));
         
     // 3. File output layer (disable ANSI formatting, use local timezone)
     let file_layer = fmt::Layer::new()
         .with_writer(non_blocking)
         .with_ansi(false)
-        .with_target(true)
-        .with_level(true)
-        .with_timer(LocalTimer);
+        .event_format(SwitchableFormat::new(true));
 
-    // 4. Set filtering layer (default to INFO level to reduce log size)
-    let filter_layer = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("info"));
+    // 4. Set filtering (default to INFO level to reduce log size).
+    // Filters are per-layer so that pipeline trace spans (target "ag_otel") stay out of console/file output.
+    let env_filter = || {
+        EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new("info"))
+            .add_directive(format!("{}=off", crate::proxy::telemetry::TARGET).parse().expect("valid directive"))
+    };
 
     // 5. Initialize global subscriber (use try_init to avoid crash on repeated initialization)
     let _ = tracing_subscriber::registry()
-        .with(filter_layer)
-        .with(console_layer)
-        .with(file_layer)
+        .with(console_layer.with_filter(env_filter()))
+        .with(file_layer.with_filter(env_filter()))
+        .with(crate::proxy::telemetry::layer())
         .try_init();
 
     // Leak _guard to ensure its lifetime lasts until program exit
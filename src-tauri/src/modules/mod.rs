@@ -15,6 +15,23 @@ pub mod update_checker;
 pub mod scheduler;
 pub mod http_api;
 pub mod token_stats;
+pub mod benchmark;
+pub mod cloud_sync;
+pub mod transcript;
+pub mod diagnostics;
+pub mod notifications;
+pub mod daily_report;
+pub mod capability_probe;
+pub mod model_limits;
+pub mod quirk_manifest;
+pub mod watchdog;
+pub mod background_service;
+pub mod setup_wizard;
+pub mod client_config;
+pub mod conversation_tree;
+pub mod config_overrides;
+pub mod retention;
+pub mod bulk_ops;
 
 use crate::models;
 
@@ -0,0 +1,115 @@
+// 模型上下文窗口 / 最大输出登记表的持久化
+// 远程清单下载后缓存到 model_limits_manifest.json，启动时即可使用，无需每次联网；
+// 用户覆盖规则保存在 proxy.experimental.model_limits.overrides 中。
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::modules::{account, config, logger};
+use crate::proxy::config::ModelLimitRule;
+use crate::proxy::mappers::model_limits;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ManifestCache {
+    url: String,
+    fetched_at: i64,
+    rules: Vec<ModelLimitRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelLimitsReport {
+    pub builtin: Vec<ModelLimitRule>,
+    pub manifest: Vec<ModelLimitRule>,
+    pub overrides: Vec<ModelLimitRule>,
+    /// 清单来源与下载时间 (未下载过时为空)
+    pub manifest_url: Option<String>,
+    pub fetched_at: Option<i64>,
+}
+
+fn cache_path() -> Result<PathBuf, String> {
+    Ok(account::get_data_dir()?.join("model_limits_manifest.json"))
+}
+
+fn load_cache() -> Option<ManifestCache> {
+    cache_path()
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str(&c).ok())
+}
+
+fn save_cache(cache: &ManifestCache) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(cache).map_err(|e| format!("序列化模型清单失败: {}", e))?;
+    std::fs::write(cache_path()?, content).map_err(|e| format!("保存模型清单失败: {}", e))
+}
+
+/// 启动时加载缓存的清单与配置中的覆盖规则
+pub fn init() {
+    if let Some(cache) = load_cache() {
+        logger::log_info(&format!("[Model Limits] 已加载 {} 条清单规则 ({})", cache.rules.len(), cache.url));
+        model_limits::set_manifest(cache.rules);
+    }
+    if let Ok(app_config) = config::load_app_config() {
+        model_limits::set_overrides(app_config.proxy.experimental.model_limits.overrides);
+    }
+}
+
+pub fn get_report() -> ModelLimitsReport {
+    let cache = load_cache();
+    let overrides = config::load_app_config()
+        .map(|c| c.proxy.experimental.model_limits.overrides)
+        .unwrap_or_default();
+    ModelLimitsReport {
+        builtin: model_limits::builtin_rules(),
+        manifest: cache.as_ref().map(|c| c.rules.clone()).unwrap_or_default(),
+        overrides,
+        manifest_url: cache.as_ref().map(|c| c.url.clone()),
+        fetched_at: cache.map(|c| c.fetched_at),
+    }
+}
+
+/// 保存覆盖规则并立即生效
+pub fn set_overrides(overrides: Vec<ModelLimitRule>) -> Result<ModelLimitsReport, String> {
+    if let Some(bad) = overrides.iter().find(|r| r.model.trim().is_empty()) {
+        return Err(format!("invalid override: empty model name ({:?})", bad));
+    }
+    let mut app_config = config::load_app_config()?;
+    app_config.proxy.experimental.model_limits.overrides = overrides.clone();
+    config::save_app_config(&app_config)?;
+    model_limits::set_overrides(overrides);
+    Ok(get_report())
+}
+
+/// 下载远程清单；url 为空时使用配置中的 manifest_url
+pub async fn refresh_manifest(url: Option<String>) -> Result<ModelLimitsReport, String> {
+    let url = match url.map(|u| u.trim().to_string()).filter(|u| !u.is_empty()) {
+        Some(url) => url,
+        None => config::load_app_config()?.proxy.experimental.model_limits.manifest_url.trim().to_string(),
+    };
+    if url.is_empty() {
+        return Err("No model limits manifest URL configured".to_string());
+    }
+
+    let client = reqwest::Client::builder()
+        .user_agent("Antigravity-Manager")
+        .timeout(std::time::Duration::from_secs(15))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch model limits manifest: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Manifest server returned status: {}", response.status()));
+    }
+    let manifest: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid model limits manifest: {}", e))?;
+    let rules = model_limits::parse_manifest(&manifest)?;
+
+    logger::log_info(&format!("[Model Limits] 已更新清单: {} 条规则 ({})", rules.len(), url));
+    save_cache(&ManifestCache { url, fetched_at: chrono::Utc::now().timestamp(), rules: rules.clone() })?;
+    model_limits::set_manifest(rules);
+    Ok(get_report())
+}
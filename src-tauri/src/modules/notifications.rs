@@ -0,0 +1,280 @@
+// 账号事件通知 (Webhook + 桌面通知)
+// 配额耗尽 / Token 刷新失败 / 全部账号不可用时，按模板向 ntfy / Discord / Slack 等 Webhook 推送，
+// 并可弹出系统原生通知。同一事件 + 账号在冷却时间内只通知一次，避免 429 风暴时刷屏。
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+use crate::models::{NotificationConfig, WebhookConfig};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    QuotaExhausted,
+    TokenRefreshFailed,
+    AllAccountsUnhealthy,
+    /// 设置页的 "发送测试" (不受冷却与事件过滤影响)
+    Test,
+}
+
+impl NotificationEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::QuotaExhausted => "quota_exhausted",
+            Self::TokenRefreshFailed => "token_refresh_failed",
+            Self::AllAccountsUnhealthy => "all_accounts_unhealthy",
+            Self::Test => "test",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Self::QuotaExhausted => "Account quota exhausted",
+            Self::TokenRefreshFailed => "Token refresh failed",
+            Self::AllAccountsUnhealthy => "All accounts unavailable",
+            Self::Test => "Test notification",
+        }
+    }
+}
+
+struct Notifier {
+    app: OnceLock<AppHandle>,
+    config: RwLock<Option<NotificationConfig>>,
+    last_sent: Mutex<HashMap<(NotificationEvent, String), Instant>>,
+}
+
+fn notifier() -> &'static Notifier {
+    static INSTANCE: OnceLock<Notifier> = OnceLock::new();
+    INSTANCE.get_or_init(|| Notifier {
+        app: OnceLock::new(),
+        config: RwLock::new(None),
+        last_sent: Mutex::new(HashMap::new()),
+    })
+}
+
+/// 应用启动时注入 AppHandle (桌面通知需要)
+pub fn init(app: AppHandle) {
+    let _ = notifier().app.set(app);
+}
+
+/// 保存设置后刷新缓存的通知配置
+pub fn update_config(config: NotificationConfig) {
+    if let Ok(mut guard) = notifier().config.write() {
+        *guard = Some(config);
+    }
+}
+
+fn current_config() -> NotificationConfig {
+    if let Some(config) = notifier().config.read().ok().and_then(|c| c.clone()) {
+        return config;
+    }
+    let config = crate::modules::config::load_app_config()
+        .map(|c| c.notifications)
+        .unwrap_or_default();
+    update_config(config.clone());
+    config
+}
+
+/// 冷却检查: 冷却期内返回 false，否则记录本次发送时间
+fn should_send(last_sent: &mut HashMap<(NotificationEvent, String), Instant>, event: NotificationEvent, subject: &str, cooldown: Duration) -> bool {
+    let key = (event, subject.to_string());
+    let now = Instant::now();
+    if let Some(last) = last_sent.get(&key) {
+        if now.duration_since(*last) < cooldown {
+            return false;
+        }
+    }
+    last_sent.insert(key, now);
+    true
+}
+
+fn webhook_wants(webhook: &WebhookConfig, event: NotificationEvent) -> bool {
+    webhook.enabled
+        && !webhook.url.trim().is_empty()
+        && (event == NotificationEvent::Test || webhook.events.is_empty() || webhook.events.iter().any(|e| e == event.as_str()))
+}
+
+/// 渲染模板: 占位符替换为 JSON 转义后的值，便于直接写在 JSON 字符串字面量中
+fn render_template(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = template.to_string();
+    for (name, value) in vars {
+        let escaped = serde_json::to_string(value).unwrap_or_default();
+        let escaped = &escaped[1..escaped.len().saturating_sub(1)];
+        out = out.replace(&format!("{{{{{}}}}}", name), escaped);
+    }
+    out
+}
+
+fn build_payload(webhook: &WebhookConfig, event: NotificationEvent, account: Option<&str>, message: &str, timestamp: &str) -> (String, &'static str) {
+    if webhook.template.trim().is_empty() {
+        let payload = json!({
+            "event": event.as_str(),
+            "title": event.title(),
+            "message": message,
+            "account": account,
+            "timestamp": timestamp,
+            "app": "Antigravity Tools",
+        });
+        return (payload.to_string(), "application/json");
+    }
+    let body = render_template(
+        &webhook.template,
+        &[
+            ("event", event.as_str()),
+            ("title", event.title()),
+            ("message", message),
+            ("account", account.unwrap_or("")),
+            ("timestamp", timestamp),
+        ],
+    );
+    // 非 JSON 模板 (如 ntfy 纯文本) 以 text/plain 发送
+    let content_type = if serde_json::from_str::<Value>(&body).is_ok() { "application/json" } else { "text/plain" };
+    (body, content_type)
+}
+
+async fn post_webhook(client: &reqwest::Client, webhook: &WebhookConfig, body: String, content_type: &str) -> Result<(), String> {
+    let mut request = client
+        .post(webhook.url.trim())
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body);
+    for (name, value) in &webhook.headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request.send().await.map_err(|e| format!("Webhook '{}' request failed: {}", webhook.name, e))?;
+    if !response.status().is_success() {
+        return Err(format!("Webhook '{}' returned HTTP {}", webhook.name, response.status()));
+    }
+    Ok(())
+}
+
+/// 弹出系统原生通知 (不受事件过滤与冷却影响，供日报等定时任务直接调用)
+pub fn show_desktop_message(title: &str, body: &str) {
+    use tauri_plugin_notification::NotificationExt;
+    if let Some(app) = notifier().app.get() {
+        if let Err(e) = app.notification().builder().title(title).body(body).show() {
+            tracing::debug!("[Notify] 桌面通知失败: {}", e);
+        }
+    }
+}
+
+fn show_desktop(event: NotificationEvent, message: &str) {
+    show_desktop_message(event.title(), message);
+}
+
+async fn dispatch(config: NotificationConfig, event: NotificationEvent, account: Option<String>, message: String) -> Result<usize, String> {
+    if config.desktop {
+        show_desktop(event, &message);
+    }
+    let targets: Vec<&WebhookConfig> = config.webhooks.iter().filter(|w| webhook_wants(w, event)).collect();
+    if targets.is_empty() {
+        return Ok(0);
+    }
+    let client = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let mut errors = Vec::new();
+    for webhook in &targets {
+        let (body, content_type) = build_payload(webhook, event, account.as_deref(), &message, &timestamp);
+        if let Err(e) = post_webhook(&client, webhook, body, content_type).await {
+            tracing::warn!("[Notify] {}", e);
+            errors.push(e);
+        }
+    }
+    if errors.is_empty() {
+        Ok(targets.len())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+/// 触发事件通知 (异步发送，不阻塞调用方)
+pub fn notify(event: NotificationEvent, account: Option<&str>, message: impl Into<String>) {
+    let config = current_config();
+    if !config.desktop && !config.webhooks.iter().any(|w| webhook_wants(w, event)) {
+        return;
+    }
+    let subject = account.unwrap_or("*");
+    let allowed = notifier()
+        .last_sent
+        .lock()
+        .map(|mut last| should_send(&mut last, event, subject, Duration::from_secs(config.cooldown_secs)))
+        .unwrap_or(true);
+    if !allowed {
+        return;
+    }
+    let account = account.map(|a| a.to_string());
+    let message = message.into();
+    tracing::info!("[Notify] {}: {}", event.as_str(), message);
+    tauri::async_runtime::spawn(async move {
+        let _ = dispatch(config, event, account, message).await;
+    });
+}
+
+/// 发送测试通知 (设置页)，返回成功推送的 Webhook 数量
+pub async fn send_test(config: NotificationConfig) -> Result<usize, String> {
+    dispatch(
+        config,
+        NotificationEvent::Test,
+        Some("test@example.com".to_string()),
+        "Notifications from Antigravity Tools are working.".to_string(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(events: &[&str], template: &str) -> WebhookConfig {
+        WebhookConfig {
+            enabled: true,
+            name: "test".to_string(),
+            url: "https://example.com/hook".to_string(),
+            events: events.iter().map(|e| e.to_string()).collect(),
+            template: template.to_string(),
+            headers: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_template_values_are_json_escaped() {
+        let hook = webhook(&[], r#"{"content": "**{{title}}** {{account}}: {{message}}"}"#);
+        let (body, content_type) = build_payload(
+            &hook,
+            NotificationEvent::TokenRefreshFailed,
+            Some("a@b.com"),
+            "invalid_grant: \"revoked\"\nline2",
+            "2026-01-01T00:00:00Z",
+        );
+        assert_eq!(content_type, "application/json");
+        let parsed: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(parsed["content"], "**Token refresh failed** a@b.com: invalid_grant: \"revoked\"\nline2");
+
+        let (body, content_type) = build_payload(&webhook(&[], "{{title}}: {{message}}"), NotificationEvent::QuotaExhausted, None, "x", "t");
+        assert_eq!(content_type, "text/plain");
+        assert_eq!(body, "Account quota exhausted: x");
+    }
+
+    #[test]
+    fn test_event_filter_and_cooldown() {
+        let hook = webhook(&["quota_exhausted"], "");
+        assert!(webhook_wants(&hook, NotificationEvent::QuotaExhausted));
+        assert!(!webhook_wants(&hook, NotificationEvent::AllAccountsUnhealthy));
+        assert!(webhook_wants(&hook, NotificationEvent::Test));
+
+        let mut last = HashMap::new();
+        let cooldown = Duration::from_secs(600);
+        assert!(should_send(&mut last, NotificationEvent::QuotaExhausted, "a", cooldown));
+        assert!(!should_send(&mut last, NotificationEvent::QuotaExhausted, "a", cooldown));
+        assert!(should_send(&mut last, NotificationEvent::QuotaExhausted, "b", cooldown));
+        assert!(should_send(&mut last, NotificationEvent::TokenRefreshFailed, "a", cooldown));
+    }
+}
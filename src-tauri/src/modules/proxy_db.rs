@@ -1,8 +1,21 @@
 use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use crate::proxy::monitor::ProxyRequestLog;
 
+/// 请求历史数据库位置覆盖 (state_store.history_db_path，如挂载卷上的文件)
+static DB_PATH_OVERRIDE: std::sync::RwLock<Option<PathBuf>> = std::sync::RwLock::new(None);
+
+pub fn set_db_path_override(path: Option<PathBuf>) {
+    if let Ok(mut current) = DB_PATH_OVERRIDE.write() {
+        *current = path;
+    }
+}
+
 pub fn get_proxy_db_path() -> Result<PathBuf, String> {
+    if let Some(path) = DB_PATH_OVERRIDE.read().ok().and_then(|p| p.clone()) {
+        return Ok(path);
+    }
     let data_dir = crate::modules::account::get_data_dir()?;
     Ok(data_dir.join("proxy_logs.db"))
 }
@@ -26,7 +39,10 @@ fn connect_db() -> Result<Connection, String> {
 pub fn init_db() -> Result<(), String> {
     // connect_db will initialize WAL mode and other pragmas
     let conn = connect_db()?;
-    
+    init_schema(&conn)
+}
+
+fn init_schema(conn: &Connection) -> Result<(), String> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS request_logs (
             id TEXT PRIMARY KEY,
@@ -49,6 +65,16 @@ pub fn init_db() -> Result<(), String> {
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN account_email TEXT", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN mapped_model TEXT", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN protocol TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN session_id TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN notes TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN tags TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN ttft_ms INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN tokens_per_sec REAL", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN chunk_count INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN message_id TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN parent_message_id TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN error_code TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN error_hint TEXT", []);
 
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_timestamp ON request_logs (timestamp DESC)",
@@ -61,6 +87,86 @@ pub fn init_db() -> Result<(), String> {
         [],
     ).map_err(|e| e.to_string())?;
 
+    // 按会话分组查询
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_session ON request_logs (session_id, timestamp)",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    // 会话树: 按消息节点查询分支
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_message ON request_logs (session_id, message_id)",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    // A/B 实验结果 (与请求日志独立，监控关闭时同样记录)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ab_results (
+            id TEXT PRIMARY KEY,
+            timestamp INTEGER,
+            experiment TEXT,
+            arm TEXT,
+            alias TEXT,
+            model TEXT,
+            status INTEGER,
+            duration INTEGER,
+            first_byte_ms INTEGER,
+            output_chars INTEGER,
+            output_tokens INTEGER,
+            finish_reason TEXT
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_ab_experiment ON ab_results (experiment, timestamp)",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    init_search_index(conn)
+}
+
+/// 日志全文索引: FTS5 外部内容表，内容仍只存一份在 request_logs 中，由触发器同步。
+/// trigram 分词支持任意子串与中日韩文本 (检索词至少 3 个字符)。
+fn init_search_index(conn: &Connection) -> Result<(), String> {
+    let existed: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'request_logs_fts'",
+        [],
+        |row| row.get(0),
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS request_logs_fts USING fts5(
+            request_body, response_body, error, notes, tags,
+            content='request_logs', content_rowid='rowid', tokenize='trigram'
+        );
+        CREATE TRIGGER IF NOT EXISTS request_logs_fts_ai AFTER INSERT ON request_logs BEGIN
+            INSERT INTO request_logs_fts (rowid, request_body, response_body, error, notes, tags)
+            VALUES (new.rowid, new.request_body, new.response_body, new.error, new.notes, new.tags);
+        END;
+        CREATE TRIGGER IF NOT EXISTS request_logs_fts_ad AFTER DELETE ON request_logs BEGIN
+            INSERT INTO request_logs_fts (request_logs_fts, rowid, request_body, response_body, error, notes, tags)
+            VALUES ('delete', old.rowid, old.request_body, old.response_body, old.error, old.notes, old.tags);
+        END;
+        CREATE TRIGGER IF NOT EXISTS request_logs_fts_au AFTER UPDATE ON request_logs BEGIN
+            INSERT INTO request_logs_fts (request_logs_fts, rowid, request_body, response_body, error, notes, tags)
+            VALUES ('delete', old.rowid, old.request_body, old.response_body, old.error, old.notes, old.tags);
+            INSERT INTO request_logs_fts (rowid, request_body, response_body, error, notes, tags)
+            VALUES (new.rowid, new.request_body, new.response_body, new.error, new.notes, new.tags);
+        END;"
+    ).map_err(|e| e.to_string())?;
+
+    // 首次创建时为已有日志建立索引
+    if existed == 0 {
+        rebuild_search_index(conn)?;
+    }
+    Ok(())
+}
+
+/// 按 request_logs 当前内容重建全文索引。
+/// request_logs 没有 INTEGER PRIMARY KEY，VACUUM 可能重排 rowid，因此每次 VACUUM 后都要重建。
+fn rebuild_search_index(conn: &Connection) -> Result<(), String> {
+    conn.execute("INSERT INTO request_logs_fts (request_logs_fts) VALUES ('rebuild')", [])
+        .map_err(|e| e.to_string())?;
     Ok(())
 }
 
@@ -68,8 +174,8 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
     let conn = connect_db()?;
 
     conn.execute(
-        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model, protocol)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model, protocol, session_id, notes, tags, ttft_ms, tokens_per_sec, chunk_count, message_id, parent_message_id, error_code, error_hint)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25)",
         params![
             log.id,
             log.timestamp,
@@ -86,6 +192,16 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
             log.account_email,
             log.mapped_model,
             log.protocol,
+            log.session_id,
+            log.notes,
+            log.tags,
+            log.ttft_ms,
+            log.tokens_per_sec,
+            log.chunk_count,
+            log.message_id,
+            log.parent_message_id,
+            log.error_code,
+            log.error_hint,
         ],
     ).map_err(|e| e.to_string())?;
 
@@ -99,7 +215,8 @@ pub fn get_logs_summary(limit: usize, offset: usize) -> Result<Vec<ProxyRequestL
     let mut stmt = conn.prepare(
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol
+                input_tokens, output_tokens, account_email, mapped_model, protocol, session_id, notes, tags, ttft_ms, tokens_per_sec, chunk_count,
+                message_id, parent_message_id, error_code, error_hint
          FROM request_logs 
          ORDER BY timestamp DESC 
          LIMIT ?1 OFFSET ?2"
@@ -122,6 +239,16 @@ pub fn get_logs_summary(limit: usize, offset: usize) -> Result<Vec<ProxyRequestL
             input_tokens: row.get(10).unwrap_or(None),
             output_tokens: row.get(11).unwrap_or(None),
             protocol: row.get(14).unwrap_or(None),
+            session_id: row.get(15).unwrap_or(None),
+            notes: row.get(16).unwrap_or(None),
+            tags: row.get(17).unwrap_or(None),
+            ttft_ms: row.get(18).unwrap_or(None),
+            tokens_per_sec: row.get(19).unwrap_or(None),
+            chunk_count: row.get(20).unwrap_or(None),
+            message_id: row.get(21).unwrap_or(None),
+            parent_message_id: row.get(22).unwrap_or(None),
+            error_code: row.get(23).unwrap_or(None),
+            error_hint: row.get(24).unwrap_or(None),
         })
     }).map_err(|e| e.to_string())?;
 
@@ -158,6 +285,28 @@ pub fn get_stats() -> Result<crate::proxy::monitor::ProxyStats, String> {
     })
 }
 
+/// Error counts per account between two timestamps (milliseconds, end exclusive)
+pub fn get_error_counts_between(start_ms: i64, end_ms: i64) -> Result<Vec<(String, u64)>, String> {
+    let conn = connect_db()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(account_email, ''), COUNT(*) as errors
+         FROM request_logs
+         WHERE timestamp >= ?1 AND timestamp < ?2 AND (status < 200 OR status >= 400)
+         GROUP BY account_email
+         ORDER BY errors DESC"
+    ).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map([start_ms, end_ms], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        result.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(result)
+}
+
 /// Get single log detail (with request_body and response_body)
 pub fn get_log_detail(log_id: &str) -> Result<ProxyRequestLog, String> {
     let conn = connect_db()?;
@@ -165,7 +314,8 @@ pub fn get_log_detail(log_id: &str) -> Result<ProxyRequestLog, String> {
     let mut stmt = conn.prepare(
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 request_body, response_body, input_tokens, output_tokens, 
-                account_email, mapped_model, protocol
+                account_email, mapped_model, protocol, session_id, notes, tags, ttft_ms, tokens_per_sec, chunk_count,
+                message_id, parent_message_id, error_code, error_hint
          FROM request_logs 
          WHERE id = ?1"
     ).map_err(|e| e.to_string())?;
@@ -187,41 +337,266 @@ pub fn get_log_detail(log_id: &str) -> Result<ProxyRequestLog, String> {
             input_tokens: row.get(10).unwrap_or(None),
             output_tokens: row.get(11).unwrap_or(None),
             protocol: row.get(14).unwrap_or(None),
+            session_id: row.get(15).unwrap_or(None),
+            notes: row.get(16).unwrap_or(None),
+            tags: row.get(17).unwrap_or(None),
+            ttft_ms: row.get(18).unwrap_or(None),
+            tokens_per_sec: row.get(19).unwrap_or(None),
+            chunk_count: row.get(20).unwrap_or(None),
+            message_id: row.get(21).unwrap_or(None),
+            parent_message_id: row.get(22).unwrap_or(None),
+            error_code: row.get(23).unwrap_or(None),
+            error_hint: row.get(24).unwrap_or(None),
         })
     }).map_err(|e| e.to_string())
 }
 
-/// Cleanup old logs (keep last N days)
-pub fn cleanup_old_logs(days: i64) -> Result<usize, String> {
+/// [NEW] 获取同一会话 (conversation fingerprint) 的全部日志, 按时间正序, 含完整请求/响应体
+pub fn get_logs_by_session(session_id: &str) -> Result<Vec<ProxyRequestLog>, String> {
     let conn = connect_db()?;
-    
-    let cutoff_timestamp = chrono::Utc::now().timestamp() - (days * 24 * 3600);
-    
-    let deleted = conn.execute(
-        "DELETE FROM request_logs WHERE timestamp < ?1",
-        [cutoff_timestamp],
+
+    let mut stmt = conn.prepare(
+        "SELECT id, timestamp, method, url, status, duration, model, error,
+                request_body, response_body, input_tokens, output_tokens,
+                account_email, mapped_model, protocol, session_id, notes, tags, ttft_ms, tokens_per_sec, chunk_count,
+                message_id, parent_message_id, error_code, error_hint
+         FROM request_logs
+         WHERE session_id = ?1
+         ORDER BY timestamp ASC"
     ).map_err(|e| e.to_string())?;
-    
-    // Execute VACUUM to reclaim disk space
-    conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
-    
-    Ok(deleted)
+
+    let logs_iter = stmt.query_map([session_id], |row| {
+        Ok(ProxyRequestLog {
+            id: row.get(0)?,
+            timestamp: row.get(1)?,
+            method: row.get(2)?,
+            url: row.get(3)?,
+            status: row.get(4)?,
+            duration: row.get(5)?,
+            model: row.get(6)?,
+            mapped_model: row.get(13).unwrap_or(None),
+            account_email: row.get(12).unwrap_or(None),
+            error: row.get(7)?,
+            request_body: row.get(8).unwrap_or(None),
+            response_body: row.get(9).unwrap_or(None),
+            input_tokens: row.get(10).unwrap_or(None),
+            output_tokens: row.get(11).unwrap_or(None),
+            protocol: row.get(14).unwrap_or(None),
+            session_id: row.get(15).unwrap_or(None),
+            notes: row.get(16).unwrap_or(None),
+            tags: row.get(17).unwrap_or(None),
+            ttft_ms: row.get(18).unwrap_or(None),
+            tokens_per_sec: row.get(19).unwrap_or(None),
+            chunk_count: row.get(20).unwrap_or(None),
+            message_id: row.get(21).unwrap_or(None),
+            parent_message_id: row.get(22).unwrap_or(None),
+            error_code: row.get(23).unwrap_or(None),
+            error_hint: row.get(24).unwrap_or(None),
+        })
+    }).map_err(|e| e.to_string())?;
+
+    let mut logs = Vec::new();
+    for log in logs_iter {
+        logs.push(log.map_err(|e| e.to_string())?);
+    }
+    Ok(logs)
 }
 
-/// Limit maximum log count (keep newest N records)
-#[allow(dead_code)]
-pub fn limit_max_logs(max_count: usize) -> Result<usize, String> {
+/// 会话树节点 (仅含已计算 message_id 的日志)，按时间正序
+pub fn get_session_nodes(session_id: &str) -> Result<Vec<crate::modules::conversation_tree::NodeRow>, String> {
     let conn = connect_db()?;
-    
-    let deleted = conn.execute(
-        "DELETE FROM request_logs WHERE id NOT IN (
-            SELECT id FROM request_logs ORDER BY timestamp DESC LIMIT ?1
-        )",
-        [max_count],
+
+    let mut stmt = conn.prepare(
+        "SELECT id, timestamp, model, status, message_id, parent_message_id
+         FROM request_logs
+         WHERE session_id = ?1 AND message_id IS NOT NULL
+         ORDER BY timestamp ASC"
     ).map_err(|e| e.to_string())?;
-    
+
+    let rows = stmt.query_map([session_id], |row| {
+        Ok(crate::modules::conversation_tree::NodeRow {
+            log_id: row.get(0)?,
+            timestamp: row.get(1)?,
+            model: row.get(2).unwrap_or(None),
+            status: row.get(3)?,
+            message_id: row.get(4)?,
+            parent_message_id: row.get(5).unwrap_or(None),
+        })
+    }).map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        result.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(result)
+}
+
+/// 删除会话中指定节点的全部日志，返回删除条数
+pub fn delete_logs_by_messages(session_id: &str, message_ids: &[String]) -> Result<usize, String> {
+    if message_ids.is_empty() {
+        return Ok(0);
+    }
+    let conn = connect_db()?;
+
+    let placeholders: Vec<String> = (0..message_ids.len()).map(|i| format!("?{}", i + 2)).collect();
+    let sql = format!(
+        "DELETE FROM request_logs WHERE session_id = ?1 AND message_id IN ({})",
+        placeholders.join(", ")
+    );
+    let mut params: Vec<&dyn rusqlite::ToSql> = vec![&session_id];
+    params.extend(message_ids.iter().map(|s| s as &dyn rusqlite::ToSql));
+    conn.execute(&sql, params.as_slice()).map_err(|e| e.to_string())
+}
+
+pub fn save_ab_result(result: &crate::proxy::ab_test::AbResult) -> Result<(), String> {
+    let conn = connect_db()?;
+    conn.execute(
+        "INSERT INTO ab_results (id, timestamp, experiment, arm, alias, model, status, duration, first_byte_ms, output_chars, output_tokens, finish_reason)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            result.id,
+            result.timestamp,
+            result.experiment,
+            result.arm,
+            result.alias,
+            result.model,
+            result.status,
+            result.duration,
+            result.first_byte_ms,
+            result.output_chars,
+            result.output_tokens,
+            result.finish_reason,
+        ],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 读取 A/B 实验结果，experiment 为空时返回全部实验
+pub fn get_ab_results(experiment: Option<&str>) -> Result<Vec<crate::proxy::ab_test::AbResult>, String> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, timestamp, experiment, arm, alias, model, status, duration, first_byte_ms, output_chars, output_tokens, finish_reason
+         FROM ab_results
+         WHERE ?1 IS NULL OR experiment = ?1
+         ORDER BY timestamp ASC"
+    ).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map(params![experiment], |row| {
+        Ok(crate::proxy::ab_test::AbResult {
+            id: row.get(0)?,
+            timestamp: row.get(1)?,
+            experiment: row.get(2)?,
+            arm: row.get(3)?,
+            alias: row.get(4)?,
+            model: row.get(5)?,
+            status: row.get(6)?,
+            duration: row.get(7)?,
+            first_byte_ms: row.get(8).unwrap_or(None),
+            output_chars: row.get(9)?,
+            output_tokens: row.get(10).unwrap_or(None),
+            finish_reason: row.get(11).unwrap_or(None),
+        })
+    }).map_err(|e| e.to_string())?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(results)
+}
+
+pub fn clear_ab_results(experiment: Option<&str>) -> Result<usize, String> {
+    let conn = connect_db()?;
+    conn.execute(
+        "DELETE FROM ab_results WHERE ?1 IS NULL OR experiment = ?1",
+        params![experiment],
+    ).map_err(|e| e.to_string())
+}
+
+/// 单次裁剪中按数据库大小删除的最多轮数
+const MAX_SIZE_PRUNE_PASSES: usize = 5;
+
+/// 数据库实际占用字节数 (不含空闲页)
+fn used_bytes(conn: &Connection) -> Result<u64, String> {
+    conn.query_row(
+        "SELECT (c.page_count - f.freelist_count) * s.page_size FROM pragma_page_count() c, pragma_freelist_count() f, pragma_page_size() s",
+        [],
+        |row| row.get::<_, i64>(0),
+    ).map(|n| n.max(0) as u64).map_err(|e| e.to_string())
+}
+
+fn delete_oldest_logs(conn: &Connection, count: u64) -> Result<usize, String> {
+    conn.execute(
+        "DELETE FROM request_logs WHERE rowid IN (SELECT rowid FROM request_logs ORDER BY timestamp ASC LIMIT ?1)",
+        [count as i64],
+    ).map_err(|e| e.to_string())
+}
+
+fn prune_logs_on(conn: &Connection, now_ms: i64, max_age_days: u32, max_rows: u64, max_bytes: u64) -> Result<usize, String> {
+    let mut deleted = 0;
+
+    if max_age_days > 0 {
+        let cutoff = now_ms - max_age_days as i64 * 24 * 3600 * 1000;
+        deleted += conn.execute("DELETE FROM request_logs WHERE timestamp < ?1", [cutoff])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM ab_results WHERE timestamp < ?1", [cutoff])
+            .map_err(|e| e.to_string())?;
+    }
+
+    if max_rows > 0 {
+        deleted += conn.execute(
+            "DELETE FROM request_logs WHERE rowid IN (
+                SELECT rowid FROM request_logs ORDER BY timestamp DESC LIMIT -1 OFFSET ?1
+            )",
+            [max_rows as i64],
+        ).map_err(|e| e.to_string())?;
+    }
+
+    if max_bytes > 0 {
+        for _ in 0..MAX_SIZE_PRUNE_PASSES {
+            // 外部内容索引删除时只追加墓碑记录，重建后占用才准确
+            rebuild_search_index(conn)?;
+            let used = used_bytes(conn)?;
+            if used <= max_bytes {
+                break;
+            }
+            let rows: u64 = conn.query_row("SELECT COUNT(*) FROM request_logs", [], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            if rows == 0 {
+                break;
+            }
+            // 按超出比例估算删除条数，多删 5% 余量避免反复多轮
+            let excess = (used - max_bytes) as f64 / used as f64;
+            let count = ((rows as f64 * (excess + 0.05)).ceil() as u64).clamp(1, rows);
+            deleted += delete_oldest_logs(conn, count)?;
+        }
+    }
+
+    if deleted > 0 {
+        // Execute VACUUM to reclaim disk space
+        conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
+        rebuild_search_index(conn)?;
+    }
+    Ok(deleted)
+}
+
+/// 按保留策略裁剪请求历史 (各上限为 0 表示不限制)，返回删除条数与裁剪后的数据库大小
+pub fn prune_logs(max_age_days: u32, max_rows: u64, max_bytes: u64) -> Result<(usize, u64), String> {
+    let conn = connect_db()?;
+    let deleted = prune_logs_on(&conn, chrono::Utc::now().timestamp_millis(), max_age_days, max_rows, max_bytes)?;
+    Ok((deleted, used_bytes(&conn)?))
+}
+
+/// 安全清空请求历史与 A/B 结果: secure_delete 下删除 (释放的页以零覆盖)，
+/// 再 VACUUM 重写数据库文件并截断 WAL，返回删除的日志条数
+pub fn secure_wipe() -> Result<usize, String> {
+    let conn = connect_db()?;
+    conn.pragma_update(None, "secure_delete", true).map_err(|e| e.to_string())?;
+    let deleted = conn.execute("DELETE FROM request_logs", []).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM ab_results", []).map_err(|e| e.to_string())?;
     conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
-    
+    rebuild_search_index(&conn)?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);").map_err(|e| e.to_string())?;
     Ok(deleted)
 }
 
@@ -231,6 +606,13 @@ pub fn clear_logs() -> Result<(), String> {
     Ok(())
 }
 
+/// Flush the WAL into the main database file (used on shutdown)
+pub fn checkpoint() -> Result<(), String> {
+    let conn = connect_db()?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);").map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 /// Get total count of logs in database
 pub fn get_logs_count() -> Result<u64, String> {
     let conn = connect_db()?;
@@ -258,7 +640,7 @@ pub fn get_logs_count_filtered(filter: &str, errors_only: bool) -> Result<u64, S
         "SELECT COUNT(*) FROM request_logs"
     } else {
         "SELECT COUNT(*) FROM request_logs WHERE 
-            (url LIKE ?1 OR method LIKE ?1 OR model LIKE ?1 OR CAST(status AS TEXT) LIKE ?1)"
+            (url LIKE ?1 OR method LIKE ?1 OR model LIKE ?1 OR session_id LIKE ?1 OR tags LIKE ?1 OR CAST(status AS TEXT) LIKE ?1)"
     };
     
     let count: u64 = if filter.is_empty() && !errors_only {
@@ -283,7 +665,8 @@ pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset:
     let sql = if errors_only {
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol
+                input_tokens, output_tokens, account_email, mapped_model, protocol, session_id, notes, tags, ttft_ms, tokens_per_sec, chunk_count,
+                message_id, parent_message_id, error_code, error_hint
          FROM request_logs 
          WHERE (status < 200 OR status >= 400)
          ORDER BY timestamp DESC 
@@ -291,16 +674,18 @@ pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset:
     } else if filter.is_empty() {
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol
+                input_tokens, output_tokens, account_email, mapped_model, protocol, session_id, notes, tags, ttft_ms, tokens_per_sec, chunk_count,
+                message_id, parent_message_id, error_code, error_hint
          FROM request_logs 
          ORDER BY timestamp DESC 
          LIMIT ?1 OFFSET ?2"
     } else {
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol
+                input_tokens, output_tokens, account_email, mapped_model, protocol, session_id, notes, tags, ttft_ms, tokens_per_sec, chunk_count,
+                message_id, parent_message_id, error_code, error_hint
          FROM request_logs 
-         WHERE (url LIKE ?3 OR method LIKE ?3 OR model LIKE ?3 OR CAST(status AS TEXT) LIKE ?3)
+         WHERE (url LIKE ?3 OR method LIKE ?3 OR model LIKE ?3 OR session_id LIKE ?3 OR tags LIKE ?3 OR CAST(status AS TEXT) LIKE ?3)
          ORDER BY timestamp DESC 
          LIMIT ?1 OFFSET ?2"
     };
@@ -324,6 +709,16 @@ pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset:
                 input_tokens: row.get(10).unwrap_or(None),
                 output_tokens: row.get(11).unwrap_or(None),
                 protocol: row.get(14).unwrap_or(None),
+                session_id: row.get(15).unwrap_or(None),
+                notes: row.get(16).unwrap_or(None),
+                tags: row.get(17).unwrap_or(None),
+                ttft_ms: row.get(18).unwrap_or(None),
+                tokens_per_sec: row.get(19).unwrap_or(None),
+                chunk_count: row.get(20).unwrap_or(None),
+                message_id: row.get(21).unwrap_or(None),
+                parent_message_id: row.get(22).unwrap_or(None),
+                error_code: row.get(23).unwrap_or(None),
+                error_hint: row.get(24).unwrap_or(None),
             })
         }).map_err(|e| e.to_string())?;
         logs_iter.filter_map(|r| r.ok()).collect()
@@ -346,6 +741,16 @@ pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset:
                 input_tokens: row.get(10).unwrap_or(None),
                 output_tokens: row.get(11).unwrap_or(None),
                 protocol: row.get(14).unwrap_or(None),
+                session_id: row.get(15).unwrap_or(None),
+                notes: row.get(16).unwrap_or(None),
+                tags: row.get(17).unwrap_or(None),
+                ttft_ms: row.get(18).unwrap_or(None),
+                tokens_per_sec: row.get(19).unwrap_or(None),
+                chunk_count: row.get(20).unwrap_or(None),
+                message_id: row.get(21).unwrap_or(None),
+                parent_message_id: row.get(22).unwrap_or(None),
+                error_code: row.get(23).unwrap_or(None),
+                error_hint: row.get(24).unwrap_or(None),
             })
         }).map_err(|e| e.to_string())?;
         logs_iter.filter_map(|r| r.ok()).collect()
@@ -368,6 +773,16 @@ pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset:
                 input_tokens: row.get(10).unwrap_or(None),
                 output_tokens: row.get(11).unwrap_or(None),
                 protocol: row.get(14).unwrap_or(None),
+                session_id: row.get(15).unwrap_or(None),
+                notes: row.get(16).unwrap_or(None),
+                tags: row.get(17).unwrap_or(None),
+                ttft_ms: row.get(18).unwrap_or(None),
+                tokens_per_sec: row.get(19).unwrap_or(None),
+                chunk_count: row.get(20).unwrap_or(None),
+                message_id: row.get(21).unwrap_or(None),
+                parent_message_id: row.get(22).unwrap_or(None),
+                error_code: row.get(23).unwrap_or(None),
+                error_hint: row.get(24).unwrap_or(None),
             })
         }).map_err(|e| e.to_string())?;
         logs_iter.filter_map(|r| r.ok()).collect()
@@ -376,6 +791,272 @@ pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset:
     Ok(logs)
 }
 
+/// 日志查看器的查询条件，各条件取交集，未设置的条件不参与过滤
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogQuery {
+    /// 全文检索 (请求体 / 响应体 / 错误 / 备注 / 标签)，空白分隔的多个词须同时命中
+    pub text: String,
+    /// 模型 (匹配请求模型或映射后的模型)
+    pub model: Option<String>,
+    pub account: Option<String>,
+    pub status_min: Option<u16>,
+    pub status_max: Option<u16>,
+    /// 仅返回失败请求 (status < 200 或 >= 400)
+    pub errors_only: bool,
+    /// 单个标签，如 `project=foo`
+    pub tag: Option<String>,
+    pub session_id: Option<String>,
+    /// 时间范围 (毫秒时间戳，含 from 不含 to)
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    /// 每页条数，0 表示默认 50，最大 500
+    pub limit: usize,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogSearchHit {
+    #[serde(flatten)]
+    pub log: ProxyRequestLog,
+    /// 全文命中片段，命中文字以 `[[` `]]` 包围；未使用全文检索时为空
+    pub snippet: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LogFacet {
+    pub value: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogQueryResult {
+    /// 满足条件的总条数 (不受分页影响)
+    pub total: u64,
+    pub error_count: u64,
+    pub logs: Vec<LogSearchHit>,
+    /// 按模型 / 账号 / 状态码 / 标签聚合的计数 (基于全部命中记录，各取前 20)
+    pub models: Vec<LogFacet>,
+    pub accounts: Vec<LogFacet>,
+    pub statuses: Vec<LogFacet>,
+    pub tags: Vec<LogFacet>,
+}
+
+const DEFAULT_QUERY_LIMIT: usize = 50;
+const MAX_QUERY_LIMIT: usize = 500;
+const MAX_FACETS: usize = 20;
+/// trigram 分词下能走索引的最短检索词
+const MIN_FTS_TERM_CHARS: usize = 3;
+
+/// 把检索文本拆为 FTS5 MATCH 表达式 (每个词按短语引用，避免被解析为语法) 与过短的词 (改用 LIKE)
+fn split_search_terms(text: &str) -> (Option<String>, Vec<String>) {
+    let mut phrases = Vec::new();
+    let mut short = Vec::new();
+    for term in text.split_whitespace() {
+        if term.chars().count() >= MIN_FTS_TERM_CHARS {
+            phrases.push(format!("\"{}\"", term.replace('"', "\"\"")));
+        } else {
+            short.push(term.to_string());
+        }
+    }
+    ((!phrases.is_empty()).then(|| phrases.join(" AND ")), short)
+}
+
+/// 组装 FROM / WHERE 子句与参数，列表、计数与聚合查询共用
+fn build_log_query(query: &LogQuery) -> (String, String, Vec<Box<dyn rusqlite::ToSql>>) {
+    let (match_expr, short_terms) = split_search_terms(&query.text);
+    let mut clauses: Vec<String> = Vec::new();
+    let mut args: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    let from = if let Some(expr) = match_expr {
+        clauses.push("request_logs_fts MATCH ?".to_string());
+        args.push(Box::new(expr));
+        "request_logs_fts JOIN request_logs l ON l.rowid = request_logs_fts.rowid"
+    } else {
+        "request_logs l"
+    };
+    for term in short_terms {
+        clauses.push(
+            "(l.request_body LIKE ? OR l.response_body LIKE ? OR l.error LIKE ? OR l.notes LIKE ? OR l.tags LIKE ?)".to_string(),
+        );
+        let pattern = format!("%{}%", term);
+        for _ in 0..5 {
+            args.push(Box::new(pattern.clone()));
+        }
+    }
+    if let Some(model) = query.model.as_deref().filter(|m| !m.is_empty()) {
+        clauses.push("(l.model = ? OR l.mapped_model = ?)".to_string());
+        args.push(Box::new(model.to_string()));
+        args.push(Box::new(model.to_string()));
+    }
+    if let Some(account) = query.account.as_deref().filter(|a| !a.is_empty()) {
+        clauses.push("l.account_email = ?".to_string());
+        args.push(Box::new(account.to_string()));
+    }
+    if let Some(min) = query.status_min {
+        clauses.push("l.status >= ?".to_string());
+        args.push(Box::new(min));
+    }
+    if let Some(max) = query.status_max {
+        clauses.push("l.status <= ?".to_string());
+        args.push(Box::new(max));
+    }
+    if query.errors_only {
+        clauses.push("(l.status < 200 OR l.status >= 400)".to_string());
+    }
+    if let Some(tag) = query.tag.as_deref().filter(|t| !t.is_empty()) {
+        // tags 以逗号拼接存储，按完整标签匹配
+        clauses.push("(',' || l.tags || ',') LIKE ?".to_string());
+        args.push(Box::new(format!("%,{},%", tag)));
+    }
+    if let Some(session_id) = query.session_id.as_deref().filter(|s| !s.is_empty()) {
+        clauses.push("l.session_id = ?".to_string());
+        args.push(Box::new(session_id.to_string()));
+    }
+    if let Some(from) = query.from {
+        clauses.push("l.timestamp >= ?".to_string());
+        args.push(Box::new(from));
+    }
+    if let Some(to) = query.to {
+        clauses.push("l.timestamp < ?".to_string());
+        args.push(Box::new(to));
+    }
+
+    let where_sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+    (from.to_string(), where_sql, args)
+}
+
+fn query_facet(conn: &Connection, column: &str, from: &str, where_sql: &str, args: &[Box<dyn rusqlite::ToSql>]) -> Result<Vec<LogFacet>, String> {
+    let sql = format!(
+        "SELECT CAST({col} AS TEXT) AS value, COUNT(*) AS cnt FROM {from} {where_sql}
+         GROUP BY {col} HAVING value IS NOT NULL AND value != ''
+         ORDER BY cnt DESC, value ASC LIMIT {limit}",
+        col = column,
+        from = from,
+        where_sql = where_sql,
+        limit = MAX_FACETS,
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(args.iter()), |row| {
+        Ok(LogFacet { value: row.get(0)?, count: row.get(1)? })
+    }).map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// 标签以逗号拼接存储，逐条拆分后计数
+fn query_tag_facet(conn: &Connection, from: &str, where_sql: &str, args: &[Box<dyn rusqlite::ToSql>]) -> Result<Vec<LogFacet>, String> {
+    let sql = format!(
+        "SELECT l.tags, COUNT(*) FROM {} {} {} l.tags IS NOT NULL AND l.tags != '' GROUP BY l.tags",
+        from,
+        where_sql,
+        if where_sql.is_empty() { "WHERE" } else { "AND" },
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(args.iter()), |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+    }).map_err(|e| e.to_string())?;
+
+    let mut counts: std::collections::HashMap<String, u64> = std::collections::HashMap::new();
+    for row in rows {
+        let (tags, count) = row.map_err(|e| e.to_string())?;
+        for tag in tags.split(',').filter(|t| !t.is_empty()) {
+            *counts.entry(tag.to_string()).or_default() += count;
+        }
+    }
+    let mut facets: Vec<LogFacet> = counts.into_iter().map(|(value, count)| LogFacet { value, count }).collect();
+    facets.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+    facets.truncate(MAX_FACETS);
+    Ok(facets)
+}
+
+fn query_logs_on(conn: &Connection, query: &LogQuery) -> Result<LogQueryResult, String> {
+    let (from, where_sql, args) = build_log_query(query);
+    let has_match = from.starts_with("request_logs_fts");
+
+    let (total, error_count): (u64, u64) = conn.query_row(
+        &format!(
+            "SELECT COUNT(*), COALESCE(SUM(CASE WHEN l.status < 200 OR l.status >= 400 THEN 1 ELSE 0 END), 0) FROM {} {}",
+            from, where_sql
+        ),
+        rusqlite::params_from_iter(args.iter()),
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).map_err(|e| e.to_string())?;
+
+    let limit = match query.limit {
+        0 => DEFAULT_QUERY_LIMIT,
+        n => n.min(MAX_QUERY_LIMIT),
+    };
+    let snippet = if has_match {
+        "snippet(request_logs_fts, -1, '[[', ']]', '…', 16)"
+    } else {
+        "NULL"
+    };
+    let sql = format!(
+        "SELECT l.id, l.timestamp, l.method, l.url, l.status, l.duration, l.model, l.error,
+                NULL as request_body, NULL as response_body,
+                l.input_tokens, l.output_tokens, l.account_email, l.mapped_model, l.protocol, l.session_id, l.notes, l.tags, l.ttft_ms, l.tokens_per_sec, l.chunk_count,
+                l.message_id, l.parent_message_id, l.error_code, l.error_hint, {}
+         FROM {} {}
+         ORDER BY l.timestamp DESC
+         LIMIT {} OFFSET {}",
+        snippet, from, where_sql, limit, query.offset
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(args.iter()), |row| {
+        Ok(LogSearchHit {
+            log: ProxyRequestLog {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                method: row.get(2)?,
+                url: row.get(3)?,
+                status: row.get(4)?,
+                duration: row.get(5)?,
+                model: row.get(6)?,
+                mapped_model: row.get(13).unwrap_or(None),
+                account_email: row.get(12).unwrap_or(None),
+                error: row.get(7)?,
+                request_body: None,
+                response_body: None,
+                input_tokens: row.get(10).unwrap_or(None),
+                output_tokens: row.get(11).unwrap_or(None),
+                protocol: row.get(14).unwrap_or(None),
+                session_id: row.get(15).unwrap_or(None),
+                notes: row.get(16).unwrap_or(None),
+                tags: row.get(17).unwrap_or(None),
+                ttft_ms: row.get(18).unwrap_or(None),
+                tokens_per_sec: row.get(19).unwrap_or(None),
+                chunk_count: row.get(20).unwrap_or(None),
+                message_id: row.get(21).unwrap_or(None),
+                parent_message_id: row.get(22).unwrap_or(None),
+                error_code: row.get(23).unwrap_or(None),
+                error_hint: row.get(24).unwrap_or(None),
+            },
+            snippet: row.get(25).unwrap_or(None),
+        })
+    }).map_err(|e| e.to_string())?;
+    let logs = rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+
+    Ok(LogQueryResult {
+        total,
+        error_count,
+        logs,
+        models: query_facet(conn, "l.model", &from, &where_sql, &args)?,
+        accounts: query_facet(conn, "l.account_email", &from, &where_sql, &args)?,
+        statuses: query_facet(conn, "l.status", &from, &where_sql, &args)?,
+        tags: query_tag_facet(conn, &from, &where_sql, &args)?,
+    })
+}
+
+/// 日志查看器查询: 全文检索 + 条件过滤 + 分页，并返回总数与聚合计数
+pub fn query_logs(query: &LogQuery) -> Result<LogQueryResult, String> {
+    let conn = connect_db()?;
+    query_logs_on(&conn, query)
+}
+
 /// Get all logs with full details for export
 pub fn get_all_logs_for_export() -> Result<Vec<ProxyRequestLog>, String> {
     let conn = connect_db()?;
@@ -383,7 +1064,8 @@ pub fn get_all_logs_for_export() -> Result<Vec<ProxyRequestLog>, String> {
     let mut stmt = conn.prepare(
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 request_body, response_body, input_tokens, output_tokens, 
-                account_email, mapped_model, protocol
+                account_email, mapped_model, protocol, session_id, notes, tags, ttft_ms, tokens_per_sec, chunk_count,
+                message_id, parent_message_id, error_code, error_hint
          FROM request_logs 
          ORDER BY timestamp DESC"
     ).map_err(|e| e.to_string())?;
@@ -405,6 +1087,16 @@ pub fn get_all_logs_for_export() -> Result<Vec<ProxyRequestLog>, String> {
             input_tokens: row.get(10).unwrap_or(None),
             output_tokens: row.get(11).unwrap_or(None),
             protocol: row.get(14).unwrap_or(None),
+            session_id: row.get(15).unwrap_or(None),
+            notes: row.get(16).unwrap_or(None),
+            tags: row.get(17).unwrap_or(None),
+            ttft_ms: row.get(18).unwrap_or(None),
+            tokens_per_sec: row.get(19).unwrap_or(None),
+            chunk_count: row.get(20).unwrap_or(None),
+            message_id: row.get(21).unwrap_or(None),
+            parent_message_id: row.get(22).unwrap_or(None),
+            error_code: row.get(23).unwrap_or(None),
+            error_hint: row.get(24).unwrap_or(None),
         })
     }).map_err(|e| e.to_string())?;
 
@@ -429,7 +1121,8 @@ pub fn get_logs_by_ids(ids: &[String]) -> Result<Vec<ProxyRequestLog>, String> {
     let sql = format!(
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 request_body, response_body, input_tokens, output_tokens, 
-                account_email, mapped_model, protocol
+                account_email, mapped_model, protocol, session_id, notes, tags, ttft_ms, tokens_per_sec, chunk_count,
+                message_id, parent_message_id, error_code, error_hint
          FROM request_logs 
          WHERE id IN ({})
          ORDER BY timestamp DESC",
@@ -458,6 +1151,16 @@ pub fn get_logs_by_ids(ids: &[String]) -> Result<Vec<ProxyRequestLog>, String> {
             input_tokens: row.get(10).unwrap_or(None),
             output_tokens: row.get(11).unwrap_or(None),
             protocol: row.get(14).unwrap_or(None),
+            session_id: row.get(15).unwrap_or(None),
+            notes: row.get(16).unwrap_or(None),
+            tags: row.get(17).unwrap_or(None),
+            ttft_ms: row.get(18).unwrap_or(None),
+            tokens_per_sec: row.get(19).unwrap_or(None),
+            chunk_count: row.get(20).unwrap_or(None),
+            message_id: row.get(21).unwrap_or(None),
+            parent_message_id: row.get(22).unwrap_or(None),
+            error_code: row.get(23).unwrap_or(None),
+            error_hint: row.get(24).unwrap_or(None),
         })
     }).map_err(|e| e.to_string())?;
 
@@ -467,3 +1170,109 @@ pub fn get_logs_by_ids(ids: &[String]) -> Result<Vec<ProxyRequestLog>, String> {
     }
     Ok(logs)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        let rows = [
+            ("a", 1000, 200, "gemini-3-flash", "a@x.com", "explain quantum tunneling", "Quantum tunneling is...", Some("project=foo,nightly")),
+            ("b", 2000, 429, "gemini-3-pro-high", "b@x.com", "明天的天气预报", "", Some("project=foo")),
+            ("c", 3000, 200, "gemini-3-flash", "b@x.com", "say ok", "ok", None),
+        ];
+        for (id, timestamp, status, model, account, request, response, tags) in rows {
+            conn.execute(
+                "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, account_email, request_body, response_body, tags)
+                 VALUES (?1, ?2, 'POST', '/v1/chat/completions', ?3, 100, ?4, ?5, ?6, ?7, ?8)",
+                params![id, timestamp, status, model, account, request, response, tags],
+            ).unwrap();
+        }
+        conn
+    }
+
+    fn ids(result: &LogQueryResult) -> Vec<&str> {
+        result.logs.iter().map(|h| h.log.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_query_logs_full_text_and_filters() {
+        let conn = test_db();
+
+        let all = query_logs_on(&conn, &LogQuery::default()).unwrap();
+        assert_eq!((all.total, all.error_count), (3, 1));
+        assert_eq!(ids(&all), vec!["c", "b", "a"]);
+        assert!(all.logs[0].snippet.is_none());
+        assert_eq!(all.models[0], LogFacet { value: "gemini-3-flash".to_string(), count: 2 });
+        assert_eq!(all.tags[0], LogFacet { value: "project=foo".to_string(), count: 2 });
+
+        // 全文命中 (大小写不敏感、中日韩子串) 并返回片段
+        let hit = query_logs_on(&conn, &LogQuery { text: "QUANTUM tunnel".to_string(), ..Default::default() }).unwrap();
+        assert_eq!(ids(&hit), vec!["a"]);
+        assert!(hit.logs[0].snippet.as_deref().unwrap().contains("[["));
+        let cjk = query_logs_on(&conn, &LogQuery { text: "天气预".to_string(), ..Default::default() }).unwrap();
+        assert_eq!(ids(&cjk), vec!["b"]);
+        // 少于 3 个字符的词退回 LIKE
+        let short = query_logs_on(&conn, &LogQuery { text: "ok".to_string(), ..Default::default() }).unwrap();
+        assert_eq!(ids(&short), vec!["c"]);
+
+        let filtered = query_logs_on(&conn, &LogQuery {
+            account: Some("b@x.com".to_string()),
+            tag: Some("project=foo".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(ids(&filtered), vec!["b"]);
+        let errors = query_logs_on(&conn, &LogQuery { errors_only: true, from: Some(1500), ..Default::default() }).unwrap();
+        assert_eq!(errors.statuses, vec![LogFacet { value: "429".to_string(), count: 1 }]);
+        let page = query_logs_on(&conn, &LogQuery { limit: 1, offset: 1, ..Default::default() }).unwrap();
+        assert_eq!((page.total, ids(&page)), (3, vec!["b"]));
+    }
+
+    #[test]
+    fn test_prune_logs_by_age_rows_and_size() {
+        let conn = test_db();
+        // a: 1000, b: 2000, c: 3000 (ms)；保留 1 天时 now 取 c 之后 1 天
+        let day = 24 * 3600 * 1000;
+        assert_eq!(prune_logs_on(&conn, 2500 + day, 1, 0, 0).unwrap(), 2);
+        assert_eq!(ids(&query_logs_on(&conn, &LogQuery::default()).unwrap()), vec!["c"]);
+
+        let conn = test_db();
+        assert_eq!(prune_logs_on(&conn, 0, 0, 2, 0).unwrap(), 1);
+        assert_eq!(ids(&query_logs_on(&conn, &LogQuery::default()).unwrap()), vec!["c", "b"]);
+
+        // 按大小: 写入大量正文后限制到当前占用的一半，最旧的记录先被删除
+        let conn = test_db();
+        let body = "lorem ipsum dolor sit amet ".repeat(400);
+        for i in 0..200 {
+            conn.execute(
+                "INSERT INTO request_logs (id, timestamp, method, url, status, duration, request_body) VALUES (?1, ?2, 'POST', '/v1/messages', 200, 100, ?3)",
+                params![format!("bulk-{}", i), 10_000 + i, format!("{} {}", body, i)],
+            ).unwrap();
+        }
+        let before = used_bytes(&conn).unwrap();
+        let limit = before / 2;
+        assert!(prune_logs_on(&conn, 0, 0, 0, limit).unwrap() > 0);
+        assert!(used_bytes(&conn).unwrap() <= limit);
+        let newest = query_logs_on(&conn, &LogQuery { limit: 1, ..Default::default() }).unwrap();
+        assert_eq!(ids(&newest), vec!["bulk-199"]);
+        assert!(query_logs_on(&conn, &LogQuery { text: "QUANTUM".to_string(), ..Default::default() }).unwrap().logs.is_empty());
+    }
+
+    #[test]
+    fn test_search_index_follows_updates_deletes_and_vacuum() {
+        let conn = test_db();
+        let search = |text: &str| ids(&query_logs_on(&conn, &LogQuery { text: text.to_string(), ..Default::default() }).unwrap()).into_iter().map(String::from).collect::<Vec<_>>();
+
+        conn.execute("UPDATE request_logs SET notes = 'flaky upstream' WHERE id = 'c'", []).unwrap();
+        assert_eq!(search("flaky"), vec!["c"]);
+        conn.execute("DELETE FROM request_logs WHERE id = 'a'", []).unwrap();
+        assert!(search("quantum").is_empty());
+
+        conn.execute("VACUUM", []).unwrap();
+        rebuild_search_index(&conn).unwrap();
+        assert_eq!(search("天气预报"), vec!["b"]);
+        assert_eq!(search("flaky"), vec!["c"]);
+    }
+}
@@ -0,0 +1,253 @@
+// 远程 quirk 清单的下载、签名校验与缓存
+// 清单与签名 (`<url>.sig`) 一起下载，签名校验通过后连同签名缓存到 quirk_manifest.json，
+// 启动时用当前配置的公钥重新校验后再加载。
+// 后台按 refresh_interval_hours 定期刷新；本地覆盖保存在 proxy.experimental.quirks.overrides 中。
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::time::{self, Duration};
+
+use crate::modules::{account, config, logger};
+use crate::proxy::config::{ModelQuirk, QuirksConfig};
+use crate::proxy::mappers::quirks::{self, QuirkManifest};
+
+/// 检查是否需要刷新的间隔
+const CHECK_INTERVAL_SECS: u64 = 600;
+/// 清单与签名的响应体上限 (超出即中止下载)
+const MAX_MANIFEST_BYTES: usize = 1024 * 1024;
+const MAX_SIGNATURE_BYTES: usize = 1024;
+
+/// 缓存原始清单与签名 (而非解析结果)，加载时重新校验，防止缓存文件被篡改
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ManifestCache {
+    url: String,
+    fetched_at: i64,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuirkManifestReport {
+    pub manifest: Option<QuirkManifest>,
+    pub manifest_url: Option<String>,
+    pub fetched_at: Option<i64>,
+    pub overrides: Vec<ModelQuirk>,
+}
+
+fn cache_path() -> Result<PathBuf, String> {
+    Ok(account::get_data_dir()?.join("quirk_manifest.json"))
+}
+
+fn load_cache() -> Option<ManifestCache> {
+    cache_path()
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str(&c).ok())
+}
+
+fn save_cache(cache: &ManifestCache) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(cache).map_err(|e| format!("序列化 quirk 清单失败: {}", e))?;
+    std::fs::write(cache_path()?, content).map_err(|e| format!("保存 quirk 清单失败: {}", e))
+}
+
+fn load_quirks_config() -> QuirksConfig {
+    config::load_app_config().map(|c| c.proxy.experimental.quirks).unwrap_or_default()
+}
+
+fn verify_cache(cache: &ManifestCache, public_key: &str) -> Result<QuirkManifest, String> {
+    if cache.signature.trim().is_empty() {
+        return Err("cached manifest has no signature".to_string());
+    }
+    quirks::verify_manifest(cache.body.as_bytes(), &cache.signature, public_key)
+}
+
+/// 读取缓存并用当前公钥校验；没有缓存时返回 Ok(None)
+fn load_verified_cache(public_key: &str) -> Result<Option<(ManifestCache, QuirkManifest)>, String> {
+    let Some(cache) = load_cache() else {
+        return Ok(None);
+    };
+    let manifest = verify_cache(&cache, public_key)?;
+    Ok(Some((cache, manifest)))
+}
+
+/// 启动时加载缓存的清单与本地覆盖
+pub fn init() {
+    let quirks_config = load_quirks_config();
+    match load_verified_cache(&quirks_config.public_key) {
+        Ok(Some((_, manifest))) => {
+            logger::log_info(&format!(
+                "[Quirks] 已加载 quirk 清单 v{} ({} 条规则)",
+                manifest.version,
+                manifest.models.len()
+            ));
+            quirks::set_manifest(manifest);
+        }
+        Ok(None) => {}
+        Err(e) => logger::log_warn(&format!("[Quirks] 缓存的 quirk 清单校验失败，已忽略: {}", e)),
+    }
+    quirks::set_overrides(quirks_config.overrides);
+}
+
+pub fn get_report() -> QuirkManifestReport {
+    let quirks_config = load_quirks_config();
+    let cache = load_verified_cache(&quirks_config.public_key).ok().flatten();
+    QuirkManifestReport {
+        manifest_url: cache.as_ref().map(|(c, _)| c.url.clone()),
+        fetched_at: cache.as_ref().map(|(c, _)| c.fetched_at),
+        manifest: cache.map(|(_, m)| m),
+        overrides: quirks_config.overrides,
+    }
+}
+
+async fn fetch_bytes(client: &reqwest::Client, url: &str, limit: usize) -> Result<Vec<u8>, String> {
+    let response = client.get(url).send().await.map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{} returned status: {}", url, response.status()));
+    }
+    read_limited(response, url, limit).await
+}
+
+/// 先检查 Content-Length，再按块读取并在累计超出上限时中止 (Content-Length 可能缺失或不实)
+async fn read_limited(mut response: reqwest::Response, url: &str, limit: usize) -> Result<Vec<u8>, String> {
+    let too_large = || format!("{} exceeds the {} byte limit", url, limit);
+    if response.content_length().is_some_and(|len| len > limit as u64) {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| format!("Failed to read {}: {}", url, e))? {
+        if body.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// 下载并校验清单
+pub async fn refresh_manifest() -> Result<QuirkManifestReport, String> {
+    let quirks_config = load_quirks_config();
+    let url = quirks_config.manifest_url.trim().to_string();
+    if url.is_empty() {
+        return Err("No quirk manifest URL configured".to_string());
+    }
+    if quirks_config.public_key.trim().is_empty() {
+        return Err("No public key configured for verifying the quirk manifest".to_string());
+    }
+
+    let client = reqwest::Client::builder()
+        .user_agent("Antigravity-Manager")
+        .timeout(std::time::Duration::from_secs(15))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let body = fetch_bytes(&client, &url, MAX_MANIFEST_BYTES).await?;
+    let body = String::from_utf8(body).map_err(|_| "Quirk manifest is not valid UTF-8".to_string())?;
+    let signature = fetch_bytes(&client, &format!("{}.sig", url), MAX_SIGNATURE_BYTES).await?;
+    let signature = String::from_utf8(signature).map_err(|_| "Manifest signature is not valid text".to_string())?;
+    let manifest = quirks::verify_manifest(body.as_bytes(), &signature, &quirks_config.public_key)?;
+
+    // 同一来源的清单不允许回退到旧版本
+    if let Ok(Some((cached, cached_manifest))) = load_verified_cache(&quirks_config.public_key) {
+        if cached.url == url && manifest.version < cached_manifest.version {
+            return Err(format!(
+                "Quirk manifest version {} is older than the cached version {}",
+                manifest.version, cached_manifest.version
+            ));
+        }
+    }
+
+    logger::log_info(&format!("[Quirks] 已更新 quirk 清单 v{} ({} 条规则)", manifest.version, manifest.models.len()));
+    save_cache(&ManifestCache { url, fetched_at: chrono::Utc::now().timestamp(), body, signature })?;
+    quirks::set_manifest(manifest);
+    Ok(get_report())
+}
+
+/// 后台任务: 按配置的间隔刷新清单 (每 10 分钟检查一次是否到期)
+pub fn start_refresh_loop() {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(CHECK_INTERVAL_SECS));
+        // 失败后同样等待一个完整周期，避免每 10 分钟重复请求
+        let mut last_attempt = 0i64;
+        loop {
+            interval.tick().await;
+
+            let quirks_config = load_quirks_config();
+            if quirks_config.manifest_url.trim().is_empty() || quirks_config.refresh_interval_hours == 0 {
+                continue;
+            }
+            // 缓存校验失败 (被篡改或更换了公钥) 时视为从未下载，立即重新获取
+            let last = load_verified_cache(&quirks_config.public_key)
+                .ok()
+                .flatten()
+                .filter(|(c, _)| c.url == quirks_config.manifest_url.trim())
+                .map(|(c, _)| c.fetched_at)
+                .unwrap_or(0)
+                .max(last_attempt);
+            let now = chrono::Utc::now().timestamp();
+            if now < last + quirks_config.refresh_interval_hours as i64 * 3600 {
+                continue;
+            }
+            last_attempt = now;
+            if let Err(e) = refresh_manifest().await {
+                logger::log_warn(&format!("[Quirks] 刷新 quirk 清单失败: {}", e));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 与 mappers::quirks 测试相同的 Ed25519 测试密钥
+    const TEST_PRIVATE_KEY: &str = "MC4CAQAwBQYDK2VwBCIEIBES7w5P8wRTNE57OrDbtzgyzL+zNSquirn6chE76h6R";
+    const TEST_PUBLIC_KEY: &str = "R53DWM_tqtFvllfnyvPRZIizR_6_dnmK-wDA-f3beQw";
+
+    fn sign(body: &[u8]) -> String {
+        use base64::Engine as _;
+        let der = base64::engine::general_purpose::STANDARD.decode(TEST_PRIVATE_KEY).unwrap();
+        let key = jsonwebtoken::EncodingKey::from_ed_der(&der);
+        jsonwebtoken::crypto::sign(body, &key, jsonwebtoken::Algorithm::EdDSA).unwrap()
+    }
+
+    #[test]
+    fn test_cached_manifest_is_verified_on_load() {
+        let body = r#"{"version":4,"models":[{"model":"gemini-9-*","strip_thought_parts":true}]}"#.to_string();
+        let cache = ManifestCache {
+            url: "https://example.com/quirks.json".to_string(),
+            fetched_at: 1,
+            signature: sign(body.as_bytes()),
+            body,
+        };
+        assert_eq!(verify_cache(&cache, TEST_PUBLIC_KEY).unwrap().version, 4);
+
+        // 缓存内容被修改
+        let tampered = ManifestCache { body: cache.body.replace("true", "false"), ..cache.clone() };
+        assert!(verify_cache(&tampered, TEST_PUBLIC_KEY).is_err());
+        // 旧格式缓存 (没有签名) 或公钥不匹配
+        let unsigned = ManifestCache { signature: String::new(), ..cache.clone() };
+        assert!(verify_cache(&unsigned, TEST_PUBLIC_KEY).is_err());
+        assert!(verify_cache(&cache, "R53DWM_tqtFvllfnyvPRZIizR_6_dnmK-wDA-f3beQx").is_err());
+
+        // 旧格式的缓存文件仍可解析，但校验失败
+        let legacy: ManifestCache =
+            serde_json::from_str(r#"{"url":"u","fetched_at":1,"manifest":{"version":9,"models":[]}}"#).unwrap();
+        assert!(verify_cache(&legacy, TEST_PUBLIC_KEY).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_limited_caps_response_body() {
+        let response = |body: Vec<u8>| reqwest::Response::from(axum::http::Response::new(body));
+
+        assert_eq!(read_limited(response(vec![1; 16]), "u", 16).await.unwrap().len(), 16);
+        assert!(read_limited(response(vec![1; 17]), "u", 16).await.unwrap_err().contains("limit"));
+
+        // 未声明长度的流式响应按累计字节数截断
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> = vec![Ok(vec![1; 10]), Ok(vec![1; 10])];
+        let streamed = axum::http::Response::new(reqwest::Body::wrap_stream(futures::stream::iter(chunks)));
+        let streamed = reqwest::Response::from(streamed);
+        assert!(streamed.content_length().is_none());
+        assert!(read_limited(streamed, "u", 16).await.unwrap_err().contains("limit"));
+    }
+}
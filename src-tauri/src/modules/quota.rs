@@ -247,6 +247,9 @@ pub async fn fetch_all_quotas(accounts: Vec<(String, String)>) -> Vec<(String, c
 /// Get valid token (auto-refresh if expired)
 pub async fn get_valid_token_for_warmup(account: &crate::models::account::Account) -> Result<(String, String), String> {
     let mut account = account.clone();
+    if account.is_api_key() {
+        return Err("API key accounts do not need warmup".to_string());
+    }
     
     // Check and auto-refresh token
     let new_token = crate::modules::oauth::ensure_fresh_token(&account.token).await?;
@@ -321,7 +324,13 @@ pub async fn warm_up_all_accounts() -> Result<String, String> {
     let mut retry_count = 0;
     
     loop {
-        let target_accounts = crate::modules::account::list_accounts().unwrap_or_default();
+        // 暂停中的账号不预热
+        let now = chrono::Utc::now().timestamp();
+        let target_accounts: Vec<_> = crate::modules::account::list_accounts()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|a| !a.is_paused(now))
+            .collect();
 
         if target_accounts.is_empty() {
             return Ok("No accounts available".to_string());
@@ -0,0 +1,216 @@
+// 数据保留策略与紧急擦除
+// 后台任务按 proxy.retention 的间隔裁剪请求历史 (时间 / 条数 / 数据库大小) 并删除过期的流式落盘文件，
+// 取代原先反代启动时固定保留 30 天的清理。
+// panic_wipe 立即安全删除全部请求历史、流式落盘文件、批处理文件、保存的生成图片与签名缓存；
+// 共享状态存储中镜像的请求历史由命令另行删除 (需要异步连接)。
+
+use chrono::{Days, Local, NaiveDate};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::time::{self, Duration};
+
+use crate::commands::proxy::ProxyServiceState;
+use crate::modules::{config, proxy_db};
+use crate::proxy::config::RetentionConfig;
+use crate::proxy::ProxyConfig;
+use crate::proxy::mappers::image_output;
+use crate::proxy::middleware::stream_tee;
+
+/// 检查是否到期的间隔
+const CHECK_INTERVAL_SECS: u64 = 300;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionReport {
+    pub deleted_logs: usize,
+    pub deleted_files: usize,
+    /// 裁剪后请求历史数据库的占用 (字节)
+    pub db_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WipeReport {
+    pub deleted_logs: usize,
+    pub deleted_files: usize,
+    /// 批处理输入/结果文件与任务记录
+    pub deleted_batch_files: usize,
+    pub deleted_images: usize,
+    /// 共享状态存储中镜像的请求历史
+    pub deleted_remote_history: usize,
+}
+
+/// 以零覆写文件内容并落盘后再删除
+fn secure_remove(path: &Path) -> std::io::Result<()> {
+    let len = std::fs::metadata(path)?.len();
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = vec![0u8; 64 * 1024];
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+    std::fs::remove_file(path)
+}
+
+/// 删除日期早于 cutoff 的落盘文件，返回删除数
+fn prune_files(files: Vec<(PathBuf, NaiveDate)>, cutoff: NaiveDate) -> usize {
+    let mut deleted = 0;
+    for (path, _) in files.into_iter().filter(|(_, date)| *date < cutoff) {
+        match std::fs::remove_file(&path) {
+            Ok(()) => deleted += 1,
+            Err(e) => tracing::warn!("[Retention] 无法删除 {:?}: {}", path, e),
+        }
+    }
+    deleted
+}
+
+/// 按保留策略执行一次清理 (阻塞调用)
+pub fn apply(retention: &RetentionConfig, tee_dir: &str) -> Result<RetentionReport, String> {
+    proxy_db::init_db()?;
+    let (deleted_logs, db_bytes) = proxy_db::prune_logs(
+        retention.max_age_days,
+        retention.max_rows,
+        retention.max_db_size_mb.saturating_mul(1024 * 1024),
+    )?;
+
+    let deleted_files = match Local::now().date_naive().checked_sub_days(Days::new(retention.max_age_days as u64)) {
+        Some(cutoff) if retention.max_age_days > 0 => prune_files(stream_tee::list_files(tee_dir), cutoff),
+        _ => 0,
+    };
+
+    Ok(RetentionReport { deleted_logs, deleted_files, db_bytes })
+}
+
+/// 安全删除一组文件，返回删除数；失败项记入 errors
+fn secure_remove_all(paths: Vec<PathBuf>, errors: &mut Vec<String>) -> usize {
+    let mut deleted = 0;
+    for path in paths {
+        match secure_remove(&path) {
+            Ok(()) => deleted += 1,
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    deleted
+}
+
+/// 紧急擦除: 请求历史 (含 A/B 结果与全文索引)、流式落盘文件、批处理文件、保存的生成图片、签名缓存 (内存与状态日志)
+/// 单项失败不影响其余项，全部执行完后汇总报错
+pub fn panic_wipe(tee_dir: &str, image_dir: &str) -> Result<WipeReport, String> {
+    let mut errors = Vec::new();
+
+    crate::proxy::SignatureCache::global().clear();
+    crate::proxy::mappers::signature_store::clear_thought_signature();
+    // 压缩后快照中不再包含签名，日志文件被截断
+    crate::proxy::state_journal::StateJournal::global().compact();
+
+    let deleted_logs = match proxy_db::init_db().and_then(|_| proxy_db::secure_wipe()) {
+        Ok(n) => n,
+        Err(e) => {
+            errors.push(format!("history: {}", e));
+            0
+        }
+    };
+
+    stream_tee::close_files();
+    let tee_files = stream_tee::list_files(tee_dir).into_iter().map(|(path, _)| path).collect();
+    let deleted_files = secure_remove_all(tee_files, &mut errors);
+
+    // 停止运行中的批任务后再删除，避免结果文件被重新写入
+    let deleted_batch_files = secure_remove_all(crate::proxy::batch::BatchManager::global().wipe(), &mut errors);
+    let deleted_images = secure_remove_all(image_output::list_saved_images(image_dir), &mut errors);
+
+    tracing::warn!(
+        "[Retention] 紧急擦除: {} 条请求历史, {} 个落盘文件, {} 个批处理文件, {} 张图片",
+        deleted_logs, deleted_files, deleted_batch_files, deleted_images
+    );
+    if !errors.is_empty() {
+        return Err(format!("Wipe incomplete: {}", errors.join("; ")));
+    }
+    Ok(WipeReport { deleted_logs, deleted_files, deleted_batch_files, deleted_images, deleted_remote_history: 0 })
+}
+
+/// 当前生效的反代配置
+/// headless 模式使用运行中服务的配置 (--config / 环境变量 / 命令行覆盖)，服务重启期间返回 None；
+/// 桌面端以 gui_config.json 为准 (反代未启动时保留策略同样生效)
+async fn effective_config(proxy: Option<&ProxyServiceState>) -> Option<ProxyConfig> {
+    match proxy {
+        Some(state) => match state.instance.read().await.as_ref() {
+            Some(instance) => Some(instance.axum_server.proxy_config().await),
+            None => None,
+        },
+        None => config::load_app_config().ok().map(|c| c.proxy),
+    }
+}
+
+/// 后台任务: 每 5 分钟检查一次，按配置的间隔执行保留策略 (启动后立即执行一次)
+/// headless 模式传入反代服务状态，桌面端传 None
+pub fn start_retention_loop(proxy: Option<ProxyServiceState>) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(CHECK_INTERVAL_SECS));
+        let mut last_run = 0i64;
+        loop {
+            interval.tick().await;
+
+            let Some(proxy_config) = effective_config(proxy.as_ref()).await else {
+                continue;
+            };
+            let retention = proxy_config.retention;
+            if !retention.enabled {
+                continue;
+            }
+            let now = chrono::Utc::now().timestamp();
+            if now < last_run + retention.interval_minutes.max(1) as i64 * 60 {
+                continue;
+            }
+            last_run = now;
+
+            let tee_dir = proxy_config.stream_tee.directory;
+            match tokio::task::spawn_blocking(move || apply(&retention, &tee_dir)).await {
+                Ok(Ok(report)) => {
+                    if report.deleted_logs > 0 || report.deleted_files > 0 {
+                        tracing::info!(
+                            "[Retention] 已删除 {} 条请求历史, {} 个落盘文件 (数据库 {} KB)",
+                            report.deleted_logs,
+                            report.deleted_files,
+                            report.db_bytes / 1024
+                        );
+                    }
+                }
+                Ok(Err(e)) => tracing::warn!("[Retention] 清理失败: {}", e),
+                Err(e) => tracing::warn!("[Retention] 任务异常: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secure_remove_and_prune_files() {
+        let dir = std::env::temp_dir().join(format!("ag-retention-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let date = |d: u32| NaiveDate::from_ymd_opt(2026, 3, d).unwrap();
+        let files: Vec<(PathBuf, NaiveDate)> = (1..=3)
+            .map(|d| {
+                let path = dir.join(format!("stream-2026-03-0{}.jsonl", d));
+                std::fs::write(&path, "{\"type\":\"request\"}\n").unwrap();
+                (path, date(d))
+            })
+            .collect();
+
+        assert_eq!(prune_files(files.clone(), date(3)), 2);
+        assert!(!files[0].0.exists() && !files[1].0.exists());
+        assert!(files[2].0.exists());
+
+        secure_remove(&files[2].0).unwrap();
+        assert!(!files[2].0.exists());
+        assert!(secure_remove(&files[2].0).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -42,6 +42,12 @@ pub fn record_warmup_history(key: &str, timestamp: i64) {
     save_warmup_history(&history);
 }
 
+/// Persist in-memory scheduler state to disk (called on shutdown)
+pub fn persist_state() {
+    let history = WARMUP_HISTORY.lock().unwrap();
+    save_warmup_history(&history);
+}
+
 pub fn check_cooldown(key: &str, cooldown_seconds: i64) -> bool {
     let history = WARMUP_HISTORY.lock().unwrap();
     if let Some(&last_ts) = history.get(key) {
@@ -52,7 +58,85 @@ pub fn check_cooldown(key: &str, cooldown_seconds: i64) -> bool {
     }
 }
 
+/// Keep-warm loop: periodically pings each account with a tiny request
+/// (token refresh + warm connection), recording `last_warm_at` on success.
+pub fn start_keep_warm_loop() {
+    tauri::async_runtime::spawn(async move {
+        logger::log_info("Keep-warm scheduler started.");
+
+        // Check every minute; the actual ping interval comes from config
+        let mut interval = time::interval(Duration::from_secs(60));
+
+        loop {
+            interval.tick().await;
+
+            let Ok(app_config) = config::load_app_config() else {
+                continue;
+            };
+            let keep_warm = &app_config.keep_warm;
+            if !keep_warm.enabled {
+                continue;
+            }
+
+            let Ok(accounts) = account::list_accounts() else {
+                continue;
+            };
+
+            let now_ts = Utc::now().timestamp();
+            let interval_secs = keep_warm.interval_minutes.max(5) as i64 * 60;
+
+            for acc in accounts {
+                if acc.disabled || acc.proxy_disabled || acc.is_paused(now_ts) {
+                    continue;
+                }
+                if let Some(last) = acc.last_warm_at {
+                    if now_ts - last < interval_secs {
+                        continue;
+                    }
+                }
+                // Respect quota protection: never spend reserved quota on a ping
+                if acc.protected_models.contains(&keep_warm.model) {
+                    continue;
+                }
+                let percentage = acc
+                    .quota
+                    .as_ref()
+                    .and_then(|q| q.models.iter().find(|m| m.name == keep_warm.model))
+                    .map(|m| m.percentage)
+                    .unwrap_or(100);
+                let reserve = if app_config.quota_protection.enabled {
+                    app_config.quota_protection.threshold_percentage as i32
+                } else {
+                    0
+                };
+                if percentage <= reserve {
+                    continue;
+                }
+
+                let Ok((token, pid)) = quota::get_valid_token_for_warmup(&acc).await else {
+                    continue;
+                };
+
+                if quota::warmup_model_directly(&token, &keep_warm.model, &pid, &acc.email, percentage).await {
+                    // Reload to avoid clobbering token refreshed above
+                    if let Ok(mut fresh) = account::load_account(&acc.id) {
+                        fresh.last_warm_at = Some(Utc::now().timestamp());
+                        if let Err(e) = account::save_account(&fresh) {
+                            logger::log_warn(&format!("[KeepWarm] Failed to save last_warm_at for {}: {}", acc.email, e));
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
+
 pub fn start_scheduler(app_handle: tauri::AppHandle) {
+    start_keep_warm_loop();
+    crate::modules::daily_report::start_daily_report_loop();
+    crate::modules::quirk_manifest::start_refresh_loop();
+    crate::modules::retention::start_retention_loop(None);
+
     tauri::async_runtime::spawn(async move {
         logger::log_info("Smart Warmup Scheduler started. Monitoring quota at 100%...");
         
@@ -90,8 +174,8 @@ pub fn start_scheduler(app_handle: tauri::AppHandle) {
 
             // Scan each model for each account
             for account in &accounts {
-                // Skip disabled accounts
-                if account.proxy_disabled {
+                // Skip disabled and paused accounts
+                if account.proxy_disabled || account.is_paused(Utc::now().timestamp()) {
                     continue;
                 }
 
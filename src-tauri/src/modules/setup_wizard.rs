@@ -0,0 +1,327 @@
+// 首次启动向导
+// 依次: 检测端口 → 确保存在 API Key → 测试生成，每一步通过 setup://progress 事件推送进度，
+// 最后返回可直接复制到常用客户端 (Cline / Cursor / SillyTavern) 的 Base URL 与 Key。
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+
+use crate::modules::diagnostics::{self, CheckStatus, DiagnosticCheck};
+use crate::modules::{config, logger};
+
+/// 端口被占用时向后查找空闲端口的范围
+const PORT_SEARCH_RANGE: u16 = 50;
+/// 客户端配置中默认填写的模型
+const DEFAULT_CLIENT_MODEL: &str = "gemini-2.5-flash";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SetupOptions {
+    /// 期望的端口 (为空时使用当前配置)
+    pub port: Option<u16>,
+    /// 已有 Key 时是否重新生成
+    pub regenerate_api_key: bool,
+    /// 客户端配置中填写的模型 (为空时使用 gemini-2.5-flash)
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PortCheck {
+    pub port: u16,
+    /// 端口空闲，或正被本应用的反代服务使用
+    pub available: bool,
+    pub proxy_running: bool,
+    /// 端口被其他进程占用时建议的空闲端口
+    pub suggested_port: Option<u16>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SetupProgress {
+    pub step: String,
+    /// None 表示该步骤进行中
+    pub status: Option<CheckStatus>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ClientSetup {
+    pub client: String,
+    /// 客户端设置项 (标签, 值)，按界面中的填写顺序
+    pub settings: Vec<(String, String)>,
+    /// 可直接复制的文本
+    pub snippet: String,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SetupResult {
+    pub port: u16,
+    pub base_url: String,
+    pub api_key: String,
+    pub api_key_created: bool,
+    pub generation: DiagnosticCheck,
+    pub clients: Vec<ClientSetup>,
+}
+
+fn is_port_free(port: u16) -> bool {
+    std::net::TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+async fn is_proxy_listening(port: u16) -> bool {
+    let Ok(client) = reqwest::Client::builder().timeout(Duration::from_secs(3)).build() else {
+        return false;
+    };
+    match client.get(format!("http://127.0.0.1:{}/healthz", port)).send().await {
+        Ok(resp) if resp.status().is_success() => resp
+            .json::<Value>()
+            .await
+            .map(|body| body.get("status").is_some())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// 检测端口是否可用；被其他进程占用时给出向后最近的空闲端口
+pub async fn check_port(port: u16) -> PortCheck {
+    if is_port_free(port) {
+        return PortCheck { port, available: true, proxy_running: false, suggested_port: None, message: format!("Port {} is free", port) };
+    }
+    if is_proxy_listening(port).await {
+        return PortCheck {
+            port,
+            available: true,
+            proxy_running: true,
+            suggested_port: None,
+            message: format!("The proxy is already listening on port {}", port),
+        };
+    }
+    let suggested_port = (1..=PORT_SEARCH_RANGE)
+        .filter_map(|offset| port.checked_add(offset))
+        .find(|p| is_port_free(*p));
+    PortCheck {
+        port,
+        available: false,
+        proxy_running: false,
+        suggested_port,
+        message: match suggested_port {
+            Some(p) => format!("Port {} is used by another program; port {} is free", port, p),
+            None => format!("Port {} is used by another program and no free port was found nearby", port),
+        },
+    }
+}
+
+fn generate_api_key() -> String {
+    format!("sk-{}", uuid::Uuid::new_v4().simple())
+}
+
+/// 生成各客户端的配置说明
+pub fn client_setups(base_url: &str, api_key: &str, model: &str) -> Vec<ClientSetup> {
+    let openai_base = format!("{}/v1", base_url);
+    let build = |client: &str, settings: Vec<(&str, String)>, note: Option<&str>| {
+        let settings: Vec<(String, String)> = settings.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+        ClientSetup {
+            client: client.to_string(),
+            snippet: settings.iter().map(|(k, v)| format!("{}: {}", k, v)).collect::<Vec<_>>().join("\n"),
+            settings,
+            note: note.map(|n| n.to_string()),
+        }
+    };
+    vec![
+        build(
+            "Cline",
+            vec![
+                ("API Provider", "OpenAI Compatible".to_string()),
+                ("Base URL", openai_base.clone()),
+                ("API Key", api_key.to_string()),
+                ("Model ID", model.to_string()),
+            ],
+            None,
+        ),
+        build(
+            "Cursor",
+            vec![
+                ("OpenAI API Key", api_key.to_string()),
+                ("Override OpenAI Base URL", openai_base.clone()),
+                ("Custom model", model.to_string()),
+            ],
+            Some("Cursor sends requests from its own servers, so a 127.0.0.1 address only works through a public tunnel to this port."),
+        ),
+        build(
+            "SillyTavern",
+            vec![
+                ("API", "Chat Completion".to_string()),
+                ("Chat Completion Source", "Custom (OpenAI-compatible)".to_string()),
+                ("Custom Endpoint (Base URL)", openai_base),
+                ("Custom API Key", api_key.to_string()),
+                ("Model ID", model.to_string()),
+            ],
+            None,
+        ),
+    ]
+}
+
+/// 通过正在运行的反代服务发送一次测试请求 (验证 Key 与完整链路)
+async fn test_through_proxy(base_url: &str, api_key: &str, model: &str) -> DiagnosticCheck {
+    let started = Instant::now();
+    let result = async {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        let response = client
+            .post(format!("{}/v1/chat/completions", base_url))
+            .bearer_auth(api_key)
+            .json(&json!({
+                "model": model,
+                "messages": [{ "role": "user", "content": "Reply with the single word OK." }],
+                "max_tokens": 16,
+                "stream": false
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            let snippet: String = diagnostics::redact_text(&body.to_string()).chars().take(300).collect();
+            return Err(format!("HTTP {}: {}", status, snippet));
+        }
+        Ok(body
+            .pointer("/choices/0/message/content")
+            .and_then(|c| c.as_str())
+            .unwrap_or("")
+            .trim()
+            .chars()
+            .take(50)
+            .collect::<String>())
+    }
+    .await;
+
+    let (status, message, details) = match result {
+        Ok(reply) => (
+            CheckStatus::Pass,
+            format!("{} responded through the proxy in {}ms", model, started.elapsed().as_millis()),
+            json!({ "model": model, "reply": reply }),
+        ),
+        Err(e) => (CheckStatus::Fail, e, Value::Null),
+    };
+    DiagnosticCheck {
+        name: "generation".to_string(),
+        status,
+        message,
+        duration_ms: started.elapsed().as_millis() as u64,
+        details,
+    }
+}
+
+fn emit_progress(app: Option<&tauri::AppHandle>, step: &str, status: Option<CheckStatus>, message: &str) {
+    if let Some(app) = app {
+        let _ = app.emit(
+            "setup://progress",
+            SetupProgress { step: step.to_string(), status, message: message.to_string() },
+        );
+    }
+}
+
+/// 运行首次启动向导。端口或 Key 有变化时写回配置
+pub async fn run(app: Option<&tauri::AppHandle>, options: SetupOptions) -> Result<SetupResult, String> {
+    logger::log_info("[Setup] Running first-run setup");
+    let mut app_config = config::load_app_config()?;
+    let mut config_changed = false;
+
+    // 1. 端口
+    let requested = options.port.unwrap_or(app_config.proxy.port);
+    emit_progress(app, "port", None, &format!("Checking port {}", requested));
+    let port_check = check_port(requested).await;
+    let port = match (port_check.available, port_check.suggested_port) {
+        (true, _) => requested,
+        (false, Some(p)) => p,
+        (false, None) => {
+            emit_progress(app, "port", Some(CheckStatus::Fail), &port_check.message);
+            return Err(port_check.message);
+        }
+    };
+    let port_status = if port_check.available { CheckStatus::Pass } else { CheckStatus::Warn };
+    emit_progress(app, "port", Some(port_status), &port_check.message);
+    if app_config.proxy.port != port {
+        app_config.proxy.port = port;
+        config_changed = true;
+    }
+
+    // 2. API Key
+    let api_key_created = options.regenerate_api_key || app_config.proxy.api_key.trim().is_empty();
+    if api_key_created {
+        app_config.proxy.api_key = generate_api_key();
+        config_changed = true;
+    }
+    let api_key = app_config.proxy.api_key.clone();
+    emit_progress(
+        app,
+        "api_key",
+        Some(CheckStatus::Pass),
+        if api_key_created { "Created a new API key" } else { "Using the existing API key" },
+    );
+
+    if config_changed {
+        config::save_app_config(&app_config)?;
+    }
+    // 反代正在运行时同步新 Key，后续测试请求与客户端才能通过鉴权
+    if api_key_created {
+        if let Some(app) = app {
+            let state = app.state::<crate::commands::proxy::ProxyServiceState>();
+            if let Some(instance) = state.instance.read().await.as_ref() {
+                instance.axum_server.update_security(&app_config.proxy).await;
+            }
+        }
+    }
+
+    // 3. 测试生成: 反代已在该端口运行时走完整链路，否则直接请求上游
+    let base_url = format!("http://127.0.0.1:{}", port);
+    let model = options
+        .model
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty())
+        .unwrap_or_else(|| DEFAULT_CLIENT_MODEL.to_string());
+    emit_progress(app, "generation", None, "Sending a test request");
+    let generation = if port_check.proxy_running {
+        test_through_proxy(&base_url, &api_key, &model).await
+    } else {
+        diagnostics::run_generation_test().await
+    };
+    emit_progress(app, "generation", Some(generation.status), &generation.message);
+    if generation.status == CheckStatus::Fail {
+        logger::log_warn(&format!("[Setup] Test generation failed: {}", generation.message));
+    }
+
+    let clients = client_setups(&base_url, &api_key, &model);
+    emit_progress(app, "done", Some(CheckStatus::Pass), &base_url);
+    Ok(SetupResult { port, base_url, api_key, api_key_created, generation, clients })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_setups() {
+        let clients = client_setups("http://127.0.0.1:8045", "sk-test", "gemini-2.5-flash");
+        let names: Vec<&str> = clients.iter().map(|c| c.client.as_str()).collect();
+        assert_eq!(names, vec!["Cline", "Cursor", "SillyTavern"]);
+        for c in &clients {
+            assert!(c.snippet.contains("http://127.0.0.1:8045/v1"));
+            assert!(c.snippet.contains("sk-test"));
+        }
+        assert_eq!(clients[0].settings[0], ("API Provider".to_string(), "OpenAI Compatible".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_check_port_suggests_free_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let result = check_port(port).await;
+        assert!(!result.available);
+        assert!(result.suggested_port.is_some_and(|p| p > port));
+    }
+}
@@ -34,6 +34,24 @@ pub struct AccountTokenStats {
     pub request_count: u64,
 }
 
+/// Per-tag token statistics (tags come from the `X-AG-Tag` request header)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagTokenStats {
+    pub tag: String,
+    pub total_input_tokens: u64,
+    pub total_output_tokens: u64,
+    pub total_tokens: u64,
+    pub request_count: u64,
+}
+
+/// Per-model token statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelTokenStats {
+    pub model: String,
+    pub total_tokens: u64,
+    pub request_count: u64,
+}
+
 /// Summary statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenStatsSummary {
@@ -103,6 +121,21 @@ pub fn init_db() -> Result<(), String> {
         )",
         [],
     ).map_err(|e| e.to_string())?;
+
+    // Raw usage keeps the tag string; hourly aggregation per single tag (`key=value`)
+    let _ = conn.execute("ALTER TABLE token_usage ADD COLUMN tags TEXT", []);
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS token_stats_tag_hourly (
+            hour_bucket TEXT NOT NULL,
+            tag TEXT NOT NULL,
+            total_input_tokens INTEGER NOT NULL DEFAULT 0,
+            total_output_tokens INTEGER NOT NULL DEFAULT 0,
+            total_tokens INTEGER NOT NULL DEFAULT 0,
+            request_count INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (hour_bucket, tag)
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
     
     Ok(())
 }
@@ -113,6 +146,7 @@ pub fn record_usage(
     model: &str,
     input_tokens: u32,
     output_tokens: u32,
+    tags: Option<&str>,
 ) -> Result<(), String> {
     let conn = connect_db()?;
     let timestamp = chrono::Utc::now().timestamp();
@@ -120,9 +154,9 @@ pub fn record_usage(
     
     // Insert into raw usage table
     conn.execute(
-        "INSERT INTO token_usage (timestamp, account_email, model, input_tokens, output_tokens, total_tokens)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        params![timestamp, account_email, model, input_tokens, output_tokens, total_tokens],
+        "INSERT INTO token_usage (timestamp, account_email, model, input_tokens, output_tokens, total_tokens, tags)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![timestamp, account_email, model, input_tokens, output_tokens, total_tokens, tags],
     ).map_err(|e| e.to_string())?;
     
 
@@ -137,6 +171,19 @@ pub fn record_usage(
             request_count = request_count + 1",
         params![hour_bucket, account_email, input_tokens, output_tokens, total_tokens],
     ).map_err(|e| e.to_string())?;
+
+    for tag in tags.into_iter().flat_map(|t| t.split(',')).filter(|t| !t.is_empty()) {
+        conn.execute(
+            "INSERT INTO token_stats_tag_hourly (hour_bucket, tag, total_input_tokens, total_output_tokens, total_tokens, request_count)
+             VALUES (?1, ?2, ?3, ?4, ?5, 1)
+             ON CONFLICT(hour_bucket, tag) DO UPDATE SET
+                total_input_tokens = total_input_tokens + ?3,
+                total_output_tokens = total_output_tokens + ?4,
+                total_tokens = total_tokens + ?5,
+                request_count = request_count + 1",
+            params![hour_bucket, tag, input_tokens, output_tokens, total_tokens],
+        ).map_err(|e| e.to_string())?;
+    }
     
     Ok(())
 }
@@ -281,6 +328,139 @@ pub fn get_account_stats(hours: i64) -> Result<Vec<AccountTokenStats>, String> {
     Ok(result)
 }
 
+/// Get per-account token statistics for the current UTC day
+pub fn get_today_account_stats() -> Result<Vec<AccountTokenStats>, String> {
+    let conn = connect_db()?;
+    let day_start = chrono::Utc::now().format("%Y-%m-%d 00:00").to_string();
+
+    let mut stmt = conn.prepare(
+        "SELECT account_email,
+                SUM(total_input_tokens) as input,
+                SUM(total_output_tokens) as output,
+                SUM(total_tokens) as total,
+                SUM(request_count) as count
+         FROM token_stats_hourly
+         WHERE hour_bucket >= ?1
+         GROUP BY account_email"
+    ).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map([day_start], |row| {
+        Ok(AccountTokenStats {
+            account_email: row.get(0)?,
+            total_input_tokens: row.get(1)?,
+            total_output_tokens: row.get(2)?,
+            total_tokens: row.get(3)?,
+            request_count: row.get(4)?,
+        })
+    }).map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        result.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(result)
+}
+
+/// Get per-account statistics between two unix timestamps (seconds, end exclusive),
+/// read from the raw usage table so local-day boundaries are exact
+pub fn get_account_stats_between(start_ts: i64, end_ts: i64) -> Result<Vec<AccountTokenStats>, String> {
+    let conn = connect_db()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT account_email,
+                SUM(input_tokens) as input,
+                SUM(output_tokens) as output,
+                SUM(total_tokens) as total,
+                COUNT(*) as count
+         FROM token_usage
+         WHERE timestamp >= ?1 AND timestamp < ?2
+         GROUP BY account_email
+         ORDER BY total DESC"
+    ).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map(params![start_ts, end_ts], |row| {
+        Ok(AccountTokenStats {
+            account_email: row.get(0)?,
+            total_input_tokens: row.get(1)?,
+            total_output_tokens: row.get(2)?,
+            total_tokens: row.get(3)?,
+            request_count: row.get(4)?,
+        })
+    }).map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        result.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(result)
+}
+
+/// Get the most used models between two unix timestamps (seconds, end exclusive)
+pub fn get_model_stats_between(start_ts: i64, end_ts: i64, limit: usize) -> Result<Vec<ModelTokenStats>, String> {
+    let conn = connect_db()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT model, SUM(total_tokens) as total, COUNT(*) as count
+         FROM token_usage
+         WHERE timestamp >= ?1 AND timestamp < ?2
+         GROUP BY model
+         ORDER BY count DESC, total DESC
+         LIMIT ?3"
+    ).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map(params![start_ts, end_ts, limit as i64], |row| {
+        Ok(ModelTokenStats {
+            model: row.get(0)?,
+            total_tokens: row.get(1)?,
+            request_count: row.get(2)?,
+        })
+    }).map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        result.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(result)
+}
+
+/// Get per-tag statistics for a time range, optionally only tags with the given key
+/// (e.g. `project` matches `project=foo` and `project=bar`)
+pub fn get_tag_stats(hours: i64, key: Option<&str>) -> Result<Vec<TagTokenStats>, String> {
+    let conn = connect_db()?;
+    let cutoff = chrono::Utc::now() - chrono::Duration::hours(hours);
+    let cutoff_bucket = cutoff.format("%Y-%m-%d %H:00").to_string();
+    let key = key.map(|k| k.trim().to_lowercase()).filter(|k| !k.is_empty());
+
+    let mut stmt = conn.prepare(
+        "SELECT tag,
+                SUM(total_input_tokens) as input,
+                SUM(total_output_tokens) as output,
+                SUM(total_tokens) as total,
+                SUM(request_count) as count
+         FROM token_stats_tag_hourly
+         WHERE hour_bucket >= ?1
+           AND (?2 IS NULL OR tag = ?2 OR substr(tag, 1, length(?2) + 1) = ?2 || '=')
+         GROUP BY tag
+         ORDER BY total DESC"
+    ).map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map(params![cutoff_bucket, key], |row| {
+        Ok(TagTokenStats {
+            tag: row.get(0)?,
+            total_input_tokens: row.get(1)?,
+            total_output_tokens: row.get(2)?,
+            total_tokens: row.get(3)?,
+            request_count: row.get(4)?,
+        })
+    }).map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        result.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(result)
+}
+
 /// Get summary statistics for a time range
 pub fn get_summary_stats(hours: i64) -> Result<TokenStatsSummary, String> {
     let conn = connect_db()?;
@@ -313,6 +493,13 @@ pub fn get_summary_stats(hours: i64) -> Result<TokenStatsSummary, String> {
     })
 }
 
+/// Flush the WAL into the main database file (used on shutdown)
+pub fn checkpoint() -> Result<(), String> {
+    let conn = connect_db()?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);").map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 /// Clean up old data (keep last N days of raw data)
 pub fn cleanup_old_data(days: i64) -> Result<usize, String> {
     let conn = connect_db()?;
@@ -329,7 +516,11 @@ pub fn cleanup_old_data(days: i64) -> Result<usize, String> {
         .to_string();
     conn.execute(
         "DELETE FROM token_stats_hourly WHERE hour_bucket < ?1",
-        [cutoff_bucket],
+        [&cutoff_bucket],
+    ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM token_stats_tag_hourly WHERE hour_bucket < ?1",
+        [&cutoff_bucket],
     ).map_err(|e| e.to_string())?;
     
     conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
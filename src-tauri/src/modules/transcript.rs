@@ -0,0 +1,652 @@
+// 会话记录导出 (Markdown / OpenAI JSONL)
+// 以监控日志中的 session_id (会话指纹) 为索引，取该会话最后一次成功请求的完整上下文 + 助手回复，
+// 统一转换成 OpenAI 消息格式后输出。JSONL 每行一个 {"messages": [...]}，可直接用作微调数据集。
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::proxy::monitor::ProxyRequestLog;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptFormat {
+    Markdown,
+    Jsonl,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Image(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Message {
+    role: String,
+    parts: Vec<Part>,
+    tool_calls: Vec<ToolCall>,
+    tool_call_id: Option<String>,
+}
+
+impl Message {
+    fn new(role: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            parts: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    fn text(role: &str, text: &str) -> Self {
+        let mut msg = Self::new(role);
+        msg.parts.push(Part::Text(text.to_string()));
+        msg
+    }
+
+    fn is_empty(&self) -> bool {
+        self.parts.is_empty() && self.tool_calls.is_empty()
+    }
+}
+
+/// 导出指定会话，返回写出的消息条数
+pub fn export_session(session_id: &str, format: TranscriptFormat, file_path: &str) -> Result<usize, String> {
+    let logs = crate::modules::proxy_db::get_logs_by_session(session_id)?;
+    if logs.is_empty() {
+        return Err(format!("No logs found for conversation {}", session_id));
+    }
+
+    let messages = build_transcript(&logs)
+        .ok_or_else(|| format!("Conversation {} has no exportable request", session_id))?;
+
+    let output = match format {
+        TranscriptFormat::Markdown => {
+            let attachments_dir = attachments_dir_for(Path::new(file_path));
+            render_markdown(session_id, &messages, Some(&attachments_dir))?
+        }
+        TranscriptFormat::Jsonl => render_jsonl(&messages)?,
+    };
+
+    std::fs::write(file_path, output).map_err(|e| format!("Failed to write file: {}", e))?;
+    Ok(messages.len())
+}
+
+/// 每次请求都携带完整历史，所以取最后一条请求体可解析的成功日志即可还原全部对话
+/// (超过 2MB 的请求体在日志中被截断，无法解析时向前回退)
+fn build_transcript(logs: &[ProxyRequestLog]) -> Option<Vec<Message>> {
+    for log in logs.iter().rev() {
+        if log.status < 200 || log.status >= 400 {
+            continue;
+        }
+        let Some(request) = log
+            .request_body
+            .as_deref()
+            .and_then(|b| serde_json::from_str::<Value>(b).ok())
+        else {
+            continue;
+        };
+
+        let mut messages = parse_request(&request, log.protocol.as_deref());
+        if messages.is_empty() {
+            continue;
+        }
+        if let Some(reply) = log.response_body.as_deref().and_then(parse_response) {
+            messages.push(reply);
+        }
+        return Some(messages);
+    }
+    None
+}
+
+fn parse_request(body: &Value, protocol: Option<&str>) -> Vec<Message> {
+    if body.get("contents").is_some() || protocol == Some("gemini") {
+        return parse_gemini_request(body);
+    }
+    if protocol == Some("anthropic") || body.get("system").is_some() {
+        return parse_anthropic_request(body);
+    }
+    parse_openai_request(body)
+}
+
+// ===== OpenAI =====
+
+fn parse_openai_request(body: &Value) -> Vec<Message> {
+    body.get("messages")
+        .and_then(|m| m.as_array())
+        .map(|arr| arr.iter().map(parse_openai_message).filter(|m| !m.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+fn parse_openai_message(msg: &Value) -> Message {
+    let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+    let mut out = Message::new(role);
+
+    match msg.get("content") {
+        Some(Value::String(s)) if !s.is_empty() => out.parts.push(Part::Text(s.clone())),
+        Some(Value::Array(items)) => {
+            for item in items {
+                match item.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => {
+                        if let Some(t) = item.get("text").and_then(|t| t.as_str()) {
+                            out.parts.push(Part::Text(t.to_string()));
+                        }
+                    }
+                    Some("image_url") => {
+                        let url = item
+                            .get("image_url")
+                            .and_then(|i| i.get("url").or(Some(i)))
+                            .and_then(|u| u.as_str());
+                        if let Some(url) = url {
+                            out.parts.push(Part::Image(url.to_string()));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(calls) = msg.get("tool_calls").and_then(|c| c.as_array()) {
+        for call in calls {
+            let func = call.get("function");
+            out.tool_calls.push(ToolCall {
+                id: call.get("id").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                name: func
+                    .and_then(|f| f.get("name"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                arguments: func
+                    .and_then(|f| f.get("arguments"))
+                    .map(stringify_arguments)
+                    .unwrap_or_else(|| "{}".to_string()),
+            });
+        }
+    }
+    out.tool_call_id = msg.get("tool_call_id").and_then(|v| v.as_str()).map(String::from);
+    out
+}
+
+// ===== Anthropic =====
+
+fn parse_anthropic_request(body: &Value) -> Vec<Message> {
+    let mut out = Vec::new();
+
+    match body.get("system") {
+        Some(Value::String(s)) if !s.is_empty() => out.push(Message::text("system", s)),
+        Some(Value::Array(blocks)) => {
+            let text: Vec<&str> = blocks
+                .iter()
+                .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+                .collect();
+            if !text.is_empty() {
+                out.push(Message::text("system", &text.join("\n\n")));
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(messages) = body.get("messages").and_then(|m| m.as_array()) {
+        for msg in messages {
+            let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+            match msg.get("content") {
+                Some(Value::String(s)) => out.push(Message::text(role, s)),
+                Some(Value::Array(blocks)) => out.extend(anthropic_blocks_to_messages(role, blocks)),
+                _ => {}
+            }
+        }
+    }
+    out.retain(|m| !m.is_empty());
+    out
+}
+
+/// tool_result 块在 OpenAI 格式中是独立的 tool 消息，需要拆分出来
+fn anthropic_blocks_to_messages(role: &str, blocks: &[Value]) -> Vec<Message> {
+    let mut tool_messages = Vec::new();
+    let mut main = Message::new(role);
+
+    for block in blocks {
+        match block.get("type").and_then(|t| t.as_str()) {
+            Some("text") => {
+                if let Some(t) = block.get("text").and_then(|t| t.as_str()) {
+                    main.parts.push(Part::Text(t.to_string()));
+                }
+            }
+            Some("image") => {
+                if let Some(url) = anthropic_image_url(block) {
+                    main.parts.push(Part::Image(url));
+                }
+            }
+            Some("tool_use") => main.tool_calls.push(ToolCall {
+                id: block.get("id").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                name: block.get("name").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                arguments: block.get("input").map(stringify_arguments).unwrap_or_else(|| "{}".to_string()),
+            }),
+            Some("tool_result") => {
+                let mut tool = Message::new("tool");
+                tool.tool_call_id = block.get("tool_use_id").and_then(|v| v.as_str()).map(String::from);
+                match block.get("content") {
+                    Some(Value::String(s)) => tool.parts.push(Part::Text(s.clone())),
+                    Some(Value::Array(inner)) => {
+                        for item in inner {
+                            if let Some(t) = item.get("text").and_then(|t| t.as_str()) {
+                                tool.parts.push(Part::Text(t.to_string()));
+                            } else if let Some(url) = anthropic_image_url(item) {
+                                tool.parts.push(Part::Image(url));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                tool_messages.push(tool);
+            }
+            // thinking / redacted_thinking 不导出
+            _ => {}
+        }
+    }
+
+    let mut out = tool_messages;
+    if !main.is_empty() {
+        out.push(main);
+    }
+    out
+}
+
+fn anthropic_image_url(block: &Value) -> Option<String> {
+    let source = block.get("source")?;
+    match source.get("type").and_then(|t| t.as_str()) {
+        Some("base64") => Some(format!(
+            "data:{};base64,{}",
+            source.get("media_type").and_then(|m| m.as_str()).unwrap_or("image/png"),
+            source.get("data").and_then(|d| d.as_str())?
+        )),
+        Some("url") => source.get("url").and_then(|u| u.as_str()).map(String::from),
+        _ => None,
+    }
+}
+
+// ===== Gemini =====
+
+fn parse_gemini_request(body: &Value) -> Vec<Message> {
+    let mut out = Vec::new();
+
+    let system = body.get("systemInstruction").or_else(|| body.get("system_instruction"));
+    if let Some(parts) = system.and_then(|s| s.get("parts")).and_then(|p| p.as_array()) {
+        let text: Vec<&str> = parts.iter().filter_map(|p| p.get("text").and_then(|t| t.as_str())).collect();
+        if !text.is_empty() {
+            out.push(Message::text("system", &text.join("\n\n")));
+        }
+    }
+
+    if let Some(contents) = body.get("contents").and_then(|c| c.as_array()) {
+        for content in contents {
+            let role = match content.get("role").and_then(|r| r.as_str()) {
+                Some("model") => "assistant",
+                _ => "user",
+            };
+            if let Some(parts) = content.get("parts").and_then(|p| p.as_array()) {
+                out.extend(gemini_parts_to_messages(role, parts));
+            }
+        }
+    }
+    out.retain(|m| !m.is_empty());
+    out
+}
+
+fn gemini_parts_to_messages(role: &str, parts: &[Value]) -> Vec<Message> {
+    let mut tool_messages = Vec::new();
+    let mut main = Message::new(role);
+
+    for part in parts {
+        if part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false) {
+            continue;
+        }
+        if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+            main.parts.push(Part::Text(t.to_string()));
+        } else if let Some(data) = part.get("inlineData").or_else(|| part.get("inline_data")) {
+            let mime = data
+                .get("mimeType")
+                .or_else(|| data.get("mime_type"))
+                .and_then(|m| m.as_str())
+                .unwrap_or("image/png");
+            if let Some(b64) = data.get("data").and_then(|d| d.as_str()) {
+                main.parts.push(Part::Image(format!("data:{};base64,{}", mime, b64)));
+            }
+        } else if let Some(file) = part.get("fileData").or_else(|| part.get("file_data")) {
+            if let Some(uri) = file.get("fileUri").or_else(|| file.get("file_uri")).and_then(|u| u.as_str()) {
+                main.parts.push(Part::Image(uri.to_string()));
+            }
+        } else if let Some(call) = part.get("functionCall") {
+            let name = call.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+            main.tool_calls.push(ToolCall {
+                id: call
+                    .get("id")
+                    .and_then(|v| v.as_str())
+                    .map(String::from)
+                    .unwrap_or_else(|| name.to_string()),
+                name: name.to_string(),
+                arguments: call.get("args").map(stringify_arguments).unwrap_or_else(|| "{}".to_string()),
+            });
+        } else if let Some(resp) = part.get("functionResponse") {
+            let name = resp.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+            let mut tool = Message::new("tool");
+            tool.tool_call_id = Some(
+                resp.get("id")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(name)
+                    .to_string(),
+            );
+            tool.parts.push(Part::Text(
+                resp.get("response").map(stringify_arguments).unwrap_or_default(),
+            ));
+            tool_messages.push(tool);
+        }
+    }
+
+    let mut out = tool_messages;
+    if !main.is_empty() {
+        out.push(main);
+    }
+    out
+}
+
+// ===== 响应 =====
+
+/// 解析日志中的响应体: 监控中间件合并后的 SSE 结果、OpenAI / Anthropic / Gemini 原始 JSON，或原始 SSE 文本
+fn parse_response(body: &str) -> Option<Message> {
+    if let Ok(value) = serde_json::from_str::<Value>(body) {
+        return parse_response_value(&value).filter(|m| !m.is_empty());
+    }
+
+    // 原始 SSE: 逐条累加
+    let mut merged = Message::new("assistant");
+    let mut text = String::new();
+    for line in body.lines() {
+        let Some(data) = line.trim().strip_prefix("data:") else { continue };
+        let Ok(chunk) = serde_json::from_str::<Value>(data.trim()) else { continue };
+
+        if let Some(delta) = chunk.pointer("/choices/0/delta/content").and_then(|c| c.as_str()) {
+            text.push_str(delta);
+            continue;
+        }
+        if let Some(msg) = parse_response_value(&chunk) {
+            for part in msg.parts {
+                match part {
+                    Part::Text(t) => text.push_str(&t),
+                    image => merged.parts.push(image),
+                }
+            }
+            merged.tool_calls.extend(msg.tool_calls);
+        }
+    }
+    if !text.is_empty() {
+        merged.parts.insert(0, Part::Text(text));
+    }
+    (!merged.is_empty()).then_some(merged)
+}
+
+fn parse_response_value(value: &Value) -> Option<Message> {
+    // v1internal 外层包装
+    let value = value.get("response").unwrap_or(value);
+
+    if let Some(message) = value.pointer("/choices/0/message") {
+        let mut msg = parse_openai_message(message);
+        msg.role = "assistant".to_string();
+        return Some(msg);
+    }
+    if let Some(parts) = value.pointer("/candidates/0/content/parts").and_then(|p| p.as_array()) {
+        return gemini_parts_to_messages("assistant", parts).pop();
+    }
+    match value.get("content") {
+        // 监控中间件合并后的流式结果
+        Some(Value::String(s)) => Some(Message::text("assistant", s)),
+        Some(Value::Array(blocks)) => anthropic_blocks_to_messages("assistant", blocks).pop(),
+        _ => None,
+    }
+}
+
+fn stringify_arguments(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// ===== 输出 =====
+
+fn render_jsonl(messages: &[Message]) -> Result<String, String> {
+    let openai: Vec<Value> = messages.iter().map(to_openai_message).collect();
+    let line = serde_json::to_string(&json!({ "messages": openai }))
+        .map_err(|e| format!("Failed to serialize transcript: {}", e))?;
+    Ok(format!("{}\n", line))
+}
+
+fn to_openai_message(msg: &Message) -> Value {
+    let has_image = msg.parts.iter().any(|p| matches!(p, Part::Image(_)));
+    let content = if has_image {
+        Value::Array(
+            msg.parts
+                .iter()
+                .map(|p| match p {
+                    Part::Text(t) => json!({ "type": "text", "text": t }),
+                    Part::Image(url) => json!({ "type": "image_url", "image_url": { "url": url } }),
+                })
+                .collect(),
+        )
+    } else {
+        let text: Vec<&str> = msg
+            .parts
+            .iter()
+            .filter_map(|p| match p {
+                Part::Text(t) => Some(t.as_str()),
+                Part::Image(_) => None,
+            })
+            .collect();
+        if text.is_empty() && !msg.tool_calls.is_empty() {
+            Value::Null
+        } else {
+            Value::String(text.join("\n\n"))
+        }
+    };
+
+    let mut out = json!({ "role": msg.role, "content": content });
+    if !msg.tool_calls.is_empty() {
+        out["tool_calls"] = Value::Array(
+            msg.tool_calls
+                .iter()
+                .map(|c| {
+                    json!({
+                        "id": c.id,
+                        "type": "function",
+                        "function": { "name": c.name, "arguments": c.arguments }
+                    })
+                })
+                .collect(),
+        );
+    }
+    if let Some(id) = &msg.tool_call_id {
+        out["tool_call_id"] = json!(id);
+    }
+    out
+}
+
+fn attachments_dir_for(file_path: &Path) -> PathBuf {
+    let stem = file_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "transcript".to_string());
+    file_path.with_file_name(format!("{}_attachments", stem))
+}
+
+/// data URI 图片写入附件目录并以相对路径链接; attachments_dir 为 None 时只输出占位说明
+fn render_markdown(session_id: &str, messages: &[Message], attachments_dir: Option<&Path>) -> Result<String, String> {
+    let mut out = format!("# Conversation {}\n\n", session_id);
+    let mut image_index = 0usize;
+
+    for msg in messages {
+        let heading = match msg.role.as_str() {
+            "system" => "System".to_string(),
+            "user" => "User".to_string(),
+            "assistant" => "Assistant".to_string(),
+            "tool" => match &msg.tool_call_id {
+                Some(id) => format!("Tool result (`{}`)", id),
+                None => "Tool result".to_string(),
+            },
+            other => other.to_string(),
+        };
+        out.push_str(&format!("## {}\n\n", heading));
+
+        for part in &msg.parts {
+            match part {
+                Part::Text(t) => {
+                    out.push_str(t.trim_end());
+                    out.push_str("\n\n");
+                }
+                Part::Image(url) => {
+                    image_index += 1;
+                    let link = if url.starts_with("data:") {
+                        match attachments_dir {
+                            Some(dir) => save_data_uri(dir, url, image_index)?,
+                            None => format!("attachment-{}", image_index),
+                        }
+                    } else {
+                        url.clone()
+                    };
+                    out.push_str(&format!("![image {}]({})\n\n", image_index, link));
+                }
+            }
+        }
+
+        for call in &msg.tool_calls {
+            let pretty = serde_json::from_str::<Value>(&call.arguments)
+                .ok()
+                .and_then(|v| serde_json::to_string_pretty(&v).ok())
+                .unwrap_or_else(|| call.arguments.clone());
+            out.push_str(&format!("**Tool call** `{}` (`{}`)\n\n```json\n{}\n```\n\n", call.name, call.id, pretty));
+        }
+    }
+    Ok(out)
+}
+
+fn save_data_uri(dir: &Path, url: &str, index: usize) -> Result<String, String> {
+    let (meta, data) = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(','))
+        .ok_or_else(|| "Malformed data URI".to_string())?;
+    let mime = meta.split(';').next().unwrap_or("image/png");
+    let ext = mime.rsplit('/').next().filter(|e| !e.is_empty()).unwrap_or("bin");
+    let bytes = general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| format!("Failed to decode image attachment: {}", e))?;
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create attachments dir: {}", e))?;
+    let file_name = format!("image_{:03}.{}", index, ext);
+    std::fs::write(dir.join(&file_name), bytes).map_err(|e| format!("Failed to write attachment: {}", e))?;
+
+    let dir_name = dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    Ok(format!("{}/{}", dir_name, file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(request: Value, response: &str, protocol: &str) -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: "1".to_string(),
+            timestamp: 0,
+            method: "POST".to_string(),
+            url: "/v1/messages".to_string(),
+            status: 200,
+            duration: 10,
+            model: None,
+            mapped_model: None,
+            account_email: None,
+            error: None,
+            request_body: Some(request.to_string()),
+            response_body: Some(response.to_string()),
+            input_tokens: None,
+            output_tokens: None,
+            protocol: Some(protocol.to_string()),
+            session_id: Some("sid-test".to_string()),
+            notes: None,
+            tags: None,
+            ttft_ms: None,
+            tokens_per_sec: None,
+            chunk_count: None,
+            message_id: None,
+            parent_message_id: None,
+            error_code: None,
+            error_hint: None,
+        }
+    }
+
+    #[test]
+    fn test_anthropic_tool_exchange_to_openai_jsonl() {
+        let request = json!({
+            "system": "be brief",
+            "messages": [
+                {"role": "user", "content": "weather?"},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {"city": "Paris"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1", "content": "sunny"}
+                ]}
+            ]
+        });
+        let response = r#"{"content": "It is sunny."}"#;
+        let messages = build_transcript(&[log(request, response, "anthropic")]).unwrap();
+
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["system", "user", "assistant", "tool", "assistant"]);
+
+        let jsonl = render_jsonl(&messages).unwrap();
+        let parsed: Value = serde_json::from_str(jsonl.trim()).unwrap();
+        assert_eq!(parsed["messages"][2]["tool_calls"][0]["function"]["name"], "get_weather");
+        assert_eq!(parsed["messages"][2]["content"], Value::Null);
+        assert_eq!(parsed["messages"][3]["tool_call_id"], "toolu_1");
+        assert_eq!(parsed["messages"][4]["content"], "It is sunny.");
+    }
+
+    #[test]
+    fn test_gemini_request_with_image_to_markdown() {
+        let request = json!({
+            "contents": [
+                {"role": "user", "parts": [
+                    {"text": "describe"},
+                    {"inlineData": {"mimeType": "image/png", "data": "aGVsbG8="}}
+                ]}
+            ]
+        });
+        let response = r#"{"candidates":[{"content":{"parts":[{"text":"a cat"}]}}]}"#;
+        let messages = build_transcript(&[log(request, response, "gemini")]).unwrap();
+        let md = render_markdown("sid-test", &messages, None).unwrap();
+
+        assert!(md.contains("## User"));
+        assert!(md.contains("![image 1](attachment-1)"));
+        assert!(md.contains("## Assistant\n\na cat"));
+    }
+
+    #[test]
+    fn test_skips_failed_and_unparseable_logs() {
+        let good = log(json!({"messages": [{"role": "user", "content": "hi"}]}), "{}", "openai");
+        let mut failed = log(json!({"messages": [{"role": "user", "content": "hi again"}]}), "{}", "openai");
+        failed.status = 500;
+        let mut truncated = good.clone();
+        truncated.request_body = Some("{\"messages\": [...".to_string());
+
+        let messages = build_transcript(&[good, failed, truncated]).unwrap();
+        assert_eq!(messages, vec![Message::text("user", "hi")]);
+    }
+}
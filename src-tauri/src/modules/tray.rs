@@ -1,12 +1,136 @@
+use serde::Serialize;
 use tauri::{
     image::Image,
-    menu::{Menu, MenuItem, PredefinedMenuItem},
+    menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
     tray::{MouseButton, TrayIconBuilder, TrayIconEvent},
     Manager, Runtime, Emitter, Listener,
 };
+use crate::commands::proxy::ProxyServiceState;
 use crate::modules;
+use crate::proxy::sticky_config::SchedulingMode;
 
-pub fn create_tray<R: Runtime>(app: &tauri::AppHandle<R>) -> tauri::Result<()> {
+/// 托盘状态刷新间隔 (每分钟请求数徽标)
+const STATE_REFRESH_SECS: u64 = 5;
+
+/// 托盘菜单中可切换的调度模式 (菜单 ID 后缀, 模式)
+const SCHEDULING_MODES: [(&str, SchedulingMode); 4] = [
+    ("cache_first", SchedulingMode::CacheFirst),
+    ("balance", SchedulingMode::Balance),
+    ("performance_first", SchedulingMode::PerformanceFirst),
+    ("fairness", SchedulingMode::Fairness),
+];
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TrayAccount {
+    pub id: String,
+    pub email: String,
+    /// 已从反代池暂停 (临时暂停或 proxy_disabled)
+    pub paused: bool,
+}
+
+/// 托盘展示与控制所需的反代状态，变化时通过 tray://proxy-state 推送
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TrayProxyState {
+    pub running: bool,
+    pub port: u16,
+    pub requests_per_minute: usize,
+    pub scheduling_mode: SchedulingMode,
+    pub accounts: Vec<TrayAccount>,
+}
+
+pub async fn get_proxy_state<R: Runtime>(app: &tauri::AppHandle<R>) -> TrayProxyState {
+    let config = modules::load_app_config().unwrap_or_default();
+    let state = app.state::<ProxyServiceState>();
+    let (running, port, scheduling_mode) = match state.instance.read().await.as_ref() {
+        Some(instance) => (true, instance.config.port, instance.token_manager.get_sticky_config().await.mode),
+        None => (false, config.proxy.port, config.proxy.scheduling.mode),
+    };
+    let requests_per_minute = match (running, state.monitor.read().await.as_ref()) {
+        (true, Some(monitor)) => monitor.requests_per_minute(),
+        _ => 0,
+    };
+    let now = chrono::Utc::now().timestamp();
+    let accounts = modules::list_accounts()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|a| !a.disabled)
+        .map(|a| TrayAccount { paused: a.proxy_disabled || a.is_paused(now), id: a.id, email: a.email })
+        .collect();
+    TrayProxyState { running, port, requests_per_minute, scheduling_mode, accounts }
+}
+
+/// 启动或停止反代服务 (托盘与命令共用)
+pub async fn set_proxy_running(app: &tauri::AppHandle, running: bool) -> Result<TrayProxyState, String> {
+    let state = app.state::<ProxyServiceState>();
+    let is_running = state.instance.read().await.is_some();
+    if running && !is_running {
+        let config = modules::load_app_config()?;
+        crate::commands::proxy::internal_start_proxy_service(config.proxy, &state, Some(app.clone())).await?;
+    } else if !running && is_running {
+        modules::watchdog::set_desired(None);
+        crate::commands::proxy::shutdown_proxy_gracefully(&state).await;
+    }
+    modules::logger::log_info(&format!("[Tray] Proxy {}", if running { "started" } else { "stopped" }));
+    Ok(notify_proxy_state(app).await)
+}
+
+/// 切换调度模式: 写入配置，运行中时立即生效
+pub async fn set_scheduling_mode<R: Runtime>(app: &tauri::AppHandle<R>, mode: SchedulingMode) -> Result<TrayProxyState, String> {
+    let mut config = modules::load_app_config()?;
+    config.proxy.scheduling.mode = mode;
+    modules::save_app_config(&config)?;
+    let state = app.state::<ProxyServiceState>();
+    if let Some(instance) = state.instance.read().await.as_ref() {
+        instance.token_manager.update_sticky_config(config.proxy.scheduling.clone()).await;
+    }
+    Ok(notify_proxy_state(app).await)
+}
+
+/// 刷新托盘菜单并向前端推送最新状态
+async fn notify_proxy_state<R: Runtime>(app: &tauri::AppHandle<R>) -> TrayProxyState {
+    let proxy_state = get_proxy_state(app).await;
+    let _ = app.emit("tray://proxy-state", &proxy_state);
+    update_tray_menus(app);
+    proxy_state
+}
+
+/// 后台任务: 定期刷新每分钟请求数徽标；运行状态/调度模式/账号变化时 (如在主窗口中修改) 重建菜单
+pub fn start_state_loop(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(STATE_REFRESH_SECS));
+        let mut last: Option<TrayProxyState> = None;
+        loop {
+            interval.tick().await;
+            let current = get_proxy_state(&app).await;
+            if last.as_ref() == Some(&current) {
+                continue;
+            }
+            let menu_changed = last.as_ref().map_or(true, |l| {
+                (l.running, l.scheduling_mode, &l.accounts) != (current.running, current.scheduling_mode, &current.accounts)
+            });
+            if let Some(tray) = app.tray_by_id("main") {
+                let config = modules::load_app_config().unwrap_or_default();
+                let texts = modules::i18n::get_tray_texts(&config.language);
+                let badge = current
+                    .running
+                    .then(|| texts.requests_per_minute.replace("{count}", &current.requests_per_minute.to_string()));
+                // 标题仅 macOS 菜单栏可见，其余平台通过悬停提示显示
+                let _ = tray.set_title(current.running.then(|| current.requests_per_minute.to_string()));
+                let _ = tray.set_tooltip(Some(match badge {
+                    Some(badge) => format!("Antigravity Tools · {}", badge),
+                    None => "Antigravity Tools".to_string(),
+                }));
+            }
+            let _ = app.emit("tray://proxy-state", &current);
+            if menu_changed && last.is_some() {
+                update_tray_menus(&app);
+            }
+            last = Some(current);
+        }
+    });
+}
+
+pub fn create_tray(app: &tauri::AppHandle) -> tauri::Result<()> {
     // 1. Load config to get language settings
     let config = modules::load_app_config().unwrap_or_default();
     let texts = modules::i18n::get_tray_texts(&config.language);
@@ -70,6 +194,46 @@ pub fn create_tray<R: Runtime>(app: &tauri::AppHandle<R>) -> tauri::Result<()> {
                 "quit" => {
                     app.exit(0);
                 }
+                "proxy_toggle" => {
+                    tauri::async_runtime::spawn(async move {
+                        let running = app_handle.state::<ProxyServiceState>().instance.read().await.is_some();
+                        if let Err(e) = set_proxy_running(&app_handle, !running).await {
+                            modules::logger::log_error(&format!("Tray proxy toggle failed: {}", e));
+                        }
+                    });
+                }
+                id if id.starts_with("mode_") => {
+                    let mode = SCHEDULING_MODES.iter().find(|(key, _)| id == format!("mode_{}", key)).map(|(_, m)| *m);
+                    if let Some(mode) = mode {
+                        tauri::async_runtime::spawn(async move {
+                            if let Err(e) = set_scheduling_mode(&app_handle, mode).await {
+                                modules::logger::log_error(&format!("Tray scheduling switch failed: {}", e));
+                            }
+                        });
+                    }
+                }
+                id if id.starts_with("pause:") => {
+                    let account_id = id["pause:".len()..].to_string();
+                    tauri::async_runtime::spawn(async move {
+                        let Ok(account) = modules::load_account(&account_id) else { return };
+                        let state = app_handle.state::<ProxyServiceState>();
+                        // 已暂停 / 禁用则恢复，否则暂停 (直到手动恢复)
+                        let result = if account.is_paused(chrono::Utc::now().timestamp()) {
+                            crate::commands::resume_account(app_handle.clone(), state, account_id.clone()).await
+                        } else if account.proxy_disabled {
+                            crate::commands::toggle_proxy_status(app_handle.clone(), state, account_id.clone(), true, None).await
+                        } else {
+                            crate::commands::pause_account(app_handle.clone(), state, account_id.clone(), None, None).await
+                        };
+                        match result {
+                            Ok(()) => {
+                                let _ = app_handle.emit("tray://account-paused", account_id);
+                                notify_proxy_state(&app_handle).await;
+                            }
+                            Err(e) => modules::logger::log_error(&format!("Tray account pause failed: {}", e)),
+                        }
+                    });
+                }
                 "refresh_curr" => {
                     // Execute refresh asynchronously
                     tauri::async_runtime::spawn(async move {
@@ -219,6 +383,40 @@ pub fn update_tray_menus<R: Runtime>(app: &tauri::AppHandle<R>) {
              }
          }
          
+         // 反代控制区
+         let proxy_state = get_proxy_state(&app_clone).await;
+         let proxy_text = if proxy_state.running {
+             texts.proxy_running.replace("{port}", &proxy_state.port.to_string())
+         } else {
+             texts.proxy_stopped.clone()
+         };
+         let proxy_info = MenuItem::with_id(&app_clone, "info_proxy", &proxy_text, false, None::<&str>);
+         let toggle_text = if proxy_state.running { &texts.stop_proxy } else { &texts.start_proxy };
+         let proxy_toggle = MenuItem::with_id(&app_clone, "proxy_toggle", toggle_text, true, None::<&str>);
+
+         let mode_items: Vec<_> = SCHEDULING_MODES
+             .iter()
+             .filter_map(|(key, mode)| {
+                 let label = match mode {
+                     SchedulingMode::CacheFirst => &texts.mode_cache_first,
+                     SchedulingMode::Balance => &texts.mode_balance,
+                     SchedulingMode::PerformanceFirst => &texts.mode_performance_first,
+                     SchedulingMode::Fairness => &texts.mode_fairness,
+                 };
+                 CheckMenuItem::with_id(&app_clone, format!("mode_{}", key), label, true, *mode == proxy_state.scheduling_mode, None::<&str>).ok()
+             })
+             .collect();
+         let mode_refs: Vec<&dyn tauri::menu::IsMenuItem<R>> = mode_items.iter().map(|i| i as &dyn tauri::menu::IsMenuItem<R>).collect();
+         let mode_menu = Submenu::with_id_and_items(&app_clone, "routing_mode", &texts.routing_mode, true, &mode_refs);
+
+         let pause_items: Vec<_> = proxy_state
+             .accounts
+             .iter()
+             .filter_map(|a| CheckMenuItem::with_id(&app_clone, format!("pause:{}", a.id), &a.email, true, a.paused, None::<&str>).ok())
+             .collect();
+         let pause_refs: Vec<&dyn tauri::menu::IsMenuItem<R>> = pause_items.iter().map(|i| i as &dyn tauri::menu::IsMenuItem<R>).collect();
+         let pause_menu = Submenu::with_id_and_items(&app_clone, "pause_account", &texts.pause_account, !pause_refs.is_empty(), &pause_refs);
+
          let switch_next = MenuItem::with_id(&app_clone, "switch_next", &texts.switch_next, true, None::<&str>);
          let refresh_curr = MenuItem::with_id(&app_clone, "refresh_curr", &texts.refresh_current, true, None::<&str>);
          
@@ -237,6 +435,15 @@ pub fn update_tray_menus<R: Runtime>(app: &tauri::AppHandle<R>) {
              }
              
              if let Some(ref s) = sep1 { items.push(s); }
+             let sep_proxy = PredefinedMenuItem::separator(&app_clone).ok();
+             let proxy_items: [Option<&dyn tauri::menu::IsMenuItem<R>>; 4] = [
+                 proxy_info.as_ref().ok().map(|i| i as &dyn tauri::menu::IsMenuItem<R>),
+                 proxy_toggle.as_ref().ok().map(|i| i as &dyn tauri::menu::IsMenuItem<R>),
+                 mode_menu.as_ref().ok().map(|i| i as &dyn tauri::menu::IsMenuItem<R>),
+                 pause_menu.as_ref().ok().map(|i| i as &dyn tauri::menu::IsMenuItem<R>),
+             ];
+             items.extend(proxy_items.into_iter().flatten());
+             if let Some(ref s) = sep_proxy { items.push(s); }
              items.push(&s_n);
              items.push(&r_c);
              if let Some(ref s) = sep2 { items.push(s); }
@@ -1,11 +1,33 @@
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
 use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_updater::UpdaterExt;
 use crate::modules::logger;
 
 const GITHUB_API_URL: &str = "https://api.github.com/repos/lbjlaq/Antigravity-Manager/releases/latest";
+/// 含预发布版本的发布列表 (beta 通道)
+const GITHUB_RELEASES_URL: &str = "https://api.github.com/repos/lbjlaq/Antigravity-Manager/releases?per_page=10";
+/// 各发布附带的签名更新清单 (与 tauri.conf.json 中的 updater endpoint 一致)
+const STABLE_UPDATER_ENDPOINT: &str = "https://github.com/lbjlaq/Antigravity-Manager/releases/latest/download/updater.json";
+const RELEASE_UPDATER_ENDPOINT: &str = "https://github.com/lbjlaq/Antigravity-Manager/releases/download";
+/// 后台检查循环的轮询间隔 (实际检查频率由 check_interval_hours 决定)
+const UPDATE_LOOP_INTERVAL_SECS: u64 = 1800;
 const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 const DEFAULT_CHECK_INTERVAL_HOURS: u64 = 24;
 
+/// 更新通道
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    /// 仅正式版
+    #[default]
+    Stable,
+    /// 包含预发布版本
+    Beta,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateInfo {
     pub current_version: String,
@@ -14,6 +36,12 @@ pub struct UpdateInfo {
     pub download_url: String, // previously release_url
     pub release_notes: String,
     pub published_at: String,
+    #[serde(default)]
+    pub tag_name: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub channel: UpdateChannel,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,18 +50,33 @@ pub struct UpdateSettings {
     pub last_check_time: u64,
     #[serde(default = "default_check_interval")]
     pub check_interval_hours: u64,
+    #[serde(default)]
+    pub channel: UpdateChannel,
+    /// 发现新版本后在后台下载并校验签名，安装推迟到用户确认或退出时
+    #[serde(default)]
+    pub auto_download: bool,
+    /// 退出应用时安装已下载的更新
+    #[serde(default = "default_install_on_quit")]
+    pub install_on_quit: bool,
 }
 
 fn default_check_interval() -> u64 {
     DEFAULT_CHECK_INTERVAL_HOURS
 }
 
+fn default_install_on_quit() -> bool {
+    true
+}
+
 impl Default for UpdateSettings {
     fn default() -> Self {
         Self {
             auto_check: true,
             last_check_time: 0,
             check_interval_hours: DEFAULT_CHECK_INTERVAL_HOURS,
+            channel: UpdateChannel::default(),
+            auto_download: false,
+            install_on_quit: default_install_on_quit(),
         }
     }
 }
@@ -42,12 +85,35 @@ impl Default for UpdateSettings {
 struct GitHubRelease {
     tag_name: String,
     html_url: String,
-    body: String,
-    published_at: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    published_at: Option<String>,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    draft: bool,
+}
+
+fn release_version(release: &GitHubRelease) -> &str {
+    release.tag_name.trim_start_matches('v')
+}
+
+/// beta 通道: 从发布列表中取版本最高的非草稿发布 (正式版与预发布均可)
+fn pick_release(releases: Vec<GitHubRelease>) -> Option<GitHubRelease> {
+    releases.into_iter().filter(|r| !r.draft).fold(None, |best, release| match best {
+        Some(best) if !compare_versions(release_version(&release), release_version(&best)) => Some(best),
+        _ => Some(release),
+    })
 }
 
-/// Check for updates from GitHub releases
+/// Check for updates from GitHub releases on the configured channel
 pub async fn check_for_updates() -> Result<UpdateInfo, String> {
+    let channel = load_update_settings().map(|s| s.channel).unwrap_or_default();
+    check_for_updates_on(channel).await
+}
+
+pub async fn check_for_updates_on(channel: UpdateChannel) -> Result<UpdateInfo, String> {
     let client = reqwest::Client::builder()
         .user_agent("Antigravity-Manager")
         .timeout(std::time::Duration::from_secs(10))
@@ -58,10 +124,14 @@ pub async fn check_for_updates() -> Result<UpdateInfo, String> {
             err_msg
         })?;
 
-    logger::log_info("Checking for new version from GitHub...");
+    logger::log_info(&format!("Checking for new version from GitHub ({:?} channel)...", channel));
 
+    let url = match channel {
+        UpdateChannel::Stable => GITHUB_API_URL,
+        UpdateChannel::Beta => GITHUB_RELEASES_URL,
+    };
     let response = client
-        .get(GITHUB_API_URL)
+        .get(url)
         .send()
         .await
         .map_err(|e| {
@@ -74,10 +144,19 @@ pub async fn check_for_updates() -> Result<UpdateInfo, String> {
         return Err(format!("GitHub API returned status: {}", response.status()));
     }
 
-    let release: GitHubRelease = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse release info: {}", e))?;
+    let release: GitHubRelease = match channel {
+        UpdateChannel::Stable => response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse release info: {}", e))?,
+        UpdateChannel::Beta => {
+            let releases: Vec<GitHubRelease> = response
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse release info: {}", e))?;
+            pick_release(releases).ok_or("No releases found")?
+        }
+    };
 
     // Remove 'v' prefix if present
     let latest_version = release.tag_name.trim_start_matches('v').to_string();
@@ -96,21 +175,27 @@ pub async fn check_for_updates() -> Result<UpdateInfo, String> {
         latest_version,
         has_update,
         download_url: release.html_url,
-        release_notes: release.body,
-        published_at: release.published_at,
+        release_notes: release.body.unwrap_or_default(),
+        published_at: release.published_at.unwrap_or_default(),
+        tag_name: release.tag_name,
+        prerelease: release.prerelease,
+        channel,
     })
 }
 
-/// Compare two semantic versions (e.g., "3.3.30" vs "3.3.29")
+/// Compare two semantic versions (e.g., "3.3.30" vs "3.3.29", "3.4.0" vs "3.4.0-beta.2")
 fn compare_versions(latest: &str, current: &str) -> bool {
     let parse_version = |v: &str| -> Vec<u32> {
         v.split('.')
             .filter_map(|s| s.parse::<u32>().ok())
             .collect()
     };
+    // 预发布后缀 (-beta.2) 单独比较: 同一版本号下正式版高于预发布
+    let (latest_core, latest_pre) = latest.split_once('-').map_or((latest, None), |(c, p)| (c, Some(p)));
+    let (current_core, current_pre) = current.split_once('-').map_or((current, None), |(c, p)| (c, Some(p)));
 
-    let latest_parts = parse_version(latest);
-    let current_parts = parse_version(current);
+    let latest_parts = parse_version(latest_core);
+    let current_parts = parse_version(current_core);
 
     for i in 0..latest_parts.len().max(current_parts.len()) {
         let latest_part = latest_parts.get(i).unwrap_or(&0);
@@ -123,7 +208,15 @@ fn compare_versions(latest: &str, current: &str) -> bool {
         }
     }
 
-    false
+    match (latest_pre, current_pre) {
+        (None, Some(_)) => true,
+        (Some(latest_pre), Some(current_pre)) => {
+            let latest_pre = parse_version(latest_pre.trim_start_matches(|c: char| !c.is_ascii_digit()));
+            let current_pre = parse_version(current_pre.trim_start_matches(|c: char| !c.is_ascii_digit()));
+            latest_pre > current_pre
+        }
+        _ => false,
+    }
 }
 
 /// Check if enough time has passed since last check
@@ -186,6 +279,173 @@ pub fn update_last_check_time() -> Result<(), String> {
     save_update_settings(&settings)
 }
 
+/// 已下载并通过签名校验、等待安装的更新
+struct PendingUpdate {
+    update: tauri_plugin_updater::Update,
+    bytes: Vec<u8>,
+}
+
+fn pending() -> &'static Mutex<Option<PendingUpdate>> {
+    static PENDING: OnceLock<Mutex<Option<PendingUpdate>>> = OnceLock::new();
+    PENDING.get_or_init(|| Mutex::new(None))
+}
+
+static DOWNLOADING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateStatus {
+    pub current_version: String,
+    pub channel: UpdateChannel,
+    pub downloading: bool,
+    /// 已下载、等待安装的版本
+    pub pending_version: Option<String>,
+    pub install_on_quit: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DownloadProgress {
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+pub fn get_update_status() -> UpdateStatus {
+    let settings = load_update_settings().unwrap_or_default();
+    UpdateStatus {
+        current_version: CURRENT_VERSION.to_string(),
+        channel: settings.channel,
+        downloading: DOWNLOADING.load(Ordering::SeqCst),
+        pending_version: pending().lock().ok().and_then(|p| p.as_ref().map(|p| p.update.version.clone())),
+        install_on_quit: settings.install_on_quit,
+    }
+}
+
+/// 对应发布的签名更新清单地址: 正式版沿用 latest，预发布按 tag 定位
+fn updater_endpoint(info: &UpdateInfo) -> String {
+    if info.prerelease && !info.tag_name.is_empty() {
+        format!("{}/{}/updater.json", RELEASE_UPDATER_ENDPOINT, info.tag_name)
+    } else {
+        STABLE_UPDATER_ENDPOINT.to_string()
+    }
+}
+
+/// 下载所选通道的最新版本并校验签名 (updater 插件使用 tauri.conf.json 中的公钥)，不立即安装
+pub async fn download_update(app: &AppHandle) -> Result<UpdateStatus, String> {
+    if DOWNLOADING.swap(true, Ordering::SeqCst) {
+        return Err("An update is already being downloaded".to_string());
+    }
+    let result = download_update_inner(app).await;
+    DOWNLOADING.store(false, Ordering::SeqCst);
+    result.map(|_| get_update_status())
+}
+
+async fn download_update_inner(app: &AppHandle) -> Result<(), String> {
+    let info = check_for_updates().await?;
+    if !info.has_update {
+        return Err(format!("Already up to date ({})", info.current_version));
+    }
+    let pending_version = get_update_status().pending_version;
+    if pending_version.as_deref() == Some(info.latest_version.as_str()) {
+        return Ok(());
+    }
+
+    let endpoint = url::Url::parse(&updater_endpoint(&info)).map_err(|e| format!("Invalid updater endpoint: {}", e))?;
+    let update = app
+        .updater_builder()
+        .endpoints(vec![endpoint])
+        .and_then(|b| b.build())
+        .map_err(|e| format!("Failed to create updater: {}", e))?
+        .check()
+        .await
+        .map_err(|e| format!("Failed to fetch update manifest: {}", e))?
+        .ok_or_else(|| format!("No signed update package found for {}", info.latest_version))?;
+
+    logger::log_info(&format!("Downloading update {} ({:?} channel)...", update.version, info.channel));
+    let mut downloaded = 0u64;
+    let bytes = update
+        .download(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                let _ = app.emit("update://progress", DownloadProgress { downloaded, total });
+            },
+            || {},
+        )
+        .await
+        .map_err(|e| {
+            let err_msg = format!("Failed to download or verify update: {}", e);
+            logger::log_error(&err_msg);
+            err_msg
+        })?;
+
+    logger::log_info(&format!("Update {} downloaded and verified, waiting to be installed", update.version));
+    let version = update.version.clone();
+    if let Ok(mut guard) = pending().lock() {
+        *guard = Some(PendingUpdate { update, bytes });
+    }
+    let _ = app.emit("update://downloaded", version);
+    Ok(())
+}
+
+/// 安装已下载的更新；restart 为 true 时随后重启应用
+pub fn install_pending_update(app: &AppHandle, restart: bool) -> Result<(), String> {
+    let pending = pending()
+        .lock()
+        .map_err(|_| "Update state is unavailable".to_string())?
+        .take()
+        .ok_or("No downloaded update to install")?;
+    logger::log_info(&format!("Installing update {}...", pending.update.version));
+    pending
+        .update
+        .install(&pending.bytes)
+        .map_err(|e| format!("Failed to install update: {}", e))?;
+    if restart {
+        app.restart();
+    }
+    Ok(())
+}
+
+/// 退出时安装推迟的更新 (install_on_quit)
+pub fn install_on_exit(app: &AppHandle) {
+    let has_pending = pending().lock().map(|p| p.is_some()).unwrap_or(false);
+    if !has_pending || !load_update_settings().map(|s| s.install_on_quit).unwrap_or(true) {
+        return;
+    }
+    if let Err(e) = install_pending_update(app, false) {
+        logger::log_error(&e);
+    }
+}
+
+/// 后台任务: 长时间运行 (如 24/7 反代) 时也按间隔检查更新，并按设置预先下载
+pub fn start_update_loop(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(UPDATE_LOOP_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+
+            let Ok(settings) = load_update_settings() else {
+                continue;
+            };
+            if !should_check_for_updates(&settings) {
+                continue;
+            }
+            let _ = update_last_check_time();
+            let info = match check_for_updates_on(settings.channel).await {
+                Ok(info) if info.has_update => info,
+                Ok(_) => continue,
+                Err(e) => {
+                    logger::log_warn(&format!("Background update check failed: {}", e));
+                    continue;
+                }
+            };
+            let _ = app.emit("update://available", &info);
+            if settings.auto_download {
+                if let Err(e) = download_update(&app).await {
+                    logger::log_warn(&format!("Background update download failed: {}", e));
+                }
+            }
+        }
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -197,6 +457,31 @@ mod tests {
         assert!(compare_versions("4.0.0", "3.3.35"));
         assert!(!compare_versions("3.3.34", "3.3.35"));
         assert!(!compare_versions("3.3.35", "3.3.35"));
+        assert!(compare_versions("3.4.0", "3.4.0-beta.2"));
+        assert!(compare_versions("3.4.0-beta.3", "3.4.0-beta.2"));
+        assert!(!compare_versions("3.4.0-beta.2", "3.4.0"));
+        assert!(compare_versions("3.4.0-beta.1", "3.3.35"));
+    }
+
+    #[test]
+    fn test_pick_release() {
+        let release = |tag: &str, prerelease: bool, draft: bool| GitHubRelease {
+            tag_name: tag.to_string(),
+            html_url: String::new(),
+            body: None,
+            published_at: None,
+            prerelease,
+            draft,
+        };
+        let picked = pick_release(vec![
+            release("v3.5.0-beta.1", false, true),
+            release("v3.4.1", false, false),
+            release("v3.4.1-beta.2", true, false),
+            release("v3.4.0", false, false),
+        ])
+        .unwrap();
+        assert_eq!(picked.tag_name, "v3.4.1");
+        assert!(pick_release(Vec::new()).is_none());
     }
 
     #[test]
@@ -0,0 +1,99 @@
+// 反代看门狗
+// 记录用户期望运行的反代配置 (启动成功时写入，主动停止时清除)。后台定期检查:
+// - 期望运行但服务不存在 (如开机自启时端口暂被占用导致启动失败) → 重新启动
+// - 服务任务已退出，或 /healthz 连续无响应 → 重启监听器
+// 保证编辑器等客户端在重启电脑后总能找到端点。
+
+use std::sync::Mutex;
+use tokio::time::{self, Duration};
+
+use crate::commands::proxy::{internal_start_proxy_service, shutdown_proxy_gracefully, ProxyServiceState};
+use crate::modules::logger;
+use crate::proxy::ProxyConfig;
+
+const CHECK_INTERVAL_SECS: u64 = 15;
+/// 健康检查连续失败多少次后重启
+const MAX_HEALTH_FAILURES: u32 = 3;
+
+static DESIRED: Mutex<Option<ProxyConfig>> = Mutex::new(None);
+
+/// 设置期望运行的配置；None 表示用户已主动停止
+pub fn set_desired(config: Option<ProxyConfig>) {
+    if let Ok(mut desired) = DESIRED.lock() {
+        *desired = config;
+    }
+}
+
+fn desired() -> Option<ProxyConfig> {
+    DESIRED.lock().ok().and_then(|d| d.clone())
+}
+
+/// 监听器是否仍在接受连接 (任何 HTTP 响应都算存活，鉴权失败同样说明监听器正常)
+async fn is_listener_alive(port: u16) -> bool {
+    let Ok(client) = reqwest::Client::builder().timeout(Duration::from_secs(5)).build() else {
+        return true;
+    };
+    client.get(format!("http://127.0.0.1:{}/healthz", port)).send().await.is_ok()
+}
+
+async fn restart(state: &ProxyServiceState, config: ProxyConfig, app_handle: &Option<tauri::AppHandle>) {
+    if state.instance.read().await.is_some() {
+        shutdown_proxy_gracefully(state).await;
+    }
+    match internal_start_proxy_service(config, state, app_handle.clone()).await {
+        Ok(status) => logger::log_info(&format!("[Watchdog] 反代服务已恢复，端口 {}", status.port)),
+        Err(e) => logger::log_warn(&format!("[Watchdog] 重启反代服务失败，稍后重试: {}", e)),
+    }
+}
+
+/// 启动看门狗 (桌面端与 headless 共用)
+pub fn start(state: ProxyServiceState, app_handle: Option<tauri::AppHandle>) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(CHECK_INTERVAL_SECS));
+        let mut health_failures = 0u32;
+        loop {
+            interval.tick().await;
+            let Some(config) = desired() else {
+                health_failures = 0;
+                continue;
+            };
+            if !config.watchdog_enabled {
+                continue;
+            }
+
+            let (exists, finished, port) = match state.instance.read().await.as_ref() {
+                Some(instance) => (true, instance.server_handle.is_finished(), instance.config.port),
+                None => (false, false, config.port),
+            };
+            if !exists {
+                // 端口已由其他实例 (如开机自启的 headless 进程) 提供服务，无需重复启动
+                if is_listener_alive(port).await {
+                    continue;
+                }
+                logger::log_warn("[Watchdog] 反代服务未运行，尝试启动");
+                restart(&state, config, &app_handle).await;
+                continue;
+            }
+            if finished {
+                logger::log_warn("[Watchdog] 反代监听器已退出，正在重启");
+                health_failures = 0;
+                restart(&state, config, &app_handle).await;
+                continue;
+            }
+
+            if is_listener_alive(port).await {
+                health_failures = 0;
+                continue;
+            }
+            health_failures += 1;
+            logger::log_warn(&format!(
+                "[Watchdog] 端口 {} 健康检查失败 ({}/{})",
+                port, health_failures, MAX_HEALTH_FAILURES
+            ));
+            if health_failures >= MAX_HEALTH_FAILURES {
+                health_failures = 0;
+                restart(&state, config, &app_handle).await;
+            }
+        }
+    });
+}
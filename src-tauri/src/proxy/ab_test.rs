@@ -0,0 +1,323 @@
+// A/B 分流实验: 结果采集与对比统计
+// 分流本身在 middleware::ab_routing 中完成，这里只负责从响应中提取指标并汇总两组差异。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub const ARM_CONTROL: &str = "control";
+pub const ARM_VARIANT: &str = "variant";
+
+/// 单次实验请求的结果 (存储于 proxy_logs.db 的 ab_results 表)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbResult {
+    pub id: String,
+    pub timestamp: i64,
+    pub experiment: String,
+    pub arm: String,
+    /// 客户端请求的模型名
+    pub alias: String,
+    /// 本次实际路由的模型名
+    pub model: String,
+    pub status: u16,
+    /// 从转发到响应体结束的总耗时 (ms)
+    pub duration: u64,
+    /// 首个响应体分片的耗时 (ms)
+    pub first_byte_ms: Option<u64>,
+    pub output_chars: u64,
+    pub output_tokens: Option<u32>,
+    pub finish_reason: Option<String>,
+}
+
+/// 从响应 (JSON 或 SSE) 中提取输出长度、finish reason 与输出 token 数
+#[derive(Debug, Default)]
+pub struct ResponseObserver {
+    pub output_chars: u64,
+    pub output_tokens: Option<u32>,
+    pub finish_reason: Option<String>,
+    line_buf: Vec<u8>,
+}
+
+impl ResponseObserver {
+    /// 处理一段 SSE 原始字节 (可能在行中间截断)
+    pub fn observe_sse_chunk(&mut self, chunk: &[u8]) {
+        self.line_buf.extend_from_slice(chunk);
+        while let Some(pos) = self.line_buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.line_buf.drain(..=pos).collect();
+            self.observe_sse_line(&String::from_utf8_lossy(&line));
+        }
+    }
+
+    pub fn finish_sse(&mut self) {
+        let rest = std::mem::take(&mut self.line_buf);
+        self.observe_sse_line(&String::from_utf8_lossy(&rest));
+    }
+
+    fn observe_sse_line(&mut self, line: &str) {
+        let Some(payload) = line.trim().strip_prefix("data:") else { return };
+        if let Ok(json) = serde_json::from_str::<Value>(payload.trim()) {
+            self.observe_json(&json);
+        }
+    }
+
+    /// 兼容 OpenAI / Claude / Gemini 的完整响应与流式事件
+    pub fn observe_json(&mut self, value: &Value) {
+        // OpenAI chat / completions
+        if let Some(choices) = value.get("choices").and_then(|c| c.as_array()) {
+            for choice in choices {
+                let text = choice
+                    .get("delta")
+                    .or_else(|| choice.get("message"))
+                    .and_then(|m| m.get("content"))
+                    .and_then(|c| c.as_str())
+                    .or_else(|| choice.get("text").and_then(|t| t.as_str()));
+                self.add_text(text);
+                self.set_finish(choice.get("finish_reason"));
+            }
+        }
+
+        // Gemini
+        if let Some(candidates) = value.get("candidates").and_then(|c| c.as_array()) {
+            for candidate in candidates {
+                if let Some(parts) = candidate.pointer("/content/parts").and_then(|p| p.as_array()) {
+                    for part in parts {
+                        if part.get("thought").and_then(|t| t.as_bool()) != Some(true) {
+                            self.add_text(part.get("text").and_then(|t| t.as_str()));
+                        }
+                    }
+                }
+                self.set_finish(candidate.get("finishReason"));
+            }
+        }
+
+        // Claude 完整响应 / content_block_delta / message_delta
+        if value.get("type").and_then(|t| t.as_str()) == Some("message") {
+            if let Some(blocks) = value.get("content").and_then(|c| c.as_array()) {
+                for block in blocks {
+                    if block.get("type").and_then(|t| t.as_str()) == Some("text") {
+                        self.add_text(block.get("text").and_then(|t| t.as_str()));
+                    }
+                }
+            }
+        }
+        if let Some(delta) = value.get("delta") {
+            match delta {
+                // OpenAI Responses API: response.output_text.delta
+                Value::String(s) => self.add_text(Some(s)),
+                _ => {
+                    if delta.get("type").and_then(|t| t.as_str()) == Some("text_delta") {
+                        self.add_text(delta.get("text").and_then(|t| t.as_str()));
+                    }
+                    self.set_finish(delta.get("stop_reason"));
+                }
+            }
+        }
+        self.set_finish(value.get("stop_reason"));
+
+        if let Some(usage) = value.get("usage").or_else(|| value.get("usageMetadata")) {
+            let output = usage
+                .get("completion_tokens")
+                .or_else(|| usage.get("output_tokens"))
+                .or_else(|| usage.get("candidatesTokenCount"))
+                .and_then(|v| v.as_u64());
+            if let Some(output) = output {
+                self.output_tokens = Some(output as u32);
+            }
+        }
+    }
+
+    fn add_text(&mut self, text: Option<&str>) {
+        if let Some(text) = text {
+            self.output_chars += text.chars().count() as u64;
+        }
+    }
+
+    fn set_finish(&mut self, reason: Option<&Value>) {
+        if let Some(reason) = reason.and_then(|r| r.as_str()).filter(|r| !r.is_empty()) {
+            self.finish_reason = Some(reason.to_lowercase());
+        }
+    }
+}
+
+/// 单组统计 (延迟与长度仅统计成功请求)
+#[derive(Debug, Clone, Serialize)]
+pub struct ArmSummary {
+    pub arm: String,
+    pub models: Vec<String>,
+    pub requests: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub avg_latency_ms: f64,
+    pub p50_latency_ms: u64,
+    pub p95_latency_ms: u64,
+    pub avg_first_byte_ms: Option<f64>,
+    pub avg_output_chars: f64,
+    pub avg_output_tokens: Option<f64>,
+    pub finish_reasons: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExperimentSummary {
+    pub experiment: String,
+    pub control: Option<ArmSummary>,
+    pub variant: Option<ArmSummary>,
+    /// 以下差值均为 variant - control
+    pub latency_delta_ms: Option<f64>,
+    pub output_chars_delta: Option<f64>,
+    pub error_rate_delta: Option<f64>,
+}
+
+fn average(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0u64), |(s, c), v| (s + v, c + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let idx = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[idx.min(sorted.len() - 1)]
+}
+
+fn summarize_arm(arm: &str, results: &[&AbResult]) -> Option<ArmSummary> {
+    if results.is_empty() {
+        return None;
+    }
+    let ok: Vec<&&AbResult> = results.iter().filter(|r| r.status < 400).collect();
+    let mut latencies: Vec<u64> = ok.iter().map(|r| r.duration).collect();
+    latencies.sort_unstable();
+
+    let mut models: Vec<String> = results.iter().map(|r| r.model.clone()).collect();
+    models.sort();
+    models.dedup();
+
+    let mut finish_reasons = BTreeMap::new();
+    for r in &ok {
+        let reason = r.finish_reason.clone().unwrap_or_else(|| "unknown".to_string());
+        *finish_reasons.entry(reason).or_insert(0) += 1;
+    }
+
+    let requests = results.len() as u64;
+    let errors = requests - ok.len() as u64;
+    Some(ArmSummary {
+        arm: arm.to_string(),
+        models,
+        requests,
+        errors,
+        error_rate: errors as f64 / requests as f64,
+        avg_latency_ms: average(latencies.iter().map(|v| *v as f64)).unwrap_or(0.0),
+        p50_latency_ms: percentile(&latencies, 0.5),
+        p95_latency_ms: percentile(&latencies, 0.95),
+        avg_first_byte_ms: average(ok.iter().filter_map(|r| r.first_byte_ms).map(|v| v as f64)),
+        avg_output_chars: average(ok.iter().map(|r| r.output_chars as f64)).unwrap_or(0.0),
+        avg_output_tokens: average(ok.iter().filter_map(|r| r.output_tokens).map(|v| v as f64)),
+        finish_reasons,
+    })
+}
+
+/// 按实验分组汇总两组差异
+pub fn summarize(results: &[AbResult]) -> Vec<ExperimentSummary> {
+    let mut by_experiment: BTreeMap<&str, Vec<&AbResult>> = BTreeMap::new();
+    for r in results {
+        by_experiment.entry(r.experiment.as_str()).or_default().push(r);
+    }
+
+    by_experiment
+        .into_iter()
+        .map(|(experiment, rows)| {
+            let control: Vec<&AbResult> = rows.iter().copied().filter(|r| r.arm == ARM_CONTROL).collect();
+            let variant: Vec<&AbResult> = rows.iter().copied().filter(|r| r.arm == ARM_VARIANT).collect();
+            let control = summarize_arm(ARM_CONTROL, &control);
+            let variant = summarize_arm(ARM_VARIANT, &variant);
+            let delta = |f: fn(&ArmSummary) -> f64| match (&control, &variant) {
+                (Some(c), Some(v)) => Some(f(v) - f(c)),
+                _ => None,
+            };
+            ExperimentSummary {
+                experiment: experiment.to_string(),
+                latency_delta_ms: delta(|s| s.avg_latency_ms),
+                output_chars_delta: delta(|s| s.avg_output_chars),
+                error_rate_delta: delta(|s| s.error_rate),
+                control,
+                variant,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn result(arm: &str, status: u16, duration: u64, chars: u64, finish: &str) -> AbResult {
+        AbResult {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: 0,
+            experiment: "flash-vs-pro".to_string(),
+            arm: arm.to_string(),
+            alias: "gemini-2.5-flash".to_string(),
+            model: if arm == ARM_CONTROL { "gemini-2.5-flash" } else { "gemini-3-flash" }.to_string(),
+            status,
+            duration,
+            first_byte_ms: Some(duration / 4),
+            output_chars: chars,
+            output_tokens: None,
+            finish_reason: Some(finish.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_observer_sse_across_chunks() {
+        let mut observer = ResponseObserver::default();
+        observer.observe_sse_chunk(b"data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\ndata: {\"choi");
+        observer.observe_sse_chunk(b"ces\":[{\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}],\"usage\":{\"completion_tokens\":2}}\n\n");
+        observer.observe_sse_chunk(b"data: [DONE]\n\n");
+        observer.finish_sse();
+        assert_eq!(observer.output_chars, 5);
+        assert_eq!(observer.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(observer.output_tokens, Some(2));
+    }
+
+    #[test]
+    fn test_observer_gemini_and_claude() {
+        let mut gemini = ResponseObserver::default();
+        gemini.observe_json(&json!({
+            "candidates": [{"content": {"parts": [{"text": "plan", "thought": true}, {"text": "answer"}]}, "finishReason": "MAX_TOKENS"}],
+            "usageMetadata": {"candidatesTokenCount": 7}
+        }));
+        assert_eq!(gemini.output_chars, 6);
+        assert_eq!(gemini.finish_reason.as_deref(), Some("max_tokens"));
+        assert_eq!(gemini.output_tokens, Some(7));
+
+        let mut claude = ResponseObserver::default();
+        claude.observe_json(&json!({"type": "content_block_delta", "delta": {"type": "text_delta", "text": "héllo"}}));
+        claude.observe_json(&json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 3}}));
+        assert_eq!(claude.output_chars, 5);
+        assert_eq!(claude.finish_reason.as_deref(), Some("end_turn"));
+        assert_eq!(claude.output_tokens, Some(3));
+    }
+
+    #[test]
+    fn test_summarize_pairs_arms() {
+        let results = vec![
+            result(ARM_CONTROL, 200, 1000, 100, "stop"),
+            result(ARM_CONTROL, 200, 3000, 300, "stop"),
+            result(ARM_CONTROL, 503, 50, 0, "stop"),
+            result(ARM_VARIANT, 200, 500, 400, "length"),
+        ];
+        let summary = summarize(&results);
+        assert_eq!(summary.len(), 1);
+        let s = &summary[0];
+        let control = s.control.as_ref().unwrap();
+        assert_eq!(control.requests, 3);
+        assert_eq!(control.errors, 1);
+        assert_eq!(control.avg_latency_ms, 2000.0);
+        assert_eq!(control.finish_reasons["stop"], 2);
+        let variant = s.variant.as_ref().unwrap();
+        assert_eq!(variant.models, vec!["gemini-3-flash".to_string()]);
+        assert_eq!(s.latency_delta_ms, Some(-1500.0));
+        assert_eq!(s.output_chars_delta, Some(200.0));
+    }
+}
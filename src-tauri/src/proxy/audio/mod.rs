@@ -1,6 +1,8 @@
 use base64::{engine::general_purpose, Engine as _};
 use std::path::Path;
 
+pub mod tts; // 语音合成 (/v1/audio/speech)
+
 pub struct AudioProcessor;
 
 impl AudioProcessor {
@@ -32,6 +34,95 @@ impl AudioProcessor {
         const MAX_SIZE: usize = 15 * 1024 * 1024; // 15MB
         size_bytes > MAX_SIZE
     }
+
+    /// 解析模型返回的分段 JSON (`{"segments": [...]}` 或直接数组)
+    /// 时间戳可能是秒数、"mm:ss(.ms)" 或 "hh:mm:ss(.ms)"，无法解析的分段丢弃
+    pub fn parse_segments(raw: &str) -> Vec<TranscriptSegment> {
+        let trimmed = raw
+            .trim()
+            .trim_start_matches("```json")
+            .trim_start_matches("```")
+            .trim_end_matches("```")
+            .trim();
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(trimmed) else {
+            return Vec::new();
+        };
+        let items = value
+            .get("segments")
+            .and_then(|s| s.as_array())
+            .or_else(|| value.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        let mut segments: Vec<TranscriptSegment> = items
+            .iter()
+            .filter_map(|item| {
+                let text = item.get("text")?.as_str()?.trim().to_string();
+                let start = parse_timestamp(item.get("start")?)?;
+                let end = parse_timestamp(item.get("end")?).unwrap_or(start).max(start);
+                (!text.is_empty()).then_some(TranscriptSegment { start, end, text })
+            })
+            .collect();
+        segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+        segments
+    }
+}
+
+/// 带时间戳的转录分段 (秒)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptSegment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+fn parse_timestamp(value: &serde_json::Value) -> Option<f64> {
+    if let Some(n) = value.as_f64() {
+        return Some(n.max(0.0));
+    }
+    let s = value.as_str()?.trim();
+    let mut total = 0.0;
+    for part in s.split(':') {
+        total = total * 60.0 + part.trim().parse::<f64>().ok()?;
+    }
+    Some(total.max(0.0))
+}
+
+/// 生成 SRT / WebVTT 字幕时间戳
+pub fn format_subtitle_time(seconds: f64, decimal_sep: char) -> String {
+    let ms_total = (seconds * 1000.0).round() as u64;
+    let (h, m, s, ms) = (ms_total / 3_600_000, ms_total / 60_000 % 60, ms_total / 1000 % 60, ms_total % 1000);
+    format!("{:02}:{:02}:{:02}{}{:03}", h, m, s, decimal_sep, ms)
+}
+
+pub fn segments_to_srt(segments: &[TranscriptSegment]) -> String {
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, seg)| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                format_subtitle_time(seg.start, ','),
+                format_subtitle_time(seg.end, ','),
+                seg.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn segments_to_vtt(segments: &[TranscriptSegment]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for seg in segments {
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            format_subtitle_time(seg.start, '.'),
+            format_subtitle_time(seg.end, '.'),
+            seg.text
+        ));
+    }
+    out
 }
 
 #[cfg(test)]
@@ -59,6 +150,30 @@ mod tests {
         assert!(!AudioProcessor::exceeds_size_limit(15 * 1024 * 1024)); // 刚好等于限制
     }
 
+    #[test]
+    fn test_parse_segments() {
+        let raw = r#"```json
+{"segments": [
+  {"start": "00:01.5", "end": "00:03", "text": "second"},
+  {"start": 0, "end": 1.2, "text": " first "},
+  {"start": "bad", "end": 1, "text": "dropped"}
+]}
+```"#;
+        let segments = AudioProcessor::parse_segments(raw);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0], TranscriptSegment { start: 0.0, end: 1.2, text: "first".to_string() });
+        assert_eq!(segments[1].start, 1.5);
+        assert_eq!(segments[1].end, 3.0);
+        assert!(AudioProcessor::parse_segments("plain text").is_empty());
+    }
+
+    #[test]
+    fn test_subtitle_rendering() {
+        let segments = vec![TranscriptSegment { start: 61.25, end: 3725.0, text: "hello".to_string() }];
+        assert_eq!(segments_to_srt(&segments), "1\n00:01:01,250 --> 01:02:05,000\nhello\n");
+        assert!(segments_to_vtt(&segments).starts_with("WEBVTT\n\n00:01:01.250 --> 01:02:05.000\nhello"));
+    }
+
     #[test]
     fn test_base64_encoding() {
         let data = b"test audio data";
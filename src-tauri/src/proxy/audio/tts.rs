@@ -0,0 +1,244 @@
+// 语音合成 (OpenAI /v1/audio/speech -> Gemini TTS)
+// Gemini TTS 返回 16-bit 小端单声道 PCM (audio/L16;codec=pcm;rate=24000)，
+// wav/pcm 直接封装；mp3/opus/aac/flac/ogg 通过本机 ffmpeg 转码，不可用时回退为 wav。
+
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+
+const DEFAULT_SAMPLE_RATE: u32 = 24000;
+const DEFAULT_TTS_MODEL: &str = "gemini-2.5-flash-preview-tts";
+const HD_TTS_MODEL: &str = "gemini-2.5-pro-preview-tts";
+const DEFAULT_VOICE: &str = "Kore";
+
+/// Gemini 预置音色
+const GEMINI_VOICES: &[&str] = &[
+    "Zephyr", "Puck", "Charon", "Kore", "Fenrir", "Leda", "Orus", "Aoede", "Callirrhoe", "Autonoe",
+    "Enceladus", "Iapetus", "Umbriel", "Algieba", "Despina", "Erinome", "Algenib", "Rasalgethi",
+    "Laomedeia", "Achernar", "Alnilam", "Schedar", "Gacrux", "Pulcherrima", "Achird", "Zubenelgenubi",
+    "Vindemiatrix", "Sadachbia", "Sadaltager", "Sulafat",
+];
+
+/// 输出容器格式 (OpenAI response_format)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeechFormat {
+    Mp3,
+    Opus,
+    Aac,
+    Flac,
+    Wav,
+    Pcm,
+    Ogg,
+}
+
+impl SpeechFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "mp3" => Ok(Self::Mp3),
+            "opus" => Ok(Self::Opus),
+            "aac" => Ok(Self::Aac),
+            "flac" => Ok(Self::Flac),
+            "wav" => Ok(Self::Wav),
+            "pcm" => Ok(Self::Pcm),
+            "ogg" => Ok(Self::Ogg),
+            other => Err(format!("Unsupported response_format: {}", other)),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Mp3 => "audio/mpeg",
+            Self::Opus => "audio/opus",
+            Self::Aac => "audio/aac",
+            Self::Flac => "audio/flac",
+            Self::Wav => "audio/wav",
+            Self::Pcm => "audio/pcm",
+            Self::Ogg => "audio/ogg",
+        }
+    }
+
+    /// ffmpeg 输出参数 (容器 + 编码器)
+    fn ffmpeg_args(self) -> Option<&'static [&'static str]> {
+        match self {
+            Self::Mp3 => Some(&["-f", "mp3", "-codec:a", "libmp3lame", "-q:a", "4"]),
+            Self::Opus => Some(&["-f", "opus", "-codec:a", "libopus"]),
+            Self::Ogg => Some(&["-f", "ogg", "-codec:a", "libvorbis"]),
+            Self::Aac => Some(&["-f", "adts", "-codec:a", "aac"]),
+            Self::Flac => Some(&["-f", "flac"]),
+            Self::Wav | Self::Pcm => None,
+        }
+    }
+}
+
+/// OpenAI TTS 模型名 -> Gemini TTS 模型
+pub fn map_tts_model(model: &str) -> String {
+    let lower = model.to_lowercase();
+    if lower.starts_with("gemini-") {
+        return model.to_string();
+    }
+    if lower.contains("hd") {
+        HD_TTS_MODEL.to_string()
+    } else {
+        DEFAULT_TTS_MODEL.to_string()
+    }
+}
+
+/// OpenAI 音色 -> Gemini 预置音色；传入 Gemini 音色名时原样使用 (忽略大小写)
+pub fn map_voice(voice: &str) -> String {
+    if let Some(v) = GEMINI_VOICES.iter().find(|v| v.eq_ignore_ascii_case(voice)) {
+        return v.to_string();
+    }
+    match voice.to_lowercase().as_str() {
+        "alloy" => "Zephyr",
+        "ash" => "Orus",
+        "ballad" => "Algieba",
+        "coral" => "Aoede",
+        "echo" => "Puck",
+        "fable" => "Leda",
+        "onyx" => "Charon",
+        "nova" => "Kore",
+        "sage" => "Despina",
+        "shimmer" => "Callirrhoe",
+        "verse" => "Fenrir",
+        _ => DEFAULT_VOICE,
+    }
+    .to_string()
+}
+
+/// 构造朗读提示: instructions (风格) 与 speed 以自然语言传给模型
+pub fn build_prompt(input: &str, instructions: Option<&str>, speed: Option<f64>) -> String {
+    let mut directions = Vec::new();
+    if let Some(instr) = instructions.map(str::trim).filter(|s| !s.is_empty()) {
+        directions.push(instr.to_string());
+    }
+    if let Some(speed) = speed.filter(|s| (s - 1.0).abs() > 0.05) {
+        directions.push(format!("Speak at {:.2}x the normal speaking rate.", speed));
+    }
+    if directions.is_empty() {
+        input.to_string()
+    } else {
+        format!("{}\n\nRead the following text aloud:\n{}", directions.join(" "), input)
+    }
+}
+
+/// 从 `audio/L16;codec=pcm;rate=24000` 中解析采样率
+pub fn parse_sample_rate(mime_type: &str) -> u32 {
+    mime_type
+        .split(';')
+        .filter_map(|p| p.trim().strip_prefix("rate="))
+        .find_map(|r| r.parse().ok())
+        .unwrap_or(DEFAULT_SAMPLE_RATE)
+}
+
+/// 16-bit 单声道 PCM 封装为 WAV
+pub fn pcm_to_wav(pcm: &[u8], sample_rate: u32) -> Vec<u8> {
+    const CHANNELS: u16 = 1;
+    const BITS_PER_SAMPLE: u16 = 16;
+    let byte_rate = sample_rate * CHANNELS as u32 * BITS_PER_SAMPLE as u32 / 8;
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_len = pcm.len() as u32;
+
+    let mut wav = Vec::with_capacity(44 + pcm.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&CHANNELS.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&byte_rate.to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.extend_from_slice(pcm);
+    wav
+}
+
+/// 按目标格式封装/转码，返回 (音频字节, 实际格式)
+pub async fn encode_speech(pcm: &[u8], sample_rate: u32, format: SpeechFormat) -> (Vec<u8>, SpeechFormat) {
+    let wav = pcm_to_wav(pcm, sample_rate);
+    let Some(args) = format.ffmpeg_args() else {
+        return match format {
+            SpeechFormat::Pcm => (pcm.to_vec(), SpeechFormat::Pcm),
+            _ => (wav, SpeechFormat::Wav),
+        };
+    };
+
+    match transcode_with_ffmpeg(&wav, args).await {
+        Ok(bytes) => (bytes, format),
+        Err(e) => {
+            tracing::warn!("[TTS] ffmpeg 转码为 {:?} 失败，回退为 wav: {}", format, e);
+            (wav, SpeechFormat::Wav)
+        }
+    }
+}
+
+async fn transcode_with_ffmpeg(wav: &[u8], output_args: &[&str]) -> Result<Vec<u8>, String> {
+    let mut child = tokio::process::Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-f", "wav", "-i", "pipe:0"])
+        .args(output_args)
+        .arg("pipe:1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("ffmpeg not available: {}", e))?;
+
+    let mut stdin = child.stdin.take().ok_or("ffmpeg stdin unavailable")?;
+    let input = wav.to_vec();
+    // 与读取 stdout 并行写入，避免管道缓冲区写满导致死锁
+    let writer = tokio::spawn(async move {
+        let result = stdin.write_all(&input).await;
+        drop(stdin);
+        result
+    });
+
+    let output = child.wait_with_output().await.map_err(|e| e.to_string())?;
+    let _ = writer.await;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_voice_and_model() {
+        assert_eq!(map_voice("alloy"), "Zephyr");
+        assert_eq!(map_voice("puck"), "Puck");
+        assert_eq!(map_voice("unknown"), DEFAULT_VOICE);
+        assert_eq!(map_tts_model("tts-1"), DEFAULT_TTS_MODEL);
+        assert_eq!(map_tts_model("tts-1-hd"), HD_TTS_MODEL);
+        assert_eq!(map_tts_model("gemini-2.5-pro-preview-tts"), "gemini-2.5-pro-preview-tts");
+    }
+
+    #[test]
+    fn test_parse_sample_rate() {
+        assert_eq!(parse_sample_rate("audio/L16;codec=pcm;rate=24000"), 24000);
+        assert_eq!(parse_sample_rate("audio/L16; rate=16000"), 16000);
+        assert_eq!(parse_sample_rate("audio/L16"), DEFAULT_SAMPLE_RATE);
+    }
+
+    #[test]
+    fn test_pcm_to_wav_header() {
+        let wav = pcm_to_wav(&[0u8; 100], 24000);
+        assert_eq!(wav.len(), 144);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()), 136);
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 24000);
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 100);
+    }
+
+    #[test]
+    fn test_build_prompt() {
+        assert_eq!(build_prompt("hi", None, Some(1.0)), "hi");
+        let p = build_prompt("hi", Some("Cheerful"), Some(1.5));
+        assert!(p.starts_with("Cheerful Speak at 1.50x"));
+        assert!(p.ends_with("\nhi"));
+    }
+}
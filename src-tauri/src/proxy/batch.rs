@@ -0,0 +1,848 @@
+// Batch API (OpenAI /v1/files + /v1/batches 兼容)
+// 上传 JSONL 请求文件 -> 创建批任务 -> 后台按账号数并发执行，结果逐行追加落盘 (可断点续跑)。
+// 目录结构: <data_dir>/batches/files/<file_id>(.json) 与 <data_dir>/batches/jobs/<batch_id>.json
+
+use axum::{
+    body::to_bytes,
+    extract::State,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+use crate::proxy::common::streaming_json::StreamingJson;
+use crate::proxy::middleware::monitor::{parse_tags, usage_tokens};
+use crate::proxy::middleware::pii_scrub::scrub_body;
+use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::server::AppState;
+
+/// 支持的批处理端点
+pub const SUPPORTED_ENDPOINTS: &[&str] = &["/v1/chat/completions", "/v1/completions", "/v1/responses", "/v1/messages"];
+/// 单个请求在限流类错误 (429/503/529) 下的最大尝试次数
+const MAX_ATTEMPTS: u32 = 6;
+/// 并发上限 (实际并发 = min(账号数, 上限))
+const MAX_CONCURRENCY: usize = 8;
+/// 无可用账号时的轮询间隔
+const ACCOUNT_WAIT_SECS: u64 = 15;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileObject {
+    pub id: String,
+    pub object: String,
+    pub bytes: u64,
+    pub created_at: i64,
+    pub filename: String,
+    pub purpose: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Validating,
+    Failed,
+    InProgress,
+    Finalizing,
+    Completed,
+    Expired,
+    Cancelling,
+    Cancelled,
+}
+
+impl BatchStatus {
+    fn is_terminal(self) -> bool {
+        matches!(self, Self::Failed | Self::Completed | Self::Expired | Self::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RequestCounts {
+    pub total: u64,
+    pub completed: u64,
+    pub failed: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchObject {
+    pub id: String,
+    pub object: String,
+    pub endpoint: String,
+    pub input_file_id: String,
+    pub completion_window: String,
+    pub status: BatchStatus,
+    pub output_file_id: Option<String>,
+    pub error_file_id: Option<String>,
+    pub created_at: i64,
+    pub in_progress_at: Option<i64>,
+    pub expires_at: i64,
+    pub finalizing_at: Option<i64>,
+    pub completed_at: Option<i64>,
+    pub failed_at: Option<i64>,
+    pub expired_at: Option<i64>,
+    pub cancelling_at: Option<i64>,
+    pub cancelled_at: Option<i64>,
+    pub request_counts: RequestCounts,
+    #[serde(default)]
+    pub errors: Option<Value>,
+    #[serde(default)]
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateBatchRequest {
+    pub input_file_id: String,
+    pub endpoint: String,
+    #[serde(default = "default_completion_window")]
+    pub completion_window: String,
+    #[serde(default)]
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+fn default_completion_window() -> String {
+    "24h".to_string()
+}
+
+/// 输入文件中的一行
+#[derive(Debug, Clone, Deserialize)]
+struct BatchRequestLine {
+    custom_id: String,
+    #[serde(default)]
+    method: Option<String>,
+    url: String,
+    body: Value,
+}
+
+pub struct BatchManager {
+    root: PathBuf,
+    batches: Mutex<HashMap<String, BatchObject>>,
+    cancel_flags: DashMap<String, Arc<AtomicBool>>,
+    // 串行化结果文件追加写
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+impl BatchManager {
+    pub fn new(root: PathBuf) -> Self {
+        let _ = std::fs::create_dir_all(root.join("files"));
+        let _ = std::fs::create_dir_all(root.join("jobs"));
+
+        let mut batches = HashMap::new();
+        if let Ok(entries) = std::fs::read_dir(root.join("jobs")) {
+            for entry in entries.flatten() {
+                let Ok(content) = std::fs::read_to_string(entry.path()) else { continue };
+                match serde_json::from_str::<BatchObject>(&content) {
+                    Ok(batch) => {
+                        batches.insert(batch.id.clone(), batch);
+                    }
+                    Err(e) => tracing::warn!("[Batch] 跳过无法解析的批任务文件 {:?}: {}", entry.path(), e),
+                }
+            }
+        }
+
+        Self {
+            root,
+            batches: Mutex::new(batches),
+            cancel_flags: DashMap::new(),
+            write_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// 全局实例 (<data_dir>/batches)，反代服务重启后运行中的任务不会被重复调度
+    pub fn global() -> Arc<Self> {
+        static INSTANCE: OnceLock<Arc<BatchManager>> = OnceLock::new();
+        INSTANCE
+            .get_or_init(|| {
+                let root = crate::modules::account::get_data_dir()
+                    .map(|d| d.join("batches"))
+                    .unwrap_or_else(|_| std::env::temp_dir().join("antigravity_batches"));
+                Arc::new(Self::new(root))
+            })
+            .clone()
+    }
+
+    // ===== Files =====
+
+    fn file_path(&self, id: &str) -> PathBuf {
+        self.root.join("files").join(id)
+    }
+
+    fn file_meta_path(&self, id: &str) -> PathBuf {
+        self.root.join("files").join(format!("{}.json", id))
+    }
+
+    pub fn create_file(&self, filename: &str, purpose: &str, bytes: &[u8]) -> Result<FileObject, String> {
+        let file = FileObject {
+            id: format!("file-{}", uuid::Uuid::new_v4().simple()),
+            object: "file".to_string(),
+            bytes: bytes.len() as u64,
+            created_at: chrono::Utc::now().timestamp(),
+            filename: filename.to_string(),
+            purpose: purpose.to_string(),
+        };
+        std::fs::write(self.file_path(&file.id), bytes).map_err(|e| format!("Failed to store file: {}", e))?;
+        self.save_file_meta(&file)?;
+        Ok(file)
+    }
+
+    fn save_file_meta(&self, file: &FileObject) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(file).map_err(|e| e.to_string())?;
+        std::fs::write(self.file_meta_path(&file.id), json).map_err(|e| format!("Failed to store file metadata: {}", e))
+    }
+
+    pub fn get_file(&self, id: &str) -> Option<FileObject> {
+        if !is_safe_id(id) {
+            return None;
+        }
+        let content = std::fs::read_to_string(self.file_meta_path(id)).ok()?;
+        let mut file: FileObject = serde_json::from_str(&content).ok()?;
+        // 结果文件在执行过程中持续增长
+        if let Ok(meta) = std::fs::metadata(self.file_path(id)) {
+            file.bytes = meta.len();
+        }
+        Some(file)
+    }
+
+    pub fn list_files(&self) -> Vec<FileObject> {
+        let mut files: Vec<FileObject> = std::fs::read_dir(self.root.join("files"))
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|e| {
+                        let name = e.file_name().to_string_lossy().to_string();
+                        name.strip_suffix(".json").and_then(|id| self.get_file(id))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        files
+    }
+
+    pub fn file_content(&self, id: &str) -> Result<Vec<u8>, String> {
+        if self.get_file(id).is_none() {
+            return Err(format!("No such file: {}", id));
+        }
+        std::fs::read(self.file_path(id)).map_err(|e| format!("Failed to read file: {}", e))
+    }
+
+    pub fn delete_file(&self, id: &str) -> Result<(), String> {
+        if self.get_file(id).is_none() {
+            return Err(format!("No such file: {}", id));
+        }
+        let _ = std::fs::remove_file(self.file_path(id));
+        std::fs::remove_file(self.file_meta_path(id)).map_err(|e| format!("Failed to delete file: {}", e))
+    }
+
+    // ===== Batches =====
+
+    pub fn create_batch(&self, req: CreateBatchRequest) -> Result<BatchObject, String> {
+        if !SUPPORTED_ENDPOINTS.contains(&req.endpoint.as_str()) {
+            return Err(format!(
+                "Unsupported endpoint {}; expected one of {}",
+                req.endpoint,
+                SUPPORTED_ENDPOINTS.join(", ")
+            ));
+        }
+        let window_secs = parse_completion_window(&req.completion_window)
+            .ok_or_else(|| format!("Invalid completion_window: {}", req.completion_window))?;
+
+        let content = self.file_content(&req.input_file_id)?;
+        let lines = parse_input(&String::from_utf8_lossy(&content), &req.endpoint)?;
+
+        let now = chrono::Utc::now().timestamp();
+        let id = format!("batch_{}", uuid::Uuid::new_v4().simple());
+        let output = self.create_file(&format!("{}_output.jsonl", id), "batch_output", b"")?;
+        let errors = self.create_file(&format!("{}_errors.jsonl", id), "batch_output", b"")?;
+
+        let batch = BatchObject {
+            id: id.clone(),
+            object: "batch".to_string(),
+            endpoint: req.endpoint,
+            input_file_id: req.input_file_id,
+            completion_window: req.completion_window,
+            status: BatchStatus::Validating,
+            output_file_id: Some(output.id),
+            error_file_id: Some(errors.id),
+            created_at: now,
+            in_progress_at: None,
+            expires_at: now + window_secs,
+            finalizing_at: None,
+            completed_at: None,
+            failed_at: None,
+            expired_at: None,
+            cancelling_at: None,
+            cancelled_at: None,
+            request_counts: RequestCounts { total: lines.len() as u64, ..Default::default() },
+            errors: None,
+            metadata: req.metadata,
+        };
+        self.store_batch(batch.clone());
+        Ok(batch)
+    }
+
+    pub fn get_batch(&self, id: &str) -> Option<BatchObject> {
+        self.batches.lock().ok()?.get(id).cloned()
+    }
+
+    /// 按创建时间倒序分页 (after 为上一页最后一个 id)
+    pub fn list_batches(&self, limit: usize, after: Option<&str>) -> (Vec<BatchObject>, bool) {
+        let mut all: Vec<BatchObject> = self
+            .batches
+            .lock()
+            .map(|b| b.values().cloned().collect())
+            .unwrap_or_default();
+        all.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| b.id.cmp(&a.id)));
+
+        let start = after
+            .and_then(|after| all.iter().position(|b| b.id == after).map(|i| i + 1))
+            .unwrap_or(0);
+        let page: Vec<BatchObject> = all.iter().skip(start).take(limit).cloned().collect();
+        let has_more = start + page.len() < all.len();
+        (page, has_more)
+    }
+
+    pub fn cancel_batch(&self, id: &str) -> Result<BatchObject, String> {
+        let batch = self.get_batch(id).ok_or_else(|| format!("No such batch: {}", id))?;
+        if batch.status.is_terminal() {
+            return Err(format!("Batch {} has already finished ({:?})", id, batch.status));
+        }
+        if let Some(flag) = self.cancel_flags.get(id) {
+            flag.store(true, Ordering::SeqCst);
+        }
+        let running = self.cancel_flags.contains_key(id);
+        self.update_batch(id, |b| {
+            let now = chrono::Utc::now().timestamp();
+            b.cancelling_at = Some(now);
+            if running {
+                b.status = BatchStatus::Cancelling;
+            } else {
+                b.status = BatchStatus::Cancelled;
+                b.cancelled_at = Some(now);
+            }
+        })
+        .ok_or_else(|| format!("No such batch: {}", id))
+    }
+
+    /// 紧急擦除: 停止全部批任务并清空内存中的任务表，返回待删除的输入/结果文件与任务记录
+    /// 运行中的任务此后不再写入结果或持久化任务记录
+    pub fn wipe(&self) -> Vec<PathBuf> {
+        for flag in self.cancel_flags.iter() {
+            flag.store(true, Ordering::SeqCst);
+        }
+        if let Ok(mut batches) = self.batches.lock() {
+            batches.clear();
+        }
+        ["files", "jobs"]
+            .iter()
+            .filter_map(|sub| std::fs::read_dir(self.root.join(sub)).ok())
+            .flat_map(|entries| entries.flatten().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .collect()
+    }
+
+    fn store_batch(&self, batch: BatchObject) {
+        if let Err(e) = self.persist_batch(&batch) {
+            tracing::warn!("[Batch] 持久化批任务 {} 失败: {}", batch.id, e);
+        }
+        if let Ok(mut batches) = self.batches.lock() {
+            batches.insert(batch.id.clone(), batch);
+        }
+    }
+
+    fn persist_batch(&self, batch: &BatchObject) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(batch).map_err(|e| e.to_string())?;
+        let path = self.root.join("jobs").join(format!("{}.json", batch.id));
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+    }
+
+    fn update_batch(&self, id: &str, f: impl FnOnce(&mut BatchObject)) -> Option<BatchObject> {
+        let updated = {
+            let mut batches = self.batches.lock().ok()?;
+            let batch = batches.get_mut(id)?;
+            f(batch);
+            batch.clone()
+        };
+        if let Err(e) = self.persist_batch(&updated) {
+            tracing::warn!("[Batch] 持久化批任务 {} 失败: {}", id, e);
+        }
+        Some(updated)
+    }
+
+    // ===== 执行 =====
+
+    /// 启动后恢复未完成的批任务 (已落盘的 custom_id 不会重复执行)
+    pub fn resume_pending(self: &Arc<Self>, state: AppState) {
+        let pending: Vec<BatchObject> = self
+            .batches
+            .lock()
+            .map(|b| b.values().filter(|b| !b.status.is_terminal()).cloned().collect())
+            .unwrap_or_default();
+
+        for batch in pending {
+            if batch.status == BatchStatus::Cancelling {
+                self.update_batch(&batch.id, |b| {
+                    b.status = BatchStatus::Cancelled;
+                    b.cancelled_at = Some(chrono::Utc::now().timestamp());
+                });
+                continue;
+            }
+            tracing::info!("[Batch] 恢复批任务 {} ({}/{} 已完成)", batch.id,
+                batch.request_counts.completed + batch.request_counts.failed, batch.request_counts.total);
+            self.spawn(state.clone(), batch.id);
+        }
+    }
+
+    pub fn spawn(self: &Arc<Self>, state: AppState, batch_id: String) {
+        let flag = Arc::new(AtomicBool::new(false));
+        match self.cancel_flags.entry(batch_id.clone()) {
+            dashmap::mapref::entry::Entry::Occupied(_) => return, // 已在运行
+            dashmap::mapref::entry::Entry::Vacant(v) => {
+                v.insert(flag.clone());
+            }
+        }
+        let manager = self.clone();
+        tokio::spawn(async move {
+            manager.run(state, &batch_id, flag).await;
+            manager.cancel_flags.remove(&batch_id);
+        });
+    }
+
+    async fn run(self: &Arc<Self>, state: AppState, batch_id: &str, cancelled: Arc<AtomicBool>) {
+        let Some(batch) = self.get_batch(batch_id) else { return };
+
+        let requests = match self
+            .file_content(&batch.input_file_id)
+            .and_then(|c| parse_input(&String::from_utf8_lossy(&c), &batch.endpoint))
+        {
+            Ok(r) => r,
+            Err(e) => {
+                tracing::error!("[Batch] 批任务 {} 输入无效: {}", batch_id, e);
+                self.update_batch(batch_id, |b| {
+                    b.status = BatchStatus::Failed;
+                    b.failed_at = Some(chrono::Utc::now().timestamp());
+                    b.errors = Some(json!({ "object": "list", "data": [{ "code": "invalid_input", "message": e }] }));
+                });
+                return;
+            }
+        };
+
+        let output_path = batch.output_file_id.as_deref().map(|id| self.file_path(id));
+        let error_path = batch.error_file_id.as_deref().map(|id| self.file_path(id));
+        let done = finished_custom_ids(output_path.as_deref(), error_path.as_deref());
+
+        self.update_batch(batch_id, |b| {
+            b.status = BatchStatus::InProgress;
+            b.in_progress_at.get_or_insert(chrono::Utc::now().timestamp());
+        });
+
+        let concurrency = state.token_manager.len().clamp(1, MAX_CONCURRENCY);
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+        let mut tasks = tokio::task::JoinSet::new();
+        let mut expired = false;
+
+        for line in requests.into_iter().filter(|r| !done.contains(&r.custom_id)) {
+            if cancelled.load(Ordering::SeqCst) {
+                break;
+            }
+            if chrono::Utc::now().timestamp() > batch.expires_at {
+                expired = true;
+                break;
+            }
+            let Ok(permit) = semaphore.clone().acquire_owned().await else { break };
+
+            // 所有账号都被限流时先等待，而不是把请求打成 429
+            let model = line.body.get("model").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+            while !cancelled.load(Ordering::SeqCst)
+                && state.token_manager.len() > 0
+                && !state.token_manager.has_available_account("", &model).await
+            {
+                tokio::time::sleep(Duration::from_secs(ACCOUNT_WAIT_SECS)).await;
+            }
+
+            let manager = self.clone();
+            let state = state.clone();
+            let batch_id = batch_id.to_string();
+            let endpoint = batch.endpoint.clone();
+            let (output_path, error_path) = (output_path.clone(), error_path.clone());
+            tasks.spawn(async move {
+                let (status, body) = retry_with_backoff(&state, &endpoint, line.body, Some(batch_id.as_str())).await;
+                let (record, ok) = build_result_line(&line.custom_id, status, body);
+                let path = if ok { output_path } else { error_path };
+                // 已被擦除的批任务不再重建结果文件
+                if let Some(path) = path.filter(|_| manager.get_batch(&batch_id).is_some()) {
+                    let _guard = manager.write_lock.lock().await;
+                    if let Err(e) = append_line(&path, &record) {
+                        tracing::error!("[Batch] 写入结果失败 ({}): {}", batch_id, e);
+                    }
+                }
+                manager.update_batch(&batch_id, |b| {
+                    if ok {
+                        b.request_counts.completed += 1;
+                    } else {
+                        b.request_counts.failed += 1;
+                    }
+                });
+                drop(permit);
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+
+        if !cancelled.load(Ordering::SeqCst) && !expired {
+            self.update_batch(batch_id, |b| {
+                b.status = BatchStatus::Finalizing;
+                b.finalizing_at = Some(chrono::Utc::now().timestamp());
+            });
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let updated = self.update_batch(batch_id, |b| {
+            if cancelled.load(Ordering::SeqCst) {
+                b.status = BatchStatus::Cancelled;
+                b.cancelled_at = Some(now);
+            } else if expired {
+                b.status = BatchStatus::Expired;
+                b.expired_at = Some(now);
+            } else {
+                b.status = BatchStatus::Completed;
+                b.completed_at = Some(now);
+            }
+        });
+        if let Some(b) = updated {
+            tracing::info!(
+                "[Batch] 批任务 {} 结束: {:?} (成功 {}, 失败 {}, 共 {})",
+                b.id, b.status, b.request_counts.completed, b.request_counts.failed, b.request_counts.total
+            );
+        }
+    }
+}
+
+/// 限流类错误按 Retry-After 或指数退避重试
+pub(crate) async fn execute_with_backoff(state: &AppState, endpoint: &str, body: Value) -> (u16, Value) {
+    retry_with_backoff(state, endpoint, body, None).await
+}
+
+/// batch_id 不为空时每次尝试都写入请求历史 (批处理行没有外层请求可供 monitor 记录)
+async fn retry_with_backoff(state: &AppState, endpoint: &str, body: Value, batch_id: Option<&str>) -> (u16, Value) {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let (status, retry_after, value) = dispatch_recorded(state, endpoint, body.clone(), batch_id).await;
+        let retryable = matches!(status, 429 | 503 | 529);
+        if !retryable || attempt >= MAX_ATTEMPTS {
+            return (status, value);
+        }
+        let wait = retry_after.unwrap_or_else(|| (15u64 << (attempt - 1)).min(300));
+        tracing::warn!("[Batch] 上游返回 {}，{} 秒后重试 (第 {}/{} 次)", status, wait, attempt, MAX_ATTEMPTS);
+        tokio::time::sleep(Duration::from_secs(wait)).await;
+    }
+}
+
+/// 进程内直接调用对应 handler，复用账号轮换/重试/协议转换逻辑
+pub(crate) async fn dispatch(state: &AppState, endpoint: &str, body: Value) -> (u16, Option<u64>, Value) {
+    dispatch_recorded(state, endpoint, body, None).await
+}
+
+async fn dispatch_recorded(
+    state: &AppState,
+    endpoint: &str,
+    mut body: Value,
+    batch_id: Option<&str>,
+) -> (u16, Option<u64>, Value) {
+    use crate::proxy::handlers;
+
+    // 批处理只支持非流式
+    if let Some(obj) = body.as_object_mut() {
+        obj.insert("stream".to_string(), Value::Bool(false));
+    }
+    // 不经过路由中间件，脱敏在这里补上
+    let pii_config = state.pii_scrub.read().await.clone();
+    let pii_map = scrub_body(&pii_config, &mut body);
+    // 与 monitor 中间件一致，历史中记录的是已脱敏的请求与尚未还原的响应
+    let history = batch_id.filter(|_| state.monitor.is_enabled()).map(|id| (id, body.clone()));
+    let started = Instant::now();
+
+    let response: Response = match endpoint {
+        "/v1/chat/completions" => {
+            handlers::openai::handle_chat_completions(State(state.clone()), StreamingJson(body))
+                .await
+                .into_response()
+        }
+        "/v1/completions" | "/v1/responses" => {
+            handlers::openai::handle_completions(State(state.clone()), StreamingJson(body))
+                .await
+                .into_response()
+        }
+        "/v1/messages" => {
+            handlers::claude::handle_messages(State(state.clone()), HeaderMap::new(), StreamingJson(body)).await
+        }
+        other => {
+            return (400, None, json!({ "error": { "message": format!("Unsupported endpoint {}", other) } }));
+        }
+    };
+
+    let status = response.status().as_u16();
+    let header = |name: &str| response.headers().get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string());
+    let retry_after = header("retry-after").and_then(|v| v.trim().parse::<u64>().ok());
+    let (account_email, mapped_model) = (header("X-Account-Email"), header("X-Mapped-Model"));
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap_or_default();
+    let mut value = serde_json::from_slice::<Value>(&bytes)
+        .unwrap_or_else(|_| json!({ "error": { "message": String::from_utf8_lossy(&bytes) } }));
+    if let Some((batch_id, request)) = history {
+        let mut log = history_record(batch_id, endpoint, &request, status, &value, started.elapsed().as_millis() as u64);
+        log.account_email = account_email;
+        log.mapped_model = mapped_model;
+        state.monitor.record_hit();
+        state.monitor.log_request(log).await;
+    }
+    if let Some(map) = pii_map {
+        map.unmask_value(&mut value);
+    }
+    (status, retry_after, value)
+}
+
+/// 批处理行的请求历史 (与 monitor 中间件记录的非流式请求相同，按 `batch=<id>` 打标签以便归集用量)
+fn history_record(batch_id: &str, endpoint: &str, request: &Value, status: u16, response: &Value, duration: u64) -> ProxyRequestLog {
+    let (input_tokens, output_tokens) = usage_tokens(response);
+    let response_body = response.to_string();
+    let tag = format!("batch={}", batch_id);
+    ProxyRequestLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        method: "POST".to_string(),
+        url: endpoint.to_string(),
+        status,
+        duration,
+        model: request.get("model").and_then(|m| m.as_str()).map(|m| m.to_string()),
+        mapped_model: None,
+        account_email: None,
+        error: (status >= 400).then(|| response_body.clone()),
+        request_body: Some(request.to_string()),
+        response_body: Some(response_body),
+        input_tokens,
+        output_tokens,
+        protocol: Some(if endpoint == "/v1/messages" { "anthropic" } else { "openai" }.to_string()),
+        session_id: None,
+        notes: None,
+        tags: parse_tags(std::iter::once(tag.as_str())),
+        ttft_ms: None,
+        tokens_per_sec: None,
+        chunk_count: None,
+        message_id: None,
+        parent_message_id: None,
+        error_code: None,
+        error_hint: None,
+    }
+}
+
+/// 构造结果行 (OpenAI batch output 格式)，返回 (行, 是否成功)
+fn build_result_line(custom_id: &str, status: u16, body: Value) -> (Value, bool) {
+    let ok = (200..300).contains(&status);
+    let request_id = format!("req_{}", uuid::Uuid::new_v4().simple());
+    let error = if ok {
+        Value::Null
+    } else {
+        json!({
+            "code": status.to_string(),
+            "message": body
+                .pointer("/error/message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("request failed")
+        })
+    };
+    let line = json!({
+        "id": format!("batch_req_{}", uuid::Uuid::new_v4().simple()),
+        "custom_id": custom_id,
+        "response": { "status_code": status, "request_id": request_id, "body": body },
+        "error": error,
+    });
+    (line, ok)
+}
+
+fn parse_input(content: &str, endpoint: &str) -> Result<Vec<BatchRequestLine>, String> {
+    let mut seen = HashSet::new();
+    let mut lines = Vec::new();
+    for (idx, raw) in content.lines().enumerate() {
+        if raw.trim().is_empty() {
+            continue;
+        }
+        let line: BatchRequestLine = serde_json::from_str(raw)
+            .map_err(|e| format!("Line {}: invalid request: {}", idx + 1, e))?;
+        if line.url != endpoint {
+            return Err(format!("Line {}: url {} does not match batch endpoint {}", idx + 1, line.url, endpoint));
+        }
+        if line.method.as_deref().is_some_and(|m| !m.eq_ignore_ascii_case("POST")) {
+            return Err(format!("Line {}: only POST is supported", idx + 1));
+        }
+        if !seen.insert(line.custom_id.clone()) {
+            return Err(format!("Line {}: duplicate custom_id {}", idx + 1, line.custom_id));
+        }
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        return Err("Input file contains no requests".to_string());
+    }
+    Ok(lines)
+}
+
+fn parse_completion_window(window: &str) -> Option<i64> {
+    let (num, unit) = window.split_at(window.len().checked_sub(1)?);
+    let n: i64 = num.parse().ok().filter(|n| *n > 0)?;
+    match unit {
+        "h" => Some(n * 3600),
+        "d" => Some(n * 86400),
+        _ => None,
+    }
+}
+
+fn finished_custom_ids(output: Option<&Path>, errors: Option<&Path>) -> HashSet<String> {
+    let mut ids = HashSet::new();
+    for path in [output, errors].into_iter().flatten() {
+        let Ok(content) = std::fs::read_to_string(path) else { continue };
+        for line in content.lines() {
+            if let Some(id) = serde_json::from_str::<Value>(line)
+                .ok()
+                .and_then(|v| v.get("custom_id").and_then(|c| c.as_str()).map(String::from))
+            {
+                ids.insert(id);
+            }
+        }
+    }
+    ids
+}
+
+fn append_line(path: &Path, value: &Value) -> Result<(), String> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", value).map_err(|e| e.to_string())
+}
+
+/// 防止路径穿越: id 只允许字母数字、- 与 _
+fn is_safe_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_manager() -> BatchManager {
+        BatchManager::new(std::env::temp_dir().join(format!("ag_batch_test_{}", uuid::Uuid::new_v4().simple())))
+    }
+
+    #[test]
+    fn test_batch_line_produces_history_record() {
+        let request = json!({"model": "gemini-2.5-flash", "messages": [{"role": "user", "content": "hi"}], "stream": false});
+        let response = json!({"choices": [], "usage": {"prompt_tokens": 12, "completion_tokens": 34}});
+        let log = history_record("batch_abc", "/v1/chat/completions", &request, 200, &response, 850);
+        assert_eq!((log.method.as_str(), log.url.as_str(), log.status), ("POST", "/v1/chat/completions", 200));
+        assert_eq!(log.model.as_deref(), Some("gemini-2.5-flash"));
+        assert_eq!((log.input_tokens, log.output_tokens), (Some(12), Some(34)));
+        assert_eq!(log.tags.as_deref(), Some("batch=batch_abc"));
+        assert_eq!(log.protocol.as_deref(), Some("openai"));
+        assert!(log.error.is_none());
+        assert_eq!(log.request_body.as_deref(), Some(request.to_string().as_str()));
+
+        // 失败的行同样记录，错误体写入 error
+        let error = json!({"error": {"message": "quota exhausted"}});
+        let log = history_record("batch_abc", "/v1/messages", &request, 429, &error, 10);
+        assert_eq!(log.protocol.as_deref(), Some("anthropic"));
+        assert_eq!((log.input_tokens, log.output_tokens), (None, None));
+        assert!(log.error.unwrap().contains("quota exhausted"));
+    }
+
+    #[test]
+    fn test_parse_input_validates_lines() {
+        let ok = "{\"custom_id\":\"a\",\"method\":\"POST\",\"url\":\"/v1/chat/completions\",\"body\":{}}\n\n\
+                  {\"custom_id\":\"b\",\"url\":\"/v1/chat/completions\",\"body\":{}}";
+        assert_eq!(parse_input(ok, "/v1/chat/completions").unwrap().len(), 2);
+
+        let dup = "{\"custom_id\":\"a\",\"url\":\"/v1/chat/completions\",\"body\":{}}\n\
+                   {\"custom_id\":\"a\",\"url\":\"/v1/chat/completions\",\"body\":{}}";
+        assert!(parse_input(dup, "/v1/chat/completions").unwrap_err().contains("duplicate"));
+
+        let wrong_url = "{\"custom_id\":\"a\",\"url\":\"/v1/messages\",\"body\":{}}";
+        assert!(parse_input(wrong_url, "/v1/chat/completions").is_err());
+    }
+
+    #[test]
+    fn test_completion_window() {
+        assert_eq!(parse_completion_window("24h"), Some(86400));
+        assert_eq!(parse_completion_window("2d"), Some(172800));
+        assert_eq!(parse_completion_window("0h"), None);
+        assert_eq!(parse_completion_window(""), None);
+    }
+
+    #[test]
+    fn test_create_batch_and_resume_skips_finished() {
+        let manager = temp_manager();
+        let input = "{\"custom_id\":\"a\",\"url\":\"/v1/chat/completions\",\"body\":{}}\n\
+                     {\"custom_id\":\"b\",\"url\":\"/v1/chat/completions\",\"body\":{}}";
+        let file = manager.create_file("in.jsonl", "batch", input.as_bytes()).unwrap();
+        let batch = manager
+            .create_batch(CreateBatchRequest {
+                input_file_id: file.id,
+                endpoint: "/v1/chat/completions".to_string(),
+                completion_window: "24h".to_string(),
+                metadata: None,
+            })
+            .unwrap();
+        assert_eq!(batch.request_counts.total, 2);
+        assert_eq!(batch.status, BatchStatus::Validating);
+
+        let (line, ok) = build_result_line("a", 200, json!({"choices": []}));
+        assert!(ok);
+        let output = manager.file_path(batch.output_file_id.as_deref().unwrap());
+        append_line(&output, &line).unwrap();
+        let done = finished_custom_ids(Some(&output), None);
+        assert!(done.contains("a") && !done.contains("b"));
+
+        // 重新加载后批任务仍在
+        let reloaded = BatchManager::new(manager.root.clone());
+        assert!(reloaded.get_batch(&batch.id).is_some());
+        let _ = std::fs::remove_dir_all(&manager.root);
+    }
+
+    #[test]
+    fn test_wipe_lists_all_files_and_forgets_batches() {
+        let manager = temp_manager();
+        let input = "{\"custom_id\":\"a\",\"url\":\"/v1/chat/completions\",\"body\":{}}";
+        let file = manager.create_file("in.jsonl", "batch", input.as_bytes()).unwrap();
+        let batch = manager
+            .create_batch(CreateBatchRequest {
+                input_file_id: file.id.clone(),
+                endpoint: "/v1/chat/completions".to_string(),
+                completion_window: "24h".to_string(),
+                metadata: None,
+            })
+            .unwrap();
+
+        let paths = manager.wipe();
+        assert!(paths.contains(&manager.file_path(&file.id)));
+        assert!(paths.contains(&manager.file_meta_path(&file.id)));
+        assert!(paths.contains(&manager.root.join("jobs").join(format!("{}.json", batch.id))));
+        assert!(manager.get_batch(&batch.id).is_none());
+        // 擦除后的任务不再被持久化
+        assert!(manager.update_batch(&batch.id, |b| b.status = BatchStatus::Failed).is_none());
+        let _ = std::fs::remove_dir_all(&manager.root);
+    }
+
+    #[test]
+    fn test_rejects_path_traversal_ids() {
+        let manager = temp_manager();
+        assert!(manager.get_file("../secrets").is_none());
+        assert!(manager.file_content("..").is_err());
+        let _ = std::fs::remove_dir_all(&manager.root);
+    }
+}
@@ -0,0 +1,353 @@
+// 熔断器
+// 按 (账号, 端点) 统计连续失败: 达到阈值后熔断，熔断期间该账号在该端点上的请求不再发出、立即失败，
+// 账号的所有端点都处于熔断时调度直接跳过该账号，坏掉的账号不再给轮询叠加数秒的超时。
+// 熔断到期后放行一个探测请求 (半开)，成功则恢复，失败则熔断时长翻倍。
+// 上游客户端只拿到 access token，调度器发放 token 时登记 token -> 账号的对应关系。
+
+use crate::proxy::config::CircuitBreakerConfig;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+/// 半开探测超过该时长仍未回报结果 (请求被取消等)，允许重新探测
+const PROBE_STALE_SECS: u64 = 120;
+/// 记录的最近错误信息长度上限
+const MAX_ERROR_LEN: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    consecutive_failures: u32,
+    /// 熔断截止时间 (Some 表示处于熔断或半开)
+    open_until: Option<Instant>,
+    /// 本轮熔断时长 (半开探测失败后翻倍)
+    open_for: Duration,
+    /// 半开探测开始时间
+    probe_started: Option<Instant>,
+    successes: u64,
+    failures: u64,
+    times_opened: u64,
+    /// 熔断期间被直接拒绝的请求数
+    short_circuited: u64,
+    last_error: Option<String>,
+}
+
+impl Circuit {
+    fn state(&self, now: Instant) -> CircuitState {
+        match self.open_until {
+            None => CircuitState::Closed,
+            Some(until) if until > now => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// 熔断中、且尚未到放行探测的时候
+    fn blocks(&self, now: Instant) -> bool {
+        match self.state(now) {
+            CircuitState::Closed => false,
+            CircuitState::Open => true,
+            CircuitState::HalfOpen => self
+                .probe_started
+                .is_some_and(|at| now.duration_since(at) < Duration::from_secs(PROBE_STALE_SECS)),
+        }
+    }
+}
+
+/// 单个熔断器的对外视图 (/admin/circuits)
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitInfo {
+    pub account: String,
+    pub endpoint: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub successes: u64,
+    pub failures: u64,
+    pub times_opened: u64,
+    pub short_circuited: u64,
+    /// 距离放行探测的剩余秒数 (仅熔断中)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+pub struct CircuitBreakers {
+    config: RwLock<CircuitBreakerConfig>,
+    /// (账号邮箱, 端点) -> 熔断器
+    circuits: DashMap<(String, String), Circuit>,
+    /// access token -> 账号邮箱
+    tokens: DashMap<String, String>,
+}
+
+impl CircuitBreakers {
+    fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            circuits: DashMap::new(),
+            tokens: DashMap::new(),
+        }
+    }
+
+    pub fn global() -> Arc<Self> {
+        static INSTANCE: OnceLock<Arc<CircuitBreakers>> = OnceLock::new();
+        INSTANCE
+            .get_or_init(|| Arc::new(CircuitBreakers::new(CircuitBreakerConfig::default())))
+            .clone()
+    }
+
+    fn config(&self) -> CircuitBreakerConfig {
+        self.config.read().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    /// 更新配置 (启动及热更新时调用)；关闭后清空已有状态
+    pub fn set_config(&self, config: CircuitBreakerConfig) {
+        if !config.enabled {
+            self.circuits.clear();
+        }
+        if let Ok(mut guard) = self.config.write() {
+            *guard = config;
+        }
+    }
+
+    /// 登记 access token 所属账号 (token 刷新后旧 token 一并移除)
+    pub fn bind_token(&self, access_token: &str, account: &str) {
+        if self.tokens.get(access_token).is_some_and(|a| a.value() == account) {
+            return;
+        }
+        self.tokens.retain(|_, a| a != account);
+        self.tokens.insert(access_token.to_string(), account.to_string());
+    }
+
+    pub fn account_for_token(&self, access_token: &str) -> Option<String> {
+        self.tokens.get(access_token).map(|a| a.value().clone())
+    }
+
+    /// 请求发出前调用: 熔断中返回 false (计入短路次数)；半开时只放行一个探测请求
+    pub fn try_acquire(&self, account: &str, endpoint: &str) -> bool {
+        self.try_acquire_at(account, endpoint, Instant::now())
+    }
+
+    fn try_acquire_at(&self, account: &str, endpoint: &str, now: Instant) -> bool {
+        if !self.config().enabled {
+            return true;
+        }
+        let Some(mut circuit) = self.circuits.get_mut(&(account.to_string(), endpoint.to_string())) else {
+            return true;
+        };
+        if circuit.blocks(now) {
+            circuit.short_circuited += 1;
+            return false;
+        }
+        if circuit.state(now) == CircuitState::HalfOpen {
+            circuit.probe_started = Some(now);
+            tracing::info!("[CircuitBreaker] {} @ {} 半开，放行探测请求", account, endpoint);
+        }
+        true
+    }
+
+    pub fn record_success(&self, account: &str, endpoint: &str) {
+        if !self.config().enabled {
+            return;
+        }
+        let mut circuit = self.circuits.entry((account.to_string(), endpoint.to_string())).or_default();
+        if circuit.open_until.is_some() {
+            tracing::info!("[CircuitBreaker] {} @ {} 探测成功，熔断恢复", account, endpoint);
+        }
+        circuit.successes += 1;
+        circuit.consecutive_failures = 0;
+        circuit.open_until = None;
+        circuit.probe_started = None;
+        circuit.open_for = Duration::ZERO;
+    }
+
+    pub fn record_failure(&self, account: &str, endpoint: &str, error: &str) {
+        self.record_failure_at(account, endpoint, error, Instant::now())
+    }
+
+    fn record_failure_at(&self, account: &str, endpoint: &str, error: &str, now: Instant) {
+        let config = self.config();
+        if !config.enabled {
+            return;
+        }
+        let mut circuit = self.circuits.entry((account.to_string(), endpoint.to_string())).or_default();
+        circuit.failures += 1;
+        circuit.consecutive_failures += 1;
+        circuit.last_error = Some(error.chars().take(MAX_ERROR_LEN).collect());
+
+        let base = Duration::from_secs(config.open_secs.max(1));
+        let max = Duration::from_secs(config.max_open_secs).max(base);
+        let open_for = match circuit.state(now) {
+            // 半开探测失败: 熔断时长翻倍
+            CircuitState::HalfOpen => (circuit.open_for * 2).clamp(base, max),
+            CircuitState::Closed if circuit.consecutive_failures >= config.failure_threshold.max(1) => base,
+            // 熔断期间仍在途的请求回报失败，或未达阈值
+            _ => return,
+        };
+        circuit.open_for = open_for;
+        circuit.open_until = Some(now + open_for);
+        circuit.probe_started = None;
+        circuit.times_opened += 1;
+        tracing::warn!(
+            "[CircuitBreaker] {} @ {} 连续失败 {} 次，熔断 {}s: {}",
+            account,
+            endpoint,
+            circuit.consecutive_failures,
+            open_for.as_secs(),
+            error
+        );
+    }
+
+    /// 账号的所有端点均处于熔断中 (调度时跳过)；未出现过失败的账号不受影响
+    pub fn is_account_open(&self, account: &str) -> bool {
+        self.is_account_open_at(account, Instant::now())
+    }
+
+    fn is_account_open_at(&self, account: &str, now: Instant) -> bool {
+        if !self.config().enabled {
+            return false;
+        }
+        let mut seen = false;
+        for entry in self.circuits.iter().filter(|e| e.key().0 == account) {
+            if entry.value().state(now) != CircuitState::Open {
+                return false;
+            }
+            seen = true;
+        }
+        seen
+    }
+
+    /// 手动复位 (指定账号或全部)，返回复位的熔断器数量
+    pub fn reset(&self, account: Option<&str>) -> usize {
+        let before = self.circuits.len();
+        match account {
+            Some(account) => self.circuits.retain(|(a, _), _| a != account),
+            None => self.circuits.clear(),
+        }
+        before - self.circuits.len()
+    }
+
+    pub fn snapshot(&self) -> Vec<CircuitInfo> {
+        let now = Instant::now();
+        let mut out: Vec<CircuitInfo> = self
+            .circuits
+            .iter()
+            .map(|e| {
+                let circuit = e.value();
+                let state = circuit.state(now);
+                CircuitInfo {
+                    account: e.key().0.clone(),
+                    endpoint: e.key().1.clone(),
+                    state,
+                    consecutive_failures: circuit.consecutive_failures,
+                    successes: circuit.successes,
+                    failures: circuit.failures,
+                    times_opened: circuit.times_opened,
+                    short_circuited: circuit.short_circuited,
+                    retry_in_secs: circuit
+                        .open_until
+                        .filter(|_| state == CircuitState::Open)
+                        .map(|until| until.duration_since(now).as_secs()),
+                    last_error: circuit.last_error.clone(),
+                }
+            })
+            .collect();
+        out.sort_by(|a, b| (&a.account, &a.endpoint).cmp(&(&b.account, &b.endpoint)));
+        out
+    }
+}
+
+/// 上游响应是否计入熔断失败: 凭证/权限错误、超时与服务端错误
+/// 429 与 503/529 (容量不足) 由限流跟踪处理，不代表账号或端点已损坏
+pub fn is_breaker_failure(status: u16) -> bool {
+    matches!(status, 401 | 403 | 408 | 500 | 502 | 504)
+}
+
+/// 不影响熔断状态的响应 (既不算失败也不清零连续失败)
+pub fn is_breaker_neutral(status: u16) -> bool {
+    matches!(status, 429 | 503 | 529)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROD: &str = "https://prod";
+    const DAILY: &str = "https://daily";
+
+    fn breakers() -> CircuitBreakers {
+        CircuitBreakers::new(CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 3,
+            open_secs: 10,
+            max_open_secs: 25,
+        })
+    }
+
+    #[test]
+    fn test_opens_after_threshold_and_probes() {
+        let cb = breakers();
+        let now = Instant::now();
+        cb.record_failure_at("a@x.com", PROD, "timeout", now);
+        cb.record_failure_at("a@x.com", PROD, "timeout", now);
+        assert!(cb.try_acquire_at("a@x.com", PROD, now));
+        cb.record_failure_at("a@x.com", PROD, "timeout", now);
+        assert!(!cb.try_acquire_at("a@x.com", PROD, now + Duration::from_secs(5)));
+
+        // 到期后半开: 只放行一个探测
+        let later = now + Duration::from_secs(11);
+        assert!(cb.try_acquire_at("a@x.com", PROD, later));
+        assert!(!cb.try_acquire_at("a@x.com", PROD, later));
+        // 探测失败: 熔断时长翻倍 (20s)
+        cb.record_failure_at("a@x.com", PROD, "timeout", later);
+        assert!(!cb.try_acquire_at("a@x.com", PROD, later + Duration::from_secs(15)));
+        let probe = later + Duration::from_secs(21);
+        assert!(cb.try_acquire_at("a@x.com", PROD, probe));
+        // 再次失败: 受 max_open_secs 限制
+        cb.record_failure_at("a@x.com", PROD, "timeout", probe);
+        assert!(cb.try_acquire_at("a@x.com", PROD, probe + Duration::from_secs(26)));
+        cb.record_success("a@x.com", PROD);
+        assert!(cb.try_acquire_at("a@x.com", PROD, probe + Duration::from_secs(26)));
+
+        let info = &cb.snapshot()[0];
+        assert_eq!(info.state, CircuitState::Closed);
+        assert_eq!((info.failures, info.successes, info.times_opened, info.short_circuited), (5, 1, 3, 3));
+    }
+
+    #[test]
+    fn test_account_open_only_when_all_endpoints_open() {
+        let cb = breakers();
+        let now = Instant::now();
+        for _ in 0..3 {
+            cb.record_failure_at("a@x.com", PROD, "502", now);
+        }
+        assert!(cb.is_account_open_at("a@x.com", now));
+        cb.record_success("a@x.com", DAILY);
+        assert!(!cb.is_account_open_at("a@x.com", now));
+        assert!(!cb.is_account_open_at("b@x.com", now));
+        // 半开的账号重新参与调度 (由真实请求充当探测)
+        cb.reset(Some("a@x.com"));
+        for _ in 0..3 {
+            cb.record_failure_at("a@x.com", PROD, "502", now);
+        }
+        assert!(!cb.is_account_open_at("a@x.com", now + Duration::from_secs(11)));
+    }
+
+    #[test]
+    fn test_token_binding_replaces_stale_tokens() {
+        let cb = breakers();
+        cb.bind_token("tok-1", "a@x.com");
+        cb.bind_token("tok-2", "a@x.com");
+        cb.bind_token("tok-3", "b@x.com");
+        assert_eq!(cb.account_for_token("tok-1"), None);
+        assert_eq!(cb.account_for_token("tok-2").as_deref(), Some("a@x.com"));
+        assert_eq!(cb.tokens.len(), 2);
+    }
+}
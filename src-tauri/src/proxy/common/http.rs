@@ -0,0 +1,57 @@
+// HTTP 公共工具
+// OpenAI 风格的 400 错误响应与走上游代理的 reqwest 客户端，供各 handler / upstream 复用。
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+use tokio::time::Duration;
+
+use crate::proxy::config::UpstreamProxyConfig;
+
+/// 400 invalid_request_error (OpenAI 错误格式)，param 为出错的请求字段
+pub fn invalid_request(message: impl Into<String>, param: Option<&str>) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "error": {
+                "message": message.into(),
+                "type": "invalid_request_error",
+                "param": param,
+                "code": null
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 按上游代理配置构建 HTTP 客户端 (超时至少 5 秒)
+pub fn build_client(upstream_proxy: &UpstreamProxyConfig, timeout_secs: u64) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder().timeout(Duration::from_secs(timeout_secs.max(5)));
+    if upstream_proxy.enabled && !upstream_proxy.url.is_empty() {
+        let proxy = reqwest::Proxy::all(&upstream_proxy.url).map_err(|e| format!("Invalid upstream proxy url: {}", e))?;
+        builder = builder.proxy(proxy);
+    }
+    builder.build().map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_invalid_request_body() {
+        let resp = invalid_request("fanout must be between 2 and 8", Some("fanout"));
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["type"], "invalid_request_error");
+        assert_eq!(json["error"]["param"], "fanout");
+        assert!(json["error"]["code"].is_null());
+
+        let proxy = UpstreamProxyConfig { enabled: true, url: "not a url".to_string() };
+        assert!(build_client(&proxy, 30).unwrap_err().starts_with("Invalid upstream proxy url"));
+        assert!(build_client(&UpstreamProxyConfig::default(), 0).is_ok());
+    }
+}
@@ -0,0 +1,216 @@
+// 结构化输出校验与修复
+// `response_format` 要求 JSON 时，对最终文本做解析与 (子集) JSON Schema 校验；
+// 失败时尝试本地修复：去除 Markdown 代码围栏、截取首个 JSON 值、删除尾随逗号。
+
+use serde_json::Value;
+
+/// 本地修复步骤 (记录到请求历史)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepairStep {
+    CodeFence,
+    ExtractValue,
+    TrailingComma,
+}
+
+impl RepairStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepairStep::CodeFence => "code_fence",
+            RepairStep::ExtractValue => "extract_value",
+            RepairStep::TrailingComma => "trailing_comma",
+        }
+    }
+}
+
+/// 去除 ```json ... ``` 代码围栏
+fn strip_code_fence(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    let rest = trimmed.strip_prefix("```")?;
+    // 跳过语言标记行
+    let body = match rest.find('\n') {
+        Some(pos) => &rest[pos + 1..],
+        None => rest,
+    };
+    let body = body.trim_end();
+    let body = body.strip_suffix("```").unwrap_or(body);
+    Some(body.trim().to_string())
+}
+
+/// 截取首个 `{` / `[` 到最后一个 `}` / `]` 之间的内容 (去除前后说明文字)
+fn extract_value(text: &str) -> Option<String> {
+    let start = text.find(['{', '['])?;
+    let end = text.rfind(['}', ']'])?;
+    if end <= start || (start == 0 && end == text.len() - 1) {
+        return None;
+    }
+    Some(text[start..=end].to_string())
+}
+
+/// 删除对象/数组中的尾随逗号 (跳过字符串内容)
+fn remove_trailing_commas(text: &str) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut changed = false;
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+            if matches!(next, Some('}') | Some(']')) {
+                changed = true;
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    changed.then_some(out)
+}
+
+/// 解析 JSON 文本，必要时依次尝试本地修复；返回解析结果与实际使用的修复步骤
+pub fn parse_with_repair(text: &str) -> Option<(Value, Vec<RepairStep>)> {
+    if let Ok(v) = serde_json::from_str::<Value>(text.trim()) {
+        return Some((v, Vec::new()));
+    }
+    let mut current = text.trim().to_string();
+    let mut steps = Vec::new();
+    let fixes: [(RepairStep, fn(&str) -> Option<String>); 3] = [
+        (RepairStep::CodeFence, strip_code_fence),
+        (RepairStep::ExtractValue, extract_value),
+        (RepairStep::TrailingComma, remove_trailing_commas),
+    ];
+    for (step, fix) in fixes {
+        if let Some(fixed) = fix(&current) {
+            current = fixed;
+            steps.push(step);
+            if let Ok(v) = serde_json::from_str::<Value>(&current) {
+                return Some((v, steps));
+            }
+        }
+    }
+    None
+}
+
+fn type_matches(value: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// JSON Schema 子集校验 (type / enum / required / properties / additionalProperties / items / anyOf)
+pub fn validate(value: &Value, schema: &Value, path: &str) -> Result<(), String> {
+    let Some(schema) = schema.as_object() else {
+        return Ok(());
+    };
+    match schema.get("type") {
+        Some(Value::String(ty)) if !type_matches(value, ty) => {
+            return Err(format!("{}: expected {}", path_or_root(path), ty));
+        }
+        Some(Value::Array(types)) if !types.iter().filter_map(|t| t.as_str()).any(|t| type_matches(value, t)) => {
+            return Err(format!("{}: type not allowed", path_or_root(path)));
+        }
+        _ => {}
+    }
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !options.contains(value) {
+            return Err(format!("{}: value not in enum", path_or_root(path)));
+        }
+    }
+    if let Some(branches) = schema.get("anyOf").or_else(|| schema.get("oneOf")).and_then(|b| b.as_array()) {
+        if !branches.iter().any(|b| validate(value, b, path).is_ok()) {
+            return Err(format!("{}: matches no allowed schema", path_or_root(path)));
+        }
+    }
+    if let Some(obj) = value.as_object() {
+        for key in schema.get("required").and_then(|r| r.as_array()).into_iter().flatten().filter_map(|k| k.as_str()) {
+            if !obj.contains_key(key) {
+                return Err(format!("{}: missing required property '{}'", path_or_root(path), key));
+            }
+        }
+        let props = schema.get("properties").and_then(|p| p.as_object());
+        for (key, child) in obj {
+            match props.and_then(|p| p.get(key)) {
+                Some(child_schema) => validate(child, child_schema, &format!("{}.{}", path, key))?,
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    return Err(format!("{}: unexpected property '{}'", path_or_root(path), key));
+                }
+                None => {}
+            }
+        }
+    }
+    if let (Some(arr), Some(items)) = (value.as_array(), schema.get("items")) {
+        for (i, item) in arr.iter().enumerate() {
+            validate(item, items, &format!("{}[{}]", path, i))?;
+        }
+    }
+    Ok(())
+}
+
+fn path_or_root(path: &str) -> &str {
+    if path.is_empty() {
+        "$"
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_repairs_fences_prose_and_trailing_commas() {
+        let (v, steps) = parse_with_repair("{\"a\": 1}").unwrap();
+        assert_eq!(v, json!({"a": 1}));
+        assert!(steps.is_empty());
+
+        let (v, steps) = parse_with_repair("```json\n{\"a\": [1, 2,],}\n```").unwrap();
+        assert_eq!(v, json!({"a": [1, 2]}));
+        assert_eq!(steps, vec![RepairStep::CodeFence, RepairStep::TrailingComma]);
+
+        let (v, steps) = parse_with_repair("Here you go: {\"s\": \"a,}\"} Hope it helps").unwrap();
+        assert_eq!(v, json!({"s": "a,}"}));
+        assert_eq!(steps, vec![RepairStep::ExtractValue]);
+
+        assert!(parse_with_repair("not json at all").is_none());
+    }
+
+    #[test]
+    fn test_schema_subset_validation() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "level": { "enum": ["low", "high"] }
+            },
+            "required": ["name"],
+            "additionalProperties": false
+        });
+        assert!(validate(&json!({"name": "x", "tags": ["a"], "level": "low"}), &schema, "").is_ok());
+        assert!(validate(&json!({"tags": []}), &schema, "").unwrap_err().contains("name"));
+        assert!(validate(&json!({"name": "x", "tags": [1]}), &schema, "").unwrap_err().contains(".tags[0]"));
+        assert!(validate(&json!({"name": "x", "extra": 1}), &schema, "").is_err());
+        assert!(validate(&json!({"name": "x", "level": "mid"}), &schema, "").is_err());
+    }
+}
@@ -0,0 +1,188 @@
+// 本地文件附件 (受信模式)
+// 同机 CLI 脚本常直接传 `file:///path/to/a.png` 或绝对路径，而不是自行编码 base64。
+// 开启后由代理读取这些文件并内联；路径会先解析符号链接与 `..`，必须落在配置的根目录内，
+// 并且只对受信的 API Key 生效 (未配置受信 Key 或反代未开启鉴权时一律拒绝)，避免任意客户端借代理读取本机文件。
+
+use base64::Engine as _;
+use std::path::{Path, PathBuf};
+
+use crate::proxy::common::mime_sniff;
+use crate::proxy::config::LocalFilesConfig;
+
+/// 是否为本地文件引用: `file://` URL、Unix 绝对路径或 Windows 盘符路径
+pub fn is_local_reference(reference: &str) -> bool {
+    let r = reference.trim();
+    let b = r.as_bytes();
+    r.starts_with("file://")
+        || (r.starts_with('/') && !r.starts_with("//"))
+        || (b.len() > 2 && b[0].is_ascii_alphabetic() && b[1] == b':' && (b[2] == b'\\' || b[2] == b'/'))
+}
+
+/// 引用转换为路径；file URL 中的 %XX 转义与 Windows 盘符由 url 库处理
+pub fn local_path(reference: &str) -> PathBuf {
+    let reference = reference.trim();
+    if reference.starts_with("file://") {
+        if let Some(path) = url::Url::parse(reference).ok().and_then(|u| u.to_file_path().ok()) {
+            return path;
+        }
+    }
+    PathBuf::from(reference)
+}
+
+/// 无法嗅探出类型时 (文本类文档等) 按扩展名推断
+fn mime_from_extension(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "txt" | "log" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        "xml" => "text/xml",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        _ => return None,
+    })
+}
+
+/// 从文件名推断类型 (供 data URL 未声明类型时回退)
+pub fn mime_from_filename(filename: &str) -> Option<&'static str> {
+    mime_from_extension(Path::new(filename))
+}
+
+/// 读取到的文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalFile {
+    pub path: PathBuf,
+    pub mime_type: String,
+    pub data: String,
+}
+
+impl LocalFile {
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data)
+    }
+
+    pub fn filename(&self) -> String {
+        self.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+}
+
+/// 单次请求的本地文件访问权限
+#[derive(Debug, Clone)]
+pub struct LocalFileAccess {
+    roots: Vec<PathBuf>,
+    max_bytes: u64,
+}
+
+impl LocalFileAccess {
+    /// 未开启、反代未开启鉴权 (auth_required = false)、Key 不在受信列表或没有可用根目录时返回 None
+    /// 会解析根目录 (文件系统访问)，异步上下文中需放入 spawn_blocking
+    pub fn for_request(config: &LocalFilesConfig, api_key: Option<&str>, auth_required: bool) -> Option<Self> {
+        if !config.enabled || !auth_required {
+            return None;
+        }
+        // 受信列表为空时拒绝: Key 未经校验时无法判断请求来自受信的本机脚本
+        if !api_key.is_some_and(|k| !k.is_empty() && config.trusted_keys.iter().any(|t| t == k)) {
+            return None;
+        }
+        let roots: Vec<PathBuf> = config
+            .roots
+            .iter()
+            .map(|r| r.trim())
+            .filter(|r| !r.is_empty())
+            .filter_map(|r| match std::fs::canonicalize(r) {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    tracing::warn!("[LocalFiles] 忽略无效的根目录 {}: {}", r, e);
+                    None
+                }
+            })
+            .collect();
+        if roots.is_empty() {
+            return None;
+        }
+        Some(Self { roots, max_bytes: config.max_file_mb as u64 * 1024 * 1024 })
+    }
+
+    /// 解析并校验路径: 必须是根目录内的普通文件且不超过大小上限 (阻塞调用)
+    pub fn check(&self, reference: &str) -> Result<PathBuf, String> {
+        let requested = local_path(reference);
+        let path = std::fs::canonicalize(&requested).map_err(|e| format!("{}: {}", requested.display(), e))?;
+        if !self.roots.iter().any(|root| path.starts_with(root)) {
+            return Err(format!("{} is outside the allowed local file roots", requested.display()));
+        }
+        let meta = std::fs::metadata(&path).map_err(|e| format!("{}: {}", requested.display(), e))?;
+        if !meta.is_file() {
+            return Err(format!("{} is not a regular file", requested.display()));
+        }
+        if meta.len() > self.max_bytes {
+            return Err(format!("{} exceeds the {} byte limit", requested.display(), self.max_bytes));
+        }
+        Ok(path)
+    }
+
+    pub fn read(&self, reference: &str) -> Result<LocalFile, String> {
+        let path = self.check(reference)?;
+        let bytes = std::fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mime_type = mime_sniff::sniff(&bytes)
+            .or_else(|| mime_from_extension(&path))
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        tracing::debug!("[LocalFiles] 已读取 {} ({} 字节, {})", path.display(), bytes.len(), mime_type);
+        Ok(LocalFile { path, mime_type, data: base64::engine::general_purpose::STANDARD.encode(bytes) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_is_confined_to_roots() {
+        let dir = std::env::temp_dir().join(format!("local-files-{}", uuid::Uuid::new_v4()));
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a b.txt"), "hello").unwrap();
+        std::fs::write(dir.join("secret.txt"), "nope").unwrap();
+
+        let mut config = LocalFilesConfig {
+            enabled: true,
+            roots: vec![root.display().to_string()],
+            trusted_keys: vec!["sk-cli".to_string()],
+            ..Default::default()
+        };
+        let access = LocalFileAccess::for_request(&config, Some("sk-cli"), true).unwrap();
+
+        let file = access.read(&root.join("a b.txt").display().to_string()).unwrap();
+        assert_eq!((file.mime_type.as_str(), file.data.as_str()), ("text/plain", "aGVsbG8="));
+        #[cfg(not(target_os = "windows"))]
+        assert!(access.read(&format!("file://{}/a%20b.txt", root.display())).is_ok());
+        // `..` 逃逸与根目录外的文件都被拒绝
+        assert!(access.read(&root.join("../secret.txt").display().to_string()).is_err());
+        assert!(access.read(&root.display().to_string()).is_err());
+
+        assert!(LocalFileAccess::for_request(&config, Some("sk-other"), true).is_none());
+        assert!(LocalFileAccess::for_request(&config, None, true).is_none());
+        // 反代未开启鉴权时 Key 不可信
+        assert!(LocalFileAccess::for_request(&config, Some("sk-cli"), false).is_none());
+        config.trusted_keys.clear();
+        assert!(LocalFileAccess::for_request(&config, None, true).is_none());
+        assert!(LocalFileAccess::for_request(&config, Some("sk-cli"), true).is_none());
+        config.trusted_keys = vec!["sk-cli".to_string()];
+        config.roots.clear();
+        assert!(LocalFileAccess::for_request(&config, Some("sk-cli"), true).is_none());
+
+        assert!(is_local_reference("file:///tmp/a.png"));
+        assert!(is_local_reference("C:\\Users\\a.png"));
+        assert!(!is_local_reference("https://example.com/a.png"));
+        assert!(!is_local_reference("//cdn.example.com/a.png"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,162 @@
+// MIME 类型嗅探
+// 部分客户端发送 `data:;base64,...` 这类缺少类型的 data URL，或把 PNG 标成 JPEG；
+// 上游按声明类型解码会直接报错。这里根据解码后载荷的魔数判断真实类型，
+// 嗅探失败 (如纯文本类文档) 时才回退到客户端声明的类型。
+
+use base64::Engine as _;
+use serde_json::{json, Value};
+
+/// 嗅探只需要文件头，解码 base64 前缀即可 (64 字符 = 48 字节)
+const SNIFF_PREFIX_CHARS: usize = 64;
+
+/// 根据魔数判断类型
+pub fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    let starts = |magic: &[u8]| bytes.starts_with(magic);
+    let at = |offset: usize, magic: &[u8]| bytes.len() >= offset + magic.len() && &bytes[offset..offset + magic.len()] == magic;
+
+    if starts(b"\x89PNG\r\n\x1a\n") {
+        return Some("image/png");
+    }
+    if starts(b"\xff\xd8\xff") {
+        return Some("image/jpeg");
+    }
+    if starts(b"GIF87a") || starts(b"GIF89a") {
+        return Some("image/gif");
+    }
+    if starts(b"RIFF") {
+        if at(8, b"WEBP") {
+            return Some("image/webp");
+        }
+        if at(8, b"WAVE") {
+            return Some("audio/wav");
+        }
+        return None;
+    }
+    if starts(b"FORM") && (at(8, b"AIFF") || at(8, b"AIFC")) {
+        return Some("audio/aiff");
+    }
+    if at(4, b"ftyp") && bytes.len() >= 12 {
+        return Some(match &bytes[8..12] {
+            b"heic" | b"heix" | b"hevc" | b"hevx" => "image/heic",
+            b"mif1" | b"msf1" | b"heif" => "image/heif",
+            b"avif" | b"avis" => "image/avif",
+            b"M4A " | b"M4B " => "audio/mp4",
+            b"qt  " => "video/quicktime",
+            b"3gp4" | b"3gp5" | b"3gp6" | b"3g2a" => "video/3gpp",
+            _ => "video/mp4",
+        });
+    }
+    if starts(b"%PDF-") {
+        return Some("application/pdf");
+    }
+    if starts(b"II*\0") || starts(b"MM\0*") {
+        return Some("image/tiff");
+    }
+    // BMP: "BM" + 文件大小 + 4 字节保留位 (恒为 0)，避免把以 "BM" 开头的文本误判
+    if starts(b"BM") && at(6, b"\0\0\0\0") {
+        return Some("image/bmp");
+    }
+    if starts(b"OggS") {
+        return Some("audio/ogg");
+    }
+    if starts(b"fLaC") {
+        return Some("audio/flac");
+    }
+    if starts(b"ID3") || starts(b"\xff\xfb") || starts(b"\xff\xf3") || starts(b"\xff\xf2") {
+        return Some("audio/mpeg");
+    }
+    if starts(b"\xff\xf1") || starts(b"\xff\xf9") {
+        return Some("audio/aac");
+    }
+    if starts(b"\x1a\x45\xdf\xa3") {
+        return Some("video/webm");
+    }
+    None
+}
+
+/// 嗅探 base64 载荷 (兼容 URL-safe 字母表与换行)
+pub fn sniff_base64(data: &str) -> Option<&'static str> {
+    let prefix: String = data
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .take(SNIFF_PREFIX_CHARS)
+        .collect();
+    let prefix = prefix.trim_end_matches('=');
+    let usable = &prefix[..prefix.len() / 4 * 4];
+    if usable.is_empty() {
+        return None;
+    }
+    let bytes = base64::engine::general_purpose::STANDARD_NO_PAD
+        .decode(usable)
+        .or_else(|_| base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(usable))
+        .ok()?;
+    sniff(&bytes)
+}
+
+/// 嗅探结果优先；嗅探失败时使用声明类型，二者都没有时使用 fallback
+pub fn resolve(declared: Option<&str>, data: &str, fallback: &str) -> String {
+    let declared = declared.map(str::trim).filter(|d| !d.is_empty() && d.contains('/'));
+    match (sniff_base64(data), declared) {
+        (Some(sniffed), Some(declared)) if !sniffed.eq_ignore_ascii_case(declared) => {
+            tracing::debug!("[MimeSniff] 声明类型 {} 与实际内容 {} 不符，已更正", declared, sniffed);
+            sniffed.to_string()
+        }
+        (Some(sniffed), _) => sniffed.to_string(),
+        (None, Some(declared)) => declared.to_string(),
+        (None, None) => fallback.to_string(),
+    }
+}
+
+/// 解析 data URL: 返回 (类型, base64 数据)。类型按载荷嗅探更正
+pub fn parse_data_url(url: &str, fallback: &str) -> Option<(String, String)> {
+    let rest = url.strip_prefix("data:")?;
+    let (meta, data) = rest.split_once(',')?;
+    let declared = meta.split(';').next();
+    Some((resolve(declared, data, fallback), data.to_string()))
+}
+
+/// 更正 Gemini 原生请求中 inlineData / inline_data 的 mimeType
+pub fn fix_inline_data(part: &mut Value) {
+    let key = if part.get("inlineData").is_some() { "inlineData" } else { "inline_data" };
+    let Some(inline) = part.get_mut(key).and_then(|v| v.as_object_mut()) else { return };
+    let mime_key = if inline.contains_key("mime_type") { "mime_type" } else { "mimeType" };
+    let Some(data) = inline.get("data").and_then(|d| d.as_str()) else { return };
+    let declared = inline.get(mime_key).and_then(|m| m.as_str());
+    let resolved = resolve(declared, data, "application/octet-stream");
+    inline.insert(mime_key.to_string(), json!(resolved));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_B64: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    #[test]
+    fn test_sniff_magic_bytes() {
+        assert_eq!(sniff(b"\xff\xd8\xff\xe0\0\x10JFIF"), Some("image/jpeg"));
+        assert_eq!(sniff(b"RIFF\x24\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff(b"RIFF\x24\0\0\0WAVEfmt "), Some("audio/wav"));
+        assert_eq!(sniff(b"\0\0\0\x18ftypheic\0\0\0\0"), Some("image/heic"));
+        assert_eq!(sniff(b"\0\0\0\x20ftypisom\0\0\x02\0"), Some("video/mp4"));
+        assert_eq!(sniff(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff(b"hello world"), None);
+        assert_eq!(sniff_base64(PNG_B64), Some("image/png"));
+    }
+
+    #[test]
+    fn test_resolve_prefers_sniffed_type() {
+        assert_eq!(resolve(Some("image/jpeg"), PNG_B64, "image/jpeg"), "image/png");
+        assert_eq!(resolve(None, PNG_B64, "image/jpeg"), "image/png");
+        assert_eq!(resolve(Some("text/csv"), "YSxiLGMKMSwyLDM=", "application/octet-stream"), "text/csv");
+        assert_eq!(resolve(Some(""), "YSxiLGMKMSwyLDM=", "image/jpeg"), "image/jpeg");
+
+        let (mime, data) = parse_data_url(&format!("data:;base64,{}", PNG_B64), "image/jpeg").unwrap();
+        assert_eq!(mime, "image/png");
+        assert_eq!(data, PNG_B64);
+
+        let mut part = json!({ "inline_data": { "mime_type": "image/jpeg", "data": PNG_B64 } });
+        fix_inline_data(&mut part);
+        assert_eq!(part["inline_data"]["mime_type"], "image/png");
+    }
+}
@@ -5,3 +5,9 @@
 pub mod model_mapping;
 pub mod utils;
 pub mod json_schema;
+pub mod json_repair;
+pub mod streaming_json;
+pub mod mime_sniff;
+pub mod local_files;
+pub mod tokenizer;
+pub mod http;
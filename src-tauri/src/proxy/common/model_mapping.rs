@@ -138,14 +138,42 @@ pub async fn get_all_dynamic_models(
 /// - `gpt-4*` 匹配 `gpt-4`, `gpt-4-turbo`, `gpt-4-0613` 等
 /// - `claude-3-5-sonnet-*` 匹配所有 3.5 sonnet 版本
 /// - `*-thinking` 匹配所有以 `-thinking` 结尾的模型
-fn wildcard_match(pattern: &str, text: &str) -> bool {
-    if let Some(star_pos) = pattern.find('*') {
-        let prefix = &pattern[..star_pos];
-        let suffix = &pattern[star_pos + 1..];
-        text.starts_with(prefix) && text.ends_with(suffix)
-    } else {
-        pattern == text
+/// - `gemini-*-image*` 匹配 `gemini-3-pro-image`, `gemini-2.5-flash-image-preview` 等 (可包含多个 *)
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let Some(star_pos) = pattern.find('*') else {
+        return pattern == text;
+    };
+    let prefix = &pattern[..star_pos];
+    let Some(mut rest) = text.strip_prefix(prefix) else {
+        return false;
+    };
+    let mut segments: Vec<&str> = pattern[star_pos + 1..].split('*').collect();
+    let suffix = segments.pop().unwrap_or_default();
+    for segment in segments.into_iter().filter(|s| !s.is_empty()) {
+        match rest.find(segment) {
+            Some(pos) => rest = &rest[pos + segment.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(suffix)
+}
+
+/// 仅查询用户自定义映射 (精确匹配优先，其次通配符)，未命中返回 None
+pub fn lookup_custom_mapping(
+    original_model: &str,
+    custom_mapping: &std::collections::HashMap<String, String>,
+) -> Option<String> {
+    if let Some(target) = custom_mapping.get(original_model) {
+        crate::modules::logger::log_info(&format!("[Router] 精确映射: {} -> {}", original_model, target));
+        return Some(target.clone());
+    }
+    for (pattern, target) in custom_mapping.iter() {
+        if pattern.contains('*') && wildcard_match(pattern, original_model) {
+            crate::modules::logger::log_info(&format!("[Router] 通配符映射: {} -> {} (规则: {})", original_model, target, pattern));
+            return Some(target.clone());
+        }
     }
+    None
 }
 
 /// 核心模型路由解析引擎
@@ -161,18 +189,9 @@ pub fn resolve_model_route(
     original_model: &str,
     custom_mapping: &std::collections::HashMap<String, String>,
 ) -> String {
-    // 1. 精确匹配 (最高优先级)
-    if let Some(target) = custom_mapping.get(original_model) {
-        crate::modules::logger::log_info(&format!("[Router] 精确映射: {} -> {}", original_model, target));
-        return target.clone();
-    }
-    
-    // 2. 通配符匹配
-    for (pattern, target) in custom_mapping.iter() {
-        if pattern.contains('*') && wildcard_match(pattern, original_model) {
-            crate::modules::logger::log_info(&format!("[Router] 通配符映射: {} -> {} (规则: {})", original_model, target, pattern));
-            return target.clone();
-        }
+    // 1-2. 自定义映射 (精确 > 通配符)
+    if let Some(target) = lookup_custom_mapping(original_model, custom_mapping) {
+        return target;
     }
     
     // 3. 系统默认映射
@@ -233,4 +252,15 @@ mod tests {
             "claude-sonnet-4-5"
         );
     }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("gpt-4*", "gpt-4-turbo"));
+        assert!(wildcard_match("*-thinking", "claude-opus-4-5-thinking"));
+        assert!(wildcard_match("gemini-*-image*", "gemini-2.5-flash-image-preview"));
+        assert!(wildcard_match("gemini-*-image*", "gemini-3-pro-image"));
+        assert!(!wildcard_match("gemini-*-image*", "gemini-3-pro-high"));
+        assert!(!wildcard_match("a*a", "a"));
+        assert!(wildcard_match("exact", "exact"));
+    }
 }
@@ -0,0 +1,122 @@
+// 流式 JSON 请求体解析
+// axum::Json 需要先把完整请求体缓冲成 Bytes 再反序列化，50MB 的 base64 图片会同时存在两份。
+// 这里边读取 body 分片边反序列化 (阻塞线程中 serde_json::from_reader)，峰值内存约等于解析结果本身。
+
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    RequestExt,
+};
+use futures::TryStreamExt;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tokio_util::io::{StreamReader, SyncIoBridge};
+
+use crate::proxy::server::AppState;
+
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// 流式解析的 JSON 提取器，用法与 `Json<T>` 相同
+pub struct StreamingJson<T>(pub T);
+
+/// 413 Payload Too Large (OpenAI 风格错误体)
+pub fn payload_too_large_response(limit_bytes: usize) -> Response {
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        Json(json!({
+            "error": {
+                "message": format!(
+                    "Request body too large: limit is {} MB",
+                    limit_bytes / (1024 * 1024)
+                ),
+                "type": "invalid_request_error",
+                "code": "request_too_large"
+            }
+        })),
+    )
+        .into_response()
+}
+
+pub fn is_length_limit_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut current: Option<&(dyn std::error::Error + 'static)> = Some(err);
+    while let Some(e) = current {
+        if e.to_string().contains("length limit exceeded") {
+            return true;
+        }
+        current = e.source();
+    }
+    false
+}
+
+/// 在阻塞线程中边读取边反序列化
+async fn parse_body<T>(body: Body) -> Result<Result<T, serde_json::Error>, tokio::task::JoinError>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let stream = body
+        .into_data_stream()
+        .map_err(std::io::Error::other);
+    let reader = SyncIoBridge::new_with_handle(
+        StreamReader::new(stream),
+        tokio::runtime::Handle::current(),
+    );
+
+    tokio::task::spawn_blocking(move || {
+        serde_json::from_reader::<_, T>(std::io::BufReader::with_capacity(READ_BUFFER_SIZE, reader))
+    })
+    .await
+}
+
+#[async_trait]
+impl<T> FromRequest<AppState> for StreamingJson<T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &AppState) -> Result<Self, Self::Rejection> {
+        // with_limited_body 应用 DefaultBodyLimit 配置的上限
+        let parsed = parse_body::<T>(req.with_limited_body().into_body())
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Body parser task failed: {}", e)).into_response())?;
+
+        match parsed {
+            Ok(value) => Ok(StreamingJson(value)),
+            Err(e) if e.is_io() && is_length_limit_error(&e) => {
+                Err(payload_too_large_response(state.max_body_bytes))
+            }
+            Err(e) => Err((
+                StatusCode::BAD_REQUEST,
+                format!("Failed to parse the request body as JSON: {}", e),
+            )
+                .into_response()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_length_limit_hit_while_streaming() {
+        // 分块请求体没有 Content-Length，只能在读取途中发现超限 (未配置 DefaultBodyLimit 时默认 2MB)
+        let chunks = (0..3).map(|_| Ok::<_, std::io::Error>(bytes::Bytes::from(vec![b' '; 1024 * 1024])));
+        let req = Request::post("/v1/chat/completions")
+            .body(Body::from_stream(futures::stream::iter(chunks)))
+            .unwrap();
+        let err = parse_body::<serde_json::Value>(req.with_limited_body().into_body())
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(err.is_io() && is_length_limit_error(&err));
+        assert_eq!(payload_too_large_response(2 * 1024 * 1024).status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let small = Request::post("/v1/chat/completions").body(Body::from("{\"model\":\"m\"}")).unwrap();
+        let value = parse_body::<serde_json::Value>(small.with_limited_body().into_body()).await.unwrap().unwrap();
+        assert_eq!(value["model"], "m");
+    }
+}
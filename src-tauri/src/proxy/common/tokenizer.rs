@@ -0,0 +1,156 @@
+// 本地 token 估算
+// 按近似 BPE 预分词的规则把文本切成片段 (单词、数字、空白、标点、非 ASCII 字符) 并估算每段的 token 数，
+// 不依赖具体模型的词表；比按字节数除以常数更贴近代码文本的实际分词，用于在本地按 token 预算截断 prompt。
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CharClass {
+    Word,
+    Digit,
+    Space,
+    Newline,
+    Punct,
+    Other,
+}
+
+fn classify(c: char) -> CharClass {
+    match c {
+        'a'..='z' | 'A'..='Z' | '_' => CharClass::Word,
+        '0'..='9' => CharClass::Digit,
+        '\n' => CharClass::Newline,
+        c if c.is_whitespace() => CharClass::Space,
+        c if c.is_ascii() => CharClass::Punct,
+        _ => CharClass::Other,
+    }
+}
+
+/// 单个片段的 token 估算
+fn segment_cost(class: CharClass, text: &str) -> usize {
+    let chars = text.chars().count();
+    match class {
+        // 常见单词 / 标识符约 5 个字符一个 token
+        CharClass::Word => chars.div_ceil(5),
+        CharClass::Digit => chars.div_ceil(3),
+        // 单个空格通常与后一个单词合并
+        CharClass::Space if text == " " => 0,
+        // 缩进等连续空白会被合并
+        CharClass::Space => chars.div_ceil(8),
+        CharClass::Newline => chars.div_ceil(2),
+        // 常见运算符 / 括号组合 (`()`、`=>`、`::`) 通常为一个 token
+        CharClass::Punct => chars.div_ceil(2),
+        // CJK 约一字一个 token，其他非 ASCII 按 UTF-8 字节数估算
+        CharClass::Other => text.len().div_ceil(3),
+    }
+}
+
+/// 文本切分为 (起始字节, 结束字节, token 估算) 片段
+fn segments(text: &str) -> Vec<(usize, usize, usize)> {
+    let mut out = Vec::new();
+    let mut iter = text.char_indices().peekable();
+    while let Some((start, c)) = iter.next() {
+        let class = classify(c);
+        let mut end = start + c.len_utf8();
+        while let Some(&(idx, next)) = iter.peek() {
+            // 标识符中的数字 (`i32`、`utf8`) 与字母合并
+            let joins = classify(next) == class || (class == CharClass::Word && next.is_ascii_digit());
+            if !joins {
+                break;
+            }
+            end = idx + next.len_utf8();
+            iter.next();
+        }
+        out.push((start, end, segment_cost(class, &text[start..end])));
+    }
+    out
+}
+
+/// 估算文本的 token 数
+pub fn count_tokens(text: &str) -> usize {
+    segments(text).iter().map(|(_, _, cost)| cost).sum()
+}
+
+/// 保留文本末尾不超过 `max_tokens` 的部分 (截掉开头)，尽量从整行开始
+/// 返回保留部分的起始字节位置
+pub fn keep_tail(text: &str, max_tokens: usize) -> usize {
+    let mut used = 0;
+    let mut cut = text.len();
+    for (start, _, cost) in segments(text).into_iter().rev() {
+        if used + cost > max_tokens {
+            break;
+        }
+        used += cost;
+        cut = start;
+    }
+    if cut == 0 || text[..cut].ends_with('\n') {
+        return cut;
+    }
+    // 从下一行开头开始 (保留部分内没有换行时保持原位置)
+    match text[cut..].find('\n') {
+        Some(pos) if cut + pos + 1 < text.len() => cut + pos + 1,
+        _ => cut,
+    }
+}
+
+/// 保留文本开头不超过 `max_tokens` 的部分 (截掉末尾)，尽量在整行处结束
+/// 返回保留部分的结束字节位置
+pub fn keep_head(text: &str, max_tokens: usize) -> usize {
+    let mut used = 0;
+    let mut cut = 0;
+    for (_, end, cost) in segments(text) {
+        if used + cost > max_tokens {
+            break;
+        }
+        used += cost;
+        cut = end;
+    }
+    if cut == text.len() {
+        return cut;
+    }
+    match text[..cut].rfind('\n') {
+        Some(pos) => pos + 1,
+        None => cut,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_tokens() {
+        assert_eq!(count_tokens(""), 0);
+        // fn / main / () / {，单个空格并入后一个单词
+        assert_eq!(count_tokens("fn main() {"), 4);
+        // 缩进 / return / x / ; / 换行
+        assert_eq!(count_tokens("    return x;\n"), 6);
+        assert_eq!(count_tokens("你好世界"), 4);
+        assert_eq!(count_tokens("i32 utf8"), 2);
+        // 粗略对照: 代码文本约 2-4 字节一个 token
+        let code = "pub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n".repeat(20);
+        let tokens = count_tokens(&code);
+        assert!(code.len() / 4 < tokens && tokens < code.len() / 2, "tokens = {}", tokens);
+    }
+
+    #[test]
+    fn test_keep_tail_snaps_to_line_start() {
+        let text = "line one\nline two\nline three\n";
+        assert_eq!(keep_tail(text, 1000), 0);
+        let cut = keep_tail(text, 6);
+        assert_eq!(&text[cut..], "line two\nline three\n");
+        // 预算落在行中间时从下一行开始
+        let cut = keep_tail(text, 5);
+        assert_eq!(&text[cut..], "line three\n");
+        // 单行文本: 按片段截断
+        let single = "alpha beta gamma delta";
+        let cut = keep_tail(single, 2);
+        assert_eq!(single[cut..].trim_start(), "gamma delta");
+    }
+
+    #[test]
+    fn test_keep_head_snaps_to_line_end() {
+        let text = "line one\nline two\nline three\n";
+        assert_eq!(keep_head(text, 1000), text.len());
+        let cut = keep_head(text, 6);
+        assert_eq!(&text[..cut], "line one\nline two\n");
+        assert_eq!(keep_head(text, 0), 0);
+    }
+}
@@ -69,153 +69,1584 @@ pub struct ZaiMcpConfig {
     pub web_reader_enabled: bool,
     #[serde(default)]
     pub vision_enabled: bool,
+    /// 单个 MCP 会话最多执行的工具调用次数 (0 = 不限制)
+    #[serde(default = "default_mcp_max_tool_calls")]
+    pub max_tool_calls: u32,
+    /// 单个 MCP 会话工具执行的累计耗时上限 (秒，0 = 不限制)
+    #[serde(default = "default_mcp_tool_loop_timeout_secs")]
+    pub tool_loop_timeout_secs: u64,
+    /// 连续相同 (工具名 + 参数) 调用的最大次数，超出视为死循环 (0 = 不检测)
+    #[serde(default = "default_mcp_max_repeated_calls")]
+    pub max_repeated_calls: u32,
+}
+
+fn default_mcp_max_tool_calls() -> u32 {
+    25
+}
+
+fn default_mcp_tool_loop_timeout_secs() -> u64 {
+    600
+}
+
+fn default_mcp_max_repeated_calls() -> u32 {
+    3
 }
 
 impl Default for ZaiMcpConfig {
     fn default() -> Self {
         Self {
             enabled: false,
-            web_search_enabled: false,
-            web_reader_enabled: false,
-            vision_enabled: false,
+            web_search_enabled: false,
+            web_reader_enabled: false,
+            vision_enabled: false,
+            max_tool_calls: default_mcp_max_tool_calls(),
+            tool_loop_timeout_secs: default_mcp_tool_loop_timeout_secs(),
+            max_repeated_calls: default_mcp_max_repeated_calls(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZaiConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_zai_base_url")]
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub dispatch_mode: ZaiDispatchMode,
+    /// Optional per-model mapping overrides for Anthropic/Claude model ids.
+    /// Key: incoming `model` string, Value: upstream z.ai model id (e.g. `glm-4.7`).
+    #[serde(default)]
+    pub model_mapping: HashMap<String, String>,
+    #[serde(default)]
+    pub models: ZaiModelDefaults,
+    #[serde(default)]
+    pub mcp: ZaiMcpConfig,
+}
+
+impl Default for ZaiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            base_url: default_zai_base_url(),
+            api_key: String::new(),
+            dispatch_mode: ZaiDispatchMode::Off,
+            model_mapping: HashMap::new(),
+            models: ZaiModelDefaults::default(),
+            mcp: ZaiMcpConfig::default(),
+        }
+    }
+}
+
+/// OpenAI 兼容上游
+/// 模型映射目标写成 `<name>:<model>` (如 `openrouter:anthropic/claude-3.5-haiku`) 即路由到该上游；
+/// 也可在 `models` 中直接列出该上游服务的模型 (支持 * 通配符)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAICompatBackend {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 唯一名称，用作映射目标前缀
+    pub name: String,
+    /// 包含 `/v1` 的基础地址，如 `https://openrouter.ai/api/v1`、`http://127.0.0.1:8081/v1`
+    pub base_url: String,
+    /// 为空时不发送 Authorization (本地 llama.cpp 等)
+    #[serde(default)]
+    pub api_key: String,
+    /// 直接由该上游服务的模型名
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// 附加请求头 (如 OpenRouter 的 HTTP-Referer / X-Title)
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// Vertex AI 服务账号上游
+/// 模型映射目标写成 `vertex:<model>` (如 `vertex:gemini-2.5-pro`)，或模型命中 `models` 时由 Vertex 服务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VertexConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 服务账号 JSON 密钥文件路径
+    #[serde(default)]
+    pub credentials_path: String,
+    /// GCP 项目 ID，为空时使用密钥中的 project_id
+    #[serde(default)]
+    pub project_id: String,
+    /// 区域 (如 us-central1)，`global` 使用全局端点
+    #[serde(default = "default_vertex_location")]
+    pub location: String,
+    /// 直接由 Vertex 服务的模型名 (支持 * 通配符)
+    #[serde(default)]
+    pub models: Vec<String>,
+}
+
+fn default_vertex_location() -> String {
+    "us-central1".to_string()
+}
+
+/// AI Studio API Key 账号 (账号列表中的 API Key 类型账号)
+/// 模型映射目标写成 `aistudio:<model>` 或模型命中 `models` 时由 API Key 服务；
+/// `fallback` 开启时 OAuth 账号池无可用账号的 Gemini 请求也会改用 API Key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiStudioConfig {
+    #[serde(default = "default_true")]
+    pub fallback: bool,
+    /// 直接由 API Key 服务的模型名 (支持 * 通配符)
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// 每个 Key 每日请求上限 (按太平洋时间重置)，0 表示不限制
+    #[serde(default)]
+    pub daily_request_limit: u32,
+}
+
+impl Default for AiStudioConfig {
+    fn default() -> Self {
+        Self {
+            fallback: true,
+            models: Vec::new(),
+            daily_request_limit: 0,
+        }
+    }
+}
+
+impl Default for VertexConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            credentials_path: String::new(),
+            project_id: String::new(),
+            location: default_vertex_location(),
+            models: Vec::new(),
+        }
+    }
+}
+
+/// Gemini 3 历史 functionCall 缺少 thoughtSignature 时的降级策略
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureDegradationMode {
+    /// 不做处理，由上游校验 (原有行为)
+    Passthrough,
+    /// 注入合成思维块并使用跳过校验的占位签名
+    SyntheticThought,
+    /// 从历史中删除无签名的 functionCall/functionResponse 对
+    DropOrphaned,
+    /// 降级到不强制签名的非 Gemini 3 模型
+    DowngradeModel,
+}
+
+impl Default for SignatureDegradationMode {
+    fn default() -> Self {
+        Self::Passthrough
+    }
+}
+
+/// functionResponse.response 的外层包装键
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FunctionResponseWrapper {
+    /// `{"result": ...}` (原有行为)
+    Result,
+    /// `{"content": ...}`
+    Content,
+    /// `{"output": ...}`
+    Output,
+    /// 结构化对象直接作为 response，非对象值回退到 `{"result": ...}`
+    Inline,
+}
+
+impl Default for FunctionResponseWrapper {
+    fn default() -> Self {
+        Self::Result
+    }
+}
+
+/// 按模型定制工具结果 (functionResponse) 的格式
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionResponseRule {
+    /// 模型名，支持通配符 (如 gemini-2.5-*)
+    pub model: String,
+    #[serde(default)]
+    pub wrapper: FunctionResponseWrapper,
+    /// 工具输出为合法 JSON 对象/数组时以结构化值传递，而不是字符串
+    #[serde(default = "default_true")]
+    pub structured: bool,
+}
+
+/// FIM (legacy completions 的 `suffix`) 提示模板风格
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FimStyle {
+    /// system 指令 + user 消息 (通用聊天模型)
+    #[default]
+    Instruction,
+    /// 模型原生的 FIM 特殊 token，渲染结果作为原始 prompt 续写
+    Tokens,
+}
+
+/// 按模型定制 FIM 提示模板
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FimTemplateRule {
+    /// 模型名，支持通配符 (同时匹配请求模型与映射后的目标模型)
+    pub model: String,
+    #[serde(default)]
+    pub style: FimStyle,
+    /// 模板正文，`{prefix}` / `{suffix}` 为占位符
+    pub template: String,
+    /// instruction 风格的 system 指令 (留空时使用内置的 <prefix>/<suffix> 说明)
+    #[serde(default)]
+    pub system: String,
+    /// 追加的 stop 序列 (tokens 风格通常需要模型的结束 token)
+    #[serde(default)]
+    pub stop: Vec<String>,
+}
+
+/// 补全 prompt 截断 (本地 token 估算)
+/// 超出预算时保留离光标最近的文本: prompt 保留末尾，suffix 保留开头
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CompletionTruncationConfig {
+    /// FIM (带 suffix) 请求 prompt + suffix 的 token 上限，0 表示不截断
+    #[serde(default = "default_fim_max_tokens")]
+    pub fim_max_tokens: u32,
+    /// 普通续写请求 prompt 的 token 上限，0 表示不截断
+    #[serde(default)]
+    pub prompt_max_tokens: u32,
+    /// FIM 预算中分配给 prompt (光标前) 的百分比，另一侧用不完的额度会让给对方
+    #[serde(default = "default_fim_prefix_percent")]
+    pub prefix_percent: u8,
+}
+
+impl Default for CompletionTruncationConfig {
+    fn default() -> Self {
+        Self {
+            fim_max_tokens: default_fim_max_tokens(),
+            prompt_max_tokens: 0,
+            prefix_percent: default_fim_prefix_percent(),
+        }
+    }
+}
+
+fn default_fim_max_tokens() -> u32 {
+    8192
+}
+
+fn default_fim_prefix_percent() -> u8 {
+    75
+}
+
+/// 虚拟模型步骤所处阶段
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VirtualStepStage {
+    /// 前处理: 改写最后一条用户消息
+    Pre,
+    /// 主模型: 基于 (改写后的) 完整对话作答
+    Main,
+    /// 后处理: 改写主模型的回答
+    Post,
+}
+
+impl Default for VirtualStepStage {
+    fn default() -> Self {
+        Self::Pre
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VirtualModelStep {
+    #[serde(default)]
+    pub stage: VirtualStepStage,
+    pub model: String,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// 输入模板，`{{input}}` 替换为本步输入 (pre: 用户消息，post: 主模型回答)；为空时直接使用输入
+    #[serde(default)]
+    pub prompt: Option<String>,
+}
+
+/// 由前/后处理步骤串联而成的虚拟模型 (如 translate-then-answer)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VirtualModel {
+    /// 客户端请求的模型名
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub steps: Vec<VirtualModelStep>,
+}
+
+/// 响应改写动作
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseRewriteAction {
+    /// 正则替换 (`pattern` → `replacement`，支持 `$1` 引用)
+    #[default]
+    RegexReplace,
+    /// 删除短语 (不区分大小写，连同其后的标点与空白)
+    StripPhrase,
+    /// 去掉包裹 JSON 输出的 Markdown 代码围栏
+    StripJsonFences,
+    /// CRLF / CR 统一为 LF
+    NormalizeNewlines,
+}
+
+/// 响应后处理规则，流式与非流式输出均生效 (思维内容不改写)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResponseRewriteRule {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub action: ResponseRewriteAction,
+    /// regex_replace 的正则 / strip_phrase 的短语
+    #[serde(default)]
+    pub pattern: String,
+    #[serde(default)]
+    pub replacement: String,
+    /// 仅对这些 API Key 生效 (为空 = 所有)
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    /// 仅对这些客户端模型名生效，支持通配符 (为空 = 所有)
+    #[serde(default)]
+    pub models: Vec<String>,
+}
+
+/// 提示词自动翻译: 非英文提示词先用廉价模型译为英文再发送，回答再译回用户语言
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TranslationConfig {
+    /// 全局默认 (未在 keys 中配置的 API Key)
+    #[serde(default)]
+    pub enabled: bool,
+    /// 按 API Key 覆盖开关
+    #[serde(default)]
+    pub keys: HashMap<String, bool>,
+    /// 用于检测与翻译的模型
+    #[serde(default = "default_translation_model")]
+    pub model: String,
+    /// 非流式回答译回用户语言 (流式回答改为要求模型直接用用户语言作答)
+    #[serde(default = "default_true")]
+    pub translate_responses: bool,
+}
+
+impl Default for TranslationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keys: HashMap::new(),
+            model: default_translation_model(),
+            translate_responses: true,
+        }
+    }
+}
+
+/// 受信客户端的本地文件附件: `file://` 与绝对路径引用由代理读取并内联为 base64
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LocalFilesConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 允许读取的根目录；路径解析 (含符号链接) 后必须位于其中之一，为空时拒绝所有路径
+    #[serde(default)]
+    pub roots: Vec<String>,
+    /// 受信的 API Key；为空时拒绝所有客户端 (反代未开启鉴权时同样拒绝)
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+    /// 单个文件大小上限 (MB)
+    #[serde(default = "default_local_file_max_mb")]
+    pub max_file_mb: u32,
+}
+
+impl Default for LocalFilesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            roots: Vec::new(),
+            trusted_keys: Vec::new(),
+            max_file_mb: default_local_file_max_mb(),
+        }
+    }
+}
+
+fn default_local_file_max_mb() -> u32 {
+    20
+}
+
+/// 聊天响应中模型生成图片 (inlineData) 的呈现方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageOutputMode {
+    /// 以 `![image](data:...)` 写入 content (兼容只渲染 Markdown 的客户端)
+    #[default]
+    Markdown,
+    /// 放入 message.images 扩展数组 (OpenRouter 兼容)，content 只保留文本
+    Images,
+    /// 两者都输出
+    Both,
+}
+
+/// 聊天响应中生成图片的输出配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ImageOutputConfig {
+    #[serde(default)]
+    pub mode: ImageOutputMode,
+    /// 图片另存目录 (为空时不保存)；保存后的路径会写入响应
+    #[serde(default)]
+    pub save_dir: String,
+    /// 已保存的图片在响应中以 file:// 地址代替 base64，减小响应体积
+    #[serde(default)]
+    pub link_saved_files: bool,
+}
+
+/// 请求超出模型能力 (工具 / 图片 / 思维 / JSON 模式等) 时的处理方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CapabilityGating {
+    /// 不检查，原样转发 (由上游报错)
+    Off,
+    /// 去掉 / 改写不支持的部分后继续请求
+    #[default]
+    Degrade,
+    /// 直接返回 400 并说明不支持的能力
+    Reject,
+}
+
+/// 能力表覆盖规则: 未设置的字段沿用内置能力表
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct CapabilityRule {
+    /// 上游类型: antigravity / vertex / aistudio，为空表示任意
+    #[serde(default)]
+    pub backend: String,
+    /// 模型名，支持通配符
+    pub model: String,
+    #[serde(default)]
+    pub tools: Option<bool>,
+    #[serde(default)]
+    pub images: Option<bool>,
+    #[serde(default)]
+    pub system_instruction: Option<bool>,
+    #[serde(default)]
+    pub thinking: Option<bool>,
+    #[serde(default)]
+    pub json_mode: Option<bool>,
+}
+
+/// 模型上下文窗口 / 最大输出覆盖规则: 未设置的字段沿用远程清单或内置表
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ModelLimitRule {
+    /// 模型名，支持通配符
+    pub model: String,
+    #[serde(default)]
+    pub context_window: Option<u64>,
+    #[serde(default)]
+    pub max_output_tokens: Option<u64>,
+}
+
+/// 模型上下文窗口 / 最大输出登记表配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelLimitsConfig {
+    /// 用户覆盖 (优先级最高)
+    #[serde(default)]
+    pub overrides: Vec<ModelLimitRule>,
+    /// 远程 JSON 清单地址 (为空时只使用内置表与覆盖)
+    #[serde(default)]
+    pub manifest_url: String,
+    /// 请求的 maxOutputTokens 超出模型上限时自动下调 (而不是由上游报错)
+    #[serde(default = "default_true")]
+    pub clamp_max_output: bool,
+}
+
+impl Default for ModelLimitsConfig {
+    fn default() -> Self {
+        Self {
+            overrides: Vec::new(),
+            manifest_url: String::new(),
+            clamp_max_output: true,
+        }
+    }
+}
+
+/// 模型行为差异 (quirk) 规则: 新模型发布后通过远程清单或本地覆盖修正，无需更新应用。
+/// 未设置的字段沿用下层 (清单 / 内置行为)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ModelQuirk {
+    /// 模型名，支持通配符
+    pub model: String,
+    /// 历史 functionCall 是否必须携带 thoughtSignature (内置: gemini-3 系列)
+    #[serde(default)]
+    pub requires_thought_signature: Option<bool>,
+    /// 发送前移除历史中的 thought part (模型拒绝回传思维内容时)
+    #[serde(default)]
+    pub strip_thought_parts: Option<bool>,
+    /// 额外从工具参数 / responseSchema 中移除的 JSON Schema 关键字
+    #[serde(default)]
+    pub schema_drop_keywords: Vec<String>,
+}
+
+/// 远程 quirk 清单配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuirksConfig {
+    /// 清单地址 (为空时只使用本地覆盖)；签名从 `<manifest_url>.sig` 获取
+    #[serde(default)]
+    pub manifest_url: String,
+    /// 验证清单签名的 Ed25519 公钥 (base64url，32 字节)
+    #[serde(default)]
+    pub public_key: String,
+    /// 自动刷新间隔 (小时)，0 表示仅手动刷新
+    #[serde(default = "default_quirks_refresh_hours")]
+    pub refresh_interval_hours: u32,
+    /// 本地覆盖 (优先于清单)
+    #[serde(default)]
+    pub overrides: Vec<ModelQuirk>,
+}
+
+fn default_quirks_refresh_hours() -> u32 {
+    24
+}
+
+impl Default for QuirksConfig {
+    fn default() -> Self {
+        Self {
+            manifest_url: String::new(),
+            public_key: String::new(),
+            refresh_interval_hours: default_quirks_refresh_hours(),
+            overrides: Vec::new(),
+        }
+    }
+}
+
+/// 实验性功能配置 (Feature Flags)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentalConfig {
+    /// 启用双层签名缓存 (Signature Cache)
+    #[serde(default = "default_true")]
+    pub enable_signature_cache: bool,
+    
+    /// 启用工具循环自动恢复 (Tool Loop Recovery)
+    #[serde(default = "default_true")]
+    pub enable_tool_loop_recovery: bool,
+    
+    /// 启用跨模型兼容性检查 (Cross-Model Checks)
+    #[serde(default = "default_true")]
+    pub enable_cross_model_checks: bool,
+
+    /// 启用上下文用量缩放 (Context Usage Scaling)
+    /// 用于解决客户端因 Gemini 上下文过大而错误触发压缩的问题
+    #[serde(default = "default_true")]
+    pub enable_usage_scaling: bool,
+
+    /// 无签名工具调用降级策略 (仅对 Gemini 3 生效)
+    #[serde(default)]
+    pub signature_degradation: SignatureDegradationMode,
+
+    /// downgrade_model 策略使用的目标模型
+    #[serde(default = "default_signature_fallback_model")]
+    pub signature_fallback_model: String,
+
+    /// 支持 Chat Completions 的 `best_of` 参数 (生成多个候选并由裁判模型挑选)
+    #[serde(default = "default_true")]
+    pub enable_best_of: bool,
+
+    /// best_of 裁判模型 (建议使用低成本模型)
+    #[serde(default = "default_signature_fallback_model")]
+    pub best_of_judge_model: String,
+
+    /// best_of 允许的最大候选数
+    #[serde(default = "default_best_of_max_candidates")]
+    pub best_of_max_candidates: u32,
+
+    /// 模拟 Anthropic Prompt Caching: 按 cache_control 断点检测稳定前缀并在 usage 中报告缓存用量
+    #[serde(default = "default_true")]
+    pub enable_prompt_cache_emulation: bool,
+
+    /// 按模型的 functionResponse 格式规则 (未命中时使用 result 包装 + 结构化 JSON)
+    #[serde(default)]
+    pub function_response_rules: Vec<FunctionResponseRule>,
+
+    /// 上游因 functionResponse 格式返回 400 时自动切换到下一种格式
+    #[serde(default = "default_true")]
+    pub function_response_auto_detect: bool,
+
+    /// 工具输出按结构化 JSON 传递的大小上限 (字节)，超出时以字符串传递
+    #[serde(default = "default_function_response_structured_max_bytes")]
+    pub function_response_structured_max_bytes: usize,
+
+    /// 上游流在生成中途断开时的最大续传次数 (0 = 关闭)
+    #[serde(default = "default_stream_resume_max_attempts")]
+    pub stream_resume_max_attempts: u32,
+
+    /// response_format 要求 JSON 时校验输出并尝试本地修复 (代码围栏、尾随逗号等)
+    #[serde(default = "default_true")]
+    pub enable_json_output_repair: bool,
+
+    /// 本地修复失败时追加一次纠错重提示
+    #[serde(default)]
+    pub json_output_reprompt: bool,
+
+    /// 流式输出平滑速率 (字符/秒，0 = 关闭)，把上游大块文本拆成小段定时下发
+    #[serde(default)]
+    pub stream_pacing_cps: u32,
+
+    /// 按 API Key 覆盖平滑速率 (0 = 对该 Key 关闭)
+    #[serde(default)]
+    pub stream_pacing_keys: std::collections::HashMap<String, u32>,
+
+    /// 虚拟模型 (Chat Completions): 按声明的步骤在代理内依次调用多个模型
+    #[serde(default)]
+    pub virtual_models: Vec<VirtualModel>,
+
+    /// 按上游 / 模型能力表拦截或降级不支持的请求特性
+    #[serde(default)]
+    pub capability_gating: CapabilityGating,
+
+    /// 能力表覆盖规则 (按顺序匹配第一条)
+    #[serde(default)]
+    pub capability_rules: Vec<CapabilityRule>,
+
+    /// 添加账号后自动用小请求探测该账号各模型实际支持的特性，结果并入能力表
+    #[serde(default = "default_true")]
+    pub capability_probe_on_add: bool,
+
+    /// 响应改写规则 (按顺序依次应用)
+    #[serde(default)]
+    pub response_rewrites: Vec<ResponseRewriteRule>,
+
+    /// 流式统计: SSE 末尾追加 `: x-ag-stats` 注释行，非流式响应附加 X-AG-* 响应头，并写入请求历史
+    #[serde(default = "default_true")]
+    pub stream_stats: bool,
+
+    /// 提示词自动翻译
+    #[serde(default)]
+    pub translation: TranslationConfig,
+
+    /// 把用户消息正文中的 `![alt](data:image/...)` 提取为图片 part，原位置替换为占位符
+    #[serde(default)]
+    pub markdown_images: bool,
+
+    /// 同时提取 Markdown 中引用的本地图片路径 (受 local_files 的根目录与受信 Key 限制)
+    #[serde(default)]
+    pub markdown_images_local_files: bool,
+
+    /// 受信客户端本地文件附件
+    #[serde(default)]
+    pub local_files: LocalFilesConfig,
+
+    /// 聊天响应中生成图片的输出方式
+    #[serde(default)]
+    pub image_output: ImageOutputConfig,
+
+    /// 模型上下文窗口 / 最大输出登记表
+    #[serde(default)]
+    pub model_limits: ModelLimitsConfig,
+
+    /// 远程清单驱动的模型 quirk 修正
+    #[serde(default)]
+    pub quirks: QuirksConfig,
+
+    /// FIM 提示模板 (按顺序匹配，优先于内置模板)
+    #[serde(default)]
+    pub fim_templates: Vec<FimTemplateRule>,
+
+    /// /v1/completions prompt 截断
+    #[serde(default)]
+    pub completion_truncation: CompletionTruncationConfig,
+}
+
+impl Default for ExperimentalConfig {
+    fn default() -> Self {
+        Self {
+            enable_signature_cache: true,
+            enable_tool_loop_recovery: true,
+            enable_cross_model_checks: true,
+            enable_usage_scaling: true,
+            signature_degradation: SignatureDegradationMode::default(),
+            signature_fallback_model: default_signature_fallback_model(),
+            enable_best_of: true,
+            best_of_judge_model: default_signature_fallback_model(),
+            best_of_max_candidates: default_best_of_max_candidates(),
+            enable_prompt_cache_emulation: true,
+            function_response_rules: Vec::new(),
+            function_response_auto_detect: true,
+            function_response_structured_max_bytes: default_function_response_structured_max_bytes(),
+            stream_resume_max_attempts: default_stream_resume_max_attempts(),
+            enable_json_output_repair: true,
+            json_output_reprompt: false,
+            stream_pacing_cps: 0,
+            stream_pacing_keys: std::collections::HashMap::new(),
+            virtual_models: Vec::new(),
+            capability_gating: CapabilityGating::default(),
+            capability_rules: Vec::new(),
+            capability_probe_on_add: true,
+            response_rewrites: Vec::new(),
+            stream_stats: true,
+            translation: TranslationConfig::default(),
+            markdown_images: false,
+            markdown_images_local_files: false,
+            local_files: LocalFilesConfig::default(),
+            image_output: ImageOutputConfig::default(),
+            model_limits: ModelLimitsConfig::default(),
+            quirks: QuirksConfig::default(),
+            fim_templates: Vec::new(),
+            completion_truncation: CompletionTruncationConfig::default(),
+        }
+    }
+}
+
+fn default_true() -> bool { true }
+
+fn default_signature_fallback_model() -> String {
+    "gemini-2.5-flash".to_string()
+}
+
+fn default_translation_model() -> String {
+    "gemini-2.5-flash".to_string()
+}
+
+fn default_best_of_max_candidates() -> u32 {
+    5
+}
+
+fn default_function_response_structured_max_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_stream_resume_max_attempts() -> u32 {
+    2
+}
+
+/// 单个监听地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListenAddr {
+    pub ip: std::net::IpAddr,
+    /// IPv6 套接字同时接受 IPv4 连接 (IPV6_V6ONLY = false)
+    pub dual_stack: bool,
+}
+
+/// 反代服务配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// 是否启用反代服务
+    pub enabled: bool,
+
+    /// 是否允许局域网访问
+    /// - false: 仅本机访问 127.0.0.1（默认，隐私优先）
+    /// - true: 允许局域网访问 0.0.0.0
+    #[serde(default)]
+    pub allow_lan_access: bool,
+
+    /// 是否在 IPv4 上监听 (127.0.0.1 / 0.0.0.0)
+    #[serde(default = "default_true")]
+    pub listen_ipv4: bool,
+
+    /// 是否在 IPv6 上监听 (::1 / ::)
+    /// 部分客户端会把 localhost 解析为 ::1，仅监听 IPv4 时无法连接
+    #[serde(default = "default_true")]
+    pub listen_ipv6: bool,
+
+    /// 允许局域网访问时通过 mDNS 广播服务 (`_antigravity._tcp`)，供配套应用自动发现
+    #[serde(default = "default_true")]
+    pub mdns_advertise: bool,
+
+    /// Authorization policy for the proxy.
+    /// - off: no auth required
+    /// - strict: auth required for all routes
+    /// - all_except_health: auth required for all routes except `/healthz` and `/readyz`
+    /// - auto: recommended defaults (currently: allow_lan_access => all_except_health, else off)
+    #[serde(default)]
+    pub auth_mode: ProxyAuthMode,
+
+    /// 是否开放远程管理 API (/admin/*)
+    /// 开启后无论 auth_mode 如何，/admin/* 始终要求 admin_api_key
+    #[serde(default)]
+    pub enable_admin_api: bool,
+
+    /// 管理 API 专用密钥 (必须非空且不同于 api_key，否则 /admin/* 一律 401)
+    #[serde(default)]
+    pub admin_api_key: String,
+    
+    /// 监听端口
+    pub port: u16,
+    
+    /// API 密钥
+    pub api_key: String,
+    
+
+    /// 是否自动启动
+    pub auto_start: bool,
+
+    /// 自定义精确模型映射表 (key: 原始模型名, value: 目标模型名)
+    #[serde(default)]
+    pub custom_mapping: std::collections::HashMap<String, String>,
+
+    /// API 请求超时时间(秒)
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+
+    /// 请求体大小上限(MB)，超出返回 413 (修改后需重启反代服务生效)
+    #[serde(default = "default_max_request_body_mb")]
+    pub max_request_body_mb: u64,
+
+    /// 优雅停机时等待在途请求/流完成的最长时间(秒)
+    #[serde(default = "default_shutdown_drain_timeout")]
+    pub shutdown_drain_timeout: u64,
+
+    /// 看门狗: 监听器意外退出或 /healthz 无响应时自动重启反代服务 (下次启动服务时生效)
+    #[serde(default = "default_true")]
+    pub watchdog_enabled: bool,
+
+    /// 是否开启请求日志记录 (监控)
+    #[serde(default)]
+    pub enable_logging: bool,
+
+    /// 运行日志输出格式 (控制台与 app.log，保存后立即生效)
+    #[serde(default)]
+    pub log_format: LogFormat,
+
+    /// 上游代理配置
+    #[serde(default)]
+    pub upstream_proxy: UpstreamProxyConfig,
+
+    /// 上游 v1internal 端点配置 (多区域/备用主机 + 故障转移)
+    #[serde(default)]
+    pub upstream_endpoints: UpstreamEndpointsConfig,
+
+    /// 上游连接池配置 (修改后需重启反代服务生效)
+    #[serde(default)]
+    pub upstream_pool: UpstreamPoolConfig,
+
+    /// 浏览器跨域配置 (修改后需重启反代服务生效)
+    #[serde(default)]
+    pub cors: CorsConfig,
+
+    /// 本地套接字监听 (Unix Domain Socket / Windows 命名管道，修改后需重启反代服务生效)
+    #[serde(default)]
+    pub local_socket: LocalSocketConfig,
+
+    /// 外部状态存储: 多副本共享账号与限流状态 (修改后需重启反代服务生效)
+    #[serde(default)]
+    pub state_store: StateStoreConfig,
+
+    /// OpenTelemetry 链路追踪导出 (OTLP/HTTP)
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    /// 扩展钩子 (请求前/响应后调用 Webhook 或本地程序)
+    #[serde(default)]
+    pub hooks: HooksConfig,
+
+    /// 出站敏感信息脱敏
+    #[serde(default)]
+    pub pii_scrub: PiiScrubConfig,
+
+    /// 流式响应及其请求实时写入 JSONL 文件
+    #[serde(default)]
+    pub stream_tee: StreamTeeConfig,
+
+    /// 请求历史与流式落盘文件的保留策略
+    #[serde(default)]
+    pub retention: RetentionConfig,
+
+    /// 请求预检: 本地估算 prompt token，超出上下文窗口/额度时直接返回 400
+    #[serde(default)]
+    pub token_budget: TokenBudgetConfig,
+
+    /// 模型降级链 (主模型容量不足/不受支持时依次尝试)
+    #[serde(default)]
+    pub model_fallback: ModelFallbackConfig,
+
+    /// A/B 分流实验
+    #[serde(default)]
+    pub ab_test: AbTestConfig,
+
+    /// 延迟预算 (X-AG-Max-Latency-Ms): 优先历史更快的账号，必要时关闭 thinking 或降级
+    #[serde(default)]
+    pub latency_budget: LatencyBudgetConfig,
+
+    /// 竞速模式: 命中规则的请求同时发往多个账号，采用最先输出 token 的一路
+    #[serde(default)]
+    pub racing: RacingConfig,
+
+    /// 按请求固定账号 (X-AG-Account 请求头或模型名后缀 `@<账号>`)
+    #[serde(default)]
+    pub account_pinning: AccountPinningConfig,
+
+    /// z.ai provider configuration (Anthropic-compatible).
+    #[serde(default)]
+    pub zai: ZaiConfig,
+
+    /// OpenAI 兼容上游 (OpenRouter / llama.cpp / vLLM 等)，与 Google 账号池并列作为路由目标
+    #[serde(default)]
+    pub openai_backends: Vec<OpenAICompatBackend>,
+
+    /// Vertex AI 服务账号上游 (使用 GCP 额度，与 Antigravity 账号混用)
+    #[serde(default)]
+    pub vertex: VertexConfig,
+
+    /// AI Studio API Key 账号的路由与免费额度限制
+    #[serde(default)]
+    pub ai_studio: AiStudioConfig,
+    
+    /// 账号调度配置 (粘性会话/限流重试)
+    #[serde(default)]
+    pub scheduling: crate::proxy::sticky_config::StickySessionConfig,
+
+    /// 实验性功能配置
+    #[serde(default)]
+    pub experimental: ExperimentalConfig,
+}
+
+/// 上游代理配置
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UpstreamProxyConfig {
+    /// 是否启用
+    pub enabled: bool,
+    /// 代理地址 (http://, https://, socks5://)
+    pub url: String,
+}
+
+/// 上游端点选择策略
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointSelectionStrategy {
+    /// 按配置顺序使用，失败时依次切换 (默认，有利于缓存命中)
+    #[default]
+    Ordered,
+    /// 优先使用近期响应最快的端点
+    Latency,
+}
+
+/// 上游 v1internal 端点配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamEndpointsConfig {
+    /// 端点列表 (如 https://cloudcode-pa.googleapis.com/v1internal)，为空时使用内置 prod → daily
+    #[serde(default)]
+    pub base_urls: Vec<String>,
+    #[serde(default)]
+    pub strategy: EndpointSelectionStrategy,
+    /// 端点返回 5xx/超时后被降级的时长(秒)
+    #[serde(default = "default_endpoint_failure_cooldown")]
+    pub failure_cooldown_secs: u64,
+    /// 等待响应头的超时(秒)，超时后切换下一个端点；0 = 不限制
+    #[serde(default)]
+    pub header_timeout_secs: u64,
+    /// 按 (账号, 端点) 的熔断器
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+}
+
+impl Default for UpstreamEndpointsConfig {
+    fn default() -> Self {
+        Self {
+            base_urls: Vec::new(),
+            strategy: EndpointSelectionStrategy::default(),
+            failure_cooldown_secs: default_endpoint_failure_cooldown(),
+            header_timeout_secs: 0,
+            circuit_breaker: CircuitBreakerConfig::default(),
+        }
+    }
+}
+
+fn default_endpoint_failure_cooldown() -> u64 {
+    30
+}
+
+/// 熔断器配置
+/// 连续失败 failure_threshold 次后熔断 open_secs 秒，期间该账号在该端点上的请求直接失败；
+/// 到期后放行一个探测请求 (半开)，探测失败则熔断时长翻倍，直至 max_open_secs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_breaker_threshold")]
+    pub failure_threshold: u32,
+    #[serde(default = "default_breaker_open_secs")]
+    pub open_secs: u64,
+    #[serde(default = "default_breaker_max_open_secs")]
+    pub max_open_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            failure_threshold: default_breaker_threshold(),
+            open_secs: default_breaker_open_secs(),
+            max_open_secs: default_breaker_max_open_secs(),
+        }
+    }
+}
+
+fn default_breaker_threshold() -> u32 {
+    5
+}
+
+fn default_breaker_open_secs() -> u64 {
+    30
+}
+
+fn default_breaker_max_open_secs() -> u64 {
+    600
+}
+
+/// 上游连接池配置
+/// HTTP/2 下同一端点的所有账号共享少量多路复用连接，通过 keepalive ping 保持连接常热，避免每次请求重新 TLS 握手
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamPoolConfig {
+    /// 是否启用 HTTP/2 (ALPN 协商)，关闭则强制 HTTP/1.1
+    #[serde(default = "default_true")]
+    pub http2: bool,
+    /// 每主机最多保留的空闲连接数
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub max_idle_per_host: usize,
+    /// 空闲连接保留时长(秒)
+    #[serde(default = "default_pool_idle_timeout")]
+    pub idle_timeout_secs: u64,
+    /// TCP keepalive 探测间隔(秒)，0 = 关闭
+    #[serde(default = "default_tcp_keepalive")]
+    pub tcp_keepalive_secs: u64,
+    /// HTTP/2 PING 保活间隔(秒)，0 = 关闭
+    #[serde(default = "default_http2_keepalive_interval")]
+    pub http2_keepalive_interval_secs: u64,
+    /// 启动时预先建立到各端点的连接
+    #[serde(default = "default_true")]
+    pub prewarm: bool,
+}
+
+impl Default for UpstreamPoolConfig {
+    fn default() -> Self {
+        Self {
+            http2: true,
+            max_idle_per_host: default_pool_max_idle_per_host(),
+            idle_timeout_secs: default_pool_idle_timeout(),
+            tcp_keepalive_secs: default_tcp_keepalive(),
+            http2_keepalive_interval_secs: default_http2_keepalive_interval(),
+            prewarm: true,
+        }
+    }
+}
+
+fn default_pool_max_idle_per_host() -> usize {
+    16
+}
+
+fn default_pool_idle_timeout() -> u64 {
+    90
+}
+
+fn default_tcp_keepalive() -> u64 {
+    60
+}
+
+fn default_http2_keepalive_interval() -> u64 {
+    30
+}
+
+/// 本地套接字监听配置
+/// 与 TCP 端口并行提供同一套 API: Linux/macOS 上为 Unix Domain Socket (文件权限 0600)，
+/// Windows 上为命名管道 (默认仅当前用户与管理员可访问)，不占用端口且由操作系统控制访问权限
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LocalSocketConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 套接字路径 / 管道名，空 = 默认 (`<数据目录>/proxy.sock` 或 `\\.\pipe\antigravity-proxy`)
+    #[serde(default)]
+    pub path: String,
+    /// 本地套接字连接免 API Key (由系统权限控制访问，管理 API 仍需鉴权)
+    #[serde(default)]
+    pub trust_peers: bool,
+}
+
+/// OpenTelemetry 链路追踪导出配置
+/// 请求管道各阶段 (transform / schedule / upstream / map_back) 以 span 形式按批发送到 OTLP/HTTP 端点，
+/// 可在 Jaeger 等后端查看端到端耗时 (保存后立即生效)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// OTLP traces 端点 (JSON 编码)，如 Jaeger 的 `http://127.0.0.1:4318/v1/traces`
+    #[serde(default = "default_otlp_endpoint")]
+    pub endpoint: String,
+    /// 上报的 service.name
+    #[serde(default = "default_otel_service_name")]
+    pub service_name: String,
+    /// 采样率 (0.0 - 1.0)；客户端携带 traceparent 时沿用其采样标记
+    #[serde(default = "default_otel_sample_ratio")]
+    pub sample_ratio: f64,
+    /// 附加请求头 (如采集端鉴权)
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: default_otlp_endpoint(),
+            service_name: default_otel_service_name(),
+            sample_ratio: default_otel_sample_ratio(),
+            headers: HashMap::new(),
         }
     }
 }
 
+fn default_otlp_endpoint() -> String {
+    "http://127.0.0.1:4318/v1/traces".to_string()
+}
+
+fn default_otel_service_name() -> String {
+    "antigravity-proxy".to_string()
+}
+
+fn default_otel_sample_ratio() -> f64 {
+    1.0
+}
+
+/// 运行日志格式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// 面向人工阅读的文本
+    #[default]
+    Text,
+    /// 每行一个 JSON 事件 (便于 Loki / Vector 等采集)
+    Json,
+}
+
+/// 外部状态存储后端
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StateStoreBackend {
+    /// 仅使用本地数据目录 (单实例)
+    #[default]
+    Local,
+    /// 共享目录 (如挂载的网络卷)，每条记录一个 JSON 文件
+    Path,
+    /// 挂载卷上的 SQLite 文件
+    Sqlite,
+    /// PostgreSQL
+    Postgres,
+}
+
+/// 外部状态存储配置
+/// 本地数据目录仍是工作副本，后台按间隔与共享存储双向同步账号、限流冷却，并镜像请求历史，
+/// 使负载均衡后的多个反代副本共用同一账号池与配额状态
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ZaiConfig {
+pub struct StateStoreConfig {
     #[serde(default)]
-    pub enabled: bool,
-    #[serde(default = "default_zai_base_url")]
-    pub base_url: String,
+    pub backend: StateStoreBackend,
+    /// path: 共享目录；sqlite: 数据库文件路径
     #[serde(default)]
-    pub api_key: String,
+    pub path: String,
+    /// postgres: 连接串，如 `postgres://user:pass@db:5432/antigravity?sslmode=require` (TLS 按 sslmode 协商)
     #[serde(default)]
-    pub dispatch_mode: ZaiDispatchMode,
-    /// Optional per-model mapping overrides for Anthropic/Claude model ids.
-    /// Key: incoming `model` string, Value: upstream z.ai model id (e.g. `glm-4.7`).
+    pub url: String,
+    /// 同步间隔(秒)
+    #[serde(default = "default_state_sync_interval")]
+    pub sync_interval_secs: u64,
+    /// 是否将请求历史镜像到共享存储
+    #[serde(default = "default_true")]
+    pub mirror_history: bool,
+    /// 请求历史数据库 (proxy_logs.db) 的位置，空 = 数据目录
     #[serde(default)]
-    pub model_mapping: HashMap<String, String>,
+    pub history_db_path: String,
+}
+
+impl Default for StateStoreConfig {
+    fn default() -> Self {
+        Self {
+            backend: StateStoreBackend::Local,
+            path: String::new(),
+            url: String::new(),
+            sync_interval_secs: default_state_sync_interval(),
+            mirror_history: true,
+            history_db_path: String::new(),
+        }
+    }
+}
+
+fn default_state_sync_interval() -> u64 {
+    10
+}
+
+/// 浏览器跨域 (CORS) 配置 (修改后需重启反代服务生效)
+/// 供 Open WebUI / LibreChat / 浏览器扩展等网页客户端直接调用本地反代
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorsConfig {
+    /// 是否返回 CORS 响应头，关闭后浏览器跨域请求将被拦截
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 允许的来源，空 = 任意；支持 `*` 通配 (如 `http://localhost:*`、`chrome-extension://*`)
     #[serde(default)]
-    pub models: ZaiModelDefaults,
+    pub allowed_origins: Vec<String>,
+    /// 允许的请求头，空 = 任意
     #[serde(default)]
-    pub mcp: ZaiMcpConfig,
+    pub allowed_headers: Vec<String>,
+    /// 额外暴露给浏览器脚本的响应头
+    #[serde(default)]
+    pub expose_headers: Vec<String>,
+    /// 是否允许携带凭据 (Cookie / Authorization)
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// 是否响应 Chrome Private Network Access 预检 (公网页面访问 localhost)
+    #[serde(default = "default_true")]
+    pub allow_private_network: bool,
+    /// 预检结果缓存时间(秒)
+    #[serde(default = "default_cors_max_age")]
+    pub max_age_secs: u64,
 }
 
-impl Default for ZaiConfig {
+impl Default for CorsConfig {
     fn default() -> Self {
         Self {
-            enabled: false,
-            base_url: default_zai_base_url(),
-            api_key: String::new(),
-            dispatch_mode: ZaiDispatchMode::Off,
-            model_mapping: HashMap::new(),
-            models: ZaiModelDefaults::default(),
-            mcp: ZaiMcpConfig::default(),
+            enabled: true,
+            allowed_origins: Vec::new(),
+            allowed_headers: Vec::new(),
+            expose_headers: Vec::new(),
+            allow_credentials: false,
+            allow_private_network: true,
+            max_age_secs: default_cors_max_age(),
         }
     }
 }
 
-/// 实验性功能配置 (Feature Flags)
+fn default_cors_max_age() -> u64 {
+    3600
+}
+
+/// 扩展钩子类型
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookKind {
+    /// POST JSON 到指定 URL
+    Webhook,
+    /// 执行本地程序，stdin 传入 JSON，stdout 返回 JSON
+    Command,
+}
+
+/// 钩子触发阶段
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HookStage {
+    /// 转发上游之前 (可改写请求体/请求头或拒绝请求)
+    #[default]
+    Before,
+    /// 收到响应之后 (仅非流式 JSON 响应可改写)
+    After,
+    Both,
+}
+
+/// 单个扩展钩子
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ExperimentalConfig {
-    /// 启用双层签名缓存 (Signature Cache)
-    #[serde(default = "default_true")]
-    pub enable_signature_cache: bool,
-    
-    /// 启用工具循环自动恢复 (Tool Loop Recovery)
+pub struct HookConfig {
     #[serde(default = "default_true")]
-    pub enable_tool_loop_recovery: bool,
-    
-    /// 启用跨模型兼容性检查 (Cross-Model Checks)
+    pub enabled: bool,
+    pub name: String,
+    pub kind: HookKind,
+    /// Webhook URL 或可执行文件路径
+    pub target: String,
+    /// Command 类型的命令行参数
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub stage: HookStage,
+    /// 仅对这些路径前缀生效，空 = 所有 /v1、/v1beta 请求
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// 单次调用超时(毫秒)
+    #[serde(default = "default_hook_timeout_ms")]
+    pub timeout_ms: u64,
+    /// 钩子失败或超时时是否放行请求 (false = 返回 502)
     #[serde(default = "default_true")]
-    pub enable_cross_model_checks: bool,
+    pub fail_open: bool,
+}
 
-    /// 启用上下文用量缩放 (Context Usage Scaling)
-    /// 用于解决客户端因 Gemini 上下文过大而错误触发压缩的问题
+/// 扩展钩子配置 (实时生效)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HooksConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub hooks: Vec<HookConfig>,
+}
+
+fn default_hook_timeout_ms() -> u64 {
+    3000
+}
+
+/// 流式响应落盘配置 (实时生效)
+/// 独立于 SQLite 请求历史，每个流式响应的请求体与原始分块按到达顺序追加写入当天的 JSONL 文件，供离线分析或回放
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct StreamTeeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 输出目录 (为空 = 数据目录下的 stream_tee)，按本地日期写入 stream-YYYY-MM-DD.jsonl
+    #[serde(default)]
+    pub directory: String,
+}
+
+/// 数据保留策略 (实时生效，后台任务按间隔执行)
+/// 请求历史按时间 / 条数 / 数据库大小裁剪，流式落盘文件按时间清理；各上限为 0 表示不限制
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RetentionConfig {
     #[serde(default = "default_true")]
-    pub enable_usage_scaling: bool,
+    pub enabled: bool,
+    /// 保留天数
+    #[serde(default = "default_retention_max_age_days")]
+    pub max_age_days: u32,
+    /// 请求历史最多保留条数 (超出时删除最旧的记录)
+    #[serde(default)]
+    pub max_rows: u64,
+    /// 请求历史数据库大小上限 (MB)
+    #[serde(default)]
+    pub max_db_size_mb: u64,
+    /// 执行间隔 (分钟)
+    #[serde(default = "default_retention_interval_minutes")]
+    pub interval_minutes: u32,
 }
 
-impl Default for ExperimentalConfig {
+impl Default for RetentionConfig {
     fn default() -> Self {
         Self {
-            enable_signature_cache: true,
-            enable_tool_loop_recovery: true,
-            enable_cross_model_checks: true,
-            enable_usage_scaling: true,
+            enabled: true,
+            max_age_days: default_retention_max_age_days(),
+            max_rows: 0,
+            max_db_size_mb: 0,
+            interval_minutes: default_retention_interval_minutes(),
         }
     }
 }
 
-fn default_true() -> bool { true }
+fn default_retention_max_age_days() -> u32 {
+    30
+}
 
-/// 反代服务配置
+fn default_retention_interval_minutes() -> u32 {
+    60
+}
+
+/// 出站敏感信息脱敏配置 (实时生效)
+/// 请求离开本机前把命中的值替换为 `[EMAIL_1]` 之类的令牌，响应中出现的令牌再还原给客户端
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProxyConfig {
-    /// 是否启用反代服务
+pub struct PiiScrubConfig {
+    #[serde(default)]
     pub enabled: bool,
-
-    /// 是否允许局域网访问
-    /// - false: 仅本机访问 127.0.0.1（默认，隐私优先）
-    /// - true: 允许局域网访问 0.0.0.0
+    /// 邮箱地址
+    #[serde(default = "default_true")]
+    pub mask_emails: bool,
+    /// 常见 API Key / Token 格式 (OpenAI / Anthropic / Google / GitHub / AWS / Slack)
+    #[serde(default = "default_true")]
+    pub mask_api_keys: bool,
+    /// 用户主目录路径 (/home/<user>、/Users/<user>、C:\Users\<user>)
+    #[serde(default = "default_true")]
+    pub mask_file_paths: bool,
+    /// 自定义正则 (无效正则会被忽略并记录警告)
     #[serde(default)]
-    pub allow_lan_access: bool,
+    pub custom_patterns: Vec<String>,
+    /// 是否在响应中还原令牌
+    #[serde(default = "default_true")]
+    pub unmask_responses: bool,
+}
 
-    /// Authorization policy for the proxy.
-    /// - off: no auth required
-    /// - strict: auth required for all routes
-    /// - all_except_health: auth required for all routes except `/healthz`
-    /// - auto: recommended defaults (currently: allow_lan_access => all_except_health, else off)
+impl Default for PiiScrubConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mask_emails: true,
+            mask_api_keys: true,
+            mask_file_paths: true,
+            custom_patterns: Vec::new(),
+            unmask_responses: true,
+        }
+    }
+}
+
+/// Token 预算预检配置 (实时生效)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenBudgetConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 单次请求 prompt token 上限 (0 = 不限制)
     #[serde(default)]
-    pub auth_mode: ProxyAuthMode,
-    
-    /// 监听端口
-    pub port: u16,
-    
-    /// API 密钥
-    pub api_key: String,
-    
+    pub max_prompt_tokens: u64,
+    /// 按 API Key 单独设置的上限，优先于 max_prompt_tokens
+    #[serde(default)]
+    pub key_budgets: std::collections::HashMap<String, u64>,
+    /// 上下文窗口覆盖 (目标模型名，支持 `*` 通配符)
+    #[serde(default)]
+    pub context_windows: std::collections::HashMap<String, u64>,
+    /// 估算误差容忍度 (百分比)，估算值超过窗口的 (100 + n)% 才拒绝
+    #[serde(default = "default_token_budget_tolerance")]
+    pub tolerance_percent: u32,
+    /// 超出窗口时自动升级到更大窗口的同系列模型 (而不是直接拒绝)
+    #[serde(default)]
+    pub auto_upgrade: bool,
+    /// 按 API Key 覆盖 auto_upgrade
+    #[serde(default)]
+    pub auto_upgrade_keys: std::collections::HashMap<String, bool>,
+    /// 自定义升级路线 (目标模型名，支持 `*` 通配符 -> 更大窗口的模型)，优先于内置路线
+    #[serde(default)]
+    pub upgrade_routes: std::collections::HashMap<String, String>,
+}
 
-    /// 是否自动启动
-    pub auto_start: bool,
+impl Default for TokenBudgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_prompt_tokens: 0,
+            key_budgets: std::collections::HashMap::new(),
+            context_windows: std::collections::HashMap::new(),
+            tolerance_percent: default_token_budget_tolerance(),
+            auto_upgrade: false,
+            auto_upgrade_keys: std::collections::HashMap::new(),
+            upgrade_routes: std::collections::HashMap::new(),
+        }
+    }
+}
 
-    /// 自定义精确模型映射表 (key: 原始模型名, value: 目标模型名)
+fn default_token_budget_tolerance() -> u32 {
+    10
+}
+
+/// 模型降级链配置 (实时生效)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelFallbackConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 请求模型名 (支持 `*` 通配符) -> 按顺序尝试的备用模型
+    /// 例: "gemini-3-pro-high": ["gemini-2.5-pro", "gemini-2.5-flash"]
     #[serde(default)]
-    pub custom_mapping: std::collections::HashMap<String, String>,
+    pub chains: std::collections::HashMap<String, Vec<String>>,
+    /// 触发降级的状态码 (400 仅在错误信息表明模型不受支持时触发)
+    #[serde(default = "default_fallback_statuses")]
+    pub trigger_statuses: Vec<u16>,
+}
 
-    /// API 请求超时时间(秒)
-    #[serde(default = "default_request_timeout")]
-    pub request_timeout: u64,
+impl Default for ModelFallbackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            chains: std::collections::HashMap::new(),
+            trigger_statuses: default_fallback_statuses(),
+        }
+    }
+}
 
-    /// 是否开启请求日志记录 (监控)
-    #[serde(default)]
-    pub enable_logging: bool,
+fn default_fallback_statuses() -> Vec<u16> {
+    vec![400, 404, 429, 503, 529]
+}
 
-    /// 上游代理配置
+/// A/B 分流实验配置 (实时生效)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AbTestConfig {
     #[serde(default)]
-    pub upstream_proxy: UpstreamProxyConfig,
+    pub enabled: bool,
+    #[serde(default)]
+    pub experiments: Vec<AbExperiment>,
+}
 
-    /// z.ai provider configuration (Anthropic-compatible).
+/// 单个实验: 命中 alias 的请求按 percentage 比例改用 variant_model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbExperiment {
+    /// 实验名 (统计时的分组键)
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 请求模型名，支持 `*` 通配符
+    pub alias: String,
+    /// 实验组使用的模型
+    pub variant_model: String,
+    /// 分流到实验组的百分比 (0-100)
+    #[serde(default = "default_ab_percentage")]
+    pub percentage: u8,
+}
+
+fn default_ab_percentage() -> u8 {
+    10
+}
+
+/// 延迟预算配置 (实时生效)
+/// 只对携带 X-AG-Max-Latency-Ms 头的请求生效
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyBudgetConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 预算低于该值 (毫秒) 时关闭 thinking；目标模型有历史数据且估计超出预算时同样关闭
+    #[serde(default = "default_latency_thinking_threshold")]
+    pub disable_thinking_below_ms: u64,
+    /// 预算低于该值 (毫秒) 时降级到 fast_model；目标模型历史延迟超出预算时同样降级
+    #[serde(default = "default_latency_downgrade_threshold")]
+    pub downgrade_below_ms: u64,
+    /// 降级目标模型
+    #[serde(default = "default_latency_fast_model")]
+    pub fast_model: String,
+}
+
+impl Default for LatencyBudgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            disable_thinking_below_ms: default_latency_thinking_threshold(),
+            downgrade_below_ms: default_latency_downgrade_threshold(),
+            fast_model: default_latency_fast_model(),
+        }
+    }
+}
+
+fn default_latency_thinking_threshold() -> u64 {
+    10_000
+}
+
+fn default_latency_downgrade_threshold() -> u64 {
+    3_000
+}
+
+fn default_latency_fast_model() -> String {
+    "gemini-2.5-flash".to_string()
+}
+
+/// 竞速模式配置 (实时生效)
+/// 高峰期上游排队时用配额换首 token 延迟，只建议对交互式的别名开启
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RacingConfig {
     #[serde(default)]
-    pub zai: ZaiConfig,
-    
-    /// 账号调度配置 (粘性会话/限流重试)
+    pub enabled: bool,
     #[serde(default)]
-    pub scheduling: crate::proxy::sticky_config::StickySessionConfig,
+    pub rules: Vec<RaceRule>,
+}
 
-    /// 实验性功能配置
+/// 单条竞速规则: 命中 alias 的请求同时发往 accounts 个不同账号
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RaceRule {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 请求模型名或映射后的目标模型，支持 `*` 通配符
+    pub alias: String,
+    /// 同时发起的账号数 (2-4)
+    #[serde(default = "default_race_accounts")]
+    pub accounts: u8,
+    /// 仅对流式请求竞速 (非流式请求只能按整体完成时间比较)
     #[serde(default)]
-    pub experimental: ExperimentalConfig,
+    pub streaming_only: bool,
 }
 
-/// 上游代理配置
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct UpstreamProxyConfig {
-    /// 是否启用
+fn default_race_accounts() -> u8 {
+    2
+}
+
+/// 按请求固定账号: 跳过调度，只使用指定账号 (该账号不可用时直接报错而不是换号)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct AccountPinningConfig {
+    #[serde(default)]
     pub enabled: bool,
-    /// 代理地址 (http://, https://, socks5://)
-    pub url: String,
+    /// 是否识别模型名后缀 `gemini-2.5-pro@work-2`
+    #[serde(default = "default_true")]
+    pub allow_model_suffix: bool,
+    /// 允许固定账号的 API Key (为空 = 所有)
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    /// 账号别名 -> 账号 ID 或邮箱
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
 }
 
 impl Default for ProxyConfig {
@@ -223,15 +1654,43 @@ impl Default for ProxyConfig {
         Self {
             enabled: false,
             allow_lan_access: false, // 默认仅本机访问，隐私优先
+            listen_ipv4: true,
+            listen_ipv6: true,
+            mdns_advertise: true,
             auth_mode: ProxyAuthMode::default(),
+            enable_admin_api: false,
+            admin_api_key: String::new(),
             port: 8045,
             api_key: format!("sk-{}", uuid::Uuid::new_v4().simple()),
             auto_start: false,
             custom_mapping: std::collections::HashMap::new(),
             request_timeout: default_request_timeout(),
+            max_request_body_mb: default_max_request_body_mb(),
+            shutdown_drain_timeout: default_shutdown_drain_timeout(),
+            watchdog_enabled: true,
             enable_logging: false, // 默认关闭，节省性能
+            log_format: LogFormat::Text,
             upstream_proxy: UpstreamProxyConfig::default(),
+            upstream_endpoints: UpstreamEndpointsConfig::default(),
+            upstream_pool: UpstreamPoolConfig::default(),
+            cors: CorsConfig::default(),
+            local_socket: LocalSocketConfig::default(),
+            state_store: StateStoreConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            hooks: HooksConfig::default(),
+            pii_scrub: PiiScrubConfig::default(),
+            stream_tee: StreamTeeConfig::default(),
+            retention: RetentionConfig::default(),
+            token_budget: TokenBudgetConfig::default(),
+            model_fallback: ModelFallbackConfig::default(),
+            ab_test: AbTestConfig::default(),
+            latency_budget: LatencyBudgetConfig::default(),
+            racing: RacingConfig::default(),
+            account_pinning: AccountPinningConfig::default(),
             zai: ZaiConfig::default(),
+            openai_backends: Vec::new(),
+            vertex: VertexConfig::default(),
+            ai_studio: AiStudioConfig::default(),
             scheduling: crate::proxy::sticky_config::StickySessionConfig::default(),
             experimental: ExperimentalConfig::default(),
         }
@@ -242,6 +1701,14 @@ fn default_request_timeout() -> u64 {
     120  // 默认 120 秒,原来 60 秒太短
 }
 
+fn default_max_request_body_mb() -> u64 {
+    100
+}
+
+fn default_shutdown_drain_timeout() -> u64 {
+    15
+}
+
 fn default_zai_base_url() -> String {
     "https://api.z.ai/api/anthropic".to_string()
 }
@@ -259,14 +1726,37 @@ fn default_zai_haiku_model() -> String {
 }
 
 impl ProxyConfig {
+    /// 请求体大小上限(字节)
+    pub fn max_request_body_bytes(&self) -> usize {
+        (self.max_request_body_mb.max(1) as usize).saturating_mul(1024 * 1024)
+    }
+
     /// 获取实际的监听地址
-    /// - allow_lan_access = false: 返回 "127.0.0.1"（默认，隐私优先）
-    /// - allow_lan_access = true: 返回 "0.0.0.0"（允许局域网访问）
-    pub fn get_bind_address(&self) -> &str {
+    /// - allow_lan_access = false: 127.0.0.1 / ::1（默认，隐私优先）
+    /// - allow_lan_access = true: 0.0.0.0 / ::（允许局域网访问），两个地址族都启用时使用单个 :: 双栈套接字
+    /// - 两个地址族都被关闭时回退为仅 IPv4，避免服务无法启动
+    pub fn listen_addresses(&self) -> Vec<ListenAddr> {
+        use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+        let (v4, v6) = match (self.listen_ipv4, self.listen_ipv6) {
+            (false, false) => (true, false),
+            other => other,
+        };
         if self.allow_lan_access {
-            "0.0.0.0"
-        } else {
-            "127.0.0.1"
+            if v4 && v6 {
+                return vec![ListenAddr { ip: IpAddr::V6(Ipv6Addr::UNSPECIFIED), dual_stack: true }];
+            }
+            if v6 {
+                return vec![ListenAddr { ip: IpAddr::V6(Ipv6Addr::UNSPECIFIED), dual_stack: false }];
+            }
+            return vec![ListenAddr { ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED), dual_stack: false }];
+        }
+        let mut addrs = Vec::new();
+        if v4 {
+            addrs.push(ListenAddr { ip: IpAddr::V4(Ipv4Addr::LOCALHOST), dual_stack: false });
+        }
+        if v6 {
+            addrs.push(ListenAddr { ip: IpAddr::V6(Ipv6Addr::LOCALHOST), dual_stack: false });
         }
+        addrs
     }
 }
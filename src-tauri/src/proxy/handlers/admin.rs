@@ -0,0 +1,627 @@
+// Admin 管理 API 处理器
+// 供桌面端或 curl 远程管理 headless 实例: 账号增删/禁用/排序、实时统计、配置读写
+// 鉴权由 auth_middleware 统一处理 (/admin/* 始终要求独立的 admin_api_key)
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+
+use crate::models::{Account, AccountState, QuotaData, TokenData};
+use crate::modules::{account, config, oauth};
+use crate::proxy::config::HookKind;
+use crate::proxy::server::AppState;
+use crate::proxy::ProxyConfig;
+
+/// 对外暴露的账号视图 (不包含 token 等敏感字段)
+#[derive(Debug, Serialize)]
+pub struct AdminAccountView {
+    pub id: String,
+    pub email: String,
+    pub name: Option<String>,
+    pub disabled: bool,
+    pub disabled_reason: Option<String>,
+    pub proxy_disabled: bool,
+    pub proxy_disabled_reason: Option<String>,
+    pub quota: Option<QuotaData>,
+    pub protected_models: Vec<String>,
+    pub last_used: i64,
+    pub last_warm_at: Option<i64>,
+    pub usage_schedule: Option<crate::models::UsageSchedule>,
+    pub tags: Vec<String>,
+    pub paused_until: Option<i64>,
+    pub paused_reason: Option<String>,
+    /// 当前状态 (active / paused / cooldown / disabled / error)
+    pub state: AccountState,
+    pub state_reason: Option<String>,
+}
+
+impl AdminAccountView {
+    /// 状态结合运行中账号池的限流冷却与熔断信息
+    fn new(state: &AppState, a: Account) -> Self {
+        let status = state.token_manager.account_status(&a);
+        Self {
+            id: a.id,
+            email: a.email,
+            name: a.name,
+            disabled: a.disabled,
+            disabled_reason: a.disabled_reason,
+            proxy_disabled: a.proxy_disabled,
+            proxy_disabled_reason: a.proxy_disabled_reason,
+            quota: a.quota,
+            protected_models: a.protected_models.into_iter().collect(),
+            last_used: a.last_used,
+            last_warm_at: a.last_warm_at,
+            usage_schedule: a.usage_schedule,
+            tags: a.tags,
+            paused_until: a.paused_until,
+            paused_reason: a.paused_reason,
+            state: status.state,
+            state_reason: status.reason,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddAccountRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetAccountStatusRequest {
+    /// true = 启用反代, false = 禁用反代
+    pub enabled: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PauseAccountRequest {
+    /// 暂停时长 (分钟)，为空表示直到手动恢复
+    pub minutes: Option<u32>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReorderAccountsRequest {
+    pub account_ids: Vec<String>,
+}
+
+fn admin_error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+/// 磁盘账号变更后刷新运行中的账号池
+async fn reload_pool(state: &AppState) {
+    state.token_manager.clear_all_sessions();
+    if let Err(e) = state.token_manager.load_accounts().await {
+        tracing::warn!("[Admin] Failed to reload account pool: {}", e);
+    }
+}
+
+/// GET /admin/accounts
+pub async fn handle_list_accounts(State(state): State<AppState>) -> Response {
+    match account::list_accounts() {
+        Ok(accounts) => {
+            let views: Vec<AdminAccountView> = accounts.into_iter().map(|a| AdminAccountView::new(&state, a)).collect();
+            Json(json!({ "accounts": views })).into_response()
+        }
+        Err(e) => admin_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+/// POST /admin/accounts  { "refresh_token": "..." }
+pub async fn handle_add_account(
+    State(state): State<AppState>,
+    Json(req): Json<AddAccountRequest>,
+) -> Response {
+    let token_res = match oauth::refresh_access_token(&req.refresh_token).await {
+        Ok(t) => t,
+        Err(e) => return admin_error(StatusCode::BAD_REQUEST, e),
+    };
+    let user_info = match oauth::get_user_info(&token_res.access_token).await {
+        Ok(u) => u,
+        Err(e) => return admin_error(StatusCode::BAD_REQUEST, e),
+    };
+
+    let token = TokenData::new(
+        token_res.access_token,
+        req.refresh_token,
+        token_res.expires_in,
+        Some(user_info.email.clone()),
+        None,
+        None,
+    );
+
+    match account::upsert_account(user_info.email.clone(), user_info.get_display_name(), token) {
+        Ok(acc) => {
+            tracing::info!("[Admin] Account added: {}", acc.email);
+            reload_pool(&state).await;
+            (StatusCode::CREATED, Json(AdminAccountView::new(&state, acc))).into_response()
+        }
+        Err(e) => admin_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+/// POST /admin/accounts/:id/status  { "enabled": false, "reason": "..." }
+pub async fn handle_set_account_status(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+    Json(req): Json<SetAccountStatusRequest>,
+) -> Response {
+    let mut acc = match account::load_account(&account_id) {
+        Ok(a) => a,
+        Err(e) => return admin_error(StatusCode::NOT_FOUND, e),
+    };
+
+    if req.enabled {
+        acc.proxy_disabled = false;
+        acc.proxy_disabled_reason = None;
+        acc.proxy_disabled_at = None;
+    } else {
+        acc.proxy_disabled = true;
+        acc.proxy_disabled_reason = Some(req.reason.unwrap_or_else(|| "Disabled via admin API".to_string()));
+        acc.proxy_disabled_at = Some(chrono::Utc::now().timestamp());
+    }
+
+    if let Err(e) = account::save_account(&acc) {
+        return admin_error(StatusCode::INTERNAL_SERVER_ERROR, e);
+    }
+    tracing::info!(
+        "[Admin] Account {} proxy status -> {}",
+        acc.email,
+        if req.enabled { "enabled" } else { "disabled" }
+    );
+    reload_pool(&state).await;
+    Json(AdminAccountView::new(&state, acc)).into_response()
+}
+
+/// POST /admin/accounts/:id/pause  { "minutes": 60, "reason": "..." }
+pub async fn handle_pause_account(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+    req: Option<Json<PauseAccountRequest>>,
+) -> Response {
+    let req = req.map(|Json(r)| r).unwrap_or_default();
+    if req.minutes == Some(0) {
+        return admin_error(StatusCode::BAD_REQUEST, "minutes must be greater than 0");
+    }
+    let mut acc = match account::load_account(&account_id) {
+        Ok(a) => a,
+        Err(e) => return admin_error(StatusCode::NOT_FOUND, e),
+    };
+    acc.pause(chrono::Utc::now().timestamp(), req.minutes, req.reason);
+    if let Err(e) = account::save_account(&acc) {
+        return admin_error(StatusCode::INTERNAL_SERVER_ERROR, e);
+    }
+    tracing::info!("[Admin] Account {} paused (until {:?})", acc.email, acc.paused_until);
+    reload_pool(&state).await;
+    Json(AdminAccountView::new(&state, acc)).into_response()
+}
+
+/// POST /admin/accounts/bulk  { "action": "refresh_tokens" | "verify_projects" | "pause_tag" | "resume_tag" | "purge_failed", ... }
+pub async fn handle_bulk_accounts(
+    State(state): State<AppState>,
+    Json(action): Json<crate::modules::bulk_ops::BulkAction>,
+) -> Response {
+    match crate::modules::bulk_ops::run(action).await {
+        Ok(report) => {
+            reload_pool(&state).await;
+            Json(report).into_response()
+        }
+        Err(e) => admin_error(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+/// POST /admin/accounts/:id/resume
+pub async fn handle_resume_account(State(state): State<AppState>, Path(account_id): Path<String>) -> Response {
+    let mut acc = match account::load_account(&account_id) {
+        Ok(a) => a,
+        Err(e) => return admin_error(StatusCode::NOT_FOUND, e),
+    };
+    acc.resume();
+    if let Err(e) = account::save_account(&acc) {
+        return admin_error(StatusCode::INTERNAL_SERVER_ERROR, e);
+    }
+    tracing::info!("[Admin] Account {} resumed", acc.email);
+    reload_pool(&state).await;
+    Json(AdminAccountView::new(&state, acc)).into_response()
+}
+
+/// DELETE /admin/accounts/:id
+pub async fn handle_delete_account(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+) -> Response {
+    match account::delete_account(&account_id) {
+        Ok(()) => {
+            reload_pool(&state).await;
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => admin_error(StatusCode::NOT_FOUND, e),
+    }
+}
+
+/// POST /admin/accounts/reorder  { "account_ids": [...] }
+pub async fn handle_reorder_accounts(
+    State(state): State<AppState>,
+    Json(req): Json<ReorderAccountsRequest>,
+) -> Response {
+    match account::reorder_accounts(&req.account_ids) {
+        Ok(()) => {
+            reload_pool(&state).await;
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => admin_error(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+/// GET /admin/stats
+pub async fn handle_stats(State(state): State<AppState>) -> Response {
+    let stats = state.monitor.get_stats().await;
+    let recent = state.monitor.get_logs(20).await;
+    Json(json!({
+        "requests": stats,
+        "active_accounts": state.token_manager.len(),
+        "recent": recent,
+    }))
+    .into_response()
+}
+
+/// GET /admin/inflight
+/// 当前在途请求 (排队 / 等待上游 / 输出中)，可配合 POST /v1/cancel/:request_id 取消卡住的请求
+pub async fn handle_inflight(State(state): State<AppState>) -> Response {
+    let requests = state.inflight.snapshot();
+    Json(json!({
+        "count": requests.len(),
+        "requests": requests,
+    }))
+    .into_response()
+}
+
+/// GET /admin/circuits
+/// 各 (账号, 端点) 熔断器的状态与计数
+pub async fn handle_circuits() -> Response {
+    use crate::proxy::circuit_breaker::{CircuitBreakers, CircuitState};
+    let circuits = CircuitBreakers::global().snapshot();
+    let open = circuits.iter().filter(|c| c.state == CircuitState::Open).count();
+    Json(json!({
+        "open": open,
+        "circuits": circuits,
+    }))
+    .into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ResetCircuitsRequest {
+    /// 省略时复位全部
+    pub account: Option<String>,
+}
+
+/// POST /admin/circuits/reset  { "account": "a@example.com" }
+pub async fn handle_reset_circuits(req: Option<Json<ResetCircuitsRequest>>) -> Response {
+    let req = req.map(|Json(r)| r).unwrap_or_default();
+    let reset = crate::proxy::circuit_breaker::CircuitBreakers::global().reset(req.account.as_deref());
+    Json(json!({ "reset": reset })).into_response()
+}
+
+/// GET /admin/quota-profiles
+/// 根据观察到的 429 学习到的各账号配额窗口 (每窗口请求数、恢复时间、当前余量)
+pub async fn handle_quota_profiles(State(state): State<AppState>) -> Response {
+    Json(json!({ "profiles": state.token_manager.quota_profiles() })).into_response()
+}
+
+const REDACTED: &str = "***";
+
+fn mask(value: &mut String) {
+    if !value.is_empty() {
+        *value = REDACTED.to_string();
+    }
+}
+
+fn unmask(value: &mut String, current: &str) {
+    if value == REDACTED {
+        *value = current.to_string();
+    }
+}
+
+/// 请求头只脱敏取值，保留头名便于识别
+fn mask_header_values(headers: &mut HashMap<String, String>) {
+    headers.values_mut().for_each(mask);
+}
+
+fn unmask_header_values(headers: &mut HashMap<String, String>, current: &HashMap<String, String>) {
+    for (name, value) in headers.iter_mut() {
+        unmask(value, current.get(name).map(String::as_str).unwrap_or_default());
+    }
+}
+
+/// API Key 列表: 每项替换为 `***<序号>`，提交时按序号还原，未知序号丢弃
+fn mask_key_list(keys: &mut [String]) {
+    for (i, key) in keys.iter_mut().enumerate() {
+        *key = format!("{}{}", REDACTED, i);
+    }
+}
+
+fn unmask_key_list(keys: &mut Vec<String>, current: &[String]) {
+    for key in keys.iter_mut() {
+        if let Some(i) = key.strip_prefix(REDACTED).and_then(|n| n.parse::<usize>().ok()) {
+            *key = current.get(i).cloned().unwrap_or_default();
+        }
+    }
+    keys.retain(|k| !k.is_empty());
+}
+
+/// 以 API Key 为键的映射: 键按排序后的序号替换为 `***<序号>`，取值保留
+fn mask_key_map<V>(map: &mut HashMap<String, V>) {
+    let mut entries: Vec<(String, V)> = map.drain().collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    map.extend(entries.into_iter().enumerate().map(|(i, (_, v))| (format!("{}{}", REDACTED, i), v)));
+}
+
+fn unmask_key_map<V>(map: &mut HashMap<String, V>, current: &HashMap<String, V>) {
+    let mut current_keys: Vec<&String> = current.keys().collect();
+    current_keys.sort();
+    let entries: Vec<(String, V)> = map.drain().collect();
+    for (key, value) in entries {
+        match key.strip_prefix(REDACTED).map(|n| n.parse::<usize>().ok()) {
+            Some(Some(i)) => {
+                if let Some(original) = current_keys.get(i) {
+                    map.insert((*original).clone(), value);
+                }
+            }
+            Some(None) => {}
+            None => {
+                map.insert(key, value);
+            }
+        }
+    }
+}
+
+/// 脱敏所有含密钥的字段 (GET /admin/config)
+/// 新增含密钥 / 凭据的配置项时必须同时加入此处与 restore_redacted
+fn redact_config(proxy: &mut ProxyConfig) {
+    mask(&mut proxy.api_key);
+    mask(&mut proxy.admin_api_key);
+    mask(&mut proxy.zai.api_key);
+    mask(&mut proxy.upstream_proxy.url);
+    mask(&mut proxy.state_store.url);
+    mask_header_values(&mut proxy.telemetry.headers);
+    for backend in proxy.openai_backends.iter_mut() {
+        mask(&mut backend.api_key);
+        mask_header_values(&mut backend.headers);
+    }
+    for hook in proxy.hooks.hooks.iter_mut().filter(|h| h.kind == HookKind::Webhook) {
+        mask(&mut hook.target);
+    }
+
+    // 按客户端 API Key 配置的规则
+    let exp = &mut proxy.experimental;
+    mask_key_list(&mut exp.local_files.trusted_keys);
+    for rule in exp.response_rewrites.iter_mut() {
+        mask_key_list(&mut rule.api_keys);
+    }
+    mask_key_map(&mut exp.translation.keys);
+    mask_key_map(&mut exp.stream_pacing_keys);
+    mask_key_list(&mut proxy.account_pinning.api_keys);
+    mask_key_map(&mut proxy.token_budget.key_budgets);
+    mask_key_map(&mut proxy.token_budget.auto_upgrade_keys);
+}
+
+/// PUT /admin/config: 未修改的脱敏字段沿用当前值
+fn restore_redacted(new_proxy: &mut ProxyConfig, current: &ProxyConfig) {
+    unmask(&mut new_proxy.api_key, &current.api_key);
+    unmask(&mut new_proxy.admin_api_key, &current.admin_api_key);
+    unmask(&mut new_proxy.zai.api_key, &current.zai.api_key);
+    unmask(&mut new_proxy.upstream_proxy.url, &current.upstream_proxy.url);
+    unmask(&mut new_proxy.state_store.url, &current.state_store.url);
+    unmask_header_values(&mut new_proxy.telemetry.headers, &current.telemetry.headers);
+    for backend in new_proxy.openai_backends.iter_mut() {
+        let old = current.openai_backends.iter().find(|old| old.name == backend.name);
+        unmask(&mut backend.api_key, old.map(|o| o.api_key.as_str()).unwrap_or_default());
+        match old {
+            Some(old) => unmask_header_values(&mut backend.headers, &old.headers),
+            None => unmask_header_values(&mut backend.headers, &HashMap::new()),
+        }
+    }
+    // hooks 与 local_files 不可远程修改，由 keep_host_only_fields 整体沿用
+
+    let (exp, old_exp) = (&mut new_proxy.experimental, &current.experimental);
+    for (i, rule) in exp.response_rewrites.iter_mut().enumerate() {
+        let old = old_exp.response_rewrites.get(i);
+        unmask_key_list(&mut rule.api_keys, old.map(|o| o.api_keys.as_slice()).unwrap_or_default());
+    }
+    unmask_key_map(&mut exp.translation.keys, &old_exp.translation.keys);
+    unmask_key_map(&mut exp.stream_pacing_keys, &old_exp.stream_pacing_keys);
+    unmask_key_list(&mut new_proxy.account_pinning.api_keys, &current.account_pinning.api_keys);
+    unmask_key_map(&mut new_proxy.token_budget.key_budgets, &current.token_budget.key_budgets);
+    unmask_key_map(&mut new_proxy.token_budget.auto_upgrade_keys, &current.token_budget.auto_upgrade_keys);
+}
+
+/// GET /admin/config (生效中的配置，密钥类字段已脱敏)
+pub async fn handle_get_config(State(state): State<AppState>) -> Response {
+    let mut proxy = state.proxy_config.read().await.clone();
+    redact_config(&mut proxy);
+    Json(proxy).into_response()
+}
+
+/// 只能在本机修改的字段: 钩子 (执行命令 / 外发请求)、本地文件根目录、图片与流式落盘目录、管理密钥，
+/// 以及决定流量与状态去向的地址 (上游代理、共享状态存储、本地套接字路径)
+/// 远程提交的值一律忽略，沿用当前配置
+fn keep_host_only_fields(new_proxy: &mut ProxyConfig, current: &ProxyConfig) {
+    new_proxy.admin_api_key = current.admin_api_key.clone();
+    new_proxy.hooks = current.hooks.clone();
+    new_proxy.experimental.local_files = current.experimental.local_files.clone();
+    new_proxy.experimental.markdown_images_local_files = current.experimental.markdown_images_local_files;
+    new_proxy.experimental.image_output.save_dir = current.experimental.image_output.save_dir.clone();
+    new_proxy.stream_tee.directory = current.stream_tee.directory.clone();
+    // 上游代理可截获全部上游流量 (含账号 token)，共享存储可写入账号状态，套接字路径决定本机上被删除 / 创建的文件
+    new_proxy.upstream_proxy.url = current.upstream_proxy.url.clone();
+    new_proxy.state_store.url = current.state_store.url.clone();
+    new_proxy.state_store.path = current.state_store.path.clone();
+    new_proxy.local_socket.path = current.local_socket.path.clone();
+}
+
+/// PUT /admin/config
+/// 保存反代配置并热更新可在线生效的部分 (映射/上游代理/上游端点/z.ai/调度/实验性)
+/// 端口、鉴权等监听相关配置需要重启后生效；钩子与本地路径类配置不可远程修改
+pub async fn handle_update_config(
+    State(state): State<AppState>,
+    Json(mut new_proxy): Json<ProxyConfig>,
+) -> Response {
+    let current = state.proxy_config.read().await.clone();
+
+    keep_host_only_fields(&mut new_proxy, &current);
+    restore_redacted(&mut new_proxy, &current);
+
+    let restart_required = new_proxy.port != current.port
+        || new_proxy.allow_lan_access != current.allow_lan_access
+        || new_proxy.listen_ipv4 != current.listen_ipv4
+        || new_proxy.listen_ipv6 != current.listen_ipv6
+        || new_proxy.mdns_advertise != current.mdns_advertise
+        || new_proxy.local_socket.enabled != current.local_socket.enabled
+        || new_proxy.local_socket.path != current.local_socket.path
+        || new_proxy.api_key != current.api_key
+        || new_proxy.enable_admin_api != current.enable_admin_api;
+
+    // headless 的配置来自 --config / 环境变量 / 命令行，不写入桌面端设置，修改只在本次运行中生效
+    if state.persist_config {
+        let mut app_config = match config::load_app_config() {
+            Ok(c) => c,
+            Err(e) => return admin_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+        };
+        app_config.proxy = new_proxy.clone();
+        if let Err(e) = config::save_app_config(&app_config) {
+            return admin_error(StatusCode::INTERNAL_SERVER_ERROR, e);
+        }
+    }
+
+    *state.proxy_config.write().await = new_proxy.clone();
+    *state.custom_mapping.write().await = new_proxy.custom_mapping.clone();
+    *state.upstream_proxy.write().await = new_proxy.upstream_proxy.clone();
+    *state.zai.write().await = new_proxy.zai.clone();
+    *state.openai_backends.write().await = new_proxy.openai_backends.clone();
+    *state.vertex.write().await = new_proxy.vertex.clone();
+    *state.ai_studio.write().await = new_proxy.ai_studio.clone();
+    *state.experimental.write().await = new_proxy.experimental.clone();
+    *state.pii_scrub.write().await = new_proxy.pii_scrub.clone();
+    *state.stream_tee.write().await = new_proxy.stream_tee.clone();
+    *state.token_budget.write().await = new_proxy.token_budget.clone();
+    *state.model_fallback.write().await = new_proxy.model_fallback.clone();
+    *state.ab_test.write().await = new_proxy.ab_test.clone();
+    *state.latency_budget.write().await = new_proxy.latency_budget.clone();
+    *state.racing.write().await = new_proxy.racing.clone();
+    *state.account_pinning.write().await = new_proxy.account_pinning.clone();
+    state.upstream.set_endpoints(new_proxy.upstream_endpoints.clone());
+    state.token_manager.update_sticky_config(new_proxy.scheduling.clone()).await;
+    state.monitor.set_enabled(new_proxy.enable_logging);
+
+    tracing::info!("[Admin] Proxy config updated (restart_required: {})", restart_required);
+    Json(json!({ "ok": true, "restart_required": restart_required })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::config::{HookConfig, OpenAICompatBackend};
+
+    #[test]
+    fn test_update_config_keeps_host_only_fields() {
+        let mut current = ProxyConfig { admin_api_key: "sk-admin".to_string(), ..Default::default() };
+        current.experimental.local_files.roots = vec!["/srv/docs".to_string()];
+
+        let mut incoming = current.clone();
+        incoming.admin_api_key = "sk-attacker".to_string();
+        incoming.hooks.enabled = true;
+        incoming.hooks.hooks.push(HookConfig {
+            enabled: true,
+            name: "pwn".to_string(),
+            kind: HookKind::Command,
+            target: "/bin/sh".to_string(),
+            args: vec!["-c".to_string(), "id".to_string()],
+            stage: Default::default(),
+            paths: Vec::new(),
+            timeout_ms: 1000,
+            fail_open: true,
+        });
+        incoming.experimental.local_files.enabled = true;
+        incoming.experimental.local_files.roots = vec!["/".to_string()];
+        incoming.experimental.image_output.save_dir = "/etc".to_string();
+        incoming.stream_tee.directory = "/etc".to_string();
+        incoming.upstream_proxy.enabled = true;
+        incoming.upstream_proxy.url = "http://attacker:8080".to_string();
+        incoming.state_store.url = "postgres://attacker/ag".to_string();
+        incoming.state_store.path = "/etc".to_string();
+        incoming.local_socket.path = "/etc/passwd".to_string();
+        incoming.request_timeout = 42;
+
+        keep_host_only_fields(&mut incoming, &current);
+        assert_eq!(incoming.admin_api_key, "sk-admin");
+        assert!(!incoming.hooks.enabled && incoming.hooks.hooks.is_empty());
+        assert_eq!(incoming.experimental.local_files, current.experimental.local_files);
+        assert!(incoming.experimental.image_output.save_dir.is_empty());
+        assert!(incoming.stream_tee.directory.is_empty());
+        assert_eq!(incoming.upstream_proxy.url, current.upstream_proxy.url);
+        assert_eq!(incoming.state_store.url, current.state_store.url);
+        assert_eq!(incoming.state_store.path, current.state_store.path);
+        assert_eq!(incoming.local_socket.path, current.local_socket.path);
+        // 其他字段照常更新
+        assert!(incoming.upstream_proxy.enabled);
+        assert_eq!(incoming.request_timeout, 42);
+    }
+
+    #[test]
+    fn test_redact_config_round_trip() {
+        let mut current = ProxyConfig { admin_api_key: "sk-admin".to_string(), ..Default::default() };
+        current.state_store.url = "postgres://ag:hunter2@db/ag".to_string();
+        current.upstream_proxy.url = "http://user:pw@proxy:8080".to_string();
+        current.telemetry.headers.insert("Authorization".to_string(), "Bearer otel-secret".to_string());
+        current.openai_backends.push(OpenAICompatBackend {
+            enabled: true,
+            name: "router".to_string(),
+            base_url: "https://openrouter.ai/api/v1".to_string(),
+            api_key: "or-secret".to_string(),
+            models: Vec::new(),
+            headers: HashMap::from([("X-Token".to_string(), "hdr-secret".to_string())]),
+        });
+        current.experimental.local_files.trusted_keys = vec!["sk-trusted".to_string()];
+        current.account_pinning.api_keys = vec!["sk-pin-a".to_string(), "sk-pin-b".to_string()];
+        current.token_budget.key_budgets.insert("sk-budget".to_string(), 1000);
+
+        let mut redacted = current.clone();
+        redact_config(&mut redacted);
+        let json = serde_json::to_string(&redacted).unwrap();
+        for secret in [
+            current.api_key.as_str(),
+            "sk-admin",
+            "hunter2",
+            "user:pw",
+            "otel-secret",
+            "or-secret",
+            "hdr-secret",
+            "sk-trusted",
+            "sk-pin-a",
+            "sk-budget",
+        ] {
+            assert!(!json.contains(secret), "{} leaked", secret);
+        }
+
+        // 原样提交 (仅修改一项预算) 时恢复全部密钥
+        let mut submitted = redacted.clone();
+        submitted.token_budget.key_budgets.insert("***0".to_string(), 2000);
+        submitted.account_pinning.api_keys.push("sk-pin-new".to_string());
+        restore_redacted(&mut submitted, &current);
+        assert_eq!(submitted.api_key, current.api_key);
+        assert_eq!(submitted.admin_api_key, "sk-admin");
+        assert_eq!(submitted.state_store.url, current.state_store.url);
+        assert_eq!(submitted.upstream_proxy.url, current.upstream_proxy.url);
+        assert_eq!(submitted.telemetry.headers, current.telemetry.headers);
+        assert_eq!(submitted.openai_backends[0].api_key, "or-secret");
+        assert_eq!(submitted.openai_backends[0].headers, current.openai_backends[0].headers);
+        assert_eq!(submitted.account_pinning.api_keys, vec!["sk-pin-a", "sk-pin-b", "sk-pin-new"]);
+        assert_eq!(submitted.token_budget.key_budgets.get("sk-budget"), Some(&2000));
+    }
+}
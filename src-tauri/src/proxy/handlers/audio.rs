@@ -1,15 +1,16 @@
 use axum::{
     extract::{Multipart, State},
-    http::StatusCode,
+    http::{header, StatusCode},
     response::IntoResponse,
     Json,
 };
+use base64::Engine as _;
 use serde_json::{json, Value};
 use tracing::{debug, info};
 use uuid::Uuid;
 
 use crate::proxy::{
-    audio::AudioProcessor,
+    audio::{segments_to_srt, segments_to_vtt, tts, AudioProcessor, TranscriptSegment},
     server::AppState,
 };
 
@@ -22,6 +23,9 @@ pub async fn handle_audio_transcription(
     let mut filename: Option<String> = None;
     let mut model = "gemini-2.0-flash-exp".to_string();
     let mut prompt = "Generate a transcript of the speech.".to_string();
+    let mut response_format = "json".to_string();
+    let mut language: Option<String> = None;
+    let mut temperature: Option<f64> = None;
 
     // 1. 解析 multipart/form-data
     while let Some(field) = multipart.next_field().await.map_err(|e| {
@@ -42,6 +46,15 @@ pub async fn handle_audio_transcription(
             "prompt" => {
                 prompt = field.text().await.unwrap_or(prompt);
             }
+            "response_format" => {
+                response_format = field.text().await.unwrap_or(response_format).to_lowercase();
+            }
+            "language" => {
+                language = field.text().await.ok().filter(|l| !l.trim().is_empty());
+            }
+            "temperature" => {
+                temperature = field.text().await.ok().and_then(|t| t.trim().parse().ok());
+            }
             _ => {}
         }
     }
@@ -56,6 +69,27 @@ pub async fn handle_audio_transcription(
         "无法获取文件名".to_string(),
     ))?;
 
+    if !matches!(response_format.as_str(), "json" | "text" | "verbose_json" | "srt" | "vtt") {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("不支持的 response_format: {}", response_format),
+        ));
+    }
+    // Whisper 客户端常发送 whisper-1 / gpt-4o-transcribe，统一映射到 Gemini 模型
+    if !model.starts_with("gemini") {
+        model = "gemini-2.0-flash-exp".to_string();
+    }
+    let wants_segments = matches!(response_format.as_str(), "verbose_json" | "srt" | "vtt");
+    if let Some(lang) = &language {
+        prompt.push_str(&format!(" The spoken language is {}; transcribe it in that language.", lang));
+    }
+    if wants_segments {
+        prompt.push_str(
+            " Split the transcript into consecutive segments of at most a couple of sentences. \
+             For each segment give the start and end time in seconds from the beginning of the audio.",
+        );
+    }
+
     info!(
         "收到音频转录请求: 文件={}, 大小={} bytes, 模型={}",
         file_name,
@@ -84,7 +118,7 @@ pub async fn handle_audio_transcription(
     let base64_audio = AudioProcessor::encode_to_base64(&audio_bytes);
 
     // 5. 构建 Gemini 请求
-    let gemini_request = json!({
+    let mut gemini_request = json!({
         "contents": [{
             "parts": [
                 {"text": prompt},
@@ -97,6 +131,35 @@ pub async fn handle_audio_transcription(
             ]
         }]
     });
+    let mut generation_config = serde_json::Map::new();
+    if let Some(t) = temperature {
+        generation_config.insert("temperature".to_string(), json!(t));
+    }
+    if wants_segments {
+        // 要求结构化输出，时间戳为模型估计值 (best-effort)
+        generation_config.insert("responseMimeType".to_string(), json!("application/json"));
+        generation_config.insert("responseSchema".to_string(), json!({
+            "type": "OBJECT",
+            "properties": {
+                "segments": {
+                    "type": "ARRAY",
+                    "items": {
+                        "type": "OBJECT",
+                        "properties": {
+                            "start": { "type": "NUMBER" },
+                            "end": { "type": "NUMBER" },
+                            "text": { "type": "STRING" }
+                        },
+                        "required": ["start", "end", "text"]
+                    }
+                }
+            },
+            "required": ["segments"]
+        }));
+    }
+    if !generation_config.is_empty() {
+        gemini_request["generationConfig"] = Value::Object(generation_config);
+    }
 
     // 6. 获取 Token 和上游客户端
     let token_manager = state.token_manager;
@@ -139,7 +202,7 @@ pub async fn handle_audio_transcription(
 
     // 9. 提取文本响应（解包 v1internal 响应）
     let inner_response = result.get("response").unwrap_or(&result);
-    let text = inner_response
+    let raw_text = inner_response
         .get("candidates")
         .and_then(|c| c.get(0))
         .and_then(|c| c.get("content"))
@@ -149,14 +212,192 @@ pub async fn handle_audio_transcription(
         .and_then(|t| t.as_str())
         .unwrap_or("");
 
-    info!("音频转录完成，返回 {} 字符", text.len());
+    // 分段模式下由分段拼出全文；模型未按 JSON 输出时退化为单个无时间戳分段
+    let segments = if wants_segments {
+        let parsed = AudioProcessor::parse_segments(raw_text);
+        if parsed.is_empty() && !raw_text.trim().is_empty() {
+            vec![TranscriptSegment { start: 0.0, end: 0.0, text: raw_text.trim().to_string() }]
+        } else {
+            parsed
+        }
+    } else {
+        Vec::new()
+    };
+    let text = if wants_segments {
+        segments.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" ")
+    } else {
+        raw_text.to_string()
+    };
+
+    info!("音频转录完成，返回 {} 字符 (格式: {})", text.len(), response_format);
+
+    // 10. 按 response_format 返回
+    let account_header = [("X-Account-Email", email.clone())];
+    let response = match response_format.as_str() {
+        "text" => (StatusCode::OK, account_header, text).into_response(),
+        "srt" => (StatusCode::OK, account_header, segments_to_srt(&segments)).into_response(),
+        "vtt" => (
+            StatusCode::OK,
+            account_header,
+            [(header::CONTENT_TYPE, "text/vtt; charset=utf-8")],
+            segments_to_vtt(&segments),
+        )
+            .into_response(),
+        "verbose_json" => {
+            let duration = segments.iter().map(|s| s.end).fold(0.0, f64::max);
+            let segment_values: Vec<Value> = segments
+                .iter()
+                .enumerate()
+                .map(|(i, seg)| {
+                    json!({
+                        "id": i,
+                        "seek": 0,
+                        "start": seg.start,
+                        "end": seg.end,
+                        "text": seg.text,
+                        "tokens": [],
+                        "temperature": temperature.unwrap_or(0.0),
+                        "avg_logprob": 0.0,
+                        "compression_ratio": 0.0,
+                        "no_speech_prob": 0.0
+                    })
+                })
+                .collect();
+            (
+                StatusCode::OK,
+                account_header,
+                Json(json!({
+                    "task": "transcribe",
+                    "language": language.unwrap_or_else(|| "unknown".to_string()),
+                    "duration": duration,
+                    "text": text,
+                    "segments": segment_values
+                })),
+            )
+                .into_response()
+        }
+        _ => (
+            StatusCode::OK,
+            account_header,
+            Json(json!({
+                "text": text
+            })),
+        )
+            .into_response(),
+    };
+    Ok(response)
+}
+
+/// 处理语音合成请求 (OpenAI /v1/audio/speech 兼容)
+pub async fn handle_audio_speech(
+    State(state): State<AppState>,
+    Json(body): Json<Value>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let input = body
+        .get("input")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.trim().is_empty())
+        .ok_or((StatusCode::BAD_REQUEST, "缺少 input 文本".to_string()))?;
+    let model = tts::map_tts_model(body.get("model").and_then(|v| v.as_str()).unwrap_or("tts-1"));
+    let voice = tts::map_voice(body.get("voice").and_then(|v| v.as_str()).unwrap_or("alloy"));
+    let format = tts::SpeechFormat::parse(
+        body.get("response_format").and_then(|v| v.as_str()).unwrap_or("mp3"),
+    )
+    .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let prompt = tts::build_prompt(
+        input,
+        body.get("instructions").and_then(|v| v.as_str()),
+        body.get("speed").and_then(|v| v.as_f64()),
+    );
+
+    info!(
+        "收到语音合成请求: {} 字符, 模型={}, 音色={}, 格式={:?}",
+        input.chars().count(),
+        model,
+        voice,
+        format
+    );
+
+    let gemini_request = json!({
+        "contents": [{
+            "role": "user",
+            "parts": [{ "text": prompt }]
+        }],
+        "generationConfig": {
+            "responseModalities": ["AUDIO"],
+            "speechConfig": {
+                "voiceConfig": {
+                    "prebuiltVoiceConfig": { "voiceName": voice }
+                }
+            }
+        }
+    });
+
+    let (access_token, project_id, email) = state
+        .token_manager
+        .get_token("text", false, None, &model)
+        .await
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
+
+    info!("使用账号: {}", email);
+
+    let wrapped_body = json!({
+        "project": project_id,
+        "requestId": format!("tts-{}", Uuid::new_v4()),
+        "request": gemini_request,
+        "model": model,
+        "userAgent": "antigravity",
+        "requestType": "text"
+    });
+
+    let response = state
+        .upstream
+        .call_v1_internal("generateContent", &access_token, wrapped_body, None)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("上游请求失败: {}", e)))?;
+
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        return Err((
+            StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY),
+            format!("Gemini API 错误: {}", error_text),
+        ));
+    }
+
+    let result: Value = response
+        .json()
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("解析响应失败: {}", e)))?;
+
+    let inner_response = result.get("response").unwrap_or(&result);
+    let inline_data = inner_response
+        .pointer("/candidates/0/content/parts")
+        .and_then(|p| p.as_array())
+        .and_then(|parts| {
+            parts
+                .iter()
+                .find_map(|p| p.get("inlineData").or_else(|| p.get("inline_data")))
+        })
+        .ok_or((StatusCode::BAD_GATEWAY, "上游未返回音频数据".to_string()))?;
+
+    let mime_type = inline_data
+        .get("mimeType")
+        .and_then(|m| m.as_str())
+        .unwrap_or("audio/L16;codec=pcm;rate=24000");
+    let pcm = base64::engine::general_purpose::STANDARD
+        .decode(inline_data.get("data").and_then(|d| d.as_str()).unwrap_or(""))
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("音频解码失败: {}", e)))?;
+
+    let (audio, actual_format) = tts::encode_speech(&pcm, tts::parse_sample_rate(mime_type), format).await;
+    debug!("语音合成完成: {} bytes PCM -> {} bytes {:?}", pcm.len(), audio.len(), actual_format);
 
-    // 10. 返回标准格式响应
     Ok((
         StatusCode::OK,
-        [("X-Account-Email", email.as_str())],
-        Json(json!({
-            "text": text
-        }))
-    ).into_response())
+        [
+            (header::CONTENT_TYPE, actual_format.content_type().to_string()),
+            (header::HeaderName::from_static("x-account-email"), email),
+        ],
+        audio,
+    ))
 }
@@ -0,0 +1,151 @@
+// Batch API 处理器 (OpenAI /v1/files + /v1/batches 兼容)
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::info;
+
+use crate::proxy::batch::CreateBatchRequest;
+use crate::proxy::server::AppState;
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(json!({
+            "error": {
+                "message": message.into(),
+                "type": "invalid_request_error"
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// POST /v1/files (multipart: file + purpose)
+pub async fn handle_upload_file(State(state): State<AppState>, mut multipart: Multipart) -> Response {
+    let mut data: Option<Vec<u8>> = None;
+    let mut filename = "upload.jsonl".to_string();
+    let mut purpose = "batch".to_string();
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, format!("Invalid multipart body: {}", e)),
+        };
+        match field.name().unwrap_or("") {
+            "file" => {
+                if let Some(name) = field.file_name() {
+                    filename = name.to_string();
+                }
+                match field.bytes().await {
+                    Ok(bytes) => data = Some(bytes.to_vec()),
+                    Err(e) => return error_response(StatusCode::BAD_REQUEST, format!("Failed to read file: {}", e)),
+                }
+            }
+            "purpose" => purpose = field.text().await.unwrap_or(purpose),
+            _ => {}
+        }
+    }
+
+    let Some(data) = data else {
+        return error_response(StatusCode::BAD_REQUEST, "Missing 'file' field");
+    };
+    let batches = state.batches.clone();
+    match tokio::task::spawn_blocking(move || batches.create_file(&filename, &purpose, &data)).await {
+        Ok(Ok(file)) => {
+            info!("[Batch] 收到文件上传: {} ({} bytes)", file.id, file.bytes);
+            Json(file).into_response()
+        }
+        Ok(Err(e)) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// GET /v1/files
+pub async fn handle_list_files(State(state): State<AppState>) -> Response {
+    Json(json!({ "object": "list", "data": state.batches.list_files() })).into_response()
+}
+
+/// GET /v1/files/:id
+pub async fn handle_get_file(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.batches.get_file(&id) {
+        Some(file) => Json(file).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, format!("No such file: {}", id)),
+    }
+}
+
+/// DELETE /v1/files/:id
+pub async fn handle_delete_file(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.batches.delete_file(&id) {
+        Ok(()) => Json(json!({ "id": id, "object": "file", "deleted": true })).into_response(),
+        Err(e) => error_response(StatusCode::NOT_FOUND, e),
+    }
+}
+
+/// GET /v1/files/:id/content (批任务执行中也可下载已完成的部分结果)
+pub async fn handle_file_content(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.batches.file_content(&id) {
+        Ok(bytes) => ([(header::CONTENT_TYPE, "application/jsonl")], bytes).into_response(),
+        Err(e) => error_response(StatusCode::NOT_FOUND, e),
+    }
+}
+
+/// POST /v1/batches
+pub async fn handle_create_batch(State(state): State<AppState>, Json(req): Json<CreateBatchRequest>) -> Response {
+    let batches = state.batches.clone();
+    match tokio::task::spawn_blocking(move || batches.create_batch(req)).await {
+        Ok(Ok(batch)) => {
+            info!("[Batch] 创建批任务 {} ({} 个请求, 端点 {})", batch.id, batch.request_counts.total, batch.endpoint);
+            state.batches.spawn(state.clone(), batch.id.clone());
+            Json(batch).into_response()
+        }
+        Ok(Err(e)) => error_response(StatusCode::BAD_REQUEST, e),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListBatchesQuery {
+    #[serde(default = "default_list_limit")]
+    limit: usize,
+    after: Option<String>,
+}
+
+fn default_list_limit() -> usize {
+    20
+}
+
+/// GET /v1/batches
+pub async fn handle_list_batches(State(state): State<AppState>, Query(query): Query<ListBatchesQuery>) -> Response {
+    let (data, has_more) = state.batches.list_batches(query.limit.clamp(1, 100), query.after.as_deref());
+    Json(json!({
+        "object": "list",
+        "first_id": data.first().map(|b| b.id.clone()),
+        "last_id": data.last().map(|b| b.id.clone()),
+        "has_more": has_more,
+        "data": data,
+    }))
+    .into_response()
+}
+
+/// GET /v1/batches/:id
+pub async fn handle_get_batch(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.batches.get_batch(&id) {
+        Some(batch) => Json(batch).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, format!("No such batch: {}", id)),
+    }
+}
+
+/// POST /v1/batches/:id/cancel
+pub async fn handle_cancel_batch(State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    match state.batches.cancel_batch(&id) {
+        Ok(batch) => Json(batch).into_response(),
+        Err(e) if e.starts_with("No such batch") => error_response(StatusCode::NOT_FOUND, e),
+        Err(e) => error_response(StatusCode::CONFLICT, e),
+    }
+}
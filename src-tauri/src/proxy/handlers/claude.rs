@@ -18,6 +18,7 @@ use crate::proxy::mappers::claude::{
     clean_cache_control_from_messages, merge_consecutive_messages,
 };
 use crate::proxy::server::AppState;
+use crate::proxy::common::streaming_json::StreamingJson;
 use crate::proxy::mappers::context_manager::{ContextManager, PurificationStrategy};
 use axum::http::HeaderMap;
 use std::sync::atomic::Ordering;
@@ -182,9 +183,12 @@ fn should_rotate_account(status_code: u16) -> bool {
 pub async fn handle_messages(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(body): Json<Value>,
+    StreamingJson(body): StreamingJson<Value>,
 ) -> Response {
-    tracing::debug!("handle_messages called. Body JSON len: {}", body.to_string().len());
+    tracing::debug!(
+        "handle_messages called. Messages: {}",
+        body.get("messages").and_then(|m| m.as_array()).map(|a| a.len()).unwrap_or(0)
+    );
     
     // 生成随机 Trace ID 用户追踪
     let trace_id: String = rand::Rng::sample_iter(rand::thread_rng(), &rand::distributions::Alphanumeric)
@@ -197,6 +201,13 @@ pub async fn handle_messages(
     let zai_enabled = zai.enabled && !matches!(zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
     let google_accounts = state.token_manager.len();
 
+    // [NEW] Prompt caching 模拟: cache_control 在反序列化时会丢失，需基于原始请求体检测断点
+    let cache_hint = if state.experimental.read().await.enable_prompt_cache_emulation {
+        crate::proxy::mappers::claude::prompt_cache::analyze(&body)
+    } else {
+        None
+    };
+
     // [CRITICAL REFACTOR] 优先解析请求以获取模型信息(用于智能兜底判断)
     let mut request: crate::proxy::mappers::claude::models::ClaudeRequest = match serde_json::from_value(body) {
         Ok(r) => r,
@@ -418,7 +429,8 @@ pub async fn handle_messages(
     
     // 3. 准备闭包
     let mut request_for_body = request.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
+    let rewrites = crate::proxy::middleware::response_rewrite::rewrites_for(&state, &request.model).await;
     
     let pool_size = token_manager.len();
     // [FIX] Ensure max_attempts is at least 2 to allow for internal retries (e.g. stripping signatures)
@@ -427,6 +439,7 @@ pub async fn handle_messages(
 
     let mut last_error = String::new();
     let mut retried_without_thinking = false;
+    let mut reshaped_function_response = false;
     let mut last_email: Option<String> = None;
     
     for attempt in 0..max_attempts {
@@ -565,7 +578,7 @@ pub async fn handle_messages(
         // 生成 Trace ID (简单用时间戳后缀)
         // let _trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
 
-        let gemini_body = match transform_claude_request_in(&request_with_mapped, &project_id, retried_without_thinking) {
+        let mut gemini_body = match transform_claude_request_in(&request_with_mapped, &project_id, retried_without_thinking) {
             Ok(b) => {
                 debug!("[{}] Transformed Gemini Body: {}", trace_id, serde_json::to_string_pretty(&b).unwrap_or_default());
                 b
@@ -583,6 +596,41 @@ pub async fn handle_messages(
                 ).into_response();
             }
         };
+
+        // [NEW] Gemini 3 无签名工具调用降级
+        let stream_resume_attempts = {
+            let exp = state.experimental.read().await;
+            crate::proxy::mappers::signature_degradation::apply_signature_degradation(
+                &mut gemini_body,
+                &exp.signature_degradation,
+                &exp.signature_fallback_model,
+            );
+            crate::proxy::mappers::function_response::apply_function_response_format(
+                &mut gemini_body,
+                &exp.function_response_rules,
+                exp.function_response_auto_detect,
+                exp.function_response_structured_max_bytes,
+            );
+            exp.stream_resume_max_attempts
+        };
+        let upstream_model = gemini_body["model"].as_str().unwrap_or_default().to_string();
+        if let Err((status, message)) = crate::proxy::handlers::common::gate_capabilities(
+            &state,
+            &mut gemini_body,
+            crate::proxy::mappers::capabilities::BACKEND_ANTIGRAVITY,
+            &upstream_model,
+        )
+        .await
+        {
+            return (
+                status,
+                Json(json!({
+                    "type": "error",
+                    "error": { "type": "invalid_request_error", "message": message }
+                })),
+            )
+                .into_response();
+        }
         
     // 4. 上游调用 - 自动转换逻辑
     let client_wants_stream = request.stream;
@@ -605,7 +653,7 @@ pub async fn handle_messages(
 
         // 5. 上游调用
         let response = match upstream
-            .call_v1_internal_with_headers(method, &access_token, gemini_body, query, extra_headers.clone())
+            .call_v1_internal_with_headers(method, &access_token, &gemini_body, query, extra_headers.clone())
             .await {
             Ok(r) => r,
             Err(e) => {
@@ -627,8 +675,21 @@ pub async fn handle_messages(
 
             // 处理流式响应
             if actual_stream {
-                let stream = response.bytes_stream();
-                let gemini_stream = Box::pin(stream);
+                let gemini_stream = crate::proxy::upstream::resume::resumable_stream(
+                    Box::pin(response.bytes_stream()),
+                    upstream.clone(),
+                    access_token.clone(),
+                    gemini_body.clone(),
+                    stream_resume_attempts,
+                    trace_id.clone(),
+                );
+                let gemini_stream = crate::proxy::mappers::prefill::strip_echo_stream(
+                    gemini_stream,
+                    crate::proxy::mappers::prefill::prefill_text(&gemini_body),
+                );
+                let gemini_stream = crate::proxy::middleware::response_rewrite::apply_to_stream(gemini_stream, rewrites.clone());
+                let pacing_cps = if client_wants_stream { crate::proxy::middleware::stream_pacing::current_cps() } else { 0 };
+                let gemini_stream = crate::proxy::middleware::stream_pacing::pace_stream(gemini_stream, pacing_cps);
 
 
                 // [FIX #530/#529/#859] Enhanced Peek logic to handle heartbeats and slow start
@@ -640,7 +701,8 @@ pub async fn handle_messages(
                     email.clone(),
                     Some(session_id_str.clone()),
                     scaling_enabled,
-                    context_limit
+                    context_limit,
+                    cache_hint
                 );
 
                 let mut first_data_chunk = None;
@@ -756,10 +818,11 @@ pub async fn handle_messages(
                     debug!("Upstream Response for Claude request: {}", text);
                 }
 
-                let gemini_resp: Value = match serde_json::from_slice(&bytes) {
+                let mut gemini_resp: Value = match serde_json::from_slice(&bytes) {
                     Ok(v) => v,
                     Err(e) => return (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)).into_response(),
                 };
+                crate::proxy::middleware::response_rewrite::apply_to_response(&mut gemini_resp, &rewrites);
 
                 // 解包 response 字段（v1internal 格式）
                 let raw = gemini_resp.get("response").unwrap_or(&gemini_resp);
@@ -776,10 +839,13 @@ pub async fn handle_messages(
                 // 转换
                 // [FIX #765] Pass session_id and model_name for signature caching
                 let s_id_owned = session_id.map(|s| s.to_string());
-                let claude_response = match transform_response(&gemini_response, scaling_enabled, context_limit, s_id_owned, request_with_mapped.model.clone()) {
+                let mut claude_response = match transform_response(&gemini_response, scaling_enabled, context_limit, s_id_owned, request_with_mapped.model.clone()) {
                     Ok(r) => r,
                     Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Transform error: {}", e)).into_response(),
                 };
+                if let Some(hint) = &cache_hint {
+                    hint.apply(&mut claude_response.usage);
+                }
 
                 // [Optimization] 记录闭环日志：消耗情况
                 let cache_info = if let Some(cached) = claude_response.usage.cache_read_input_tokens {
@@ -808,6 +874,26 @@ pub async fn handle_messages(
         // 2. 获取错误文本并转移 Response 所有权
         let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status));
         last_error = format!("HTTP {}: {}", status_code, error_text);
+
+        // [NEW] project_id 失效时自动走 loadCodeAssist 发现流程修复并重试
+        if crate::proxy::project_resolver::is_project_error(status_code, &error_text) {
+            match token_manager.repair_project_id(&email).await {
+                Ok(new_pid) => {
+                    tracing::warn!("[Claude] Project ID for {} was invalid, repaired to {}, retrying", email, new_pid);
+                    continue;
+                }
+                Err(e) => tracing::warn!("[Claude] Project ID repair failed for {}: {}", email, e),
+            }
+        }
+
+        // [NEW] 上游拒绝 functionResponse 格式时切换到下一种候选格式并重试一次 (请求体每轮重新构建)
+        if !reshaped_function_response
+            && state.experimental.read().await.function_response_auto_detect
+            && crate::proxy::mappers::function_response::record_rejection(&upstream_model, status_code, &error_text)
+        {
+            reshaped_function_response = true;
+            continue;
+        }
         debug!("[{}] Upstream Error Response: {}", trace_id, error_text);
         
         // 3. 标记限流状态(用于 UI 显示) - 使用异步版本以支持实时配额刷新
@@ -1,4 +1,4 @@
-use axum::{extract::State, extract::Json, http::StatusCode, response::IntoResponse};
+use axum::{extract::Path, extract::State, extract::Json, http::StatusCode, response::IntoResponse};
 use serde_json::{json, Value};
 use crate::proxy::server::AppState;
 
@@ -27,6 +27,15 @@ pub async fn handle_detect_model(
         &None // We don't check tools for static capability detection
     );
 
+    let capabilities = {
+        let exp = state.experimental.read().await;
+        crate::proxy::mappers::capabilities::resolve(
+            crate::proxy::mappers::capabilities::BACKEND_ANTIGRAVITY,
+            &config.final_model,
+            &exp.capability_rules,
+        )
+    };
+
     // 3. Construct response
     let mut response = json!({
         "model": model_name,
@@ -35,6 +44,13 @@ pub async fn handle_detect_model(
         "features": {
             "has_web_search": config.inject_google_search,
             "is_image_gen": config.request_type == "image_gen"
+        },
+        "capabilities": {
+            "tools": capabilities.tools,
+            "images": capabilities.images,
+            "system_instruction": capabilities.system_instruction,
+            "thinking": capabilities.thinking,
+            "json_mode": capabilities.json_mode
         }
     });
 
@@ -46,3 +62,45 @@ pub async fn handle_detect_model(
 
     Json(response).into_response()
 }
+
+/// Applies the capability matrix to a converted Gemini request (degrade or reject unsupported features)
+/// clamps maxOutputTokens to the model limit registry and applies model quirks
+pub async fn gate_capabilities(
+    state: &AppState,
+    body: &mut Value,
+    backend: &str,
+    model: &str,
+) -> Result<(), (StatusCode, String)> {
+    let exp = state.experimental.read().await;
+    if exp.model_limits.clamp_max_output {
+        crate::proxy::mappers::model_limits::clamp_max_output(body, model);
+    }
+    crate::proxy::mappers::quirks::apply_to_request(body, model);
+    crate::proxy::mappers::capabilities::gate_request(body, backend, model, exp.capability_gating, &exp.capability_rules)
+        .map(|_| ())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Aborts an in-flight generation identified by its X-AG-Request-Id
+/// POST /v1/cancel/:request_id
+/// Only the API key that started the request may cancel it; other callers get the same 404 as for an unknown ID
+pub async fn handle_cancel_request(
+    State(state): State<AppState>,
+    Path(request_id): Path<String>,
+    request: axum::extract::Request,
+) -> impl IntoResponse {
+    let client_key = crate::proxy::middleware::token_budget::request_api_key(&request);
+    if state.inflight.cancel_owned(&request_id, client_key.as_deref()) {
+        return Json(json!({ "id": request_id, "cancelled": true })).into_response();
+    }
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "error": {
+                "message": format!("No in-flight request with id {}", request_id),
+                "type": "not_found"
+            }
+        })),
+    )
+        .into_response()
+}
@@ -0,0 +1,633 @@
+// 并行扇出 (/v1/fanout，非标准端点)
+// 同一个 Chat Completions 请求并行发往多个模型 / 账号:
+// - 默认模式: 各路结果以 choice index (= 目标序号) 标注，复用到同一个 SSE 流 (非流式时合并为多 choice 响应)，便于对比模型行为
+// - race 模式: 谁先输出第一个 token 就返回谁，其余请求立即取消 (用配额换延迟)
+// 每一路在进程内走 chat completions handler，并通过 token_manager::pin_account 固定到不同账号。
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Json, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio::task::{AbortHandle, JoinSet};
+
+use crate::proxy::common::http::invalid_request;
+use crate::proxy::common::streaming_json::StreamingJson;
+use crate::proxy::config::AccountPinningConfig;
+use crate::proxy::middleware::account_pinning::resolve_pinned;
+use crate::proxy::middleware::best_of::{add_usage, choices_of};
+use crate::proxy::middleware::token_budget::api_key_from_parts;
+use crate::proxy::server::AppState;
+use crate::proxy::token_manager::pin_account;
+
+/// 单次扇出的最大并行数
+const MAX_TARGETS: usize = 8;
+
+/// race 模式胜出的目标序号
+pub const FANOUT_WINNER_HEADER: &str = "x-fanout-winner";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Target {
+    model: String,
+    /// 客户端指定的账号 (别名、账号 ID 或邮箱，受账号固定策略约束)
+    account: Option<String>,
+}
+
+enum LegEvent {
+    /// handler 已返回响应头 (账号与实际模型已确定)
+    Started {
+        index: usize,
+        account: Option<String>,
+        mapped_model: Option<String>,
+    },
+    /// 流式响应的一个 chat.completion.chunk
+    Chunk { index: usize, chunk: Value },
+    /// 结束: 非流式或失败时带完整响应体
+    Done { index: usize, status: u16, body: Option<Value> },
+}
+
+/// 每一路的展示信息与计时
+#[derive(Debug, Clone, Default)]
+struct LegInfo {
+    model: String,
+    account: Option<String>,
+    mapped_model: Option<String>,
+    first_token_ms: Option<u64>,
+}
+
+impl LegInfo {
+    fn label(&self, index: usize) -> Value {
+        json!({
+            "index": index,
+            "model": self.model,
+            "mapped_model": self.mapped_model,
+            "account": self.account,
+        })
+    }
+}
+
+/// fanout 为并行数 (同一模型、不同账号) 或目标数组 (模型名字符串 / {model, account} 对象)
+fn parse_targets(body: &Value) -> Result<Vec<Target>, String> {
+    let default_model = body.get("model").and_then(|m| m.as_str()).filter(|m| !m.is_empty());
+    let targets = match body.get("fanout") {
+        Some(Value::Number(n)) => {
+            let model = default_model.ok_or("model is required when fanout is a number")?;
+            let count = n.as_u64().unwrap_or(0) as usize;
+            if !(2..=MAX_TARGETS).contains(&count) {
+                return Err(format!("fanout must be between 2 and {}", MAX_TARGETS));
+            }
+            vec![Target { model: model.to_string(), account: None }; count]
+        }
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(model) if !model.is_empty() => Ok(Target { model: model.clone(), account: None }),
+                Value::Object(obj) => {
+                    let model = obj
+                        .get("model")
+                        .and_then(|m| m.as_str())
+                        .filter(|m| !m.is_empty())
+                        .or(default_model)
+                        .ok_or("each fanout target needs a model (or set a top-level model)")?;
+                    let account = obj
+                        .get("account")
+                        .and_then(|a| a.as_str())
+                        .filter(|a| !a.is_empty())
+                        .map(|a| a.to_string());
+                    Ok(Target { model: model.to_string(), account })
+                }
+                _ => Err("fanout targets must be model names or {model, account} objects".to_string()),
+            })
+            .collect::<Result<Vec<_>, String>>()?,
+        _ => return Err("fanout must be the number of parallel requests or an array of targets".to_string()),
+    };
+    if targets.is_empty() || targets.len() > MAX_TARGETS {
+        return Err(format!("fanout must contain between 1 and {} targets", MAX_TARGETS));
+    }
+    Ok(targets)
+}
+
+/// 为未指定账号的目标分配账号: 优先使用未被显式指定的账号并轮流分配，使各路尽量落在不同账号上
+/// `explicit` 与目标一一对应，`pool` 为可调度账号 (账号 ID, 邮箱)；账号池为空时交给正常调度
+fn assign_accounts(explicit: &[Option<(String, String)>], pool: &[(String, String)]) -> Vec<Option<(String, String)>> {
+    let used: HashSet<&str> = explicit.iter().flatten().map(|(id, _)| id.as_str()).collect();
+    let free: Vec<&(String, String)> = pool.iter().filter(|(id, _)| !used.contains(id.as_str())).collect();
+    let candidates: Vec<&(String, String)> = if free.is_empty() { pool.iter().collect() } else { free };
+    let mut next = 0;
+    explicit
+        .iter()
+        .map(|account| {
+            if account.is_some() || candidates.is_empty() {
+                return account.clone();
+            }
+            let picked = candidates[next % candidates.len()].clone();
+            next += 1;
+            Some(picked)
+        })
+        .collect()
+}
+
+/// 单路请求体: 去掉扇出参数，替换模型
+fn leg_body(body: &Value, model: &str, stream: bool) -> Value {
+    let mut leg = body.clone();
+    if let Some(obj) = leg.as_object_mut() {
+        obj.remove("fanout");
+        obj.remove("race");
+        obj.insert("model".to_string(), Value::String(model.to_string()));
+        obj.insert("stream".to_string(), Value::Bool(stream));
+    }
+    leg
+}
+
+/// chunk 是否包含实际输出 (正文 / 思考 / 工具调用)
+fn has_token(chunk: &Value) -> bool {
+    choices_of(chunk).iter().any(|choice| {
+        let Some(delta) = choice.get("delta") else { return false };
+        ["content", "reasoning_content"]
+            .iter()
+            .any(|key| delta.get(*key).and_then(|v| v.as_str()).is_some_and(|s| !s.is_empty()))
+            || delta.get("tool_calls").is_some_and(|t| !t.is_null())
+    })
+}
+
+/// 把 choice index 改写为目标序号
+fn relabel(chunk: &mut Value, index: usize) {
+    if let Some(choices) = chunk.get_mut("choices").and_then(|c| c.as_array_mut()) {
+        for choice in choices {
+            choice["index"] = json!(index);
+        }
+    }
+}
+
+/// 按行解析 SSE 字节流中的 data 负载 (分片可能在行中间截断)
+#[derive(Default)]
+struct SseDecoder {
+    line_buf: Vec<u8>,
+}
+
+impl SseDecoder {
+    fn push(&mut self, bytes: &[u8]) -> Vec<Value> {
+        self.line_buf.extend_from_slice(bytes);
+        let mut out = Vec::new();
+        while let Some(pos) = self.line_buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.line_buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(payload) = line.trim().strip_prefix("data:") else { continue };
+            if let Ok(value) = serde_json::from_str::<Value>(payload.trim()) {
+                out.push(value);
+            }
+        }
+        out
+    }
+}
+
+fn error_body(bytes: &[u8]) -> Value {
+    serde_json::from_slice::<Value>(bytes)
+        .unwrap_or_else(|_| json!({ "error": { "message": String::from_utf8_lossy(bytes) } }))
+}
+
+fn header_string(response: &Response, name: &str) -> Option<String> {
+    response.headers().get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string())
+}
+
+fn sse_event(value: &Value) -> Bytes {
+    Bytes::from(format!("data: {}\n\n", value))
+}
+
+/// 执行一路请求，把响应头 / chunk / 结果发送到复用通道
+async fn run_leg(state: AppState, index: usize, body: Value, stream: bool, tx: mpsc::UnboundedSender<LegEvent>) {
+    let response = crate::proxy::handlers::openai::handle_chat_completions(State(state), StreamingJson(body))
+        .await
+        .into_response();
+    let status = response.status().as_u16();
+    let _ = tx.send(LegEvent::Started {
+        index,
+        account: header_string(&response, "x-account-email"),
+        mapped_model: header_string(&response, "x-mapped-model"),
+    });
+
+    if !stream || !(200..300).contains(&status) {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap_or_default();
+        let _ = tx.send(LegEvent::Done { index, status, body: Some(error_body(&bytes)) });
+        return;
+    }
+    let mut decoder = SseDecoder::default();
+    let mut data = response.into_body().into_data_stream();
+    while let Some(bytes) = data.next().await {
+        let Ok(bytes) = bytes else {
+            let body = json!({ "error": { "message": "upstream stream interrupted", "type": "upstream_error" } });
+            let _ = tx.send(LegEvent::Done { index, status: 502, body: Some(body) });
+            return;
+        };
+        for chunk in decoder.push(&bytes) {
+            if tx.send(LegEvent::Chunk { index, chunk }).is_err() {
+                return;
+            }
+        }
+    }
+    let _ = tx.send(LegEvent::Done { index, status, body: None });
+}
+
+/// 解析各路显式指定的账号，与 X-AG-Account 请求头走同一套策略 (功能开关、Key 白名单、别名)
+fn resolve_targets(
+    targets: &[Target],
+    config: &AccountPinningConfig,
+    api_key: Option<&str>,
+    accounts: &[(String, String)],
+) -> Result<Vec<Option<(String, String)>>, Response> {
+    targets
+        .iter()
+        .map(|target| match &target.account {
+            Some(name) => resolve_pinned(config, api_key, name, accounts).map(Some),
+            None => Ok(None),
+        })
+        .collect()
+}
+
+pub async fn handle_fanout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    uri: Uri,
+    Json(body): Json<Value>,
+) -> Response {
+    if body.get("n").and_then(|n| n.as_u64()).unwrap_or(1) > 1 {
+        return invalid_request("fanout only supports n = 1 (use more targets instead)", Some("fanout"));
+    }
+    let targets = match parse_targets(&body) {
+        Ok(t) => t,
+        Err(e) => return invalid_request(e, Some("fanout")),
+    };
+    let race = body.get("race").and_then(|r| r.as_bool()).unwrap_or(false);
+    let stream = body.get("stream").and_then(|s| s.as_bool()).unwrap_or(false);
+
+    let explicit = if targets.iter().any(|t| t.account.is_some()) {
+        let config = state.account_pinning.read().await.clone();
+        let api_key = api_key_from_parts(&headers, &uri);
+        match resolve_targets(&targets, &config, api_key.as_deref(), &state.token_manager.all_accounts()) {
+            Ok(explicit) => explicit,
+            Err(response) => return response,
+        }
+    } else {
+        vec![None; targets.len()]
+    };
+    let accounts = assign_accounts(&explicit, &state.token_manager.schedulable_accounts());
+    tracing::info!(
+        "[Fanout] {} 路 (race={}, stream={}): {:?}",
+        targets.len(),
+        race,
+        stream,
+        targets
+            .iter()
+            .zip(&accounts)
+            .map(|(t, a)| format!("{}@{}", t.model, a.as_ref().map(|(_, email)| email.as_str()).unwrap_or("auto")))
+            .collect::<Vec<_>>()
+    );
+
+    let started = Instant::now();
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut tasks = JoinSet::new();
+    let mut handles = Vec::with_capacity(targets.len());
+    let mut legs = Vec::with_capacity(targets.len());
+    for (index, (target, account)) in targets.iter().zip(accounts).enumerate() {
+        let leg = run_leg(state.clone(), index, leg_body(&body, &target.model, stream), stream, tx.clone());
+        handles.push(match account.clone() {
+            Some((account_id, _)) => tasks.spawn(pin_account(account_id, leg)),
+            None => tasks.spawn(leg),
+        });
+        legs.push(LegInfo {
+            model: target.model.clone(),
+            account: account.map(|(_, email)| email),
+            ..Default::default()
+        });
+    }
+    drop(tx);
+
+    let requested_model = body.get("model").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+    match (race, stream) {
+        (true, _) => race_legs(rx, tasks, handles, legs, stream, started).await,
+        (false, true) => stream_all(rx, tasks, legs, started),
+        (false, false) => collect_all(rx, legs, &requested_model, started).await,
+    }
+}
+
+/// 默认流式模式: 各路 chunk 按到达顺序复用，choice index 即目标序号；每路结束时追加一个带 x_fanout 摘要的 chunk
+fn stream_all(
+    mut rx: mpsc::UnboundedReceiver<LegEvent>,
+    tasks: JoinSet<()>,
+    mut legs: Vec<LegInfo>,
+    started: Instant,
+) -> Response {
+    let output = async_stream::stream! {
+        // 客户端断开时随流一起释放，取消仍在进行的请求
+        let _tasks = tasks;
+        let mut labeled = vec![false; legs.len()];
+        while let Some(event) = rx.recv().await {
+            match event {
+                LegEvent::Started { index, account, mapped_model } => {
+                    legs[index].account = account.or(legs[index].account.take());
+                    legs[index].mapped_model = mapped_model;
+                }
+                LegEvent::Chunk { index, mut chunk } => {
+                    if legs[index].first_token_ms.is_none() && has_token(&chunk) {
+                        legs[index].first_token_ms = Some(started.elapsed().as_millis() as u64);
+                    }
+                    relabel(&mut chunk, index);
+                    // 每路的第一个 chunk 附带目标信息
+                    chunk["x_fanout"] = if labeled[index] { json!({ "index": index }) } else { legs[index].label(index) };
+                    labeled[index] = true;
+                    yield Ok::<_, std::io::Error>(sse_event(&chunk));
+                }
+                LegEvent::Done { index, status, body } => {
+                    let mut summary = legs[index].label(index);
+                    summary["done"] = json!(true);
+                    summary["status"] = json!(status);
+                    summary["latency_ms"] = json!(started.elapsed().as_millis() as u64);
+                    summary["first_token_ms"] = json!(legs[index].first_token_ms);
+                    let failed = !(200..300).contains(&status);
+                    if failed {
+                        summary["error"] = body.and_then(|b| b.get("error").cloned()).unwrap_or(Value::Null);
+                    }
+                    let choices = if failed {
+                        json!([{ "index": index, "delta": {}, "finish_reason": "error" }])
+                    } else {
+                        json!([])
+                    };
+                    yield Ok(sse_event(&json!({
+                        "object": "chat.completion.chunk",
+                        "created": chrono::Utc::now().timestamp(),
+                        "choices": choices,
+                        "x_fanout": summary
+                    })));
+                }
+            }
+        }
+        yield Ok(Bytes::from("data: [DONE]\n\n"));
+    };
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::CONNECTION, "keep-alive")
+        .body(Body::from_stream(output))
+        .unwrap()
+        .into_response()
+}
+
+/// 默认非流式模式: 等待所有路完成，合并为一个多 choice 的 chat.completion；失败的路以 finish_reason=error 占位
+async fn collect_all(
+    mut rx: mpsc::UnboundedReceiver<LegEvent>,
+    mut legs: Vec<LegInfo>,
+    requested_model: &str,
+    started: Instant,
+) -> Response {
+    let mut choices = vec![Value::Null; legs.len()];
+    let mut summaries = vec![Value::Null; legs.len()];
+    let mut usage = json!({});
+    let mut first_error: Option<(u16, Value)> = None;
+    while let Some(event) = rx.recv().await {
+        let (index, status, body) = match event {
+            LegEvent::Started { index, account, mapped_model } => {
+                legs[index].account = account.or(legs[index].account.take());
+                legs[index].mapped_model = mapped_model;
+                continue;
+            }
+            LegEvent::Chunk { .. } => continue,
+            LegEvent::Done { index, status, body } => (index, status, body.unwrap_or(Value::Null)),
+        };
+        let mut summary = legs[index].label(index);
+        summary["status"] = json!(status);
+        summary["latency_ms"] = json!(started.elapsed().as_millis() as u64);
+        if (200..300).contains(&status) {
+            add_usage(&mut usage, body.get("usage"));
+            let mut choice = choices_of(&body).into_iter().next().unwrap_or(Value::Null);
+            choice["index"] = json!(index);
+            choices[index] = choice;
+        } else {
+            summary["error"] = body.get("error").cloned().unwrap_or(Value::Null);
+            choices[index] = json!({
+                "index": index,
+                "message": { "role": "assistant", "content": null },
+                "finish_reason": "error"
+            });
+            first_error.get_or_insert((status, body));
+        }
+        summaries[index] = summary;
+    }
+
+    let all_failed = choices.iter().all(|c| c.get("finish_reason").and_then(|f| f.as_str()) == Some("error"));
+    if let Some((status, body)) = first_error.filter(|_| all_failed) {
+        return (StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY), axum::Json(body)).into_response();
+    }
+    axum::Json(json!({
+        "id": format!("chatcmpl-fanout-{}", uuid::Uuid::new_v4().simple()),
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": requested_model,
+        "choices": choices,
+        "usage": usage,
+        "x_fanout": summaries
+    }))
+    .into_response()
+}
+
+/// race 模式: 第一个输出 token (非流式为第一个成功完成) 的目标胜出，其余立即取消；全部失败时返回第一个错误
+async fn race_legs(
+    mut rx: mpsc::UnboundedReceiver<LegEvent>,
+    tasks: JoinSet<()>,
+    handles: Vec<AbortHandle>,
+    mut legs: Vec<LegInfo>,
+    stream: bool,
+    started: Instant,
+) -> Response {
+    let mut buffered: Vec<Vec<Value>> = vec![Vec::new(); legs.len()];
+    let mut failures = 0;
+    let mut first_error: Option<(u16, Value)> = None;
+    let (winner, finished, final_body) = loop {
+        let Some(event) = rx.recv().await else {
+            let (status, body) = first_error
+                .unwrap_or((502, json!({ "error": { "message": "all fanout requests failed", "type": "upstream_error" } })));
+            return (StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY), axum::Json(body)).into_response();
+        };
+        match event {
+            LegEvent::Started { index, account, mapped_model } => {
+                legs[index].account = account.or(legs[index].account.take());
+                legs[index].mapped_model = mapped_model;
+            }
+            LegEvent::Chunk { index, chunk } => {
+                let token = has_token(&chunk);
+                buffered[index].push(chunk);
+                if token {
+                    break (index, false, None);
+                }
+            }
+            LegEvent::Done { index, status, body } if (200..300).contains(&status) => break (index, true, body),
+            LegEvent::Done { status, body, .. } => {
+                failures += 1;
+                first_error.get_or_insert((status, body.unwrap_or(Value::Null)));
+                if failures == legs.len() {
+                    let (status, body) = first_error.unwrap_or_default();
+                    return (StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY), axum::Json(body))
+                        .into_response();
+                }
+            }
+        }
+    };
+    for (index, handle) in handles.iter().enumerate() {
+        if index != winner {
+            handle.abort();
+        }
+    }
+    tracing::info!(
+        "[Fanout] race 胜出: #{} {} ({}), 用时 {}ms",
+        winner,
+        legs[winner].model,
+        legs[winner].account.as_deref().unwrap_or("auto"),
+        started.elapsed().as_millis()
+    );
+
+    let info = legs[winner].clone();
+    let mut response = if stream {
+        let pending = std::mem::take(&mut buffered[winner]);
+        let output = async_stream::stream! {
+            let _tasks = tasks;
+            for chunk in pending {
+                yield Ok::<_, std::io::Error>(sse_event(&chunk));
+            }
+            if !finished {
+                while let Some(event) = rx.recv().await {
+                    match event {
+                        LegEvent::Chunk { index, chunk } if index == winner => yield Ok(sse_event(&chunk)),
+                        LegEvent::Done { index, status, body } if index == winner => {
+                            if !(200..300).contains(&status) {
+                                if let Some(body) = body {
+                                    yield Ok(sse_event(&body));
+                                }
+                            }
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            yield Ok(Bytes::from("data: [DONE]\n\n"));
+        };
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .header(header::CONNECTION, "keep-alive")
+            .body(Body::from_stream(output))
+            .unwrap()
+            .into_response()
+    } else {
+        axum::Json(final_body.unwrap_or(Value::Null)).into_response()
+    };
+
+    let headers = response.headers_mut();
+    headers.insert(FANOUT_WINNER_HEADER, HeaderValue::from(winner));
+    for (name, value) in [("x-account-email", &info.account), ("x-mapped-model", &info.mapped_model)] {
+        if let Some(v) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+            headers.insert(name, v);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(id: &str) -> (String, String) {
+        (id.to_string(), format!("{}@example.com", id))
+    }
+
+    #[test]
+    fn test_parse_targets() {
+        let targets = parse_targets(&json!({"model": "gemini-2.5-flash", "fanout": 3})).unwrap();
+        assert_eq!(targets.len(), 3);
+        assert!(targets.iter().all(|t| t.model == "gemini-2.5-flash" && t.account.is_none()));
+
+        let targets = parse_targets(&json!({
+            "model": "gemini-2.5-flash",
+            "fanout": ["claude-sonnet-4-5", {"account": "a@example.com"}, {"model": "gemini-2.5-pro"}]
+        }))
+        .unwrap();
+        assert_eq!(targets[0].model, "claude-sonnet-4-5");
+        assert_eq!(targets[1], Target { model: "gemini-2.5-flash".into(), account: Some("a@example.com".into()) });
+        assert_eq!(targets[2].model, "gemini-2.5-pro");
+
+        assert!(parse_targets(&json!({"model": "m", "fanout": 1})).is_err());
+        assert!(parse_targets(&json!({"model": "m", "fanout": 9})).is_err());
+        assert!(parse_targets(&json!({"fanout": 2})).is_err());
+        assert!(parse_targets(&json!({"fanout": [{"account": "a"}]})).is_err());
+        assert!(parse_targets(&json!({"model": "m"})).is_err());
+    }
+
+    #[test]
+    fn test_assign_accounts_spreads_over_pool() {
+        let pool = vec![pair("a"), pair("b"), pair("c")];
+        // 显式指定的账号保留，其余轮流分配未被占用的账号
+        let assigned = assign_accounts(&[None, Some(pair("a")), None, None], &pool);
+        assert_eq!(assigned, vec![Some(pair("b")), Some(pair("a")), Some(pair("c")), Some(pair("b"))]);
+        // 账号池为空: 交给正常调度
+        assert_eq!(assign_accounts(&[None, None], &[]), vec![None, None]);
+        // 所有账号都被显式占用: 复用账号池
+        let assigned = assign_accounts(&[Some(pair("a")), None], &[pair("a")]);
+        assert_eq!(assigned, vec![Some(pair("a")), Some(pair("a"))]);
+    }
+
+    #[test]
+    fn test_resolve_targets_applies_pinning_policy() {
+        let pool = vec![pair("a"), pair("b")];
+        let targets = vec![
+            Target { model: "m".into(), account: Some("work".into()) },
+            Target { model: "m".into(), account: None },
+        ];
+        let mut config = AccountPinningConfig {
+            enabled: true,
+            allow_model_suffix: true,
+            api_keys: vec!["sk-pin".into()],
+            aliases: [("work".to_string(), "b@example.com".to_string())].into_iter().collect(),
+        };
+        let status = |r: Result<Vec<Option<(String, String)>>, Response>| r.unwrap_err().status();
+
+        // 别名解析
+        let resolved = resolve_targets(&targets, &config, Some("sk-pin"), &pool).unwrap();
+        assert_eq!(resolved, vec![Some(pair("b")), None]);
+        // 白名单之外的 Key 与缺失的 Key 被拒绝
+        assert_eq!(status(resolve_targets(&targets, &config, Some("sk-other"), &pool)), StatusCode::FORBIDDEN);
+        assert_eq!(status(resolve_targets(&targets, &config, None, &pool)), StatusCode::FORBIDDEN);
+        // 未知账号
+        let unknown = vec![Target { model: "m".into(), account: Some("nobody".into()) }];
+        assert_eq!(status(resolve_targets(&unknown, &config, Some("sk-pin"), &pool)), StatusCode::BAD_REQUEST);
+        // 功能关闭时一律拒绝
+        config.enabled = false;
+        assert_eq!(status(resolve_targets(&targets, &config, Some("sk-pin"), &pool)), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_chunk_helpers() {
+        let mut chunk = json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": ""}}]});
+        assert!(!has_token(&chunk));
+        relabel(&mut chunk, 3);
+        assert_eq!(chunk["choices"][0]["index"], 3);
+        assert!(has_token(&json!({"choices": [{"delta": {"content": "Hi"}}]})));
+        assert!(has_token(&json!({"choices": [{"delta": {"tool_calls": [{"index": 0}]}}]})));
+        assert!(!has_token(&json!({"choices": [], "usage": {"total_tokens": 3}})));
+
+        let body = leg_body(&json!({"model": "x", "fanout": 2, "race": true, "messages": []}), "y", true);
+        assert_eq!(body, json!({"model": "y", "stream": true, "messages": []}));
+    }
+
+    #[test]
+    fn test_sse_decoder_handles_split_lines() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"data: {\"a\":").is_empty());
+        let values = decoder.push(b"1}\n\n: keepalive\n\ndata: [DONE]\n\ndata: {\"b\":2}\n\n");
+        assert_eq!(values, vec![json!({"a": 1}), json!({"b": 2})]);
+    }
+}
@@ -5,17 +5,104 @@ use tracing::{debug, error, info};
 
 use crate::proxy::mappers::gemini::{wrap_request, unwrap_response};
 use crate::proxy::server::AppState;
+use crate::proxy::common::streaming_json::StreamingJson;
 use crate::proxy::session_manager::SessionManager;
+use crate::proxy::handlers::common::gate_capabilities;
+use crate::proxy::middleware::response_rewrite::{self, CompiledRewrites};
  
 const MAX_RETRY_ATTEMPTS: usize = 3;
+
+/// 路由到 Vertex AI 的请求: 原生 Gemini 请求体直接转发，响应原样返回
+async fn forward_to_vertex(
+    state: &AppState,
+    config: &crate::proxy::config::VertexConfig,
+    model: &str,
+    is_stream: bool,
+    body: Value,
+    rewrites: CompiledRewrites,
+) -> axum::response::Response {
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    let resp = match crate::proxy::upstream::vertex::call_generate(config, &upstream_proxy, state.request_timeout, model, is_stream, body).await {
+        Ok(r) => r,
+        Err(e) => {
+            error!("[Gemini] Vertex request failed: {}", e);
+            return (StatusCode::BAD_GATEWAY, e).into_response();
+        }
+    };
+    passthrough_response(resp, "vertex", model, rewrites).await
+}
+
+/// 路由到 AI Studio API Key 账号的请求: 原生 Gemini 请求体直接转发 (429 时自动换 Key)
+async fn forward_to_ai_studio(
+    state: &AppState,
+    model: &str,
+    is_stream: bool,
+    body: Value,
+    rewrites: CompiledRewrites,
+) -> axum::response::Response {
+    let keys = state.token_manager.api_key_accounts();
+    let config = state.ai_studio.read().await.clone();
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    match crate::proxy::upstream::aistudio::call_generate(&keys, &config, &upstream_proxy, state.request_timeout, model, is_stream, body).await {
+        Ok((resp, email)) => passthrough_response(resp, &email, model, rewrites).await,
+        Err((status, e)) => {
+            error!("[Gemini] AI Studio request failed: {}", e);
+            (StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY), e).into_response()
+        }
+    }
+}
+
+/// 直连上游 (Vertex / AI Studio) 的响应原样返回 (仅应用响应改写规则)，account 记入请求历史
+async fn passthrough_response(
+    resp: reqwest::Response,
+    account: &str,
+    model: &str,
+    rewrites: CompiledRewrites,
+) -> axum::response::Response {
+    use axum::response::Response;
+    let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let content_type = resp
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .cloned()
+        .unwrap_or_else(|| axum::http::HeaderValue::from_static("application/json"));
+    let is_sse = content_type.to_str().is_ok_and(|ct| ct.starts_with("text/event-stream"));
+    let body = if !status.is_success() || rewrites.is_empty() {
+        axum::body::Body::from_stream(resp.bytes_stream())
+    } else if is_sse {
+        axum::body::Body::from_stream(response_rewrite::apply_to_stream(Box::pin(resp.bytes_stream()), rewrites))
+    } else {
+        match resp.json::<Value>().await {
+            Ok(mut json) => {
+                response_rewrite::apply_to_response(&mut json, &rewrites);
+                axum::body::Body::from(json.to_string())
+            }
+            Err(e) => return (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)).into_response(),
+        }
+    };
+    Response::builder()
+        .status(status)
+        .header(axum::http::header::CONTENT_TYPE, content_type)
+        .header("X-Account-Email", account)
+        .header("X-Mapped-Model", model)
+        .body(body)
+        .unwrap_or_else(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to build response").into_response())
+}
  
 /// 处理 generateContent 和 streamGenerateContent
 /// 路径参数: model_name, method (e.g. "gemini-pro", "generateContent")
 pub async fn handle_generate(
     State(state): State<AppState>,
     Path(model_action): Path<String>,
-    Json(body): Json<Value>
+    uri: axum::http::Uri,
+    StreamingJson(mut body): StreamingJson<Value>
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
+    // 中间件 (上下文升级 / 降级链) 可能改写了 URI 中的模型，路径参数仍是路由时的原值，以当前 URI 为准
+    let model_action = uri
+        .path()
+        .strip_prefix("/v1beta/models/")
+        .map(|s| s.to_string())
+        .unwrap_or(model_action);
     // 解析 model:method
     let (model_name, method) = if let Some((m, action)) = model_action.rsplit_once(':') {
         (m.to_string(), action.to_string())
@@ -30,10 +117,30 @@ pub async fn handle_generate(
         return Err((StatusCode::BAD_REQUEST, format!("Unsupported method: {}", method)));
     }
     let is_stream = method == "streamGenerateContent";
+    let rewrites = response_rewrite::rewrites_for(&state, &model_name).await;
+
+    // 模型路由到 Vertex AI (服务账号) 时直接转发，不占用账号池
+    let vertex_config = state.vertex.read().await.clone();
+    let vertex_model = crate::proxy::upstream::vertex::resolve_model(&vertex_config, &model_name, &*state.custom_mapping.read().await);
+    if let Some(vertex_model) = vertex_model {
+        info!("[Gemini] Routing {} to Vertex AI as {}", model_name, vertex_model);
+        gate_capabilities(&state, &mut body, "vertex", &vertex_model).await?;
+        return Ok(forward_to_vertex(&state, &vertex_config, &vertex_model, is_stream, body, rewrites).await);
+    }
+    let ai_studio_model = crate::proxy::upstream::aistudio::resolve_model(
+        &*state.ai_studio.read().await,
+        &model_name,
+        &*state.custom_mapping.read().await,
+    );
+    if let Some(ai_studio_model) = ai_studio_model {
+        info!("[Gemini] Routing {} to AI Studio API keys as {}", model_name, ai_studio_model);
+        gate_capabilities(&state, &mut body, "aistudio", &ai_studio_model).await?;
+        return Ok(forward_to_ai_studio(&state, &ai_studio_model, is_stream, body, rewrites).await);
+    }
 
     // 2. 获取 UpstreamClient 和 TokenManager
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     let pool_size = token_manager.len();
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
     
@@ -69,6 +176,15 @@ pub async fn handle_generate(
         let (access_token, project_id, email) = match token_manager.get_token(&config.request_type, attempt > 0, Some(&session_id), &config.final_model).await {
             Ok(t) => t,
             Err(e) => {
+                // OAuth 账号池无可用账号时回退到 AI Studio API Key
+                if state.ai_studio.read().await.fallback
+                    && crate::proxy::upstream::aistudio::supports_model(&config.final_model)
+                    && !token_manager.api_key_accounts().is_empty()
+                {
+                    info!("[Gemini] No OAuth account available ({}), falling back to AI Studio API keys", e);
+                    gate_capabilities(&state, &mut body, "aistudio", &config.final_model).await?;
+                    return Ok(forward_to_ai_studio(&state, &config.final_model, is_stream, body, rewrites).await);
+                }
                 return Err((StatusCode::SERVICE_UNAVAILABLE, format!("Token error: {}", e)));
             }
         };
@@ -78,14 +194,31 @@ pub async fn handle_generate(
 
         // 5. 包装请求 (project injection)
         // [FIX #765] Pass session_id to wrap_request for signature injection
-        let wrapped_body = wrap_request(&body, &project_id, &mapped_model, Some(&session_id));
+        let mut wrapped_body = wrap_request(&body, &project_id, &mapped_model, Some(&session_id));
+        gate_capabilities(
+            &state,
+            &mut wrapped_body,
+            crate::proxy::mappers::capabilities::BACKEND_ANTIGRAVITY,
+            &config.final_model,
+        )
+        .await?;
+        let stream_resume_attempts = {
+            let exp = state.experimental.read().await;
+            crate::proxy::mappers::signature_degradation::apply_signature_degradation(
+                &mut wrapped_body,
+                &exp.signature_degradation,
+                &exp.signature_fallback_model,
+            );
+            exp.stream_resume_max_attempts
+        };
+        let local_stops = crate::proxy::mappers::stop_sequences::take_local_stops(&mut wrapped_body);
 
         // 5. 上游调用
         let query_string = if is_stream { Some("alt=sse") } else { None };
         let upstream_method = if is_stream { "streamGenerateContent" } else { "generateContent" };
 
         let response = match upstream
-            .call_v1_internal(upstream_method, &access_token, wrapped_body, query_string)
+            .call_v1_internal(upstream_method, &access_token, &wrapped_body, query_string)
             .await {
                 Ok(r) => r,
                 Err(e) => {
@@ -104,7 +237,24 @@ pub async fn handle_generate(
                 use bytes::{Bytes, BytesMut};
                 use futures::StreamExt;
                 
-                let mut response_stream = response.bytes_stream();
+                let response_stream = crate::proxy::upstream::resume::resumable_stream(
+                    Box::pin(response.bytes_stream()),
+                    upstream.clone(),
+                    access_token.clone(),
+                    wrapped_body.clone(),
+                    stream_resume_attempts,
+                    format!("Gemini:{}", email),
+                );
+                let response_stream = crate::proxy::mappers::prefill::strip_echo_stream(
+                    response_stream,
+                    crate::proxy::mappers::prefill::prefill_text(&wrapped_body),
+                );
+                let response_stream = crate::proxy::mappers::stop_sequences::apply_to_stream(response_stream, local_stops);
+                let response_stream = response_rewrite::apply_to_stream(response_stream, rewrites.clone());
+                let mut response_stream = crate::proxy::middleware::stream_pacing::pace_stream(
+                    response_stream,
+                    crate::proxy::middleware::stream_pacing::current_cps(),
+                );
                 let mut buffer = BytesMut::new();
                 let s_id = session_id.clone(); // Clone for stream closure
 
@@ -225,8 +375,9 @@ pub async fn handle_generate(
                         }
                     }
                 };
-                
-                let body = Body::from_stream(stream);
+                // 链路追踪: 流式响应转换阶段 (随流结束关闭)
+                let span = tracing::info_span!(target: crate::proxy::telemetry::TARGET, "map_back", ag.protocol = "gemini", ag.streaming = true);
+                let body = Body::from_stream(crate::proxy::telemetry::traced_stream(span, stream));
                 return Ok(Response::builder()
                     .header("Content-Type", "text/event-stream")
                     .header("Cache-Control", "no-cache")
@@ -238,10 +389,15 @@ pub async fn handle_generate(
                     .into_response());
             }
 
-            let gemini_resp: Value = response
+            let mut gemini_resp: Value = response
                 .json()
                 .await
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+            if let Some(prefill) = crate::proxy::mappers::prefill::prefill_text(&wrapped_body) {
+                crate::proxy::mappers::prefill::strip_echo_in_response(&mut gemini_resp, &prefill);
+            }
+            crate::proxy::mappers::stop_sequences::apply_to_response(&mut gemini_resp, &local_stops);
+            response_rewrite::apply_to_response(&mut gemini_resp, &rewrites);
 
             // [FIX #765] Extract thoughtSignature from non-streaming response
             let inner_val = if gemini_resp.get("response").is_some() {
@@ -274,6 +430,17 @@ pub async fn handle_generate(
         let retry_after = response.headers().get("Retry-After").and_then(|h| h.to_str().ok()).map(|s| s.to_string());
         let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status_code));
         last_error = format!("HTTP {}: {}", status_code, error_text);
+
+        // [NEW] project_id 失效时自动走 loadCodeAssist 发现流程修复并重试
+        if crate::proxy::project_resolver::is_project_error(status_code, &error_text) {
+            match token_manager.repair_project_id(&email).await {
+                Ok(new_pid) => {
+                    tracing::warn!("[Gemini] Project ID for {} was invalid, repaired to {}, retrying", email, new_pid);
+                    continue;
+                }
+                Err(e) => tracing::warn!("[Gemini] Project ID repair failed for {}: {}", email, e),
+            }
+        }
  
         // 只有 429 (限流), 529 (过载), 503, 403 (权限) 和 401 (认证失效) 触发账号轮换
         if status_code == 429 || status_code == 529 || status_code == 503 || status_code == 500 || status_code == 403 || status_code == 401 {
@@ -312,13 +479,14 @@ pub async fn handle_list_models(State(state): State<AppState>) -> Result<impl In
 
     // 转换为 Gemini API 格式
     let models: Vec<_> = model_ids.into_iter().map(|id| {
+        let limits = crate::proxy::mappers::model_limits::resolve_listed(&id);
         json!({
             "name": format!("models/{}", id),
             "version": "001",
             "displayName": id.clone(),
             "description": "",
-            "inputTokenLimit": 128000,
-            "outputTokenLimit": 8192,
+            "inputTokenLimit": limits.context_window.unwrap_or(128000),
+            "outputTokenLimit": limits.max_output_tokens.unwrap_or(8192),
             "supportedGenerationMethods": ["generateContent", "countTokens"],
             "temperature": 1.0,
             "topP": 0.95,
@@ -330,10 +498,18 @@ pub async fn handle_list_models(State(state): State<AppState>) -> Result<impl In
 }
 
 pub async fn handle_get_model(Path(model_name): Path<String>) -> impl IntoResponse {
-    Json(json!({
+    let limits = crate::proxy::mappers::model_limits::resolve_listed(&model_name);
+    let mut model = json!({
         "name": format!("models/{}", model_name),
         "displayName": model_name
-    }))
+    });
+    if let Some(window) = limits.context_window {
+        model["inputTokenLimit"] = json!(window);
+    }
+    if let Some(max_output) = limits.max_output_tokens {
+        model["outputTokenLimit"] = json!(max_output);
+    }
+    Json(model)
 }
 
 pub async fn handle_count_tokens(State(state): State<AppState>, Path(_model_name): Path<String>, Json(_body): Json<Value>) -> Result<impl IntoResponse, (StatusCode, String)> {
@@ -336,15 +336,51 @@ async fn handle_vision_post(state: AppState, headers: HeaderMap, body: Body) ->
             let upstream_proxy = state.upstream_proxy.read().await.clone();
             let timeout = state.request_timeout;
 
-            match crate::proxy::zai_vision_tools::call_tool(
+            // 工具循环保护: 调用次数 / 累计耗时 / 连续重复调用
+            let limits = crate::proxy::zai_vision_mcp::ToolLoopLimits::from_config(&zai.mcp);
+            let remaining = match state
+                .zai_vision_mcp
+                .begin_tool_call(&session_id, tool_name, &arguments, &limits)
+                .await
+            {
+                Ok(remaining) => remaining,
+                Err(reason) => {
+                    tracing::warn!("[MCP] 会话 {} 工具循环已中止: {}", session_id, reason);
+                    return (
+                        StatusCode::OK,
+                        axum::Json(jsonrpc_result(id, crate::proxy::zai_vision_mcp::loop_aborted_result(&reason))),
+                    )
+                        .into_response();
+                }
+            };
+
+            let started = std::time::Instant::now();
+            let call = crate::proxy::zai_vision_tools::call_tool(
                 &zai,
                 upstream_proxy,
                 timeout,
                 tool_name,
                 &arguments,
-            )
-            .await
-            {
+            );
+            let outcome = match remaining {
+                Some(budget) => match tokio::time::timeout(budget, call).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        state.zai_vision_mcp.finish_tool_call(&session_id, started.elapsed()).await;
+                        let reason = format!("timeout: cumulative tool time exceeded {}s", limits.max_total.as_secs());
+                        tracing::warn!("[MCP] 会话 {} 工具循环已中止: {}", session_id, reason);
+                        return (
+                            StatusCode::OK,
+                            axum::Json(jsonrpc_result(id, crate::proxy::zai_vision_mcp::loop_aborted_result(&reason))),
+                        )
+                            .into_response();
+                    }
+                },
+                None => call.await,
+            };
+            state.zai_vision_mcp.finish_tool_call(&session_id, started.elapsed()).await;
+
+            match outcome {
                 Ok(tool_result) => {
                     (StatusCode::OK, axum::Json(jsonrpc_result(id, tool_result))).into_response()
                 }
@@ -8,4 +8,10 @@ pub mod mcp;
 pub mod common;
 pub mod audio;  // 音频转录处理器
 pub mod warmup; // 预热处理器
-
+pub mod admin;  // 远程管理 API
+pub mod batches; // Batch API (/v1/files, /v1/batches)
+pub mod moderations; // 内容审核 (/v1/moderations)
+pub mod summarize; // 长文本 map-reduce 摘要 (/v1/summarize)
+pub mod ocr; // 图片文字识别 (/v1/ocr)
+pub mod rerank; // 文档重排序 (/v1/rerank)
+pub mod fanout; // 多账号/多模型并行扇出 (/v1/fanout)
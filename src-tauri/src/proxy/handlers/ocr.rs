@@ -0,0 +1,222 @@
+// 图片文字识别 (/v1/ocr，非标准端点)
+// 接收一张图片 (data URL / 裸 base64 / http(s) 地址)，以带 JSON Schema 的视觉提示词驱动 Gemini，
+// 返回结构化的文字块 (类型 + 文本 + 归一化坐标) 及拼接后的全文，调用方无需自行实现对话协议。
+// 模型调用在进程内走 chat completions handler，复用账号轮换/重试/协议转换。
+
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+
+use crate::proxy::batch::execute_with_backoff;
+use crate::proxy::common::http::invalid_request;
+use crate::proxy::middleware::best_of::{choices_of, content_text};
+use crate::proxy::server::AppState;
+
+const DEFAULT_OCR_MODEL: &str = "gemini-2.5-flash";
+const BLOCK_TYPES: [&str; 5] = ["paragraph", "heading", "list", "table", "other"];
+
+/// image 支持 data URL、http(s) 地址或裸 base64 (按 PNG 处理，Gemini 会自行识别实际格式)
+fn parse_image(image: &Value) -> Result<String, String> {
+    let image = image
+        .as_str()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .ok_or("image must be a non-empty string (data URL, http(s) URL or base64)")?;
+    if image.starts_with("data:") || image.starts_with("http://") || image.starts_with("https://") {
+        return Ok(image.to_string());
+    }
+    let looks_base64 = image
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=' | b'-' | b'_' | b'\n' | b'\r'));
+    if !looks_base64 {
+        return Err("image must be a data URL, an http(s) URL or base64-encoded bytes".to_string());
+    }
+    Ok(format!("data:image/png;base64,{}", image))
+}
+
+fn response_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "language": { "type": "string" },
+            "blocks": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "type": { "type": "string", "enum": BLOCK_TYPES },
+                        "text": { "type": "string" },
+                        "bbox": { "type": "array", "items": { "type": "integer" } }
+                    },
+                    "required": ["type", "text"]
+                }
+            }
+        },
+        "required": ["blocks"]
+    })
+}
+
+fn ocr_prompt(language: Option<&str>) -> String {
+    let mut prompt = String::from(
+        "Transcribe all text visible in this image exactly as written, in natural reading order. \
+         Group it into blocks (paragraph, heading, list, table or other). Do not translate, correct or summarize. \
+         For tables, keep one row per line with cells separated by \" | \". \
+         For each block give bbox as [ymin, xmin, ymax, xmax] normalized to 0-1000. \
+         Set language to the BCP-47 code of the dominant language. If there is no text, return an empty blocks array.",
+    );
+    if let Some(language) = language {
+        prompt.push_str(&format!(" The text is expected to be in {}.", language));
+    }
+    prompt
+}
+
+fn chat_body(model: &str, image_url: &str, language: Option<&str>) -> Value {
+    json!({
+        "model": model,
+        "stream": false,
+        "temperature": 0,
+        "messages": [{
+            "role": "user",
+            "content": [
+                { "type": "image_url", "image_url": { "url": image_url } },
+                { "type": "text", "text": ocr_prompt(language) }
+            ]
+        }],
+        "response_format": {
+            "type": "json_schema",
+            "json_schema": { "name": "ocr_result", "schema": response_schema(), "strict": true }
+        }
+    })
+}
+
+/// 解析模型输出；兼容被 ```json 代码块包裹的情况，并剔除空块 / 非法坐标
+fn parse_result(content: &str) -> Result<(Option<String>, Vec<Value>), String> {
+    let trimmed = content.trim();
+    let unfenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|s| s.trim_end().strip_suffix("```"))
+        .unwrap_or(trimmed);
+    let parsed: Value =
+        serde_json::from_str(unfenced.trim()).map_err(|e| format!("Model returned invalid OCR JSON: {}", e))?;
+
+    let blocks = parsed
+        .get("blocks")
+        .and_then(|b| b.as_array())
+        .map(|blocks| {
+            blocks
+                .iter()
+                .filter_map(|block| {
+                    let text = block.get("text").and_then(|t| t.as_str())?.trim_end();
+                    if text.trim().is_empty() {
+                        return None;
+                    }
+                    let kind = block
+                        .get("type")
+                        .and_then(|t| t.as_str())
+                        .filter(|t| BLOCK_TYPES.contains(t))
+                        .unwrap_or("other");
+                    let bbox = block
+                        .get("bbox")
+                        .and_then(|b| b.as_array())
+                        .map(|b| b.iter().filter_map(|v| v.as_f64()).map(|v| v.clamp(0.0, 1000.0).round() as u32).collect::<Vec<_>>())
+                        .filter(|b| b.len() == 4);
+                    Some(json!({ "type": kind, "text": text, "bbox": bbox }))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let language = parsed
+        .get("language")
+        .and_then(|l| l.as_str())
+        .filter(|l| !l.is_empty())
+        .map(|l| l.to_string());
+    Ok((language, blocks))
+}
+
+pub async fn handle_ocr(State(state): State<AppState>, Json(body): Json<Value>) -> Response {
+    let image_url = match parse_image(body.get("image").unwrap_or(&Value::Null)) {
+        Ok(i) => i,
+        Err(e) => return invalid_request(e, None),
+    };
+    let model = body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .filter(|m| !m.is_empty())
+        .unwrap_or(DEFAULT_OCR_MODEL)
+        .to_string();
+    let language = body.get("language").and_then(|l| l.as_str()).filter(|l| !l.trim().is_empty());
+    tracing::info!("[OCR] 模型={}, 语言提示={:?}", model, language);
+
+    let (status, response) = execute_with_backoff(&state, "/v1/chat/completions", chat_body(&model, &image_url, language)).await;
+    if !(200..300).contains(&status) {
+        return (StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY), axum::Json(response)).into_response();
+    }
+    let content = choices_of(&response)
+        .first()
+        .and_then(|c| c.pointer("/message/content"))
+        .map(content_text)
+        .unwrap_or_default();
+    let (detected, blocks) = match parse_result(&content) {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::warn!("[OCR] {}", e);
+            return (
+                StatusCode::BAD_GATEWAY,
+                axum::Json(json!({ "error": { "message": e, "type": "upstream_error", "code": null } })),
+            )
+                .into_response();
+        }
+    };
+    let text = blocks
+        .iter()
+        .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    axum::Json(json!({
+        "object": "ocr.result",
+        "model": model,
+        "language": detected,
+        "text": text,
+        "blocks": blocks,
+        "usage": response.get("usage").cloned().unwrap_or(Value::Null),
+    }))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_image_forms() {
+        assert_eq!(parse_image(&json!("https://example.com/a.png")).unwrap(), "https://example.com/a.png");
+        assert_eq!(parse_image(&json!("data:image/jpeg;base64,AAAA")).unwrap(), "data:image/jpeg;base64,AAAA");
+        assert_eq!(parse_image(&json!(" iVBORw0KGgo= ")).unwrap(), "data:image/png;base64,iVBORw0KGgo=");
+        assert!(parse_image(&json!("not an image!")).is_err());
+        assert!(parse_image(&json!(null)).is_err());
+
+        let body = chat_body("gemini-2.5-flash", "data:image/png;base64,AAAA", Some("Japanese"));
+        assert_eq!(body["response_format"]["json_schema"]["name"], "ocr_result");
+        assert!(body["messages"][0]["content"][1]["text"].as_str().unwrap().ends_with("in Japanese."));
+    }
+
+    #[test]
+    fn test_parse_result_normalizes_blocks() {
+        let content = "```json\n{\"language\":\"en\",\"blocks\":[\
+            {\"type\":\"heading\",\"text\":\"Invoice\",\"bbox\":[10,20,40,1200]},\
+            {\"type\":\"caption\",\"text\":\"Total: 42\"},\
+            {\"type\":\"paragraph\",\"text\":\"  \"}]}\n```";
+        let (language, blocks) = parse_result(content).unwrap();
+        assert_eq!(language.as_deref(), Some("en"));
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0]["bbox"], json!([10, 20, 40, 1000]));
+        assert_eq!(blocks[1]["type"], "other");
+        assert_eq!(blocks[1]["bbox"], Value::Null);
+        assert!(parse_result("sorry, I can't").is_err());
+    }
+}
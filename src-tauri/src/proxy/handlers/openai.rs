@@ -5,16 +5,251 @@ use bytes::Bytes;
 use serde_json::{json, Value};
 use tracing::{debug, error, info}; // Import Engine trait for encode method
 
+use crate::proxy::mappers::common_utils::ImageGenOptions;
+use crate::proxy::mappers::openai::completions::{clean_fim_output, LegacyPrompt, PROMPT_TRUNCATED_HEADER};
 use crate::proxy::mappers::openai::{
-    transform_openai_request, transform_openai_response, OpenAIRequest,
+    prepare_attempt_body, transform_openai_request, transform_openai_response, OpenAIRequest,
 };
 // use crate::proxy::upstream::client::UpstreamClient; // 通过 state 获取
 use crate::proxy::server::AppState;
+use crate::proxy::common::streaming_json::StreamingJson;
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
+/// /v1/images/generations 单次请求的图片数上限 (与 OpenAI 一致)
+const MAX_IMAGES_PER_REQUEST: usize = 10;
 use crate::proxy::session_manager::SessionManager;
 use tokio::time::{sleep, Duration};
 
+/// 纠错重提示 (本地修复失败时追加一次)
+const JSON_REPROMPT: &str = "Your previous reply could not be used because it is not valid JSON for the requested format";
+
+/// 把处理备注写入响应头，由监控中间件记录到请求历史
+fn with_notes(mut response: axum::response::Response, note: Option<String>) -> axum::response::Response {
+    if let Some(note) = note {
+        // 响应头只允许可见 ASCII
+        let sanitized: String = note
+            .chars()
+            .map(|c| if c.is_ascii() && !c.is_ascii_control() { c } else { '?' })
+            .take(256)
+            .collect();
+        if let Ok(value) = axum::http::HeaderValue::from_str(&sanitized) {
+            response
+                .headers_mut()
+                .insert(crate::proxy::middleware::monitor::NOTES_HEADER, value);
+        }
+    }
+    response
+}
+
+fn response_text(response: &crate::proxy::mappers::openai::OpenAIResponse) -> Option<String> {
+    let message = &response.choices.first()?.message;
+    if message.tool_calls.is_some() {
+        return None;
+    }
+    match message.content.as_ref()? {
+        crate::proxy::mappers::openai::OpenAIContent::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+fn set_response_text(response: &mut crate::proxy::mappers::openai::OpenAIResponse, text: String) {
+    if let Some(choice) = response.choices.first_mut() {
+        choice.message.content = Some(crate::proxy::mappers::openai::OpenAIContent::String(text));
+    }
+}
+
+/// 解析 (必要时本地修复) 并按 schema 校验；返回规范化后的 JSON 文本与修复步骤
+fn check_json_output(
+    text: &str,
+    schema: Option<&Value>,
+) -> Result<(String, Vec<crate::proxy::common::json_repair::RepairStep>), String> {
+    use crate::proxy::common::json_repair;
+    let (value, steps) = json_repair::parse_with_repair(text).ok_or_else(|| "invalid JSON".to_string())?;
+    if let Some(schema) = schema {
+        json_repair::validate(&value, schema, "")?;
+    }
+    let text = if steps.is_empty() { text.to_string() } else { value.to_string() };
+    Ok((text, steps))
+}
+
+/// `response_format` 要求 JSON 时校验最终文本；失败时本地修复，仍失败且开启重提示时纠错重试一次。
+/// 返回写入请求历史的备注 (输出本身合法时为 None)
+async fn enforce_json_output(
+    response: &mut crate::proxy::mappers::openai::OpenAIResponse,
+    format: &crate::proxy::mappers::openai::ResponseFormat,
+    reprompt: bool,
+    upstream: &std::sync::Arc<crate::proxy::upstream::client::UpstreamClient>,
+    access_token: &str,
+    gemini_body: &Value,
+) -> Option<String> {
+    let original = response_text(response)?;
+    let schema = format.schema();
+    let error = match check_json_output(&original, schema) {
+        Ok((_, steps)) if steps.is_empty() => return None,
+        Ok((fixed, steps)) => {
+            set_response_text(response, fixed);
+            let steps: Vec<&str> = steps.iter().map(|s| s.as_str()).collect();
+            return Some(format!("json_repair=repaired({})", steps.join(",")));
+        }
+        Err(e) => e,
+    };
+    if !reprompt {
+        tracing::warn!("[OpenAI] Structured output failed validation: {}", error);
+        return Some(format!("json_repair=failed({})", error));
+    }
+
+    let mut body = gemini_body.clone();
+    if let Some(contents) = body["request"]["contents"].as_array_mut() {
+        contents.push(json!({ "role": "model", "parts": [{ "text": original }] }));
+        contents.push(json!({
+            "role": "user",
+            "parts": [{ "text": format!("{} ({}). Reply again with only the corrected JSON, no prose and no code fences.", JSON_REPROMPT, error) }]
+        }));
+    }
+    let retried = match upstream
+        .call_v1_internal("generateContent", access_token, &body, None)
+        .await
+    {
+        Ok(resp) if resp.status().is_success() => resp.json::<Value>().await.ok(),
+        Ok(resp) => {
+            tracing::warn!("[OpenAI] JSON re-prompt failed: HTTP {}", resp.status());
+            None
+        }
+        Err(e) => {
+            tracing::warn!("[OpenAI] JSON re-prompt failed: {}", e);
+            None
+        }
+    };
+    let retried_text = retried.map(|v| transform_openai_response(&v)).as_ref().and_then(response_text);
+    match retried_text.map(|t| check_json_output(&t, schema)) {
+        Some(Ok((fixed, _))) => {
+            set_response_text(response, fixed);
+            Some("json_repair=reprompted".to_string())
+        }
+        Some(Err(e)) => Some(format!("json_repair=failed({})", e)),
+        None => Some(format!("json_repair=failed({})", error)),
+    }
+}
+
+/// 模型经映射表路由到 OpenAI 兼容上游时原样转发 (path 为上游相对路径)，否则返回 None 走账号池
+async fn forward_to_openai_backend(state: &AppState, body: &Value, path: &str) -> Option<axum::response::Response> {
+    let model = body.get("model")?.as_str()?;
+    let (backend, upstream_model) = {
+        let backends = state.openai_backends.read().await;
+        if backends.is_empty() {
+            return None;
+        }
+        let mapping = state.custom_mapping.read().await;
+        crate::proxy::providers::openai_compat::resolve_backend(&backends, model, &mapping)
+            .map(|(b, m)| (b.clone(), m))?
+    };
+    info!("[OpenAI] Routing {} to OpenAI-compatible upstream '{}' as {}", model, backend.name, upstream_model);
+    Some(
+        crate::proxy::providers::openai_compat::forward_openai_json(state, &backend, path, &upstream_model, body.clone())
+            .await,
+    )
+}
+
+/// 路由到 Vertex AI (服务账号) 的 Chat 请求: 复用转换后的 Gemini 请求体，流式调用后转换为 OpenAI 格式
+async fn vertex_chat_completion(
+    state: &AppState,
+    config: &crate::proxy::config::VertexConfig,
+    vertex_model: &str,
+    gemini_body: &Value,
+    local_stops: Vec<crate::proxy::mappers::stop_sequences::StopPattern>,
+    client_wants_stream: bool,
+    client_model: &str,
+) -> Result<axum::response::Response, (StatusCode, String)> {
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    let response = crate::proxy::upstream::vertex::call_generate(
+        config,
+        &upstream_proxy,
+        state.request_timeout,
+        vertex_model,
+        true,
+        gemini_body["request"].clone(),
+    )
+    .await
+    .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+    let rewrites = crate::proxy::middleware::response_rewrite::rewrites_for(state, client_model).await;
+    direct_chat_completion(response, "vertex", vertex_model, gemini_body, local_stops, rewrites, client_wants_stream, client_model).await
+}
+
+/// 路由到 AI Studio API Key 账号的 Chat 请求 (显式路由或 OAuth 账号池无可用账号时回退)
+async fn ai_studio_chat_completion(
+    state: &AppState,
+    model: &str,
+    gemini_body: &Value,
+    local_stops: Vec<crate::proxy::mappers::stop_sequences::StopPattern>,
+    client_wants_stream: bool,
+    client_model: &str,
+) -> Result<axum::response::Response, (StatusCode, String)> {
+    let keys = state.token_manager.api_key_accounts();
+    let config = state.ai_studio.read().await.clone();
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    let (response, email) = crate::proxy::upstream::aistudio::call_generate(
+        &keys,
+        &config,
+        &upstream_proxy,
+        state.request_timeout,
+        model,
+        true,
+        gemini_body["request"].clone(),
+    )
+    .await
+    .map_err(|(status, e)| (StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY), e))?;
+    let rewrites = crate::proxy::middleware::response_rewrite::rewrites_for(state, client_model).await;
+    direct_chat_completion(response, &email, model, gemini_body, local_stops, rewrites, client_wants_stream, client_model).await
+}
+
+/// 直连 Gemini 上游 (Vertex / AI Studio) 的流式响应转换为 OpenAI 格式
+async fn direct_chat_completion(
+    response: reqwest::Response,
+    account: &str,
+    upstream_model: &str,
+    gemini_body: &Value,
+    local_stops: Vec<crate::proxy::mappers::stop_sequences::StopPattern>,
+    rewrites: crate::proxy::middleware::response_rewrite::CompiledRewrites,
+    client_wants_stream: bool,
+    client_model: &str,
+) -> Result<axum::response::Response, (StatusCode, String)> {
+    use crate::proxy::mappers::openai::collect_openai_stream_to_json;
+    use crate::proxy::mappers::openai::streaming::create_openai_sse_stream;
+    use futures::StreamExt;
+
+    if !response.status().is_success() {
+        let status = StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+        let error_text = response.text().await.unwrap_or_default();
+        tracing::error!("[OpenAI-Direct] Error Response {} from {}: {}", status, account, error_text);
+        return Ok((status, [("X-Account-Email", account)], error_text).into_response());
+    }
+
+    let gemini_stream: crate::proxy::upstream::resume::GeminiByteStream = Box::pin(response.bytes_stream());
+    let gemini_stream = crate::proxy::mappers::prefill::strip_echo_stream(
+        gemini_stream,
+        crate::proxy::mappers::prefill::prefill_text(gemini_body),
+    );
+    let gemini_stream = crate::proxy::mappers::stop_sequences::apply_to_stream(gemini_stream, local_stops);
+    let gemini_stream = crate::proxy::middleware::response_rewrite::apply_to_stream(gemini_stream, rewrites);
+    let openai_stream = create_openai_sse_stream(gemini_stream, client_model.to_string());
+    let headers = [("X-Account-Email", account), ("X-Mapped-Model", upstream_model)];
+
+    if client_wants_stream {
+        return Ok((
+            headers,
+            [("Content-Type", "text/event-stream"), ("Cache-Control", "no-cache")],
+            axum::body::Body::from_stream(openai_stream),
+        )
+            .into_response());
+    }
+
+    let sse_stream = openai_stream.map(|result| result.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)));
+    let full_response = collect_openai_stream_to_json(sse_stream)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Stream collection error: {}", e)))?;
+    Ok((StatusCode::OK, headers, Json(full_response)).into_response())
+}
+
 /// 重试策略枚举
 #[derive(Debug, Clone)]
 enum RetryStrategy {
@@ -69,7 +304,7 @@ async fn apply_retry_strategy(strategy: RetryStrategy, attempt: usize, status_co
 
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
-    Json(mut body): Json<Value>,
+    StreamingJson(mut body): StreamingJson<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     // [NEW] 自动检测并转换 Responses 格式
     // 如果请求包含 instructions 或 input 但没有 messages，则认为是 Responses 格式
@@ -120,6 +355,11 @@ pub async fn handle_chat_completions(
         }
     }
 
+    // 模型路由到 OpenAI 兼容上游: 原样转发，不经过 Gemini 转换
+    if let Some(response) = forward_to_openai_backend(&state, &body, "chat/completions").await {
+        return Ok(response);
+    }
+
     let mut openai_req: OpenAIRequest = serde_json::from_value(body)
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
 
@@ -137,6 +377,8 @@ pub async fn handle_chat_completions(
                 tool_calls: None,
                 tool_call_id: None,
                 name: None,
+                prefix: None,
+                images: None,
             });
     }
 
@@ -144,33 +386,113 @@ pub async fn handle_chat_completions(
 
     // 1. 获取 UpstreamClient (Clone handle)
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     let pool_size = token_manager.len();
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
 
     let mut last_error = String::new();
+    let mut reshaped_function_response = false;
     let mut last_email: Option<String> = None;
 
-    for attempt in 0..max_attempts {
-        // 2. 模型路由解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &openai_req.model,
-            &*state.custom_mapping.read().await,
+    // 2. 模型路由解析 (与账号无关，重试间复用)
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        &openai_req.model,
+        &*state.custom_mapping.read().await,
+    );
+    // 模型路由到 Vertex AI (服务账号) 时以 Vertex 模型名参与转换，之后不占用账号池
+    let vertex_config = state.vertex.read().await.clone();
+    let vertex_model = crate::proxy::upstream::vertex::resolve_model(
+        &vertex_config,
+        &openai_req.model,
+        &*state.custom_mapping.read().await,
+    );
+    let ai_studio_model = crate::proxy::upstream::aistudio::resolve_model(
+        &*state.ai_studio.read().await,
+        &openai_req.model,
+        &*state.custom_mapping.read().await,
+    );
+    let mapped_model = vertex_model.clone().or_else(|| ai_studio_model.clone()).unwrap_or(mapped_model);
+    let config = crate::proxy::mappers::common_utils::resolve_request_config(
+        &openai_req.model,
+        &mapped_model,
+        &openai_req.tools,
+    );
+
+    // 3. 提取 SessionId (粘性指纹)
+    let session_id = SessionManager::extract_openai_session_id(&openai_req);
+
+    // 转换请求 (只转换一次，每次重试仅替换 project / requestId)
+    let mut gemini_body = transform_openai_request(&openai_req, "", &mapped_model);
+    let (stream_resume_attempts, json_output, json_reprompt) = {
+        let exp = state.experimental.read().await;
+        crate::proxy::mappers::signature_degradation::apply_signature_degradation(
+            &mut gemini_body,
+            &exp.signature_degradation,
+            &exp.signature_fallback_model,
         );
-        // 将 OpenAI 工具转为 Value 数组以便探测联网
-        let tools_val: Option<Vec<Value>> = openai_req
-            .tools
-            .as_ref()
-            .map(|list| list.iter().cloned().collect());
-        let config = crate::proxy::mappers::common_utils::resolve_request_config(
-            &openai_req.model,
-            &mapped_model,
-            &tools_val,
+        crate::proxy::mappers::function_response::apply_function_response_format(
+            &mut gemini_body,
+            &exp.function_response_rules,
+            exp.function_response_auto_detect,
+            exp.function_response_structured_max_bytes,
         );
+        let json_output = openai_req
+            .response_format
+            .clone()
+            .filter(|f| f.wants_json() && exp.enable_json_output_repair);
+        (exp.stream_resume_max_attempts, json_output, exp.json_output_reprompt)
+    };
+    let local_stops = crate::proxy::mappers::stop_sequences::take_local_stops(&mut gemini_body);
+    let rewrites = crate::proxy::middleware::response_rewrite::rewrites_for(&state, &openai_req.model).await;
+    let image_output =
+        crate::proxy::mappers::image_output::ImageOutput::from_config(&state.experimental.read().await.image_output);
+
+    // [New] 打印转换后的报文摘要；完整报文仅在 trace 级别输出
+    debug!(
+        "[OpenAI-Request] Transformed Gemini Body ({} contents)",
+        gemini_body["request"]["contents"].as_array().map(|a| a.len()).unwrap_or(0)
+    );
+    if tracing::enabled!(tracing::Level::TRACE) {
+        tracing::trace!("[OpenAI-Request] Transformed Gemini Body: {}", gemini_body);
+    }
 
-        // 3. 提取 SessionId (粘性指纹)
-        let session_id = SessionManager::extract_openai_session_id(&openai_req);
+    let capability_backend = if vertex_model.is_some() {
+        "vertex"
+    } else if ai_studio_model.is_some() {
+        "aistudio"
+    } else {
+        crate::proxy::mappers::capabilities::BACKEND_ANTIGRAVITY
+    };
+    crate::proxy::handlers::common::gate_capabilities(&state, &mut gemini_body, capability_backend, &config.final_model)
+        .await?;
+
+    if let Some(vertex_model) = vertex_model {
+        info!("[OpenAI] Routing {} to Vertex AI as {}", openai_req.model, vertex_model);
+        return vertex_chat_completion(
+            &state,
+            &vertex_config,
+            &vertex_model,
+            &gemini_body,
+            local_stops,
+            openai_req.stream,
+            &openai_req.model,
+        )
+        .await;
+    }
+    if let Some(ai_studio_model) = ai_studio_model {
+        info!("[OpenAI] Routing {} to AI Studio API keys as {}", openai_req.model, ai_studio_model);
+        return ai_studio_chat_completion(
+            &state,
+            &ai_studio_model,
+            &gemini_body,
+            local_stops,
+            openai_req.stream,
+            &openai_req.model,
+        )
+        .await;
+    }
 
+    for attempt in 0..max_attempts {
         // 4. 获取 Token (使用准确的 request_type)
         // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
         let (access_token, project_id, email) = match token_manager
@@ -179,6 +501,24 @@ pub async fn handle_chat_completions(
         {
             Ok(t) => t,
             Err(e) => {
+                // OAuth 账号池无可用账号时回退到 AI Studio API Key
+                if state.ai_studio.read().await.fallback
+                    && crate::proxy::upstream::aistudio::supports_model(&config.final_model)
+                    && !token_manager.api_key_accounts().is_empty()
+                {
+                    info!("[OpenAI] No OAuth account available ({}), falling back to AI Studio API keys", e);
+                    crate::proxy::handlers::common::gate_capabilities(&state, &mut gemini_body, "aistudio", &config.final_model)
+                        .await?;
+                    return ai_studio_chat_completion(
+                        &state,
+                        &config.final_model,
+                        &gemini_body,
+                        local_stops,
+                        openai_req.stream,
+                        &openai_req.model,
+                    )
+                    .await;
+                }
                 return Err((
                     StatusCode::SERVICE_UNAVAILABLE,
                     format!("Token error: {}", e),
@@ -189,13 +529,7 @@ pub async fn handle_chat_completions(
         last_email = Some(email.clone());
         info!("✓ Using account: {} (type: {})", email, config.request_type);
 
-        // 4. 转换请求
-        let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
-
-        // [New] 打印转换后的报文 (Gemini Body) 供调试
-        if let Ok(body_json) = serde_json::to_string_pretty(&gemini_body) {
-            debug!("[OpenAI-Request] Transformed Gemini Body:\n{}", body_json);
-        }
+        prepare_attempt_body(&mut gemini_body, &project_id);
 
         // 5. 发送请求 - 自动转换逻辑
         let client_wants_stream = openai_req.stream;
@@ -215,7 +549,7 @@ pub async fn handle_chat_completions(
         let query_string = if actual_stream { Some("alt=sse") } else { None };
 
         let response = match upstream
-            .call_v1_internal(method, &access_token, gemini_body, query_string)
+            .call_v1_internal(method, &access_token, &gemini_body, query_string)
             .await
         {
             Ok(r) => r,
@@ -239,9 +573,24 @@ pub async fn handle_chat_completions(
                 use axum::body::Body;
                 use axum::response::Response;
 
-                let gemini_stream = response.bytes_stream();
-                let openai_stream =
-                    create_openai_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                let gemini_stream = crate::proxy::upstream::resume::resumable_stream(
+                    Box::pin(response.bytes_stream()),
+                    upstream.clone(),
+                    access_token.clone(),
+                    gemini_body.clone(),
+                    stream_resume_attempts,
+                    format!("OpenAI:{}", email),
+                );
+                let gemini_stream = crate::proxy::mappers::prefill::strip_echo_stream(
+                    gemini_stream,
+                    crate::proxy::mappers::prefill::prefill_text(&gemini_body),
+                );
+                let gemini_stream = crate::proxy::mappers::stop_sequences::apply_to_stream(gemini_stream, local_stops.clone());
+                let gemini_stream = crate::proxy::middleware::response_rewrite::apply_to_stream(gemini_stream, rewrites.clone());
+                let gemini_stream = crate::proxy::mappers::image_output::apply_to_stream(gemini_stream, image_output.clone());
+                let pacing_cps = if client_wants_stream { crate::proxy::middleware::stream_pacing::current_cps() } else { 0 };
+                let gemini_stream = crate::proxy::middleware::stream_pacing::pace_stream(gemini_stream, pacing_cps);
+                let openai_stream = create_openai_sse_stream(gemini_stream, openai_req.model.clone());
                 
                 // 判断客户端期望的格式
                 if client_wants_stream {
@@ -270,9 +619,13 @@ pub async fn handle_chat_completions(
                     });
                     
                     match collect_openai_stream_to_json(sse_stream).await {
-                        Ok(full_response) => {
+                        Ok(mut full_response) => {
                             info!("[OpenAI] ✓ Stream collected and converted to JSON");
-                            return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(full_response)).into_response());
+                            let note = match &json_output {
+                                Some(fmt) => enforce_json_output(&mut full_response, fmt, json_reprompt, &upstream, &access_token, &gemini_body).await,
+                                None => None,
+                            };
+                            return Ok(with_notes((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(full_response)).into_response(), note));
                         }
                         Err(e) => {
                             return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Stream collection error: {}", e)));
@@ -281,13 +634,23 @@ pub async fn handle_chat_completions(
                 }
             }
 
-            let gemini_resp: Value = response
+            let mut gemini_resp: Value = response
                 .json()
                 .await
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
-
-            let openai_response = transform_openai_response(&gemini_resp);
-            return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(openai_response)).into_response());
+            if let Some(prefill) = crate::proxy::mappers::prefill::prefill_text(&gemini_body) {
+                crate::proxy::mappers::prefill::strip_echo_in_response(&mut gemini_resp, &prefill);
+            }
+            crate::proxy::mappers::stop_sequences::apply_to_response(&mut gemini_resp, &local_stops);
+            crate::proxy::middleware::response_rewrite::apply_to_response(&mut gemini_resp, &rewrites);
+            crate::proxy::mappers::image_output::apply_to_response(&mut gemini_resp, image_output.as_ref());
+
+            let mut openai_response = transform_openai_response(&gemini_resp);
+            let note = match &json_output {
+                Some(fmt) => enforce_json_output(&mut openai_response, fmt, json_reprompt, &upstream, &access_token, &gemini_body).await,
+                None => None,
+            };
+            return Ok(with_notes((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(openai_response)).into_response(), note));
         }
 
         // 处理特定错误并重试
@@ -296,6 +659,35 @@ pub async fn handle_chat_completions(
         let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status_code));
         last_error = format!("HTTP {}: {}", status_code, error_text);
 
+        // [NEW] project_id 失效时自动走 loadCodeAssist 发现流程修复并重试
+        if crate::proxy::project_resolver::is_project_error(status_code, &error_text) {
+            match token_manager.repair_project_id(&email).await {
+                Ok(new_pid) => {
+                    tracing::warn!("[OpenAI] Project ID for {} was invalid, repaired to {}, retrying", email, new_pid);
+                    continue;
+                }
+                Err(e) => tracing::warn!("[OpenAI] Project ID repair failed for {}: {}", email, e),
+            }
+        }
+
+        // [NEW] 上游拒绝 functionResponse 格式时切换到下一种候选格式并重试一次
+        if !reshaped_function_response {
+            let exp = state.experimental.read().await;
+            let model = gemini_body["model"].as_str().unwrap_or_default().to_string();
+            if exp.function_response_auto_detect
+                && crate::proxy::mappers::function_response::record_rejection(&model, status_code, &error_text)
+            {
+                reshaped_function_response = true;
+                crate::proxy::mappers::function_response::apply_function_response_format(
+                    &mut gemini_body,
+                    &exp.function_response_rules,
+                    true,
+                    exp.function_response_structured_max_bytes,
+                );
+                continue;
+            }
+        }
+
         // [New] 打印错误报文日志
         tracing::error!(
             "[OpenAI-Upstream] Error Response {}: {}",
@@ -384,7 +776,7 @@ pub async fn handle_chat_completions(
 /// 将 Prompt 转换为 Chat Message 格式，复用 handle_chat_completions
 pub async fn handle_completions(
     State(state): State<AppState>,
-    Json(mut body): Json<Value>,
+    StreamingJson(mut body): StreamingJson<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     info!(
         "Received /v1/completions or /v1/responses payload: {:?}",
@@ -393,6 +785,40 @@ pub async fn handle_completions(
 
     let is_codex_style = body.get("input").is_some() || body.get("instructions").is_some();
 
+    // Legacy 补全: prompt 续写 / prompt + suffix 中间填充 (FIM)
+    // 超出 token 预算时先截断 (保留光标附近的上下文)，再按模型解析 FIM 模板；
+    // special-token 模板需在转发到 OpenAI 兼容上游之前展开
+    let mut legacy_prompt = (!is_codex_style && (body.get("prompt").is_some() || body.get("suffix").is_some()))
+        .then(|| LegacyPrompt::from_body(&body));
+    let truncation = match legacy_prompt.as_mut() {
+        Some(Ok(legacy)) => legacy.truncate(&mut body, &state.experimental.read().await.completion_truncation),
+        _ => None,
+    };
+    if let Some(t) = &truncation {
+        info!("[Completions] Prompt truncated: ~{} -> ~{} tokens", t.before, t.after);
+    }
+    let truncated_header = truncation.and_then(|t| axum::http::HeaderValue::from_str(&t.header_value()).ok());
+    if let Some(Ok(legacy)) = legacy_prompt.as_mut().filter(|p| p.as_ref().is_ok_and(|p| p.is_fim())) {
+        let model = body.get("model").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let target = crate::proxy::middleware::token_budget::resolve_target_model(&model, &*state.custom_mapping.read().await);
+        let template = crate::proxy::mappers::openai::completions::resolve_template(
+            &state.experimental.read().await.fim_templates,
+            &[model.as_str(), target.as_str()],
+        );
+        debug!("[Completions] FIM template for {} ({}): {:?}", model, target, template.style);
+        legacy.template = Some(template);
+        legacy.apply_token_template(&mut body);
+    }
+
+    let backend_path = if is_codex_style { "responses" } else { "completions" };
+    if let Some(mut response) = forward_to_openai_backend(&state, &body, backend_path).await {
+        if let Some(v) = &truncated_header {
+            response.headers_mut().insert(PROMPT_TRUNCATED_HEADER, v.clone());
+        }
+        return Ok(response);
+    }
+    let legacy_prompt = legacy_prompt.transpose().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
     // 1. Convert Payload to Messages (Shared Chat Format)
     if is_codex_style {
         let instructions = body
@@ -618,23 +1044,17 @@ pub async fn handle_completions(
         if let Some(obj) = body.as_object_mut() {
             obj.insert("messages".to_string(), json!(messages));
         }
-    } else if let Some(prompt_val) = body.get("prompt") {
-        // Legacy OpenAI Style: prompt -> Chat
-        let prompt_str = match prompt_val {
-            Value::String(s) => s.clone(),
-            Value::Array(arr) => arr
-                .iter()
-                .filter_map(|v| v.as_str())
-                .collect::<Vec<_>>()
-                .join("\n"),
-            _ => prompt_val.to_string(),
-        };
-        let messages = json!([ { "role": "user", "content": prompt_str } ]);
+    } else if let Some(legacy) = &legacy_prompt {
+        // Legacy OpenAI Style: prompt -> 预填充续写 / FIM 模板消息
         if let Some(obj) = body.as_object_mut() {
-            obj.remove("prompt");
-            obj.insert("messages".to_string(), messages);
+            for key in ["prompt", "suffix", "echo"] {
+                obj.remove(key);
+            }
+            obj.insert("messages".to_string(), json!(legacy.to_messages()));
         }
     }
+    let echo_text = legacy_prompt.as_ref().and_then(|p| p.echo_text());
+    let clean_fim = legacy_prompt.as_ref().is_some_and(|p| p.is_instruction_fim());
 
     // 2. Reuse handle_chat_completions logic (wrapping with custom handler or direct call)
     // Actually, due to SSE handling differences (Codex uses different event format), we replicate the loop here or abstract it.
@@ -704,41 +1124,69 @@ pub async fn handle_completions(
                 tool_calls: None,
                 tool_call_id: None,
                 name: None,
+                prefix: None,
+                images: None,
             });
     }
 
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     let pool_size = token_manager.len();
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
 
     let mut last_error = String::new();
+    let mut reshaped_function_response = false;
 
     let mut last_email: Option<String> = None;
     let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
 
-    for attempt in 0..max_attempts {
-        // 1. 模型路由解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &openai_req.model,
-            &*state.custom_mapping.read().await,
+    // 1. 模型路由解析 (与账号无关，重试间复用)
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        &openai_req.model,
+        &*state.custom_mapping.read().await,
+    );
+    let config = crate::proxy::mappers::common_utils::resolve_request_config(
+        &openai_req.model,
+        &mapped_model,
+        &openai_req.tools,
+    );
+
+    // 3. 提取 SessionId (复用)
+    // [New] 使用 TokenManager 内部逻辑提取 session_id，支持粘性调度
+    let session_id_str = SessionManager::extract_openai_session_id(&openai_req);
+    let session_id = Some(session_id_str.as_str());
+
+    // 转换请求 (只转换一次，每次重试仅替换 project / requestId)
+    let mut gemini_body = transform_openai_request(&openai_req, "", &mapped_model);
+    {
+        let exp = state.experimental.read().await;
+        crate::proxy::mappers::signature_degradation::apply_signature_degradation(
+            &mut gemini_body,
+            &exp.signature_degradation,
+            &exp.signature_fallback_model,
         );
-        // 将 OpenAI 工具转为 Value 数组以便探测联网
-        let tools_val: Option<Vec<Value>> = openai_req
-            .tools
-            .as_ref()
-            .map(|list| list.iter().cloned().collect());
-        let config = crate::proxy::mappers::common_utils::resolve_request_config(
-            &openai_req.model,
-            &mapped_model,
-            &tools_val,
+        crate::proxy::mappers::function_response::apply_function_response_format(
+            &mut gemini_body,
+            &exp.function_response_rules,
+            exp.function_response_auto_detect,
+            exp.function_response_structured_max_bytes,
         );
+    }
+    crate::proxy::handlers::common::gate_capabilities(
+        &state,
+        &mut gemini_body,
+        crate::proxy::mappers::capabilities::BACKEND_ANTIGRAVITY,
+        &config.final_model,
+    )
+    .await?;
 
-        // 3. 提取 SessionId (复用)
-        // [New] 使用 TokenManager 内部逻辑提取 session_id，支持粘性调度
-        let session_id_str = SessionManager::extract_openai_session_id(&openai_req);
-        let session_id = Some(session_id_str.as_str());
-        
+    let rewrites = crate::proxy::middleware::response_rewrite::rewrites_for(&state, &openai_req.model).await;
+
+    // [New] 打印转换后的报文 (Gemini Body) 供调试 (Codex 路径) ———— 缩减为 simple debug
+    debug!("[Codex-Request] Transformed Gemini Body ({} parts)", 
+       gemini_body["request"]["contents"].as_array().map(|a| a.len()).unwrap_or(0));
+
+    for attempt in 0..max_attempts {
         // 重试时强制轮换，除非只是简单的网络抖动但 Claude 逻辑里 attempt > 0 总是 force_rotate
         let force_rotate = attempt > 0;
 
@@ -757,11 +1205,7 @@ pub async fn handle_completions(
 
         info!("✓ Using account: {} (type: {})", email, config.request_type);
 
-        let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
-
-        // [New] 打印转换后的报文 (Gemini Body) 供调试 (Codex 路径) ———— 缩减为 simple debug
-        debug!("[Codex-Request] Transformed Gemini Body ({} parts)", 
-           gemini_body.get("contents").and_then(|c| c.as_array()).map(|a| a.len()).unwrap_or(0));
+        prepare_attempt_body(&mut gemini_body, &project_id);
 
         let list_response = openai_req.stream;
         let method = if list_response {
@@ -772,7 +1216,7 @@ pub async fn handle_completions(
         let query_string = if list_response { Some("alt=sse") } else { None };
 
         let response = match upstream
-            .call_v1_internal(method, &access_token, gemini_body, query_string)
+            .call_v1_internal(method, &access_token, &gemini_body, query_string)
             .await
         {
             Ok(r) => r,
@@ -792,20 +1236,21 @@ pub async fn handle_completions(
                 use axum::body::Body;
                 use axum::response::Response;
 
-                let gemini_stream = response.bytes_stream();
+                let gemini_stream = crate::proxy::middleware::response_rewrite::apply_to_stream(
+                    Box::pin(response.bytes_stream()),
+                    rewrites.clone(),
+                );
                 let body = if is_codex_style {
                     use crate::proxy::mappers::openai::streaming::create_codex_sse_stream;
-                    let s =
-                        create_codex_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                    let s = create_codex_sse_stream(gemini_stream, openai_req.model.clone());
                     Body::from_stream(s)
                 } else {
                     use crate::proxy::mappers::openai::streaming::create_legacy_sse_stream;
-                    let s =
-                        create_legacy_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                    let s = create_legacy_sse_stream(gemini_stream, openai_req.model.clone(), echo_text.clone());
                     Body::from_stream(s)
                 };
 
-                return Ok(Response::builder()
+                let mut response = Response::builder()
                     .header("Content-Type", "text/event-stream")
                     .header("Cache-Control", "no-cache")
                     .header("Connection", "keep-alive")
@@ -813,23 +1258,35 @@ pub async fn handle_completions(
                     .header("X-Mapped-Model", &mapped_model)
                     .body(body)
                     .unwrap()
-                    .into_response());
+                    .into_response();
+                if let Some(v) = &truncated_header {
+                    response.headers_mut().insert(PROMPT_TRUNCATED_HEADER, v.clone());
+                }
+                return Ok(response);
             }
 
-            let gemini_resp: Value = response
+            let mut gemini_resp: Value = response
                 .json()
                 .await
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+            crate::proxy::middleware::response_rewrite::apply_to_response(&mut gemini_resp, &rewrites);
 
             let chat_resp = transform_openai_response(&gemini_resp);
 
             // Map Chat Response -> Legacy Completions Response
             let choices = chat_resp.choices.iter().map(|c| {
+                let mut text = match &c.message.content {
+                    Some(crate::proxy::mappers::openai::OpenAIContent::String(s)) => s.clone(),
+                    _ => "".to_string()
+                };
+                if clean_fim {
+                    text = clean_fim_output(&text);
+                }
+                if let Some(echo) = &echo_text {
+                    text.insert_str(0, echo);
+                }
                 json!({
-                    "text": match &c.message.content {
-                        Some(crate::proxy::mappers::openai::OpenAIContent::String(s)) => s.clone(),
-                        _ => "".to_string()
-                    },
+                    "text": text,
                     "index": c.index,
                     "logprobs": null,
                     "finish_reason": c.finish_reason
@@ -841,10 +1298,15 @@ pub async fn handle_completions(
                 "object": "text_completion",
                 "created": chat_resp.created,
                 "model": chat_resp.model,
-                "choices": choices
+                "choices": choices,
+                "usage": chat_resp.usage
             });
 
-            return Ok(axum::Json(legacy_resp).into_response());
+            let mut response = axum::Json(legacy_resp).into_response();
+            if let Some(v) = &truncated_header {
+                response.headers_mut().insert(PROMPT_TRUNCATED_HEADER, v.clone());
+            }
+            return Ok(response);
         }
 
         // Handle errors and retry
@@ -853,6 +1315,35 @@ pub async fn handle_completions(
         let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status_code));
         last_error = format!("HTTP {}: {}", status_code, error_text);
 
+        // [NEW] project_id 失效时自动走 loadCodeAssist 发现流程修复并重试
+        if crate::proxy::project_resolver::is_project_error(status_code, &error_text) {
+            match token_manager.repair_project_id(&email).await {
+                Ok(new_pid) => {
+                    tracing::warn!("[OpenAI] Project ID for {} was invalid, repaired to {}, retrying", email, new_pid);
+                    continue;
+                }
+                Err(e) => tracing::warn!("[OpenAI] Project ID repair failed for {}: {}", email, e),
+            }
+        }
+
+        // [NEW] 上游拒绝 functionResponse 格式时切换到下一种候选格式并重试一次
+        if !reshaped_function_response {
+            let exp = state.experimental.read().await;
+            let model = gemini_body["model"].as_str().unwrap_or_default().to_string();
+            if exp.function_response_auto_detect
+                && crate::proxy::mappers::function_response::record_rejection(&model, status_code, &error_text)
+            {
+                reshaped_function_response = true;
+                crate::proxy::mappers::function_response::apply_function_response_format(
+                    &mut gemini_body,
+                    &exp.function_response_rules,
+                    true,
+                    exp.function_response_structured_max_bytes,
+                );
+                continue;
+            }
+        }
+
         tracing::error!(
             "[Codex-Upstream] Error Response {}: {}",
             status_code,
@@ -897,15 +1388,33 @@ pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoRespo
     let model_ids = get_all_dynamic_models(
         &state.custom_mapping,
     ).await;
+    let backend_models = crate::proxy::providers::openai_compat::listed_models(&state.openai_backends.read().await);
 
-    let data: Vec<_> = model_ids.into_iter().map(|id| {
-        json!({
+    let mut data: Vec<_> = model_ids.into_iter().map(|id| {
+        // 上下文窗口与最大输出来自模型登记表 (非标准字段，未登记时省略)
+        let limits = crate::proxy::mappers::model_limits::resolve_listed(&id);
+        let mut model = json!({
             "id": id,
             "object": "model",
             "created": 1706745600,
             "owned_by": "antigravity"
-        })
+        });
+        if let Some(context_window) = limits.context_window {
+            model["context_window"] = json!(context_window);
+        }
+        if let Some(max_output_tokens) = limits.max_output_tokens {
+            model["max_output_tokens"] = json!(max_output_tokens);
+        }
+        model
     }).collect();
+    data.extend(backend_models.into_iter().map(|id| {
+        json!({
+            "id": id,
+            "object": "model",
+            "created": 1706745600,
+            "owned_by": "openai-compat"
+        })
+    }));
 
     Json(json!({
         "object": "list",
@@ -931,11 +1440,19 @@ pub async fn handle_images_generations(
         .unwrap_or("gemini-3-pro-image");
 
     let n = body.get("n").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
+    if !(1..=MAX_IMAGES_PER_REQUEST).contains(&n) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("'n' must be between 1 and {}", MAX_IMAGES_PER_REQUEST),
+        ));
+    }
 
-    let size = body
-        .get("size")
-        .and_then(|v| v.as_str())
-        .unwrap_or("1024x1024");
+    // size / aspect_ratio / image_size / negative_prompt (厂商扩展字段)
+    let options: ImageGenOptions = serde_json::from_value(body.clone())
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid image options: {}", e)))?;
+    options.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    // 指定 seed 时第 i 张图使用 seed + i，结果可复现且各不相同
+    let seed = body.get("seed").and_then(|v| v.as_i64());
 
     let response_format = body
         .get("response_format")
@@ -951,26 +1468,21 @@ pub async fn handle_images_generations(
         .and_then(|v| v.as_str())
         .unwrap_or("vivid");
 
+    // 2. 模型后缀 + 显式参数 -> imageConfig
+    let image_config = options.image_config(model, true);
+    let upstream_model = image_model(model);
+
     info!(
-        "[Images] Received request: model={}, prompt={:.50}..., n={}, size={}, quality={}, style={}",
+        "[Images] Received request: model={}, prompt={:.50}..., n={}, image_config={}, seed={:?}, quality={}, style={}",
         model,
         prompt,
         n,
-        size,
+        image_config,
+        seed,
         quality,
         style
     );
 
-    // 2. 解析尺寸为宽高比
-    let aspect_ratio = match size {
-        "1792x768" | "2560x1080" => "21:9", // Ultra-wide
-        "1792x1024" | "1920x1080" => "16:9",
-        "1024x1792" | "1080x1920" => "9:16",
-        "1024x768" | "1280x960" => "4:3",
-        "768x1024" | "960x1280" => "3:4",
-        _ => "1:1", // 默认 1024x1024
-    };
-
     // Prompt Enhancement
     let mut final_prompt = prompt.to_string();
     if quality == "hd" {
@@ -981,6 +1493,10 @@ pub async fn handle_images_generations(
         "natural" => final_prompt.push_str(", (natural lighting, realistic, photorealistic)"),
         _ => {}
     }
+    if let Some(negative) = options.negative_prompt_text() {
+        final_prompt.push_str(". ");
+        final_prompt.push_str(&negative);
+    }
 
     // 3. 获取 Token
     let upstream = state.upstream.clone();
@@ -1002,19 +1518,25 @@ pub async fn handle_images_generations(
     // 4. 并发发送请求 (解决 candidateCount > 1 不支持的问题)
     let mut tasks = Vec::new();
 
-    for _ in 0..n {
+    for i in 0..n {
         let upstream = upstream.clone();
         let access_token = access_token.clone();
         let project_id = project_id.clone();
         let final_prompt = final_prompt.clone();
-        let aspect_ratio = aspect_ratio.to_string();
-        let _response_format = response_format.to_string();
+        let upstream_model = upstream_model.clone();
+        let mut generation_config = json!({
+            "candidateCount": 1, // 强制单张
+            "imageConfig": image_config.clone()
+        });
+        if let Some(seed) = seed {
+            generation_config["seed"] = json!(seed.wrapping_add(i as i64));
+        }
 
         tasks.push(tokio::spawn(async move {
             let gemini_body = json!({
                 "project": project_id,
                 "requestId": format!("img-{}", uuid::Uuid::new_v4()),
-                "model": "gemini-3-pro-image",
+                "model": upstream_model,
                 "userAgent": "antigravity",
                 "requestType": "image_gen",
                 "request": {
@@ -1022,12 +1544,7 @@ pub async fn handle_images_generations(
                         "role": "user",
                         "parts": [{"text": final_prompt}]
                     }],
-                    "generationConfig": {
-                        "candidateCount": 1, // 强制单张
-                        "imageConfig": {
-                            "aspectRatio": aspect_ratio
-                        }
-                    },
+                    "generationConfig": generation_config,
                     "safetySettings": [
                         { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
                         { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
@@ -1078,19 +1595,24 @@ pub async fn handle_images_generations(
                             if let Some(img) = part.get("inlineData") {
                                 let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
                                 if !data.is_empty() {
-                                    if response_format == "url" {
+                                    let mut item = if response_format == "url" {
                                         let mime_type = img
                                             .get("mimeType")
                                             .and_then(|v| v.as_str())
                                             .unwrap_or("image/png");
-                                        images.push(json!({
+                                        json!({
                                             "url": format!("data:{};base64,{}", mime_type, data)
-                                        }));
+                                        })
                                     } else {
-                                        images.push(json!({
+                                        json!({
                                             "b64_json": data
-                                        }));
+                                        })
+                                    };
+                                    // 返回实际使用的 seed，便于复现单张结果
+                                    if let Some(seed) = seed {
+                                        item["seed"] = json!(seed.wrapping_add(idx as i64));
                                     }
+                                    images.push(item);
                                     tracing::debug!("[Images] Task {} succeeded", idx);
                                 }
                             }
@@ -1149,20 +1671,99 @@ pub async fn handle_images_generations(
     ).into_response())
 }
 
-pub async fn handle_images_edits(
-    State(state): State<AppState>,
-    mut multipart: axum::extract::Multipart,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    tracing::info!("[Images] Received edit request");
+/// 图像编辑的输入图片 (base64 + MIME)
+#[derive(Debug, Clone, PartialEq)]
+struct EditImage {
+    mime_type: String,
+    data: String,
+}
+
+/// /v1/images/edits 参数 (multipart 与 JSON 两种请求体统一到这里)
+#[derive(Debug)]
+struct ImageEditParams {
+    images: Vec<EditImage>,
+    mask: Option<EditImage>,
+    prompt: String,
+    n: usize,
+    options: ImageGenOptions,
+    response_format: String,
+    model: String,
+}
+
+impl Default for ImageEditParams {
+    fn default() -> Self {
+        Self {
+            images: Vec::new(),
+            mask: None,
+            prompt: String::new(),
+            n: 1,
+            options: ImageGenOptions::default(),
+            response_format: "b64_json".to_string(), // Default to b64_json for better compatibility with tools handling edits
+            model: "gemini-3-pro-image".to_string(),
+        }
+    }
+}
+
+impl EditImage {
+    /// 类型按载荷魔数判断；无法识别时使用声明的 image/* 类型，否则按 PNG 处理
+    fn new(declared: Option<&str>, data: String) -> Self {
+        let declared = declared.filter(|m| m.starts_with("image/"));
+        let mime_type = crate::proxy::common::mime_sniff::resolve(declared, &data, "image/png");
+        Self { mime_type, data }
+    }
+}
 
-    let mut image_data = None;
-    let mut mask_data = None;
-    let mut prompt = String::new();
-    let mut n = 1;
-    let mut size = "1024x1024".to_string();
-    let mut response_format = "b64_json".to_string(); // Default to b64_json for better compatibility with tools handling edits
-    let mut model = "gemini-3-pro-image".to_string();
+/// JSON 请求体中的图片: data URL 或裸 base64
+fn decode_edit_image(value: &str) -> Result<EditImage, String> {
+    let value = value.trim();
+    let (declared, data) = match value.strip_prefix("data:") {
+        Some(rest) => {
+            let (meta, data) = rest.split_once(',').ok_or("Invalid data URL")?;
+            let mime = meta.strip_suffix(";base64").ok_or("Data URL must be base64-encoded")?;
+            (Some(mime), data)
+        }
+        None => (None, value),
+    };
+    let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    base64::engine::general_purpose::STANDARD
+        .decode(&data)
+        .map_err(|e| format!("Invalid base64 image: {}", e))?;
+    Ok(EditImage::new(declared, data))
+}
 
+fn parse_edit_json(body: &Value) -> Result<ImageEditParams, String> {
+    let mut params = ImageEditParams::default();
+    match body.get("image") {
+        Some(Value::String(s)) => params.images.push(decode_edit_image(s)?),
+        Some(Value::Array(items)) => {
+            for item in items {
+                let s = item.as_str().ok_or("image array must contain strings")?;
+                params.images.push(decode_edit_image(s)?);
+            }
+        }
+        _ => {}
+    }
+    if let Some(mask) = body.get("mask").and_then(|v| v.as_str()) {
+        params.mask = Some(decode_edit_image(mask)?);
+    }
+    if let Some(prompt) = body.get("prompt").and_then(|v| v.as_str()) {
+        params.prompt = prompt.to_string();
+    }
+    if let Some(n) = body.get("n").and_then(|v| v.as_u64()) {
+        params.n = n as usize;
+    }
+    params.options = serde_json::from_value(body.clone()).map_err(|e| format!("Invalid image options: {}", e))?;
+    if let Some(format) = body.get("response_format").and_then(|v| v.as_str()) {
+        params.response_format = format.to_string();
+    }
+    if let Some(model) = body.get("model").and_then(|v| v.as_str()).filter(|m| !m.is_empty()) {
+        params.model = model.to_string();
+    }
+    Ok(params)
+}
+
+async fn parse_edit_multipart(mut multipart: axum::extract::Multipart) -> Result<ImageEditParams, (StatusCode, String)> {
+    let mut params = ImageEditParams::default();
     while let Some(field) = multipart
         .next_field()
         .await
@@ -1170,72 +1771,141 @@ pub async fn handle_images_edits(
     {
         let name = field.name().unwrap_or("").to_string();
 
-        if name == "image" {
+        if name == "image" || name == "image[]" {
+            let declared = field.content_type().map(|m| m.to_string());
             let data = field
                 .bytes()
                 .await
                 .map_err(|e| (StatusCode::BAD_REQUEST, format!("Image read error: {}", e)))?;
-            image_data = Some(base64::engine::general_purpose::STANDARD.encode(data));
+            params.images.push(EditImage::new(declared.as_deref(), base64::engine::general_purpose::STANDARD.encode(data)));
         } else if name == "mask" {
+            let declared = field.content_type().map(|m| m.to_string());
             let data = field
                 .bytes()
                 .await
                 .map_err(|e| (StatusCode::BAD_REQUEST, format!("Mask read error: {}", e)))?;
-            mask_data = Some(base64::engine::general_purpose::STANDARD.encode(data));
+            params.mask = Some(EditImage::new(declared.as_deref(), base64::engine::general_purpose::STANDARD.encode(data)));
         } else if name == "prompt" {
-            prompt = field
+            params.prompt = field
                 .text()
                 .await
                 .map_err(|e| (StatusCode::BAD_REQUEST, format!("Prompt read error: {}", e)))?;
         } else if name == "n" {
             if let Ok(val) = field.text().await {
-                n = val.parse().unwrap_or(1);
+                params.n = val.parse().unwrap_or(1);
             }
-        } else if name == "size" {
+        } else if matches!(name.as_str(), "size" | "aspect_ratio" | "image_size" | "negative_prompt") {
             if let Ok(val) = field.text().await {
-                size = val;
+                let slot = match name.as_str() {
+                    "size" => &mut params.options.size,
+                    "aspect_ratio" => &mut params.options.aspect_ratio,
+                    "image_size" => &mut params.options.image_size,
+                    _ => &mut params.options.negative_prompt,
+                };
+                *slot = Some(val);
             }
         } else if name == "response_format" {
             if let Ok(val) = field.text().await {
-                response_format = val;
+                params.response_format = val;
             }
         } else if name == "model" {
             if let Ok(val) = field.text().await {
                 if !val.is_empty() {
-                    model = val;
+                    params.model = val;
                 }
             }
         }
     }
+    Ok(params)
+}
+
+/// 图像端点的上游模型: gemini-3-pro-image 的后缀变体归一为基础模型，
+/// OpenAI 客户端常带的 dall-e-3 / gpt-image-1 等模型名回落到 gemini-3-pro-image
+fn image_model(model: &str) -> String {
+    let mapped = if model.starts_with("gemini-") && model.contains("image") { model } else { "gemini-3-pro-image" };
+    crate::proxy::mappers::common_utils::resolve_request_config(model, mapped, &None).final_model
+}
+
+/// 复用模型后缀的 image_config 解析 (-2k / -4k / -16x9 ...)，显式参数 (size / aspect_ratio / image_size) 优先。
+/// 均未指定宽高比时不发送 aspectRatio，由上游沿用原图比例，避免非方形图片被裁成 1:1。
+fn edit_image_config(model: &str, options: &ImageGenOptions) -> (Value, String) {
+    (options.image_config(model, false), image_model(model))
+}
+
+fn edit_parts(params: &ImageEditParams) -> Vec<Value> {
+    let mut prompt = format!("Edit this image: {}", params.prompt);
+    if params.images.len() > 1 {
+        prompt = format!("Edit these images: {}", params.prompt);
+    }
+    if params.mask.is_some() {
+        prompt.push_str("\nThe last image is a mask: only change the regions where the mask is transparent and keep everything else unchanged.");
+    }
+    if let Some(negative) = params.options.negative_prompt_text() {
+        prompt.push('\n');
+        prompt.push_str(&negative);
+    }
+    let mut parts = vec![json!({ "text": prompt })];
+    for image in params.images.iter().chain(params.mask.iter()) {
+        parts.push(json!({
+            "inlineData": {
+                "mimeType": image.mime_type,
+                "data": image.data
+            }
+        }));
+    }
+    parts
+}
+
+/// POST /v1/images/edits —— 接受 multipart/form-data (OpenAI 标准) 或 JSON (base64 / data URL)
+pub async fn handle_images_edits(
+    State(state): State<AppState>,
+    request: axum::extract::Request,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    use axum::extract::FromRequest;
+    tracing::info!("[Images] Received edit request");
 
-    if image_data.is_none() {
+    let is_multipart = request
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.starts_with("multipart/form-data"))
+        .unwrap_or(false);
+    let params = if is_multipart {
+        let multipart = axum::extract::Multipart::from_request(request, &state)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Multipart error: {}", e)))?;
+        parse_edit_multipart(multipart).await?
+    } else {
+        let bytes = axum::body::to_bytes(request.into_body(), state.max_body_bytes)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Failed to read request body: {}", e)))?;
+        let body: Value = serde_json::from_slice(&bytes)
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid JSON body: {}", e)))?;
+        parse_edit_json(&body).map_err(|e| (StatusCode::BAD_REQUEST, e))?
+    };
+
+    if params.images.is_empty() {
         return Err((StatusCode::BAD_REQUEST, "Missing image".to_string()));
     }
-    if prompt.is_empty() {
+    if params.prompt.is_empty() {
         return Err((StatusCode::BAD_REQUEST, "Missing prompt".to_string()));
     }
+    params.options.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let n = params.n.max(1);
+    let response_format = params.response_format.clone();
+    let (image_config, model) = edit_image_config(&params.model, &params.options);
 
     tracing::info!(
-        "[Images] Edit Request: model={}, prompt={}, n={}, size={}, mask={}, response_format={}",
-        model,
-        prompt,
+        "[Images] Edit Request: model={}, prompt={}, n={}, images={}, image_config={}, mask={}, response_format={}",
+        params.model,
+        params.prompt,
         n,
-        size,
-        mask_data.is_some(),
+        params.images.len(),
+        image_config,
+        params.mask.is_some(),
         response_format
     );
 
-    // FIX: Client Display Issue
-    // Cherry Studio (and potentially others) might accept Data URI for generations but display raw text for edits
-    // if 'url' format is used with a data-uri.
-    // If request asks for 'url' but we are a local proxy, returning b64_json is often safer for correct rendering if the client supports it.
-    // However, strictly following spec means 'url' should be 'url'.
-    // Let's rely on client requesting the right thing, BUT allow a server-side heuristic:
-    // If we simply return b64_json structure even if url was requested? No, that breaks spec.
-    // Instead, let's assume successful clients request b64_json.
-    // But if users see raw text, it means client defaulted to 'url' or we defaulted to 'url'.
-    // Let's keep the log to confirm.
-
     // 1. 获取 Upstream
     let upstream = state.upstream.clone();
     let token_manager = state.token_manager;
@@ -1251,32 +1921,18 @@ pub async fn handle_images_edits(
         }
     };
 
-    // 2. 映射配置
-    let mut contents_parts = Vec::new();
-
-    contents_parts.push(json!({
-        "text": format!("Edit this image: {}", prompt)
-    }));
-
-    if let Some(data) = image_data {
-        contents_parts.push(json!({
-            "inlineData": {
-                "mimeType": "image/png",
-                "data": data
-            }
-        }));
-    }
-
-    if let Some(data) = mask_data {
-        contents_parts.push(json!({
-            "inlineData": {
-                "mimeType": "image/png",
-                "data": data
-            }
-        }));
+    // 2. 构造 Gemini 内网 API Body (Envelope Structure)
+    let mut generation_config = json!({
+        "candidateCount": 1,
+        "maxOutputTokens": crate::proxy::mappers::model_limits::resolve(&model).max_output_tokens.unwrap_or(8192),
+        "stopSequences": [],
+        "temperature": 1.0,
+        "topP": 0.95,
+        "topK": 40
+    });
+    if image_config.as_object().map_or(false, |o| !o.is_empty()) {
+        generation_config["imageConfig"] = image_config;
     }
-
-    // 构造 Gemini 内网 API Body (Envelope Structure)
     let gemini_body = json!({
         "project": project_id,
         "requestId": format!("img-edit-{}", uuid::Uuid::new_v4()),
@@ -1286,16 +1942,9 @@ pub async fn handle_images_edits(
         "request": {
             "contents": [{
                 "role": "user",
-                "parts": contents_parts
+                "parts": edit_parts(&params)
             }],
-            "generationConfig": {
-                "candidateCount": 1,
-                "maxOutputTokens": 8192,
-                "stopSequences": [],
-                "temperature": 1.0,
-                "topP": 0.95,
-                "topK": 40
-            },
+            "generationConfig": generation_config,
             "safetySettings": [
                 { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
                 { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
@@ -1424,3 +2073,49 @@ pub async fn handle_images_edits(
         Json(openai_response)
     ).into_response())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_edit_json_accepts_data_urls_and_base64() {
+        let body = json!({
+            "image": ["data:image/jpeg;base64,/9j/4AAQ", "iVBORw0KGgo="],
+            "mask": "iVBORw0KGgo=",
+            "prompt": "add a hat",
+            "n": 2,
+            "size": "1792x1024"
+        });
+        let params = parse_edit_json(&body).unwrap();
+        assert_eq!(params.images.len(), 2);
+        assert_eq!(params.images[0], EditImage { mime_type: "image/jpeg".to_string(), data: "/9j/4AAQ".to_string() });
+        assert_eq!(params.images[1].mime_type, "image/png");
+        // 类型标错时按魔数更正
+        assert_eq!(decode_edit_image("data:image/jpeg;base64,iVBORw0KGgo=").unwrap().mime_type, "image/png");
+        assert_eq!(params.response_format, "b64_json");
+        assert_eq!(params.n, 2);
+        assert_eq!(edit_parts(&params).len(), 4);
+
+        assert!(parse_edit_json(&json!({ "image": "not base64!" })).is_err());
+        assert!(parse_edit_json(&json!({ "image": "data:image/png,raw" })).is_err());
+    }
+
+    #[test]
+    fn test_edit_image_config() {
+        let none = ImageGenOptions::default();
+        let (config, model) = edit_image_config("gemini-3-pro-image-2k", &none);
+        assert_eq!(model, "gemini-3-pro-image");
+        assert_eq!(config, json!({ "imageSize": "2K" }));
+
+        let tall = ImageGenOptions { size: Some("1024x1792".to_string()), ..Default::default() };
+        let (config, _) = edit_image_config("gemini-3-pro-image-16x9", &tall);
+        assert_eq!(config["aspectRatio"], "9:16");
+
+        let auto = ImageGenOptions { size: Some("auto".to_string()), ..Default::default() };
+        let (config, model) = edit_image_config("dall-e-2", &auto);
+        assert_eq!(model, "gemini-3-pro-image");
+        assert_eq!(config, json!({}));
+        assert_eq!(edit_image_config("gemini-3-pro-image-21x9", &none).0["aspectRatio"], "21:9");
+    }
+}
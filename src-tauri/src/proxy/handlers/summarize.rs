@@ -0,0 +1,409 @@
+// 长文本摘要 (/v1/summarize，非标准端点)
+// 输入任意长度的文本 -> 按模型上下文切块 -> 各块并行摘要 (map，按账号数并发) ->
+// 合并摘要 (reduce，超长时分组多轮合并) -> 最后一轮以 OpenAI chat.completion(.chunk) 格式返回/流式输出。
+// 每次模型调用都在进程内走 chat completions handler，复用账号轮换/重试/协议转换。
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Json, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::proxy::batch::execute_with_backoff;
+use crate::proxy::common::http::invalid_request;
+use crate::proxy::common::streaming_json::StreamingJson;
+use crate::proxy::middleware::best_of::{add_usage, choices_of, content_text};
+use crate::proxy::server::AppState;
+
+const DEFAULT_SUMMARIZE_MODEL: &str = "gemini-2.5-flash";
+/// 默认每块 token 数 (为提示词和输出预留足够余量)
+const DEFAULT_CHUNK_TOKENS: usize = 100_000;
+const MIN_CHUNK_TOKENS: usize = 1_000;
+/// 估算用的字符/token 比例 (偏保守，兼顾中日韩文本)
+const CHARS_PER_TOKEN: usize = 3;
+/// map 阶段并发上限 (实际并发 = min(账号数, 上限))
+const MAX_CONCURRENCY: usize = 8;
+/// 多轮 reduce 的轮数上限，防止摘要无法收敛时无限循环
+const MAX_REDUCE_ROUNDS: usize = 4;
+
+pub const SUMMARY_CHUNKS_HEADER: &str = "x-summary-chunks";
+
+struct SummarizeOptions {
+    model: String,
+    instructions: Option<String>,
+    chunk_chars: usize,
+    stream: bool,
+}
+
+/// input 为字符串或字符串数组 (多个文档以空行拼接)
+fn parse_input(input: &Value) -> Result<String, String> {
+    let text = match input {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(|i| i.as_str().map(|s| s.to_string()).ok_or("input array must contain strings only"))
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n\n"),
+        _ => return Err("input must be a string or an array of strings".to_string()),
+    };
+    if text.trim().is_empty() {
+        return Err("input must not be empty".to_string());
+    }
+    Ok(text)
+}
+
+fn parse_options(body: &Value) -> SummarizeOptions {
+    let chunk_tokens = body
+        .get("chunk_tokens")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize)
+        .unwrap_or(DEFAULT_CHUNK_TOKENS)
+        .max(MIN_CHUNK_TOKENS);
+    SummarizeOptions {
+        model: body
+            .get("model")
+            .and_then(|m| m.as_str())
+            .filter(|m| !m.is_empty())
+            .unwrap_or(DEFAULT_SUMMARIZE_MODEL)
+            .to_string(),
+        instructions: body
+            .get("instructions")
+            .and_then(|i| i.as_str())
+            .filter(|i| !i.trim().is_empty())
+            .map(|i| i.to_string()),
+        chunk_chars: chunk_tokens * CHARS_PER_TOKEN,
+        stream: body.get("stream").and_then(|s| s.as_bool()).unwrap_or(true),
+    }
+}
+
+/// 按段落 -> 行 -> 字符的优先级切分，贪心装箱到不超过 `max_chars` 的块
+fn chunk_text(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut pieces: Vec<String> = Vec::new();
+    for paragraph in text.split_inclusive("\n\n") {
+        if paragraph.chars().count() <= max_chars {
+            pieces.push(paragraph.to_string());
+            continue;
+        }
+        for line in paragraph.split_inclusive('\n') {
+            let chars: Vec<char> = line.chars().collect();
+            pieces.extend(chars.chunks(max_chars).map(|c| c.iter().collect::<String>()));
+        }
+    }
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for piece in pieces {
+        let len = piece.chars().count();
+        if current_chars + len > max_chars && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        current.push_str(&piece);
+        current_chars += len;
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn focus_line(instructions: &Option<String>) -> String {
+    instructions
+        .as_ref()
+        .map(|i| format!("\nAdditional instructions: {}", i))
+        .unwrap_or_default()
+}
+
+fn map_prompt(chunk: &str, index: usize, total: usize, instructions: &Option<String>) -> String {
+    format!(
+        "You are summarizing part {} of {} of a longer document. Write a dense summary of this part that keeps \
+         all key facts, names, numbers, decisions and open questions. Do not add commentary about the document \
+         being split.{}\n\n<document_part>\n{}\n</document_part>",
+        index + 1,
+        total,
+        focus_line(instructions),
+        chunk
+    )
+}
+
+fn reduce_prompt(summaries: &[String], final_round: bool, instructions: &Option<String>) -> String {
+    let joined = summaries
+        .iter()
+        .enumerate()
+        .map(|(i, s)| format!("<summary part=\"{}\">\n{}\n</summary>", i + 1, s.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let task = if final_round {
+        "Merge the following partial summaries of one document into a single coherent summary. \
+         Remove repetition and keep the original order of topics."
+    } else {
+        "Merge the following consecutive partial summaries of one document into one dense summary \
+         that keeps all key facts, names and numbers."
+    };
+    format!("{}{}\n\n{}", task, focus_line(instructions), joined)
+}
+
+/// 直接摘要整篇 (只有一块时)
+fn single_prompt(text: &str, instructions: &Option<String>) -> String {
+    format!(
+        "Summarize the following document. Keep key facts, names, numbers and decisions.{}\n\n<document>\n{}\n</document>",
+        focus_line(instructions),
+        text
+    )
+}
+
+fn chat_body(model: &str, prompt: String, stream: bool) -> Value {
+    json!({ "model": model, "stream": stream, "messages": [{ "role": "user", "content": prompt }] })
+}
+
+/// 非流式调用一次模型，返回 (正文, usage)
+async fn complete(state: &AppState, model: &str, prompt: String) -> Result<(String, Option<Value>), (u16, Value)> {
+    let (status, response) = execute_with_backoff(state, "/v1/chat/completions", chat_body(model, prompt, false)).await;
+    if !(200..300).contains(&status) {
+        return Err((status, response));
+    }
+    let text = choices_of(&response)
+        .first()
+        .and_then(|c| c.pointer("/message/content"))
+        .map(content_text)
+        .unwrap_or_default();
+    Ok((text, response.get("usage").cloned()))
+}
+
+/// map 阶段: 并行摘要所有块，`on_done` 在每块完成时回调 (用于流式进度)
+async fn map_chunks(
+    state: &AppState,
+    options: &SummarizeOptions,
+    chunks: Vec<String>,
+    usage: &mut Value,
+    mut on_done: impl FnMut(usize, usize),
+) -> Result<Vec<String>, (u16, Value)> {
+    let total = chunks.len();
+    let semaphore = Arc::new(Semaphore::new(state.token_manager.len().clamp(1, MAX_CONCURRENCY)));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let state = state.clone();
+        let semaphore = semaphore.clone();
+        let model = options.model.clone();
+        let prompt = map_prompt(&chunk, index, total, &options.instructions);
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            (index, complete(&state, &model, prompt).await)
+        });
+    }
+
+    let mut summaries = vec![String::new(); total];
+    let mut done = 0;
+    while let Some(joined) = tasks.join_next().await {
+        let Ok((index, result)) = joined else { continue };
+        let (text, part_usage) = result?;
+        add_usage(usage, part_usage.as_ref());
+        summaries[index] = text;
+        done += 1;
+        on_done(done, total);
+    }
+    Ok(summaries)
+}
+
+/// 中间轮 reduce: 合并后的摘要仍超过单块大小时分组合并，直到可以一次放入最终请求
+async fn reduce_until_fits(
+    state: &AppState,
+    options: &SummarizeOptions,
+    mut summaries: Vec<String>,
+    usage: &mut Value,
+) -> Result<Vec<String>, (u16, Value)> {
+    for round in 0..MAX_REDUCE_ROUNDS {
+        let total_chars: usize = summaries.iter().map(|s| s.chars().count()).sum();
+        if total_chars <= options.chunk_chars || summaries.len() <= 1 {
+            break;
+        }
+        let groups = chunk_text(&summaries.join("\n\n"), options.chunk_chars);
+        if groups.len() >= summaries.len() {
+            break;
+        }
+        tracing::info!("[Summarize] 第 {} 轮合并: {} 段摘要 -> {} 组", round + 1, summaries.len(), groups.len());
+        let mut next = Vec::with_capacity(groups.len());
+        for group in groups {
+            let (text, part_usage) = complete(state, &options.model, reduce_prompt(&[group], false, &options.instructions)).await?;
+            add_usage(usage, part_usage.as_ref());
+            next.push(text);
+        }
+        summaries = next;
+    }
+    Ok(summaries)
+}
+
+fn final_prompt(text: &str, chunks: usize, summaries: &[String], options: &SummarizeOptions) -> String {
+    if chunks == 1 {
+        single_prompt(text, &options.instructions)
+    } else {
+        reduce_prompt(summaries, true, &options.instructions)
+    }
+}
+
+fn error_event(status: u16, error: &Value) -> Bytes {
+    let message = error
+        .pointer("/error/message")
+        .and_then(|m| m.as_str())
+        .map(|m| m.to_string())
+        .unwrap_or_else(|| error.to_string());
+    Bytes::from(format!(
+        "data: {}\n\ndata: [DONE]\n\n",
+        json!({ "error": { "message": message, "type": "upstream_error", "code": status } })
+    ))
+}
+
+/// 处理 /v1/summarize
+pub async fn handle_summarize(State(state): State<AppState>, Json(body): Json<Value>) -> Response {
+    let text = match parse_input(body.get("input").unwrap_or(&Value::Null)) {
+        Ok(t) => t,
+        Err(e) => return invalid_request(e, None),
+    };
+    let options = parse_options(&body);
+    let chunks = chunk_text(&text, options.chunk_chars);
+    let chunk_count = chunks.len();
+    tracing::info!(
+        "[Summarize] {} 字符 -> {} 块, 模型={}, stream={}",
+        text.chars().count(),
+        chunk_count,
+        options.model,
+        options.stream
+    );
+
+    if !options.stream {
+        let mut usage = json!({});
+        let summaries = if chunk_count == 1 {
+            Vec::new()
+        } else {
+            let mapped = match map_chunks(&state, &options, chunks, &mut usage, |_, _| {}).await {
+                Ok(s) => s,
+                Err((status, e)) => return (StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY), axum::Json(e)).into_response(),
+            };
+            match reduce_until_fits(&state, &options, mapped, &mut usage).await {
+                Ok(s) => s,
+                Err((status, e)) => return (StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY), axum::Json(e)).into_response(),
+            }
+        };
+        let prompt = final_prompt(&text, chunk_count, &summaries, &options);
+        let (status, mut response) = execute_with_backoff(&state, "/v1/chat/completions", chat_body(&options.model, prompt, false)).await;
+        let status = StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY);
+        if status.is_success() {
+            add_usage(&mut usage, response.get("usage"));
+            response["usage"] = usage;
+            response["summary_chunks"] = json!(chunk_count);
+        }
+        return (status, [(SUMMARY_CHUNKS_HEADER, chunk_count.to_string())], axum::Json(response)).into_response();
+    }
+
+    // 流式: map/reduce 期间以 SSE 注释行报告进度 (同时保活)，最后一轮直接转发 chat.completion.chunk
+    let stream = async_stream::stream! {
+        let mut usage = json!({});
+        let summaries = if chunk_count == 1 {
+            Vec::new()
+        } else {
+            let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+            let mapped = {
+                let mapping = map_chunks(&state, &options, chunks, &mut usage, move |done, total| {
+                    let _ = progress_tx.send(format!(": map {}/{}\n\n", done, total));
+                });
+                tokio::pin!(mapping);
+                loop {
+                    tokio::select! {
+                        Some(line) = progress_rx.recv() => yield Ok::<Bytes, std::io::Error>(Bytes::from(line)),
+                        result = &mut mapping => break result,
+                    }
+                }
+            };
+            while let Ok(line) = progress_rx.try_recv() {
+                yield Ok(Bytes::from(line));
+            }
+            let mapped = match mapped {
+                Ok(s) => s,
+                Err((status, e)) => {
+                    yield Ok(error_event(status, &e));
+                    return;
+                }
+            };
+            yield Ok(Bytes::from(": reduce\n\n"));
+            match reduce_until_fits(&state, &options, mapped, &mut usage).await {
+                Ok(s) => s,
+                Err((status, e)) => {
+                    yield Ok(error_event(status, &e));
+                    return;
+                }
+            }
+        };
+
+        let prompt = final_prompt(&text, chunk_count, &summaries, &options);
+        let response = crate::proxy::handlers::openai::handle_chat_completions(
+            State(state.clone()),
+            StreamingJson(chat_body(&options.model, prompt, true)),
+        )
+        .await
+        .into_response();
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap_or_default();
+            let error = serde_json::from_slice::<Value>(&bytes)
+                .unwrap_or_else(|_| json!({ "error": { "message": String::from_utf8_lossy(&bytes) } }));
+            yield Ok(error_event(status, &error));
+            return;
+        }
+        let mut body = response.into_body().into_data_stream();
+        while let Some(chunk) = body.next().await {
+            match chunk {
+                Ok(bytes) => yield Ok(bytes),
+                Err(e) => {
+                    yield Err(std::io::Error::new(std::io::ErrorKind::Other, e.to_string()));
+                    return;
+                }
+            }
+        }
+    };
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(SUMMARY_CHUNKS_HEADER, chunk_count.to_string())
+        .body(Body::from_stream(stream))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_text_prefers_paragraph_boundaries() {
+        let text = "aaaa\n\nbbbb\n\ncccc";
+        let chunks = chunk_text(text, 12);
+        assert_eq!(chunks, vec!["aaaa\n\nbbbb\n\n".to_string(), "cccc".to_string()]);
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[test]
+    fn test_chunk_text_splits_oversized_paragraph() {
+        let text = "一二三四五六七八九十";
+        let chunks = chunk_text(text, 4);
+        assert_eq!(chunks, vec!["一二三四", "五六七八", "九十"]);
+    }
+
+    #[test]
+    fn test_parse_input_and_options() {
+        assert_eq!(parse_input(&json!(["doc a", "doc b"])).unwrap(), "doc a\n\ndoc b");
+        assert!(parse_input(&json!("   ")).is_err());
+        assert!(parse_input(&json!(42)).is_err());
+
+        let options = parse_options(&json!({ "chunk_tokens": 10 }));
+        assert_eq!(options.model, DEFAULT_SUMMARIZE_MODEL);
+        assert_eq!(options.chunk_chars, MIN_CHUNK_TOKENS * CHARS_PER_TOKEN);
+        assert!(options.stream);
+    }
+}
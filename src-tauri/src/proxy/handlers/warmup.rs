@@ -168,7 +168,7 @@ pub async fn handle_warmup(
 
     let mut result = state
         .upstream
-        .call_v1_internal(method, &access_token, body.clone(), query)
+        .call_v1_internal(method, &access_token, &body, query)
         .await;
 
     // 如果流式请求失败，尝试非流式请求
@@ -0,0 +1,364 @@
+// 在途请求登记表
+// 每个生成类请求分配一个 X-AG-Request-Id，并登记可取消令牌；
+// 通过 `POST /v1/cancel/{request_id}` 或 Tauri 命令取消时，中止对应的上游请求/流，真正停止配额消耗。
+// 经 API 取消时只能取消使用同一 API Key 发起的请求 (请求 ID 可能由客户端自带，容易被猜到)。
+// `/admin/inflight` 与 get_inflight_requests 命令通过 snapshot() 展示排队/执行中的请求。
+
+use dashmap::DashMap;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// 请求 ID 响应头 (客户端也可在请求中自带)
+pub const REQUEST_ID_HEADER: &str = "x-ag-request-id";
+
+/// 客户端自带请求 ID 的最大长度
+const MAX_CLIENT_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    /// 当前任务所属的请求 ID (供账号调度回填模型/账号)
+    static CURRENT_REQUEST: String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InflightPhase {
+    /// 已接收，尚未分配账号 (等待调度/预检/限流等待)
+    Queued,
+    /// 已分配账号，等待上游响应
+    Upstream,
+    /// 上游已开始返回，正在向客户端输出
+    Streaming,
+}
+
+struct InflightEntry {
+    method: String,
+    path: String,
+    conversation_id: Option<String>,
+    client_key: Option<String>,
+    /// 发起请求的 API Key 摘要，用于校验取消者
+    owner: Option<[u8; 32]>,
+    model: Option<String>,
+    account: Option<String>,
+    phase: InflightPhase,
+    /// 请求体大小 (Content-Length)，用于公平调度估算输入 token
+    request_bytes: Option<u64>,
+    started_at: i64,
+    started: Instant,
+    cancel: CancellationToken,
+}
+
+/// 对外展示的在途请求视图
+#[derive(Debug, Clone, Serialize)]
+pub struct InflightInfo {
+    pub id: String,
+    pub method: String,
+    pub path: String,
+    pub model: Option<String>,
+    pub account: Option<String>,
+    /// 脱敏后的客户端 API Key
+    pub client_key: Option<String>,
+    pub conversation_id: Option<String>,
+    pub phase: InflightPhase,
+    pub cancelled: bool,
+    pub started_at: i64,
+    pub age_ms: u64,
+}
+
+pub struct InflightRegistry {
+    entries: DashMap<String, InflightEntry>,
+}
+
+/// 请求结束 (含流式响应结束) 时自动注销
+pub struct InflightGuard {
+    registry: Arc<InflightRegistry>,
+    id: String,
+}
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        self.registry.entries.remove(&self.id);
+    }
+}
+
+/// 客户端提供的 ID 仅接受可打印 ASCII，避免写入响应头时出错
+pub fn sanitize_client_id(id: &str) -> Option<String> {
+    let id = id.trim();
+    if id.is_empty() || id.len() > MAX_CLIENT_ID_LEN || !id.bytes().all(|b| b.is_ascii_graphic()) {
+        return None;
+    }
+    Some(id.to_string())
+}
+
+pub fn generate_request_id() -> String {
+    format!("req_{}", uuid::Uuid::new_v4().simple())
+}
+
+/// API Key 仅保留首尾少量字符
+pub fn mask_client_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "***".to_string();
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}***{}", head, tail)
+}
+
+fn key_digest(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+/// 在请求 ID 作用域内执行 (调度阶段可通过 note_assignment 回填信息)
+pub async fn scope<F: std::future::Future>(id: String, fut: F) -> F::Output {
+    CURRENT_REQUEST.scope(id, fut).await
+}
+
+/// 账号调度完成后记录实际模型与账号 (不在请求作用域内时忽略)
+pub fn note_assignment(model: &str, account: &str) {
+    let _ = CURRENT_REQUEST.try_with(|id| {
+        if let Some(mut entry) = InflightRegistry::global().entries.get_mut(id) {
+            entry.model = Some(model.to_string());
+            entry.account = Some(account.to_string());
+            if entry.phase == InflightPhase::Queued {
+                entry.phase = InflightPhase::Upstream;
+            }
+        }
+    });
+}
+
+/// 当前请求的日志上下文 (结构化日志为每条事件附加)
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogContext {
+    pub request_id: String,
+    pub model: Option<String>,
+    pub account: Option<String>,
+    /// 请求开始至今的耗时
+    pub latency_ms: u64,
+}
+
+/// 供日志格式化器读取当前请求上下文。
+/// 日志可能在持有登记表条目写锁时输出，这里只尝试加锁，拿不到时仅返回请求 ID，避免死锁。
+pub fn current_log_context() -> Option<LogContext> {
+    CURRENT_REQUEST
+        .try_with(|id| {
+            InflightRegistry::global().log_context(id).unwrap_or_else(|| LogContext {
+                request_id: id.clone(),
+                model: None,
+                account: None,
+                latency_ms: 0,
+            })
+        })
+        .ok()
+}
+
+/// 当前请求的请求体大小 (不在请求作用域内或未知时为 None)
+pub fn current_request_bytes() -> Option<u64> {
+    CURRENT_REQUEST
+        .try_with(|id| InflightRegistry::global().entries.get(id).and_then(|e| e.request_bytes))
+        .ok()
+        .flatten()
+}
+
+impl InflightRegistry {
+    fn new() -> Self {
+        Self { entries: DashMap::new() }
+    }
+
+    /// Global singleton instance (Tauri 命令与反代服务共享)
+    pub fn global() -> Arc<Self> {
+        static INSTANCE: OnceLock<Arc<InflightRegistry>> = OnceLock::new();
+        INSTANCE.get_or_init(|| Arc::new(InflightRegistry::new())).clone()
+    }
+
+    /// 登记请求；ID 已被占用时返回 None
+    pub fn register(
+        self: &Arc<Self>,
+        id: &str,
+        method: &str,
+        path: &str,
+        conversation_id: Option<String>,
+        client_key: Option<String>,
+    ) -> Option<(CancellationToken, InflightGuard)> {
+        let token = CancellationToken::new();
+        match self.entries.entry(id.to_string()) {
+            dashmap::mapref::entry::Entry::Occupied(_) => return None,
+            dashmap::mapref::entry::Entry::Vacant(v) => {
+                v.insert(InflightEntry {
+                    method: method.to_string(),
+                    path: path.to_string(),
+                    conversation_id,
+                    client_key: client_key.as_deref().map(mask_client_key),
+                    owner: client_key.as_deref().map(key_digest),
+                    model: None,
+                    account: None,
+                    phase: InflightPhase::Queued,
+                    request_bytes: None,
+                    started_at: chrono::Utc::now().timestamp_millis(),
+                    started: Instant::now(),
+                    cancel: token.clone(),
+                });
+            }
+        }
+        Some((token, InflightGuard { registry: self.clone(), id: id.to_string() }))
+    }
+
+    /// 取消指定请求，返回 false 表示请求不存在或已结束
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.entries.get(id) {
+            Some(entry) => {
+                entry.cancel.cancel();
+                tracing::info!("[Inflight] 请求 {} ({}) 已被取消", id, entry.path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 以客户端身份取消: 仅当 API Key 与发起请求时一致 (均未携带也视为一致)，
+    /// 否则与请求不存在一样返回 false，不暴露其他客户端的请求 ID
+    pub fn cancel_owned(&self, id: &str, client_key: Option<&str>) -> bool {
+        let owned = self
+            .entries
+            .get(id)
+            .is_some_and(|entry| entry.owner == client_key.map(key_digest));
+        if !owned {
+            return false;
+        }
+        self.cancel(id)
+    }
+
+    /// 指定请求的日志上下文 (条目被占用或不存在时为 None)
+    pub fn log_context(&self, id: &str) -> Option<LogContext> {
+        match self.entries.try_get(id) {
+            dashmap::try_result::TryResult::Present(entry) => Some(LogContext {
+                request_id: id.to_string(),
+                model: entry.model.clone(),
+                account: entry.account.clone(),
+                latency_ms: entry.started.elapsed().as_millis() as u64,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn set_phase(&self, id: &str, phase: InflightPhase) {
+        if let Some(mut entry) = self.entries.get_mut(id) {
+            entry.phase = phase;
+        }
+    }
+
+    pub fn set_request_bytes(&self, id: &str, bytes: u64) {
+        if let Some(mut entry) = self.entries.get_mut(id) {
+            entry.request_bytes = Some(bytes);
+        }
+    }
+
+    /// 当前在途请求，按等待时间从长到短排序
+    pub fn snapshot(&self) -> Vec<InflightInfo> {
+        let mut list: Vec<InflightInfo> = self
+            .entries
+            .iter()
+            .map(|e| InflightInfo {
+                id: e.key().clone(),
+                method: e.method.clone(),
+                path: e.path.clone(),
+                model: e.model.clone(),
+                account: e.account.clone(),
+                client_key: e.client_key.clone(),
+                conversation_id: e.conversation_id.clone(),
+                phase: e.phase,
+                cancelled: e.cancel.is_cancelled(),
+                started_at: e.started_at,
+                age_ms: e.started.elapsed().as_millis() as u64,
+            })
+            .collect();
+        list.sort_by(|a, b| b.age_ms.cmp(&a.age_ms));
+        list
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_cancel_and_guard_cleanup() {
+        let registry = Arc::new(InflightRegistry::new());
+        let (token, guard) = registry.register("req_1", "POST", "/v1/messages", None, Some("sk-abcdef123456".to_string())).unwrap();
+        // 重复 ID 被拒绝
+        assert!(registry.register("req_1", "POST", "/v1/messages", None, None).is_none());
+
+        let info = &registry.snapshot()[0];
+        assert_eq!(info.phase, InflightPhase::Queued);
+        assert_eq!(info.client_key.as_deref(), Some("sk-a***3456"));
+        registry.set_phase("req_1", InflightPhase::Streaming);
+        assert_eq!(registry.snapshot()[0].phase, InflightPhase::Streaming);
+
+        assert!(!token.is_cancelled());
+        assert!(registry.cancel("req_1"));
+        assert!(token.is_cancelled());
+
+        drop(guard);
+        assert_eq!(registry.len(), 0);
+        assert!(!registry.cancel("req_1"));
+    }
+
+    #[test]
+    fn test_cancel_owned_requires_same_key() {
+        let registry = Arc::new(InflightRegistry::new());
+        let (token, _guard) = registry.register("req_a", "POST", "/v1/messages", None, Some("sk-owner-123456".to_string())).unwrap();
+        assert!(!registry.cancel_owned("req_a", None));
+        assert!(!registry.cancel_owned("req_a", Some("sk-other-123456")));
+        assert!(!token.is_cancelled());
+        assert!(registry.cancel_owned("req_a", Some("sk-owner-123456")));
+        assert!(token.is_cancelled());
+
+        // 未携带 Key 的请求只能由同样未携带 Key 的调用取消
+        let (anon, _guard) = registry.register("req_b", "POST", "/v1/messages", None, None).unwrap();
+        assert!(!registry.cancel_owned("req_b", Some("sk-owner-123456")));
+        assert!(registry.cancel_owned("req_b", None));
+        assert!(anon.is_cancelled());
+        assert!(!registry.cancel_owned("req_missing", None));
+    }
+
+    #[test]
+    fn test_sanitize_client_id() {
+        assert_eq!(sanitize_client_id(" my-id_1 ").as_deref(), Some("my-id_1"));
+        assert!(sanitize_client_id("").is_none());
+        assert!(sanitize_client_id("has space").is_none());
+        assert!(sanitize_client_id(&"x".repeat(200)).is_none());
+        assert!(generate_request_id().starts_with("req_"));
+        assert_eq!(mask_client_key("short"), "***");
+    }
+
+    #[tokio::test]
+    async fn test_note_assignment_in_scope() {
+        let registry = InflightRegistry::global();
+        let (_token, _guard) = registry.register("req_scope_test", "POST", "/v1/chat/completions", None, None).unwrap();
+        // 作用域外调用被忽略
+        note_assignment("gemini-2.5-flash", "nobody@example.com");
+        assert!(current_log_context().is_none());
+        let ctx = scope("req_scope_test".to_string(), async {
+            note_assignment("gemini-2.5-flash", "a@example.com");
+            current_log_context()
+        })
+        .await
+        .unwrap();
+        assert_eq!(ctx.request_id, "req_scope_test");
+        assert_eq!(ctx.model.as_deref(), Some("gemini-2.5-flash"));
+        assert_eq!(ctx.account.as_deref(), Some("a@example.com"));
+        let info = registry.snapshot().into_iter().find(|i| i.id == "req_scope_test").unwrap();
+        assert_eq!(info.account.as_deref(), Some("a@example.com"));
+        assert_eq!(info.phase, InflightPhase::Upstream);
+    }
+}
@@ -0,0 +1,170 @@
+// 请求延迟统计与延迟预算
+// 按 (账号, 实际模型) 维护首字节与总耗时的指数滑动平均；
+// 携带 X-AG-Max-Latency-Ms 的请求在预算作用域内执行，账号调度据此优先历史更快的账号。
+
+use dashmap::DashMap;
+use std::sync::{Arc, OnceLock};
+
+/// 客户端延迟预算请求头 (毫秒)
+pub const MAX_LATENCY_HEADER: &str = "x-ag-max-latency-ms";
+
+/// 滑动平均权重: 新样本占 20%
+const EWMA_ALPHA: f64 = 0.2;
+
+tokio::task_local! {
+    /// 当前请求的延迟预算
+    static LATENCY_BUDGET: LatencyBudget;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyBudget {
+    pub max_ms: u64,
+    /// 流式请求按首字节耗时衡量，非流式按总耗时衡量
+    pub streaming: bool,
+    /// 中间件已决定关闭 thinking (OpenAI 协议按模型名自动注入 thinkingConfig，需在映射时跳过)
+    pub suppress_thinking: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LatencyStats {
+    ttfb_ms: f64,
+    total_ms: f64,
+}
+
+impl LatencyStats {
+    fn update(&mut self, ttfb_ms: u64, total_ms: u64) {
+        self.ttfb_ms += EWMA_ALPHA * (ttfb_ms as f64 - self.ttfb_ms);
+        self.total_ms += EWMA_ALPHA * (total_ms as f64 - self.total_ms);
+    }
+
+    fn estimate_ms(&self, streaming: bool) -> u64 {
+        if streaming { self.ttfb_ms } else { self.total_ms }.round() as u64
+    }
+}
+
+pub struct LatencyTracker {
+    /// (账号邮箱, 实际模型) -> 统计
+    stats: DashMap<(String, String), LatencyStats>,
+}
+
+impl LatencyTracker {
+    fn new() -> Self {
+        Self { stats: DashMap::new() }
+    }
+
+    pub fn global() -> Arc<Self> {
+        static INSTANCE: OnceLock<Arc<LatencyTracker>> = OnceLock::new();
+        INSTANCE.get_or_init(|| Arc::new(LatencyTracker::new())).clone()
+    }
+
+    /// 记录一次成功请求的耗时
+    pub fn record(&self, account: &str, model: &str, ttfb_ms: u64, total_ms: u64) {
+        self.stats
+            .entry((account.to_string(), model.to_string()))
+            .and_modify(|s| s.update(ttfb_ms, total_ms))
+            .or_insert(LatencyStats {
+                ttfb_ms: ttfb_ms as f64,
+                total_ms: total_ms as f64,
+            });
+    }
+
+    /// 指定账号 + 模型的历史延迟估计 (无样本时返回 None)
+    pub fn estimate(&self, account: &str, model: &str, streaming: bool) -> Option<u64> {
+        self.stats
+            .get(&(account.to_string(), model.to_string()))
+            .map(|s| s.estimate_ms(streaming))
+    }
+
+    /// 模型在所有账号中的最佳历史延迟 (调度会优先选中该账号)
+    pub fn model_estimate(&self, model: &str, streaming: bool) -> Option<u64> {
+        self.stats
+            .iter()
+            .filter(|e| e.key().1 == model)
+            .map(|e| e.value().estimate_ms(streaming))
+            .min()
+    }
+
+    /// 调度排序键: 无历史数据的账号视为恰好等于预算 (排在已知满足预算的账号之后)
+    pub fn rank(&self, account: &str, model: &str, budget: &LatencyBudget) -> u64 {
+        self.estimate(account, model, budget.streaming).unwrap_or(budget.max_ms)
+    }
+
+    /// 账号的历史延迟是否在预算内 (无历史数据视为满足)
+    pub fn within_budget(&self, account: &str, model: &str, budget: &LatencyBudget) -> bool {
+        self.rank(account, model, budget) <= budget.max_ms
+    }
+}
+
+/// 在延迟预算作用域内执行
+pub async fn scope<F: std::future::Future>(budget: LatencyBudget, fut: F) -> F::Output {
+    LATENCY_BUDGET.scope(budget, fut).await
+}
+
+/// 当前请求的延迟预算 (未携带预算头时为 None)
+pub fn current_budget() -> Option<LatencyBudget> {
+    LATENCY_BUDGET.try_with(|b| *b).ok()
+}
+
+/// OpenAI 映射是否应跳过自动注入的 thinkingConfig
+pub fn thinking_suppressed() -> bool {
+    current_budget().is_some_and(|b| b.suppress_thinking)
+}
+
+/// 解析预算请求头 (0 或非法值视为未设置)
+pub fn parse_budget_header(value: &str) -> Option<u64> {
+    value.trim().parse::<u64>().ok().filter(|ms| *ms > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ewma_and_estimates() {
+        let tracker = LatencyTracker::new();
+        tracker.record("a@example.com", "gemini-2.5-pro", 1000, 5000);
+        tracker.record("a@example.com", "gemini-2.5-pro", 2000, 10000);
+        // 1000 + 0.2 * (2000 - 1000) = 1200
+        assert_eq!(tracker.estimate("a@example.com", "gemini-2.5-pro", true), Some(1200));
+        assert_eq!(tracker.estimate("a@example.com", "gemini-2.5-pro", false), Some(6000));
+        assert_eq!(tracker.estimate("b@example.com", "gemini-2.5-pro", true), None);
+
+        tracker.record("b@example.com", "gemini-2.5-pro", 400, 3000);
+        assert_eq!(tracker.model_estimate("gemini-2.5-pro", true), Some(400));
+        assert_eq!(tracker.model_estimate("gemini-2.5-pro", false), Some(3000));
+        assert_eq!(tracker.model_estimate("gemini-2.5-flash", true), None);
+    }
+
+    #[test]
+    fn test_rank_treats_unknown_as_budget() {
+        let tracker = LatencyTracker::new();
+        tracker.record("fast@example.com", "m", 300, 900);
+        tracker.record("slow@example.com", "m", 4000, 9000);
+        let budget = LatencyBudget { max_ms: 1500, streaming: true, suppress_thinking: false };
+
+        let mut accounts = vec!["slow@example.com", "new@example.com", "fast@example.com"];
+        accounts.sort_by_cached_key(|a| tracker.rank(a, "m", &budget));
+        assert_eq!(accounts, vec!["fast@example.com", "new@example.com", "slow@example.com"]);
+
+        assert!(tracker.within_budget("fast@example.com", "m", &budget));
+        assert!(tracker.within_budget("new@example.com", "m", &budget));
+        assert!(!tracker.within_budget("slow@example.com", "m", &budget));
+    }
+
+    #[test]
+    fn test_parse_budget_header() {
+        assert_eq!(parse_budget_header(" 1500 "), Some(1500));
+        assert_eq!(parse_budget_header("0"), None);
+        assert_eq!(parse_budget_header("fast"), None);
+    }
+
+    #[tokio::test]
+    async fn test_budget_scope() {
+        assert_eq!(current_budget(), None);
+        let budget = LatencyBudget { max_ms: 800, streaming: false, suppress_thinking: true };
+        let (seen, suppressed) = scope(budget, async { (current_budget(), thinking_suppressed()) }).await;
+        assert_eq!(seen, Some(budget));
+        assert!(suppressed);
+        assert!(!thinking_suppressed());
+    }
+}
@@ -0,0 +1,190 @@
+// 本地套接字监听 (Unix Domain Socket / Windows 命名管道)
+// 与 TCP 端口并行提供同一套 API，避免端口冲突，并可依赖操作系统权限控制访问。
+
+use std::path::PathBuf;
+
+#[cfg(windows)]
+const DEFAULT_PIPE_NAME: &str = r"\\.\pipe\antigravity-proxy";
+
+/// 经本地套接字建立的连接会在请求扩展中带上该标记 (供鉴权中间件识别)
+#[derive(Debug, Clone, Copy)]
+pub struct LocalSocketPeer;
+
+#[cfg(unix)]
+pub type LocalStream = tokio::net::UnixStream;
+#[cfg(windows)]
+pub type LocalStream = tokio::net::windows::named_pipe::NamedPipeServer;
+
+/// 解析实际的套接字路径 / 管道名
+pub fn resolve_path(configured: &str) -> Result<PathBuf, String> {
+    if !configured.trim().is_empty() {
+        return Ok(PathBuf::from(configured.trim()));
+    }
+    #[cfg(unix)]
+    {
+        Ok(crate::modules::account::get_data_dir()?.join("proxy.sock"))
+    }
+    #[cfg(windows)]
+    {
+        Ok(PathBuf::from(DEFAULT_PIPE_NAME))
+    }
+}
+
+pub struct LocalListener {
+    path: PathBuf,
+    #[cfg(unix)]
+    inner: tokio::net::UnixListener,
+    #[cfg(windows)]
+    next: tokio::net::windows::named_pipe::NamedPipeServer,
+}
+
+impl LocalListener {
+    #[cfg(unix)]
+    pub fn bind(path: PathBuf) -> Result<Self, String> {
+        use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+
+        match std::fs::symlink_metadata(&path) {
+            Ok(meta) => {
+                // 只清理套接字文件: 路径配置错误时不能误删普通文件、目录或符号链接指向的内容
+                if !meta.file_type().is_socket() {
+                    return Err(format!("{} 已存在且不是套接字文件，拒绝覆盖", path.display()));
+                }
+                // 上次异常退出残留的套接字文件: 无进程监听时才删除，避免抢占正在运行的实例
+                if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                    return Err(format!("套接字 {} 已被其他进程使用", path.display()));
+                }
+                std::fs::remove_file(&path).map_err(|e| format!("无法删除残留套接字 {}: {}", path.display(), e))?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("无法检查套接字路径 {}: {}", path.display(), e)),
+        }
+
+        let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)
+            .map_err(|e| format!("无法创建目录 {}: {}", parent.display(), e))?;
+
+        // 先在仅当前用户可访问的临时目录中绑定并收紧权限，再原子移动到目标路径:
+        // 套接字出现在目标路径时已是 0600，不存在 bind 与 chmod 之间可被其他用户连接的窗口
+        let staging = parent.join(format!(".ag-sock-{}", &uuid::Uuid::new_v4().simple().to_string()[..12]));
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&staging)
+            .map_err(|e| format!("无法创建临时目录 {}: {}", staging.display(), e))?;
+        let staged = staging.join("s");
+        let result = tokio::net::UnixListener::bind(&staged)
+            .map_err(|e| format!("套接字 {} 绑定失败: {}", path.display(), e))
+            .and_then(|inner| {
+                std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))
+                    .map_err(|e| format!("无法设置套接字权限: {}", e))?;
+                std::fs::rename(&staged, &path)
+                    .map_err(|e| format!("套接字 {} 绑定失败: {}", path.display(), e))?;
+                Ok(inner)
+            });
+        let _ = std::fs::remove_dir_all(&staging);
+        Ok(Self { path, inner: result? })
+    }
+
+    #[cfg(windows)]
+    pub fn bind(path: PathBuf) -> Result<Self, String> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+        let next = ServerOptions::new()
+            .first_pipe_instance(true)
+            .reject_remote_clients(true)
+            .create(&path)
+            .map_err(|e| format!("命名管道 {} 创建失败: {}", path.display(), e))?;
+        Ok(Self { path, next })
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    #[cfg(unix)]
+    pub async fn accept(&mut self) -> std::io::Result<LocalStream> {
+        self.inner.accept().await.map(|(stream, _)| stream)
+    }
+
+    /// 命名管道每个实例只服务一个客户端: 连接后立即创建下一个实例继续等待
+    #[cfg(windows)]
+    pub async fn accept(&mut self) -> std::io::Result<LocalStream> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+        self.next.connect().await?;
+        let next = ServerOptions::new().reject_remote_clients(true).create(&self.path)?;
+        Ok(std::mem::replace(&mut self.next, next))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for LocalListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// 在可选的监听器上等待连接；未启用时永不返回 (用于 select! 分支)
+pub async fn accept_optional(listener: &mut Option<LocalListener>) -> std::io::Result<LocalStream> {
+    match listener {
+        Some(listener) => listener.accept().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_replaces_stale_socket_and_cleans_up() {
+        let dir = std::env::temp_dir().join(format!("ag-local-socket-{}", uuid::Uuid::new_v4().simple()));
+        let path = dir.join("proxy.sock");
+
+        // 残留的套接字文件 (无进程监听)
+        std::fs::create_dir_all(&dir).unwrap();
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let mut listener = Some(LocalListener::bind(path.clone()).unwrap());
+        // 已被占用时拒绝绑定
+        assert!(LocalListener::bind(path.clone()).is_err());
+
+        let client = tokio::net::UnixStream::connect(&path);
+        let (accepted, connected) = tokio::join!(accept_optional(&mut listener), client);
+        assert!(accepted.is_ok() && connected.is_ok());
+
+        drop(listener);
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_bind_refuses_non_socket_paths() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ag-local-socket-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // 普通文件与指向它的符号链接都不能被删除
+        let file = dir.join("notes.txt");
+        std::fs::write(&file, b"keep me").unwrap();
+        assert!(LocalListener::bind(file.clone()).is_err());
+        let link = dir.join("link.sock");
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+        assert!(LocalListener::bind(link.clone()).is_err());
+        assert_eq!(std::fs::read(&file).unwrap(), b"keep me");
+        assert!(std::fs::symlink_metadata(&link).is_ok());
+
+        // 新建的父目录为 0700，套接字为 0600，且不留下临时目录
+        let nested = dir.join("private");
+        let path = nested.join("proxy.sock");
+        let listener = LocalListener::bind(path.clone()).unwrap();
+        assert_eq!(std::fs::metadata(&nested).unwrap().permissions().mode() & 0o777, 0o700);
+        assert_eq!(std::fs::symlink_metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(std::fs::read_dir(&nested).unwrap().count(), 1);
+
+        drop(listener);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,381 @@
+// 上游 / 模型能力表
+// 不同上游与模型支持的请求特性不同 (工具、图片输入、systemInstruction、思维、JSON 模式)。
+// 在转换后的 Gemini 请求发出前按能力表检查: 降级模式下去掉或改写不支持的部分，拒绝模式下返回明确的 400，
+// 避免把不支持的组合转发给上游后收到含糊的 400。
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::config::{CapabilityGating, CapabilityRule};
+
+/// Google 账号池
+pub const BACKEND_ANTIGRAVITY: &str = "antigravity";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelCapabilities {
+    pub tools: bool,
+    pub images: bool,
+    pub system_instruction: bool,
+    pub thinking: bool,
+    pub json_mode: bool,
+}
+
+const ALL: ModelCapabilities = ModelCapabilities {
+    tools: true,
+    images: true,
+    system_instruction: true,
+    thinking: true,
+    json_mode: true,
+};
+
+/// 内置能力表 (按顺序匹配第一条，未命中时视为全部支持)
+const BUILTIN: &[(&str, ModelCapabilities)] = &[
+    // 图像生成模型不接受工具、思维配置与 JSON 输出
+    ("gemini-*-image*", ModelCapabilities { tools: false, thinking: false, json_mode: false, ..ALL }),
+    ("gemini-2.0-flash*", ModelCapabilities { thinking: false, ..ALL }),
+    ("claude-*-thinking", ModelCapabilities { json_mode: false, ..ALL }),
+    ("claude-*", ModelCapabilities { thinking: false, json_mode: false, ..ALL }),
+    ("gemini-*", ALL),
+];
+
+/// 账号池实测能力 (None = 未能确定，沿用内置表)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProbedCapabilities {
+    pub tools: Option<bool>,
+    pub images: Option<bool>,
+    pub system_instruction: Option<bool>,
+    pub thinking: Option<bool>,
+    pub json_mode: Option<bool>,
+}
+
+/// 按模型名索引的探测结果 (已按账号池合并)
+fn probed_store() -> &'static RwLock<HashMap<String, ProbedCapabilities>> {
+    static STORE: OnceLock<RwLock<HashMap<String, ProbedCapabilities>>> = OnceLock::new();
+    STORE.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+pub fn set_probed(probed: HashMap<String, ProbedCapabilities>) {
+    if let Ok(mut store) = probed_store().write() {
+        *store = probed;
+    }
+}
+
+pub fn resolve(backend: &str, model: &str, rules: &[CapabilityRule]) -> ModelCapabilities {
+    let mut base = BUILTIN
+        .iter()
+        .find(|(pattern, _)| wildcard_match(pattern, model))
+        .map(|(_, caps)| *caps)
+        .unwrap_or(ALL);
+    // 实测结果只来自 Google 账号池，优先于内置表，低于用户规则
+    if backend == BACKEND_ANTIGRAVITY {
+        if let Some(probed) = probed_store().read().ok().and_then(|s| s.get(model).copied()) {
+            base = ModelCapabilities {
+                tools: probed.tools.unwrap_or(base.tools),
+                images: probed.images.unwrap_or(base.images),
+                system_instruction: probed.system_instruction.unwrap_or(base.system_instruction),
+                thinking: probed.thinking.unwrap_or(base.thinking),
+                json_mode: probed.json_mode.unwrap_or(base.json_mode),
+            };
+        }
+    }
+    let Some(rule) = rules
+        .iter()
+        .find(|r| (r.backend.is_empty() || r.backend == backend) && wildcard_match(&r.model, model))
+    else {
+        return base;
+    };
+    ModelCapabilities {
+        tools: rule.tools.unwrap_or(base.tools),
+        images: rule.images.unwrap_or(base.images),
+        system_instruction: rule.system_instruction.unwrap_or(base.system_instruction),
+        thinking: rule.thinking.unwrap_or(base.thinking),
+        json_mode: rule.json_mode.unwrap_or(base.json_mode),
+    }
+}
+
+fn is_image_part(part: &Value) -> bool {
+    ["inlineData", "fileData"].iter().any(|key| {
+        part.get(*key)
+            .and_then(|d| d.get("mimeType"))
+            .and_then(|m| m.as_str())
+            .is_some_and(|m| m.starts_with("image/"))
+    })
+}
+
+fn parts_iter(request: &Value) -> impl Iterator<Item = &Value> {
+    request
+        .get("contents")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|c| c.get("parts").and_then(|p| p.as_array()))
+        .flatten()
+}
+
+fn for_each_part(request: &mut Value, mut f: impl FnMut(&mut Value)) {
+    if let Some(contents) = request.get_mut("contents").and_then(|c| c.as_array_mut()) {
+        for part in contents
+            .iter_mut()
+            .filter_map(|c| c.get_mut("parts").and_then(|p| p.as_array_mut()))
+            .flatten()
+        {
+            f(part);
+        }
+    }
+}
+
+fn uses_tools(request: &Value) -> bool {
+    request.get("tools").and_then(|t| t.as_array()).is_some_and(|t| !t.is_empty())
+        || parts_iter(request).any(|p| p.get("functionCall").is_some() || p.get("functionResponse").is_some())
+}
+
+fn uses_json_mode(request: &Value) -> bool {
+    let gen = &request["generationConfig"];
+    gen.get("responseMimeType").and_then(|m| m.as_str()) == Some("application/json")
+        || gen.get("responseSchema").is_some()
+        || gen.get("responseJsonSchema").is_some()
+}
+
+/// 工具降级: 去掉声明，历史中的调用与结果改写为文本
+fn strip_tools(request: &mut Value) {
+    if let Some(obj) = request.as_object_mut() {
+        obj.remove("tools");
+        obj.remove("toolConfig");
+    }
+    for_each_part(request, |part| {
+        if let Some(call) = part.get("functionCall") {
+            let text = format!(
+                "[Called tool {} with {}]",
+                call["name"].as_str().unwrap_or("unknown"),
+                call.get("args").cloned().unwrap_or(json!({}))
+            );
+            *part = json!({ "text": text });
+        } else if let Some(resp) = part.get("functionResponse") {
+            let text = format!(
+                "[Tool {} returned {}]",
+                resp["name"].as_str().unwrap_or("unknown"),
+                resp.get("response").cloned().unwrap_or(Value::Null)
+            );
+            *part = json!({ "text": text });
+        }
+    });
+}
+
+fn strip_images(request: &mut Value, model: &str) {
+    for_each_part(request, |part| {
+        if is_image_part(part) {
+            *part = json!({ "text": format!("[Image omitted: {} does not accept image input]", model) });
+        }
+    });
+}
+
+/// 并入第一条用户消息 (没有时在开头插入一条)
+fn prepend_user_text(request: &mut Value, text: String) {
+    let Some(contents) = request.get_mut("contents").and_then(|c| c.as_array_mut()) else {
+        request["contents"] = json!([{ "role": "user", "parts": [{ "text": text }] }]);
+        return;
+    };
+    let first_is_user = contents.first().is_some_and(|c| c["role"] == "user" && c["parts"].is_array());
+    if !first_is_user {
+        contents.insert(0, json!({ "role": "user", "parts": [] }));
+    }
+    if let Some(parts) = contents[0]["parts"].as_array_mut() {
+        parts.insert(0, json!({ "text": text }));
+    }
+}
+
+fn inline_system_instruction(request: &mut Value) {
+    let Some(system) = request.as_object_mut().and_then(|o| o.remove("systemInstruction")) else {
+        return;
+    };
+    let text: Vec<&str> = system["parts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|p| p["text"].as_str())
+        .collect();
+    if !text.is_empty() {
+        prepend_user_text(request, text.join("\n"));
+    }
+}
+
+/// JSON 模式降级: 去掉 responseMimeType / schema，改为在最后一条用户消息中要求 JSON 输出
+fn json_mode_to_instruction(request: &mut Value) {
+    let schema = request
+        .get_mut("generationConfig")
+        .and_then(|g| g.as_object_mut())
+        .map(|gen| {
+            gen.remove("responseMimeType");
+            gen.remove("responseJsonSchema").or_else(|| gen.remove("responseSchema"))
+        })
+        .unwrap_or_default();
+    let mut text = "Respond with valid JSON only, without markdown fences.".to_string();
+    if let Some(schema) = schema {
+        text.push_str(&format!(" The JSON must match this schema: {}", schema));
+    }
+    let last_user = request
+        .get_mut("contents")
+        .and_then(|c| c.as_array_mut())
+        .and_then(|c| c.iter_mut().rev().find(|c| c["role"] == "user"))
+        .and_then(|c| c["parts"].as_array_mut());
+    match last_user {
+        Some(parts) => parts.push(json!({ "text": text })),
+        None => prepend_user_text(request, text),
+    }
+}
+
+fn strip_thinking(request: &mut Value) {
+    if let Some(gen) = request.get_mut("generationConfig").and_then(|g| g.as_object_mut()) {
+        gen.remove("thinkingConfig");
+    }
+}
+
+/// 按能力表检查请求 (body 可以是 v1internal 包装或原生 Gemini 请求体)。
+/// 返回被降级的特性列表；拒绝模式下遇到不支持的特性时返回错误信息
+pub fn gate_request(
+    body: &mut Value,
+    backend: &str,
+    model: &str,
+    mode: CapabilityGating,
+    rules: &[CapabilityRule],
+) -> Result<Vec<&'static str>, String> {
+    if mode == CapabilityGating::Off {
+        return Ok(Vec::new());
+    }
+    let caps = resolve(backend, model, rules);
+    let request = if body.get("request").is_some() { &mut body["request"] } else { body };
+
+    let mut unsupported: Vec<&'static str> = Vec::new();
+    if !caps.tools && uses_tools(request) {
+        unsupported.push("tools");
+    }
+    if !caps.images && parts_iter(request).any(is_image_part) {
+        unsupported.push("images");
+    }
+    if !caps.system_instruction && request.get("systemInstruction").is_some() {
+        unsupported.push("system_instruction");
+    }
+    if !caps.thinking && request["generationConfig"].get("thinkingConfig").is_some() {
+        unsupported.push("thinking");
+    }
+    if !caps.json_mode && uses_json_mode(request) {
+        unsupported.push("json_mode");
+    }
+    if unsupported.is_empty() {
+        return Ok(unsupported);
+    }
+
+    if mode == CapabilityGating::Reject {
+        return Err(format!(
+            "Model '{}' on backend '{}' does not support: {}. Remove these features from the request or switch capability gating to degrade.",
+            model,
+            backend,
+            unsupported.join(", ")
+        ));
+    }
+
+    for feature in &unsupported {
+        match *feature {
+            "tools" => strip_tools(request),
+            "images" => strip_images(request, model),
+            "system_instruction" => inline_system_instruction(request),
+            "thinking" => strip_thinking(request),
+            "json_mode" => json_mode_to_instruction(request),
+            _ => {}
+        }
+    }
+    tracing::info!("[Capabilities] {} ({}) degraded: {}", model, backend, unsupported.join(", "));
+    Ok(unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Value {
+        json!({
+            "model": "gemini-3-pro-image",
+            "request": {
+                "systemInstruction": { "parts": [{ "text": "Be brief." }] },
+                "contents": [
+                    { "role": "user", "parts": [{ "text": "draw" }, { "inlineData": { "mimeType": "image/png", "data": "AA" } }] },
+                    { "role": "model", "parts": [{ "functionCall": { "name": "lookup", "args": { "q": 1 } } }] },
+                    { "role": "user", "parts": [{ "functionResponse": { "name": "lookup", "response": { "result": "ok" } } }] }
+                ],
+                "tools": [{ "functionDeclarations": [{ "name": "lookup" }] }],
+                "generationConfig": { "thinkingConfig": { "thinkingBudget": 1024 }, "responseMimeType": "application/json" }
+            }
+        })
+    }
+
+    #[test]
+    fn test_resolve_builtin_and_overrides() {
+        assert!(!resolve(BACKEND_ANTIGRAVITY, "gemini-3-pro-image", &[]).tools);
+        assert!(resolve(BACKEND_ANTIGRAVITY, "claude-sonnet-4-5-thinking", &[]).thinking);
+        assert!(!resolve(BACKEND_ANTIGRAVITY, "claude-sonnet-4-5", &[]).thinking);
+        assert_eq!(resolve(BACKEND_ANTIGRAVITY, "some-new-model", &[]), ALL);
+
+        let rules = vec![CapabilityRule {
+            backend: "aistudio".to_string(),
+            model: "gemini-2.5-flash-lite*".to_string(),
+            images: Some(false),
+            ..Default::default()
+        }];
+        assert!(!resolve("aistudio", "gemini-2.5-flash-lite", &rules).images);
+        assert!(resolve(BACKEND_ANTIGRAVITY, "gemini-2.5-flash-lite", &rules).images);
+    }
+
+    #[test]
+    fn test_probed_results_override_builtin() {
+        let mut probed = HashMap::new();
+        probed.insert(
+            "gemini-probe-test-image".to_string(),
+            ProbedCapabilities { tools: Some(true), images: Some(false), ..Default::default() },
+        );
+        set_probed(probed);
+
+        let caps = resolve(BACKEND_ANTIGRAVITY, "gemini-probe-test-image", &[]);
+        assert!(caps.tools && !caps.images && !caps.thinking);
+        // 仅作用于账号池
+        assert!(!resolve("vertex", "gemini-probe-test-image", &[]).tools);
+        let rules = vec![CapabilityRule { model: "gemini-probe-test-*".to_string(), tools: Some(false), ..Default::default() }];
+        assert!(!resolve(BACKEND_ANTIGRAVITY, "gemini-probe-test-image", &rules).tools);
+        set_probed(HashMap::new());
+    }
+
+    #[test]
+    fn test_reject_lists_unsupported_features() {
+        let mut body = sample();
+        let err = gate_request(&mut body, BACKEND_ANTIGRAVITY, "gemini-3-pro-image", CapabilityGating::Reject, &[]).unwrap_err();
+        assert!(err.contains("does not support: tools, thinking, json_mode"));
+        assert_eq!(body, sample());
+        assert!(gate_request(&mut body, BACKEND_ANTIGRAVITY, "gemini-3-pro-image", CapabilityGating::Off, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_degrade_rewrites_request() {
+        let mut body = sample();
+        let rules = vec![CapabilityRule {
+            model: "gemini-3-pro-image".to_string(),
+            images: Some(false),
+            system_instruction: Some(false),
+            ..Default::default()
+        }];
+        let degraded = gate_request(&mut body, BACKEND_ANTIGRAVITY, "gemini-3-pro-image", CapabilityGating::Degrade, &rules).unwrap();
+        assert_eq!(degraded, vec!["tools", "images", "system_instruction", "thinking", "json_mode"]);
+
+        let request = &body["request"];
+        assert!(request.get("tools").is_none() && request.get("systemInstruction").is_none());
+        assert_eq!(request["generationConfig"], json!({}));
+        let first = request["contents"][0]["parts"].as_array().unwrap();
+        assert_eq!(first[0]["text"], "Be brief.");
+        assert!(first[2]["text"].as_str().unwrap().starts_with("[Image omitted"));
+        assert_eq!(request["contents"][1]["parts"][0]["text"], "[Called tool lookup with {\"q\":1}]");
+        let last = request["contents"][2]["parts"].as_array().unwrap();
+        assert!(last[0]["text"].as_str().unwrap().starts_with("[Tool lookup returned"));
+        assert!(last[1]["text"].as_str().unwrap().starts_with("Respond with valid JSON only"));
+    }
+}
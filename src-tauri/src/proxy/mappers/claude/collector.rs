@@ -83,6 +83,7 @@ where
             cache_creation_input_tokens: None,
             server_tool_use: None,
         },
+        x_ag_truncated: None,
     };
 
     // 用于累积内容块
@@ -208,6 +209,9 @@ where
                         response.usage = u;
                     }
                 }
+                if event.data.get("x_ag_truncated").and_then(|v| v.as_bool()) == Some(true) {
+                    response.x_ag_truncated = Some(true);
+                }
             }
 
             "message_stop" => {
@@ -8,6 +8,7 @@ pub mod streaming;
 pub mod utils;
 pub mod thinking_utils;
 pub mod collector;
+pub mod prompt_cache;
 
 pub use models::*;
 pub use request::{transform_claude_request_in, clean_cache_control_from_messages, merge_consecutive_messages};
@@ -28,16 +29,20 @@ pub fn create_claude_sse_stream(
     session_id: Option<String>, // [NEW v3.3.17] Session ID for signature caching
     scaling_enabled: bool, // [NEW] Flag for context usage scaling
     context_limit: u32,
+    cache_hint: Option<prompt_cache::CacheHint>, // [NEW] cache_control 断点的缓存用量估算
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     use async_stream::stream;
     use bytes::BytesMut;
     use futures::StreamExt;
 
-    Box::pin(stream! {
+    // 链路追踪: 流式响应转换阶段 (随流结束关闭)
+    let span = tracing::info_span!(target: crate::proxy::telemetry::TARGET, "map_back", ag.protocol = "claude", ag.streaming = true);
+    crate::proxy::telemetry::traced_stream(span, stream! {
         let mut state = StreamingState::new();
         state.session_id = session_id; // Set session ID for signature caching
         state.scaling_enabled = scaling_enabled; // Set scaling enabled flag
         state.context_limit = context_limit;
+        state.cache_hint = cache_hint;
         let mut buffer = BytesMut::new();
 
         loop {
@@ -69,6 +74,11 @@ pub fn create_claude_sse_stream(
                             }
                         }
                         Err(e) => {
+                            // 已有部分输出: 以 max_tokens + x_ag_truncated 结束，而不是报错
+                            if state.has_output() && !state.message_stop_sent {
+                                tracing::warn!("[{}] Upstream stream failed after partial output, returning truncated response: {}", trace_id, e);
+                                break;
+                            }
                             yield Err(format!("Stream error: {}", e));
                             break;
                         }
@@ -82,6 +92,11 @@ pub fn create_claude_sse_stream(
             }
         }
 
+        // 未收到 finishReason 即结束 (断线且续传失败)
+        if state.has_output() && !state.message_stop_sent {
+            state.truncated = true;
+        }
+
         // Ensure termination events are sent
         for chunk in emit_force_stop(&mut state) {
             yield Ok(chunk);
@@ -395,4 +410,23 @@ mod tests {
         assert!(all_text.contains("content_block_start"));
         assert!(all_text.contains("Hello"));
     }
+
+    #[tokio::test]
+    async fn test_stream_ending_without_finish_reason_is_marked_truncated() {
+        use futures::StreamExt;
+
+        let line = "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Partial\"}]}}],\"modelVersion\":\"test\",\"responseId\":\"123\"}\n\n";
+        let upstream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>> =
+            Box::pin(futures::stream::iter(vec![Ok(Bytes::from(line))]));
+        let mut stream = create_claude_sse_stream(upstream, "t".into(), "e".into(), None, false, 1_048_576, None);
+
+        let mut all_text = String::new();
+        while let Some(chunk) = stream.next().await {
+            all_text.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+        }
+        assert!(all_text.contains("Partial"));
+        assert!(all_text.contains("\"stop_reason\":\"max_tokens\""));
+        assert!(all_text.contains("\"x_ag_truncated\":true"));
+        assert!(all_text.contains("message_stop"));
+    }
 }
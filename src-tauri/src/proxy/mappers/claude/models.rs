@@ -232,6 +232,9 @@ pub struct ClaudeResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stop_sequence: Option<String>,
     pub usage: Usage,
+    /// 上游中途断开且无法续传时为 true (stop_reason 为 "max_tokens")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x_ag_truncated: Option<bool>,
 }
 
 /// Usage
@@ -0,0 +1,241 @@
+// Anthropic Prompt Caching 模拟
+// Gemini 侧没有与 `cache_control` 对应的显式缓存，这里在本地按 Anthropic 的规则做稳定前缀检测：
+// tools → system → messages 依次累计哈希，每个带 `cache_control` 的块构成一个缓存断点，
+// 断点在 TTL 内再次出现即视为缓存命中。检测结果按比例写回 usage 的
+// cache_read_input_tokens / cache_creation_input_tokens，供 Claude Code 的费用显示使用。
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use super::models::Usage;
+use crate::proxy::middleware::token_budget::estimate_prompt_tokens;
+
+/// 默认缓存 TTL (ephemeral, 5 分钟)
+const DEFAULT_TTL: Duration = Duration::from_secs(300);
+/// `ttl: "1h"` 对应的扩展 TTL
+const EXTENDED_TTL: Duration = Duration::from_secs(3600);
+/// 与 Anthropic 一致：不足该长度的前缀不会被缓存
+const MIN_CACHEABLE_TOKENS: u64 = 1024;
+/// 超过该条目数时清理过期断点
+const MAX_ENTRIES: usize = 10_000;
+
+/// 前缀哈希 -> 过期时间
+static PREFIX_CACHE: Lazy<DashMap<u64, Instant>> = Lazy::new(DashMap::new);
+
+/// 单次请求的缓存用量估算 (相对整个 prompt 的比例)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheHint {
+    pub read_ratio: f64,
+    pub creation_ratio: f64,
+}
+
+impl CacheHint {
+    /// 将估算比例应用到 usage；上游已返回真实缓存命中 (Gemini 隐式缓存) 时以上游为准
+    pub fn apply(&self, usage: &mut Usage) {
+        if usage.cache_read_input_tokens.unwrap_or(0) > 0 || usage.input_tokens == 0 {
+            return;
+        }
+        let input = usage.input_tokens;
+        let read = ((input as f64 * self.read_ratio) as u32).min(input);
+        let creation = ((input as f64 * self.creation_ratio) as u32).min(input - read);
+        usage.input_tokens = input - read - creation;
+        usage.cache_read_input_tokens = Some(read);
+        usage.cache_creation_input_tokens = Some(creation);
+    }
+}
+
+#[derive(Debug)]
+struct Checkpoint {
+    hash: u64,
+    tokens: u64,
+    ttl: Duration,
+}
+
+fn marker_ttl(block: &Value) -> Option<Duration> {
+    let cc = block.get("cache_control")?;
+    if cc.get("type").and_then(|t| t.as_str()) != Some("ephemeral") {
+        return None;
+    }
+    Some(match cc.get("ttl").and_then(|t| t.as_str()) {
+        Some("1h") => EXTENDED_TTL,
+        _ => DEFAULT_TTL,
+    })
+}
+
+/// 按请求顺序展开可缓存的块: (角色标签, 块)
+fn collect_blocks(body: &Value) -> Vec<(&'static str, &Value)> {
+    let mut blocks = Vec::new();
+    if let Some(tools) = body.get("tools").and_then(|t| t.as_array()) {
+        blocks.extend(tools.iter().map(|t| ("tool", t)));
+    }
+    match body.get("system") {
+        Some(Value::Array(items)) => blocks.extend(items.iter().map(|b| ("system", b))),
+        Some(s @ Value::String(_)) => blocks.push(("system", s)),
+        _ => {}
+    }
+    if let Some(messages) = body.get("messages").and_then(|m| m.as_array()) {
+        for msg in messages {
+            let role = match msg.get("role").and_then(|r| r.as_str()) {
+                Some("assistant") => "assistant",
+                _ => "user",
+            };
+            match msg.get("content") {
+                Some(Value::Array(items)) => blocks.extend(items.iter().map(|b| (role, b))),
+                Some(c) => blocks.push((role, c)),
+                None => {}
+            }
+        }
+    }
+    blocks
+}
+
+fn collect_checkpoints(body: &Value) -> (Vec<Checkpoint>, u64) {
+    let mut hasher = DefaultHasher::new();
+    body.get("model").and_then(|m| m.as_str()).unwrap_or_default().hash(&mut hasher);
+
+    let mut checkpoints = Vec::new();
+    let mut tokens = 0u64;
+    for (role, block) in collect_blocks(body) {
+        // 哈希时去掉 cache_control 本身，断点位置移动不影响前缀一致性
+        let mut stripped = block.clone();
+        if let Some(obj) = stripped.as_object_mut() {
+            obj.remove("cache_control");
+        }
+        role.hash(&mut hasher);
+        serde_json::to_string(&stripped).unwrap_or_default().hash(&mut hasher);
+        tokens += estimate_prompt_tokens(&stripped);
+
+        if let Some(ttl) = marker_ttl(block) {
+            checkpoints.push(Checkpoint { hash: hasher.clone().finish(), tokens, ttl });
+        }
+    }
+    (checkpoints, tokens)
+}
+
+/// 分析请求中的 cache_control 断点并登记到前缀缓存；没有有效断点时返回 None
+pub fn analyze(body: &Value) -> Option<CacheHint> {
+    let (checkpoints, total) = collect_checkpoints(body);
+    let checkpoints: Vec<_> = checkpoints.into_iter().filter(|c| c.tokens >= MIN_CACHEABLE_TOKENS).collect();
+    if checkpoints.is_empty() || total == 0 {
+        return None;
+    }
+
+    let now = Instant::now();
+    let read_tokens = checkpoints
+        .iter()
+        .rev()
+        .find(|c| PREFIX_CACHE.get(&c.hash).map(|exp| *exp > now).unwrap_or(false))
+        .map(|c| c.tokens)
+        .unwrap_or(0);
+    let cached_tokens = checkpoints.last().map(|c| c.tokens).unwrap_or(0);
+    let creation_tokens = cached_tokens.saturating_sub(read_tokens);
+
+    if PREFIX_CACHE.len() > MAX_ENTRIES {
+        PREFIX_CACHE.retain(|_, exp| *exp > now);
+    }
+    // 命中与新写入的断点都刷新 TTL
+    for c in &checkpoints {
+        PREFIX_CACHE.insert(c.hash, now + c.ttl);
+    }
+
+    tracing::debug!(
+        "[Prompt-Cache] checkpoints: {}, est. read: {}, creation: {}, total: {}",
+        checkpoints.len(),
+        read_tokens,
+        creation_tokens,
+        total
+    );
+
+    Some(CacheHint {
+        read_ratio: (read_tokens as f64 / total as f64).min(1.0),
+        creation_ratio: (creation_tokens as f64 / total as f64).min(1.0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(model: &str, user_turns: &[&str]) -> Value {
+        let big = "lorem ipsum dolor sit amet ".repeat(400);
+        let mut messages: Vec<Value> = user_turns
+            .iter()
+            .map(|t| json!({"role": "user", "content": [{"type": "text", "text": t}]}))
+            .collect();
+        // 标记最后一条消息，模拟 Claude Code 的增量缓存断点
+        if let Some(last) = messages.last_mut() {
+            last["content"][0]["cache_control"] = json!({"type": "ephemeral"});
+        }
+        json!({
+            "model": model,
+            "system": [{"type": "text", "text": big, "cache_control": {"type": "ephemeral"}}],
+            "messages": messages,
+        })
+    }
+
+    #[test]
+    fn test_first_request_creates_then_hits() {
+        let first = request("claude-test-hit", &["hello"]);
+        let hint = analyze(&first).unwrap();
+        assert_eq!(hint.read_ratio, 0.0);
+        assert!(hint.creation_ratio > 0.9);
+
+        // 追加对话后，system 断点命中
+        let second = request("claude-test-hit", &["hello", "and then?"]);
+        let hint = analyze(&second).unwrap();
+        assert!(hint.read_ratio > 0.9, "{:?}", hint);
+
+        // 完全相同的请求，最后一个断点命中
+        let hint = analyze(&second).unwrap();
+        assert!(hint.read_ratio > 0.99);
+        assert!(hint.creation_ratio < 0.01);
+    }
+
+    #[test]
+    fn test_model_isolated_and_small_prefix_ignored() {
+        analyze(&request("claude-test-a", &["hi"])).unwrap();
+        let hint = analyze(&request("claude-test-b", &["hi"])).unwrap();
+        assert_eq!(hint.read_ratio, 0.0);
+
+        let small = json!({
+            "model": "claude-test-small",
+            "system": [{"type": "text", "text": "short", "cache_control": {"type": "ephemeral"}}],
+            "messages": [{"role": "user", "content": "hi"}],
+        });
+        assert!(analyze(&small).is_none());
+        assert!(analyze(&json!({"model": "x", "messages": []})).is_none());
+    }
+
+    #[test]
+    fn test_apply_splits_input_tokens() {
+        let hint = CacheHint { read_ratio: 0.75, creation_ratio: 0.2 };
+        let mut usage = Usage {
+            input_tokens: 1000,
+            output_tokens: 10,
+            cache_read_input_tokens: Some(0),
+            cache_creation_input_tokens: Some(0),
+            server_tool_use: None,
+        };
+        hint.apply(&mut usage);
+        assert_eq!(usage.cache_read_input_tokens, Some(750));
+        assert_eq!(usage.cache_creation_input_tokens, Some(200));
+        assert_eq!(usage.input_tokens, 50);
+
+        // 上游已报告真实缓存命中时不覆盖
+        let mut usage = Usage {
+            input_tokens: 1000,
+            output_tokens: 10,
+            cache_read_input_tokens: Some(300),
+            cache_creation_input_tokens: Some(0),
+            server_tool_use: None,
+        };
+        hint.apply(&mut usage);
+        assert_eq!(usage.cache_read_input_tokens, Some(300));
+        assert_eq!(usage.input_tokens, 1000);
+    }
+}
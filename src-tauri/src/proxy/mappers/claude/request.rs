@@ -334,6 +334,7 @@ pub fn transform_claude_request_in(
     project_id: &str,
     is_retry: bool,
 ) -> Result<Value, String> {
+    let _span = tracing::info_span!(target: crate::proxy::telemetry::TARGET, "transform", ag.protocol = "claude").entered();
     // [CRITICAL FIX] 预先清理所有消息中的 cache_control 字段
     // 这解决了 VS Code 插件等客户端在多轮对话中将历史消息的 cache_control 字段
     // 原封不动发回导致的 "Extra inputs are not permitted" 错误
@@ -450,6 +451,12 @@ pub fn transform_claude_request_in(
         }
     }
 
+    // 预填充续写 (末尾为 assistant 文本消息) 与 thinking 不兼容: 上游要求末尾 assistant 消息以 thinking 块开头
+    if is_thinking_enabled && ends_with_prefill(&claude_req.messages) {
+        tracing::info!("[Thinking-Mode] Assistant prefill detected, disabling thinking for this request");
+        is_thinking_enabled = false;
+    }
+
     // [FIX #295 & #298] If thinking enabled but no signature available,
     // disable thinking to prevent Gemini 3 Pro rejection
     if is_thinking_enabled {
@@ -628,6 +635,22 @@ fn should_disable_thinking_due_to_history(messages: &[Message]) -> bool {
     false
 }
 
+/// 最后一条消息是否为 assistant 预填充 (不含工具调用与 thinking 块)
+fn ends_with_prefill(messages: &[Message]) -> bool {
+    match messages.last() {
+        Some(msg) if msg.role == "assistant" => match &msg.content {
+            MessageContent::String(text) => !text.trim().is_empty(),
+            MessageContent::Array(blocks) => {
+                blocks.iter().any(|b| matches!(b, ContentBlock::Text { .. }))
+                    && !blocks.iter().any(|b| {
+                        matches!(b, ContentBlock::ToolUse { .. } | ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. })
+                    })
+            }
+        },
+        _ => false,
+    }
+}
+
 /// Check if thinking mode should be enabled by default for a given model
 ///
 /// Claude Code v2.0.67+ enables thinking by default for Opus 4.5 models.
@@ -949,7 +972,7 @@ fn build_contents(
                         if source.source_type == "base64" {
                             parts.push(json!({
                                 "inlineData": {
-                                    "mimeType": source.media_type,
+                                    "mimeType": crate::proxy::common::mime_sniff::resolve(Some(source.media_type.as_str()), &source.data, "image/jpeg"),
                                     "data": source.data
                                 }
                             }));
@@ -960,7 +983,7 @@ fn build_contents(
                         if source.source_type == "base64" {
                             parts.push(json!({
                                 "inlineData": {
-                                    "mimeType": source.media_type,
+                                    "mimeType": crate::proxy::common::mime_sniff::resolve(Some(source.media_type.as_str()), &source.data, "application/pdf"),
                                     "data": source.data
                                 }
                             }));
@@ -532,12 +532,14 @@ impl NonStreamingProcessor {
             stop_reason: stop_reason.to_string(),
             stop_sequence: None,
             usage,
+            x_ag_truncated: None,
         }
     }
 }
 
 /// 转换 Gemini 响应为 Claude 响应 (公共接口)
 pub fn transform_response(gemini_response: &GeminiResponse, scaling_enabled: bool, context_limit: u32, session_id: Option<String>, model_name: String) -> Result<ClaudeResponse, String> {
+    let _span = tracing::info_span!(target: crate::proxy::telemetry::TARGET, "map_back", ag.protocol = "claude", ag.streaming = false).entered();
     let mut processor = NonStreamingProcessor::new(session_id, model_name);
     Ok(processor.process(gemini_response, scaling_enabled, context_limit))
 }
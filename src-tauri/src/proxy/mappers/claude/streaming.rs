@@ -228,9 +228,13 @@ pub struct StreamingState {
     pub scaling_enabled: bool,
     // [NEW] Context limit for smart threshold recovery (default to 1M)
     pub context_limit: u32,
+    // [NEW] Prompt caching 模拟的用量估算
+    pub cache_hint: Option<super::prompt_cache::CacheHint>,
     // [NEW] MCP XML Bridge 缓冲区
     pub mcp_xml_buffer: String,
     pub in_mcp_xml: bool,
+    // 上游中途断开且无法续传: 以 max_tokens 结束并标记 x_ag_truncated
+    pub truncated: bool,
 }
 
 impl StreamingState {
@@ -252,8 +256,10 @@ impl StreamingState {
             session_id: None,
             scaling_enabled: false,
             context_limit: 1_048_576, // Default to 1M
+            cache_hint: None,
             mcp_xml_buffer: String::new(),
             in_mcp_xml: false,
+            truncated: false,
         }
     }
 
@@ -276,7 +282,13 @@ impl StreamingState {
         let usage = raw_json
             .get("usageMetadata")
             .and_then(|u| serde_json::from_value::<UsageMetadata>(u.clone()).ok())
-            .map(|u| to_claude_usage(&u, self.scaling_enabled, self.context_limit));
+            .map(|u| to_claude_usage(&u, self.scaling_enabled, self.context_limit))
+            .map(|mut u| {
+                if let Some(hint) = &self.cache_hint {
+                    hint.apply(&mut u);
+                }
+                u
+            });
 
         let mut message = json!({
             "id": raw_json.get("responseId")
@@ -452,13 +464,13 @@ impl StreamingState {
         // 确定 stop_reason
         let stop_reason = if self.used_tool {
             "tool_use"
-        } else if finish_reason == Some("MAX_TOKENS") {
+        } else if finish_reason == Some("MAX_TOKENS") || self.truncated {
             "max_tokens"
         } else {
             "end_turn"
         };
 
-        let usage = usage_metadata
+        let mut usage = usage_metadata
             .map(|u| to_claude_usage(u, self.scaling_enabled, self.context_limit))
             .unwrap_or(Usage {
                 input_tokens: 0,
@@ -467,15 +479,19 @@ impl StreamingState {
                 cache_creation_input_tokens: None,
                 server_tool_use: None,
             });
+        if let Some(hint) = &self.cache_hint {
+            hint.apply(&mut usage);
+        }
 
-        chunks.push(self.emit(
-            "message_delta",
-            json!({
-                "type": "message_delta",
-                "delta": { "stop_reason": stop_reason, "stop_sequence": null },
-                "usage": usage
-            }),
-        ));
+        let mut message_delta = json!({
+            "type": "message_delta",
+            "delta": { "stop_reason": stop_reason, "stop_sequence": null },
+            "usage": usage
+        });
+        if self.truncated {
+            message_delta["x_ag_truncated"] = json!(true);
+        }
+        chunks.push(self.emit("message_delta", message_delta));
 
         if !self.message_stop_sent {
             chunks.push(Bytes::from(
@@ -487,6 +503,11 @@ impl StreamingState {
         chunks
     }
 
+    /// 是否已向客户端输出过内容块
+    pub fn has_output(&self) -> bool {
+        self.block_index > 0 || self.block_type != BlockType::None
+    }
+
     /// 标记使用了工具
     pub fn mark_tool_used(&mut self) {
         self.used_tool = true;
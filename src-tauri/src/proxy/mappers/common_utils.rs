@@ -1,6 +1,7 @@
 // Common utilities for request mapping across all protocols
 // Provides unified grounding/networking logic
 
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 /// Request configuration after grounding resolution
@@ -96,19 +97,132 @@ pub fn resolve_request_config(
     }
 }
 
+/// Aspect ratio explicitly requested via a model name suffix (e.g. `-16x9`), if any
+pub fn aspect_ratio_from_suffix(model_name: &str) -> Option<&'static str> {
+    if model_name.contains("-21x9") || model_name.contains("-21-9") { Some("21:9") }
+    else if model_name.contains("-16x9") || model_name.contains("-16-9") { Some("16:9") }
+    else if model_name.contains("-9x16") || model_name.contains("-9-16") { Some("9:16") }
+    else if model_name.contains("-4x3") || model_name.contains("-4-3") { Some("4:3") }
+    else if model_name.contains("-3x4") || model_name.contains("-3-4") { Some("3:4") }
+    else if model_name.contains("-1x1") || model_name.contains("-1-1") { Some("1:1") }
+    else { None }
+}
+
+/// Aspect ratios accepted by Gemini `imageConfig.aspectRatio`
+pub const SUPPORTED_ASPECT_RATIOS: &[&str] = &["1:1", "2:3", "3:2", "3:4", "4:3", "4:5", "5:4", "9:16", "16:9", "21:9"];
+/// Values accepted by Gemini `imageConfig.imageSize`
+pub const SUPPORTED_IMAGE_SIZES: &[&str] = &["1K", "2K", "4K"];
+
+/// Map an OpenAI `size` (e.g. `1792x1024`) to the closest Gemini aspect ratio
+pub fn aspect_ratio_from_size(size: &str) -> &'static str {
+    let Some((w, h)) = size
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.trim().parse::<f64>().ok()?, h.trim().parse::<f64>().ok()?)))
+        .filter(|(w, h)| *w > 0.0 && *h > 0.0)
+    else {
+        return "1:1"; // 默认 1024x1024
+    };
+    let ratio_of = |r: &str| {
+        let (a, b) = r.split_once(':').unwrap_or(("1", "1"));
+        a.parse::<f64>().unwrap_or(1.0) / b.parse::<f64>().unwrap_or(1.0)
+    };
+    // 按对数距离取最接近的比例 (1792x768 -> 21:9, 1536x1024 -> 3:2)
+    SUPPORTED_ASPECT_RATIOS
+        .iter()
+        .copied()
+        .min_by(|a, b| {
+            let da = ((w / h).ln() - ratio_of(a).ln()).abs();
+            let db = ((w / h).ln() - ratio_of(b).ln()).abs();
+            da.total_cmp(&db)
+        })
+        .unwrap_or("1:1")
+}
+
+/// Image generation controls shared by `/v1/images/*` and chat requests to image models:
+/// the OpenAI `size` field plus vendor extensions (`aspect_ratio`, `image_size`, `negative_prompt`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImageGenOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+    /// e.g. "16:9"; takes precedence over `size`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aspect_ratio: Option<String>,
+    /// "1K" / "2K" / "4K"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_size: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub negative_prompt: Option<String>,
+}
+
+impl ImageGenOptions {
+    /// 显式指定的宽高比: aspect_ratio > size (`auto` 视为未指定)
+    fn explicit_aspect_ratio(&self) -> Option<&'static str> {
+        if let Some(ratio) = self.aspect_ratio.as_deref() {
+            return SUPPORTED_ASPECT_RATIOS.iter().copied().find(|r| *r == ratio.trim());
+        }
+        self.size
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty() && *s != "auto")
+            .map(aspect_ratio_from_size)
+    }
+
+    fn explicit_image_size(&self) -> Option<&'static str> {
+        let size = self.image_size.as_deref()?.trim();
+        SUPPORTED_IMAGE_SIZES.iter().copied().find(|s| s.eq_ignore_ascii_case(size))
+    }
+
+    /// 供独立图像端点返回 400 使用；对话请求中的非法值直接忽略
+    pub fn validate(&self) -> Result<(), String> {
+        if self.aspect_ratio.is_some() && self.explicit_aspect_ratio().is_none() {
+            return Err(format!("aspect_ratio must be one of {}", SUPPORTED_ASPECT_RATIOS.join(", ")));
+        }
+        if self.image_size.is_some() && self.explicit_image_size().is_none() {
+            return Err(format!("image_size must be one of {}", SUPPORTED_IMAGE_SIZES.join(", ")));
+        }
+        Ok(())
+    }
+
+    /// Overlay explicit options on an existing `imageConfig` (e.g. parsed from model suffixes)
+    pub fn apply(&self, image_config: &mut Value) {
+        let Some(obj) = image_config.as_object_mut() else { return };
+        if let Some(ratio) = self.explicit_aspect_ratio() {
+            obj.insert("aspectRatio".to_string(), json!(ratio));
+        }
+        if let Some(size) = self.explicit_image_size() {
+            obj.insert("imageSize".to_string(), json!(size));
+        }
+    }
+
+    /// Model suffixes first, then explicit options. Without `default_aspect`, `aspectRatio` is only sent
+    /// when explicitly requested (edits keep the input image's proportions).
+    pub fn image_config(&self, model: &str, default_aspect: bool) -> Value {
+        let (mut config, _) = parse_image_config(model);
+        if !default_aspect && aspect_ratio_from_suffix(model).is_none() {
+            if let Some(obj) = config.as_object_mut() {
+                obj.remove("aspectRatio");
+            }
+        }
+        self.apply(&mut config);
+        config
+    }
+
+    /// Gemini image models have no native negative prompt, so it is appended as an instruction
+    pub fn negative_prompt_text(&self) -> Option<String> {
+        self.negative_prompt
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| format!("Do not include any of the following in the image: {}", s))
+    }
+}
+
 /// Parse image configuration from model name suffixes
 /// Returns (image_config, clean_model_name)
 fn parse_image_config(model_name: &str) -> (Value, String) {
-    let mut aspect_ratio = "1:1";
+    let aspect_ratio = aspect_ratio_from_suffix(model_name).unwrap_or("1:1");
     let _image_size = "1024x1024"; // Default, not explicitly sent unless 4k/hd
 
-    if model_name.contains("-21x9") || model_name.contains("-21-9") { aspect_ratio = "21:9"; }
-    else if model_name.contains("-16x9") || model_name.contains("-16-9") { aspect_ratio = "16:9"; }
-    else if model_name.contains("-9x16") || model_name.contains("-9-16") { aspect_ratio = "9:16"; }
-    else if model_name.contains("-4x3") || model_name.contains("-4-3") { aspect_ratio = "4:3"; }
-    else if model_name.contains("-3x4") || model_name.contains("-3-4") { aspect_ratio = "3:4"; }
-    else if model_name.contains("-1x1") || model_name.contains("-1-1") { aspect_ratio = "1:1"; }
-
     let is_hd = model_name.contains("-4k") || model_name.contains("-hd");
     let is_2k = model_name.contains("-2k");
 
@@ -336,4 +450,26 @@ mod tests {
          assert_eq!(config_4k_wide["imageSize"], "4K");
          assert_eq!(config_4k_wide["aspectRatio"], "21:9");
     }
+
+    #[test]
+    fn test_image_gen_options() {
+        assert_eq!(aspect_ratio_from_size("1792x768"), "21:9");
+        assert_eq!(aspect_ratio_from_size("1792x1024"), "16:9");
+        assert_eq!(aspect_ratio_from_size("768x1024"), "3:4");
+        assert_eq!(aspect_ratio_from_size("1536x1024"), "3:2");
+        assert_eq!(aspect_ratio_from_size("auto"), "1:1");
+
+        let options = ImageGenOptions { size: Some("1024x1792".into()), image_size: Some("4k".into()), ..Default::default() };
+        let config = options.image_config("gemini-3-pro-image-2k-16x9", true);
+        assert_eq!(config, json!({ "aspectRatio": "9:16", "imageSize": "4K" }));
+
+        let options = ImageGenOptions { size: Some("1024x1792".into()), aspect_ratio: Some("21:9".into()), ..Default::default() };
+        assert_eq!(options.image_config("gemini-3-pro-image", true)["aspectRatio"], "21:9");
+        assert_eq!(ImageGenOptions::default().image_config("gemini-3-pro-image", false), json!({}));
+
+        let bad = ImageGenOptions { aspect_ratio: Some("7:3".into()), ..Default::default() };
+        assert!(bad.validate().is_err());
+        assert!(ImageGenOptions { image_size: Some("8K".into()), ..Default::default() }.validate().is_err());
+        assert!(ImageGenOptions { negative_prompt: Some("  ".into()), ..Default::default() }.negative_prompt_text().is_none());
+    }
 }
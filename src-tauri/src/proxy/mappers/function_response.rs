@@ -0,0 +1,279 @@
+// functionResponse 格式整形
+// 不同 Gemini 模型对工具结果的包装键 (`result` / `content` / `output`) 以及结构化 JSON 的接受程度不同。
+// mappers 统一生成 `{"result": <string>}`，这里按模型规则对最终 envelope 重新整形；
+// 上游以格式错误拒绝时记录到学习表，后续请求自动改用下一种候选格式。
+
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::config::{FunctionResponseRule, FunctionResponseWrapper};
+use once_cell::sync::Lazy;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResponseShape {
+    pub wrapper: FunctionResponseWrapper,
+    pub structured: bool,
+}
+
+impl Default for ResponseShape {
+    fn default() -> Self {
+        Self { wrapper: FunctionResponseWrapper::Result, structured: true }
+    }
+}
+
+/// 模型 -> 因格式错误已跳过的候选数
+static LEARNED: Lazy<Mutex<HashMap<String, usize>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+const WRAPPER_KEYS: &[&str] = &["result", "content", "output"];
+
+/// 结构化传递允许的最大嵌套深度，超出时回退为字符串
+const MAX_STRUCTURED_DEPTH: usize = 32;
+
+fn find_rule<'a>(rules: &'a [FunctionResponseRule], model: &str) -> Option<&'a FunctionResponseRule> {
+    rules
+        .iter()
+        .find(|r| r.model == model)
+        .or_else(|| rules.iter().find(|r| r.model.contains('*') && wildcard_match(&r.model, model)))
+}
+
+/// 候选格式: 配置 (或默认) 格式 → 同包装的字符串形式 → content 字符串 → result 字符串
+fn candidates(base: ResponseShape) -> Vec<ResponseShape> {
+    let mut list = vec![base];
+    for next in [
+        ResponseShape { wrapper: base.wrapper, structured: false },
+        ResponseShape { wrapper: FunctionResponseWrapper::Content, structured: false },
+        ResponseShape { wrapper: FunctionResponseWrapper::Result, structured: false },
+    ] {
+        if !list.contains(&next) {
+            list.push(next);
+        }
+    }
+    list
+}
+
+pub fn resolve_shape(model: &str, rules: &[FunctionResponseRule], auto_detect: bool) -> ResponseShape {
+    let base = find_rule(rules, model)
+        .map(|r| ResponseShape { wrapper: r.wrapper, structured: r.structured })
+        .unwrap_or_default();
+    if !auto_detect {
+        return base;
+    }
+    let skipped = LEARNED.lock().ok().and_then(|m| m.get(model).copied()).unwrap_or(0);
+    let list = candidates(base);
+    list[skipped.min(list.len() - 1)]
+}
+
+/// 上游错误是否由 functionResponse 格式引起
+pub fn is_format_rejection(status: u16, error_text: &str) -> bool {
+    if status != 400 {
+        return false;
+    }
+    let text = error_text.to_lowercase();
+    text.contains("function_response") || text.contains("functionresponse")
+}
+
+/// 记录格式错误，返回 true 表示该模型还有可切换的候选格式
+pub fn record_rejection(model: &str, status: u16, error_text: &str) -> bool {
+    if !is_format_rejection(status, error_text) {
+        return false;
+    }
+    let Ok(mut learned) = LEARNED.lock() else {
+        return false;
+    };
+    let skipped = learned.entry(model.to_string()).or_insert(0);
+    // 候选最多 4 种
+    if *skipped >= 3 {
+        return false;
+    }
+    *skipped += 1;
+    tracing::warn!(
+        "[FunctionResponse] {} 拒绝了当前工具结果格式，后续请求切换到第 {} 种候选格式",
+        model,
+        *skipped + 1
+    );
+    true
+}
+
+/// 取出 mappers 生成的包装内的实际输出
+fn unwrap_payload(response: &Value) -> Value {
+    if let Some(obj) = response.as_object() {
+        if obj.len() == 1 {
+            if let Some((k, v)) = obj.iter().next() {
+                if WRAPPER_KEYS.contains(&k.as_str()) {
+                    return v.clone();
+                }
+            }
+        }
+    }
+    response.clone()
+}
+
+fn json_depth(value: &Value) -> usize {
+    match value {
+        Value::Array(arr) => 1 + arr.iter().map(json_depth).max().unwrap_or(0),
+        Value::Object(obj) => 1 + obj.values().map(json_depth).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// 工具输出是合法的 JSON 对象/数组且未超出大小、深度限制时解析为结构化值
+fn parse_structured(text: &str, max_bytes: usize) -> Option<Value> {
+    let text = text.trim();
+    if text.len() > max_bytes || !(text.starts_with('{') || text.starts_with('[')) {
+        return None;
+    }
+    serde_json::from_str::<Value>(text)
+        .ok()
+        .filter(|v| (v.is_object() || v.is_array()) && json_depth(v) <= MAX_STRUCTURED_DEPTH)
+}
+
+pub fn shape_response(response: &Value, shape: ResponseShape, max_structured_bytes: usize) -> Value {
+    let mut payload = unwrap_payload(response);
+    if shape.structured {
+        if let Some(parsed) = payload.as_str().and_then(|s| parse_structured(s, max_structured_bytes)) {
+            payload = parsed;
+        }
+    } else if !payload.is_string() {
+        payload = Value::String(payload.to_string());
+    }
+
+    let key = match shape.wrapper {
+        FunctionResponseWrapper::Inline => {
+            if let Value::Object(obj) = payload {
+                return Value::Object(obj);
+            }
+            "result"
+        }
+        FunctionResponseWrapper::Result => "result",
+        FunctionResponseWrapper::Content => "content",
+        FunctionResponseWrapper::Output => "output",
+    };
+    let mut wrapped = Map::new();
+    wrapped.insert(key.to_string(), payload);
+    Value::Object(wrapped)
+}
+
+/// 对 v1internal 请求体中的所有 functionResponse 按模型格式整形，返回 true 表示有修改
+pub fn apply_function_response_format(
+    body: &mut Value,
+    rules: &[FunctionResponseRule],
+    auto_detect: bool,
+    max_structured_bytes: usize,
+) -> bool {
+    let model = body.get("model").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+    let shape = resolve_shape(&model, rules, auto_detect);
+
+    let Some(contents) = body
+        .get_mut("request")
+        .and_then(|r| r.get_mut("contents"))
+        .and_then(|c| c.as_array_mut())
+    else {
+        return false;
+    };
+
+    let mut changed = false;
+    for part in contents
+        .iter_mut()
+        .filter_map(|c| c.get_mut("parts").and_then(|p| p.as_array_mut()))
+        .flatten()
+    {
+        let Some(response) = part.get_mut("functionResponse").and_then(|f| f.get_mut("response")) else {
+            continue;
+        };
+        let shaped = shape_response(response, shape, max_structured_bytes);
+        if *response != shaped {
+            *response = shaped;
+            changed = true;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const LIMIT: usize = 64 * 1024;
+
+    fn envelope(model: &str, response: Value) -> Value {
+        json!({
+            "model": model,
+            "request": { "contents": [
+                { "role": "user", "parts": [{ "functionResponse": { "name": "ls", "response": response, "id": "call_1" } }] }
+            ]}
+        })
+    }
+
+    #[test]
+    fn test_default_shape_structures_json_output() {
+        let mut body = envelope("gemini-2.5-flash", json!({"result": "{\"files\": [\"a.txt\"]}"}));
+        assert!(apply_function_response_format(&mut body, &[], false, LIMIT));
+        let resp = &body["request"]["contents"][0]["parts"][0]["functionResponse"]["response"];
+        assert_eq!(resp["result"]["files"][0], "a.txt");
+
+        // 普通文本保持不变
+        let mut body = envelope("gemini-2.5-flash", json!({"result": "plain text"}));
+        assert!(!apply_function_response_format(&mut body, &[], false, LIMIT));
+    }
+
+    #[test]
+    fn test_structured_size_and_depth_caps() {
+        let shape = ResponseShape::default();
+        let big = format!("{{\"data\": \"{}\"}}", "x".repeat(200));
+        assert!(shape_response(&json!({"result": big.clone()}), shape, 64)["result"].is_string());
+        assert!(shape_response(&json!({"result": big}), shape, LIMIT)["result"].is_object());
+
+        let deep = format!("{}{}", "[".repeat(40), "]".repeat(40));
+        assert!(shape_response(&json!({"result": deep}), shape, LIMIT)["result"].is_string());
+
+        // 非法 JSON 与标量原样保留
+        assert_eq!(shape_response(&json!({"result": "{not json"}), shape, LIMIT), json!({"result": "{not json"}));
+        assert_eq!(shape_response(&json!({"result": "42"}), shape, LIMIT), json!({"result": "42"}));
+    }
+
+    #[test]
+    fn test_rules_select_wrapper() {
+        let rules = vec![
+            FunctionResponseRule {
+                model: "gemini-2.0-*".to_string(),
+                wrapper: FunctionResponseWrapper::Content,
+                structured: false,
+            },
+            FunctionResponseRule {
+                model: "gemini-3-pro".to_string(),
+                wrapper: FunctionResponseWrapper::Inline,
+                structured: true,
+            },
+        ];
+        let shape = resolve_shape("gemini-2.0-flash", &rules, false);
+        assert_eq!(shape_response(&json!({"result": "{\"a\":1}"}), shape, LIMIT), json!({"content": "{\"a\":1}"}));
+
+        let shape = resolve_shape("gemini-3-pro", &rules, false);
+        assert_eq!(shape_response(&json!({"result": "{\"a\":1}"}), shape, LIMIT), json!({"a": 1}));
+        assert_eq!(shape_response(&json!({"result": "done"}), shape, LIMIT), json!({"result": "done"}));
+    }
+
+    #[test]
+    fn test_rejection_learning_advances_candidates() {
+        let model = "gemini-test-learning";
+        assert_eq!(resolve_shape(model, &[], true), ResponseShape::default());
+        assert!(!record_rejection(model, 400, "temperature out of range"));
+        assert!(record_rejection(model, 400, "Invalid value at 'contents[1].parts[0].function_response.response'"));
+        assert_eq!(
+            resolve_shape(model, &[], true),
+            ResponseShape { wrapper: FunctionResponseWrapper::Result, structured: false }
+        );
+        // 关闭自动检测时忽略学习结果
+        assert_eq!(resolve_shape(model, &[], false), ResponseShape::default());
+
+        assert!(record_rejection(model, 400, "functionResponse"));
+        assert_eq!(resolve_shape(model, &[], true).wrapper, FunctionResponseWrapper::Content);
+        // 候选耗尽后停在最后一种
+        record_rejection(model, 400, "functionResponse");
+        record_rejection(model, 400, "functionResponse");
+        assert!(!record_rejection(model, 400, "functionResponse"));
+        assert_eq!(resolve_shape(model, &[], true).wrapper, FunctionResponseWrapper::Content);
+    }
+}
@@ -3,6 +3,7 @@ use serde_json::{json, Value};
 
 /// 包装请求体为 v1internal 格式
 pub fn wrap_request(body: &Value, project_id: &str, mapped_model: &str, session_id: Option<&str>) -> Value {
+    let _span = tracing::info_span!(target: crate::proxy::telemetry::TARGET, "transform", ag.protocol = "gemini").entered();
     // 优先使用传入的 mapped_model，其次尝试从 body 获取
     let original_model = body.get("model").and_then(|v| v.as_str()).unwrap_or(mapped_model);
     
@@ -19,6 +20,19 @@ pub fn wrap_request(body: &Value, project_id: &str, mapped_model: &str, session_
     // 深度清理 [undefined] 字符串 (Cherry Studio 等客户端常见注入)
     crate::proxy::mappers::common_utils::deep_clean_undefined(&mut inner_request);
 
+    // 按载荷魔数更正 inlineData 的 mimeType (客户端常把 PNG 标成 JPEG)
+    if let Some(contents) = inner_request.get_mut("contents").and_then(|c| c.as_array_mut()) {
+        for part in contents
+            .iter_mut()
+            .filter_map(|c| c.get_mut("parts").and_then(|p| p.as_array_mut()))
+            .flatten()
+        {
+            if part.get("inlineData").is_some() || part.get("inline_data").is_some() {
+                crate::proxy::common::mime_sniff::fix_inline_data(part);
+            }
+        }
+    }
+
     // [FIX #765] Inject thought_signature into functionCall parts
     if let Some(s_id) = session_id {
         if let Some(contents) = inner_request.get_mut("contents").and_then(|c| c.as_array_mut()) {
@@ -192,6 +206,7 @@ mod test_fixes {
 
 /// 解包响应（提取 response 字段）
 pub fn unwrap_response(response: &Value) -> Value {
+    let _span = tracing::info_span!(target: crate::proxy::telemetry::TARGET, "map_back", ag.protocol = "gemini", ag.streaming = false).entered();
     response.get("response").unwrap_or(response).clone()
 }
 
@@ -0,0 +1,257 @@
+// 聊天响应中的生成图片
+// 图片模型在聊天响应里以 inlineData part 返回图片。默认以 Markdown data URL 写入 content；
+// 按配置也可以放入 message.images 扩展数组 (OpenRouter 兼容)，并可另存到本地目录、在响应中附带路径。
+// 这里在 Gemini 层为图片 part 标注呈现方式 (imageOutput 字段)，协议 mapper 据此渲染，mapper 本身无需读取配置。
+
+use base64::Engine as _;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::proxy::config::{ImageOutputConfig, ImageOutputMode};
+use crate::proxy::upstream::resume::GeminiByteStream;
+use crate::proxy::upstream::sse_rewrite::{rewrite_sse_stream, SseRewriter};
+
+/// 单次请求生效的输出设置
+#[derive(Debug, Clone)]
+pub struct ImageOutput {
+    mode: ImageOutputMode,
+    save_dir: Option<PathBuf>,
+    link_saved_files: bool,
+}
+
+impl ImageOutput {
+    /// 默认配置 (Markdown + 不保存) 与 mapper 的原有行为一致，返回 None 跳过处理
+    pub fn from_config(config: &ImageOutputConfig) -> Option<Self> {
+        let save_dir = Some(config.save_dir.trim()).filter(|d| !d.is_empty()).map(PathBuf::from);
+        if config.mode == ImageOutputMode::Markdown && save_dir.is_none() {
+            return None;
+        }
+        Some(Self { mode: config.mode, save_dir, link_saved_files: config.link_saved_files })
+    }
+}
+
+fn extension_for(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/jpeg" => "jpg",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        _ => "png",
+    }
+}
+
+fn save_image(dir: &Path, mime_type: &str, data: &str) -> Result<PathBuf, String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| format!("invalid image data: {}", e))?;
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let name = format!(
+        "{}-{}.{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        &id[..8],
+        extension_for(mime_type)
+    );
+    let path = dir.join(name);
+    std::fs::write(&path, bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(std::fs::canonicalize(&path).unwrap_or(path))
+}
+
+/// 目录中由 save_image 写入的图片 (`YYYYMMDD-HHMMSS-xxxxxxxx.ext`)，用户放在同一目录的其他文件不在其列
+pub fn list_saved_images(dir: &str) -> Vec<PathBuf> {
+    let dir = dir.trim();
+    if dir.is_empty() {
+        return Vec::new();
+    }
+    let is_saved_name = |name: &str| {
+        let Some((stem, ext)) = name.rsplit_once('.') else { return false };
+        let b = stem.as_bytes();
+        matches!(ext, "png" | "jpg" | "webp" | "gif")
+            && b.len() == 24
+            && b[8] == b'-'
+            && b[15] == b'-'
+            && b.iter().enumerate().all(|(i, c)| match i {
+                8 | 15 => true,
+                0..=14 => c.is_ascii_digit(),
+                _ => c.is_ascii_hexdigit(),
+            })
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.file_name().and_then(|n| n.to_str()).is_some_and(is_saved_name))
+        .collect()
+}
+
+/// 为单个图片 part 标注呈现方式；需要时先保存到本地
+fn annotate_part(part: &mut Value, output: &ImageOutput) {
+    let Some(inline) = part.get("inlineData") else { return };
+    let mime_type = inline.get("mimeType").and_then(|m| m.as_str()).unwrap_or("image/png").to_string();
+    let data = inline.get("data").and_then(|d| d.as_str()).unwrap_or_default();
+    if data.is_empty() || !mime_type.starts_with("image/") || part.get("imageOutput").is_some() {
+        return;
+    }
+
+    let path = output.save_dir.as_deref().and_then(|dir| match save_image(dir, &mime_type, data) {
+        Ok(path) => {
+            tracing::info!("[ImageOutput] 已保存生成图片: {}", path.display());
+            Some(path)
+        }
+        Err(e) => {
+            tracing::warn!("[ImageOutput] 保存生成图片失败: {}", e);
+            None
+        }
+    });
+    let linked = match (&path, output.link_saved_files) {
+        (Some(path), true) => url::Url::from_file_path(path).ok().map(|u| u.to_string()),
+        _ => None,
+    };
+    let url = linked.clone().unwrap_or_else(|| format!("data:{};base64,{}", mime_type, data));
+    part["imageOutput"] = json!({
+        "markdown": output.mode != ImageOutputMode::Images,
+        "images": output.mode != ImageOutputMode::Markdown,
+        "url": url,
+        "path": path.map(|p| p.display().to_string()),
+    });
+    // 已改用文件地址时不再携带 base64
+    if linked.is_some() {
+        if let Some(obj) = part.as_object_mut() {
+            obj.remove("inlineData");
+        }
+    }
+}
+
+fn annotate(value: &mut Value, output: &ImageOutput) {
+    let inner = if value.get("response").is_some() { &mut value["response"] } else { value };
+    let Some(candidates) = inner.get_mut("candidates").and_then(|c| c.as_array_mut()) else { return };
+    for candidate in candidates {
+        let Some(parts) = candidate.pointer_mut("/content/parts").and_then(|p| p.as_array_mut()) else { continue };
+        for part in parts {
+            annotate_part(part, output);
+        }
+    }
+}
+
+/// 非流式响应
+pub fn apply_to_response(response: &mut Value, output: Option<&ImageOutput>) {
+    if let Some(output) = output {
+        annotate(response, output);
+    }
+}
+
+struct ImageOutputRewriter(ImageOutput);
+
+impl SseRewriter for ImageOutputRewriter {
+    fn rewrite(&mut self, event: &mut Value) -> bool {
+        annotate(event, &self.0);
+        true
+    }
+}
+
+/// 流式响应: 未开启时原样返回
+pub fn apply_to_stream(stream: GeminiByteStream, output: Option<ImageOutput>) -> GeminiByteStream {
+    match output {
+        Some(output) => rewrite_sse_stream(stream, ImageOutputRewriter(output)),
+        None => stream,
+    }
+}
+
+/// mapper 渲染结果: 写入 content 的 Markdown 与 message.images 数组项
+#[derive(Debug, Default, PartialEq)]
+pub struct RenderedImage {
+    pub markdown: Option<String>,
+    pub image: Option<Value>,
+}
+
+/// 渲染图片 part；非图片 part 返回 None。未标注的 part 保持原有的 Markdown data URL 输出
+pub fn render_part(part: &Value) -> Option<RenderedImage> {
+    if let Some(annotation) = part.get("imageOutput") {
+        let url = annotation.get("url").and_then(|u| u.as_str()).unwrap_or_default();
+        let path = annotation.get("path").and_then(|p| p.as_str());
+        let markdown = annotation.get("markdown").and_then(|m| m.as_bool()).unwrap_or(true).then(|| match path {
+            // data URL 无法体现保存位置，路径写入图片标题
+            Some(path) if url.starts_with("data:") => format!("![image]({} \"{}\")", url, path.replace('"', "'")),
+            _ => format!("![image]({})", url),
+        });
+        let image = annotation.get("images").and_then(|m| m.as_bool()).unwrap_or(false).then(|| {
+            let mut item = json!({ "type": "image_url", "image_url": { "url": url } });
+            if let Some(path) = path {
+                item["path"] = json!(path);
+            }
+            item
+        });
+        return Some(RenderedImage { markdown, image });
+    }
+    let img = part.get("inlineData")?;
+    let mime_type = img.get("mimeType").and_then(|v| v.as_str()).unwrap_or("image/png");
+    let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
+    if data.is_empty() {
+        return None;
+    }
+    Some(RenderedImage { markdown: Some(format!("![image](data:{};base64,{})", mime_type, data)), image: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_B64: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    fn response() -> Value {
+        json!({ "response": { "candidates": [{ "content": { "parts": [
+            { "text": "Here you go" },
+            { "inlineData": { "mimeType": "image/png", "data": PNG_B64 } }
+        ]}}]}})
+    }
+
+    #[test]
+    fn test_modes_and_default_rendering() {
+        assert!(ImageOutput::from_config(&ImageOutputConfig::default()).is_none());
+        let mut untouched = response();
+        apply_to_response(&mut untouched, None);
+        let part = &untouched["response"]["candidates"][0]["content"]["parts"][1];
+        let rendered = render_part(part).unwrap();
+        assert_eq!(rendered.markdown.unwrap(), format!("![image](data:image/png;base64,{})", PNG_B64));
+        assert!(rendered.image.is_none());
+        assert!(render_part(&json!({ "text": "hi" })).is_none());
+
+        let config = ImageOutputConfig { mode: ImageOutputMode::Images, ..Default::default() };
+        let mut resp = response();
+        apply_to_response(&mut resp, ImageOutput::from_config(&config).as_ref());
+        let rendered = render_part(&resp["response"]["candidates"][0]["content"]["parts"][1]).unwrap();
+        assert!(rendered.markdown.is_none());
+        assert_eq!(rendered.image.unwrap()["image_url"]["url"], format!("data:image/png;base64,{}", PNG_B64));
+    }
+
+    #[test]
+    fn test_saved_images_include_path() {
+        let dir = std::env::temp_dir().join(format!("image-output-{}", uuid::Uuid::new_v4()));
+        let mut config = ImageOutputConfig { mode: ImageOutputMode::Both, save_dir: dir.display().to_string(), link_saved_files: false };
+
+        let mut resp = response();
+        apply_to_response(&mut resp, ImageOutput::from_config(&config).as_ref());
+        let rendered = render_part(&resp["response"]["candidates"][0]["content"]["parts"][1]).unwrap();
+        let image = rendered.image.unwrap();
+        let path = image["path"].as_str().unwrap().to_string();
+        assert!(path.ends_with(".png") && Path::new(&path).exists());
+        assert!(rendered.markdown.unwrap().ends_with(&format!(" \"{}\")", path)));
+
+        config.link_saved_files = true;
+        let mut resp = response();
+        apply_to_response(&mut resp, ImageOutput::from_config(&config).as_ref());
+        let part = &resp["response"]["candidates"][0]["content"]["parts"][1];
+        assert!(part.get("inlineData").is_none());
+        let rendered = render_part(part).unwrap();
+        assert!(rendered.image.unwrap()["image_url"]["url"].as_str().unwrap().starts_with("file://"));
+
+        // 只列出本模块写入的图片
+        std::fs::write(dir.join("notes.png"), b"x").unwrap();
+        std::fs::write(dir.join("20260301-120000-zzzzzzzz.png"), b"x").unwrap();
+        let saved = list_saved_images(&dir.display().to_string());
+        assert_eq!(saved.len(), 2);
+        assert!(!saved.iter().any(|p| p.ends_with("notes.png") || p.ends_with("20260301-120000-zzzzzzzz.png")));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
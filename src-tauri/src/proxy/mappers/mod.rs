@@ -7,5 +7,13 @@ pub mod error_classifier;
 pub mod gemini;
 pub mod openai;
 pub mod signature_store;
+pub mod signature_degradation;
+pub mod function_response;
 pub mod tool_result_compressor;
 pub mod context_manager;
+pub mod prefill;
+pub mod stop_sequences;
+pub mod model_limits;
+pub mod quirks;
+pub mod image_output;
+pub mod capabilities;
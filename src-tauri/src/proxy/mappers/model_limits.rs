@@ -0,0 +1,212 @@
+// 模型上下文窗口 / 最大输出登记表
+// token 预算检查、maxOutputTokens 下调与模型列表元数据共用同一份数据，避免各处硬编码 8192 等默认值。
+// 优先级: 用户覆盖 > 远程清单 > 内置表，逐字段合并 (覆盖规则只设置一个字段时另一个沿用下层)。
+// 每层内精确模型名优先于通配符，通配符按顺序匹配第一条。
+
+use serde_json::{json, Value};
+use std::sync::{OnceLock, RwLock};
+
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::config::ModelLimitRule;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelLimits {
+    /// 输入上下文窗口 (None = 未知，不做窗口检查)
+    pub context_window: Option<u64>,
+    pub max_output_tokens: Option<u64>,
+}
+
+const fn limits(context_window: Option<u64>, max_output_tokens: u64) -> ModelLimits {
+    ModelLimits { context_window, max_output_tokens: Some(max_output_tokens) }
+}
+
+/// 内置表 (按顺序匹配第一条)
+const BUILTIN: &[(&str, ModelLimits)] = &[
+    // 图像生成模型窗口差异较大，交给上游判断
+    ("gemini-*-image*", limits(None, 32_768)),
+    ("gemini-1.5-pro*", limits(Some(2_097_152), 8_192)),
+    ("gemini-1.5-*", limits(Some(1_048_576), 8_192)),
+    ("gemini-2.0-*", limits(Some(1_048_576), 8_192)),
+    ("gemini-*", limits(Some(1_048_576), 65_536)),
+    ("claude-*", limits(Some(200_000), 64_000)),
+];
+
+#[derive(Default)]
+struct Registry {
+    manifest: Vec<ModelLimitRule>,
+    overrides: Vec<ModelLimitRule>,
+}
+
+fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(Registry::default()))
+}
+
+pub fn set_manifest(rules: Vec<ModelLimitRule>) {
+    if let Ok(mut registry) = registry().write() {
+        registry.manifest = rules;
+    }
+}
+
+pub fn set_overrides(rules: Vec<ModelLimitRule>) {
+    if let Ok(mut registry) = registry().write() {
+        registry.overrides = rules;
+    }
+}
+
+/// 内置表 (供设置界面展示)
+pub fn builtin_rules() -> Vec<ModelLimitRule> {
+    BUILTIN
+        .iter()
+        .map(|(pattern, l)| ModelLimitRule {
+            model: pattern.to_string(),
+            context_window: l.context_window,
+            max_output_tokens: l.max_output_tokens,
+        })
+        .collect()
+}
+
+fn find_rule<'a>(rules: &'a [ModelLimitRule], model: &str) -> Option<&'a ModelLimitRule> {
+    rules
+        .iter()
+        .find(|r| r.model.eq_ignore_ascii_case(model))
+        .or_else(|| rules.iter().find(|r| r.model.contains('*') && wildcard_match(&r.model, model)))
+}
+
+fn layer(base: ModelLimits, rule: Option<&ModelLimitRule>) -> ModelLimits {
+    match rule {
+        Some(rule) => ModelLimits {
+            context_window: rule.context_window.or(base.context_window),
+            max_output_tokens: rule.max_output_tokens.or(base.max_output_tokens),
+        },
+        None => base,
+    }
+}
+
+pub fn resolve(model: &str) -> ModelLimits {
+    let lower = model.to_lowercase();
+    let base = BUILTIN
+        .iter()
+        .find(|(pattern, _)| wildcard_match(pattern, &lower))
+        .map(|(_, l)| *l)
+        .unwrap_or_default();
+    let Ok(registry) = registry().read() else {
+        return base;
+    };
+    let with_manifest = layer(base, find_rule(&registry.manifest, model));
+    layer(with_manifest, find_rule(&registry.overrides, model))
+}
+
+/// 模型列表展示用: 客户端别名 (如 gpt-4o) 未登记时按系统默认映射的目标模型查询
+pub fn resolve_listed(model: &str) -> ModelLimits {
+    match resolve(model) {
+        ModelLimits { context_window: None, max_output_tokens: None } => {
+            resolve(&crate::proxy::common::model_mapping::map_claude_model_to_gemini(model))
+        }
+        limits => limits,
+    }
+}
+
+/// 登记表中最小的上下文窗口 (token 预算检查的快速路径下界)
+pub fn min_context_window() -> u64 {
+    let builtin = BUILTIN.iter().filter_map(|(_, l)| l.context_window);
+    let Ok(registry) = registry().read() else {
+        return builtin.min().unwrap_or(u64::MAX);
+    };
+    builtin
+        .chain(registry.manifest.iter().chain(registry.overrides.iter()).filter_map(|r| r.context_window))
+        .min()
+        .unwrap_or(u64::MAX)
+}
+
+/// 解析远程清单: `{ "models": [{ "model", "context_window", "max_output_tokens" }] }`
+/// 或 `{ "models": { "<model>": { "context_window", "max_output_tokens" } } }`
+pub fn parse_manifest(manifest: &Value) -> Result<Vec<ModelLimitRule>, String> {
+    let models = manifest.get("models").ok_or("manifest is missing the `models` field")?;
+    let rules: Vec<ModelLimitRule> = match models {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| serde_json::from_value(item.clone()))
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("invalid manifest entry: {}", e))?,
+        Value::Object(map) => map
+            .iter()
+            .map(|(model, limits)| {
+                let mut entry = limits.as_object().cloned().unwrap_or_default();
+                entry.insert("model".to_string(), json!(model));
+                serde_json::from_value(Value::Object(entry))
+            })
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("invalid manifest entry: {}", e))?,
+        _ => return Err("`models` must be an array or an object".to_string()),
+    };
+    if let Some(bad) = rules.iter().find(|r| r.model.trim().is_empty() || r.context_window == Some(0) || r.max_output_tokens == Some(0)) {
+        return Err(format!("invalid manifest entry for model '{}'", bad.model));
+    }
+    Ok(rules)
+}
+
+/// 将 generationConfig.maxOutputTokens 限制在模型上限内 (body 可以是 v1internal 包装或原生请求体)。
+/// 下调后 thinkingBudget 不再小于 maxOutputTokens 时同步下调。返回 (原值, 上限)
+pub fn clamp_max_output(body: &mut Value, model: &str) -> Option<(u64, u64)> {
+    let limit = resolve(model).max_output_tokens?;
+    let request = if body.get("request").is_some() { &mut body["request"] } else { body };
+    let gen = request.get_mut("generationConfig")?.as_object_mut()?;
+    let requested = gen.get("maxOutputTokens")?.as_u64()?;
+    if requested <= limit {
+        return None;
+    }
+    gen.insert("maxOutputTokens".to_string(), json!(limit));
+    if let Some(thinking) = gen.get_mut("thinkingConfig").and_then(|t| t.as_object_mut()) {
+        if thinking.get("thinkingBudget").and_then(|b| b.as_u64()).is_some_and(|b| b >= limit) {
+            thinking.insert("thinkingBudget".to_string(), json!(limit * 3 / 4));
+        }
+    }
+    tracing::debug!("[ModelLimits] {} maxOutputTokens {} -> {}", model, requested, limit);
+    Some((requested, limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_merge_per_field() {
+        assert_eq!(resolve("gemini-2.0-flash").max_output_tokens, Some(8_192));
+        assert_eq!(resolve("gemini-1.5-pro-002").context_window, Some(2_097_152));
+        assert_eq!(resolve("gemini-3-pro-image").context_window, None);
+        assert_eq!(resolve("some-local-model"), ModelLimits::default());
+
+        let manifest = parse_manifest(&json!({ "models": {
+            "limits-test-*": { "context_window": 32_000, "max_output_tokens": 4_096 }
+        }}))
+        .unwrap();
+        set_manifest(manifest);
+        set_overrides(vec![ModelLimitRule { model: "limits-test-mini".to_string(), max_output_tokens: Some(2_048), ..Default::default() }]);
+
+        let mini = resolve("limits-test-mini");
+        assert_eq!((mini.context_window, mini.max_output_tokens), (Some(32_000), Some(2_048)));
+        assert_eq!(resolve("limits-test-large").max_output_tokens, Some(4_096));
+        assert!(min_context_window() <= 32_000);
+
+        assert!(parse_manifest(&json!({ "models": [{ "model": "x", "context_window": 0 }] })).is_err());
+        assert!(parse_manifest(&json!({ "version": 1 })).is_err());
+        set_manifest(Vec::new());
+        set_overrides(Vec::new());
+    }
+
+    #[test]
+    fn test_clamp_max_output() {
+        let mut body = json!({ "request": { "generationConfig": {
+            "maxOutputTokens": 16_384,
+            "thinkingConfig": { "thinkingBudget": 10_000 }
+        }}});
+        assert_eq!(clamp_max_output(&mut body, "gemini-2.0-flash"), Some((16_384, 8_192)));
+        assert_eq!(body["request"]["generationConfig"]["maxOutputTokens"], 8_192);
+        assert_eq!(body["request"]["generationConfig"]["thinkingConfig"]["thinkingBudget"], 6_144);
+
+        let mut within = json!({ "generationConfig": { "maxOutputTokens": 1_024 } });
+        assert_eq!(clamp_max_output(&mut within, "gemini-2.5-flash"), None);
+        assert_eq!(clamp_max_output(&mut within, "unknown-model"), None);
+    }
+}
@@ -67,11 +67,13 @@ where
         model: String::new(),
         choices: vec![],
         usage: None,
+        x_ag_truncated: None,
     };
 
     let mut content = String::new();
     let mut reasoning_content = String::new();
     let mut tool_calls: Vec<ToolCall> = Vec::new();
+    let mut images: Vec<serde_json::Value> = Vec::new();
     let mut finish_reason: Option<String> = None;
 
     for event in chunks {
@@ -100,6 +102,11 @@ where
                         reasoning_content.push_str(reasoning);
                     }
 
+                    // 累积生成图片 (images 扩展字段)
+                    if let Some(items) = delta.get("images").and_then(|v| v.as_array()) {
+                        images.extend(items.iter().cloned());
+                    }
+
                     // 累积 tool_calls
                     if let Some(tc_arr) = delta.get("tool_calls").and_then(|v| v.as_array()) {
                         for tc in tc_arr {
@@ -139,6 +146,10 @@ where
             }
         }
 
+        if event.data.get("x_ag_truncated").and_then(|v| v.as_bool()) == Some(true) {
+            response.x_ag_truncated = Some(true);
+        }
+
         if let Some(usage_data) = event.data.get("usage") {
             if let Ok(usage) = serde_json::from_value::<OpenAIUsage>(usage_data.clone()) {
                 response.usage = Some(usage);
@@ -147,6 +158,7 @@ where
     }
 
     // 3. 构建最终的 choice
+    let images = if images.is_empty() { None } else { Some(images) };
     let message = if !tool_calls.is_empty() {
         OpenAIMessage {
             role: "assistant".to_string(),
@@ -155,6 +167,8 @@ where
             reasoning_content: if reasoning_content.is_empty() { None } else { Some(reasoning_content) },
             tool_call_id: None,
             name: None,
+            prefix: None,
+            images,
         }
     } else {
         OpenAIMessage {
@@ -164,6 +178,8 @@ where
             reasoning_content: if reasoning_content.is_empty() { None } else { Some(reasoning_content) },
             tool_call_id: None,
             name: None,
+            prefix: None,
+            images,
         }
     };
 
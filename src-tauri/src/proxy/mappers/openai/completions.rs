@@ -0,0 +1,502 @@
+// Legacy Completions (/v1/completions) 适配
+// 普通 prompt 作为 assistant 预填充由上游直接续写 (与旧版补全语义一致)；
+// 带 suffix 时按模型选择 FIM 模板: instruction 风格构造结构化提示，要求模型只输出光标处应插入的文本；
+// tokens 风格用模型原生的 FIM 特殊 token 拼成原始 prompt (主要用于 OpenAI 兼容上游的代码模型)。
+// 超大文件的补全请求按本地 token 估算截断，只保留光标附近的上下文。
+
+use serde_json::{json, Value};
+
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::common::tokenizer::{count_tokens, keep_head, keep_tail};
+use crate::proxy::config::{CompletionTruncationConfig, FimStyle, FimTemplateRule};
+
+/// 标注 prompt 被截断的响应头 (值为截断前后的估算 token 数，如 `52000->8192`)
+pub const PROMPT_TRUNCATED_HEADER: &str = "x-ag-prompt-truncated";
+
+/// 默认 FIM 指令: 前缀 / 后缀分别放在 <prefix> / <suffix> 标签中
+const FIM_SYSTEM_PROMPT: &str = "You are a code completion engine. The user message contains the text before the cursor inside <prefix> and the text after the cursor inside <suffix>. Reply with only the text to insert at the cursor so that prefix + insertion + suffix is complete and correct. Do not repeat the prefix or the suffix, do not explain, and do not wrap the answer in code fences.";
+
+const TAG_TEMPLATE: &str = "<prefix>{prefix}</prefix>\n<suffix>{suffix}</suffix>";
+
+/// Gemini 对 "填空标记" 形式的遵循度更好
+const HOLE_SYSTEM_PROMPT: &str = "You are a code completion engine. The user message is a file with a single <FILL_HERE> marker at the cursor. Reply with only the text that replaces <FILL_HERE>. Do not repeat any text around the marker, do not explain, and do not wrap the answer in code fences.";
+
+const HOLE_TEMPLATE: &str = "{prefix}<FILL_HERE>{suffix}";
+
+/// 续写指令 (预填充之前的 user 轮次，上游要求对话以 user 开头)
+const CONTINUE_PROMPT: &str = "Continue the text exactly where it stops. Output only the continuation.";
+
+struct BuiltinTemplate {
+    model: &'static str,
+    style: FimStyle,
+    system: &'static str,
+    template: &'static str,
+    stop: &'static [&'static str],
+}
+
+/// 内置模板 (按顺序匹配第一条)
+const BUILTIN: &[BuiltinTemplate] = &[
+    BuiltinTemplate {
+        model: "*qwen*coder*",
+        style: FimStyle::Tokens,
+        system: "",
+        template: "<|fim_prefix|>{prefix}<|fim_suffix|>{suffix}<|fim_middle|>",
+        stop: &["<|endoftext|>", "<|fim_pad|>", "<|file_sep|>"],
+    },
+    BuiltinTemplate {
+        model: "*deepseek-coder*",
+        style: FimStyle::Tokens,
+        system: "",
+        template: "<｜fim▁begin｜>{prefix}<｜fim▁hole｜>{suffix}<｜fim▁end｜>",
+        stop: &["<｜end▁of▁sentence｜>"],
+    },
+    BuiltinTemplate {
+        model: "*codellama*",
+        style: FimStyle::Tokens,
+        system: "",
+        template: "<PRE> {prefix} <SUF>{suffix} <MID>",
+        stop: &["<EOT>"],
+    },
+    BuiltinTemplate {
+        model: "*starcoder*",
+        style: FimStyle::Tokens,
+        system: "",
+        template: "<fim_prefix>{prefix}<fim_suffix>{suffix}<fim_middle>",
+        stop: &["<|endoftext|>"],
+    },
+    BuiltinTemplate {
+        model: "*codestral*",
+        style: FimStyle::Tokens,
+        system: "",
+        template: "[SUFFIX]{suffix}[PREFIX]{prefix}",
+        stop: &["</s>"],
+    },
+    BuiltinTemplate {
+        model: "gemini-*",
+        style: FimStyle::Instruction,
+        system: HOLE_SYSTEM_PROMPT,
+        template: HOLE_TEMPLATE,
+        stop: &[],
+    },
+    BuiltinTemplate {
+        model: "*",
+        style: FimStyle::Instruction,
+        system: FIM_SYSTEM_PROMPT,
+        template: TAG_TEMPLATE,
+        stop: &[],
+    },
+];
+
+/// 按模型选择 FIM 模板: 用户规则优先，其次内置模板；`models` 为请求模型与映射后的目标模型
+pub fn resolve_template(rules: &[FimTemplateRule], models: &[&str]) -> FimTemplateRule {
+    let matches = |pattern: &str| models.iter().any(|m| wildcard_match(pattern, m));
+    if let Some(rule) = rules.iter().find(|r| !r.template.is_empty() && matches(&r.model)) {
+        let mut rule = rule.clone();
+        if rule.style == FimStyle::Instruction && rule.system.trim().is_empty() {
+            rule.system = FIM_SYSTEM_PROMPT.to_string();
+        }
+        return rule;
+    }
+    let builtin = BUILTIN
+        .iter()
+        .find(|t| matches(t.model))
+        .unwrap_or(&BUILTIN[BUILTIN.len() - 1]);
+    FimTemplateRule {
+        model: builtin.model.to_string(),
+        style: builtin.style,
+        template: builtin.template.to_string(),
+        system: builtin.system.to_string(),
+        stop: builtin.stop.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+/// 单次扫描替换占位符 (前缀 / 后缀正文中出现的 `{prefix}` 等字样不会被再次替换)
+fn render(template: &str, prefix: &str, suffix: &str) -> String {
+    let mut out = String::with_capacity(template.len() + prefix.len() + suffix.len());
+    let mut rest = template;
+    while let Some(pos) = rest.find('{') {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+        if let Some(after) = tail.strip_prefix("{prefix}") {
+            out.push_str(prefix);
+            rest = after;
+        } else if let Some(after) = tail.strip_prefix("{suffix}") {
+            out.push_str(suffix);
+            rest = after;
+        } else {
+            out.push('{');
+            rest = &tail[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// prompt 截断前后的估算 token 数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Truncation {
+    pub before: usize,
+    pub after: usize,
+}
+
+impl Truncation {
+    pub fn header_value(&self) -> String {
+        format!("{}->{}", self.before, self.after)
+    }
+}
+
+/// FIM 预算分配: 按比例划分，一侧用不完的额度让给另一侧；返回 (prompt 预算, suffix 预算)
+fn split_budget(max: usize, prefix_percent: u8, prefix_tokens: usize, suffix_tokens: usize) -> (usize, usize) {
+    let prefix_share = max * usize::from(prefix_percent.min(100)) / 100;
+    let suffix_budget = (max - prefix_share).max(max.saturating_sub(prefix_tokens));
+    let prefix_budget = max - suffix_budget.min(suffix_tokens);
+    (prefix_budget, suffix_budget)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegacyPrompt {
+    pub prompt: String,
+    pub suffix: Option<String>,
+    pub echo: bool,
+    /// FIM 请求使用的模板 (由 handler 按模型解析后设置)
+    pub template: Option<FimTemplateRule>,
+    /// 截断前的 prompt (echo 时返回客户端原始输入)
+    pub original_prompt: Option<String>,
+}
+
+impl LegacyPrompt {
+    /// 解析 prompt / suffix / echo；prompt 为字符串数组时按换行拼接，token ID 数组不支持
+    pub fn from_body(body: &Value) -> Result<Self, String> {
+        let prompt = match body.get("prompt") {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Array(items)) => {
+                let mut texts = Vec::with_capacity(items.len());
+                for item in items {
+                    match item {
+                        Value::String(s) => texts.push(s.as_str()),
+                        _ => return Err("Token ID prompts are not supported; send the prompt as text".to_string()),
+                    }
+                }
+                texts.join("\n")
+            }
+            Some(_) => return Err("'prompt' must be a string or an array of strings".to_string()),
+        };
+        let suffix = body
+            .get("suffix")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        let echo = body.get("echo").and_then(|v| v.as_bool()).unwrap_or(false);
+        Ok(Self {
+            prompt,
+            suffix,
+            echo,
+            template: None,
+            original_prompt: None,
+        })
+    }
+
+    pub fn is_fim(&self) -> bool {
+        self.suffix.is_some()
+    }
+
+    fn template(&self) -> FimTemplateRule {
+        self.template.clone().unwrap_or_else(|| resolve_template(&[], &[]))
+    }
+
+    /// instruction 风格的 FIM 输出需要清理代码围栏
+    pub fn is_instruction_fim(&self) -> bool {
+        self.is_fim() && self.template().style == FimStyle::Instruction
+    }
+
+    /// 按模板渲染的 FIM 正文 (非 FIM 请求返回 None)
+    pub fn rendered(&self) -> Option<String> {
+        let suffix = self.suffix.as_deref()?;
+        Some(render(&self.template().template, &self.prompt, suffix))
+    }
+
+    /// 转换为 Chat 消息
+    pub fn to_messages(&self) -> Vec<Value> {
+        if let Some(rendered) = self.rendered() {
+            let template = self.template();
+            return match template.style {
+                FimStyle::Instruction => vec![
+                    json!({ "role": "system", "content": template.system }),
+                    json!({ "role": "user", "content": rendered }),
+                ],
+                FimStyle::Tokens => vec![
+                    json!({ "role": "user", "content": CONTINUE_PROMPT }),
+                    json!({ "role": "assistant", "content": rendered }),
+                ],
+            };
+        }
+        if self.prompt.is_empty() {
+            return Vec::new();
+        }
+        vec![
+            json!({ "role": "user", "content": CONTINUE_PROMPT }),
+            json!({ "role": "assistant", "content": self.prompt }),
+        ]
+    }
+
+    /// tokens 风格: 把请求体改写为渲染后的原始 prompt (去掉 suffix)，并追加模板的 stop 序列
+    /// 转发到 OpenAI 兼容上游时上游看到的就是模型原生的 FIM 格式
+    pub fn apply_token_template(&self, body: &mut Value) {
+        let template = self.template();
+        if template.style != FimStyle::Tokens {
+            return;
+        }
+        let Some(rendered) = self.rendered() else {
+            return;
+        };
+        let Some(obj) = body.as_object_mut() else {
+            return;
+        };
+        obj.insert("prompt".to_string(), Value::String(rendered));
+        obj.remove("suffix");
+        if template.stop.is_empty() {
+            return;
+        }
+        let mut stops: Vec<Value> = match obj.remove("stop") {
+            Some(Value::String(s)) => vec![Value::String(s)],
+            Some(Value::Array(arr)) => arr,
+            _ => Vec::new(),
+        };
+        for stop in template.stop {
+            let stop = Value::String(stop);
+            if !stops.contains(&stop) {
+                stops.push(stop);
+            }
+        }
+        obj.insert("stop".to_string(), Value::Array(stops));
+    }
+
+    /// 按 token 预算截断，保留离光标最近的文本 (prompt 保留末尾，suffix 保留开头)，并同步改写请求体
+    /// 未超出预算时返回 None
+    pub fn truncate(&mut self, body: &mut Value, config: &CompletionTruncationConfig) -> Option<Truncation> {
+        let prefix_tokens = count_tokens(&self.prompt);
+        let suffix_tokens = self.suffix.as_deref().map(count_tokens).unwrap_or(0);
+        let before = prefix_tokens + suffix_tokens;
+        let max = if self.is_fim() { config.fim_max_tokens } else { config.prompt_max_tokens } as usize;
+        if max == 0 || before <= max {
+            return None;
+        }
+
+        let (prefix_budget, suffix_budget) = if self.is_fim() {
+            split_budget(max, config.prefix_percent, prefix_tokens, suffix_tokens)
+        } else {
+            (max, 0)
+        };
+        let original = self.prompt.clone();
+        let cut = keep_tail(&self.prompt, prefix_budget);
+        self.prompt.drain(..cut);
+        if let Some(suffix) = self.suffix.as_mut() {
+            let cut = keep_head(suffix, suffix_budget);
+            suffix.truncate(cut);
+        }
+        self.original_prompt.get_or_insert(original);
+
+        if let Some(obj) = body.as_object_mut() {
+            obj.insert("prompt".to_string(), Value::String(self.prompt.clone()));
+            if let Some(suffix) = &self.suffix {
+                obj.insert("suffix".to_string(), Value::String(suffix.clone()));
+            }
+        }
+        let after = count_tokens(&self.prompt) + self.suffix.as_deref().map(count_tokens).unwrap_or(0);
+        Some(Truncation { before, after })
+    }
+
+    /// echo=true 时返回需要拼在补全结果之前的原始 prompt (截断前)
+    pub fn echo_text(&self) -> Option<String> {
+        self.echo
+            .then(|| self.original_prompt.clone().unwrap_or_else(|| self.prompt.clone()))
+    }
+}
+
+/// 去掉 FIM 输出外层的代码块围栏 (模型偶尔不遵守指令)
+pub fn clean_fim_output(text: &str) -> String {
+    let trimmed = text.trim_end();
+    let Some(rest) = trimmed.trim_start().strip_prefix("```") else {
+        return text.to_string();
+    };
+    let Some(inner) = rest.strip_suffix("```") else {
+        return text.to_string();
+    };
+    // 围栏首行是语言标识
+    match inner.split_once('\n') {
+        Some((_, body)) => body.strip_suffix('\n').unwrap_or(body).to_string(),
+        None => inner.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fim(prompt: &str, suffix: &str, model: &str, rules: &[FimTemplateRule]) -> LegacyPrompt {
+        let mut legacy = LegacyPrompt::from_body(&json!({"prompt": prompt, "suffix": suffix})).unwrap();
+        legacy.template = Some(resolve_template(rules, &[model]));
+        legacy
+    }
+
+    #[test]
+    fn test_plain_prompt_is_prefilled() {
+        let prompt = LegacyPrompt::from_body(&json!({"prompt": "def add(a, b):\n"})).unwrap();
+        assert!(!prompt.is_fim());
+        let messages = prompt.to_messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1]["role"], "assistant");
+        assert_eq!(messages[1]["content"], "def add(a, b):\n");
+        assert_eq!(prompt.echo_text(), None);
+
+        let empty = LegacyPrompt::from_body(&json!({"prompt": ""})).unwrap();
+        assert!(empty.to_messages().is_empty());
+    }
+
+    #[test]
+    fn test_fim_prompt() {
+        let mut prompt = LegacyPrompt::from_body(&json!({
+            "prompt": "fn main() {\n    let x = ",
+            "suffix": ";\n}\n",
+            "echo": true
+        }))
+        .unwrap();
+        prompt.template = Some(resolve_template(&[], &["claude-sonnet-4-5"]));
+        assert!(prompt.is_fim());
+        assert!(prompt.is_instruction_fim());
+        let messages = prompt.to_messages();
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(
+            messages[1]["content"],
+            "<prefix>fn main() {\n    let x = </prefix>\n<suffix>;\n}\n</suffix>"
+        );
+        assert_eq!(prompt.echo_text().as_deref(), Some("fn main() {\n    let x = "));
+
+        // 空 suffix 视为普通续写
+        let plain = LegacyPrompt::from_body(&json!({"prompt": "a", "suffix": ""})).unwrap();
+        assert!(!plain.is_fim());
+    }
+
+    #[test]
+    fn test_builtin_templates() {
+        let gemini = fim("a = ", "\nb = 2", "gemini-2.5-flash", &[]);
+        assert_eq!(gemini.to_messages()[1]["content"], "a = <FILL_HERE>\nb = 2");
+
+        let qwen = fim("a = ", "\nb = 2", "qwen2.5-coder:7b", &[]);
+        assert!(!qwen.is_instruction_fim());
+        let messages = qwen.to_messages();
+        assert_eq!(messages[1]["role"], "assistant");
+        assert_eq!(messages[1]["content"], "<|fim_prefix|>a = <|fim_suffix|>\nb = 2<|fim_middle|>");
+    }
+
+    #[test]
+    fn test_user_rules_override_builtin() {
+        let rules = vec![FimTemplateRule {
+            model: "gemini-2.5-*".to_string(),
+            style: FimStyle::Instruction,
+            template: "BEFORE:\n{prefix}\nAFTER:\n{suffix}".to_string(),
+            system: String::new(),
+            stop: Vec::new(),
+        }];
+        let legacy = fim("x", "y", "gemini-2.5-pro", &rules);
+        let messages = legacy.to_messages();
+        // system 留空时回退到默认指令
+        assert_eq!(messages[0]["content"], FIM_SYSTEM_PROMPT);
+        assert_eq!(messages[1]["content"], "BEFORE:\nx\nAFTER:\ny");
+
+        // 未命中规则的模型仍使用内置模板
+        let other = fim("x", "y", "gemini-3-flash", &rules);
+        assert_eq!(other.to_messages()[1]["content"], "x<FILL_HERE>y");
+    }
+
+    #[test]
+    fn test_apply_token_template() {
+        let legacy = fim("a", "b", "starcoder2-15b", &[]);
+        let mut body = json!({"model": "starcoder2-15b", "prompt": "a", "suffix": "b", "stop": "\n\n"});
+        legacy.apply_token_template(&mut body);
+        assert_eq!(body["prompt"], "<fim_prefix>a<fim_suffix>b<fim_middle>");
+        assert!(body.get("suffix").is_none());
+        assert_eq!(body["stop"], json!(["\n\n", "<|endoftext|>"]));
+
+        // instruction 风格不改写请求体
+        let gemini = fim("a", "b", "gemini-2.5-flash", &[]);
+        let mut body = json!({"prompt": "a", "suffix": "b"});
+        gemini.apply_token_template(&mut body);
+        assert_eq!(body["suffix"], "b");
+    }
+
+    #[test]
+    fn test_render_is_single_pass() {
+        assert_eq!(render("{prefix}|{suffix}", "{suffix}", "s"), "{suffix}|s");
+        assert_eq!(render("{x}{prefix}", "p", "s"), "{x}p");
+    }
+
+    #[test]
+    fn test_prompt_arrays() {
+        let joined = LegacyPrompt::from_body(&json!({"prompt": ["a", "b"]})).unwrap();
+        assert_eq!(joined.prompt, "a\nb");
+        assert!(LegacyPrompt::from_body(&json!({"prompt": [1, 2, 3]})).is_err());
+        assert!(LegacyPrompt::from_body(&json!({"prompt": 42})).is_err());
+    }
+
+    fn numbered_lines(range: std::ops::Range<usize>) -> String {
+        range.map(|i| format!("let v{} = {};\n", i, i)).collect()
+    }
+
+    #[test]
+    fn test_split_budget() {
+        // 两侧都超出: 按比例
+        assert_eq!(split_budget(1000, 75, 5000, 5000), (750, 250));
+        // prompt 很短: suffix 获得剩余额度
+        assert_eq!(split_budget(1000, 75, 100, 5000), (100, 900));
+        // suffix 很短: prompt 获得剩余额度
+        assert_eq!(split_budget(1000, 75, 5000, 50), (950, 250));
+    }
+
+    #[test]
+    fn test_truncate_fim_keeps_cursor_context() {
+        let prompt = numbered_lines(0..500);
+        let suffix = numbered_lines(500..1000);
+        let mut body = json!({"prompt": prompt, "suffix": suffix, "echo": true});
+        let mut legacy = LegacyPrompt::from_body(&body).unwrap();
+        let config = CompletionTruncationConfig { fim_max_tokens: 400, ..Default::default() };
+
+        let truncation = legacy.truncate(&mut body, &config).unwrap();
+        assert!(truncation.before > 400);
+        assert!(truncation.after <= 400);
+        // prompt 保留末尾 (光标前的最后一行)，suffix 保留开头，都在整行处截断
+        assert!(legacy.prompt.ends_with("let v499 = 499;\n"));
+        assert!(legacy.prompt.starts_with("let v"));
+        assert!(legacy.suffix.as_deref().unwrap().starts_with("let v500 = 500;\n"));
+        assert!(legacy.suffix.as_deref().unwrap().ends_with(";\n"));
+        assert_eq!(body["prompt"], legacy.prompt.as_str());
+        assert_eq!(body["suffix"], legacy.suffix.as_deref().unwrap());
+        // echo 返回截断前的 prompt
+        assert_eq!(legacy.echo_text(), Some(prompt));
+        assert_eq!(truncation.header_value(), format!("{}->{}", truncation.before, truncation.after));
+    }
+
+    #[test]
+    fn test_truncate_plain_prompt() {
+        let mut body = json!({"prompt": numbered_lines(0..200)});
+        let mut legacy = LegacyPrompt::from_body(&body).unwrap();
+        // 默认只截断 FIM 请求
+        assert_eq!(legacy.truncate(&mut body, &CompletionTruncationConfig::default()), None);
+
+        let config = CompletionTruncationConfig { prompt_max_tokens: 100, ..Default::default() };
+        let truncation = legacy.truncate(&mut body, &config).unwrap();
+        assert!(truncation.after <= 100);
+        assert!(legacy.prompt.ends_with("let v199 = 199;\n"));
+        assert_eq!(body["prompt"], legacy.prompt.as_str());
+        assert_eq!(legacy.truncate(&mut body, &config), None);
+    }
+
+    #[test]
+    fn test_clean_fim_output() {
+        assert_eq!(clean_fim_output("42"), "42");
+        assert_eq!(clean_fim_output("```rust\n42\n```"), "42");
+        assert_eq!(clean_fim_output("```\nfoo()\nbar()\n```\n"), "foo()\nbar()");
+        assert_eq!(clean_fim_output("  x + 1"), "  x + 1");
+    }
+}
@@ -30,11 +30,37 @@ pub struct OpenAIRequest {
     // Codex proprietary fields
     pub instructions: Option<String>,
     pub input: Option<Value>,
+    /// 终端用户标识 (OpenAI 标准字段)，作为会话指纹的备选来源
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// 客户端显式会话 ID (非标准扩展)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+    /// 采样种子 (OpenAI 标准字段) -> generationConfig.seed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// 图像模型的 size / aspect_ratio / image_size / negative_prompt (厂商扩展)
+    #[serde(flatten, default)]
+    pub image: crate::proxy::mappers::common_utils::ImageGenOptions,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResponseFormat {
     pub r#type: String,
+    /// type 为 "json_schema" 时的 `{ name, schema, strict }`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<Value>,
+}
+
+impl ResponseFormat {
+    /// 是否要求 JSON 输出
+    pub fn wants_json(&self) -> bool {
+        matches!(self.r#type.as_str(), "json_object" | "json_schema")
+    }
+
+    pub fn schema(&self) -> Option<&Value> {
+        self.json_schema.as_ref().and_then(|s| s.get("schema"))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -59,6 +85,10 @@ pub enum OpenAIContentBlock {
     AudioUrl {
         audio_url: AudioUrlContent,
     },
+    #[serde(rename = "file")]
+    File {
+        file: OpenAIFileContent,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -68,6 +98,17 @@ pub struct OpenAIImageUrl {
     pub detail: Option<String>,
 }
 
+/// 文件 part: file_data 为 data URL (file_id 引用的已上传文件暂不支持)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OpenAIFileContent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct AudioUrlContent {
     pub url: String,
@@ -86,6 +127,12 @@ pub struct OpenAIMessage {
     pub tool_call_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// 续写前缀 (DeepSeek / Mistral 兼容的 assistant prefill 标记)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<bool>,
+    /// 生成图片扩展字段 (OpenRouter 兼容): `[{ type: "image_url", image_url: { url }, path? }]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<serde_json::Value>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -110,6 +157,9 @@ pub struct OpenAIResponse {
     pub choices: Vec<Choice>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub usage: Option<OpenAIUsage>,
+    /// 上游中途断开且无法续传时为 true (finish_reason 为 "length")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x_ag_truncated: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
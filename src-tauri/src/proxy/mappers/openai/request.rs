@@ -2,17 +2,23 @@
 use super::models::*;
 use serde_json::{json, Value};
 use super::streaming::get_thought_signature;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
 
-pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mapped_model: &str) -> Value {
-    // 将 OpenAI 工具转为 Value 数组以便探测
-    let tools_val = request.tools.as_ref().map(|list| {
-        list.iter().map(|v| v.clone()).collect::<Vec<_>>()
-    });
+/// 已清洗的 functionDeclarations 缓存 (key: 工具集哈希)
+/// 多轮对话中工具定义通常保持不变，避免每一轮都重复展开 $ref / 清洗 schema / 大写转换
+static TOOL_DECLARATION_CACHE: Lazy<Mutex<HashMap<u64, Arc<Vec<Value>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+const TOOL_DECLARATION_CACHE_CAPACITY: usize = 64;
 
+pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mapped_model: &str) -> Value {
+    let _span = tracing::info_span!(target: crate::proxy::telemetry::TARGET, "transform", ag.protocol = "openai").entered();
     let mapped_model_lower = mapped_model.to_lowercase();
     
     // Resolve grounding config
-    let config = crate::proxy::mappers::common_utils::resolve_request_config(&request.model, &mapped_model_lower, &tools_val);
+    let config = crate::proxy::mappers::common_utils::resolve_request_config(&request.model, &mapped_model_lower, &request.tools);
 
     // 检测 Gemini 3 Pro thinking 模型
     let is_gemini_3_thinking = mapped_model_lower.contains("gemini-3") && 
@@ -52,6 +58,9 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
 
 
 
+    let session_id = crate::proxy::session_manager::SessionManager::extract_openai_session_id(request);
+    let id_table = crate::proxy::ToolCallIdTable::global();
+
     // Pre-scan to map tool_call_id to function name (for Codex)
     let mut tool_id_to_name = std::collections::HashMap::new();
     for msg in &request.messages {
@@ -60,16 +69,30 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
                 let name = &call.function.name;
                 let final_name = if name == "local_shell_call" { "shell" } else { name };
                 tool_id_to_name.insert(call.id.clone(), final_name.to_string());
+                id_table.register(&session_id, &call.id, final_name, &Value::String(call.function.arguments.clone()));
             }
         }
     }
+    // 尚未收到结果的工具调用 (id, name)，用于匹配缺少 tool_call_id 的 tool / function 消息
+    let mut pending_calls: Vec<(String, String)> = Vec::new();
 
-    // 从全局存储获取 thoughtSignature 支持
-    let global_thought_sig = get_thought_signature();
+    // 优先使用本会话的 thoughtSignature，避免不同对话之间串用；缺失时回退到全局存储
+    let global_thought_sig = crate::proxy::SignatureCache::global()
+        .get_session_signature(&session_id)
+        .or_else(get_thought_signature);
     if global_thought_sig.is_some() {
         tracing::debug!("从全局存储获取到 thoughtSignature (长度: {})", global_thought_sig.as_ref().unwrap().len());
     }
 
+    // 末尾的 assistant 消息 (或带 `prefix: true`) 视为预填充，映射为末尾 model content 由上游续写
+    let has_prefill = request
+        .messages
+        .iter()
+        .rev()
+        .find(|msg| msg.role != "system")
+        .map(|msg| (msg.role == "assistant" || msg.prefix == Some(true)) && msg.tool_calls.is_none())
+        .unwrap_or(false);
+
     // 2. 构建 Gemini contents (过滤掉 system)
     let contents: Vec<Value> = request
         .messages
@@ -77,6 +100,7 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
         .filter(|msg| msg.role != "system")
         .map(|msg| {
             let role = match msg.role.as_str() {
+                _ if msg.prefix == Some(true) => "model",
                 "assistant" => "model",
                 "tool" | "function" => "user", 
                 _ => &msg.role,
@@ -117,11 +141,8 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
                                 }
                                 OpenAIContentBlock::ImageUrl { image_url } => {
                                     if image_url.url.starts_with("data:") {
-                                        if let Some(pos) = image_url.url.find(",") {
-                                            let mime_part = &image_url.url[5..pos];
-                                            let mime_type = mime_part.split(';').next().unwrap_or("image/jpeg");
-                                            let data = &image_url.url[pos + 1..];
-                                            
+                                        // 按载荷魔数更正类型 (兼容 `data:;base64,` 与类型标错的情况)
+                                        if let Some((mime_type, data)) = crate::proxy::common::mime_sniff::parse_data_url(&image_url.url, "image/jpeg") {
                                             parts.push(json!({
                                                 "inlineData": { "mimeType": mime_type, "data": data }
                                             }));
@@ -131,42 +152,25 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
                                             "fileData": { "fileUri": &image_url.url, "mimeType": "image/jpeg" }
                                         }));
                                     } else {
-                                        // [NEW] 处理本地文件路径 (file:// 或 Windows/Unix 路径)
-                                        let file_path = if image_url.url.starts_with("file://") {
-                                            // 移除 file:// 前缀
-                                            #[cfg(target_os = "windows")]
-                                            { image_url.url.trim_start_matches("file:///").replace('/', "\\") }
-                                            #[cfg(not(target_os = "windows"))]
-                                            { image_url.url.trim_start_matches("file://").to_string() }
-                                        } else {
-                                            image_url.url.clone()
-                                        };
-                                        
-                                        tracing::debug!("[OpenAI-Request] Reading local image: {}", file_path);
-                                        
-                                        // 读取文件并转换为 base64
-                                        if let Ok(file_bytes) = std::fs::read(&file_path) {
-                                            use base64::Engine as _;
-                                            let b64 = base64::engine::general_purpose::STANDARD.encode(&file_bytes);
-                                            
-                                            // 根据文件扩展名推断 MIME 类型
-                                            let mime_type = if file_path.to_lowercase().ends_with(".png") {
-                                                "image/png"
-                                            } else if file_path.to_lowercase().ends_with(".gif") {
-                                                "image/gif"
-                                            } else if file_path.to_lowercase().ends_with(".webp") {
-                                                "image/webp"
-                                            } else {
-                                                "image/jpeg"
-                                            };
-                                            
-                                            parts.push(json!({
-                                                "inlineData": { "mimeType": mime_type, "data": b64 }
-                                            }));
-                                            tracing::debug!("[OpenAI-Request] Successfully loaded image: {} ({} bytes)", file_path, file_bytes.len());
-                                        } else {
-                                            tracing::debug!("[OpenAI-Request] Failed to read local image: {}", file_path);
-                                        }
+                                        // 本地路径由 local_files 中间件在受信模式下内联，到达这里说明该功能未开启
+                                        tracing::warn!("[OpenAI-Request] Ignoring local image reference (local file access is disabled): {}", image_url.url);
+                                    }
+                                }
+                                OpenAIContentBlock::File { file } => {
+                                    let fallback = file
+                                        .filename
+                                        .as_deref()
+                                        .and_then(crate::proxy::common::local_files::mime_from_filename)
+                                        .unwrap_or("application/pdf");
+                                    match file.file_data.as_deref().and_then(|d| crate::proxy::common::mime_sniff::parse_data_url(d, fallback)) {
+                                        Some((mime_type, data)) => parts.push(json!({
+                                            "inlineData": { "mimeType": mime_type, "data": data }
+                                        })),
+                                        None => tracing::warn!(
+                                            "[OpenAI-Request] Skipping file part without inline data (filename={:?}, file_id={:?})",
+                                            file.filename,
+                                            file.file_id
+                                        ),
                                     }
                                 }
                                 OpenAIContentBlock::AudioUrl { audio_url: _ } => {
@@ -183,6 +187,7 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
 
             // Handle tool calls (assistant message)
             if let Some(tool_calls) = &msg.tool_calls {
+                pending_calls.clear();
                 for (_index, tc) in tool_calls.iter().enumerate() {
                     /* 暂时移除：防止 Codex CLI 界面碎片化
                     if index == 0 && parts.is_empty() {
@@ -225,15 +230,36 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
                     }
 
                     parts.push(func_call_part);
+                    let final_name = if tc.function.name == "local_shell_call" { "shell" } else { &tc.function.name };
+                    pending_calls.push((tc.id.clone(), final_name.to_string()));
                 }
             }
 
             // Handle tool response
             if msg.role == "tool" || msg.role == "function" {
+                // 缺少或无法识别 tool_call_id 时 (如 legacy function 消息)，按函数名匹配上一轮未完成的调用
+                let known_id = msg.tool_call_id.as_deref().filter(|id| {
+                    !id.is_empty() && (tool_id_to_name.contains_key(*id) || id_table.resolve(&session_id, id).is_some())
+                });
+                let call_id = match known_id {
+                    Some(id) => id.to_string(),
+                    None => {
+                        let by_name = pending_calls.iter().find(|(_, n)| Some(n.as_str()) == msg.name.as_deref());
+                        match (msg.tool_call_id.as_deref().filter(|id| !id.is_empty()), by_name.or(pending_calls.first())) {
+                            // 客户端给出的 ID 无法识别且函数名也对不上时保留原 ID
+                            (Some(id), _) if by_name.is_none() => id.to_string(),
+                            (_, Some((id, _))) => id.clone(),
+                            (id, None) => id.unwrap_or_default().to_string(),
+                        }
+                    }
+                };
+                pending_calls.retain(|(id, _)| id != &call_id);
+
                 let name = msg.name.as_deref().unwrap_or("unknown");
-                let final_name = if name == "local_shell_call" { "shell" } 
-                                else if let Some(id) = &msg.tool_call_id { tool_id_to_name.get(id).map(|s| s.as_str()).unwrap_or(name) }
-                                else { name };
+                let final_name = if name == "local_shell_call" { "shell".to_string() }
+                                else if let Some(n) = tool_id_to_name.get(&call_id) { n.clone() }
+                                else if let Some(r) = id_table.resolve(&session_id, &call_id) { r.name }
+                                else { name.to_string() };
 
                 let content_val = match &msg.content {
                     Some(OpenAIContent::String(s)) => s.clone(),
@@ -245,7 +271,7 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
                     "functionResponse": {
                        "name": final_name,
                        "response": { "result": content_val },
-                       "id": msg.tool_call_id.clone().unwrap_or_default()
+                       "id": call_id
                     }
                 }));
             }
@@ -257,12 +283,12 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
 
     // 合并连续相同角色的消息 (Gemini 强制要求 user/model 交替)
     let mut merged_contents: Vec<Value> = Vec::new();
-    for msg in contents {
+    for mut msg in contents {
         if let Some(last) = merged_contents.last_mut() {
             if last["role"] == msg["role"] {
-                // 合并 parts
-                if let (Some(last_parts), Some(msg_parts)) = (last["parts"].as_array_mut(), msg["parts"].as_array()) {
-                    last_parts.extend(msg_parts.iter().cloned());
+                // 合并 parts (移动而非克隆，避免复制大块 inlineData)
+                if let (Some(last_parts), Value::Array(msg_parts)) = (last["parts"].as_array_mut(), msg["parts"].take()) {
+                    last_parts.extend(msg_parts);
                     continue;
                 }
             }
@@ -283,9 +309,17 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
     if let Some(n) = request.n {
         gen_config["candidateCount"] = json!(n);
     }
+    if let Some(seed) = request.seed {
+        gen_config["seed"] = json!(seed);
+    }
 
     // 为 thinking 模型注入 thinkingConfig (使用 thinkingBudget 而非 thinkingLevel)
-    if is_thinking_model {
+    // 预填充续写时不开启 thinking: 末尾 model 消息不以 thinking 块开头会被上游拒绝
+    if is_thinking_model && has_prefill {
+        tracing::debug!("[OpenAI-Request] Assistant prefill detected, skipping thinkingConfig");
+    } else if is_thinking_model && crate::proxy::latency::thinking_suppressed() {
+        tracing::debug!("[OpenAI-Request] Latency budget is tight, skipping thinkingConfig");
+    } else if is_thinking_model {
         gen_config["thinkingConfig"] = json!({
             "includeThoughts": true,
             "thinkingBudget": 16000
@@ -300,9 +334,14 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
     }
 
     if let Some(fmt) = &request.response_format {
-        if fmt.r#type == "json_object" {
+        if fmt.wants_json() {
             gen_config["responseMimeType"] = json!("application/json");
         }
+        if let Some(schema) = fmt.schema() {
+            let mut schema = schema.clone();
+            crate::proxy::common::json_schema::clean_json_schema(&mut schema);
+            gen_config["responseSchema"] = schema;
+        }
     }
 
     let mut inner_request = json!({
@@ -320,64 +359,11 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
     // 深度清理 [undefined] 字符串 (Cherry Studio 等客户端常见注入)
     crate::proxy::mappers::common_utils::deep_clean_undefined(&mut inner_request);
 
-    // 4. Handle Tools (Merged Cleaning)
+    // 4. Handle Tools (Merged Cleaning, 按工具集哈希复用清洗结果)
     if let Some(tools) = &request.tools {
-        let mut function_declarations: Vec<Value> = Vec::new();
-        for tool in tools.iter() {
-            let mut gemini_func = if let Some(func) = tool.get("function") {
-                func.clone()
-            } else {
-                let mut func = tool.clone();
-                if let Some(obj) = func.as_object_mut() {
-                    obj.remove("type");
-                    obj.remove("strict");
-                    obj.remove("additionalProperties");
-                }
-                func
-            };
-
-            if let Some(name) = gemini_func.get("name").and_then(|v| v.as_str()) {
-                // 跳过内置联网工具名称，避免重复定义
-                if name == "web_search" || name == "google_search" || name == "web_search_20250305" {
-                    continue;
-                }
-                
-                if name == "local_shell_call" {
-                    if let Some(obj) = gemini_func.as_object_mut() {
-                        obj.insert("name".to_string(), json!("shell"));
-                    }
-                }
-            }
-
-            // [NEW CRITICAL FIX] 清除函数定义根层级的非法字段 (解决报错持久化)
-            if let Some(obj) = gemini_func.as_object_mut() {
-                obj.remove("format");
-                obj.remove("strict");
-                obj.remove("additionalProperties");
-                obj.remove("type"); // [NEW] Gemini 不支持在 FunctionDeclaration 根层级出现 type: "function"
-            }
-
-            if let Some(params) = gemini_func.get_mut("parameters") {
-                // [DEEP FIX] 统一调用公共库清洗：展开 $ref 并剔除所有层级的 format/definitions
-                crate::proxy::common::json_schema::clean_json_schema(params);
-
-                // Gemini v1internal 要求：
-                // 1. type 必须是大写 (OBJECT, STRING 等)
-                // 2. 根对象必须有 "type": "OBJECT"
-                if let Some(params_obj) = params.as_object_mut() {
-                    if !params_obj.contains_key("type") {
-                        params_obj.insert("type".to_string(), json!("OBJECT"));
-                    }
-                }
-                
-                // 递归转换 type 为大写 (符合 Protobuf 定义)
-                enforce_uppercase_types(params);
-            }
-            function_declarations.push(gemini_func);
-        }
-        
+        let function_declarations = cached_function_declarations(tools);
         if !function_declarations.is_empty() {
-            inner_request["tools"] = json!([{ "functionDeclarations": function_declarations }]);
+            inner_request["tools"] = json!([{ "functionDeclarations": &*function_declarations }]);
         }
     }
     
@@ -412,7 +398,18 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
         crate::proxy::mappers::common_utils::inject_google_search_tool(&mut inner_request);
     }
 
-    if let Some(image_config) = config.image_config {
+    if let Some(mut image_config) = config.image_config {
+         // 显式参数 (size / aspect_ratio / image_size) 覆盖模型后缀，非法值忽略
+         request.image.apply(&mut image_config);
+         if let Some(negative) = request.image.negative_prompt_text() {
+             if let Some(parts) = inner_request["contents"]
+                 .as_array_mut()
+                 .and_then(|contents| contents.iter_mut().rev().find(|c| c["role"] == "user"))
+                 .and_then(|content| content["parts"].as_array_mut())
+             {
+                 parts.push(json!({ "text": negative }));
+             }
+         }
          if let Some(obj) = inner_request.as_object_mut() {
              obj.remove("tools");
              obj.remove("systemInstruction");
@@ -436,6 +433,119 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
     })
 }
 
+/// 为每次重试准备请求体: 写入本次账号的 project 并生成新的 requestId
+/// 转换结果与账号无关，重试时无需重新转换整个消息历史
+pub fn prepare_attempt_body(body: &mut Value, project_id: &str) {
+    body["project"] = json!(project_id);
+    body["requestId"] = json!(format!("openai-{}", uuid::Uuid::new_v4()));
+}
+
+/// 递归哈希 JSON 值 (不经过字符串序列化)
+fn hash_value<H: Hasher>(value: &Value, state: &mut H) {
+    match value {
+        Value::Null => 0u8.hash(state),
+        Value::Bool(b) => (1u8, b).hash(state),
+        Value::Number(n) => (2u8, n.to_string()).hash(state),
+        Value::String(s) => (3u8, s).hash(state),
+        Value::Array(arr) => {
+            (4u8, arr.len()).hash(state);
+            for v in arr {
+                hash_value(v, state);
+            }
+        }
+        Value::Object(map) => {
+            (5u8, map.len()).hash(state);
+            for (k, v) in map {
+                k.hash(state);
+                hash_value(v, state);
+            }
+        }
+    }
+}
+
+fn tool_set_hash(tools: &[Value]) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for tool in tools {
+        hash_value(tool, &mut hasher);
+    }
+    hasher.finish()
+}
+
+/// 获取清洗后的 functionDeclarations，命中缓存时直接复用
+fn cached_function_declarations(tools: &[Value]) -> Arc<Vec<Value>> {
+    let key = tool_set_hash(tools);
+    if let Some(hit) = TOOL_DECLARATION_CACHE.lock().ok().and_then(|c| c.get(&key).cloned()) {
+        return hit;
+    }
+
+    let declarations = Arc::new(build_function_declarations(tools));
+    if let Ok(mut cache) = TOOL_DECLARATION_CACHE.lock() {
+        if cache.len() >= TOOL_DECLARATION_CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(key, declarations.clone());
+    }
+    declarations
+}
+
+/// OpenAI tools → Gemini functionDeclarations
+fn build_function_declarations(tools: &[Value]) -> Vec<Value> {
+    let mut function_declarations: Vec<Value> = Vec::new();
+    for tool in tools.iter() {
+        let mut gemini_func = if let Some(func) = tool.get("function") {
+            func.clone()
+        } else {
+            let mut func = tool.clone();
+            if let Some(obj) = func.as_object_mut() {
+                obj.remove("type");
+                obj.remove("strict");
+                obj.remove("additionalProperties");
+            }
+            func
+        };
+
+        if let Some(name) = gemini_func.get("name").and_then(|v| v.as_str()) {
+            // 跳过内置联网工具名称，避免重复定义
+            if name == "web_search" || name == "google_search" || name == "web_search_20250305" {
+                continue;
+            }
+            
+            if name == "local_shell_call" {
+                if let Some(obj) = gemini_func.as_object_mut() {
+                    obj.insert("name".to_string(), json!("shell"));
+                }
+            }
+        }
+
+        // [NEW CRITICAL FIX] 清除函数定义根层级的非法字段 (解决报错持久化)
+        if let Some(obj) = gemini_func.as_object_mut() {
+            obj.remove("format");
+            obj.remove("strict");
+            obj.remove("additionalProperties");
+            obj.remove("type"); // [NEW] Gemini 不支持在 FunctionDeclaration 根层级出现 type: "function"
+        }
+
+        if let Some(params) = gemini_func.get_mut("parameters") {
+            // [DEEP FIX] 统一调用公共库清洗：展开 $ref 并剔除所有层级的 format/definitions
+            crate::proxy::common::json_schema::clean_json_schema(params);
+
+            // Gemini v1internal 要求：
+            // 1. type 必须是大写 (OBJECT, STRING 等)
+            // 2. 根对象必须有 "type": "OBJECT"
+            if let Some(params_obj) = params.as_object_mut() {
+                if !params_obj.contains_key("type") {
+                    params_obj.insert("type".to_string(), json!("OBJECT"));
+                }
+            }
+            
+            // 递归转换 type 为大写 (符合 Protobuf 定义)
+            enforce_uppercase_types(params);
+        }
+        function_declarations.push(gemini_func);
+    }
+    function_declarations
+}
+
 fn enforce_uppercase_types(value: &mut Value) {
     if let Value::Object(map) = value {
         if let Some(type_val) = map.get_mut("type") {
@@ -481,6 +591,8 @@ mod tests {
                 tool_calls: None,
                 tool_call_id: None,
                 name: None,
+                prefix: None,
+                images: None,
             }],
             stream: false,
             n: None,
@@ -495,6 +607,10 @@ mod tests {
             instructions: None,
             input: None,
             prompt: None,
+            user: None,
+            conversation_id: None,
+            seed: None,
+            image: Default::default(),
         };
 
         let result = transform_openai_request(&req, "test-v", "gemini-1.5-flash");
@@ -503,4 +619,131 @@ mod tests {
         assert_eq!(parts[0]["text"].as_str().unwrap(), "What is in this image?");
         assert_eq!(parts[1]["inlineData"]["mimeType"].as_str().unwrap(), "image/png");
     }
+
+    #[test]
+    fn test_file_parts_and_local_paths() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": [
+                { "type": "text", "text": "Summarize" },
+                { "type": "file", "file": { "filename": "notes.txt", "file_data": "data:;base64,aGk=" } },
+                { "type": "file", "file": { "file_id": "file-abc" } },
+                { "type": "image_url", "image_url": { "url": "/etc/hosts" } }
+            ]}]
+        }))
+        .unwrap();
+
+        let result = transform_openai_request(&req, "test-v", "gemini-2.5-flash");
+        let parts = result["request"]["contents"][0]["parts"].as_array().unwrap();
+        // file_id 与未内联的本地路径均被跳过，不会由映射层读取本机文件
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1]["inlineData"]["mimeType"], "text/plain");
+        assert_eq!(parts[1]["inlineData"]["data"], "aGk=");
+    }
+
+    #[test]
+    fn test_cached_function_declarations_reused() {
+        let tools = vec![json!({
+            "type": "function",
+            "function": {
+                "name": "read_file",
+                "parameters": {
+                    "type": "object",
+                    "properties": { "path": { "type": "string", "format": "uri" } },
+                    "required": ["path"]
+                }
+            }
+        })];
+
+        let first = cached_function_declarations(&tools);
+        let second = cached_function_declarations(&tools);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first[0]["parameters"]["type"], "OBJECT");
+        assert_eq!(first[0]["parameters"]["properties"]["path"]["type"], "STRING");
+
+        // 工具集变化时重新计算
+        let mut changed = tools.clone();
+        changed[0]["function"]["name"] = json!("write_file");
+        assert_ne!(tool_set_hash(&tools), tool_set_hash(&changed));
+    }
+
+    #[test]
+    fn test_tool_response_ids_resolved() {
+        let msg = |role: &str, content: Option<&str>, tool_calls: Option<Vec<ToolCall>>, tool_call_id: Option<&str>, name: Option<&str>| OpenAIMessage {
+            role: role.to_string(),
+            content: content.map(|c| OpenAIContent::String(c.to_string())),
+            reasoning_content: None,
+            tool_calls,
+            tool_call_id: tool_call_id.map(|s| s.to_string()),
+            name: name.map(|s| s.to_string()),
+            prefix: None,
+            images: None,
+        };
+        // 上一轮响应中分配、但历史消息里已被客户端裁剪掉的调用
+        let earlier = crate::proxy::ToolCallIdTable::global().assign("conv-tool-ids", "list_dir", &json!({"path": "."}), None);
+
+        let req = OpenAIRequest {
+            model: "gemini-2.5-flash".to_string(),
+            messages: vec![
+                msg("user", Some("read the file please"), None, None, None),
+                msg("assistant", None, Some(vec![ToolCall {
+                    id: "call_a".to_string(),
+                    r#type: "function".to_string(),
+                    function: ToolFunction { name: "read_file".to_string(), arguments: "{\"path\":\"a.txt\"}".to_string() },
+                }]), None, None),
+                // legacy function 消息没有 tool_call_id
+                msg("function", Some("contents"), None, None, Some("read_file")),
+                msg("tool", Some("a.txt"), None, Some(&earlier), None),
+            ],
+            stream: false,
+            n: None,
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            stop: None,
+            response_format: None,
+            tools: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
+            instructions: None,
+            input: None,
+            prompt: None,
+            user: None,
+            conversation_id: Some("conv-tool-ids".to_string()),
+            seed: None,
+            image: Default::default(),
+        };
+
+        let result = transform_openai_request(&req, "test-v", "gemini-2.5-flash");
+        let responses: Vec<&Value> = result["request"]["contents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|c| c["parts"].as_array().unwrap().iter())
+            .filter_map(|p| p.get("functionResponse"))
+            .collect();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], "call_a");
+        assert_eq!(responses[0]["name"], "read_file");
+        assert_eq!(responses[1]["id"], earlier.as_str());
+        assert_eq!(responses[1]["name"], "list_dir");
+    }
+
+    #[test]
+    fn test_image_options_and_seed() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gemini-3-pro-image-2k",
+            "messages": [{ "role": "user", "content": "a lighthouse at dusk" }],
+            "seed": 7,
+            "size": "1792x1024",
+            "negative_prompt": "people"
+        }))
+        .unwrap();
+        let result = transform_openai_request(&req, "test-v", "gemini-3-pro-image");
+        let gen = &result["request"]["generationConfig"];
+        assert_eq!(gen["seed"], 7);
+        assert_eq!(gen["imageConfig"], json!({ "aspectRatio": "16:9", "imageSize": "2K" }));
+        let parts = result["request"]["contents"][0]["parts"].as_array().unwrap();
+        assert!(parts.last().unwrap()["text"].as_str().unwrap().ends_with("people"));
+    }
 }
@@ -3,8 +3,10 @@ use super::models::*;
 use serde_json::Value;
 
 pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
+    let _span = tracing::info_span!(target: crate::proxy::telemetry::TARGET, "map_back", ag.protocol = "openai", ag.streaming = false).entered();
     // 解包 response 字段
     let raw = gemini_response.get("response").unwrap_or(gemini_response);
+    let session_id = crate::proxy::session_manager::SessionManager::current_conversation_id();
 
     let mut choices = Vec::new();
 
@@ -14,6 +16,7 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
             let mut content_out = String::new();
             let mut thought_out = String::new();
             let mut tool_calls = Vec::new();
+            let mut images = Vec::new();
 
             // 提取 content 和 tool_calls
             if let Some(parts) = candidate
@@ -29,6 +32,9 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
                         .and_then(|s| s.as_str())
                     {
                         super::streaming::store_thought_signature(sig);
+                        if let Some(sid) = crate::proxy::session_manager::SessionManager::current_conversation_id() {
+                            crate::proxy::SignatureCache::global().cache_session_signature(&sid, sig.to_string());
+                        }
                     }
 
                     // 检查该 part 是否是思考内容 (thought: true)
@@ -54,11 +60,12 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
                             .get("args")
                             .map(|v| v.to_string())
                             .unwrap_or_else(|| "{}".to_string());
-                        let id = fc
-                            .get("id")
-                            .and_then(|v| v.as_str())
-                            .map(|s| s.to_string())
-                            .unwrap_or_else(|| format!("{}-{}", name, uuid::Uuid::new_v4()));
+                        let id = crate::proxy::ToolCallIdTable::global().assign(
+                            session_id.as_deref().unwrap_or_default(),
+                            name,
+                            fc.get("args").unwrap_or(&serde_json::json!({})),
+                            fc.get("id").and_then(|v| v.as_str()),
+                        );
 
                         tool_calls.push(ToolCall {
                             id,
@@ -70,16 +77,12 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
                         });
                     }
 
-                    // 图片处理 (响应中直接返回图片的情况)
-                    if let Some(img) = part.get("inlineData") {
-                        let mime_type = img
-                            .get("mimeType")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("image/png");
-                        let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
-                        if !data.is_empty() {
-                            content_out.push_str(&format!("![image](data:{};base64,{})", mime_type, data));
+                    // 图片处理 (响应中直接返回图片的情况，呈现方式见 image_output)
+                    if let Some(rendered) = crate::proxy::mappers::image_output::render_part(part) {
+                        if let Some(markdown) = rendered.markdown {
+                            content_out.push_str(&markdown);
                         }
+                        images.extend(rendered.image);
                     }
                 }
             }
@@ -156,6 +159,8 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
                     },
                     tool_call_id: None,
                     name: None,
+                    prefix: None,
+                    images: if images.is_empty() { None } else { Some(images) },
                 },
                 finish_reason: Some(finish_reason.to_string()),
             });
@@ -207,6 +212,7 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
             .to_string(),
         choices,
         usage,
+        x_ag_truncated: None,
     }
 }
 
@@ -87,6 +87,8 @@ pub fn create_openai_sse_stream(
     mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     model: String,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
+    // 会话 ID 在创建流时捕获 (流在请求作用域外被轮询)
+    let session_id = crate::proxy::session_manager::SessionManager::current_conversation_id();
     let mut buffer = BytesMut::new();
     
     // 在流开始时生成固定的 ID 和 timestamp，所有 chunk 共用
@@ -96,6 +98,9 @@ pub fn create_openai_sse_stream(
     let stream = async_stream::stream! {
         let mut emitted_tool_calls = std::collections::HashSet::new();
         let mut final_usage: Option<super::models::OpenAIUsage> = None;
+        // 已输出内容但未收到 finish_reason 时，结束前补发截断标记
+        let mut emitted_output = false;
+        let mut finished = false;
         while let Some(item) = gemini_stream.next().await {
             match item {
                 Ok(bytes) => {
@@ -139,6 +144,7 @@ pub fn create_openai_sse_stream(
 
                                             let mut content_out = String::new();
                                             let mut thought_out = String::new();
+                                            let mut images_out = Vec::new();
                                             
                                             if let Some(parts_list) = parts {
                                                 for part in parts_list {
@@ -156,14 +162,16 @@ pub fn create_openai_sse_stream(
                                                     // 捕获 thoughtSignature (Gemini 3 工具调用必需)
                                                     if let Some(sig) = part.get("thoughtSignature").or(part.get("thought_signature")).and_then(|s| s.as_str()) {
                                                         store_thought_signature(sig);
+                                                        if let Some(sid) = &session_id {
+                                                            crate::proxy::SignatureCache::global().cache_session_signature(sid, sig.to_string());
+                                                        }
                                                     }
 
-                                                    if let Some(img) = part.get("inlineData") {
-                                                        let mime_type = img.get("mimeType").and_then(|v| v.as_str()).unwrap_or("image/png");
-                                                        let data = img.get("data").and_then(|v| v.as_str()).unwrap_or("");
-                                                        if !data.is_empty() {
-                                                            content_out.push_str(&format!("![image](data:{};base64,{})", mime_type, data));
+                                                    if let Some(rendered) = crate::proxy::mappers::image_output::render_part(part) {
+                                                        if let Some(markdown) = rendered.markdown {
+                                                            content_out.push_str(&markdown);
                                                         }
+                                                        images_out.extend(rendered.image);
                                                     }
 
                                                     // Handle function call
@@ -175,11 +183,13 @@ pub fn create_openai_sse_stream(
                                                             let name = func_call.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
                                                             let args = func_call.get("args").unwrap_or(&json!({})).to_string();
                                                             
-                                                            // Generate stable ID
-                                                            let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                                                            use std::hash::{Hash, Hasher};
-                                                            serde_json::to_string(func_call).unwrap_or_default().hash(&mut hasher);
-                                                            let call_id = format!("call_{:x}", hasher.finish());
+                                                            // 按会话分配稳定 ID (见 ToolCallIdTable)
+                                                            let call_id = crate::proxy::ToolCallIdTable::global().assign(
+                                                                session_id.as_deref().unwrap_or_default(),
+                                                                name,
+                                                                func_call.get("args").unwrap_or(&json!({})),
+                                                                func_call.get("id").and_then(|v| v.as_str()),
+                                                            );
                                                             
                                                             // Emit tool_calls delta
                                                             let tool_call_chunk = json!({
@@ -206,6 +216,7 @@ pub fn create_openai_sse_stream(
                                                             });
                                                             
                                                             let sse_out = format!("data: {}\n\n", serde_json::to_string(&tool_call_chunk).unwrap_or_default());
+                                                            emitted_output = true;
                                                             yield Ok::<Bytes, String>(Bytes::from(sse_out));
                                                         }
                                                     }
@@ -247,8 +258,8 @@ pub fn create_openai_sse_stream(
                                                 }
                                             }
 
-                                            // 只有当 content 和 thought 都为空时才跳过
-                                            if content_out.is_empty() && thought_out.is_empty() {
+                                            // 只有当 content、thought 与图片都为空时才跳过
+                                            if content_out.is_empty() && thought_out.is_empty() && images_out.is_empty() {
                                                 // Skip empty chunks if no text/grounding/thought was found
                                                 if candidate.get("finishReason").is_none() {
                                                     continue;
@@ -266,6 +277,13 @@ pub fn create_openai_sse_stream(
                                                     _ => f,
                                                 });
 
+                                            if !content_out.is_empty() || !thought_out.is_empty() || !images_out.is_empty() {
+                                                emitted_output = true;
+                                            }
+                                            if finish_reason.is_some() {
+                                                finished = true;
+                                            }
+
                                             // Construct OpenAI SSE chunk
                                             // 如果有思考内容，先发送 reasoning_content chunk
                                             if !thought_out.is_empty() {
@@ -291,8 +309,8 @@ pub fn create_openai_sse_stream(
                                             }
 
                                             // 发送正常 content chunk
-                                            if !content_out.is_empty() || finish_reason.is_some() {
-                                                let openai_chunk = json!({
+                                            if !content_out.is_empty() || !images_out.is_empty() || finish_reason.is_some() {
+                                                let mut openai_chunk = json!({
                                                     "id": &stream_id,
                                                     "object": "chat.completion.chunk",
                                                     "created": created_ts,
@@ -307,6 +325,9 @@ pub fn create_openai_sse_stream(
                                                         }
                                                     ]
                                                 });
+                                                if !images_out.is_empty() {
+                                                    openai_chunk["choices"][0]["delta"]["images"] = json!(images_out);
+                                                }
 
                                                 let sse_out = format!("data: {}\n\n", serde_json::to_string(&openai_chunk).unwrap_or_default());
                                                 yield Ok::<Bytes, String>(Bytes::from(sse_out));
@@ -319,6 +340,12 @@ pub fn create_openai_sse_stream(
                     }
                 }
                 Err(e) => {
+                    // 已有部分输出: 以 finish_reason=length + x_ag_truncated 结束，而不是报错
+                    if emitted_output && !finished {
+                        tracing::warn!("[OpenAI-SSE] Upstream stream failed after partial output, returning truncated response: {}", e);
+                        break;
+                    }
+
                     use crate::proxy::mappers::error_classifier::classify_stream_error;
                     let (error_type, user_message, i18n_key) = classify_stream_error(&e);
                     
@@ -353,6 +380,19 @@ pub fn create_openai_sse_stream(
             }
         }
         
+        if emitted_output && !finished {
+            let truncated_chunk = json!({
+                "id": &stream_id,
+                "object": "chat.completion.chunk",
+                "created": created_ts,
+                "model": &model,
+                "choices": [{ "index": 0, "delta": {}, "finish_reason": "length" }],
+                "x_ag_truncated": true
+            });
+            let sse_out = format!("data: {}\n\n", serde_json::to_string(&truncated_chunk).unwrap_or_default());
+            yield Ok::<Bytes, String>(Bytes::from(sse_out));
+        }
+
         // Emit usage event if captured before [DONE]
         if let Some(usage) = final_usage {
             let usage_chunk = json!({
@@ -371,13 +411,19 @@ pub fn create_openai_sse_stream(
         yield Ok::<Bytes, String>(Bytes::from("data: [DONE]\n\n"));
     };
 
-    Box::pin(stream)
+    // 链路追踪: 流式响应转换阶段 (随流结束关闭)
+    let span = tracing::info_span!(target: crate::proxy::telemetry::TARGET, "map_back", ag.protocol = "openai", ag.streaming = true);
+    crate::proxy::telemetry::traced_stream(span, stream)
 }
 
+/// `echo` 不为空时先输出一个携带原始 prompt 的分片 (legacy completions 的 echo 参数)
 pub fn create_legacy_sse_stream(
     mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     model: String,
+    echo: Option<String>,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
+    // 会话 ID 在创建流时捕获 (流在请求作用域外被轮询)
+    let session_id = crate::proxy::session_manager::SessionManager::current_conversation_id();
     let mut buffer = BytesMut::new();
     
     // Generate constant alphanumeric ID (mimics OpenAI base62 format)
@@ -394,6 +440,16 @@ pub fn create_legacy_sse_stream(
     
     let stream = async_stream::stream! {
         let mut final_usage: Option<super::models::OpenAIUsage> = None;
+        if let Some(prompt) = echo.filter(|p| !p.is_empty()) {
+            let echo_chunk = json!({
+                "id": &stream_id,
+                "object": "text_completion",
+                "created": created_ts,
+                "model": &model,
+                "choices": [{ "text": prompt, "index": 0, "logprobs": null, "finish_reason": null }]
+            });
+            yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&echo_chunk).unwrap_or_default())));
+        }
         while let Some(item) = gemini_stream.next().await {
             match item {
                 Ok(bytes) => {
@@ -432,6 +488,9 @@ pub fn create_legacy_sse_stream(
                                                 // 捕获 thoughtSignature 到全局存储
                                                 if let Some(sig) = part.get("thoughtSignature").or(part.get("thought_signature")).and_then(|s| s.as_str()) {
                                                     store_thought_signature(sig);
+                                                    if let Some(sid) = &session_id {
+                                                        crate::proxy::SignatureCache::global().cache_session_signature(sid, sig.to_string());
+                                                    }
                                                 }
                                             }
                                         }
@@ -529,13 +588,17 @@ pub fn create_legacy_sse_stream(
         tokio::time::sleep(std::time::Duration::from_millis(50)).await;
     };
 
-    Box::pin(stream)
+    // 链路追踪: 流式响应转换阶段 (随流结束关闭)
+    let span = tracing::info_span!(target: crate::proxy::telemetry::TARGET, "map_back", ag.protocol = "openai", ag.streaming = true);
+    crate::proxy::telemetry::traced_stream(span, stream)
 }
 
 pub fn create_codex_sse_stream(
     mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     _model: String,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
+    // 会话 ID 在创建流时捕获 (流在请求作用域外被轮询)
+    let session_id = crate::proxy::session_manager::SessionManager::current_conversation_id();
     let mut buffer = BytesMut::new();
     
     // Generate alphanumeric ID
@@ -621,6 +684,9 @@ pub fn create_codex_sse_stream(
                                                 if let Some(sig) = part.get("thoughtSignature").or(part.get("thought_signature")).and_then(|s| s.as_str()) {
                                                     tracing::debug!("[Codex-SSE] 捕获 thoughtSignature (长度: {})", sig.len());
                                                     store_thought_signature(sig);
+                                                    if let Some(sid) = &session_id {
+                                                        crate::proxy::SignatureCache::global().cache_session_signature(sid, sig.to_string());
+                                                    }
                                                 }
                                                 // Handle function call in chunk with deduplication
                                                 if let Some(func_call) = part.get("functionCall") {
@@ -630,11 +696,13 @@ pub fn create_codex_sse_stream(
 
                                                                                 let name = func_call.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
                                                                                 let _args = func_call.get("args").unwrap_or(&json!({})).to_string();                                                        
-                                                        // Stable ID generation based on hashed content to be consistent
-                                                        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                                                        use std::hash::{Hash, Hasher};
-                                                        serde_json::to_string(func_call).unwrap_or_default().hash(&mut hasher);
-                                                        let call_id = format!("call_{:x}", hasher.finish());
+                                                        // 按会话分配稳定 ID (见 ToolCallIdTable)
+                                                        let call_id = crate::proxy::ToolCallIdTable::global().assign(
+                                                            session_id.as_deref().unwrap_or_default(),
+                                                            name,
+                                                            func_call.get("args").unwrap_or(&json!({})),
+                                                            func_call.get("id").and_then(|v| v.as_str()),
+                                                        );
                                                         
                                                         // Parse args once
                                                         let fallback_args = json!({});
@@ -933,11 +1001,12 @@ pub fn create_codex_sse_stream(
 
             if let Some(cmd_val) = detected_cmd_val {
                 if detected_cmd_type == "shell" {
-                     let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                     use std::hash::{Hash, Hasher};
-                     "ssop_shell_call".hash(&mut hasher); // Unique seed
-                     serde_json::to_string(&cmd_val).unwrap_or_default().hash(&mut hasher);
-                     let call_id = format!("call_{:x}", hasher.finish());
+                     let call_id = crate::proxy::ToolCallIdTable::global().assign(
+                         session_id.as_deref().unwrap_or_default(),
+                         "shell",
+                         &json!({ "command": &cmd_val }),
+                         None,
+                     );
 
                      let mut cmd_vec: Vec<String> = cmd_val.as_array().unwrap().iter().map(|v| v.as_str().unwrap_or("").to_string()).collect();
                      
@@ -1025,5 +1094,7 @@ pub fn create_codex_sse_stream(
         yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&completed_ev).unwrap())));
     };
 
-    Box::pin(stream)
+    // 链路追踪: 流式响应转换阶段 (随流结束关闭)
+    let span = tracing::info_span!(target: crate::proxy::telemetry::TARGET, "map_back", ag.protocol = "openai", ag.streaming = true);
+    crate::proxy::telemetry::traced_stream(span, stream)
 }
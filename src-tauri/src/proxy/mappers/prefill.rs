@@ -0,0 +1,170 @@
+// Assistant 预填充 (prefill) 支持
+// 请求最后一条为 assistant 消息 (Claude 风格 prefill，或 OpenAI 兼容客户端的 `prefix: true`) 时，
+// 映射为末尾的 model content，由上游从该前缀继续生成。
+// 部分模型会先把前缀原样重复一遍，这里在 Gemini 响应层剔除重复的前缀，保证下游只收到续写部分。
+
+use serde_json::Value;
+
+use crate::proxy::upstream::resume::GeminiByteStream;
+use crate::proxy::upstream::sse_rewrite::{candidate_mut, rewrite_sse_stream, SseRewriter};
+
+/// 从 Gemini 请求体 (v1internal 包装或原生格式) 中提取末尾 model content 的预填充文本
+pub fn prefill_text(body: &Value) -> Option<String> {
+    let contents = body.get("request").unwrap_or(body).get("contents")?.as_array()?;
+    let last = contents.last()?;
+    if last.get("role").and_then(|r| r.as_str()) != Some("model") {
+        return None;
+    }
+    let mut text = String::new();
+    for part in last.get("parts")?.as_array()? {
+        if part.get("functionCall").is_some() {
+            return None;
+        }
+        if part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false) {
+            continue;
+        }
+        if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+            text.push_str(t);
+        }
+    }
+    (!text.is_empty()).then_some(text)
+}
+
+/// 剔除响应开头重复的预填充前缀。
+/// 前缀尚未能判定是否重复时暂存文本，判定后一次性放行。
+pub struct EchoFilter {
+    prefill: String,
+    pending: String,
+    decided: bool,
+}
+
+impl EchoFilter {
+    pub fn new(prefill: String) -> Self {
+        Self {
+            prefill,
+            pending: String::new(),
+            decided: false,
+        }
+    }
+
+    pub fn push(&mut self, text: &str) -> String {
+        if self.decided {
+            return text.to_string();
+        }
+        self.pending.push_str(text);
+        if self.pending.starts_with(&self.prefill) {
+            self.decided = true;
+            let rest = self.pending.split_off(self.prefill.len());
+            self.pending.clear();
+            return rest;
+        }
+        if self.prefill.starts_with(&self.pending) {
+            return String::new();
+        }
+        self.decided = true;
+        std::mem::take(&mut self.pending)
+    }
+
+    /// 响应结束时放行仍在暂存的文本 (输出恰为前缀的一部分)
+    pub fn flush(&mut self) -> String {
+        self.decided = true;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// 对单个 Gemini 响应 (流式分块或完整响应) 的正文 part 应用过滤；`finished` 时追加暂存文本
+fn filter_response(value: &mut Value, filter: &mut EchoFilter, finished: bool) {
+    let Some(candidate) = candidate_mut(value) else {
+        return;
+    };
+    let finished = finished || candidate.get("finishReason").is_some();
+    let Some(parts) = candidate
+        .get_mut("content")
+        .and_then(|c| c.get_mut("parts"))
+        .and_then(|p| p.as_array_mut())
+    else {
+        return;
+    };
+    parts.retain_mut(|part| {
+        if part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false) {
+            return true;
+        }
+        let Some(text) = part.get("text").and_then(|t| t.as_str()) else {
+            return true;
+        };
+        let kept = filter.push(text);
+        if kept.is_empty() {
+            return false;
+        }
+        part["text"] = Value::String(kept);
+        true
+    });
+    if finished {
+        let rest = filter.flush();
+        if !rest.is_empty() {
+            parts.push(serde_json::json!({ "text": rest }));
+        }
+    }
+}
+
+/// 非流式响应: 剔除开头重复的预填充前缀
+pub fn strip_echo_in_response(response: &mut Value, prefill: &str) {
+    let mut filter = EchoFilter::new(prefill.to_string());
+    filter_response(response, &mut filter, true);
+}
+
+impl SseRewriter for EchoFilter {
+    fn rewrite(&mut self, event: &mut Value) -> bool {
+        filter_response(event, self, false);
+        true
+    }
+}
+
+/// 流式响应: 改写 SSE 分块中的正文；无预填充时原样返回
+pub fn strip_echo_stream(stream: GeminiByteStream, prefill: Option<String>) -> GeminiByteStream {
+    match prefill {
+        Some(prefill) => rewrite_sse_stream(stream, EchoFilter::new(prefill)),
+        None => stream,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_prefill_text_from_trailing_model_content() {
+        let body = json!({ "request": { "contents": [
+            { "role": "user", "parts": [{ "text": "List three colors as JSON" }] },
+            { "role": "model", "parts": [{ "text": "{\"colors\": [" }] }
+        ]}});
+        assert_eq!(prefill_text(&body).as_deref(), Some("{\"colors\": ["));
+
+        let no_prefill = json!({ "contents": [{ "role": "user", "parts": [{ "text": "hi" }] }] });
+        assert!(prefill_text(&no_prefill).is_none());
+    }
+
+    #[test]
+    fn test_echo_filter_strips_repeated_prefix_across_chunks() {
+        let mut filter = EchoFilter::new("Dear Sir,".to_string());
+        assert_eq!(filter.push("Dear "), "");
+        assert_eq!(filter.push("Sir, thank"), " thank");
+        assert_eq!(filter.push(" you"), " you");
+
+        // 未重复前缀时原样放行
+        let mut filter = EchoFilter::new("Dear Sir,".to_string());
+        assert_eq!(filter.push("Dea"), "");
+        assert_eq!(filter.push("rly"), "Dearly");
+    }
+
+    #[test]
+    fn test_strip_echo_in_response() {
+        let mut resp = json!({ "response": { "candidates": [{
+            "content": { "parts": [{ "text": "{\"colors\": [\"red\"]}" }] },
+            "finishReason": "STOP"
+        }]}});
+        strip_echo_in_response(&mut resp, "{\"colors\": [");
+        assert_eq!(resp["response"]["candidates"][0]["content"]["parts"][0]["text"], "\"red\"]}");
+    }
+}
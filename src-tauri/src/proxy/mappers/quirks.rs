@@ -0,0 +1,264 @@
+// 模型 quirk 登记表
+// 新 Gemini 版本常带来行为差异 (是否强制 thoughtSignature、拒绝回传思维内容、不支持某些 Schema 关键字、端点版本变化)。
+// 这些差异由签名的远程清单下发，本地覆盖优先，让修正无需等待完整的应用更新。
+// 优先级: 本地覆盖 > 远程清单 > 内置行为，逐字段合并；每层内精确模型名优先于通配符。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{OnceLock, RwLock};
+
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::config::ModelQuirk;
+
+/// 远程清单内容
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct QuirkManifest {
+    /// 单调递增的版本号，低于已缓存版本的清单会被拒绝 (防止回滚到旧清单)
+    pub version: u64,
+    #[serde(default)]
+    pub models: Vec<ModelQuirk>,
+    /// 替换内置的 v1internal 端点 (仅在用户未配置 upstream_endpoints 时生效)
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+}
+
+/// 合并后对单个模型生效的 quirk
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResolvedQuirks {
+    pub requires_thought_signature: Option<bool>,
+    pub strip_thought_parts: bool,
+    pub schema_drop_keywords: Vec<String>,
+}
+
+#[derive(Default)]
+struct Registry {
+    manifest: QuirkManifest,
+    overrides: Vec<ModelQuirk>,
+}
+
+fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(Registry::default()))
+}
+
+pub fn set_manifest(manifest: QuirkManifest) {
+    if let Ok(mut registry) = registry().write() {
+        registry.manifest = manifest;
+    }
+}
+
+pub fn set_overrides(overrides: Vec<ModelQuirk>) {
+    if let Ok(mut registry) = registry().write() {
+        registry.overrides = overrides;
+    }
+}
+
+fn find_rule<'a>(rules: &'a [ModelQuirk], model: &str) -> Option<&'a ModelQuirk> {
+    rules
+        .iter()
+        .find(|r| r.model.eq_ignore_ascii_case(model))
+        .or_else(|| rules.iter().find(|r| r.model.contains('*') && wildcard_match(&r.model, model)))
+}
+
+pub fn resolve(model: &str) -> ResolvedQuirks {
+    let Ok(registry) = registry().read() else {
+        return ResolvedQuirks::default();
+    };
+    let manifest = find_rule(&registry.manifest.models, model);
+    let local = find_rule(&registry.overrides, model);
+
+    let mut schema_drop_keywords: Vec<String> = Vec::new();
+    for keyword in manifest.into_iter().chain(local).flat_map(|r| r.schema_drop_keywords.iter()) {
+        if !schema_drop_keywords.contains(keyword) {
+            schema_drop_keywords.push(keyword.clone());
+        }
+    }
+    ResolvedQuirks {
+        requires_thought_signature: local
+            .and_then(|r| r.requires_thought_signature)
+            .or_else(|| manifest.and_then(|r| r.requires_thought_signature)),
+        strip_thought_parts: local
+            .and_then(|r| r.strip_thought_parts)
+            .or_else(|| manifest.and_then(|r| r.strip_thought_parts))
+            .unwrap_or(false),
+        schema_drop_keywords,
+    }
+}
+
+/// 清单下发的端点 (为空时使用内置 prod → daily)
+pub fn manifest_endpoints() -> Vec<String> {
+    registry().read().map(|r| r.manifest.endpoints.clone()).unwrap_or_default()
+}
+
+fn drop_keywords(schema: &mut Value, keywords: &[String]) {
+    match schema {
+        Value::Object(map) => {
+            map.retain(|k, _| !keywords.contains(k));
+            for (key, child) in map.iter_mut() {
+                // properties 下的键是字段名而不是关键字，只处理其值
+                if key == "properties" {
+                    if let Some(props) = child.as_object_mut() {
+                        props.values_mut().for_each(|p| drop_keywords(p, keywords));
+                    }
+                } else {
+                    drop_keywords(child, keywords);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| drop_keywords(item, keywords)),
+        _ => {}
+    }
+}
+
+/// 按 quirk 修正已转换的 Gemini 请求体 (v1internal 包装或原生)。返回请求体是否被修改
+pub fn apply_to_request(body: &mut Value, model: &str) -> bool {
+    let quirks = resolve(model);
+    if !quirks.strip_thought_parts && quirks.schema_drop_keywords.is_empty() {
+        return false;
+    }
+    let request = if body.get("request").is_some() { &mut body["request"] } else { body };
+    let mut changed = false;
+
+    if quirks.strip_thought_parts {
+        if let Some(contents) = request.get_mut("contents").and_then(|c| c.as_array_mut()) {
+            for content in contents.iter_mut() {
+                if let Some(parts) = content.get_mut("parts").and_then(|p| p.as_array_mut()) {
+                    let before = parts.len();
+                    parts.retain(|p| p.get("thought").and_then(|t| t.as_bool()) != Some(true));
+                    changed |= parts.len() != before;
+                }
+            }
+            // 只含思维内容的消息整体移除
+            contents.retain(|c| c.get("parts").and_then(|p| p.as_array()).map_or(true, |p| !p.is_empty()));
+        }
+    }
+
+    if !quirks.schema_drop_keywords.is_empty() {
+        let keywords = &quirks.schema_drop_keywords;
+        if let Some(tools) = request.get_mut("tools").and_then(|t| t.as_array_mut()) {
+            for decl in tools
+                .iter_mut()
+                .filter_map(|t| t.get_mut("functionDeclarations").and_then(|d| d.as_array_mut()))
+                .flatten()
+            {
+                if let Some(params) = decl.get_mut("parameters") {
+                    drop_keywords(params, keywords);
+                    changed = true;
+                }
+            }
+        }
+        if let Some(schema) = request.pointer_mut("/generationConfig/responseSchema") {
+            drop_keywords(schema, keywords);
+            changed = true;
+        }
+    }
+
+    if changed {
+        tracing::debug!("[Quirks] 已按 quirk 规则修正 {} 的请求体", model);
+    }
+    changed
+}
+
+/// 校验清单签名并解析。signature 为对清单原始字节的 Ed25519 签名 (base64url)，public_key 为 32 字节公钥 (base64url)
+pub fn verify_manifest(body: &[u8], signature: &str, public_key: &str) -> Result<QuirkManifest, String> {
+    let key = jsonwebtoken::DecodingKey::from_ed_components(public_key.trim().trim_end_matches('='))
+        .map_err(|e| format!("invalid public key: {}", e))?;
+    let signature = signature.trim().trim_end_matches('=').replace('+', "-").replace('/', "_");
+    let valid = jsonwebtoken::crypto::verify(&signature, body, &key, jsonwebtoken::Algorithm::EdDSA)
+        .map_err(|e| format!("invalid signature: {}", e))?;
+    if !valid {
+        return Err("manifest signature does not match".to_string());
+    }
+
+    let manifest: QuirkManifest = serde_json::from_slice(body).map_err(|e| format!("invalid quirk manifest: {}", e))?;
+    if manifest.models.iter().any(|q| q.model.trim().is_empty()) {
+        return Err("quirk manifest contains an entry without a model name".to_string());
+    }
+    if let Some(bad) = manifest.endpoints.iter().find(|e| !e.starts_with("https://")) {
+        return Err(format!("quirk manifest endpoint must use https: {}", bad));
+    }
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // 仅用于测试的 Ed25519 密钥 (PKCS#8 DER) 及其公钥
+    const TEST_PRIVATE_KEY: &str = "MC4CAQAwBQYDK2VwBCIEIBES7w5P8wRTNE57OrDbtzgyzL+zNSquirn6chE76h6R";
+    const TEST_PUBLIC_KEY: &str = "R53DWM_tqtFvllfnyvPRZIizR_6_dnmK-wDA-f3beQw";
+
+    fn sign(body: &[u8]) -> String {
+        use base64::Engine as _;
+        let der = base64::engine::general_purpose::STANDARD.decode(TEST_PRIVATE_KEY).unwrap();
+        let key = jsonwebtoken::EncodingKey::from_ed_der(&der);
+        jsonwebtoken::crypto::sign(body, &key, jsonwebtoken::Algorithm::EdDSA).unwrap()
+    }
+
+    #[test]
+    fn test_verify_manifest() {
+        let body = serde_json::to_vec(&json!({
+            "version": 3,
+            "models": [{ "model": "gemini-9-*", "strip_thought_parts": true }],
+            "endpoints": ["https://cloudcode-pa.googleapis.com/v1internal"]
+        }))
+        .unwrap();
+        let signature = sign(&body);
+
+        let manifest = verify_manifest(&body, &signature, TEST_PUBLIC_KEY).unwrap();
+        assert_eq!(manifest.version, 3);
+        assert_eq!(manifest.models[0].strip_thought_parts, Some(true));
+
+        let mut tampered = body.clone();
+        tampered[12] ^= 1;
+        assert!(verify_manifest(&tampered, &signature, TEST_PUBLIC_KEY).is_err());
+        assert!(verify_manifest(&body, &signature, "not-a-key").is_err());
+    }
+
+    #[test]
+    fn test_apply_quirks() {
+        set_manifest(QuirkManifest {
+            version: 1,
+            models: vec![ModelQuirk {
+                model: "quirk-test-*".to_string(),
+                strip_thought_parts: Some(true),
+                schema_drop_keywords: vec!["pattern".to_string()],
+                ..Default::default()
+            }],
+            endpoints: Vec::new(),
+        });
+        set_overrides(vec![ModelQuirk {
+            model: "quirk-test-b".to_string(),
+            strip_thought_parts: Some(false),
+            requires_thought_signature: Some(true),
+            ..Default::default()
+        }]);
+
+        let mut body = json!({ "request": {
+            "contents": [
+                { "role": "user", "parts": [{ "text": "hi" }] },
+                { "role": "model", "parts": [{ "text": "hmm", "thought": true }] },
+                { "role": "model", "parts": [{ "text": "thinking", "thought": true }, { "text": "hello" }] }
+            ],
+            "tools": [{ "functionDeclarations": [{ "name": "f", "parameters": {
+                "type": "object",
+                "properties": { "pattern": { "type": "string", "pattern": "^a" } }
+            }}]}]
+        }});
+        assert!(apply_to_request(&mut body, "quirk-test-a"));
+        let request = &body["request"];
+        assert_eq!(request["contents"].as_array().unwrap().len(), 2);
+        assert_eq!(request["contents"][1]["parts"], json!([{ "text": "hello" }]));
+        let params = &request["tools"][0]["functionDeclarations"][0]["parameters"];
+        assert_eq!(params["properties"]["pattern"], json!({ "type": "string" }));
+
+        let b = resolve("quirk-test-b");
+        assert_eq!((b.requires_thought_signature, b.strip_thought_parts), (Some(true), false));
+        assert_eq!(b.schema_drop_keywords, vec!["pattern".to_string()]);
+        assert_eq!(resolve("gemini-2.5-flash"), ResolvedQuirks::default());
+
+        set_manifest(QuirkManifest::default());
+        set_overrides(Vec::new());
+    }
+}
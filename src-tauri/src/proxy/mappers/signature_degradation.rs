@@ -0,0 +1,290 @@
+// Gemini 3 无签名工具调用降级处理
+// 当历史 functionCall 缺少 thoughtSignature 时，按配置策略修复请求体，避免上游 400
+
+use crate::proxy::config::SignatureDegradationMode;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+/// Gemini 接受的跳过签名校验占位值 (与 mappers 中的回退值保持一致)
+const SKIP_SIGNATURE_SENTINEL: &str = "skip_thought_signature_validator";
+
+/// 只有 Gemini 3 系列强制要求 functionCall 携带 thoughtSignature (quirk 清单可覆盖)
+fn requires_signature(model: &str) -> bool {
+    if let Some(required) = crate::proxy::mappers::quirks::resolve(model).requires_thought_signature {
+        return required;
+    }
+    let m = model.to_lowercase();
+    m.starts_with("gemini-3") && !m.starts_with("projects/")
+}
+
+/// 只处理完全没有签名的调用；已带占位签名的调用上游会跳过校验，保持原样
+fn is_unsigned_call(part: &Value) -> bool {
+    if part.get("functionCall").is_none() {
+        return false;
+    }
+    match part.get("thoughtSignature") {
+        Some(Value::String(sig)) => sig.is_empty(),
+        Some(Value::Null) | None => true,
+        Some(_) => false,
+    }
+}
+
+/// 对 v1internal 请求体应用签名降级策略
+///
+/// 返回 true 表示请求体被修改。`body` 为最终发往上游的 envelope
+/// (`{ model, request: { contents, ... } }`)。
+pub fn apply_signature_degradation(
+    body: &mut Value,
+    mode: &SignatureDegradationMode,
+    fallback_model: &str,
+) -> bool {
+    if *mode == SignatureDegradationMode::Passthrough {
+        return false;
+    }
+
+    let model = body.get("model").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+    if !requires_signature(&model) {
+        return false;
+    }
+
+    let Some(contents) = body
+        .get_mut("request")
+        .and_then(|r| r.get_mut("contents"))
+        .and_then(|c| c.as_array_mut())
+    else {
+        return false;
+    };
+
+    let unsigned_count = contents
+        .iter()
+        .filter_map(|c| c.get("parts").and_then(|p| p.as_array()))
+        .flatten()
+        .filter(|p| is_unsigned_call(p))
+        .count();
+    if unsigned_count == 0 {
+        return false;
+    }
+
+    match mode {
+        SignatureDegradationMode::Passthrough => false,
+        SignatureDegradationMode::SyntheticThought => {
+            inject_synthetic_thoughts(contents);
+            tracing::warn!(
+                "[Signature-Degradation] Injected synthetic thought for {} unsigned functionCall(s) (model: {})",
+                unsigned_count, model
+            );
+            true
+        }
+        SignatureDegradationMode::DropOrphaned => {
+            let dropped = drop_unsigned_pairs(contents);
+            tracing::warn!(
+                "[Signature-Degradation] Dropped {} unsigned functionCall/functionResponse pair(s) from history (model: {})",
+                dropped, model
+            );
+            true
+        }
+        SignatureDegradationMode::DowngradeModel => {
+            for content in contents.iter_mut() {
+                crate::proxy::mappers::claude::request::clean_thinking_fields_recursive(content);
+            }
+            if let Some(gen) = body
+                .get_mut("request")
+                .and_then(|r| r.get_mut("generationConfig"))
+                .and_then(|g| g.as_object_mut())
+            {
+                gen.remove("thinkingConfig");
+            }
+            body["model"] = json!(fallback_model);
+            tracing::warn!(
+                "[Signature-Degradation] {} unsigned functionCall(s) found. Downgrading model {} -> {}",
+                unsigned_count, model, fallback_model
+            );
+            true
+        }
+    }
+}
+
+/// 在无签名 functionCall 前插入合成思维块，并补上跳过校验的占位签名
+fn inject_synthetic_thoughts(contents: &mut [Value]) {
+    for content in contents.iter_mut() {
+        let Some(parts) = content.get_mut("parts").and_then(|p| p.as_array_mut()) else {
+            continue;
+        };
+        if !parts.iter().any(is_unsigned_call) {
+            continue;
+        }
+        for part in parts.iter_mut().filter(|p| is_unsigned_call(p)) {
+            part["thoughtSignature"] = json!(SKIP_SIGNATURE_SENTINEL);
+        }
+        let has_thought = parts
+            .first()
+            .and_then(|p| p.get("thought"))
+            .and_then(|t| t.as_bool())
+            .unwrap_or(false);
+        if !has_thought {
+            parts.insert(0, json!({ "text": "Calling tools.", "thought": true }));
+        }
+    }
+}
+
+/// 删除无签名的 functionCall 及其对应的 functionResponse，返回删除的调用数
+fn drop_unsigned_pairs(contents: &mut Vec<Value>) -> usize {
+    let mut dropped_ids: HashSet<String> = HashSet::new();
+    let mut dropped_names: Vec<String> = Vec::new();
+
+    for content in contents.iter_mut() {
+        let Some(parts) = content.get_mut("parts").and_then(|p| p.as_array_mut()) else {
+            continue;
+        };
+        parts.retain(|p| {
+            if !is_unsigned_call(p) {
+                return true;
+            }
+            let call = &p["functionCall"];
+            match call.get("id").and_then(|v| v.as_str()) {
+                Some(id) => {
+                    dropped_ids.insert(id.to_string());
+                }
+                None => {
+                    if let Some(name) = call.get("name").and_then(|v| v.as_str()) {
+                        dropped_names.push(name.to_string());
+                    }
+                }
+            }
+            false
+        });
+    }
+
+    let dropped = dropped_ids.len() + dropped_names.len();
+
+    for content in contents.iter_mut() {
+        let Some(parts) = content.get_mut("parts").and_then(|p| p.as_array_mut()) else {
+            continue;
+        };
+        parts.retain(|p| {
+            let Some(resp) = p.get("functionResponse") else {
+                return true;
+            };
+            if let Some(id) = resp.get("id").and_then(|v| v.as_str()) {
+                return !dropped_ids.contains(id);
+            }
+            // 无 id 的响应按名称逐一匹配
+            let name = resp.get("name").and_then(|v| v.as_str()).unwrap_or_default();
+            if let Some(pos) = dropped_names.iter().position(|n| n == name) {
+                dropped_names.remove(pos);
+                return false;
+            }
+            true
+        });
+    }
+
+    // 清理空消息并合并相邻同角色消息，维持角色交替
+    contents.retain(|c| {
+        c.get("parts")
+            .and_then(|p| p.as_array())
+            .map(|p| !p.is_empty())
+            .unwrap_or(true)
+    });
+    let mut merged: Vec<Value> = Vec::with_capacity(contents.len());
+    for content in contents.drain(..) {
+        if let Some(last) = merged.last_mut() {
+            if last.get("role") == content.get("role") {
+                if let (Some(dst), Some(src)) = (
+                    last.get_mut("parts").and_then(|p| p.as_array_mut()),
+                    content.get("parts").and_then(|p| p.as_array()),
+                ) {
+                    dst.extend(src.iter().cloned());
+                    continue;
+                }
+            }
+        }
+        merged.push(content);
+    }
+    *contents = merged;
+
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_body() -> Value {
+        json!({
+            "model": "gemini-3-pro-high",
+            "request": {
+                "contents": [
+                    { "role": "user", "parts": [{ "text": "list files" }] },
+                    { "role": "model", "parts": [
+                        { "text": "ok" },
+                        { "functionCall": { "name": "ls", "args": {}, "id": "call_1" } }
+                    ]},
+                    { "role": "user", "parts": [
+                        { "functionResponse": { "name": "ls", "response": { "result": "a.txt" }, "id": "call_1" } }
+                    ]},
+                    { "role": "user", "parts": [{ "text": "thanks" }] }
+                ],
+                "generationConfig": { "thinkingConfig": { "includeThoughts": true } }
+            }
+        })
+    }
+
+    #[test]
+    fn test_passthrough_and_non_gemini3_untouched() {
+        let mut body = sample_body();
+        assert!(!apply_signature_degradation(&mut body, &SignatureDegradationMode::Passthrough, "gemini-2.5-flash"));
+
+        let mut body = sample_body();
+        body["model"] = json!("gemini-2.5-pro");
+        assert!(!apply_signature_degradation(&mut body, &SignatureDegradationMode::DropOrphaned, "gemini-2.5-flash"));
+    }
+
+    #[test]
+    fn test_sentinel_signed_call_untouched() {
+        let mut signed = sample_body();
+        signed["request"]["contents"][1]["parts"][1]["thoughtSignature"] = json!(SKIP_SIGNATURE_SENTINEL);
+        for mode in [
+            SignatureDegradationMode::SyntheticThought,
+            SignatureDegradationMode::DropOrphaned,
+            SignatureDegradationMode::DowngradeModel,
+        ] {
+            let mut body = signed.clone();
+            assert!(!apply_signature_degradation(&mut body, &mode, "gemini-2.5-flash"));
+            assert_eq!(body, signed);
+        }
+
+        // 已处理过的请求再次经过时不会重复插入思维块
+        let mut body = sample_body();
+        assert!(apply_signature_degradation(&mut body, &SignatureDegradationMode::SyntheticThought, "gemini-2.5-flash"));
+        let once = body.clone();
+        assert!(!apply_signature_degradation(&mut body, &SignatureDegradationMode::SyntheticThought, "gemini-2.5-flash"));
+        assert_eq!(body, once);
+    }
+
+    #[test]
+    fn test_synthetic_thought() {
+        let mut body = sample_body();
+        assert!(apply_signature_degradation(&mut body, &SignatureDegradationMode::SyntheticThought, "gemini-2.5-flash"));
+        let parts = body["request"]["contents"][1]["parts"].as_array().unwrap();
+        assert_eq!(parts[0]["thought"], true);
+        assert_eq!(parts[2]["thoughtSignature"], SKIP_SIGNATURE_SENTINEL);
+    }
+
+    #[test]
+    fn test_drop_orphaned_pair() {
+        let mut body = sample_body();
+        assert!(apply_signature_degradation(&mut body, &SignatureDegradationMode::DropOrphaned, "gemini-2.5-flash"));
+        let contents = body["request"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[1]["parts"].as_array().unwrap().len(), 1);
+        assert_eq!(contents[2]["parts"][0]["text"], "thanks");
+    }
+
+    #[test]
+    fn test_downgrade_model() {
+        let mut body = sample_body();
+        assert!(apply_signature_degradation(&mut body, &SignatureDegradationMode::DowngradeModel, "gemini-2.5-flash"));
+        assert_eq!(body["model"], "gemini-2.5-flash");
+        assert!(body["request"]["generationConfig"].get("thinkingConfig").is_none());
+    }
+}
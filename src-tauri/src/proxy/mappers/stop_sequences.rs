@@ -0,0 +1,298 @@
+// 本地停止序列
+// Gemini 最多执行 5 个 stopSequences 且不支持正则。超出部分与 `/pattern/` 形式的正则停止序列
+// 从上游请求中拆出，由代理在响应文本中扫描，命中时在停止边界处截断并以 STOP 结束。
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::proxy::upstream::resume::GeminiByteStream;
+use crate::proxy::upstream::sse_rewrite::{candidate_mut, rewrite_sse_stream, SseRewriter};
+
+/// 上游 stopSequences 数量上限
+pub const UPSTREAM_STOP_LIMIT: usize = 5;
+
+/// 正则停止序列无法预知匹配长度，流式时固定暂存末尾若干字符等待后续分块
+const REGEX_HOLDBACK_CHARS: usize = 64;
+
+#[derive(Debug, Clone)]
+pub enum StopPattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl StopPattern {
+    /// 解析单个停止序列: `/.../` 且为合法正则时按正则处理，否则按字面量
+    fn parse(raw: &str) -> Self {
+        if raw.len() > 2 && raw.starts_with('/') && raw.ends_with('/') {
+            if let Ok(re) = Regex::new(&raw[1..raw.len() - 1]) {
+                return StopPattern::Regex(re);
+            }
+        }
+        StopPattern::Literal(raw.to_string())
+    }
+
+    /// 首个 (非空) 匹配的起始位置
+    fn find(&self, text: &str) -> Option<usize> {
+        match self {
+            StopPattern::Literal(s) => text.find(s.as_str()),
+            StopPattern::Regex(re) => re.find_iter(text).find(|m| !m.as_str().is_empty()).map(|m| m.start()),
+        }
+    }
+}
+
+/// 从 Gemini 请求体中拆出上游无法执行的停止序列，其余保留在 `generationConfig.stopSequences`
+pub fn take_local_stops(body: &mut Value) -> Vec<StopPattern> {
+    let inner = if body.get("request").is_some() {
+        &mut body["request"]
+    } else {
+        body
+    };
+    let Some(config) = inner.get_mut("generationConfig").and_then(|c| c.as_object_mut()) else {
+        return Vec::new();
+    };
+    let Some(stops) = config.get("stopSequences").and_then(|s| s.as_array()) else {
+        return Vec::new();
+    };
+
+    let mut upstream = Vec::new();
+    let mut local = Vec::new();
+    for raw in stops.iter().filter_map(|s| s.as_str()).filter(|s| !s.is_empty()) {
+        match StopPattern::parse(raw) {
+            StopPattern::Literal(s) if upstream.len() < UPSTREAM_STOP_LIMIT => upstream.push(s),
+            pattern => local.push(pattern),
+        }
+    }
+    if local.is_empty() {
+        return local;
+    }
+    tracing::debug!(
+        "[Stop-Sequences] {} stop sequence(s) enforced locally, {} sent upstream",
+        local.len(),
+        upstream.len()
+    );
+    if upstream.is_empty() {
+        config.remove("stopSequences");
+    } else {
+        config.insert("stopSequences".to_string(), json!(upstream));
+    }
+    local
+}
+
+/// 增量扫描响应文本；可能构成停止序列前缀的末尾文本暂存到下一分块再放行
+pub struct StopScanner {
+    patterns: Vec<StopPattern>,
+    pending: String,
+    holdback: usize,
+    stopped: bool,
+}
+
+impl StopScanner {
+    pub fn new(patterns: Vec<StopPattern>) -> Self {
+        let holdback = patterns
+            .iter()
+            .map(|p| match p {
+                StopPattern::Literal(s) => s.chars().count().saturating_sub(1),
+                StopPattern::Regex(_) => REGEX_HOLDBACK_CHARS,
+            })
+            .max()
+            .unwrap_or(0);
+        Self {
+            patterns,
+            pending: String::new(),
+            holdback,
+            stopped: false,
+        }
+    }
+
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// 返回可放行的文本；命中停止序列后只放行边界之前的部分
+    pub fn push(&mut self, text: &str) -> String {
+        if self.stopped {
+            return String::new();
+        }
+        self.pending.push_str(text);
+        if let Some(pos) = self.patterns.iter().filter_map(|p| p.find(&self.pending)).min() {
+            self.stopped = true;
+            self.pending.truncate(pos);
+            return std::mem::take(&mut self.pending);
+        }
+        let split = match self.holdback {
+            0 => self.pending.len(),
+            n => self
+                .pending
+                .char_indices()
+                .rev()
+                .nth(n - 1)
+                .map(|(i, _)| i)
+                .unwrap_or(0),
+        };
+        let rest = self.pending.split_off(split);
+        std::mem::replace(&mut self.pending, rest)
+    }
+
+    /// 响应结束时放行暂存文本
+    pub fn flush(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// 扫描单个响应 (流式分块或完整响应) 的正文；命中时丢弃其后的 part 并设置 finishReason=STOP
+fn scan_response(value: &mut Value, scanner: &mut StopScanner, finished: bool) {
+    let Some(candidate) = candidate_mut(value) else {
+        return;
+    };
+    let finished = finished || candidate.get("finishReason").is_some();
+    if let Some(parts) = candidate
+        .get_mut("content")
+        .and_then(|c| c.get_mut("parts"))
+        .and_then(|p| p.as_array_mut())
+    {
+        let mut kept = Vec::with_capacity(parts.len());
+        for mut part in parts.drain(..) {
+            if scanner.stopped() {
+                break;
+            }
+            let is_thought = part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false);
+            match part.get("text").and_then(|t| t.as_str()) {
+                Some(text) if !is_thought => {
+                    let out = scanner.push(text);
+                    if !out.is_empty() {
+                        part["text"] = Value::String(out);
+                        kept.push(part);
+                    }
+                }
+                _ => kept.push(part),
+            }
+        }
+        if finished && !scanner.stopped() {
+            let rest = scanner.flush();
+            if !rest.is_empty() {
+                kept.push(json!({ "text": rest }));
+            }
+        }
+        *parts = kept;
+    }
+    if scanner.stopped() {
+        candidate["finishReason"] = json!("STOP");
+    }
+}
+
+/// 非流式响应: 在停止边界处截断正文
+pub fn apply_to_response(response: &mut Value, patterns: &[StopPattern]) {
+    if patterns.is_empty() {
+        return;
+    }
+    let mut scanner = StopScanner::new(patterns.to_vec());
+    scan_response(response, &mut scanner, true);
+}
+
+struct StopRewriter {
+    scanner: StopScanner,
+    /// 最近一次的 usageMetadata / 是否为 v1internal 包装，提前结束时补在最后一个分块上
+    last_usage: Option<Value>,
+    wrapped: bool,
+}
+
+impl SseRewriter for StopRewriter {
+    fn rewrite(&mut self, event: &mut Value) -> bool {
+        self.wrapped = event.get("response").is_some();
+        let inner = event.get("response").unwrap_or(&*event);
+        if let Some(usage) = inner.get("usageMetadata") {
+            self.last_usage = Some(usage.clone());
+        }
+        scan_response(event, &mut self.scanner, false);
+        if !self.scanner.stopped() {
+            return true;
+        }
+        if let Some(usage) = self.last_usage.take() {
+            let inner = if self.wrapped { &mut event["response"] } else { &mut *event };
+            if inner.get("usageMetadata").is_none() {
+                inner["usageMetadata"] = usage;
+            }
+        }
+        false
+    }
+
+    fn finish(&mut self) -> Option<Value> {
+        let rest = self.scanner.flush();
+        if rest.is_empty() {
+            return None;
+        }
+        let inner = json!({ "candidates": [{ "content": { "role": "model", "parts": [{ "text": rest }] } }] });
+        Some(if self.wrapped { json!({ "response": inner }) } else { inner })
+    }
+}
+
+/// 流式响应: 命中停止序列时截断并提前结束流；无本地停止序列时原样返回
+pub fn apply_to_stream(stream: GeminiByteStream, patterns: Vec<StopPattern>) -> GeminiByteStream {
+    if patterns.is_empty() {
+        return stream;
+    }
+    rewrite_sse_stream(
+        stream,
+        StopRewriter {
+            scanner: StopScanner::new(patterns),
+            last_usage: None,
+            wrapped: false,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_local_stops_keeps_first_five_literals_upstream() {
+        let mut body = json!({ "request": { "generationConfig": {
+            "stopSequences": ["a", "b", "c", "d", "e", "f", "/\\d{3}/"]
+        }}});
+        let local = take_local_stops(&mut body);
+        assert_eq!(local.len(), 2);
+        assert!(matches!(&local[0], StopPattern::Literal(s) if s == "f"));
+        assert!(matches!(&local[1], StopPattern::Regex(_)));
+        assert_eq!(body["request"]["generationConfig"]["stopSequences"], json!(["a", "b", "c", "d", "e"]));
+
+        let mut body = json!({ "generationConfig": { "stopSequences": ["END"] } });
+        assert!(take_local_stops(&mut body).is_empty());
+        assert_eq!(body["generationConfig"]["stopSequences"], json!(["END"]));
+    }
+
+    #[test]
+    fn test_scanner_cuts_at_stop_boundary_across_chunks() {
+        let mut scanner = StopScanner::new(vec![StopPattern::Literal("</answer>".to_string())]);
+        let mut out = scanner.push("The result is 42</ans");
+        out.push_str(&scanner.push("wer> trailing"));
+        assert_eq!(out, "The result is 42");
+        assert!(scanner.stopped());
+        assert_eq!(scanner.push("more"), "");
+    }
+
+    #[test]
+    fn test_scanner_regex_stop_and_flush() {
+        let mut scanner = StopScanner::new(vec![StopPattern::parse("/Step \\d+:/")]);
+        let mut out = scanner.push("Step one done. ");
+        out.push_str(&scanner.push("Step 2: next"));
+        assert_eq!(out, "Step one done. ");
+
+        let mut scanner = StopScanner::new(vec![StopPattern::parse("/Step \\d+:/")]);
+        let mut out = scanner.push("no stop here");
+        out.push_str(&scanner.flush());
+        assert_eq!(out, "no stop here");
+    }
+
+    #[test]
+    fn test_apply_to_response_sets_stop_finish_reason() {
+        let mut resp = json!({ "response": { "candidates": [{
+            "content": { "parts": [{ "text": "line1\nEND\nline2" }] },
+            "finishReason": "MAX_TOKENS"
+        }]}});
+        apply_to_response(&mut resp, &[StopPattern::Literal("\nEND".to_string())]);
+        let candidate = &resp["response"]["candidates"][0];
+        assert_eq!(candidate["content"]["parts"][0]["text"], "line1");
+        assert_eq!(candidate["finishReason"], "STOP");
+    }
+}
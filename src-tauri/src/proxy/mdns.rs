@@ -0,0 +1,119 @@
+// mDNS / Zeroconf 服务广播
+// 局域网模式下以 `_antigravity._tcp` 广播反代服务，TXT 记录附带模型列表与鉴权方式，
+// 供移动端等配套应用或局域网内其他机器自动发现，无需手动输入 IP。
+
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+
+pub const SERVICE_TYPE: &str = "_antigravity._tcp.local.";
+
+/// 单条 TXT 记录 (key=value) 的上限为 255 字节
+const TXT_ENTRY_LIMIT: usize = 255;
+
+/// 广播句柄，Drop 时注销服务并关闭守护线程
+pub struct MdnsAdvertiser {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+fn host_label() -> String {
+    let raw = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or_else(|_| "antigravity".to_string());
+    let label: String = raw
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '-' })
+        .collect();
+    if label.is_empty() {
+        "antigravity".to_string()
+    } else {
+        label
+    }
+}
+
+/// 构造 TXT 记录；模型列表过长时截断 (完整列表见 models_url)
+fn txt_properties(models: &[String], auth_required: bool) -> Vec<(String, String)> {
+    // 图像模型的分辨率/比例组合过多，只保留基础 ID
+    let mut listed = String::new();
+    let mut truncated = false;
+    for model in models.iter().filter(|m| !m.contains("-image-")) {
+        let extra = if listed.is_empty() { model.len() } else { model.len() + 1 };
+        if "models=".len() + listed.len() + extra > TXT_ENTRY_LIMIT {
+            truncated = true;
+            break;
+        }
+        if !listed.is_empty() {
+            listed.push(',');
+        }
+        listed.push_str(model);
+    }
+
+    vec![
+        ("version".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+        ("path".to_string(), "/v1".to_string()),
+        ("api".to_string(), "openai,anthropic,gemini".to_string()),
+        ("auth".to_string(), if auth_required { "api_key" } else { "none" }.to_string()),
+        ("models".to_string(), listed),
+        ("models_total".to_string(), models.len().to_string()),
+        ("models_truncated".to_string(), truncated.to_string()),
+        ("models_url".to_string(), "/v1/models".to_string()),
+    ]
+}
+
+impl MdnsAdvertiser {
+    pub fn start(port: u16, models: &[String], auth_required: bool) -> Result<Self, String> {
+        let daemon = ServiceDaemon::new().map_err(|e| format!("mDNS 守护进程启动失败: {}", e))?;
+        let host = host_label();
+        let instance_name = format!("Antigravity Tools ({})", host);
+        let properties: std::collections::HashMap<String, String> =
+            txt_properties(models, auth_required).into_iter().collect();
+        let info = ServiceInfo::new(
+            SERVICE_TYPE,
+            &instance_name,
+            &format!("{}.local.", host),
+            "",
+            port,
+            properties,
+        )
+        .map_err(|e| format!("mDNS 服务信息无效: {}", e))?
+        .enable_addr_auto();
+        let fullname = info.get_fullname().to_string();
+        daemon
+            .register(info)
+            .map_err(|e| format!("mDNS 服务注册失败: {}", e))?;
+        tracing::info!("[mDNS] 已广播 {} (端口 {})", fullname, port);
+        Ok(Self { daemon, fullname })
+    }
+}
+
+impl Drop for MdnsAdvertiser {
+    fn drop(&mut self) {
+        // 发送 goodbye 报文，客户端立即移除该服务
+        if let Ok(rx) = self.daemon.unregister(&self.fullname) {
+            let _ = rx.recv_timeout(std::time::Duration::from_secs(1));
+        }
+        let _ = self.daemon.shutdown();
+        tracing::info!("[mDNS] 已停止广播 {}", self.fullname);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_txt_models_truncated_within_entry_limit() {
+        let mut models: Vec<String> = (0..40).map(|i| format!("gemini-test-model-{:02}", i)).collect();
+        models.push("gemini-3-pro-image-4k-16x9".to_string());
+        let props = txt_properties(&models, true);
+        let get = |k: &str| props.iter().find(|(key, _)| key == k).map(|(_, v)| v.clone()).unwrap();
+
+        assert!(format!("models={}", get("models")).len() <= TXT_ENTRY_LIMIT);
+        assert!(!get("models").contains("-image-"));
+        assert_eq!(get("models_total"), "41");
+        assert_eq!(get("models_truncated"), "true");
+        assert_eq!(get("auth"), "api_key");
+    }
+}
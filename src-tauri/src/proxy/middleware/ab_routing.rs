@@ -0,0 +1,229 @@
+// A/B 分流中间件
+// 命中实验 alias 的请求按比例改用 variant_model，同一会话 (x-conversation-id) 固定落在同一组；
+// 响应结束后把延迟 / 输出长度 / finish reason 写入 ab_results 表，供 get_ab_experiment_summary 对比。
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use futures::StreamExt;
+use rand::Rng;
+use serde_json::Value;
+use std::hash::{Hash, Hasher};
+use std::time::Instant;
+
+use crate::proxy::ab_test::{AbResult, ResponseObserver, ARM_CONTROL, ARM_VARIANT};
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::config::{AbExperiment, AbTestConfig};
+use crate::proxy::middleware::conversation::CONVERSATION_ID_HEADER;
+use crate::proxy::middleware::token_budget::{detect_protocol, extract_model, rewrite_model};
+use crate::proxy::server::AppState;
+
+pub const AB_EXPERIMENT_HEADER: &str = "x-ab-experiment";
+pub const AB_ARM_HEADER: &str = "x-ab-arm";
+
+/// 非流式响应体用于统计时的读取上限
+const MAX_OBSERVED_BODY: usize = 16 * 1024 * 1024;
+
+fn find_experiment<'a>(config: &'a AbTestConfig, model: &str) -> Option<&'a AbExperiment> {
+    config
+        .experiments
+        .iter()
+        .filter(|e| e.enabled && !e.variant_model.is_empty())
+        .find(|e| e.alias == model || (e.alias.contains('*') && wildcard_match(&e.alias, model)))
+}
+
+/// 有会话 ID 时按 (实验名, 会话) 哈希分组，保证同一对话不会中途换模型
+fn choose_arm(experiment: &AbExperiment, sticky_key: Option<&str>) -> &'static str {
+    let percentage = experiment.percentage.min(100) as u64;
+    let bucket = match sticky_key {
+        Some(key) => {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            experiment.name.hash(&mut hasher);
+            key.hash(&mut hasher);
+            hasher.finish() % 100
+        }
+        None => rand::thread_rng().gen_range(0..100),
+    };
+    if bucket < percentage {
+        ARM_VARIANT
+    } else {
+        ARM_CONTROL
+    }
+}
+
+fn save_result(result: AbResult) {
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = crate::modules::proxy_db::save_ab_result(&result) {
+            tracing::warn!("[A/B] 保存实验结果失败: {}", e);
+        }
+    });
+}
+
+pub async fn ab_routing_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    if detect_protocol(&path).is_none() {
+        return next.run(request).await;
+    }
+    let config = state.ab_test.read().await.clone();
+    if !config.enabled || config.experiments.is_empty() {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return payload_too_large_response(state.max_body_bytes),
+    };
+    let Ok(mut json) = serde_json::from_slice::<Value>(&bytes) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let Some(alias) = extract_model(&path, &json) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let Some(experiment) = find_experiment(&config, &alias).cloned() else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+
+    let sticky_key = parts
+        .headers
+        .get(CONVERSATION_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    let arm = choose_arm(&experiment, sticky_key.as_deref());
+
+    let request = if arm == ARM_VARIANT && rewrite_model(&mut parts, &mut json, &experiment.variant_model) {
+        let serialized = serde_json::to_vec(&json).unwrap_or_default();
+        parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+        Request::from_parts(parts, Body::from(serialized))
+    } else {
+        Request::from_parts(parts, Body::from(bytes))
+    };
+    let model = if arm == ARM_VARIANT { experiment.variant_model.clone() } else { alias.clone() };
+    tracing::debug!("[A/B] {} -> {} 组 ({})", experiment.name, arm, model);
+
+    let start = Instant::now();
+    let mut response = next.run(request).await;
+    let status = response.status().as_u16();
+
+    if let Ok(v) = HeaderValue::from_str(&experiment.name) {
+        response.headers_mut().insert(AB_EXPERIMENT_HEADER, v);
+    }
+    response.headers_mut().insert(AB_ARM_HEADER, HeaderValue::from_static(arm));
+
+    let record = AbResult {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        experiment: experiment.name.clone(),
+        arm: arm.to_string(),
+        alias,
+        model: response
+            .headers()
+            .get("X-Mapped-Model")
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string())
+            .unwrap_or(model),
+        status,
+        duration: 0,
+        first_byte_ms: None,
+        output_chars: 0,
+        output_tokens: None,
+        finish_reason: None,
+    };
+
+    let is_stream = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.contains("text/event-stream"))
+        .unwrap_or(false);
+    let (parts, body) = response.into_parts();
+    let mut stream = body.into_data_stream();
+
+    let output = async_stream::stream! {
+        let mut record = record;
+        let mut observer = ResponseObserver::default();
+        let mut buffered: Vec<u8> = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(bytes) => {
+                    if record.first_byte_ms.is_none() {
+                        record.first_byte_ms = Some(start.elapsed().as_millis() as u64);
+                    }
+                    if is_stream {
+                        observer.observe_sse_chunk(&bytes);
+                    } else if buffered.len() + bytes.len() <= MAX_OBSERVED_BODY {
+                        buffered.extend_from_slice(&bytes);
+                    }
+                    yield Ok::<_, axum::Error>(bytes);
+                }
+                Err(e) => {
+                    yield Err(e);
+                    break;
+                }
+            }
+        }
+        if is_stream {
+            observer.finish_sse();
+        } else if let Ok(json) = serde_json::from_slice::<Value>(&buffered) {
+            observer.observe_json(&json);
+        }
+        record.duration = start.elapsed().as_millis() as u64;
+        record.output_chars = observer.output_chars;
+        record.output_tokens = observer.output_tokens;
+        record.finish_reason = observer.finish_reason;
+        save_result(record);
+    };
+
+    Response::from_parts(parts, Body::from_stream(output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn experiment(percentage: u8) -> AbExperiment {
+        AbExperiment {
+            name: "flash-upgrade".to_string(),
+            enabled: true,
+            alias: "gemini-2.5-*".to_string(),
+            variant_model: "gemini-3-flash".to_string(),
+            percentage,
+        }
+    }
+
+    #[test]
+    fn test_find_experiment() {
+        let mut config = AbTestConfig { enabled: true, experiments: vec![experiment(50)] };
+        assert!(find_experiment(&config, "gemini-2.5-flash").is_some());
+        assert!(find_experiment(&config, "claude-sonnet-4-5").is_none());
+        config.experiments[0].enabled = false;
+        assert!(find_experiment(&config, "gemini-2.5-flash").is_none());
+    }
+
+    #[test]
+    fn test_choose_arm_bounds_and_stickiness() {
+        assert_eq!(choose_arm(&experiment(0), None), ARM_CONTROL);
+        assert_eq!(choose_arm(&experiment(100), None), ARM_VARIANT);
+        assert_eq!(choose_arm(&experiment(100), Some("conv-1")), ARM_VARIANT);
+
+        let exp = experiment(50);
+        let first = choose_arm(&exp, Some("conv-42"));
+        for _ in 0..20 {
+            assert_eq!(choose_arm(&exp, Some("conv-42")), first);
+        }
+        // 大量会话下分流比例接近配置值
+        let variants = (0..2000)
+            .filter(|i| choose_arm(&exp, Some(&format!("conv-{}", i))) == ARM_VARIANT)
+            .count();
+        assert!((800..1200).contains(&variants), "variants = {}", variants);
+    }
+}
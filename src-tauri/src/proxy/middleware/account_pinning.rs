@@ -0,0 +1,215 @@
+// 账号固定中间件
+// 请求头 `X-AG-Account: <账号>` 或模型名后缀 `gemini-2.5-pro@<账号>` 把本次请求固定到指定账号，跳过调度
+// (该账号限流或不可用时直接返回错误，不会换号)。账号可以是配置的别名、账号 ID、邮箱，或唯一匹配的邮箱用户名。
+// 模型后缀只在能解析为账号时才剥离，`claude-3-5-sonnet@20240620` 之类带版本号的模型名保持原样。
+// 位于档案外层: 后缀在模型映射之前剥离，内层的映射、降级与调度都在固定作用域内执行。
+
+use std::collections::HashMap;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::{json, Value};
+
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::config::AccountPinningConfig;
+use crate::proxy::middleware::token_budget::{detect_protocol, extract_model, request_api_key, rewrite_model};
+use crate::proxy::server::AppState;
+use crate::proxy::token_manager::pin_account;
+
+/// 固定账号的请求头 (响应中回显实际固定的账号名)
+pub const ACCOUNT_HEADER: &str = "x-ag-account";
+
+/// 按别名、账号 ID、邮箱、邮箱用户名 (仅唯一匹配时) 依次查找，返回 (账号 ID, 邮箱)
+fn resolve_account(
+    name: &str,
+    aliases: &HashMap<String, String>,
+    accounts: &[(String, String)],
+) -> Option<(String, String)> {
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let target = aliases.get(name).map(|t| t.trim()).unwrap_or(name);
+    if let Some(found) = accounts.iter().find(|(id, email)| id == target || email.eq_ignore_ascii_case(target)) {
+        return Some(found.clone());
+    }
+    let mut by_local = accounts
+        .iter()
+        .filter(|(_, email)| email.split('@').next().is_some_and(|local| local.eq_ignore_ascii_case(target)));
+    match (by_local.next(), by_local.next()) {
+        (Some(found), None) => Some(found.clone()),
+        _ => None,
+    }
+}
+
+/// 拆分 `<模型>@<账号>` (按最后一个 @；Gemini 路径中的 @ 可能被编码为 %40)
+fn split_model_suffix(model: &str) -> Option<(String, String)> {
+    let model = model.replace("%40", "@");
+    let (base, account) = model.rsplit_once('@')?;
+    (!base.is_empty() && !account.is_empty()).then(|| (base.to_string(), account.to_string()))
+}
+
+fn key_allowed(config: &AccountPinningConfig, api_key: Option<&str>) -> bool {
+    config.api_keys.is_empty() || api_key.is_some_and(|k| config.api_keys.iter().any(|allowed| allowed == k))
+}
+
+fn pin_error(status: StatusCode, code: &str, message: String) -> Response {
+    (
+        status,
+        Json(json!({
+            "error": {
+                "message": message,
+                "type": "invalid_request_error",
+                "code": code
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 显式指定账号时的完整策略检查: 功能开关 → Key 白名单 → 解析账号 (中间件请求头与 fanout 目标共用)
+pub(crate) fn resolve_pinned(
+    config: &AccountPinningConfig,
+    api_key: Option<&str>,
+    name: &str,
+    accounts: &[(String, String)],
+) -> Result<(String, String), Response> {
+    if !config.enabled {
+        return Err(pin_error(
+            StatusCode::FORBIDDEN,
+            "account_pinning_disabled",
+            "Account pinning is disabled".to_string(),
+        ));
+    }
+    if !key_allowed(config, api_key) {
+        tracing::warn!("[Pinning] API Key 无权固定账号");
+        return Err(pin_error(
+            StatusCode::FORBIDDEN,
+            "account_pinning_forbidden",
+            "This API key is not allowed to pin accounts".to_string(),
+        ));
+    }
+    resolve_account(name, &config.aliases, accounts)
+        .ok_or_else(|| pin_error(StatusCode::BAD_REQUEST, "account_not_found", format!("Unknown account '{}'", name)))
+}
+
+pub async fn account_pinning_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let requested = request
+        .headers()
+        .get(ACCOUNT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    let path = request.uri().path().to_string();
+    let config = state.account_pinning.read().await.clone();
+    // 先校验 Key 再解析账号: 无权的 Key 无法通过 404/403 的差异探测账号是否存在，其模型后缀也原样透传
+    let api_key = request_api_key(&request);
+    let allowed = key_allowed(&config, api_key.as_deref());
+    let check_suffix = config.enabled && config.allow_model_suffix && allowed && detect_protocol(&path).is_some();
+    if requested.is_none() && !check_suffix {
+        return next.run(request).await;
+    }
+
+    let accounts = state.token_manager.all_accounts();
+    let mut request = request;
+    let mut target = None;
+
+    if let Some(name) = requested.as_deref() {
+        match resolve_pinned(&config, api_key.as_deref(), name, &accounts) {
+            Ok(account) => target = Some((name.to_string(), account)),
+            Err(response) => return response,
+        }
+    }
+
+    if check_suffix {
+        let (mut parts, body) = request.into_parts();
+        let bytes = match to_bytes(body, state.max_body_bytes).await {
+            Ok(b) => b,
+            Err(_) => return payload_too_large_response(state.max_body_bytes),
+        };
+        let mut rewritten = None;
+        if let Ok(mut json) = serde_json::from_slice::<Value>(&bytes) {
+            let suffix = extract_model(&path, &json).and_then(|m| split_model_suffix(&m));
+            if let Some((base, name)) = suffix {
+                if let Some(account) = resolve_account(&name, &config.aliases, &accounts) {
+                    // 请求头优先，后缀仍需剥离
+                    target.get_or_insert((name, account));
+                    rewrite_model(&mut parts, &mut json, &base);
+                    rewritten = Some(serde_json::to_vec(&json).unwrap_or_default());
+                }
+            }
+        }
+        request = match rewritten {
+            Some(serialized) => {
+                parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+                Request::from_parts(parts, Body::from(serialized))
+            }
+            None => Request::from_parts(parts, Body::from(bytes)),
+        };
+    }
+
+    let Some((name, (account_id, email))) = target else {
+        return next.run(request).await;
+    };
+
+    tracing::info!("[Pinning] 请求固定到账号 {} ({})", name, email);
+    let mut response = pin_account(account_id, next.run(request)).await;
+    if let Ok(v) = HeaderValue::from_str(&name) {
+        response.headers_mut().insert(ACCOUNT_HEADER, v);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts() -> Vec<(String, String)> {
+        vec![
+            ("id-1".to_string(), "work-2@example.com".to_string()),
+            ("id-2".to_string(), "alice@corp.com".to_string()),
+            ("id-3".to_string(), "alice@home.org".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_resolve_account() {
+        let mut aliases = HashMap::new();
+        aliases.insert("team".to_string(), "alice@corp.com".to_string());
+        let accounts = accounts();
+        let id = |name: &str| resolve_account(name, &aliases, &accounts).map(|(id, _)| id);
+
+        assert_eq!(id("team").as_deref(), Some("id-2"));
+        assert_eq!(id("id-3").as_deref(), Some("id-3"));
+        assert_eq!(id("ALICE@home.org").as_deref(), Some("id-3"));
+        assert_eq!(id("work-2").as_deref(), Some("id-1"));
+        // 邮箱用户名不唯一时不猜测
+        assert_eq!(id("alice"), None);
+        assert_eq!(id("20240620"), None);
+        assert_eq!(id(" "), None);
+    }
+
+    #[test]
+    fn test_split_model_suffix_and_key_policy() {
+        assert_eq!(
+            split_model_suffix("gemini-2.5-pro@work-2"),
+            Some(("gemini-2.5-pro".to_string(), "work-2".to_string()))
+        );
+        assert_eq!(split_model_suffix("gemini-2.5-pro%40work-2").map(|(_, a)| a).as_deref(), Some("work-2"));
+        assert_eq!(split_model_suffix("gemini-2.5-pro"), None);
+        assert_eq!(split_model_suffix("@work-2"), None);
+        assert_eq!(split_model_suffix("gemini-2.5-pro@"), None);
+
+        let mut config = AccountPinningConfig::default();
+        assert!(key_allowed(&config, None));
+        config.api_keys = vec!["sk-ops".to_string()];
+        assert!(key_allowed(&config, Some("sk-ops")));
+        assert!(!key_allowed(&config, Some("sk-other")));
+        assert!(!key_allowed(&config, None));
+    }
+}
@@ -2,7 +2,7 @@
 use axum::{
     extract::State,
     extract::Request,
-    http::{header, StatusCode},
+    http::{header, HeaderMap, StatusCode},
     middleware::Next,
     response::Response,
 };
@@ -21,7 +21,8 @@ pub async fn auth_middleware(
     let path = request.uri().path().to_string();
 
     // 过滤心跳和健康检查请求,避免日志噪音
-    if !path.contains("event_logging") && path != "/healthz" {
+    let is_health = path == "/healthz" || path == "/readyz";
+    if !path.contains("event_logging") && !is_health {
         tracing::info!("Request: {} {}", method, path);
     } else {
         tracing::trace!("Heartbeat: {} {}", method, path);
@@ -32,35 +33,50 @@ pub async fn auth_middleware(
         return Ok(next.run(request).await);
     }
 
+    // API 文档不含敏感信息，浏览器直接打开时无法附带 API Key
+    if path == "/openapi.json" || path == "/docs" {
+        return Ok(next.run(request).await);
+    }
+
     let security = security.read().await.clone();
     let effective_mode = security.effective_auth_mode();
 
-    if matches!(effective_mode, ProxyAuthMode::Off) {
+    // 管理 API: 未开启时隐藏；开启时始终要求管理密钥 (不受 auth_mode 影响)
+    let is_admin = path.starts_with("/admin/");
+    if is_admin && !security.admin_api_enabled {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    if !is_admin && matches!(effective_mode, ProxyAuthMode::Off) {
         return Ok(next.run(request).await);
     }
 
-    if matches!(effective_mode, ProxyAuthMode::AllExceptHealth) && path == "/healthz" {
+    if matches!(effective_mode, ProxyAuthMode::AllExceptHealth) && is_health {
+        return Ok(next.run(request).await);
+    }
+
+    // 受信任的本地套接字连接: 访问权限已由系统文件权限 / 管道 ACL 控制
+    if !is_admin
+        && security.local_socket_trusted
+        && request.extensions().get::<crate::proxy::local_socket::LocalSocketPeer>().is_some()
+    {
         return Ok(next.run(request).await);
     }
     
-    // 从 header 中提取 API key
-    let api_key = request
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .and_then(|s| s.strip_prefix("Bearer ").or(Some(s)))
-        .or_else(|| {
-            request
-                .headers()
-                .get("x-api-key")
-                .and_then(|h| h.to_str().ok())
-        })
-        .or_else(|| {
-            request
-                .headers()
-                .get("x-goog-api-key")
-                .and_then(|h| h.to_str().ok())
-        });
+    let api_key = extract_api_key(request.headers());
+
+    // 管理 API 只接受独立的管理密钥: 普通客户端的 Key 不能修改配置、执行账号操作
+    if is_admin {
+        if security.admin_api_key.is_empty() || security.admin_api_key == security.api_key {
+            tracing::error!("Admin API is enabled but admin_api_key is empty or equals api_key; denying request");
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        return if api_key == Some(security.admin_api_key.as_str()) {
+            Ok(next.run(request).await)
+        } else {
+            Err(StatusCode::UNAUTHORIZED)
+        };
+    }
 
     if security.api_key.is_empty() {
         tracing::error!("Proxy auth is enabled but api_key is empty; denying request");
@@ -77,13 +93,61 @@ pub async fn auth_middleware(
     }
 }
 
+/// 从 Authorization (Bearer) / x-api-key / x-goog-api-key 中提取客户端提供的 Key
+fn extract_api_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.strip_prefix("Bearer ").or(Some(s)))
+        .or_else(|| headers.get("x-api-key").and_then(|h| h.to_str().ok()))
+        .or_else(|| headers.get("x-goog-api-key").and_then(|h| h.to_str().ok()))
+}
+
 #[cfg(test)]
 mod tests {
-    // 移除未使用的 use super::*;
+    use super::*;
+    use crate::proxy::ProxyConfig;
+    use axum::{body::Body, routing::get, Router};
+    use tower::Service;
+
+    fn app(api_key: &str, admin_api_key: &str, enable_admin_api: bool) -> Router {
+        let config = ProxyConfig {
+            api_key: api_key.to_string(),
+            admin_api_key: admin_api_key.to_string(),
+            enable_admin_api,
+            ..Default::default()
+        };
+        let security = Arc::new(RwLock::new(ProxySecurityConfig::from_proxy_config(&config)));
+        Router::new()
+            .route("/admin/config", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(security, auth_middleware))
+    }
+
+    async fn status(mut app: Router, path: &str, key: Option<&str>) -> StatusCode {
+        let mut req = Request::builder().uri(path);
+        if let Some(key) = key {
+            req = req.header(header::AUTHORIZATION, format!("Bearer {}", key));
+        }
+        app.call(req.body(Body::empty()).unwrap()).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_admin_api_hidden_when_disabled() {
+        let app = app("sk-client", "sk-admin", false);
+        assert_eq!(status(app, "/admin/config", Some("sk-admin")).await, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_admin_api_requires_admin_key() {
+        let app = || app("sk-client", "sk-admin", true);
+        assert_eq!(status(app(), "/admin/config", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(app(), "/admin/config", Some("sk-client")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(app(), "/admin/config", Some("sk-admin")).await, StatusCode::OK);
+    }
 
-    #[test]
-    fn test_auth_placeholder() {
-        // Placeholder test
-        assert!(true);
+    #[tokio::test]
+    async fn test_admin_api_denied_without_distinct_admin_key() {
+        assert_eq!(status(app("sk-same", "sk-same", true), "/admin/config", Some("sk-same")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(app("sk-client", "", true), "/admin/config", Some("")).await, StatusCode::UNAUTHORIZED);
     }
 }
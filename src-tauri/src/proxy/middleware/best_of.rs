@@ -0,0 +1,360 @@
+// best_of 采样中间件 (Chat Completions)
+// 请求携带 `best_of: N` 时生成 N 个候选 (优先一次请求 n=N，不足部分逐个补齐)，
+// 再由低成本裁判模型挑选最佳答案，只把胜出者以 OpenAI 格式返回；落选候选写入监控历史以便查看。
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::{json, Value};
+use std::time::Instant;
+
+use crate::proxy::common::http::invalid_request;
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::server::AppState;
+
+pub const BEST_OF_CANDIDATES_HEADER: &str = "x-best-of-candidates";
+pub const BEST_OF_WINNER_HEADER: &str = "x-best-of-winner";
+
+/// 裁判提示中对话与候选的截断长度 (字符)
+const MAX_TRANSCRIPT_CHARS: usize = 12_000;
+const MAX_CANDIDATE_CHARS: usize = 6_000;
+
+const JUDGE_SYSTEM_PROMPT: &str = "You are a strict evaluator. Given a conversation and several candidate assistant replies, \
+pick the single best reply considering correctness, instruction following, helpfulness and completeness. \
+Respond with JSON only: {\"best\": <candidate number>, \"reason\": \"<one short sentence>\"}.";
+
+pub(crate) fn clone_parts(parts: &Parts) -> Parts {
+    let (mut cloned, _) = Request::new(()).into_parts();
+    cloned.method = parts.method.clone();
+    cloned.uri = parts.uri.clone();
+    cloned.version = parts.version;
+    cloned.headers = parts.headers.clone();
+    cloned.extensions = parts.extensions.clone();
+    cloned
+}
+
+/// 在同一路由上执行一次内部请求，返回 (状态码, JSON 响应)
+pub(crate) async fn run_inner(next: &Next, parts: &Parts, body: &Value) -> (StatusCode, Value) {
+    let mut parts = clone_parts(parts);
+    let bytes = serde_json::to_vec(body).unwrap_or_default();
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(bytes.len()));
+    parts.headers.remove(header::ACCEPT_ENCODING);
+    let response = next.clone().run(Request::from_parts(parts, Body::from(bytes))).await;
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap_or_default();
+    let value = serde_json::from_slice::<Value>(&bytes)
+        .unwrap_or_else(|_| json!({ "error": { "message": String::from_utf8_lossy(&bytes) } }));
+    (status, value)
+}
+
+pub(crate) fn choices_of(response: &Value) -> Vec<Value> {
+    response
+        .get("choices")
+        .and_then(|c| c.as_array())
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub(crate) fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let head: String = text.chars().take(max).collect();
+    format!("{}\n[... truncated]", head)
+}
+
+/// 对话末尾若干轮 (保留最近的内容)
+fn render_transcript(messages: &[Value]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut total = 0;
+    for message in messages.iter().rev() {
+        let role = message.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+        let text = message.get("content").map(content_text).unwrap_or_default();
+        if text.is_empty() {
+            continue;
+        }
+        let line = format!("[{}]: {}", role, text);
+        total += line.chars().count();
+        lines.push(line);
+        if total > MAX_TRANSCRIPT_CHARS {
+            break;
+        }
+    }
+    lines.reverse();
+    let transcript = lines.join("\n\n");
+    let skip = transcript.chars().count().saturating_sub(MAX_TRANSCRIPT_CHARS);
+    transcript.chars().skip(skip).collect()
+}
+
+fn render_candidate(choice: &Value) -> String {
+    let message = choice.get("message").cloned().unwrap_or(Value::Null);
+    let mut text = message.get("content").map(content_text).unwrap_or_default();
+    if let Some(tool_calls) = message.get("tool_calls").filter(|t| !t.is_null()) {
+        text.push_str(&format!("\n[tool_calls]: {}", tool_calls));
+    }
+    truncate_chars(&text, MAX_CANDIDATE_CHARS)
+}
+
+pub fn build_judge_prompt(messages: &[Value], candidates: &[Value]) -> String {
+    let mut prompt = format!("Conversation (most recent last):\n{}\n\nCandidates:\n", render_transcript(messages));
+    for (i, choice) in candidates.iter().enumerate() {
+        prompt.push_str(&format!("\n### Candidate {}\n{}\n", i + 1, render_candidate(choice)));
+    }
+    prompt
+}
+
+/// 解析裁判输出，返回 (0 基下标, 理由)
+pub fn parse_judge_verdict(text: &str, candidates: usize) -> Option<(usize, String)> {
+    let from_json = text
+        .find('{')
+        .zip(text.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .and_then(|(start, end)| serde_json::from_str::<Value>(&text[start..=end]).ok())
+        .and_then(|v| {
+            let best = v.get("best").and_then(|b| b.as_u64().or_else(|| b.as_str()?.trim().parse().ok()))?;
+            let reason = v.get("reason").and_then(|r| r.as_str()).unwrap_or_default().to_string();
+            Some((best as usize, reason))
+        });
+    let (best, reason) = from_json.or_else(|| {
+        let digits: String = text
+            .chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse::<usize>().ok().map(|n| (n, String::new()))
+    })?;
+    (1..=candidates).contains(&best).then(|| (best - 1, reason))
+}
+
+pub(crate) fn add_usage(total: &mut Value, usage: Option<&Value>) {
+    let Some(usage) = usage else { return };
+    for key in ["prompt_tokens", "completion_tokens", "total_tokens"] {
+        let add = usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        let current = total.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        total[key] = json!(current + add);
+    }
+}
+
+pub async fn best_of_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.uri().path() != "/v1/chat/completions" {
+        return next.run(request).await;
+    }
+    let (enabled, judge_model, max_candidates) = {
+        let exp = state.experimental.read().await;
+        (exp.enable_best_of, exp.best_of_judge_model.clone(), exp.best_of_max_candidates.max(2))
+    };
+    if !enabled {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes: Bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return payload_too_large_response(state.max_body_bytes),
+    };
+    let best_of = serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .filter(|v| v.get("best_of").and_then(|b| b.as_u64()).unwrap_or(1) > 1);
+    let Some(mut body) = best_of else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+
+    let count = body["best_of"].as_u64().unwrap_or(1) as u32;
+    if count > max_candidates {
+        return invalid_request(format!("best_of must be at most {}", max_candidates), Some("best_of"));
+    }
+    if body.get("stream").and_then(|s| s.as_bool()).unwrap_or(false) {
+        return invalid_request("best_of cannot be used together with stream", Some("best_of"));
+    }
+    if body.get("n").and_then(|n| n.as_u64()).unwrap_or(1) > 1 {
+        return invalid_request("best_of only supports n = 1", Some("best_of"));
+    }
+
+    let started = Instant::now();
+    let obj = body.as_object_mut().expect("best_of body is an object");
+    obj.remove("best_of");
+    obj.insert("stream".to_string(), Value::Bool(false));
+
+    // 1. 优先一次请求多个候选 (candidateCount)，不足时逐个补齐
+    let mut batch_body = body.clone();
+    batch_body["n"] = json!(count);
+    let (status, first) = run_inner(&next, &parts, &batch_body).await;
+    let mut responses: Vec<Value> = Vec::new();
+    let mut candidates: Vec<Value> = Vec::new();
+    if status.is_success() {
+        candidates.extend(choices_of(&first));
+        responses.push(first.clone());
+    }
+    let missing = (count as usize).saturating_sub(candidates.len());
+    if missing > 0 {
+        tracing::debug!("[best_of] 单次请求得到 {} 个候选，补充 {} 次独立生成", candidates.len(), missing);
+        let runs = (0..missing).map(|_| run_inner(&next, &parts, &body));
+        for (status, response) in futures::future::join_all(runs).await {
+            if status.is_success() {
+                candidates.extend(choices_of(&response).into_iter().take(1));
+                responses.push(response);
+            }
+        }
+    }
+    candidates.truncate(count as usize);
+
+    if candidates.is_empty() {
+        // 全部失败: 原样返回第一次请求的错误
+        return (status, Json(first)).into_response();
+    }
+
+    // 2. 裁判挑选
+    let messages = body.get("messages").and_then(|m| m.as_array()).cloned().unwrap_or_default();
+    let (winner, reason) = if candidates.len() == 1 {
+        (0, "only one candidate was generated".to_string())
+    } else {
+        let judge_body = json!({
+            "model": judge_model,
+            "stream": false,
+            "temperature": 0,
+            "messages": [
+                {"role": "system", "content": JUDGE_SYSTEM_PROMPT},
+                {"role": "user", "content": build_judge_prompt(&messages, &candidates)}
+            ]
+        });
+        let (judge_status, judge_response) = run_inner(&next, &parts, &judge_body).await;
+        let verdict_text = choices_of(&judge_response)
+            .first()
+            .and_then(|c| c.pointer("/message/content").cloned())
+            .map(|c| content_text(&c))
+            .unwrap_or_default();
+        match parse_judge_verdict(&verdict_text, candidates.len()) {
+            Some(v) => v,
+            None => {
+                tracing::warn!("[best_of] 裁判未给出有效结果 (HTTP {})，默认选择第一个候选", judge_status);
+                (0, "judge unavailable, defaulted to the first candidate".to_string())
+            }
+        }
+    };
+    tracing::info!("[best_of] {} 个候选中选择 #{}: {}", candidates.len(), winner + 1, reason);
+
+    // 3. 组装胜出响应 (usage 为全部候选的总和)
+    let mut result = responses[0].clone();
+    let mut winner_choice = candidates[winner].clone();
+    winner_choice["index"] = json!(0);
+    result["choices"] = json!([winner_choice]);
+    let mut usage = json!({});
+    for response in &responses {
+        add_usage(&mut usage, response.get("usage"));
+    }
+    if usage.as_object().map(|u| !u.is_empty()).unwrap_or(false) {
+        result["usage"] = usage;
+    }
+
+    // 4. 落选候选写入监控历史
+    let model = body.get("model").and_then(|m| m.as_str()).map(|s| s.to_string());
+    let mapped_model = result.get("model").and_then(|m| m.as_str()).map(|s| s.to_string());
+    let session_id = crate::proxy::session_manager::SessionManager::current_conversation_id();
+    for (i, choice) in candidates.iter().enumerate().filter(|(i, _)| *i != winner) {
+        let record = json!({
+            "best_of": { "candidate": i + 1, "of": candidates.len(), "winner": winner + 1, "judge_reason": reason },
+            "choice": choice,
+        });
+        state
+            .monitor
+            .log_request(ProxyRequestLog {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: chrono::Utc::now().timestamp_millis(),
+                method: "POST".to_string(),
+                url: "/v1/chat/completions#best_of-candidate".to_string(),
+                status: 200,
+                duration: started.elapsed().as_millis() as u64,
+                model: model.clone(),
+                mapped_model: mapped_model.clone(),
+                account_email: None,
+                error: None,
+                request_body: None,
+                response_body: serde_json::to_string_pretty(&record).ok(),
+                input_tokens: None,
+                output_tokens: None,
+                protocol: Some("openai".to_string()),
+                session_id: session_id.clone(),
+                notes: None,
+                tags: None,
+                ttft_ms: None,
+                tokens_per_sec: None,
+                chunk_count: None,
+                message_id: None,
+                parent_message_id: None,
+                error_code: None,
+                error_hint: None,
+            })
+            .await;
+    }
+
+    let mut response = (StatusCode::OK, Json(result)).into_response();
+    response
+        .headers_mut()
+        .insert(BEST_OF_CANDIDATES_HEADER, HeaderValue::from(candidates.len()));
+    response.headers_mut().insert(BEST_OF_WINNER_HEADER, HeaderValue::from(winner));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_judge_verdict() {
+        assert_eq!(
+            parse_judge_verdict("```json\n{\"best\": 2, \"reason\": \"more complete\"}\n```", 3),
+            Some((1, "more complete".to_string()))
+        );
+        assert_eq!(parse_judge_verdict("{\"best\": \"3\"}", 3), Some((2, String::new())));
+        assert_eq!(parse_judge_verdict("Candidate 1 is best.", 2), Some((0, String::new())));
+        assert_eq!(parse_judge_verdict("{\"best\": 4}", 3), None);
+        assert_eq!(parse_judge_verdict("no idea", 3), None);
+    }
+
+    #[test]
+    fn test_build_judge_prompt() {
+        let messages = vec![
+            json!({"role": "system", "content": "Be brief."}),
+            json!({"role": "user", "content": [{"type": "text", "text": "What is 2+2?"}]}),
+        ];
+        let candidates = vec![
+            json!({"index": 0, "message": {"role": "assistant", "content": "4"}}),
+            json!({"index": 1, "message": {"role": "assistant", "content": null, "tool_calls": [{"function": {"name": "calc"}}]}}),
+        ];
+        let prompt = build_judge_prompt(&messages, &candidates);
+        assert!(prompt.contains("[user]: What is 2+2?"));
+        assert!(prompt.contains("### Candidate 1\n4\n"));
+        assert!(prompt.contains("### Candidate 2\n\n[tool_calls]:"));
+    }
+
+    #[test]
+    fn test_add_usage_sums_candidates() {
+        let mut total = json!({});
+        add_usage(&mut total, Some(&json!({"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15})));
+        add_usage(&mut total, Some(&json!({"prompt_tokens": 10, "completion_tokens": 7, "total_tokens": 17})));
+        add_usage(&mut total, None);
+        assert_eq!(total["completion_tokens"], 12);
+        assert_eq!(total["total_tokens"], 32);
+    }
+}
@@ -0,0 +1,74 @@
+// 请求体大小限制中间件
+// 根据 Content-Length 提前拒绝超限请求，避免读取/缓冲整个请求体后才失败
+
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::server::AppState;
+
+pub async fn body_limit_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    reject_declared_oversize(state.max_body_bytes, request, next).await
+}
+
+async fn reject_declared_oversize(limit: usize, request: Request, next: Next) -> Response {
+    let declared_len = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+
+    if let Some(len) = declared_len {
+        if len > limit {
+            tracing::warn!(
+                "Rejected request to {}: body {} bytes exceeds limit {} bytes",
+                request.uri().path(),
+                len,
+                limit
+            );
+            return payload_too_large_response(limit);
+        }
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, routing::post, Router};
+    use tower::Service;
+
+    fn app(limit: usize) -> Router {
+        Router::new()
+            .route("/v1/chat/completions", post(|| async { "ok" }))
+            .layer(axum::middleware::from_fn(move |req, next| reject_declared_oversize(limit, req, next)))
+    }
+
+    fn request(len: usize) -> Request {
+        Request::post("/v1/chat/completions")
+            .header(header::CONTENT_LENGTH, len)
+            .body(Body::from(vec![b'x'; len]))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rejects_declared_length_over_limit() {
+        let resp = app(1024).call(request(2048)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "request_too_large");
+
+        let resp = app(1024).call(request(1024)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,19 @@
+// 会话上下文中间件
+// 为每个请求建立会话作用域: 读取 X-Conversation-Id 头作为显式会话 ID，
+// 并让下游 (粘性调度 / 签名缓存 / 请求日志) 共享同一会话指纹
+
+use axum::{extract::Request, middleware::Next, response::Response};
+
+use crate::proxy::session_manager::SessionManager;
+
+pub const CONVERSATION_ID_HEADER: &str = "x-conversation-id";
+
+pub async fn conversation_middleware(request: Request, next: Next) -> Response {
+    let explicit_id = request
+        .headers()
+        .get(CONVERSATION_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+
+    SessionManager::scope(explicit_id, next.run(request)).await
+}
@@ -1,11 +1,45 @@
 // CORS 中间件
-use tower_http::cors::{CorsLayer, Any};
-use axum::http::Method;
+use tower_http::cors::{AllowHeaders, AllowOrigin, CorsLayer, ExposeHeaders};
+use axum::http::{HeaderName, HeaderValue, Method};
+
+use crate::proxy::config::CorsConfig;
 
 /// 创建 CORS layer
-pub fn cors_layer() -> CorsLayer {
+pub fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    if !config.enabled {
+        // 不返回任何 Access-Control-* 头，浏览器会拦截跨域请求
+        return CorsLayer::new();
+    }
+
+    // 携带凭据时规范禁止使用 `*`，改为回显请求值
+    let allow_origin = if config.allowed_origins.is_empty() {
+        if config.allow_credentials {
+            AllowOrigin::mirror_request()
+        } else {
+            AllowOrigin::any()
+        }
+    } else {
+        let patterns = config.allowed_origins.clone();
+        AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin
+                .to_str()
+                .map(|o| patterns.iter().any(|p| origin_matches(p, o)))
+                .unwrap_or(false)
+        })
+    };
+
+    let allow_headers = if config.allowed_headers.is_empty() {
+        if config.allow_credentials {
+            AllowHeaders::mirror_request()
+        } else {
+            AllowHeaders::any()
+        }
+    } else {
+        AllowHeaders::list(parse_header_names(&config.allowed_headers))
+    };
+
     CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(allow_origin)
         .allow_methods([
             Method::GET,
             Method::POST,
@@ -15,9 +49,51 @@ pub fn cors_layer() -> CorsLayer {
             Method::OPTIONS,
             Method::PATCH,
         ])
-        .allow_headers(Any)
-        .allow_credentials(false)
-        .max_age(std::time::Duration::from_secs(3600))
+        .allow_headers(allow_headers)
+        .expose_headers(ExposeHeaders::list(parse_header_names(&config.expose_headers)))
+        .allow_credentials(config.allow_credentials)
+        .allow_private_network(config.allow_private_network)
+        .max_age(std::time::Duration::from_secs(config.max_age_secs))
+}
+
+fn parse_header_names(names: &[String]) -> Vec<HeaderName> {
+    names
+        .iter()
+        .filter_map(|n| match HeaderName::from_bytes(n.trim().as_bytes()) {
+            Ok(h) => Some(h),
+            Err(_) => {
+                tracing::warn!("[CORS] 忽略无效的请求头名: {}", n);
+                None
+            }
+        })
+        .collect()
+}
+
+/// 来源匹配，`*` 匹配任意字符序列 (忽略大小写与末尾斜杠)
+fn origin_matches(pattern: &str, origin: &str) -> bool {
+    let pattern = pattern.trim().trim_end_matches('/').to_ascii_lowercase();
+    let origin = origin.trim_end_matches('/').to_ascii_lowercase();
+    if pattern == "*" {
+        return true;
+    }
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = origin.strip_prefix(first) else {
+        return false;
+    };
+    let remaining: Vec<&str> = parts.collect();
+    let Some((last, middle)) = remaining.split_last() else {
+        // 无通配符: 需完全相等
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
 }
 
 #[cfg(test)]
@@ -26,8 +102,26 @@ mod tests {
 
     #[test]
     fn test_cors_layer_creation() {
-        let _layer = cors_layer();
-        // Layer 创建成功
-        assert!(true);
+        let _layer = cors_layer(&CorsConfig::default());
+        let _restricted = cors_layer(&CorsConfig {
+            allowed_origins: vec!["http://localhost:*".to_string()],
+            allowed_headers: vec!["authorization".to_string(), "content-type".to_string()],
+            allow_credentials: true,
+            ..CorsConfig::default()
+        });
+        let _disabled = cors_layer(&CorsConfig { enabled: false, ..CorsConfig::default() });
+    }
+
+    #[test]
+    fn test_origin_matches() {
+        assert!(origin_matches("*", "https://example.com"));
+        assert!(origin_matches("https://chat.example.com", "https://chat.example.com"));
+        assert!(origin_matches("https://chat.example.com/", "https://CHAT.example.com"));
+        assert!(!origin_matches("https://chat.example.com", "https://chat.example.com.evil.io"));
+        assert!(origin_matches("http://localhost:*", "http://localhost:3000"));
+        assert!(!origin_matches("http://localhost:*", "http://localhost.evil.io"));
+        assert!(origin_matches("chrome-extension://*", "chrome-extension://abcdef"));
+        assert!(origin_matches("https://*.example.com", "https://a.example.com"));
+        assert!(!origin_matches("https://*.example.com", "https://example.org"));
     }
 }
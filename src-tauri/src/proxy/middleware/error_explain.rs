@@ -0,0 +1,114 @@
+// 错误解释中间件
+// 非流式错误响应经过错误指纹识别，命中已知场景时在错误体中附加 `error_code` 与 `hint`，
+// 并设置 X-AG-Error-Code 响应头；monitor 位于本层外侧，据此把错误码与建议写入请求历史与界面事件。
+// 未识别的错误保持原样返回。
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Value};
+
+use crate::proxy::middleware::token_budget::{detect_protocol, Protocol};
+use crate::proxy::upstream::error_classifier::{classify, ErrorCase};
+
+/// 识别出的错误码响应头
+pub const ERROR_CODE_HEADER: &str = "x-ag-error-code";
+
+/// 参与识别的错误体上限，超出时原样返回
+const MAX_ERROR_BODY: usize = 1024 * 1024;
+
+/// 在错误体中附加错误码与建议；纯文本错误按协议包装为 JSON 错误体
+fn annotate(protocol: Option<Protocol>, status: StatusCode, text: &str, case: ErrorCase) -> Value {
+    if let Ok(mut json) = serde_json::from_str::<Value>(text) {
+        if let Some(obj) = json.as_object_mut() {
+            let target = match obj.get_mut("error") {
+                Some(Value::Object(error)) => error,
+                _ => obj,
+            };
+            target.insert("error_code".to_string(), json!(case.code));
+            target.insert("hint".to_string(), json!(case.hint));
+            return json;
+        }
+    }
+    let message = text.trim();
+    match protocol {
+        Some(Protocol::Claude) => json!({
+            "type": "error",
+            "error": { "type": "api_error", "message": message, "error_code": case.code, "hint": case.hint }
+        }),
+        Some(Protocol::Gemini) => json!({
+            "error": { "code": status.as_u16(), "message": message, "error_code": case.code, "hint": case.hint }
+        }),
+        _ => json!({
+            "error": { "message": message, "type": "upstream_error", "error_code": case.code, "hint": case.hint }
+        }),
+    }
+}
+
+pub async fn error_explain_middleware(request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let response = next.run(request).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) || path.starts_with("/admin/") {
+        return response;
+    }
+    let is_stream = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/event-stream"));
+    if is_stream {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_ERROR_BODY).await {
+        Ok(b) => b,
+        Err(_) => return Response::from_parts(parts, Body::empty()),
+    };
+    let text = String::from_utf8_lossy(&bytes);
+    let Some(case) = classify(&text) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    tracing::debug!("[ErrorExplain] {} {} -> {}", path, status, case.code);
+
+    let body = serde_json::to_vec(&annotate(detect_protocol(&path), status, &text, case)).unwrap_or_default();
+    parts.headers.insert(ERROR_CODE_HEADER, HeaderValue::from_static(case.code));
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::upstream::error_classifier::{OAUTH_EXPIRED, WRONG_PROJECT};
+
+    #[test]
+    fn test_annotate_shapes() {
+        // 已是 JSON 错误体: 原字段保留，附加到 error 对象
+        let upstream = r#"{"error":{"code":403,"message":"Permission denied on resource project x.","status":"PERMISSION_DENIED"}}"#;
+        let out = annotate(Some(Protocol::Gemini), StatusCode::FORBIDDEN, upstream, WRONG_PROJECT);
+        assert_eq!(out["error"]["status"], "PERMISSION_DENIED");
+        assert_eq!(out["error"]["error_code"], "wrong_project");
+        assert_eq!(out["error"]["hint"], WRONG_PROJECT.hint);
+
+        // 纯文本错误按协议包装
+        let text = "Token error: invalid_grant";
+        let claude = annotate(Some(Protocol::Claude), StatusCode::SERVICE_UNAVAILABLE, text, OAUTH_EXPIRED);
+        assert_eq!(claude["type"], "error");
+        assert_eq!(claude["error"]["message"], text);
+        assert_eq!(claude["error"]["error_code"], "oauth_expired");
+        let openai = annotate(None, StatusCode::SERVICE_UNAVAILABLE, text, OAUTH_EXPIRED);
+        assert_eq!(openai["error"]["type"], "upstream_error");
+
+        // error 为字符串时附加在顶层
+        let out = annotate(None, StatusCode::BAD_GATEWAY, r#"{"error":"invalid_grant"}"#, OAUTH_EXPIRED);
+        assert_eq!(out["error"], "invalid_grant");
+        assert_eq!(out["error_code"], "oauth_expired");
+    }
+}
@@ -0,0 +1,461 @@
+// 扩展钩子中间件
+// 请求转发前 / 响应返回后调用用户配置的 Webhook 或本地程序，按返回值改写请求或响应。
+// 位于出站脱敏 (pii_scrub) 内侧: 开启脱敏时钩子看到的是令牌 (`[EMAIL_1]`) 而不是原值。
+//
+// 钩子输入 (Webhook POST body / 程序 stdin):
+//   {"stage": "before", "hook": "name", "method": "POST", "path": "/v1/chat/completions",
+//    "headers": {...}, "body": {...}}
+//   {"stage": "after", ..., "status": 200, "body": {...请求体}, "response": {...响应体}}
+// 钩子输出 (空 / 204 = 不修改):
+//   {"body": {...}}                       before 替换请求体，after 替换响应体
+//   {"headers": {"x-foo": "bar"}}         before 设置请求头，after 设置响应头
+//   {"reject": {"status": 403, "message": "..."}}
+//
+// 鉴权头会被脱敏；超长 base64 / data URI 字符串以占位符传递，钩子原样返回占位符时会还原为原始数据。
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::config::{HookConfig, HookKind, HookStage};
+use crate::proxy::server::AppState;
+
+/// 超过该长度的 base64 / data URI 字符串不发送给钩子
+const REDACT_MIN_LEN: usize = 4096;
+const SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "x-goog-api-key",
+    "cookie",
+    "set-cookie",
+];
+
+static WEBHOOK_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .user_agent("antigravity-hooks")
+        .build()
+        .unwrap_or_default()
+});
+
+#[derive(Debug, Default, Deserialize)]
+struct HookReject {
+    #[serde(default)]
+    status: Option<u16>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct HookOutput {
+    #[serde(default)]
+    body: Option<Value>,
+    #[serde(default)]
+    headers: Option<HashMap<String, String>>,
+    #[serde(default)]
+    reject: Option<HookReject>,
+}
+
+pub async fn hooks_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = state.hooks.read().await.clone();
+    if !config.enabled || config.hooks.is_empty() {
+        return next.run(request).await;
+    }
+
+    let path = request.uri().path().to_string();
+    let before: Vec<HookConfig> = matching_hooks(&config.hooks, &path, true);
+    let after: Vec<HookConfig> = matching_hooks(&config.hooks, &path, false);
+    if (before.is_empty() && after.is_empty()) || !is_json(request.headers()) {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let method = parts.method.to_string();
+    let bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return payload_too_large_response(state.max_body_bytes),
+    };
+    let mut request_body: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    let mut body_changed = false;
+
+    for hook in &before {
+        let mut redactor = Redactor::default();
+        let payload = json!({
+            "stage": "before",
+            "hook": hook.name,
+            "method": method,
+            "path": path,
+            "headers": redact_headers(&parts.headers),
+            "body": redactor.redact(&request_body),
+        });
+        match invoke_hook(hook, &payload).await {
+            Ok(Some(output)) => {
+                if let Some(reject) = output.reject {
+                    return reject_response(hook, reject);
+                }
+                if let Some(new_body) = output.body {
+                    request_body = redactor.restore(new_body);
+                    body_changed = true;
+                }
+                apply_headers(&mut parts.headers, output.headers);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                if let Some(resp) = hook_failure(hook, e) {
+                    return resp;
+                }
+            }
+        }
+    }
+
+    let forwarded = if body_changed {
+        let serialized = serde_json::to_vec(&request_body).unwrap_or_default();
+        parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+        Body::from(serialized)
+    } else {
+        Body::from(bytes)
+    };
+    let response = next.run(Request::from_parts(parts, forwarded)).await;
+
+    // 流式响应不改写 (SSE 需要边收边发)
+    if after.is_empty() || !is_json(response.headers()) {
+        return response;
+    }
+
+    let (mut resp_parts, resp_body) = response.into_parts();
+    let resp_bytes = match to_bytes(resp_body, usize::MAX).await {
+        Ok(b) => b,
+        Err(e) => {
+            tracing::warn!("[Hooks] 读取响应体失败: {}", e);
+            return (StatusCode::BAD_GATEWAY, "Failed to read upstream response").into_response();
+        }
+    };
+    let Ok(mut response_body) = serde_json::from_slice::<Value>(&resp_bytes) else {
+        return Response::from_parts(resp_parts, Body::from(resp_bytes));
+    };
+    let mut response_changed = false;
+
+    for hook in &after {
+        let mut redactor = Redactor::default();
+        let payload = json!({
+            "stage": "after",
+            "hook": hook.name,
+            "method": method,
+            "path": path,
+            "status": resp_parts.status.as_u16(),
+            "body": redactor.redact(&request_body),
+            "response": redactor.redact(&response_body),
+        });
+        match invoke_hook(hook, &payload).await {
+            Ok(Some(output)) => {
+                if let Some(reject) = output.reject {
+                    return reject_response(hook, reject);
+                }
+                if let Some(new_body) = output.body {
+                    response_body = redactor.restore(new_body);
+                    response_changed = true;
+                }
+                apply_headers(&mut resp_parts.headers, output.headers);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                if let Some(resp) = hook_failure(hook, e) {
+                    return resp;
+                }
+            }
+        }
+    }
+
+    if !response_changed {
+        return Response::from_parts(resp_parts, Body::from(resp_bytes));
+    }
+    let serialized = serde_json::to_vec(&response_body).unwrap_or_default();
+    resp_parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+    Response::from_parts(resp_parts, Body::from(serialized))
+}
+
+fn matching_hooks(hooks: &[HookConfig], path: &str, before: bool) -> Vec<HookConfig> {
+    hooks
+        .iter()
+        .filter(|h| h.enabled)
+        .filter(|h| match h.stage {
+            HookStage::Both => true,
+            HookStage::Before => before,
+            HookStage::After => !before,
+        })
+        .filter(|h| {
+            if h.paths.is_empty() {
+                path.starts_with("/v1/") || path.starts_with("/v1beta/")
+            } else {
+                h.paths.iter().any(|p| path.starts_with(p.as_str()))
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.contains("json"))
+        .unwrap_or(false)
+}
+
+fn redact_headers(headers: &HeaderMap) -> Value {
+    let mut out = Map::new();
+    for (name, value) in headers {
+        let value = if SENSITIVE_HEADERS.contains(&name.as_str()) {
+            "***".to_string()
+        } else {
+            value.to_str().unwrap_or_default().to_string()
+        };
+        out.insert(name.as_str().to_string(), Value::String(value));
+    }
+    Value::Object(out)
+}
+
+fn apply_headers(headers: &mut HeaderMap, updates: Option<HashMap<String, String>>) {
+    for (name, value) in updates.into_iter().flatten() {
+        match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(&value)) {
+            (Ok(n), Ok(v)) => {
+                headers.insert(n, v);
+            }
+            _ => tracing::warn!("[Hooks] 忽略无效的响应头: {}", name),
+        }
+    }
+}
+
+fn reject_response(hook: &HookConfig, reject: HookReject) -> Response {
+    let status = reject
+        .status
+        .and_then(|s| StatusCode::from_u16(s).ok())
+        .unwrap_or(StatusCode::FORBIDDEN);
+    let message = reject
+        .message
+        .unwrap_or_else(|| format!("Request rejected by hook '{}'", hook.name));
+    tracing::info!("[Hooks] 钩子 {} 拒绝了请求: {} {}", hook.name, status, message);
+    (
+        status,
+        Json(json!({ "error": { "message": message, "type": "hook_rejected", "code": hook.name } })),
+    )
+        .into_response()
+}
+
+/// fail_open 时仅记录日志并继续；否则返回 502
+fn hook_failure(hook: &HookConfig, error: String) -> Option<Response> {
+    if hook.fail_open {
+        tracing::warn!("[Hooks] 钩子 {} 调用失败，已放行: {}", hook.name, error);
+        return None;
+    }
+    tracing::error!("[Hooks] 钩子 {} 调用失败: {}", hook.name, error);
+    Some(
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({ "error": { "message": format!("Hook '{}' failed: {}", hook.name, error), "type": "hook_error" } })),
+        )
+            .into_response(),
+    )
+}
+
+async fn invoke_hook(hook: &HookConfig, payload: &Value) -> Result<Option<HookOutput>, String> {
+    let timeout = Duration::from_millis(hook.timeout_ms.max(1));
+    let raw = match hook.kind {
+        HookKind::Webhook => {
+            let resp = WEBHOOK_CLIENT
+                .post(&hook.target)
+                .timeout(timeout)
+                .json(payload)
+                .send()
+                .await
+                .map_err(|e| format!("webhook request failed: {}", e))?;
+            if !resp.status().is_success() {
+                return Err(format!("webhook returned HTTP {}", resp.status()));
+            }
+            resp.bytes().await.map_err(|e| e.to_string())?.to_vec()
+        }
+        HookKind::Command => tokio::time::timeout(timeout, run_command(hook, payload))
+            .await
+            .map_err(|_| format!("command timed out after {} ms", hook.timeout_ms))??,
+    };
+    parse_output(&raw)
+}
+
+async fn run_command(hook: &HookConfig, payload: &Value) -> Result<Vec<u8>, String> {
+    let mut child = tokio::process::Command::new(&hook.target)
+        .args(&hook.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to start {}: {}", hook.target, e))?;
+
+    let input = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    let mut stdin = child.stdin.take().ok_or("stdin unavailable")?;
+    let mut stdout = child.stdout.take().ok_or("stdout unavailable")?;
+    let mut stderr = child.stderr.take().ok_or("stderr unavailable")?;
+    // 写入与读取并行，避免管道缓冲区写满导致死锁
+    let writer = async move {
+        let result = stdin.write_all(&input).await;
+        drop(stdin);
+        result
+    };
+    let reader = async move {
+        let mut buf = Vec::new();
+        stdout.read_to_end(&mut buf).await.map(|_| buf)
+    };
+    let stderr_reader = async move {
+        let mut buf = String::new();
+        let _ = stderr.read_to_string(&mut buf).await;
+        buf
+    };
+    let (written, output, stderr_text) = tokio::join!(writer, reader, stderr_reader);
+    written.map_err(|e| format!("failed to write stdin: {}", e))?;
+    let output = output.map_err(|e| format!("failed to read stdout: {}", e))?;
+
+    let status = child.wait().await.map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("command exited with {}: {}", status, stderr_text.trim()));
+    }
+    Ok(output)
+}
+
+fn parse_output(raw: &[u8]) -> Result<Option<HookOutput>, String> {
+    if raw.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(None);
+    }
+    serde_json::from_slice::<HookOutput>(raw)
+        .map(Some)
+        .map_err(|e| format!("invalid hook output: {}", e))
+}
+
+/// 超长二进制字符串 <-> 占位符
+#[derive(Default)]
+struct Redactor {
+    originals: Vec<String>,
+}
+
+impl Redactor {
+    fn placeholder(idx: usize, len: usize) -> String {
+        format!("[[ag-redacted:{}:{} chars]]", idx, len)
+    }
+
+    fn redact(&mut self, value: &Value) -> Value {
+        match value {
+            Value::String(s) if s.len() >= REDACT_MIN_LEN && looks_binary(s) => {
+                let placeholder = Self::placeholder(self.originals.len(), s.len());
+                self.originals.push(s.clone());
+                Value::String(placeholder)
+            }
+            Value::Array(arr) => Value::Array(arr.iter().map(|v| self.redact(v)).collect()),
+            Value::Object(obj) => Value::Object(obj.iter().map(|(k, v)| (k.clone(), self.redact(v))).collect()),
+            other => other.clone(),
+        }
+    }
+
+    fn restore(&self, value: Value) -> Value {
+        match value {
+            Value::String(s) => {
+                let restored = self
+                    .originals
+                    .iter()
+                    .enumerate()
+                    .find(|(idx, orig)| s == Self::placeholder(*idx, orig.len()))
+                    .map(|(_, orig)| orig.clone());
+                Value::String(restored.unwrap_or(s))
+            }
+            Value::Array(arr) => Value::Array(arr.into_iter().map(|v| self.restore(v)).collect()),
+            Value::Object(obj) => Value::Object(obj.into_iter().map(|(k, v)| (k, self.restore(v))).collect()),
+            other => other,
+        }
+    }
+}
+
+fn looks_binary(s: &str) -> bool {
+    s.starts_with("data:")
+        || s.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=' | b'-' | b'_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(stage: HookStage, paths: &[&str]) -> HookConfig {
+        HookConfig {
+            enabled: true,
+            name: "t".to_string(),
+            kind: HookKind::Webhook,
+            target: "http://127.0.0.1:1".to_string(),
+            args: Vec::new(),
+            stage,
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+            timeout_ms: 100,
+            fail_open: true,
+        }
+    }
+
+    #[test]
+    fn test_matching_hooks() {
+        let hooks = vec![
+            hook(HookStage::Before, &[]),
+            hook(HookStage::After, &["/v1/messages"]),
+            hook(HookStage::Both, &["/v1/chat"]),
+        ];
+        assert_eq!(matching_hooks(&hooks, "/v1/chat/completions", true).len(), 2);
+        assert_eq!(matching_hooks(&hooks, "/v1/chat/completions", false).len(), 1);
+        assert_eq!(matching_hooks(&hooks, "/v1/messages", false).len(), 1);
+        assert!(matching_hooks(&hooks, "/healthz", true).is_empty());
+    }
+
+    #[test]
+    fn test_redactor_roundtrip() {
+        let image = format!("data:image/png;base64,{}", "A".repeat(REDACT_MIN_LEN));
+        let prose = "word ".repeat(REDACT_MIN_LEN);
+        let body = json!({ "messages": [{ "content": [{ "text": "hi" }, { "image_url": { "url": image } }] }], "note": prose });
+
+        let mut redactor = Redactor::default();
+        let redacted = redactor.redact(&body);
+        let sent = redacted["messages"][0]["content"][1]["image_url"]["url"].as_str().unwrap().to_string();
+        assert!(sent.starts_with("[[ag-redacted:0:"));
+        assert_eq!(redacted["note"], body["note"]);
+
+        // 钩子改写了文本但保留占位符
+        let mut modified = redacted.clone();
+        modified["messages"][0]["content"][0]["text"] = json!("rewritten");
+        let restored = redactor.restore(modified);
+        assert_eq!(restored["messages"][0]["content"][1]["image_url"]["url"], body["messages"][0]["content"][1]["image_url"]["url"]);
+        assert_eq!(restored["messages"][0]["content"][0]["text"], "rewritten");
+    }
+
+    #[test]
+    fn test_redact_headers_and_parse_output() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer sk-secret"));
+        headers.insert("x-request-id", HeaderValue::from_static("abc"));
+        let redacted = redact_headers(&headers);
+        assert_eq!(redacted["authorization"], "***");
+        assert_eq!(redacted["x-request-id"], "abc");
+
+        assert!(parse_output(b"  \n").unwrap().is_none());
+        let out = parse_output(br#"{"reject": {"status": 451}}"#).unwrap().unwrap();
+        assert_eq!(out.reject.unwrap().status, Some(451));
+        assert!(parse_output(b"not json").is_err());
+    }
+}
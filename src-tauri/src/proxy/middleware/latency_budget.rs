@@ -0,0 +1,255 @@
+// 延迟预算中间件
+// 客户端通过 X-AG-Max-Latency-Ms 声明可接受的延迟 (流式按首字节，非流式按总耗时)；
+// 预算偏紧时关闭 thinking 或降级到快速模型，并在预算作用域内执行，账号调度据此优先历史更快的账号。
+// 自动补全类的轻量调用因此可以与重型 Agent 请求共用同一个代理。
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Value};
+
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::config::LatencyBudgetConfig;
+use crate::proxy::latency::{self, LatencyBudget, LatencyTracker, MAX_LATENCY_HEADER};
+use crate::proxy::middleware::token_budget::{detect_protocol, extract_model, resolve_target_model, rewrite_model, Protocol};
+use crate::proxy::server::AppState;
+
+/// 降级时标注原目标模型的响应头 (实际使用的模型见 X-Mapped-Model)
+pub const DOWNGRADED_FROM_HEADER: &str = "x-model-downgraded-from";
+/// 本次请求因延迟预算采取的调整 (逗号分隔: downgraded / thinking_off)
+pub const LATENCY_ROUTE_HEADER: &str = "x-ag-latency-route";
+
+#[derive(Debug, Default, PartialEq)]
+struct Plan {
+    downgrade_to: Option<String>,
+    disable_thinking: bool,
+}
+
+/// 已是快速档位的模型不再降级
+fn is_fast_model(model: &str, fast_target: &str) -> bool {
+    model == fast_target || model.contains("flash") || model.contains("lite")
+}
+
+/// 根据预算与目标模型的历史延迟 (所有账号中的最佳值) 决定调整方式
+fn plan(
+    budget_ms: u64,
+    target_estimate: Option<u64>,
+    fast_estimate: Option<u64>,
+    target_model: &str,
+    fast_target: &str,
+    config: &LatencyBudgetConfig,
+) -> Plan {
+    let over_budget = target_estimate.is_some_and(|ms| ms > budget_ms);
+    let can_downgrade = !config.fast_model.is_empty() && !is_fast_model(target_model, fast_target);
+    // 快速模型的历史延迟同样超出预算时，降级也无济于事
+    let downgrade = can_downgrade
+        && (budget_ms < config.downgrade_below_ms || (over_budget && !fast_estimate.is_some_and(|ms| ms > budget_ms)));
+    Plan {
+        downgrade_to: downgrade.then(|| config.fast_model.clone()),
+        disable_thinking: budget_ms < config.disable_thinking_below_ms || over_budget,
+    }
+}
+
+pub(crate) fn is_streaming(protocol: Protocol, path: &str, body: &Value) -> bool {
+    match protocol {
+        Protocol::Gemini => path.ends_with(":streamGenerateContent"),
+        _ => body.get("stream").and_then(|v| v.as_bool()).unwrap_or(false),
+    }
+}
+
+/// 在请求体中关闭 thinking，返回本次请求原本是否会开启 thinking
+/// OpenAI 协议由映射层按模型名注入 thinkingConfig，改为通过预算作用域通知映射层跳过
+fn disable_thinking(protocol: Protocol, body: &mut Value, model: &str) -> bool {
+    match protocol {
+        Protocol::Claude => {
+            // 未显式配置时 Opus 4.5 / -thinking 模型默认开启，需显式 disabled
+            let requested = body.get("model").and_then(|m| m.as_str()).unwrap_or("").to_lowercase();
+            let enabled = match body.get("thinking").and_then(|t| t.get("type")).and_then(|t| t.as_str()) {
+                Some(kind) => kind == "enabled",
+                None => ["opus-4-5", "opus-4.5", "-thinking"].iter().any(|p| requested.contains(p)),
+            };
+            if enabled {
+                body["thinking"] = json!({ "type": "disabled" });
+            }
+            enabled
+        }
+        Protocol::Gemini => {
+            // flash 系列支持 thinkingBudget=0；其他模型不允许关闭，只去掉客户端显式要求的思考预算
+            if model.contains("flash") {
+                if !body.get("generationConfig").is_some_and(|g| g.is_object()) {
+                    body["generationConfig"] = json!({});
+                }
+                body["generationConfig"]["thinkingConfig"] = json!({ "thinkingBudget": 0 });
+                true
+            } else {
+                body.get_mut("generationConfig")
+                    .and_then(|g| g.as_object_mut())
+                    .and_then(|g| g.remove("thinkingConfig"))
+                    .is_some()
+            }
+        }
+        // 与 transform_openai_request 的 thinking 模型判断一致
+        Protocol::OpenAI => {
+            model.ends_with("-thinking")
+                || (model.contains("gemini-3") && (model.ends_with("-high") || model.ends_with("-low") || model.contains("-pro")))
+        }
+    }
+}
+
+pub async fn latency_budget_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let Some(protocol) = detect_protocol(&path) else {
+        return next.run(request).await;
+    };
+    let Some(budget_ms) = request
+        .headers()
+        .get(MAX_LATENCY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(latency::parse_budget_header)
+    else {
+        return next.run(request).await;
+    };
+    let config = state.latency_budget.read().await.clone();
+    if !config.enabled {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return payload_too_large_response(state.max_body_bytes),
+    };
+    let Ok(mut json) = serde_json::from_slice::<Value>(&bytes) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let Some(model) = extract_model(&path, &json) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let streaming = is_streaming(protocol, &path, &json);
+    let (target_model, fast_target) = {
+        let mapping = state.custom_mapping.read().await;
+        (resolve_target_model(&model, &mapping), resolve_target_model(&config.fast_model, &mapping))
+    };
+
+    let tracker = LatencyTracker::global();
+    let plan = plan(
+        budget_ms,
+        tracker.model_estimate(&target_model, streaming),
+        tracker.model_estimate(&fast_target, streaming),
+        &target_model,
+        &fast_target,
+        &config,
+    );
+
+    let mut actions = Vec::new();
+    let mut effective_model = target_model.clone();
+    if let Some(fast_model) = &plan.downgrade_to {
+        if rewrite_model(&mut parts, &mut json, fast_model) {
+            actions.push("downgraded");
+            effective_model = fast_target.clone();
+        }
+    }
+    if plan.disable_thinking && disable_thinking(protocol, &mut json, &effective_model) {
+        actions.push("thinking_off");
+    }
+
+    let budget = LatencyBudget {
+        max_ms: budget_ms,
+        streaming,
+        suppress_thinking: plan.disable_thinking,
+    };
+    if actions.is_empty() {
+        return latency::scope(budget, next.run(Request::from_parts(parts, Body::from(bytes)))).await;
+    }
+
+    tracing::info!(
+        "[LatencyBudget] {} 预算 {}ms ({}): {} -> {} [{}]",
+        path,
+        budget_ms,
+        if streaming { "首字节" } else { "总耗时" },
+        target_model,
+        effective_model,
+        actions.join(",")
+    );
+    let serialized = serde_json::to_vec(&json).unwrap_or_default();
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+    let mut response = latency::scope(budget, next.run(Request::from_parts(parts, Body::from(serialized)))).await;
+
+    let headers = response.headers_mut();
+    if let Ok(v) = HeaderValue::from_str(&actions.join(",")) {
+        headers.insert(LATENCY_ROUTE_HEADER, v);
+    }
+    if actions.contains(&"downgraded") {
+        if let Ok(v) = HeaderValue::from_str(&target_model) {
+            headers.insert(DOWNGRADED_FROM_HEADER, v);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_thresholds_without_history() {
+        let config = LatencyBudgetConfig::default();
+        let fast = "gemini-2.5-flash";
+        // 宽松预算: 不做调整
+        assert_eq!(plan(60_000, None, None, "gemini-2.5-pro", fast, &config), Plan::default());
+        // 中等预算: 仅关闭 thinking
+        assert_eq!(
+            plan(5_000, None, None, "gemini-2.5-pro", fast, &config),
+            Plan { downgrade_to: None, disable_thinking: true }
+        );
+        // 紧张预算: 降级 + 关闭 thinking
+        assert_eq!(
+            plan(1_000, None, None, "gemini-2.5-pro", fast, &config),
+            Plan { downgrade_to: Some(fast.to_string()), disable_thinking: true }
+        );
+        // 已是快速模型时不降级
+        assert_eq!(plan(1_000, None, None, "gemini-2.5-flash-lite", fast, &config).downgrade_to, None);
+    }
+
+    #[test]
+    fn test_plan_uses_history() {
+        let config = LatencyBudgetConfig::default();
+        let fast = "gemini-2.5-flash";
+        // 预算宽于阈值，但目标模型历史延迟超出预算，快速模型满足预算
+        let p = plan(20_000, Some(30_000), Some(4_000), "claude-opus-4-5-thinking", fast, &config);
+        assert_eq!(p, Plan { downgrade_to: Some(fast.to_string()), disable_thinking: true });
+        // 快速模型同样超出预算: 只关闭 thinking
+        let p = plan(20_000, Some(30_000), Some(25_000), "claude-opus-4-5-thinking", fast, &config);
+        assert_eq!(p, Plan { downgrade_to: None, disable_thinking: true });
+        // 历史延迟满足预算: 不调整
+        assert_eq!(plan(20_000, Some(8_000), None, "gemini-2.5-pro", fast, &config), Plan::default());
+    }
+
+    #[test]
+    fn test_disable_thinking_per_protocol() {
+        let mut claude = json!({"model": "claude-opus-4-5", "thinking": {"type": "enabled", "budget_tokens": 8000}});
+        assert!(disable_thinking(Protocol::Claude, &mut claude, "claude-opus-4-5-thinking"));
+        assert_eq!(claude["thinking"], json!({"type": "disabled"}));
+        assert!(!disable_thinking(Protocol::Claude, &mut claude, "claude-opus-4-5-thinking"));
+        let mut sonnet = json!({"model": "claude-sonnet-4-5"});
+        assert!(!disable_thinking(Protocol::Claude, &mut sonnet, "claude-sonnet-4-5"));
+        assert!(sonnet.get("thinking").is_none());
+
+        let mut openai = json!({"model": "gemini-3-pro-high"});
+        assert!(disable_thinking(Protocol::OpenAI, &mut openai, "gemini-3-pro-high"));
+        assert!(!disable_thinking(Protocol::OpenAI, &mut openai, "gemini-2.5-flash"));
+
+        let mut flash = json!({"contents": []});
+        assert!(disable_thinking(Protocol::Gemini, &mut flash, "gemini-2.5-flash"));
+        assert_eq!(flash["generationConfig"]["thinkingConfig"]["thinkingBudget"], 0);
+
+        let mut pro = json!({"generationConfig": {"thinkingConfig": {"thinkingBudget": 4096}, "temperature": 0.2}});
+        assert!(disable_thinking(Protocol::Gemini, &mut pro, "gemini-2.5-pro"));
+        assert_eq!(pro["generationConfig"], json!({"temperature": 0.2}));
+        assert!(!disable_thinking(Protocol::Gemini, &mut pro, "gemini-2.5-pro"));
+    }
+}
@@ -0,0 +1,222 @@
+// 本地文件附件内联 (受信模式)
+// 把请求中以 `file://` / 绝对路径引用的附件读取并替换为各协议的 base64 形式:
+// OpenAI 的 image_url / file part、Claude 的 url 类型 source、Gemini 的 fileData。
+// 访问范围由 common::local_files 校验；不受信的 Key 携带本地引用时返回 403，读取失败返回 400，
+// 而不是静默丢弃附件让模型在缺少输入的情况下作答。
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+
+use crate::proxy::common::local_files::{is_local_reference, LocalFile, LocalFileAccess};
+use crate::proxy::middleware::token_budget::{detect_protocol, request_api_key, Protocol};
+use crate::proxy::server::AppState;
+use crate::proxy::{ProxyAuthMode, ProxySecurityConfig};
+
+/// 内联失败: (状态码, 错误信息)
+type InlineError = (StatusCode, String);
+
+fn load(reference: &str, access: Option<&LocalFileAccess>) -> Result<Option<LocalFile>, InlineError> {
+    if !is_local_reference(reference) {
+        return Ok(None);
+    }
+    let Some(access) = access else {
+        return Err((StatusCode::FORBIDDEN, "Local file references are not allowed for this API key".to_string()));
+    };
+    access.read(reference).map(Some).map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+fn inline_openai_part(part: &mut Value, access: Option<&LocalFileAccess>) -> Result<usize, InlineError> {
+    match part.get("type").and_then(|t| t.as_str()) {
+        Some("image_url") => {
+            // image_url 兼容对象与字符串两种写法
+            let url = match part.get_mut("image_url") {
+                Some(Value::String(url)) => url,
+                Some(Value::Object(obj)) => match obj.get_mut("url") {
+                    Some(Value::String(url)) => url,
+                    _ => return Ok(0),
+                },
+                _ => return Ok(0),
+            };
+            let Some(file) = load(url, access)? else { return Ok(0) };
+            *url = file.data_url();
+            Ok(1)
+        }
+        Some("file") => {
+            let Some(obj) = part.get_mut("file").and_then(|f| f.as_object_mut()) else { return Ok(0) };
+            // file_data 为本地路径，或未提供 file_data 时 filename 为本地路径
+            let reference = match (obj.get("file_data").and_then(|d| d.as_str()), obj.get("filename").and_then(|n| n.as_str())) {
+                (Some(data), _) => data.to_string(),
+                (None, Some(name)) => name.to_string(),
+                (None, None) => return Ok(0),
+            };
+            let Some(file) = load(&reference, access)? else { return Ok(0) };
+            obj.insert("filename".to_string(), json!(file.filename()));
+            obj.insert("file_data".to_string(), json!(file.data_url()));
+            Ok(1)
+        }
+        _ => Ok(0),
+    }
+}
+
+fn inline_claude_block(block: &mut Value, access: Option<&LocalFileAccess>) -> Result<usize, InlineError> {
+    match block.get("type").and_then(|t| t.as_str()) {
+        Some("image") | Some("document") => {
+            let Some(source) = block.get_mut("source") else { return Ok(0) };
+            if source.get("type").and_then(|t| t.as_str()) != Some("url") {
+                return Ok(0);
+            }
+            let Some(url) = source.get("url").and_then(|u| u.as_str()) else { return Ok(0) };
+            let Some(file) = load(url, access)? else { return Ok(0) };
+            *source = json!({ "type": "base64", "media_type": file.mime_type, "data": file.data });
+            Ok(1)
+        }
+        // 工具结果中同样可以引用图片
+        Some("tool_result") => match block.get_mut("content").and_then(|c| c.as_array_mut()) {
+            Some(items) => items.iter_mut().try_fold(0, |n, item| Ok::<_, InlineError>(n + inline_claude_block(item, access)?)),
+            None => Ok(0),
+        },
+        _ => Ok(0),
+    }
+}
+
+fn inline_gemini_part(part: &mut Value, access: Option<&LocalFileAccess>) -> Result<usize, InlineError> {
+    let (key, uri_key) = if part.get("fileData").is_some() { ("fileData", "fileUri") } else { ("file_data", "file_uri") };
+    let Some(uri) = part.get(key).and_then(|d| d.get(uri_key)).and_then(|u| u.as_str()) else { return Ok(0) };
+    let Some(file) = load(uri, access)? else { return Ok(0) };
+    let Some(obj) = part.as_object_mut() else { return Ok(0) };
+    obj.remove(key);
+    obj.insert("inlineData".to_string(), json!({ "mimeType": file.mime_type, "data": file.data }));
+    Ok(1)
+}
+
+/// 内联请求中的全部本地引用，返回内联数量
+fn inline_local_files(protocol: Protocol, body: &mut Value, access: Option<&LocalFileAccess>) -> Result<usize, InlineError> {
+    let (list, parts_key) = match protocol {
+        Protocol::OpenAI | Protocol::Claude => ("messages", "content"),
+        Protocol::Gemini => ("contents", "parts"),
+    };
+    let Some(messages) = body.get_mut(list).and_then(|m| m.as_array_mut()) else {
+        return Ok(0);
+    };
+    let mut inlined = 0;
+    for message in messages {
+        let Some(parts) = message.get_mut(parts_key).and_then(|p| p.as_array_mut()) else { continue };
+        for part in parts {
+            inlined += match protocol {
+                Protocol::OpenAI => inline_openai_part(part, access)?,
+                Protocol::Claude => inline_claude_block(part, access)?,
+                Protocol::Gemini => inline_gemini_part(part, access)?,
+            };
+        }
+    }
+    Ok(inlined)
+}
+
+/// 反代是否对当前请求要求 API Key (auth_mode 解析后不为 off)
+/// 未开启鉴权时客户端提供的 Key 未经校验，不能据此授予本地文件访问
+pub async fn auth_required(state: &AppState) -> bool {
+    let config = state.proxy_config.read().await;
+    !matches!(ProxySecurityConfig::from_proxy_config(&config).effective_auth_mode(), ProxyAuthMode::Off)
+}
+
+pub async fn local_files_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let Some(protocol) = detect_protocol(&path) else {
+        return next.run(request).await;
+    };
+    if protocol == Protocol::OpenAI && path != "/v1/chat/completions" {
+        return next.run(request).await;
+    }
+    let config = state.experimental.read().await.local_files.clone();
+    if !config.enabled {
+        return next.run(request).await;
+    }
+    let api_key = request_api_key(&request);
+    let auth_required = auth_required(&state).await;
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return crate::proxy::common::streaming_json::payload_too_large_response(state.max_body_bytes),
+    };
+    let Ok(json) = serde_json::from_slice::<Value>(&bytes) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    // 解析路径与读取文件均为阻塞 IO
+    let result = tokio::task::spawn_blocking(move || {
+        let mut json = json;
+        let access = LocalFileAccess::for_request(&config, api_key.as_deref(), auth_required);
+        inline_local_files(protocol, &mut json, access.as_ref()).map(|n| (n, json))
+    })
+    .await
+    .unwrap_or_else(|e| Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Local file task failed: {}", e))));
+    let (inlined, json) = match result {
+        Ok((0, _)) => return next.run(Request::from_parts(parts, Body::from(bytes))).await,
+        Ok(done) => done,
+        Err((status, message)) => {
+            tracing::warn!("[LocalFiles] {} 拒绝本地文件引用: {}", path, message);
+            return (
+                status,
+                axum::Json(json!({ "error": { "message": message, "type": "invalid_request_error", "code": "local_file_rejected" } })),
+            )
+                .into_response();
+        }
+    };
+    tracing::info!("[LocalFiles] {} 内联了 {} 个本地文件", path, inlined);
+
+    let serialized = serde_json::to_vec(&json).unwrap_or_default();
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+    next.run(Request::from_parts(parts, Body::from(serialized))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::config::LocalFilesConfig;
+
+    #[test]
+    fn test_inline_per_protocol() {
+        let dir = std::env::temp_dir().join(format!("local-files-mw-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.txt");
+        std::fs::write(&file, "hi").unwrap();
+        let path = file.display().to_string();
+        let config = LocalFilesConfig {
+            enabled: true,
+            roots: vec![dir.display().to_string()],
+            trusted_keys: vec!["sk-cli".to_string()],
+            ..Default::default()
+        };
+        let access = LocalFileAccess::for_request(&config, Some("sk-cli"), true);
+
+        let mut openai = json!({ "messages": [{ "role": "user", "content": [
+            { "type": "image_url", "image_url": { "url": "https://example.com/a.png" } },
+            { "type": "file", "file": { "filename": path } }
+        ]}]});
+        assert_eq!(inline_local_files(Protocol::OpenAI, &mut openai, access.as_ref()).unwrap(), 1);
+        assert_eq!(openai["messages"][0]["content"][1]["file"]["file_data"], "data:text/plain;base64,aGk=");
+        assert_eq!(openai["messages"][0]["content"][1]["file"]["filename"], "notes.txt");
+
+        let mut claude = json!({ "messages": [{ "role": "user", "content": [
+            { "type": "document", "source": { "type": "url", "url": path } }
+        ]}]});
+        assert_eq!(inline_local_files(Protocol::Claude, &mut claude, access.as_ref()).unwrap(), 1);
+        assert_eq!(claude["messages"][0]["content"][0]["source"]["type"], "base64");
+
+        let mut gemini = json!({ "contents": [{ "role": "user", "parts": [{ "fileData": { "fileUri": path } }] }] });
+        assert_eq!(inline_local_files(Protocol::Gemini, &mut gemini, access.as_ref()).unwrap(), 1);
+        assert_eq!(gemini["contents"][0]["parts"][0]["inlineData"]["data"], "aGk=");
+
+        // 未授权的客户端与根目录外的路径
+        let mut denied = json!({ "contents": [{ "parts": [{ "fileData": { "fileUri": "/etc/hosts" } }] }] });
+        assert_eq!(inline_local_files(Protocol::Gemini, &mut denied.clone(), None).unwrap_err().0, StatusCode::FORBIDDEN);
+        assert_eq!(inline_local_files(Protocol::Gemini, &mut denied, access.as_ref()).unwrap_err().0, StatusCode::BAD_REQUEST);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
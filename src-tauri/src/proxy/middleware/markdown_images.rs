@@ -0,0 +1,291 @@
+// Markdown 图片提取
+// 部分客户端只能发送纯文本消息，图片只能以 `![alt](data:image/png;base64,...)` 或本地路径的形式
+// 嵌在正文里。开启后把这些引用提取为各协议正式的图片 part，并在原位置留下 `[Image #N: alt]` 占位符，
+// 模型既能看到图片，也能从占位符知道图片在文中的位置。本地路径需额外开启，并受 local_files 的根目录与受信 Key 限制。
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+use crate::proxy::common::local_files::{local_path, LocalFileAccess};
+use crate::proxy::common::mime_sniff;
+use crate::proxy::middleware::token_budget::{detect_protocol, request_api_key, Protocol};
+use crate::proxy::server::AppState;
+
+/// 单次请求最多提取的图片数
+const MAX_IMAGES: usize = 16;
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp", "heic", "heif", "avif"];
+
+fn image_ref_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        // ![alt](data:image/...;base64,...) | ![alt](file:///...) | ![alt](/abs/path) | ![alt](C:\path)，路径可用 <> 包裹
+        Regex::new(r"!\[([^\]\n]*)\]\(\s*<?(data:image/[\w.+-]*;base64,[A-Za-z0-9+/=_\-\s]+?|file://[^)>\n]+|/[^)>\n]+|[A-Za-z]:[\\/][^)>\n]+)>?\s*\)")
+            .unwrap()
+    })
+}
+
+/// 提取出的图片
+#[derive(Debug, Clone, PartialEq)]
+struct ExtractedImage {
+    mime_type: String,
+    data: String,
+}
+
+impl ExtractedImage {
+    fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data)
+    }
+
+    fn to_part(&self, protocol: Protocol) -> Value {
+        match protocol {
+            Protocol::OpenAI => json!({ "type": "image_url", "image_url": { "url": self.data_url() } }),
+            Protocol::Claude => json!({
+                "type": "image",
+                "source": { "type": "base64", "media_type": self.mime_type, "data": self.data }
+            }),
+            Protocol::Gemini => json!({ "inlineData": { "mimeType": self.mime_type, "data": self.data } }),
+        }
+    }
+}
+
+fn read_local_image(reference: &str, access: &LocalFileAccess) -> Result<ExtractedImage, String> {
+    let path = local_path(reference);
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    if !IMAGE_EXTENSIONS.contains(&ext.as_str()) {
+        return Err(format!("{} is not an image file", path.display()));
+    }
+    let file = access.read(reference)?;
+    if !file.mime_type.starts_with("image/") {
+        return Err(format!("Unrecognized image content in {}", path.display()));
+    }
+    Ok(ExtractedImage { mime_type: file.mime_type, data: file.data })
+}
+
+/// 解析单个引用；无法使用的引用 (本地路径未授权、读取失败等) 返回 None，原文保持不变
+fn resolve_reference(reference: &str, local: Option<&LocalFileAccess>) -> Option<ExtractedImage> {
+    if let Some((mime_type, data)) = mime_sniff::parse_data_url(reference, "image/png") {
+        let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+        return Some(ExtractedImage { mime_type, data });
+    }
+    read_local_image(reference.trim(), local?)
+        .map_err(|e| tracing::warn!("[MarkdownImages] 跳过本地图片: {}", e))
+        .ok()
+}
+
+/// 替换文本中的图片引用为占位符，返回 (新文本, 提取出的图片)。`counter` 为跨消息的图片序号
+fn extract_from_text(text: &str, local: Option<&LocalFileAccess>, counter: &mut usize) -> (String, Vec<ExtractedImage>) {
+    let mut images = Vec::new();
+    let replaced = image_ref_regex().replace_all(text, |caps: &regex::Captures| {
+        let original = caps[0].to_string();
+        if *counter >= MAX_IMAGES {
+            return original;
+        }
+        let Some(image) = resolve_reference(&caps[2], local) else {
+            return original;
+        };
+        *counter += 1;
+        images.push(image);
+        let alt = caps[1].trim();
+        if alt.is_empty() {
+            format!("[Image #{}]", counter)
+        } else {
+            format!("[Image #{}: {}]", counter, alt)
+        }
+    });
+    (replaced.into_owned(), images)
+}
+
+fn text_part(protocol: Protocol, text: String) -> Value {
+    match protocol {
+        Protocol::OpenAI | Protocol::Claude => json!({ "type": "text", "text": text }),
+        Protocol::Gemini => json!({ "text": text }),
+    }
+}
+
+/// 处理一条消息的内容: 字符串内容转为 part 数组，每个文本 part 之后紧跟其中提取出的图片
+fn process_content(protocol: Protocol, content: &mut Value, local: Option<&LocalFileAccess>, counter: &mut usize) -> usize {
+    let parts: Vec<Value> = match content {
+        Value::String(s) => vec![text_part(protocol, std::mem::take(s))],
+        Value::Array(parts) => std::mem::take(parts),
+        _ => return 0,
+    };
+    let was_string = content.is_string();
+    let mut extracted = 0;
+    let mut out = Vec::with_capacity(parts.len());
+    for mut part in parts {
+        let is_text = match protocol {
+            Protocol::Gemini => part.get("text").is_some_and(|t| t.is_string()) && part.get("thought").is_none(),
+            _ => part.get("type").and_then(|t| t.as_str()) == Some("text"),
+        };
+        let images = match part.get_mut("text") {
+            Some(Value::String(text)) if is_text && text.contains("![") => {
+                let (replaced, images) = extract_from_text(text, local, counter);
+                *text = replaced;
+                images
+            }
+            _ => Vec::new(),
+        };
+        extracted += images.len();
+        out.push(part);
+        out.extend(images.iter().map(|image| image.to_part(protocol)));
+    }
+    // 未提取到图片时还原字符串内容，避免无谓改变请求形态
+    *content = if was_string && extracted == 0 {
+        out.pop().and_then(|p| p.get("text").cloned()).unwrap_or_else(|| Value::String(String::new()))
+    } else {
+        Value::Array(out)
+    };
+    extracted
+}
+
+/// 处理所有用户消息 (历史中的图片引用同样需要提取，保证重放时上下文一致)，返回提取数量
+fn extract_images(protocol: Protocol, body: &mut Value, local: Option<&LocalFileAccess>) -> usize {
+    let (list, parts_key) = match protocol {
+        Protocol::OpenAI | Protocol::Claude => ("messages", "content"),
+        Protocol::Gemini => ("contents", "parts"),
+    };
+    let Some(messages) = body.get_mut(list).and_then(|m| m.as_array_mut()) else {
+        return 0;
+    };
+    let mut counter = 0;
+    let mut extracted = 0;
+    for message in messages {
+        if message.get("role").and_then(|r| r.as_str()).unwrap_or("user") != "user" {
+            continue;
+        }
+        if let Some(content) = message.get_mut(parts_key) {
+            extracted += process_content(protocol, content, local, &mut counter);
+        }
+    }
+    extracted
+}
+
+pub async fn markdown_images_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let Some(protocol) = detect_protocol(&path) else {
+        return next.run(request).await;
+    };
+    if protocol == Protocol::OpenAI && path != "/v1/chat/completions" {
+        return next.run(request).await;
+    }
+    let (enabled, local_config) = {
+        let exp = state.experimental.read().await;
+        (exp.markdown_images, exp.markdown_images_local_files.then(|| exp.local_files.clone()))
+    };
+    if !enabled {
+        return next.run(request).await;
+    }
+    let api_key = request_api_key(&request);
+    let auth_required = crate::proxy::middleware::local_files::auth_required(&state).await;
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return crate::proxy::common::streaming_json::payload_too_large_response(state.max_body_bytes),
+    };
+    // 快速路径: 正文中没有 Markdown 图片语法
+    if !bytes.windows(2).any(|w| w == b"![") {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    }
+    let Ok(json) = serde_json::from_slice::<Value>(&bytes) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    // 本地图片路径的解析与读取为阻塞 IO
+    let extracted = tokio::task::spawn_blocking(move || {
+        let mut json = json;
+        let local = local_config.and_then(|c| LocalFileAccess::for_request(&c, api_key.as_deref(), auth_required));
+        (extract_images(protocol, &mut json, local.as_ref()), json)
+    })
+    .await;
+    let (extracted, json) = match extracted {
+        Ok(done) => done,
+        Err(e) => {
+            tracing::warn!("[MarkdownImages] 提取任务失败: {}", e);
+            return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+        }
+    };
+    if extracted == 0 {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    }
+    tracing::info!("[MarkdownImages] {} 从消息文本中提取了 {} 张图片", path, extracted);
+
+    let serialized = serde_json::to_vec(&json).unwrap_or_default();
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+    next.run(Request::from_parts(parts, Body::from(serialized))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::config::LocalFilesConfig;
+    use base64::Engine as _;
+
+    const PNG_B64: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    #[test]
+    fn test_extract_per_protocol() {
+        let text = format!("Compare ![left](data:image/jpeg;base64,{}) with ![](data:image/png;base64,{}) please", PNG_B64, PNG_B64);
+
+        let mut openai = json!({ "messages": [
+            { "role": "system", "content": text.clone() },
+            { "role": "user", "content": text.clone() }
+        ]});
+        assert_eq!(extract_images(Protocol::OpenAI, &mut openai, None), 2);
+        assert_eq!(openai["messages"][0]["content"], text.as_str());
+        let content = openai["messages"][1]["content"].as_array().unwrap();
+        assert_eq!(content.len(), 3);
+        assert_eq!(content[0]["text"], "Compare [Image #1: left] with [Image #2] please");
+        // 声明为 JPEG 的 PNG 按魔数更正
+        assert!(content[1]["image_url"]["url"].as_str().unwrap().starts_with("data:image/png;base64,"));
+
+        let mut claude = json!({ "messages": [{ "role": "user", "content": [{ "type": "text", "text": text.clone() }] }] });
+        assert_eq!(extract_images(Protocol::Claude, &mut claude, None), 2);
+        assert_eq!(claude["messages"][0]["content"][1]["source"]["media_type"], "image/png");
+
+        let mut gemini = json!({ "contents": [{ "role": "user", "parts": [{ "text": text }] }] });
+        assert_eq!(extract_images(Protocol::Gemini, &mut gemini, None), 2);
+        assert_eq!(gemini["contents"][0]["parts"][2]["inlineData"]["mimeType"], "image/png");
+    }
+
+    #[test]
+    fn test_local_paths_require_opt_in() {
+        let dir = std::env::temp_dir().join(format!("md-images-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("shot.png");
+        std::fs::write(&file, base64::engine::general_purpose::STANDARD.decode(PNG_B64).unwrap()).unwrap();
+        let text = format!("see ![shot]({}) and ![x](/etc/passwd)", file.display());
+
+        let config = LocalFilesConfig {
+            enabled: true,
+            roots: vec![dir.display().to_string()],
+            trusted_keys: vec!["sk-cli".to_string()],
+            ..Default::default()
+        };
+        let access = LocalFileAccess::for_request(&config, Some("sk-cli"), true).unwrap();
+
+        let mut counter = 0;
+        let (unchanged, images) = extract_from_text(&text, None, &mut counter);
+        assert_eq!((unchanged.as_str(), images.len()), (text.as_str(), 0));
+
+        let (replaced, images) = extract_from_text(&text, Some(&access), &mut counter);
+        assert_eq!(images.len(), 1);
+        assert_eq!(replaced, "see [Image #1: shot] and ![x](/etc/passwd)");
+
+        let mut plain = json!({ "messages": [{ "role": "user", "content": "no images here ![alt](https://x/y.png)" }] });
+        assert_eq!(extract_images(Protocol::OpenAI, &mut plain, Some(&access)), 0);
+        assert!(plain["messages"][0]["content"].is_string());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
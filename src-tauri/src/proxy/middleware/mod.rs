@@ -1,9 +1,55 @@
 // Middleware 模块 - Axum 中间件
 
+pub mod ab_routing;
+pub mod account_pinning;
 pub mod auth;
+pub mod best_of;
+pub mod body_limit;
+pub mod conversation;
+pub mod error_explain;
 pub mod cors;
+pub mod hooks;
+pub mod latency_budget;
+pub mod local_files;
 pub mod logging;
+pub mod model_fallback;
 pub mod monitor;
+pub mod pii_scrub;
+pub mod presets;
+pub mod profile;
+pub mod racing;
+pub mod request_id;
+pub mod response_rewrite;
+pub mod stream_pacing;
+pub mod stream_stats;
+pub mod stream_tee;
+pub mod token_budget;
+pub mod translation;
+pub mod markdown_images;
+pub mod virtual_models;
 
+pub use ab_routing::ab_routing_middleware;
+pub use account_pinning::account_pinning_middleware;
 pub use auth::auth_middleware;
+pub use best_of::best_of_middleware;
+pub use body_limit::body_limit_middleware;
+pub use conversation::conversation_middleware;
+pub use error_explain::error_explain_middleware;
 pub use cors::cors_layer;
+pub use hooks::hooks_middleware;
+pub use latency_budget::latency_budget_middleware;
+pub use local_files::local_files_middleware;
+pub use model_fallback::model_fallback_middleware;
+pub use pii_scrub::pii_scrub_middleware;
+pub use presets::presets_middleware;
+pub use profile::profile_middleware;
+pub use racing::racing_middleware;
+pub use request_id::request_id_middleware;
+pub use response_rewrite::response_rewrite_middleware;
+pub use stream_pacing::stream_pacing_middleware;
+pub use stream_stats::stream_stats_middleware;
+pub use stream_tee::stream_tee_middleware;
+pub use token_budget::token_budget_middleware;
+pub use translation::translation_middleware;
+pub use markdown_images::markdown_images_middleware;
+pub use virtual_models::virtual_models_middleware;
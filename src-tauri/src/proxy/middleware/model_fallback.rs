@@ -0,0 +1,233 @@
+// 模型降级链中间件
+// 主模型返回容量不足 / 模型不受支持类错误时，按配置的顺序改用备用模型重新走一遍 handler，
+// 最终响应通过响应头标注实际使用的模型。
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{header, request::Parts, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::Value;
+
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::config::ModelFallbackConfig;
+use crate::proxy::middleware::token_budget::{detect_protocol, extract_model, rewrite_model};
+use crate::proxy::server::AppState;
+
+/// 发生降级时标注原请求模型的响应头 (实际使用的模型见 X-Mapped-Model)
+pub const FALLBACK_FROM_HEADER: &str = "x-model-fallback-from";
+/// 依次尝试过的模型 (逗号分隔)
+pub const FALLBACK_ATTEMPTS_HEADER: &str = "x-model-fallback-attempts";
+
+/// 判断 400 错误是否因模型不可用引起时读取的错误体上限
+const ERROR_BODY_LIMIT: usize = 1024 * 1024;
+
+/// 400 错误体中表明模型不受支持的关键字
+const UNSUPPORTED_MARKERS: &[&str] = &[
+    "not supported",
+    "unsupported model",
+    "model not found",
+    "model_not_found",
+    "is not found",
+    "invalid model",
+    "does not exist",
+];
+
+fn find_chain<'a>(config: &'a ModelFallbackConfig, model: &str) -> Option<&'a Vec<String>> {
+    if let Some(chain) = config.chains.get(model) {
+        return Some(chain);
+    }
+    config
+        .chains
+        .iter()
+        .find(|(pattern, _)| pattern.contains('*') && wildcard_match(pattern, model))
+        .map(|(_, chain)| chain)
+}
+
+fn is_unsupported_error(body: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(body).to_lowercase();
+    UNSUPPORTED_MARKERS.iter().any(|m| text.contains(m))
+}
+
+/// 复制请求头部 (保留扩展，其中包含路由阶段解析出的路径参数)
+fn clone_parts(parts: &Parts) -> Parts {
+    let (mut cloned, _) = Request::new(()).into_parts();
+    cloned.method = parts.method.clone();
+    cloned.uri = parts.uri.clone();
+    cloned.version = parts.version;
+    cloned.headers = parts.headers.clone();
+    cloned.extensions = parts.extensions.clone();
+    cloned
+}
+
+/// 检查响应是否应触发降级；读取过的错误体会重新装回响应
+async fn should_fall_back(response: Response, config: &ModelFallbackConfig) -> (bool, Response) {
+    let status = response.status();
+    if !config.trigger_statuses.contains(&status.as_u16()) {
+        return (false, response);
+    }
+    if status.as_u16() != 400 {
+        return (true, response);
+    }
+
+    let (parts, body) = response.into_parts();
+    match to_bytes(body, ERROR_BODY_LIMIT).await {
+        Ok(bytes) => {
+            let unsupported = is_unsupported_error(&bytes);
+            (unsupported, Response::from_parts(parts, Body::from(bytes)))
+        }
+        Err(_) => (false, Response::from_parts(parts, Body::empty())),
+    }
+}
+
+fn build_attempt(parts: &Parts, json: &Value, bytes: &Bytes, model: Option<&str>) -> Request {
+    let mut parts = clone_parts(parts);
+    let Some(model) = model else {
+        return Request::from_parts(parts, Body::from(bytes.clone()));
+    };
+    let mut json = json.clone();
+    if !rewrite_model(&mut parts, &mut json, model) {
+        return Request::from_parts(parts, Body::from(bytes.clone()));
+    }
+    let serialized = serde_json::to_vec(&json).unwrap_or_default();
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+    Request::from_parts(parts, Body::from(serialized))
+}
+
+pub async fn model_fallback_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    if detect_protocol(&path).is_none() {
+        return next.run(request).await;
+    }
+    let config = state.model_fallback.read().await.clone();
+    if !config.enabled || config.chains.is_empty() {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return payload_too_large_response(state.max_body_bytes),
+    };
+    let Ok(json) = serde_json::from_slice::<Value>(&bytes) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let Some(model) = extract_model(&path, &json) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let Some(chain) = find_chain(&config, &model).filter(|c| !c.is_empty()).cloned() else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+
+    let mut response = next.clone().run(build_attempt(&parts, &json, &bytes, None)).await;
+    let mut attempts = vec![model.clone()];
+
+    for candidate in &chain {
+        if attempts.contains(candidate) {
+            continue;
+        }
+        let (fall_back, resp) = should_fall_back(response, &config).await;
+        if !fall_back {
+            response = resp;
+            break;
+        }
+        tracing::warn!(
+            "[Fallback] {} 返回 {}，改用备用模型 {}",
+            attempts.last().map(|s| s.as_str()).unwrap_or_default(),
+            resp.status(),
+            candidate
+        );
+        response = next.clone().run(build_attempt(&parts, &json, &bytes, Some(candidate))).await;
+        attempts.push(candidate.clone());
+    }
+
+    if attempts.len() > 1 {
+        let served = attempts.last().cloned().unwrap_or_default();
+        let headers = response.headers_mut();
+        if let Ok(v) = HeaderValue::from_str(&model) {
+            headers.insert(FALLBACK_FROM_HEADER, v);
+        }
+        if let Ok(v) = HeaderValue::from_str(&attempts.join(",")) {
+            headers.insert(FALLBACK_ATTEMPTS_HEADER, v);
+        }
+        if !headers.contains_key("X-Mapped-Model") {
+            if let Ok(v) = HeaderValue::from_str(&served) {
+                headers.insert("X-Mapped-Model", v);
+            }
+        }
+        if response.status().is_success() {
+            tracing::info!("[Fallback] 请求 {} 最终由 {} 完成", model, served);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    #[test]
+    fn test_find_chain_exact_and_wildcard() {
+        let mut config = ModelFallbackConfig::default();
+        config.chains.insert("gemini-3-pro-high".to_string(), vec!["gemini-2.5-pro".to_string()]);
+        config.chains.insert("claude-*".to_string(), vec!["gemini-2.5-flash".to_string()]);
+
+        assert_eq!(find_chain(&config, "gemini-3-pro-high").unwrap()[0], "gemini-2.5-pro");
+        assert_eq!(find_chain(&config, "claude-sonnet-4-5").unwrap()[0], "gemini-2.5-flash");
+        assert!(find_chain(&config, "gemini-2.5-flash").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_should_fall_back_by_status_and_message() {
+        let config = ModelFallbackConfig::default();
+
+        let resp = Response::builder().status(StatusCode::SERVICE_UNAVAILABLE).body(Body::empty()).unwrap();
+        assert!(should_fall_back(resp, &config).await.0);
+
+        let resp = Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Body::from(r#"{"error":{"message":"Model gemini-x is not supported"}}"#))
+            .unwrap();
+        assert!(should_fall_back(resp, &config).await.0);
+
+        // 普通参数错误不降级，且错误体原样保留
+        let resp = Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Body::from("temperature must be <= 2"))
+            .unwrap();
+        let (fall_back, resp) = should_fall_back(resp, &config).await;
+        assert!(!fall_back);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"temperature must be <= 2");
+
+        let resp = Response::builder().status(StatusCode::OK).body(Body::empty()).unwrap();
+        assert!(!should_fall_back(resp, &config).await.0);
+    }
+
+    #[test]
+    fn test_build_attempt_rewrites_model() {
+        let (parts, _) = Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let json = serde_json::json!({"model": "gemini-3-pro-high", "messages": []});
+        let bytes = Bytes::from(serde_json::to_vec(&json).unwrap());
+
+        let request = build_attempt(&parts, &json, &bytes, Some("gemini-2.5-pro"));
+        assert_eq!(request.method(), "POST");
+        assert_eq!(request.headers()[header::CONTENT_TYPE], "application/json");
+        let len: usize = request.headers()[header::CONTENT_LENGTH].to_str().unwrap().parse().unwrap();
+        assert!(len > 0);
+    }
+}
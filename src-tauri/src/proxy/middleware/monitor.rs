@@ -1,23 +1,158 @@
 use axum::{
     extract::{Request, State},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
     body::Body,
 };
 use std::time::Instant;
 use crate::proxy::server::AppState;
 use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::middleware::stream_stats::StreamStats;
 use serde_json::Value;
 use futures::StreamExt;
+use bytes::{Bytes, BytesMut};
 
-const MAX_REQUEST_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB
+/// handler 写入处理备注的响应头，记录到请求历史的 notes 字段
+pub const NOTES_HEADER: &str = "x-ag-notes";
+
+/// 请求标签头，如 `X-AG-Tag: project=foo,task=refactor` (可出现多次)，用于按项目/脚本归集用量
+pub const TAG_HEADER: &str = "x-ag-tag";
+const MAX_TAGS: usize = 16;
+const MAX_TAG_PART_LEN: usize = 64;
+
+const MAX_REQUEST_LOG_SIZE: usize = 2 * 1024 * 1024; // 请求日志最多保留 2MB，避免大图片请求体在日志中再复制一份
+const MAX_REQUEST_CAPTURE_SIZE: usize = 16 * 1024 * 1024; // 超过 16MB 的请求体只缓冲开头部分，其余直接流向下游
 const MAX_RESPONSE_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB for image responses
 
+/// 仅解析 `model` 字段，其余字段跳过 (不为大请求体构建完整 Value)
+fn extract_model_field(bytes: &[u8]) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct ModelOnly {
+        model: Option<String>,
+    }
+    serde_json::from_slice::<ModelOnly>(bytes).ok().and_then(|m| m.model)
+}
+
+/// 请求体只捕获了开头时，按文本查找 `"model": "..."`
+fn scan_model_field(head: &[u8]) -> Option<String> {
+    static RE: std::sync::OnceLock<regex::bytes::Regex> = std::sync::OnceLock::new();
+    let re = RE.get_or_init(|| regex::bytes::Regex::new(r#""model"\s*:\s*"([^"\\]{1,200})""#).unwrap());
+    let m = re.captures(head)?.get(1)?;
+    std::str::from_utf8(m.as_bytes()).ok().map(|s| s.to_string())
+}
+
+enum CapturedBody {
+    Full(Bytes),
+    /// 超过捕获上限: 已读取的开头部分，以及开头与剩余数据流拼接成的请求体
+    Partial(Bytes, Body),
+    /// 读取过程中超过请求体上限 (未声明 Content-Length 的分块请求)
+    TooLarge,
+}
+
+/// 缓冲请求体，最多 capture_limit 字节；剩余部分的上限由下游的读取方继续检查
+async fn capture_body(body: Body, capture_limit: usize, max_bytes: usize) -> Result<CapturedBody, axum::Error> {
+    let mut stream = body.into_data_stream();
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        if buf.len() + chunk.len() > max_bytes {
+            return Ok(CapturedBody::TooLarge);
+        }
+        buf.extend_from_slice(&chunk);
+        if buf.len() > capture_limit {
+            let head = buf.freeze();
+            let replay = futures::stream::once(futures::future::ready(Ok(head.clone()))).chain(stream);
+            return Ok(CapturedBody::Partial(head, Body::from_stream(replay)));
+        }
+    }
+    Ok(CapturedBody::Full(buf.freeze()))
+}
+
+/// 请求历史中保存的请求体
+fn request_log_body(bytes: &[u8], complete: bool) -> String {
+    if !complete {
+        let head = String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_REQUEST_LOG_SIZE)]);
+        format!("{}... [truncated, more than {} bytes]", head, bytes.len())
+    } else if bytes.len() > MAX_REQUEST_LOG_SIZE {
+        let head = String::from_utf8_lossy(&bytes[..MAX_REQUEST_LOG_SIZE]);
+        format!("{}... [truncated, {} bytes total]", head, bytes.len())
+    } else if let Ok(s) = std::str::from_utf8(bytes) {
+        s.to_string()
+    } else {
+        "[Binary Request Data]".to_string()
+    }
+}
+
+fn sanitize_tag_part(raw: &str, lowercase: bool) -> String {
+    raw.trim()
+        .chars()
+        .take(MAX_TAG_PART_LEN)
+        .map(|c| {
+            let c = if lowercase { c.to_ascii_lowercase() } else { c };
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | ':' | '@') { c } else { '_' }
+        })
+        .collect()
+}
+
+/// 解析并规范化请求标签: key 小写、非法字符替换为 `_`、去重排序，返回 "key=value,key2" 形式
+pub fn parse_tags<'a>(values: impl Iterator<Item = &'a str>) -> Option<String> {
+    let mut tags = std::collections::BTreeSet::new();
+    for part in values.flat_map(|v| v.split(',')) {
+        let (key, value) = match part.split_once('=') {
+            Some((k, v)) => (sanitize_tag_part(k, true), sanitize_tag_part(v, false)),
+            None => (sanitize_tag_part(part, true), String::new()),
+        };
+        if key.is_empty() {
+            continue;
+        }
+        tags.insert(if value.is_empty() { key } else { format!("{}={}", key, value) });
+        if tags.len() >= MAX_TAGS {
+            break;
+        }
+    }
+    (!tags.is_empty()).then(|| tags.into_iter().collect::<Vec<_>>().join(","))
+}
+
+/// 从非流式响应的 usage (OpenAI / Anthropic) 或 usageMetadata (Gemini) 中提取 (输入, 输出) token 数
+pub(crate) fn usage_tokens(json: &Value) -> (Option<u32>, Option<u32>) {
+    let Some(usage) = json.get("usage").or(json.get("usageMetadata")) else {
+        return (None, None);
+    };
+    let input = usage.get("prompt_tokens")
+        .or(usage.get("input_tokens"))
+        .or(usage.get("promptTokenCount"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32);
+    let output = usage.get("completion_tokens")
+        .or(usage.get("output_tokens"))
+        .or(usage.get("candidatesTokenCount"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32);
+    if input.is_none() && output.is_none() {
+        let total = usage.get("total_tokens")
+            .or(usage.get("totalTokenCount"))
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32);
+        return (None, total);
+    }
+    (input, output)
+}
+
+fn apply_stats(log: &mut ProxyRequestLog, stats: &StreamStats) {
+    log.ttft_ms = Some(stats.ttft_ms);
+    log.tokens_per_sec = stats.tokens_per_sec;
+    log.chunk_count = (stats.chunk_count > 0).then_some(stats.chunk_count);
+}
+
 pub async fn monitor_middleware(
     State(state): State<AppState>,
     request: Request,
     next: Next,
 ) -> Response {
+    let path = request.uri().path();
+    if !(path.contains("event_logging") || path == "/healthz" || path == "/readyz") {
+        state.monitor.record_hit();
+    }
     if !state.monitor.is_enabled() {
         return next.run(request).await;
     }
@@ -25,8 +160,12 @@ pub async fn monitor_middleware(
     let start = Instant::now();
     let method = request.method().to_string();
     let uri = request.uri().to_string();
+    let tags = parse_tags(
+        request.headers().get_all(TAG_HEADER).iter().filter_map(|v| v.to_str().ok()),
+    );
     
-    if uri.contains("event_logging") {
+    // 心跳与健康检查不记录 (容器探针会高频调用)
+    if uri.contains("event_logging") || uri == "/healthz" || uri == "/readyz" {
         return next.run(request).await;
     }
     
@@ -40,27 +179,28 @@ pub async fn monitor_middleware(
     };
 
     let request_body_str;
+    let mut message_ids = None;
     let request = if method == "POST" {
         let (parts, body) = request.into_parts();
-        match axum::body::to_bytes(body, MAX_REQUEST_LOG_SIZE).await {
-            Ok(bytes) => {
-                if model.is_none() {
-                    model = serde_json::from_slice::<Value>(&bytes).ok().and_then(|v|
-                        v.get("model").and_then(|m| m.as_str()).map(|s| s.to_string())
-                    );
-                }
-                request_body_str = if let Ok(s) = std::str::from_utf8(&bytes) {
-                    Some(s.to_string())
-                } else {
-                    Some("[Binary Request Data]".to_string())
-                };
-                Request::from_parts(parts, Body::from(bytes))
+        // Bytes 为引用计数，传给下游不会复制请求体
+        let (captured, complete, body) = match capture_body(body, MAX_REQUEST_CAPTURE_SIZE, state.max_body_bytes).await {
+            Ok(CapturedBody::Full(bytes)) => (bytes.clone(), true, Body::from(bytes)),
+            Ok(CapturedBody::Partial(head, body)) => (head, false, body),
+            Ok(CapturedBody::TooLarge) => {
+                return crate::proxy::common::streaming_json::payload_too_large_response(state.max_body_bytes);
             }
-            Err(_) => {
-                request_body_str = None;
-                Request::from_parts(parts, Body::empty())
+            Err(e) => {
+                return (axum::http::StatusCode::BAD_REQUEST, format!("Failed to read request body: {}", e)).into_response();
             }
+        };
+        if model.is_none() {
+            model = if complete { extract_model_field(&captured) } else { scan_model_field(&captured) };
         }
+        if complete {
+            message_ids = crate::modules::conversation_tree::message_ids(uri.split('?').next().unwrap_or(&uri), &captured);
+        }
+        request_body_str = Some(request_log_body(&captured, complete));
+        Request::from_parts(parts, body)
     } else {
         request_body_str = None;
         request
@@ -90,6 +230,20 @@ pub async fn monitor_middleware(
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
 
+    // 处理备注 (handler 通过 X-AG-Notes 响应头写入，如 JSON 修复结果)
+    let notes = response
+        .headers()
+        .get(NOTES_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+
+    // 错误指纹识别结果 (error_explain 中间件写入)
+    let error_case = response
+        .headers()
+        .get(crate::proxy::middleware::error_explain::ERROR_CODE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(crate::proxy::upstream::error_classifier::case_for_code);
+
     // Determine protocol from URL path
     let protocol = if uri.contains("/v1/messages") {
         Some("anthropic".to_string())
@@ -118,7 +272,24 @@ pub async fn monitor_middleware(
         input_tokens: None,
         output_tokens: None,
         protocol,
+        session_id: crate::proxy::session_manager::SessionManager::current_conversation_id(),
+        notes,
+        tags,
+        ttft_ms: None,
+        tokens_per_sec: None,
+        chunk_count: None,
+        message_id: None,
+        parent_message_id: None,
+        error_code: error_case.map(|c| c.code.to_string()),
+        error_hint: error_case.map(|c| c.hint.to_string()),
     };
+    if let Some((message_id, parent_message_id)) = message_ids {
+        log.message_id = Some(message_id);
+        log.parent_message_id = parent_message_id;
+    }
+    if let Some(stats) = StreamStats::from_headers(response.headers()) {
+        apply_stats(&mut log, &stats);
+    }
 
     if content_type.contains("text/event-stream") {
         let (parts, body) = response.into_parts();
@@ -149,6 +320,9 @@ pub async fn monitor_middleware(
             
             // Parse and consolidate stream data into readable format
             if let Ok(full_response) = std::str::from_utf8(&all_stream_data) {
+                if let Some(stats) = StreamStats::from_sse(full_response) {
+                    apply_stats(&mut log, &stats);
+                }
                 let mut thinking_content = String::new();
                 let mut response_content = String::new();
                 let mut thinking_signature = String::new();
@@ -287,25 +461,7 @@ pub async fn monitor_middleware(
                 if let Ok(s) = std::str::from_utf8(&bytes) {
                     if let Ok(json) = serde_json::from_str::<Value>(&s) {
                         // 支持 OpenAI "usage" 或 Gemini "usageMetadata"
-                        if let Some(usage) = json.get("usage").or(json.get("usageMetadata")) {
-                            log.input_tokens = usage.get("prompt_tokens")
-                                .or(usage.get("input_tokens"))
-                                .or(usage.get("promptTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                            log.output_tokens = usage.get("completion_tokens")
-                                .or(usage.get("output_tokens"))
-                                .or(usage.get("candidatesTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                                
-                            if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                log.output_tokens = usage.get("total_tokens")
-                                    .or(usage.get("totalTokenCount"))
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                            }
-                        }
+                        (log.input_tokens, log.output_tokens) = usage_tokens(&json);
                     }
                     log.response_body = Some(s.to_string());
                 } else {
@@ -330,3 +486,48 @@ pub async fn monitor_middleware(
         response
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags_normalizes_and_merges_headers() {
+        let tags = parse_tags(["Project=foo, task=re factor", "nightly,project=foo,=x"].into_iter());
+        assert_eq!(tags.as_deref(), Some("nightly,project=foo,task=re_factor"));
+        assert_eq!(parse_tags(["  , "].into_iter()), None);
+    }
+
+    fn chunked(chunks: Vec<&'static [u8]>) -> Body {
+        Body::from_stream(futures::stream::iter(chunks.into_iter().map(|c| Ok::<_, std::io::Error>(Bytes::from_static(c)))))
+    }
+
+    #[tokio::test]
+    async fn test_capture_body_caps_buffering_and_replays_the_rest() {
+        let Ok(CapturedBody::Full(bytes)) = capture_body(chunked(vec![b"{\"model\":", b"\"gemini-2.5-pro\"}"]), 64, 1024).await else {
+            panic!("expected full capture");
+        };
+        assert_eq!(extract_model_field(&bytes).as_deref(), Some("gemini-2.5-pro"));
+
+        let body = chunked(vec![b"{\"model\": \"gemini-2.5-flash\", \"messages\": [", b"\"0123456789\",", b"\"abc\"]}"]);
+        let Ok(CapturedBody::Partial(head, replay)) = capture_body(body, 48, 1024).await else {
+            panic!("expected partial capture");
+        };
+        assert!(head.len() < 70);
+        assert_eq!(scan_model_field(&head).as_deref(), Some("gemini-2.5-flash"));
+        assert!(request_log_body(&head, false).ends_with(&format!("[truncated, more than {} bytes]", head.len())));
+        // 下游收到完整请求体
+        let full = axum::body::to_bytes(replay, 1024).await.unwrap();
+        assert_eq!(&full[..], b"{\"model\": \"gemini-2.5-flash\", \"messages\": [\"0123456789\",\"abc\"]}");
+    }
+
+    #[tokio::test]
+    async fn test_capture_body_rejects_oversized_stream() {
+        // 未声明 Content-Length，读取途中超过上限
+        let body = chunked(vec![&[b'x'; 600], &[b'x'; 600]]);
+        assert!(matches!(capture_body(body, 4096, 1000).await, Ok(CapturedBody::TooLarge)));
+        // 超过捕获上限后不再由这里检查，剩余部分交给下游
+        let body = chunked(vec![&[b'x'; 600], &[b'x'; 600]]);
+        assert!(matches!(capture_body(body, 100, 1000).await, Ok(CapturedBody::Partial(..))));
+    }
+}
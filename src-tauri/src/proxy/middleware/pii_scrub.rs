@@ -0,0 +1,480 @@
+// 出站敏感信息脱敏中间件
+// 请求体中的邮箱 / API Key / 主目录路径 / 自定义正则命中值替换为令牌 (`[EMAIL_1]`)，
+// 同一请求内相同值映射到同一令牌；响应 (JSON 与 SSE) 中出现的令牌还原为原值后再返回客户端。
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use futures::StreamExt;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::config::PiiScrubConfig;
+use crate::proxy::server::AppState;
+
+/// 这些字段不是自然语言内容，不做替换 (模型名、二进制数据、签名、ID、工具定义等)
+const SKIP_KEYS: &[&str] = &[
+    "model",
+    "data",
+    "url",
+    "signature",
+    "thoughtSignature",
+    "thought_signature",
+    "id",
+    "tool_call_id",
+    "tool_use_id",
+    "type",
+    "role",
+    "tools",
+    "functionDeclarations",
+    "mimeType",
+    "media_type",
+];
+
+/// 流式响应中可能被拆分到多个事件的文本增量字段
+const DELTA_KEYS: &[&str] = &["content", "text", "arguments", "partial_json", "reasoning_content", "thinking"];
+
+/// 值本身是 JSON 文本的字段 (工具调用参数)，还原时需要对原值做 JSON 转义
+const JSON_STRING_KEYS: &[&str] = &["arguments", "partial_json"];
+
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}";
+const API_KEY_PATTERN: &str = r"(?:sk-ant-[A-Za-z0-9_-]{20,}|sk-[A-Za-z0-9_-]{20,}|AIza[0-9A-Za-z_-]{35}|gh[pousr]_[A-Za-z0-9]{36,}|github_pat_[A-Za-z0-9_]{40,}|xox[abprs]-[A-Za-z0-9-]{10,}|AKIA[0-9A-Z]{16}|ya29\.[0-9A-Za-z_-]{20,}|1//[0-9A-Za-z_-]{20,})";
+const HOME_PATH_PATTERN: &str = r#"(?:/home/|/Users/|[A-Za-z]:\\Users\\)[^/\\\s"'`]+"#;
+
+/// 编译后的规则集 (按配置缓存)
+pub struct Scrubber {
+    rules: Vec<(&'static str, Regex)>,
+}
+
+impl Scrubber {
+    pub fn from_config(config: &PiiScrubConfig) -> Self {
+        let mut rules = Vec::new();
+        if config.mask_api_keys {
+            rules.push(("API_KEY", Regex::new(API_KEY_PATTERN).expect("valid api key regex")));
+        }
+        if config.mask_emails {
+            rules.push(("EMAIL", Regex::new(EMAIL_PATTERN).expect("valid email regex")));
+        }
+        if config.mask_file_paths {
+            rules.push(("PATH", Regex::new(HOME_PATH_PATTERN).expect("valid path regex")));
+        }
+        for pattern in &config.custom_patterns {
+            match Regex::new(pattern) {
+                Ok(re) => rules.push(("CUSTOM", re)),
+                Err(e) => tracing::warn!("[PII] 忽略无效的自定义正则 {}: {}", pattern, e),
+            }
+        }
+        Self { rules }
+    }
+}
+
+/// 单个请求的 值 <-> 令牌 映射
+#[derive(Default)]
+pub struct TokenMap {
+    by_value: HashMap<String, String>,
+    by_token: HashMap<String, String>,
+    counters: HashMap<&'static str, usize>,
+}
+
+impl TokenMap {
+    fn token_for(&mut self, label: &'static str, value: &str) -> String {
+        if let Some(token) = self.by_value.get(value) {
+            return token.clone();
+        }
+        let n = self.counters.entry(label).or_insert(0);
+        *n += 1;
+        let token = format!("[{}_{}]", label, n);
+        self.by_value.insert(value.to_string(), token.clone());
+        self.by_token.insert(token.clone(), value.to_string());
+        token
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_token.is_empty()
+    }
+
+    fn mask_str(&mut self, scrubber: &Scrubber, text: &str) -> String {
+        let mut out = text.to_string();
+        for &(label, ref re) in &scrubber.rules {
+            if !re.is_match(&out) {
+                continue;
+            }
+            let mut replaced = String::with_capacity(out.len());
+            let mut last = 0;
+            for m in re.find_iter(&out) {
+                // 已替换出的令牌不再二次匹配
+                if self.by_token.contains_key(m.as_str()) || m.as_str().is_empty() {
+                    continue;
+                }
+                replaced.push_str(&out[last..m.start()]);
+                replaced.push_str(&self.token_for(label, m.as_str()));
+                last = m.end();
+            }
+            replaced.push_str(&out[last..]);
+            out = replaced;
+        }
+        out
+    }
+
+    /// 递归脱敏 JSON，返回是否有修改
+    pub fn mask_value(&mut self, scrubber: &Scrubber, value: &mut Value) -> bool {
+        match value {
+            Value::String(s) => {
+                let masked = self.mask_str(scrubber, s);
+                let changed = masked != *s;
+                *s = masked;
+                changed
+            }
+            Value::Array(arr) => arr.iter_mut().fold(false, |acc, v| self.mask_value(scrubber, v) | acc),
+            Value::Object(obj) => obj
+                .iter_mut()
+                .filter(|(k, _)| !SKIP_KEYS.contains(&k.as_str()))
+                .fold(false, |acc, (_, v)| self.mask_value(scrubber, v) | acc),
+            _ => false,
+        }
+    }
+
+    pub fn unmask_str(&self, text: &str, json_escaped: bool) -> String {
+        if !text.contains('[') {
+            return text.to_string();
+        }
+        let mut out = text.to_string();
+        for (token, value) in &self.by_token {
+            if !out.contains(token.as_str()) {
+                continue;
+            }
+            if json_escaped {
+                let encoded = serde_json::to_string(value).unwrap_or_default();
+                out = out.replace(token.as_str(), &encoded[1..encoded.len() - 1]);
+            } else {
+                out = out.replace(token.as_str(), value);
+            }
+        }
+        out
+    }
+
+    pub fn unmask_value(&self, value: &mut Value) {
+        match value {
+            Value::String(s) => *s = self.unmask_str(s, false),
+            Value::Array(arr) => arr.iter_mut().for_each(|v| self.unmask_value(v)),
+            Value::Object(obj) => {
+                for (key, v) in obj.iter_mut() {
+                    match v {
+                        Value::String(s) if JSON_STRING_KEYS.contains(&key.as_str()) => {
+                            *s = self.unmask_str(s, true)
+                        }
+                        _ => self.unmask_value(v),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 文本末尾若是某个令牌的前缀，返回前缀长度 (需要留到下一个增量再处理)
+    fn pending_prefix_len(&self, text: &str) -> usize {
+        let Some(start) = text.rfind('[') else { return 0 };
+        let tail = &text[start..];
+        if self.by_token.keys().any(|t| t.len() > tail.len() && t.starts_with(tail)) {
+            tail.len()
+        } else {
+            0
+        }
+    }
+}
+
+/// SSE 响应还原器: 按行解析 data 事件，文本增量字段带跨事件缓冲
+pub struct StreamUnmasker {
+    map: Arc<TokenMap>,
+    line_buf: Vec<u8>,
+    carry: HashMap<String, String>,
+}
+
+impl StreamUnmasker {
+    pub fn new(map: Arc<TokenMap>) -> Self {
+        Self { map, line_buf: Vec::new(), carry: HashMap::new() }
+    }
+
+    /// 处理一段原始字节，返回可立即下发的字节
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.line_buf.extend_from_slice(chunk);
+        let Some(last_newline) = self.line_buf.iter().rposition(|b| *b == b'\n') else {
+            return Vec::new();
+        };
+        let complete: Vec<u8> = self.line_buf.drain(..=last_newline).collect();
+        let text = String::from_utf8_lossy(&complete);
+        let mut out = String::with_capacity(text.len());
+        for line in text.split_inclusive('\n') {
+            out.push_str(&self.process_line(line));
+        }
+        out.into_bytes()
+    }
+
+    pub fn finish(&mut self) -> Vec<u8> {
+        let rest = std::mem::take(&mut self.line_buf);
+        // 流结束时仍未补全的令牌前缀只可能是模型输出的普通文本，已无事件可承载
+        for (key, pending) in self.carry.drain() {
+            tracing::debug!("[PII] 流结束，丢弃字段 {} 中未完成的片段: {}", key, pending);
+        }
+        self.process_line(&String::from_utf8_lossy(&rest)).into_bytes()
+    }
+
+    fn process_line(&mut self, line: &str) -> String {
+        let Some(payload) = line.strip_prefix("data:") else {
+            return line.to_string();
+        };
+        let trimmed = payload.trim();
+        let Ok(mut json) = serde_json::from_str::<Value>(trimmed) else {
+            return self.map.unmask_str(line, false);
+        };
+        self.unmask_event(&mut json);
+        let newline = if line.ends_with("\r\n") { "\r\n" } else if line.ends_with('\n') { "\n" } else { "" };
+        let space = if payload.starts_with(' ') { " " } else { "" };
+        format!("data:{}{}{}", space, json, newline)
+    }
+
+    fn unmask_event(&mut self, value: &mut Value) {
+        match value {
+            Value::Object(obj) => {
+                for (key, v) in obj.iter_mut() {
+                    match v {
+                        Value::String(s) if DELTA_KEYS.contains(&key.as_str()) => {
+                            let mut text = self.carry.remove(key).unwrap_or_default();
+                            text.push_str(s);
+                            let hold = self.map.pending_prefix_len(&text);
+                            let (emit, pending) = text.split_at(text.len() - hold);
+                            if !pending.is_empty() {
+                                self.carry.insert(key.clone(), pending.to_string());
+                            }
+                            *s = self.map.unmask_str(emit, JSON_STRING_KEYS.contains(&key.as_str()));
+                        }
+                        _ => self.unmask_event(v),
+                    }
+                }
+            }
+            Value::Array(arr) => arr.iter_mut().for_each(|v| self.unmask_event(v)),
+            Value::String(s) => *s = self.map.unmask_str(s, false),
+            _ => {}
+        }
+    }
+}
+
+static SCRUBBER_CACHE: Mutex<Option<(String, Arc<Scrubber>)>> = Mutex::new(None);
+
+fn cached_scrubber(config: &PiiScrubConfig) -> Arc<Scrubber> {
+    let key = serde_json::to_string(config).unwrap_or_default();
+    let mut cache = SCRUBBER_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached_key, scrubber)) = cache.as_ref() {
+        if *cached_key == key {
+            return scrubber.clone();
+        }
+    }
+    let scrubber = Arc::new(Scrubber::from_config(config));
+    *cache = Some((key, scrubber.clone()));
+    scrubber
+}
+
+/// 进程内直接调用 handler 的请求 (batch、summarize、ocr、rerank、翻译) 不经过本中间件，由调用方在此脱敏。
+/// 返回用于还原响应的映射 (未启用、无命中或不还原响应时为 None)
+pub(crate) fn scrub_body(config: &PiiScrubConfig, body: &mut Value) -> Option<TokenMap> {
+    if !config.enabled {
+        return None;
+    }
+    let mut map = TokenMap::default();
+    if !map.mask_value(&cached_scrubber(config), body) {
+        return None;
+    }
+    tracing::info!("[PII] 已脱敏 {} 个敏感值 (进程内调用)", map.by_token.len());
+    config.unmask_responses.then_some(map)
+}
+
+pub async fn pii_scrub_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = state.pii_scrub.read().await.clone();
+    let path = request.uri().path();
+    let applies = path.starts_with("/v1/") || path.starts_with("/v1beta/");
+    let is_json = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.contains("json"))
+        .unwrap_or(false);
+    if !config.enabled || !applies || !is_json {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return payload_too_large_response(state.max_body_bytes),
+    };
+    let Ok(mut json) = serde_json::from_slice::<Value>(&bytes) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+
+    let scrubber = cached_scrubber(&config);
+    let mut map = TokenMap::default();
+    if !map.mask_value(&scrubber, &mut json) {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    }
+    tracing::info!("[PII] 已脱敏 {} 个敏感值 ({})", map.by_token.len(), parts.uri.path());
+
+    let serialized = serde_json::to_vec(&json).unwrap_or_default();
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+    let response = next.run(Request::from_parts(parts, Body::from(serialized))).await;
+
+    if !config.unmask_responses {
+        return response;
+    }
+    unmask_response(response, Arc::new(map)).await
+}
+
+async fn unmask_response(response: Response, map: Arc<TokenMap>) -> Response {
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let (mut parts, body) = response.into_parts();
+
+    if content_type.contains("text/event-stream") {
+        let mut stream = body.into_data_stream();
+        let output = async_stream::stream! {
+            let mut unmasker = StreamUnmasker::new(map);
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        let out = unmasker.push(&bytes);
+                        if !out.is_empty() {
+                            yield Ok::<_, axum::Error>(bytes::Bytes::from(out));
+                        }
+                    }
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                }
+            }
+            let rest = unmasker.finish();
+            if !rest.is_empty() {
+                yield Ok(bytes::Bytes::from(rest));
+            }
+        };
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::from_stream(output));
+    }
+
+    if !content_type.contains("json") {
+        return Response::from_parts(parts, body);
+    }
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(b) => b,
+        Err(e) => {
+            tracing::warn!("[PII] 读取响应体失败: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    let Ok(mut json) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    map.unmask_value(&mut json);
+    let serialized = serde_json::to_vec(&json).unwrap_or_default();
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+    Response::from_parts(parts, Body::from(serialized))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn scrubber() -> Scrubber {
+        Scrubber::from_config(&PiiScrubConfig {
+            enabled: true,
+            custom_patterns: vec![r"ACME-\d{4}".to_string(), "(".to_string()],
+            ..PiiScrubConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_mask_and_unmask_roundtrip() {
+        let scrubber = scrubber();
+        let mut map = TokenMap::default();
+        let mut body = json!({
+            "model": "gpt-4o",
+            "messages": [
+                {"role": "user", "content": "Mail alice@example.com or alice@example.com, key sk-abcdefghijklmnopqrstuvwxyz123456"},
+                {"role": "user", "content": [{"type": "text", "text": "open /home/alice/project/main.rs and C:\\Users\\bob\\x.txt for ACME-1234"}]}
+            ]
+        });
+        assert!(map.mask_value(&scrubber, &mut body));
+        let first = body["messages"][0]["content"].as_str().unwrap();
+        assert_eq!(first, "Mail [EMAIL_1] or [EMAIL_1], key [API_KEY_1]");
+        let second = body["messages"][1]["content"][0]["text"].as_str().unwrap();
+        assert_eq!(second, "open [PATH_1]/project/main.rs and [PATH_2]\\x.txt for [CUSTOM_1]");
+        assert_eq!(body["model"], "gpt-4o");
+
+        let mut reply = json!({"choices": [{"message": {"content": "Sent to [EMAIL_1]", "tool_calls": [
+            {"function": {"arguments": "{\"path\":\"[PATH_2]\\\\x.txt\"}"}}
+        ]}}]});
+        map.unmask_value(&mut reply);
+        assert_eq!(reply["choices"][0]["message"]["content"], "Sent to alice@example.com");
+        assert_eq!(
+            reply["choices"][0]["message"]["tool_calls"][0]["function"]["arguments"],
+            "{\"path\":\"C:\\\\Users\\\\bob\\\\x.txt\"}"
+        );
+    }
+
+    #[test]
+    fn test_scrub_body_for_in_process_calls() {
+        let mut config = PiiScrubConfig { enabled: true, ..PiiScrubConfig::default() };
+        let mut body = json!({"model": "m", "messages": [{"role": "user", "content": "ping carol@example.com"}]});
+        let map = scrub_body(&config, &mut body).unwrap();
+        assert_eq!(body["messages"][0]["content"], "ping [EMAIL_1]");
+        let mut reply = json!({"choices": [{"message": {"content": "pong [EMAIL_1]"}}]});
+        map.unmask_value(&mut reply);
+        assert_eq!(reply["choices"][0]["message"]["content"], "pong carol@example.com");
+
+        // 不还原响应时仍脱敏请求体
+        config.unmask_responses = false;
+        let mut body = json!({"messages": [{"content": "carol@example.com"}]});
+        assert!(scrub_body(&config, &mut body).is_none());
+        assert_eq!(body["messages"][0]["content"], "[EMAIL_1]");
+
+        config.enabled = false;
+        let mut body = json!({"messages": [{"content": "carol@example.com"}]});
+        assert!(scrub_body(&config, &mut body).is_none());
+        assert_eq!(body["messages"][0]["content"], "carol@example.com");
+    }
+
+    #[test]
+    fn test_stream_unmask_across_events() {
+        let scrubber = scrubber();
+        let mut map = TokenMap::default();
+        let mut body = json!({"messages": [{"content": "bob@example.org"}]});
+        map.mask_value(&scrubber, &mut body);
+
+        let mut unmasker = StreamUnmasker::new(Arc::new(map));
+        let mut out = Vec::new();
+        // 令牌被拆到两个事件中，且网络分片切在行中间
+        out.extend(unmasker.push(b"data: {\"choices\":[{\"delta\":{\"content\":\"Hi [EMA\"}}]}\n\ndata: {\"choices\":[{\"delta\""));
+        out.extend(unmasker.push(b":{\"content\":\"IL_1], bye [x\"}}]}\n\ndata: [DONE]\n\n"));
+        out.extend(unmasker.finish());
+        let text = String::from_utf8(out).unwrap();
+
+        assert!(text.contains("\"content\":\"Hi \""));
+        assert!(text.contains("\"content\":\"bob@example.org, bye [x\""));
+        assert!(text.ends_with("data: [DONE]\n\n"));
+    }
+}
@@ -0,0 +1,283 @@
+// 提示词预设中间件
+// 请求模型名为 `preset:<name>` 时，按预设改写请求: 替换为实际模型、前置系统提示词、覆盖温度、追加工具。
+// 位于分流 / 预检外层，后续中间件与 handler 看到的都是实际模型。
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::{json, Value};
+
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::middleware::token_budget::{detect_protocol, rewrite_model, Protocol};
+use crate::proxy::presets::{PresetStore, PresetTool, PromptPreset, PRESET_MODEL_PREFIX};
+use crate::proxy::server::AppState;
+
+/// 标注本次请求使用的预设
+pub const PRESET_HEADER: &str = "x-ag-preset";
+
+/// 预设名: 优先取 body.model，Gemini 原生路径为 `/v1beta/models/preset:<name>:<action>`
+fn preset_name(path: &str, body: &Value) -> Option<String> {
+    if let Some(model) = body.get("model").and_then(|m| m.as_str()) {
+        return model.strip_prefix(PRESET_MODEL_PREFIX).map(|s| s.to_string());
+    }
+    let rest = path.strip_prefix("/v1beta/models/")?.strip_prefix(PRESET_MODEL_PREFIX)?;
+    rest.rsplit_once(':').map(|(name, _)| name.to_string())
+}
+
+/// 工具定义 (description 为空时省略，Gemini 不接受 null)
+fn tool_definition(tool: &PresetTool, schema_key: &str) -> Value {
+    let mut def = json!({ "name": tool.name });
+    def[schema_key] = tool.parameters.clone();
+    if let Some(description) = &tool.description {
+        def["description"] = json!(description);
+    }
+    def
+}
+
+fn merge_tools(existing: Option<&mut Value>, injected: Vec<Value>, name_of: fn(&Value) -> Option<&str>) -> Value {
+    let mut tools = existing.map(Value::take).and_then(|v| match v {
+        Value::Array(a) => Some(a),
+        _ => None,
+    }).unwrap_or_default();
+    for tool in injected {
+        let name = name_of(&tool).map(|s| s.to_string());
+        if !tools.iter().any(|t| name_of(t).map(|s| s.to_string()) == name) {
+            tools.push(tool);
+        }
+    }
+    Value::Array(tools)
+}
+
+/// 在客户端系统提示词之前插入系统提示词 (档案中间件复用)
+pub(crate) fn inject_system_prompt(protocol: Protocol, path: &str, body: &mut Value, prompt: &str) {
+    match protocol {
+        Protocol::OpenAI => {
+            if path == "/v1/responses" {
+                body["instructions"] = match body.get("instructions").and_then(|i| i.as_str()) {
+                    Some(existing) if !existing.is_empty() => json!(format!("{}\n\n{}", prompt, existing)),
+                    _ => json!(prompt),
+                };
+            } else if let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) {
+                messages.insert(0, json!({ "role": "system", "content": prompt }));
+            }
+        }
+        Protocol::Claude => {
+            body["system"] = match body.get_mut("system").map(Value::take) {
+                Some(Value::String(existing)) if !existing.is_empty() => json!(format!("{}\n\n{}", prompt, existing)),
+                Some(Value::Array(mut blocks)) => {
+                    blocks.insert(0, json!({ "type": "text", "text": prompt }));
+                    Value::Array(blocks)
+                }
+                _ => json!(prompt),
+            };
+        }
+        Protocol::Gemini => {
+            let key = if body.get("system_instruction").is_some() { "system_instruction" } else { "systemInstruction" };
+            match body.get_mut(key).and_then(|s| s.get_mut("parts")).and_then(|p| p.as_array_mut()) {
+                Some(parts) => parts.insert(0, json!({ "text": prompt })),
+                None => body[key] = json!({ "parts": [{ "text": prompt }] }),
+            }
+        }
+    }
+}
+
+/// 按协议改写请求体 (Gemini 原生请求的模型在 URL 中，由调用方改写)
+fn apply_preset(protocol: Protocol, path: &str, body: &mut Value, preset: &PromptPreset) {
+    if body.get("model").is_some() {
+        body["model"] = json!(preset.model);
+    }
+    if let Some(prompt) = &preset.system_prompt {
+        inject_system_prompt(protocol, path, body, prompt);
+    }
+    match protocol {
+        Protocol::OpenAI => {
+            if let Some(t) = preset.temperature {
+                body["temperature"] = json!(t);
+            }
+            if !preset.tools.is_empty() && body.get("messages").is_some() {
+                let injected = preset
+                    .tools
+                    .iter()
+                    .map(|t| json!({ "type": "function", "function": tool_definition(t, "parameters") }))
+                    .collect();
+                body["tools"] = merge_tools(body.get_mut("tools"), injected, |t| {
+                    t.get("function").and_then(|f| f.get("name")).and_then(|n| n.as_str())
+                });
+            }
+        }
+        Protocol::Claude => {
+            if let Some(t) = preset.temperature {
+                body["temperature"] = json!(t);
+            }
+            if !preset.tools.is_empty() {
+                let injected = preset
+                    .tools
+                    .iter()
+                    .map(|t| tool_definition(t, "input_schema"))
+                    .collect();
+                body["tools"] = merge_tools(body.get_mut("tools"), injected, |t| t.get("name").and_then(|n| n.as_str()));
+            }
+        }
+        Protocol::Gemini => {
+            if let Some(t) = preset.temperature {
+                if !body.get("generationConfig").map(|g| g.is_object()).unwrap_or(false) {
+                    body["generationConfig"] = json!({});
+                }
+                body["generationConfig"]["temperature"] = json!(t);
+            }
+            if !preset.tools.is_empty() {
+                let declarations: Vec<Value> = preset
+                    .tools
+                    .iter()
+                    .map(|t| tool_definition(t, "parameters"))
+                    .collect();
+                if !body.get("tools").map(|t| t.is_array()).unwrap_or(false) {
+                    body["tools"] = json!([]);
+                }
+                let tools = body["tools"].as_array_mut().expect("tools is array");
+                let slot = tools.iter().position(|t| t.get("functionDeclarations").is_some());
+                let slot = match slot {
+                    Some(i) => i,
+                    None => {
+                        tools.push(json!({ "functionDeclarations": [] }));
+                        tools.len() - 1
+                    }
+                };
+                tools[slot]["functionDeclarations"] = merge_tools(
+                    tools[slot].get_mut("functionDeclarations"),
+                    declarations,
+                    |t| t.get("name").and_then(|n| n.as_str()),
+                );
+            }
+        }
+    }
+}
+
+fn unknown_preset(name: &str) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "error": {
+                "message": format!("Unknown preset '{}'", name),
+                "type": "invalid_request_error",
+                "code": "preset_not_found"
+            }
+        })),
+    )
+        .into_response()
+}
+
+pub async fn presets_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let Some(protocol) = detect_protocol(&path) else {
+        return next.run(request).await;
+    };
+    let in_path = path.starts_with(&format!("/v1beta/models/{}", PRESET_MODEL_PREFIX));
+    // 未定义任何预设时不缓冲请求体；路径中引用的预设仍按不存在处理
+    if PresetStore::global().is_empty() {
+        return match preset_name(&path, &Value::Null).filter(|_| in_path) {
+            Some(name) => unknown_preset(&name),
+            None => next.run(request).await,
+        };
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return payload_too_large_response(state.max_body_bytes),
+    };
+    // 请求体中不含前缀时无需解析 JSON
+    let marker = format!("\"{}", PRESET_MODEL_PREFIX);
+    if !in_path && !bytes.windows(marker.len()).any(|w| w == marker.as_bytes()) {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    }
+    let Ok(mut json) = serde_json::from_slice::<Value>(&bytes) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let Some(name) = preset_name(&path, &json) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let Some(preset) = PresetStore::global().get(&name) else {
+        tracing::warn!("[Presets] 未找到预设: {}", name);
+        return unknown_preset(&name);
+    };
+
+    apply_preset(protocol, &path, &mut json, &preset);
+    if in_path {
+        rewrite_model(&mut parts, &mut json, &preset.model);
+    }
+    tracing::info!("[Presets] 使用预设 {} -> {}", preset.name, preset.model);
+
+    let serialized = serde_json::to_vec(&json).unwrap_or_default();
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+    let mut response = next.run(Request::from_parts(parts, Body::from(serialized))).await;
+    if let Ok(v) = HeaderValue::from_str(&preset.name) {
+        response.headers_mut().insert(PRESET_HEADER, v);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review_preset() -> PromptPreset {
+        PromptPreset {
+            name: "code-review".to_string(),
+            description: None,
+            model: "gemini-2.5-pro".to_string(),
+            system_prompt: Some("You are a strict code reviewer.".to_string()),
+            temperature: Some(0.2),
+            tools: vec![PresetTool {
+                name: "read_file".to_string(),
+                description: Some("Read a file".to_string()),
+                parameters: json!({ "type": "object", "properties": { "path": { "type": "string" } } }),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_preset_name_from_body_and_gemini_path() {
+        assert_eq!(preset_name("/v1/chat/completions", &json!({ "model": "preset:code-review" })).as_deref(), Some("code-review"));
+        assert_eq!(preset_name("/v1/chat/completions", &json!({ "model": "gpt-4o" })), None);
+        assert_eq!(
+            preset_name("/v1beta/models/preset:code-review:streamGenerateContent", &json!({})).as_deref(),
+            Some("code-review")
+        );
+    }
+
+    #[test]
+    fn test_apply_preset_openai() {
+        let mut body = json!({
+            "model": "preset:code-review",
+            "temperature": 1.0,
+            "messages": [{ "role": "user", "content": "review this" }],
+            "tools": [{ "type": "function", "function": { "name": "read_file", "parameters": {} } }]
+        });
+        apply_preset(Protocol::OpenAI, "/v1/chat/completions", &mut body, &review_preset());
+        assert_eq!(body["model"], "gemini-2.5-pro");
+        assert_eq!(body["temperature"].as_f64().map(|t| (t * 10.0).round()), Some(2.0));
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"].as_array().unwrap().len(), 2);
+        // 与客户端同名的工具不重复注入
+        assert_eq!(body["tools"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_apply_preset_claude_and_gemini() {
+        let mut body = json!({ "model": "preset:code-review", "system": "Be brief.", "messages": [] });
+        apply_preset(Protocol::Claude, "/v1/messages", &mut body, &review_preset());
+        assert_eq!(body["system"], "You are a strict code reviewer.\n\nBe brief.");
+        assert_eq!(body["tools"][0]["input_schema"]["type"], "object");
+
+        let mut body = json!({ "contents": [], "tools": [{ "googleSearch": {} }] });
+        apply_preset(Protocol::Gemini, "/v1beta/models/preset:code-review:generateContent", &mut body, &review_preset());
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "You are a strict code reviewer.");
+        assert_eq!(body["tools"][1]["functionDeclarations"][0]["name"], "read_file");
+        assert!(body.get("model").is_none());
+    }
+}
@@ -0,0 +1,166 @@
+// 配置档案中间件
+// - 请求头 `X-AG-Profile: <name>`: 按档案的自定义映射改写模型、前置档案系统提示词，并在档案作用域内执行
+//   (账号调度只使用档案的账号子集)。档案中的其余设置 (端口、实验性开关等) 只能通过激活档案生效。
+// - 无请求头时使用激活的档案: 其映射已是当前配置，这里只前置系统提示词 (账号子集由调度直接读取)。
+// 位于预设外层: 档案映射的目标可以是 `preset:<name>`。
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::proxy::common::model_mapping::lookup_custom_mapping;
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::middleware::presets::inject_system_prompt;
+use crate::proxy::middleware::token_budget::{detect_protocol, rewrite_model, Protocol};
+use crate::proxy::profiles::{self, ConfigProfile, ProfileStore};
+use crate::proxy::server::AppState;
+
+/// 选用档案的请求头 (响应中回显实际使用的档案)
+pub const PROFILE_HEADER: &str = "x-ag-profile";
+
+/// 请求的模型名: 优先取 body.model，Gemini 原生路径为 `/v1beta/models/<model>:<action>`
+fn request_model(path: &str, body: &Value) -> Option<String> {
+    if let Some(model) = body.get("model").and_then(|m| m.as_str()) {
+        return Some(model.to_string());
+    }
+    let rest = path.strip_prefix("/v1beta/models/")?;
+    rest.rsplit_once(':').map(|(model, _)| model.to_string())
+}
+
+/// 按档案改写请求体，返回映射后的模型 (未命中映射时为 None)
+fn apply_profile(
+    protocol: Protocol,
+    path: &str,
+    body: &mut Value,
+    profile: &ConfigProfile,
+    map_model: bool,
+) -> Option<String> {
+    if let Some(prompt) = profile.system_prompt.as_deref().filter(|p| !p.trim().is_empty()) {
+        inject_system_prompt(protocol, path, body, prompt);
+    }
+    if !map_model {
+        return None;
+    }
+    let model = request_model(path, body)?;
+    lookup_custom_mapping(&model, &profile.proxy.custom_mapping).filter(|target| *target != model)
+}
+
+fn unknown_profile(name: &str) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "error": {
+                "message": format!("Unknown profile '{}'", name),
+                "type": "invalid_request_error",
+                "code": "profile_not_found"
+            }
+        })),
+    )
+        .into_response()
+}
+
+pub async fn profile_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let requested = request
+        .headers()
+        .get(PROFILE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    let from_header = requested.is_some();
+    let profile = match requested {
+        Some(name) => match ProfileStore::global().get(&name) {
+            Some(p) => p,
+            None => {
+                tracing::warn!("[Profiles] 未找到档案: {}", name);
+                return unknown_profile(&name);
+            }
+        },
+        None => match ProfileStore::global().active() {
+            // 激活档案没有系统提示词时无需改写请求
+            Some(p) if p.system_prompt.as_deref().is_some_and(|s| !s.trim().is_empty()) => p,
+            _ => return next.run(request).await,
+        },
+    };
+    let profile = Arc::new(profile);
+
+    let path = request.uri().path().to_string();
+    let request = match detect_protocol(&path) {
+        Some(protocol) => {
+            let (mut parts, body) = request.into_parts();
+            let bytes = match to_bytes(body, state.max_body_bytes).await {
+                Ok(b) => b,
+                Err(_) => return payload_too_large_response(state.max_body_bytes),
+            };
+            match serde_json::from_slice::<Value>(&bytes) {
+                Ok(mut json) => {
+                    if let Some(target) = apply_profile(protocol, &path, &mut json, &profile, from_header) {
+                        tracing::info!("[Profiles] 档案 {} 映射模型 -> {}", profile.name, target);
+                        rewrite_model(&mut parts, &mut json, &target);
+                    }
+                    let serialized = serde_json::to_vec(&json).unwrap_or_default();
+                    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+                    Request::from_parts(parts, Body::from(serialized))
+                }
+                Err(_) => Request::from_parts(parts, Body::from(bytes)),
+            }
+        }
+        None => request,
+    };
+
+    if !from_header {
+        return next.run(request).await;
+    }
+    let name = profile.name.clone();
+    let mut response = profiles::scope(profile, next.run(request)).await;
+    if let Ok(v) = HeaderValue::from_str(&name) {
+        response.headers_mut().insert(PROFILE_HEADER, v);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat_profile() -> ConfigProfile {
+        let mut proxy = crate::proxy::ProxyConfig::default();
+        proxy.custom_mapping.insert("gpt-4o".to_string(), "gemini-2.5-flash".to_string());
+        proxy.custom_mapping.insert("claude-*".to_string(), "gemini-2.5-pro".to_string());
+        ConfigProfile {
+            name: "clean-chat".to_string(),
+            description: None,
+            proxy,
+            account_ids: vec![],
+            system_prompt: Some("Answer conversationally.".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_apply_profile_maps_model_and_prompt() {
+        let mut body = json!({ "model": "gpt-4o", "messages": [{ "role": "user", "content": "hi" }] });
+        let target = apply_profile(Protocol::OpenAI, "/v1/chat/completions", &mut body, &chat_profile(), true);
+        assert_eq!(target.as_deref(), Some("gemini-2.5-flash"));
+        assert_eq!(body["messages"][0]["content"], "Answer conversationally.");
+
+        let mut body = json!({ "contents": [] });
+        let target = apply_profile(
+            Protocol::Gemini,
+            "/v1beta/models/claude-sonnet-4-5:generateContent",
+            &mut body,
+            &chat_profile(),
+            true,
+        );
+        assert_eq!(target.as_deref(), Some("gemini-2.5-pro"));
+
+        // 激活档案: 映射已是当前配置，只注入提示词
+        let mut body = json!({ "model": "gpt-4o", "system": "Be brief.", "messages": [] });
+        assert!(apply_profile(Protocol::Claude, "/v1/messages", &mut body, &chat_profile(), false).is_none());
+        assert_eq!(body["system"], "Answer conversationally.\n\nBe brief.");
+    }
+}
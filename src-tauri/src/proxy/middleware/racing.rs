@@ -0,0 +1,314 @@
+// 竞速模式中间件
+// 命中竞速规则的请求同时发往多个不同账号 (每一路固定一个账号)，采用最先输出 token 的一路，其余请求立即取消。
+// 非流式请求按最先成功完成的一路。用配额换延迟，适合高峰期上游排队严重时的交互式调用。
+// 各路在当前任务内并发轮询 (不 spawn)，请求级的 task_local (延迟预算、档案、在途登记) 对每一路都生效。
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use axum::{
+    body::{to_bytes, Body, BodyDataStream, Bytes},
+    extract::{Request, State},
+    http::{header, request::Parts, response, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::{stream::FuturesUnordered, StreamExt};
+use serde_json::{json, Value};
+
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::config::RaceRule;
+use crate::proxy::middleware::best_of::clone_parts;
+use crate::proxy::middleware::latency_budget::is_streaming;
+use crate::proxy::middleware::token_budget::{detect_protocol, extract_model, resolve_target_model};
+use crate::proxy::server::AppState;
+use crate::proxy::token_manager::{pin_account, pinned_account};
+
+/// 标注竞速结果的响应头: `<胜出序号>/<总路数>`
+pub const RACE_HEADER: &str = "x-ag-race";
+
+/// 单条规则最多同时发起的账号数
+const MAX_RACE_ACCOUNTS: usize = 4;
+
+/// 各次竞速的账号起点轮转，避免每次都压在同几个账号上
+static ROTATION: AtomicUsize = AtomicUsize::new(0);
+
+/// 按顺序匹配第一条启用的规则 (请求模型名或映射后的目标模型)
+fn match_rule<'a>(rules: &'a [RaceRule], model: &str, target_model: &str) -> Option<&'a RaceRule> {
+    rules
+        .iter()
+        .filter(|rule| rule.enabled && !rule.alias.is_empty())
+        .find(|rule| wildcard_match(&rule.alias, model) || wildcard_match(&rule.alias, target_model))
+}
+
+/// 从可调度账号中轮转选出 count 个不同账号；不足两个时不竞速
+fn pick_accounts(pool: &[(String, String)], count: usize, offset: usize) -> Vec<(String, String)> {
+    let count = count.clamp(2, MAX_RACE_ACCOUNTS).min(pool.len());
+    if count < 2 {
+        return Vec::new();
+    }
+    (0..count).map(|i| pool[(offset + i) % pool.len()].clone()).collect()
+}
+
+fn non_empty_str(value: Option<&Value>) -> bool {
+    value.and_then(|v| v.as_str()).is_some_and(|s| !s.is_empty())
+}
+
+/// SSE 事件是否已包含模型输出 (正文、思考、工具调用)
+fn event_has_output(event: &Value) -> bool {
+    // OpenAI chat / legacy completions
+    if let Some(choices) = event.get("choices").and_then(|c| c.as_array()) {
+        return choices.iter().any(|choice| {
+            let delta = choice.get("delta").unwrap_or(&Value::Null);
+            non_empty_str(delta.get("content"))
+                || non_empty_str(delta.get("reasoning_content"))
+                || delta.get("tool_calls").is_some_and(|t| !t.is_null())
+                || non_empty_str(choice.get("text"))
+        });
+    }
+    match event.get("type").and_then(|t| t.as_str()) {
+        // Claude
+        Some("content_block_delta") => return true,
+        Some("content_block_start") => {
+            return event.pointer("/content_block/type").and_then(|t| t.as_str()) == Some("tool_use")
+        }
+        // Responses API (output_text / reasoning / function_call_arguments 的增量)
+        Some(kind) if kind.starts_with("response.") => return kind.ends_with(".delta"),
+        _ => {}
+    }
+    // Gemini (v1internal 响应包在 response 字段内)
+    let inner = event.get("response").unwrap_or(event);
+    inner
+        .get("candidates")
+        .and_then(|c| c.as_array())
+        .is_some_and(|candidates| {
+            candidates.iter().any(|candidate| {
+                candidate
+                    .pointer("/content/parts")
+                    .and_then(|p| p.as_array())
+                    .is_some_and(|parts| {
+                        parts.iter().any(|part| non_empty_str(part.get("text")) || part.get("functionCall").is_some())
+                    })
+            })
+        })
+}
+
+/// 按行扫描 SSE 字节流，遇到第一条带输出的事件即返回 true (分片可能在行中间截断)
+#[derive(Default)]
+struct OutputDetector {
+    line_buf: Vec<u8>,
+}
+
+impl OutputDetector {
+    fn push(&mut self, bytes: &[u8]) -> bool {
+        self.line_buf.extend_from_slice(bytes);
+        while let Some(pos) = self.line_buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.line_buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(payload) = line.trim().strip_prefix("data:") else { continue };
+            if serde_json::from_str::<Value>(payload.trim()).is_ok_and(|event| event_has_output(&event)) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+enum Leg {
+    /// 已输出 token (或非流式成功完成): 已读取的分片 + 剩余流
+    Output {
+        parts: response::Parts,
+        prefix: Vec<Bytes>,
+        rest: Option<BodyDataStream>,
+    },
+    /// 成功状态码但流结束时没有任何输出 (如流内错误事件)，仅在其他各路都失败时采用
+    Empty(Response),
+    Failed(Response),
+}
+
+/// 固定账号执行一路请求，流式响应读到第一个输出 token 为止
+async fn run_leg(next: Next, parts: Parts, bytes: Bytes, account_id: String) -> Leg {
+    pin_account(account_id, async move {
+        let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+        if !response.status().is_success() {
+            return Leg::Failed(response);
+        }
+        let is_sse = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        let (parts, body) = response.into_parts();
+        if !is_sse {
+            return Leg::Output { parts, prefix: Vec::new(), rest: Some(body.into_data_stream()) };
+        }
+        let mut stream = body.into_data_stream();
+        let mut detector = OutputDetector::default();
+        let mut prefix = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(bytes) => {
+                    let hit = detector.push(&bytes);
+                    prefix.push(bytes);
+                    if hit {
+                        return Leg::Output { parts, prefix, rest: Some(stream) };
+                    }
+                }
+                Err(e) => {
+                    return Leg::Failed(
+                        (StatusCode::BAD_GATEWAY, axum::Json(json!({ "error": { "message": format!("Race leg stream failed: {}", e) } })))
+                            .into_response(),
+                    )
+                }
+            }
+        }
+        let chunks = prefix.into_iter().map(Ok::<_, axum::Error>);
+        Leg::Empty(Response::from_parts(parts, Body::from_stream(futures::stream::iter(chunks))))
+    })
+    .await
+}
+
+fn with_race_header(mut response: Response, winner: usize, total: usize) -> Response {
+    if let Ok(v) = HeaderValue::from_str(&format!("{}/{}", winner, total)) {
+        response.headers_mut().insert(RACE_HEADER, v);
+    }
+    response
+}
+
+pub async fn racing_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let Some(protocol) = detect_protocol(&path) else {
+        return next.run(request).await;
+    };
+    let config = state.racing.read().await.clone();
+    // 客户端已固定账号时不竞速
+    if !config.enabled || config.rules.is_empty() || pinned_account().is_some() {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return payload_too_large_response(state.max_body_bytes),
+    };
+    let Some((model, streaming)) = serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .and_then(|json| Some((extract_model(&path, &json)?, is_streaming(protocol, &path, &json))))
+    else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let target_model = resolve_target_model(&model, &*state.custom_mapping.read().await);
+    let Some(rule) = match_rule(&config.rules, &model, &target_model) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let accounts = if rule.streaming_only && !streaming {
+        Vec::new()
+    } else {
+        let pool = state.token_manager.schedulable_accounts();
+        pick_accounts(&pool, rule.accounts as usize, ROTATION.fetch_add(1, Ordering::Relaxed))
+    };
+    if accounts.is_empty() {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    }
+
+    let total = accounts.len();
+    let started = Instant::now();
+    let mut legs: FuturesUnordered<_> = accounts
+        .iter()
+        .enumerate()
+        .map(|(index, (account_id, _))| {
+            let leg = run_leg(next.clone(), clone_parts(&parts), bytes.clone(), account_id.clone());
+            async move { (index, leg.await) }
+        })
+        .collect();
+
+    let mut empty = None;
+    let mut failed = None;
+    while let Some((index, leg)) = legs.next().await {
+        match leg {
+            Leg::Output { parts, prefix, rest } => {
+                // 丢弃其余各路的 future 即取消其上游请求
+                drop(legs);
+                tracing::info!(
+                    "[Racing] {} {} 路竞速，#{} ({}) 胜出，{}ms",
+                    target_model,
+                    total,
+                    index,
+                    accounts[index].1,
+                    started.elapsed().as_millis()
+                );
+                let prefix = futures::stream::iter(prefix.into_iter().map(Ok::<_, axum::Error>));
+                let body = match rest {
+                    Some(rest) => Body::from_stream(prefix.chain(rest)),
+                    None => Body::from_stream(prefix),
+                };
+                return with_race_header(Response::from_parts(parts, body), index, total);
+            }
+            Leg::Empty(response) => {
+                empty.get_or_insert((index, response));
+            }
+            Leg::Failed(response) => {
+                tracing::debug!("[Racing] #{} ({}) 失败: {}", index, accounts[index].1, response.status());
+                failed.get_or_insert((index, response));
+            }
+        }
+    }
+    tracing::warn!("[Racing] {} {} 路竞速均未输出", target_model, total);
+    match empty.or(failed) {
+        Some((index, response)) => with_race_header(response, index, total),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "No race leg completed").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(alias: &str, enabled: bool) -> RaceRule {
+        RaceRule { enabled, alias: alias.to_string(), accounts: 2, streaming_only: false }
+    }
+
+    #[test]
+    fn test_match_rule() {
+        let rules = vec![rule("gemini-3-pro*", false), rule("fast-*", true), rule("gemini-3-*", true)];
+        assert_eq!(match_rule(&rules, "fast-chat", "gemini-2.5-flash").map(|r| r.alias.as_str()), Some("fast-*"));
+        // 禁用的规则跳过，映射后的目标模型同样可以命中
+        assert_eq!(match_rule(&rules, "my-alias", "gemini-3-pro-high").map(|r| r.alias.as_str()), Some("gemini-3-*"));
+        assert!(match_rule(&rules, "claude-sonnet-4-5", "claude-sonnet-4-5").is_none());
+    }
+
+    #[test]
+    fn test_pick_accounts_rotates_distinct() {
+        let pool: Vec<(String, String)> = (0..3).map(|i| (format!("id{}", i), format!("a{}@x.com", i))).collect();
+        let ids = |picked: Vec<(String, String)>| picked.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(ids(pick_accounts(&pool, 2, 0)), vec!["id0", "id1"]);
+        assert_eq!(ids(pick_accounts(&pool, 2, 2)), vec!["id2", "id0"]);
+        // 超出账号数时取全部，超过上限时截断
+        assert_eq!(pick_accounts(&pool, 10, 0).len(), 3);
+        assert!(pick_accounts(&pool[..1], 2, 0).is_empty());
+    }
+
+    #[test]
+    fn test_output_detection_across_protocols() {
+        // OpenAI: 只有 role 的首个分片不算输出
+        assert!(!event_has_output(&json!({"choices": [{"delta": {"role": "assistant"}}]})));
+        assert!(event_has_output(&json!({"choices": [{"delta": {"content": "Hi"}}]})));
+        assert!(event_has_output(&json!({"choices": [{"delta": {"tool_calls": [{"index": 0}]}}]})));
+        // Claude
+        assert!(!event_has_output(&json!({"type": "message_start", "message": {}})));
+        assert!(!event_has_output(&json!({"type": "content_block_start", "content_block": {"type": "text", "text": ""}})));
+        assert!(event_has_output(&json!({"type": "content_block_delta", "delta": {"type": "text_delta", "text": "Hi"}})));
+        // Gemini / Responses
+        assert!(event_has_output(&json!({"response": {"candidates": [{"content": {"parts": [{"text": "Hi"}]}}]}})));
+        assert!(!event_has_output(&json!({"candidates": [{"content": {"parts": []}}], "usageMetadata": {}})));
+        assert!(event_has_output(&json!({"type": "response.output_text.delta", "delta": "Hi"})));
+        assert!(!event_has_output(&json!({"type": "response.created"})));
+
+        // 跨分片的 SSE 行
+        let mut detector = OutputDetector::default();
+        assert!(!detector.push(b": ping\n\ndata: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n"));
+        assert!(!detector.push(b"data: {\"choices\":[{\"delta\":{\"con"));
+        assert!(detector.push(b"tent\":\"Hi\"}}]}\n\n"));
+    }
+}
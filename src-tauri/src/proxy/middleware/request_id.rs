@@ -0,0 +1,180 @@
+// 请求 ID / 取消中间件
+// 为生成类请求分配 X-AG-Request-Id (客户端可自带) 并登记到在途请求表；
+// 收到取消指令时丢弃内层 future 与上游响应流，reqwest 连接随之断开，上游停止生成。
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::StreamExt;
+use serde_json::json;
+use tracing::Instrument;
+
+use crate::proxy::inflight::{generate_request_id, sanitize_client_id, InflightPhase, REQUEST_ID_HEADER};
+use crate::proxy::latency::LatencyTracker;
+use crate::proxy::middleware::conversation::CONVERSATION_ID_HEADER;
+use crate::proxy::middleware::token_budget::{detect_protocol, request_api_key};
+use crate::proxy::server::AppState;
+use crate::proxy::telemetry;
+
+/// 非标准状态码 499 (Client Closed Request)，与 nginx 语义一致
+fn cancelled_response(id: &str) -> Response {
+    let status = StatusCode::from_u16(499).unwrap_or(StatusCode::BAD_REQUEST);
+    (
+        status,
+        Json(json!({
+            "error": {
+                "message": format!("Request {} was cancelled", id),
+                "type": "request_cancelled",
+                "code": "request_cancelled"
+            }
+        })),
+    )
+        .into_response()
+}
+
+pub async fn request_id_middleware(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    if detect_protocol(&path).is_none() {
+        return next.run(request).await;
+    }
+
+    let headers = request.headers();
+    let conversation_id = headers
+        .get(CONVERSATION_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    let client_id = headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(sanitize_client_id);
+    let method = request.method().to_string();
+    let client_key = request_api_key(&request);
+    let request_bytes = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let traceparent = headers
+        .get("traceparent")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+
+    // 客户端自带的 ID 与在途请求冲突时改用生成的 ID
+    let (id, token, guard) = match client_id
+        .and_then(|id| state.inflight.register(&id, &method, &path, conversation_id.clone(), client_key.clone()).map(|(t, g)| (id, t, g)))
+    {
+        Some(registered) => registered,
+        None => loop {
+            let id = generate_request_id();
+            if let Some((t, g)) = state.inflight.register(&id, &method, &path, conversation_id.clone(), client_key.clone()) {
+                break (id, t, g);
+            }
+        },
+    };
+    if let Some(bytes) = request_bytes {
+        state.inflight.set_request_bytes(&id, bytes);
+    }
+    let started = std::time::Instant::now();
+    let header_value = HeaderValue::from_str(&id).unwrap_or_else(|_| HeaderValue::from_static("invalid"));
+    request.headers_mut().insert(REQUEST_ID_HEADER, header_value.clone());
+
+    // 链路根 span (未启用链路导出时为空 span)，随响应流结束而关闭
+    let root_span = tracing::info_span!(
+        target: telemetry::TARGET,
+        "request",
+        otel.kind = "server",
+        otel.name = %format!("{} {}", method, path),
+        otel.status_code = tracing::field::Empty,
+        otel.status_message = tracing::field::Empty,
+        traceparent = %traceparent,
+        http.method = %method,
+        http.route = %path,
+        http.status_code = tracing::field::Empty,
+        ag.request_id = %id,
+        ag.account = tracing::field::Empty,
+        ag.model = tracing::field::Empty,
+    );
+
+    let mut response = tokio::select! {
+        response = crate::proxy::inflight::scope(id.clone(), next.run(request)).instrument(root_span.clone()) => response,
+        _ = token.cancelled() => {
+            root_span.record("otel.status_code", "error");
+            root_span.record("otel.status_message", "request cancelled");
+            return cancelled_response(&id);
+        }
+    };
+    state.inflight.set_phase(&id, InflightPhase::Streaming);
+    let status = response.status().as_u16();
+    root_span.record("http.status_code", status);
+    if status >= 500 {
+        root_span.record("otel.status_code", "error");
+    }
+    let registry = state.inflight.clone();
+    response.headers_mut().insert(REQUEST_ID_HEADER, header_value);
+
+    // 流式响应: 取消后停止拉取上游数据并结束下游流
+    let is_sse = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.contains("text/event-stream"))
+        .unwrap_or(false)
+        && !response.headers().contains_key(header::CONTENT_ENCODING);
+    let (parts, body) = response.into_parts();
+    let mut stream = body.into_data_stream();
+    let output = async_stream::stream! {
+        let _guard = guard;
+        let mut first_byte_ms = None;
+        let mut cancelled = false;
+        loop {
+            tokio::select! {
+                chunk = stream.next() => match chunk {
+                    Some(chunk) => {
+                        first_byte_ms.get_or_insert_with(|| started.elapsed().as_millis() as u64);
+                        yield chunk
+                    }
+                    None => break,
+                },
+                _ = token.cancelled() => {
+                    cancelled = true;
+                    tracing::info!("[Inflight] 请求 {} 的响应流已中止", id);
+                    if is_sse {
+                        yield Ok(bytes::Bytes::from(": request cancelled\n\n"));
+                    }
+                    break;
+                }
+            }
+        }
+        // 请求完成事件 (响应流结束时输出，含总耗时；JSON 日志模式下便于按字段聚合)
+        if let Some(ctx) = registry.log_context(&id) {
+            root_span.record("ag.account", ctx.account.as_deref().unwrap_or(""));
+            root_span.record("ag.model", ctx.model.as_deref().unwrap_or(""));
+            tracing::info!(
+                target: "access",
+                request_id = %ctx.request_id,
+                account = ctx.account.as_deref().unwrap_or(""),
+                model = ctx.model.as_deref().unwrap_or(""),
+                status,
+                latency_ms = ctx.latency_ms,
+                "[Access] {} {} {} ({}ms)",
+                method,
+                path,
+                status,
+                ctx.latency_ms
+            );
+            // 成功请求计入延迟统计 (延迟预算调度的依据)
+            if let (false, true, Some(account), Some(model)) =
+                (cancelled, (200..300).contains(&status), ctx.account.as_deref(), ctx.model.as_deref())
+            {
+                let total_ms = started.elapsed().as_millis() as u64;
+                LatencyTracker::global().record(account, model, first_byte_ms.unwrap_or(total_ms), total_ms);
+            }
+        }
+    };
+    Response::from_parts(parts, Body::from_stream(output))
+}
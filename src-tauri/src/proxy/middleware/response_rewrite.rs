@@ -0,0 +1,392 @@
+// 响应后处理规则
+// 按 API Key / 客户端模型名匹配的改写规则 (正则替换、删除套话、去掉包裹 JSON 的代码围栏、统一换行)，
+// 工作在 Gemini 响应层面，流式与非流式输出一致生效，对各协议 mapper 透明。
+// 流式输出按整行放行后再改写，正则无法跨行匹配。
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::config::{ResponseRewriteAction, ResponseRewriteRule};
+use crate::proxy::middleware::token_budget::request_api_key;
+use crate::proxy::server::AppState;
+use crate::proxy::upstream::resume::GeminiByteStream;
+use crate::proxy::upstream::sse_rewrite::{candidate_mut, rewrite_sse_stream, SseRewriter};
+
+/// 单行过长时不再等待换行，直接放行 (字节)
+const MAX_PENDING_BYTES: usize = 512;
+
+tokio::task_local! {
+    /// 当前请求的 API Key (规则按 Key 匹配)
+    static REQUEST_KEY: Option<String>;
+}
+
+pub async fn response_rewrite_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let has_rules = !state.experimental.read().await.response_rewrites.is_empty();
+    let api_key = if has_rules { request_api_key(&request) } else { None };
+    REQUEST_KEY.scope(api_key, next.run(request)).await
+}
+
+#[derive(Debug, Clone)]
+enum TextRule {
+    Replace(Regex, String),
+    Newlines,
+}
+
+/// 当前请求生效的规则
+#[derive(Debug, Clone, Default)]
+pub struct CompiledRewrites {
+    rules: Vec<TextRule>,
+    strip_fences: bool,
+}
+
+impl CompiledRewrites {
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && !self.strip_fences
+    }
+
+    fn apply_rules(&self, text: &str) -> String {
+        let mut out = text.to_string();
+        for rule in &self.rules {
+            out = match rule {
+                TextRule::Replace(re, replacement) => re.replace_all(&out, replacement.as_str()).into_owned(),
+                TextRule::Newlines => out.replace("\r\n", "\n").replace('\r', "\n"),
+            };
+        }
+        out
+    }
+
+    /// 改写完整文本 (非流式)
+    pub fn rewrite_text(&self, text: &str) -> String {
+        let text = if self.strip_fences { strip_json_fences(text) } else { text.to_string() };
+        self.apply_rules(&text)
+    }
+}
+
+fn rule_applies(rule: &ResponseRewriteRule, api_key: Option<&str>, model: &str) -> bool {
+    rule.enabled
+        && (rule.api_keys.is_empty() || api_key.is_some_and(|k| rule.api_keys.iter().any(|r| r == k)))
+        && (rule.models.is_empty() || rule.models.iter().any(|p| wildcard_match(p, model)))
+}
+
+pub fn compile(rules: &[ResponseRewriteRule], api_key: Option<&str>, model: &str) -> CompiledRewrites {
+    let mut compiled = CompiledRewrites::default();
+    for rule in rules.iter().filter(|r| rule_applies(r, api_key, model)) {
+        let text_rule = match rule.action {
+            ResponseRewriteAction::RegexReplace if !rule.pattern.is_empty() => Regex::new(&rule.pattern)
+                .map(|re| TextRule::Replace(re, rule.replacement.clone())),
+            ResponseRewriteAction::StripPhrase if !rule.pattern.trim().is_empty() => {
+                Regex::new(&format!(r"(?i){}[,.:;!]?[ \t]*", regex::escape(rule.pattern.trim())))
+                    .map(|re| TextRule::Replace(re, String::new()))
+            }
+            ResponseRewriteAction::StripJsonFences => {
+                compiled.strip_fences = true;
+                continue;
+            }
+            ResponseRewriteAction::NormalizeNewlines => Ok(TextRule::Newlines),
+            _ => continue,
+        };
+        match text_rule {
+            Ok(r) => compiled.rules.push(r),
+            Err(e) => tracing::warn!("[Response-Rewrite] 规则 '{}' 正则无效，已跳过: {}", rule.name, e),
+        }
+    }
+    compiled
+}
+
+/// 当前请求 (API Key) 与客户端模型名对应的规则
+pub async fn rewrites_for(state: &AppState, model: &str) -> CompiledRewrites {
+    let exp = state.experimental.read().await;
+    if exp.response_rewrites.is_empty() {
+        return CompiledRewrites::default();
+    }
+    let api_key = REQUEST_KEY.try_with(|k| k.clone()).ok().flatten();
+    compile(&exp.response_rewrites, api_key.as_deref(), model)
+}
+
+fn is_open_fence(line: &str) -> bool {
+    matches!(line.trim(), "```" | "```json" | "```JSON")
+}
+
+/// 整段文本被 ```json ... ``` 包裹且内容像 JSON 时去掉围栏
+fn strip_json_fences(text: &str) -> String {
+    let trimmed = text.trim();
+    let Some((first, rest)) = trimmed.split_once('\n') else {
+        return text.to_string();
+    };
+    if !is_open_fence(first) {
+        return text.to_string();
+    }
+    let Some(inner) = rest.trim_end().strip_suffix("```") else {
+        return text.to_string();
+    };
+    let inner = inner.trim();
+    if inner.starts_with('{') || inner.starts_with('[') {
+        inner.to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// 流式增量改写: 按整行放行；开头的围栏行直接丢弃，疑似结尾的围栏行暂存到确认是结尾为止
+pub struct RewriteScanner {
+    rewrites: CompiledRewrites,
+    pending: String,
+    held_fence: String,
+    started: bool,
+    in_fence: bool,
+}
+
+impl RewriteScanner {
+    pub fn new(rewrites: CompiledRewrites) -> Self {
+        Self {
+            rewrites,
+            pending: String::new(),
+            held_fence: String::new(),
+            started: false,
+            in_fence: false,
+        }
+    }
+
+    pub fn push(&mut self, text: &str) -> String {
+        self.pending.push_str(text);
+        let split = match self.pending.rfind('\n') {
+            Some(pos) => pos + 1,
+            None if self.pending.len() > MAX_PENDING_BYTES => self.pending.len(),
+            None => return String::new(),
+        };
+        let rest = self.pending.split_off(split);
+        let ready = std::mem::replace(&mut self.pending, rest);
+        let released = self.release(&ready);
+        self.rewrites.apply_rules(&released)
+    }
+
+    /// 响应结束: 放行剩余文本，丢弃结尾围栏
+    pub fn flush(&mut self) -> String {
+        let rest = std::mem::take(&mut self.pending);
+        let mut released = self.release(&rest);
+        let held = std::mem::take(&mut self.held_fence);
+        if !self.in_fence {
+            released.push_str(&held);
+        }
+        self.rewrites.apply_rules(&released)
+    }
+
+    fn release(&mut self, chunk: &str) -> String {
+        if !self.rewrites.strip_fences {
+            return chunk.to_string();
+        }
+        let mut out = String::with_capacity(chunk.len());
+        for line in chunk.split_inclusive('\n') {
+            let trimmed = line.trim();
+            if !self.started {
+                if !trimmed.is_empty() {
+                    self.started = true;
+                    if is_open_fence(trimmed) {
+                        self.in_fence = true;
+                        continue;
+                    }
+                }
+            } else if self.in_fence && (trimmed == "```" || (!self.held_fence.is_empty() && trimmed.is_empty())) {
+                self.held_fence.push_str(line);
+                continue;
+            } else if !self.held_fence.is_empty() {
+                out.push_str(&std::mem::take(&mut self.held_fence));
+            }
+            out.push_str(line);
+        }
+        out
+    }
+}
+
+/// 改写单个响应 (流式分块或完整响应) 中的正文；思维内容原样保留，工具调用前先放行暂存文本
+fn rewrite_candidate(value: &mut Value, scanner: &mut RewriteScanner, finished: bool) {
+    let Some(candidate) = candidate_mut(value) else {
+        return;
+    };
+    let finished = finished || candidate.get("finishReason").is_some();
+    let Some(parts) = candidate
+        .get_mut("content")
+        .and_then(|c| c.get_mut("parts"))
+        .and_then(|p| p.as_array_mut())
+    else {
+        return;
+    };
+    let mut kept = Vec::with_capacity(parts.len());
+    for mut part in parts.drain(..) {
+        let is_thought = part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false);
+        match part.get("text").and_then(|t| t.as_str()) {
+            Some(_) if is_thought => kept.push(part),
+            Some(text) => {
+                let out = scanner.push(text);
+                if !out.is_empty() {
+                    part["text"] = Value::String(out);
+                    kept.push(part);
+                }
+            }
+            None => {
+                if !is_thought {
+                    let rest = scanner.flush();
+                    if !rest.is_empty() {
+                        kept.push(json!({ "text": rest }));
+                    }
+                }
+                kept.push(part);
+            }
+        }
+    }
+    if finished {
+        let rest = scanner.flush();
+        if !rest.is_empty() {
+            kept.push(json!({ "text": rest }));
+        }
+    }
+    *parts = kept;
+}
+
+/// 非流式响应: 逐个文本 part 整体改写
+pub fn apply_to_response(response: &mut Value, rewrites: &CompiledRewrites) {
+    if rewrites.is_empty() {
+        return;
+    }
+    let Some(parts) = candidate_mut(response)
+        .and_then(|c| c.get_mut("content"))
+        .and_then(|c| c.get_mut("parts"))
+        .and_then(|p| p.as_array_mut())
+    else {
+        return;
+    };
+    for part in parts.iter_mut() {
+        if part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false) {
+            continue;
+        }
+        if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+            part["text"] = Value::String(rewrites.rewrite_text(text));
+        }
+    }
+}
+
+struct ResponseRewriter {
+    scanner: RewriteScanner,
+    wrapped: bool,
+}
+
+impl SseRewriter for ResponseRewriter {
+    fn rewrite(&mut self, event: &mut Value) -> bool {
+        self.wrapped = event.get("response").is_some();
+        rewrite_candidate(event, &mut self.scanner, false);
+        true
+    }
+
+    fn finish(&mut self) -> Option<Value> {
+        let rest = self.scanner.flush();
+        if rest.is_empty() {
+            return None;
+        }
+        let inner = json!({ "candidates": [{ "content": { "role": "model", "parts": [{ "text": rest }] } }] });
+        Some(if self.wrapped { json!({ "response": inner }) } else { inner })
+    }
+}
+
+/// 流式响应: 无生效规则时原样返回
+pub fn apply_to_stream(stream: GeminiByteStream, rewrites: CompiledRewrites) -> GeminiByteStream {
+    if rewrites.is_empty() {
+        return stream;
+    }
+    rewrite_sse_stream(
+        stream,
+        ResponseRewriter {
+            scanner: RewriteScanner::new(rewrites),
+            wrapped: false,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(action: ResponseRewriteAction, pattern: &str, replacement: &str) -> ResponseRewriteRule {
+        ResponseRewriteRule {
+            enabled: true,
+            name: String::new(),
+            action,
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            api_keys: Vec::new(),
+            models: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_compile_filters_by_key_and_model() {
+        let mut scoped = rule(ResponseRewriteAction::NormalizeNewlines, "", "");
+        scoped.api_keys = vec!["sk-a".to_string()];
+        scoped.models = vec!["gpt-4*".to_string()];
+        let rules = vec![scoped, rule(ResponseRewriteAction::RegexReplace, "(", "")];
+
+        assert_eq!(compile(&rules, Some("sk-a"), "gpt-4o").rules.len(), 1);
+        assert!(compile(&rules, Some("sk-b"), "gpt-4o").is_empty());
+        assert!(compile(&rules, None, "gpt-4o").is_empty());
+        assert!(compile(&rules, Some("sk-a"), "claude-sonnet-4-5").is_empty());
+    }
+
+    #[test]
+    fn test_rewrite_text_rules() {
+        let rewrites = compile(
+            &[
+                rule(ResponseRewriteAction::StripPhrase, "As a large language model", ""),
+                rule(ResponseRewriteAction::RegexReplace, r"\bcolour\b", "color"),
+                rule(ResponseRewriteAction::NormalizeNewlines, "", ""),
+                rule(ResponseRewriteAction::StripJsonFences, "", ""),
+            ],
+            None,
+            "any",
+        );
+        assert_eq!(
+            rewrites.rewrite_text("as a large language model, I like this colour.\r\nOK"),
+            "I like this color.\nOK"
+        );
+        assert_eq!(rewrites.rewrite_text("```json\n{\"a\": 1}\n```\n"), "{\"a\": 1}");
+        assert_eq!(rewrites.rewrite_text("```python\nprint(1)\n```"), "```python\nprint(1)\n```");
+    }
+
+    #[test]
+    fn test_stream_strips_fences_across_chunks() {
+        let rewrites = compile(&[rule(ResponseRewriteAction::StripJsonFences, "", "")], None, "any");
+        let mut scanner = RewriteScanner::new(rewrites);
+        let mut out = String::new();
+        for chunk in ["``", "`json\n{\"a\":", " [1, 2]}\n`", "``\n"] {
+            out.push_str(&scanner.push(chunk));
+        }
+        out.push_str(&scanner.flush());
+        assert_eq!(out, "{\"a\": [1, 2]}\n");
+
+        // 中间出现的围栏行后仍有内容时原样放行
+        let mut scanner = RewriteScanner::new(compile(&[rule(ResponseRewriteAction::StripJsonFences, "", "")], None, "any"));
+        let mut out = scanner.push("```\nfirst\n```\nmore\n");
+        out.push_str(&scanner.flush());
+        assert_eq!(out, "first\n```\nmore\n");
+    }
+
+    #[test]
+    fn test_stream_event_keeps_thoughts_and_flushes_before_tool_call() {
+        let rewrites = compile(&[rule(ResponseRewriteAction::RegexReplace, "secret", "***")], None, "any");
+        let mut scanner = RewriteScanner::new(rewrites);
+        let mut event = json!({ "response": { "candidates": [{ "content": { "parts": [
+            { "text": "secret plan", "thought": true },
+            { "text": "the secret is" },
+            { "functionCall": { "name": "f", "args": {} } }
+        ]}}]}});
+        rewrite_candidate(&mut event, &mut scanner, false);
+        let parts = &event["response"]["candidates"][0]["content"]["parts"];
+        assert_eq!(parts[0]["text"], "secret plan");
+        assert_eq!(parts[1]["text"], "the *** is");
+        assert!(parts[2].get("functionCall").is_some());
+    }
+}
@@ -0,0 +1,242 @@
+// 流式输出平滑 (打字机模式)
+// 上游偶尔一次性返回大段文本，部分 TTS / 打字效果前端会因此出现卡顿或音频断续。
+// 开启后按 API Key 配置的速率 (字符/秒) 把大块文本拆成小段并定时下发。
+// 工作在 Gemini SSE 层面，对 Claude / OpenAI / Gemini 各协议的 mapper 透明。
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::proxy::middleware::token_budget::request_api_key;
+use crate::proxy::server::AppState;
+use crate::proxy::upstream::resume::GeminiByteStream;
+use crate::proxy::upstream::sse_rewrite::candidate_mut;
+
+/// 每秒下发次数 (拆分粒度 = 速率 / 该值)
+const TICKS_PER_SECOND: u32 = 20;
+
+tokio::task_local! {
+    /// 当前请求生效的平滑速率 (字符/秒，0 = 关闭)
+    static PACING_CPS: u32;
+}
+
+/// 当前 Key 的速率: stream_pacing_keys 优先，其次全局 stream_pacing_cps
+fn cps_for_key(default_cps: u32, keys: &std::collections::HashMap<String, u32>, api_key: Option<&str>) -> u32 {
+    api_key.and_then(|k| keys.get(k).copied()).unwrap_or(default_cps)
+}
+
+pub async fn stream_pacing_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let cps = {
+        let exp = state.experimental.read().await;
+        if exp.stream_pacing_cps == 0 && exp.stream_pacing_keys.is_empty() {
+            0
+        } else {
+            cps_for_key(exp.stream_pacing_cps, &exp.stream_pacing_keys, request_api_key(&request).as_deref())
+        }
+    };
+    PACING_CPS.scope(cps, next.run(request)).await
+}
+
+/// 当前请求的平滑速率 (不在请求作用域内时为 0)
+pub fn current_cps() -> u32 {
+    PACING_CPS.try_with(|cps| *cps).unwrap_or(0)
+}
+
+/// 把分块中超过 `piece_chars` 的文本 part 拆成多个分块；只有最后一个分块保留 finishReason / usageMetadata
+fn split_event(mut event: Value, piece_chars: usize) -> Vec<Value> {
+    let needs_split = candidate_mut(&mut event)
+        .and_then(|c| c.get("content"))
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .map(|parts| {
+            parts.iter().any(|p| {
+                p.get("text")
+                    .and_then(|t| t.as_str())
+                    .map(|t| t.chars().count() > piece_chars)
+                    .unwrap_or(false)
+            })
+        })
+        .unwrap_or(false);
+    if !needs_split {
+        return vec![event];
+    }
+
+    // 展开为单 part 序列: 长文本拆段，前面的段只保留 text / thought，最后一段保留原 part 的其余字段
+    let parts = candidate_mut(&mut event)
+        .and_then(|c| c.get_mut("content"))
+        .and_then(|c| c.get_mut("parts"))
+        .map(Value::take)
+        .and_then(|p| match p {
+            Value::Array(a) => Some(a),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let mut sequence = Vec::new();
+    for mut part in parts {
+        let Some(text) = part.get("text").and_then(|t| t.as_str()).map(|t| t.to_string()) else {
+            sequence.push(part);
+            continue;
+        };
+        let chars: Vec<char> = text.chars().collect();
+        let mut pieces: Vec<String> = chars.chunks(piece_chars.max(1)).map(|c| c.iter().collect()).collect();
+        let last = pieces.pop().unwrap_or_default();
+        for piece in pieces {
+            let mut p = json!({ "text": piece });
+            if let Some(thought) = part.get("thought") {
+                p["thought"] = thought.clone();
+            }
+            sequence.push(p);
+        }
+        part["text"] = Value::String(last);
+        sequence.push(part);
+    }
+
+    // 中间分块模板: 去掉 finishReason 与 usageMetadata
+    let mut template = event.clone();
+    if let Some(candidate) = candidate_mut(&mut template) {
+        if let Some(obj) = candidate.as_object_mut() {
+            obj.remove("finishReason");
+        }
+    }
+    let inner = if template.get("response").is_some() {
+        &mut template["response"]
+    } else {
+        &mut template
+    };
+    if let Some(obj) = inner.as_object_mut() {
+        obj.remove("usageMetadata");
+    }
+
+    let total = sequence.len();
+    sequence
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let mut e = if i + 1 == total { event.clone() } else { template.clone() };
+            if let Some(candidate) = candidate_mut(&mut e) {
+                candidate["content"]["parts"] = json!([part]);
+            }
+            e
+        })
+        .collect()
+}
+
+fn text_chars(event: &Value) -> usize {
+    let inner = event.get("response").unwrap_or(event);
+    inner["candidates"][0]["content"]["parts"]
+        .as_array()
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .map(|t| t.chars().count())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// 按 `cps` 字符/秒平滑下发文本；cps 为 0 时原样返回
+pub fn pace_stream(stream: GeminiByteStream, cps: u32) -> GeminiByteStream {
+    if cps == 0 {
+        return stream;
+    }
+    let piece_chars = (cps / TICKS_PER_SECOND).max(1) as usize;
+    Box::pin(async_stream::stream! {
+        let mut stream = stream;
+        let mut buffer = BytesMut::new();
+        while let Some(item) = stream.next().await {
+            match item {
+                Ok(chunk) => {
+                    buffer.extend_from_slice(&chunk);
+                    let Some(pos) = buffer.iter().rposition(|b| *b == b'\n') else {
+                        continue;
+                    };
+                    let complete = buffer.split_to(pos + 1).freeze();
+                    let mut passthrough = String::new();
+                    for line in String::from_utf8_lossy(&complete).split_inclusive('\n') {
+                        let parsed = line
+                            .trim()
+                            .strip_prefix("data:")
+                            .and_then(|data| serde_json::from_str::<Value>(data.trim()).ok());
+                        let Some(event) = parsed else {
+                            // 分块自带空行分隔，丢弃原始空行避免重复
+                            if !line.trim().is_empty() {
+                                passthrough.push_str(line);
+                            }
+                            continue;
+                        };
+                        if !passthrough.is_empty() {
+                            yield Ok(Bytes::from(std::mem::take(&mut passthrough)));
+                        }
+                        for piece in split_event(event, piece_chars) {
+                            let chars = text_chars(&piece);
+                            yield Ok(Bytes::from(format!("data: {}\n\n", piece)));
+                            if chars > 0 {
+                                tokio::time::sleep(Duration::from_secs_f64(chars as f64 / cps as f64)).await;
+                            }
+                        }
+                    }
+                    if !passthrough.is_empty() {
+                        yield Ok(Bytes::from(passthrough));
+                    }
+                }
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            }
+        }
+        if !buffer.is_empty() {
+            yield Ok(buffer.freeze());
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_event_keeps_finish_on_last_piece() {
+        let event = json!({ "response": {
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": "abcdefghij", "thoughtSignature": "sig" }] },
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": { "totalTokenCount": 10 }
+        }});
+        let pieces = split_event(event, 4);
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(pieces[0]["response"]["candidates"][0]["content"]["parts"][0], json!({ "text": "abcd" }));
+        assert!(pieces[0]["response"]["candidates"][0].get("finishReason").is_none());
+        assert!(pieces[0]["response"].get("usageMetadata").is_none());
+        let last = &pieces[2]["response"];
+        assert_eq!(last["candidates"][0]["content"]["parts"][0]["text"], "ij");
+        assert_eq!(last["candidates"][0]["content"]["parts"][0]["thoughtSignature"], "sig");
+        assert_eq!(last["candidates"][0]["finishReason"], "STOP");
+        assert_eq!(last["usageMetadata"]["totalTokenCount"], 10);
+    }
+
+    #[test]
+    fn test_small_events_pass_through() {
+        let event = json!({ "candidates": [{ "content": { "parts": [{ "text": "hi" }] } }] });
+        assert_eq!(split_event(event.clone(), 4), vec![event]);
+    }
+
+    #[test]
+    fn test_cps_for_key() {
+        let mut keys = std::collections::HashMap::new();
+        keys.insert("sk-tts".to_string(), 40);
+        keys.insert("sk-fast".to_string(), 0);
+        assert_eq!(cps_for_key(0, &keys, Some("sk-tts")), 40);
+        assert_eq!(cps_for_key(100, &keys, Some("sk-fast")), 0);
+        assert_eq!(cps_for_key(100, &keys, Some("sk-other")), 100);
+        assert_eq!(cps_for_key(0, &keys, None), 0);
+    }
+}
@@ -0,0 +1,247 @@
+// 流式统计 (TTFT / tokens/sec / 分块数)
+// 客户端无需在反代外面再包一层计时即可做基准测试:
+// - 流式响应: 结束时追加一行 SSE 注释 `: x-ag-stats {...}` (符合规范的 SSE 解析器会忽略注释行)
+// - 非流式响应: 附加 X-AG-TTFT-Ms / X-AG-Duration-Ms / X-AG-Output-Tokens / X-AG-Tokens-Per-Sec 响应头
+// monitor 位于本层外侧，从注释行 / 响应头读取统计写入请求历史。
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Instant;
+
+use crate::proxy::server::AppState;
+
+/// SSE 统计注释行前缀
+pub const STATS_COMMENT_PREFIX: &str = ": x-ag-stats ";
+pub const TTFT_HEADER: &str = "x-ag-ttft-ms";
+pub const DURATION_HEADER: &str = "x-ag-duration-ms";
+pub const OUTPUT_TOKENS_HEADER: &str = "x-ag-output-tokens";
+pub const TOKENS_PER_SEC_HEADER: &str = "x-ag-tokens-per-sec";
+
+const MAX_JSON_BODY: usize = 100 * 1024 * 1024;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StreamStats {
+    /// 首个 SSE 事件到达耗时 (非流式为完整响应耗时)
+    pub ttft_ms: u64,
+    pub duration_ms: u64,
+    /// SSE 事件数 (不含 [DONE])，非流式为 0
+    pub chunk_count: u32,
+    #[serde(default)]
+    pub output_tokens: Option<u32>,
+    /// 生成阶段 (首 token 之后) 的输出速率；首 token 与结束同时到达时按总耗时计算
+    #[serde(default)]
+    pub tokens_per_sec: Option<f64>,
+}
+
+impl StreamStats {
+    fn finish(ttft_ms: u64, duration_ms: u64, chunk_count: u32, output_tokens: Option<u32>) -> Self {
+        let tokens_per_sec = output_tokens.filter(|t| *t > 0).and_then(|tokens| {
+            let window = if duration_ms > ttft_ms { duration_ms - ttft_ms } else { duration_ms };
+            (window > 0).then(|| (tokens as f64 * 1000.0 / window as f64 * 10.0).round() / 10.0)
+        });
+        Self { ttft_ms, duration_ms, chunk_count, output_tokens, tokens_per_sec }
+    }
+
+    pub fn to_comment(&self) -> String {
+        format!("{}{}\n\n", STATS_COMMENT_PREFIX, serde_json::to_string(self).unwrap_or_default())
+    }
+
+    /// 从完整 SSE 文本中取出统计注释 (取最后一条)
+    pub fn from_sse(text: &str) -> Option<Self> {
+        text.lines()
+            .rev()
+            .find_map(|line| line.strip_prefix(STATS_COMMENT_PREFIX))
+            .and_then(|json| serde_json::from_str(json).ok())
+    }
+
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        let mut set = |name: &'static str, value: String| {
+            if let Ok(v) = HeaderValue::from_str(&value) {
+                headers.insert(name, v);
+            }
+        };
+        set(TTFT_HEADER, self.ttft_ms.to_string());
+        set(DURATION_HEADER, self.duration_ms.to_string());
+        if let Some(tokens) = self.output_tokens {
+            set(OUTPUT_TOKENS_HEADER, tokens.to_string());
+        }
+        if let Some(tps) = self.tokens_per_sec {
+            set(TOKENS_PER_SEC_HEADER, tps.to_string());
+        }
+    }
+
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let get = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let ttft_ms = get(TTFT_HEADER)?.parse().ok()?;
+        Some(Self {
+            ttft_ms,
+            duration_ms: get(DURATION_HEADER).and_then(|v| v.parse().ok()).unwrap_or(ttft_ms),
+            chunk_count: 0,
+            output_tokens: get(OUTPUT_TOKENS_HEADER).and_then(|v| v.parse().ok()),
+            tokens_per_sec: get(TOKENS_PER_SEC_HEADER).and_then(|v| v.parse().ok()),
+        })
+    }
+}
+
+/// 各协议的输出 token 字段: OpenAI usage.completion_tokens / Claude usage.output_tokens / Gemini usageMetadata.candidatesTokenCount
+fn output_tokens(json: &Value) -> Option<u32> {
+    let usage = json
+        .get("usage")
+        .or_else(|| json.get("usageMetadata"))
+        .or_else(|| json.get("message").and_then(|m| m.get("usage")))
+        .or_else(|| json.get("response").and_then(|r| r.get("usageMetadata")))?;
+    usage
+        .get("completion_tokens")
+        .or_else(|| usage.get("output_tokens"))
+        .or_else(|| usage.get("candidatesTokenCount"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32)
+}
+
+/// 逐行扫描 SSE (分块可能在行中间断开)，统计事件数并记录首事件时间与输出 token
+struct SseCounter {
+    pending: Vec<u8>,
+    events: u32,
+    first_event_ms: Option<u64>,
+    output_tokens: Option<u32>,
+}
+
+impl SseCounter {
+    fn new() -> Self {
+        Self { pending: Vec::new(), events: 0, first_event_ms: None, output_tokens: None }
+    }
+
+    fn feed(&mut self, chunk: &[u8], elapsed_ms: u64) {
+        self.pending.extend_from_slice(chunk);
+        while let Some(pos) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            self.line(&line, elapsed_ms);
+        }
+    }
+
+    fn line(&mut self, line: &[u8], elapsed_ms: u64) {
+        let Ok(line) = std::str::from_utf8(line) else { return };
+        let Some(data) = line.trim_end().strip_prefix("data:") else { return };
+        let data = data.trim();
+        if data.is_empty() || data == "[DONE]" {
+            return;
+        }
+        self.events += 1;
+        self.first_event_ms.get_or_insert(elapsed_ms);
+        if data.contains("usage") {
+            if let Some(tokens) = serde_json::from_str::<Value>(data).ok().as_ref().and_then(output_tokens) {
+                // Claude message_start 先给出占位值，message_delta 才是最终值
+                self.output_tokens = Some(self.output_tokens.map_or(tokens, |t| t.max(tokens)));
+            }
+        }
+    }
+}
+
+pub async fn stream_stats_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !state.experimental.read().await.stream_stats || request.method() != axum::http::Method::POST {
+        return next.run(request).await;
+    }
+
+    let start = Instant::now();
+    let mut response = next.run(request).await;
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+
+    if content_type.contains("text/event-stream") {
+        let (parts, body) = response.into_parts();
+        let mut upstream = body.into_data_stream();
+        let stream = async_stream::stream! {
+            let mut counter = SseCounter::new();
+            let mut failed = false;
+            while let Some(chunk) = upstream.next().await {
+                match chunk {
+                    Ok(data) => {
+                        counter.feed(&data, start.elapsed().as_millis() as u64);
+                        yield Ok::<_, axum::Error>(data);
+                    }
+                    Err(e) => {
+                        failed = true;
+                        yield Err(e);
+                        break;
+                    }
+                }
+            }
+            if failed {
+                return;
+            }
+            let duration_ms = start.elapsed().as_millis() as u64;
+            let stats = StreamStats::finish(
+                counter.first_event_ms.unwrap_or(duration_ms),
+                duration_ms,
+                counter.events,
+                counter.output_tokens,
+            );
+            yield Ok(Bytes::from(stats.to_comment()));
+        };
+        return Response::from_parts(parts, Body::from_stream(stream));
+    }
+
+    let duration_ms = start.elapsed().as_millis() as u64;
+    if !content_type.contains("application/json") {
+        StreamStats::finish(duration_ms, duration_ms, 0, None).write_headers(response.headers_mut());
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_JSON_BODY).await {
+        Ok(b) => b,
+        Err(_) => return Response::from_parts(parts, Body::empty()),
+    };
+    let tokens = serde_json::from_slice::<Value>(&bytes).ok().as_ref().and_then(output_tokens);
+    StreamStats::finish(duration_ms, duration_ms, 0, tokens).write_headers(&mut parts.headers);
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_handles_split_lines_and_protocols() {
+        let mut counter = SseCounter::new();
+        counter.feed(b"event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"output_tokens\":1}}}\n\n", 120);
+        counter.feed(b"data: {\"type\":\"content_block_delta\",\"delta\":{\"te", 150);
+        counter.feed(b"xt\":\"hi\"}}\n\n: ping\n\n", 160);
+        counter.feed(b"data: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":42}}\n\ndata: [DONE]\n\n", 900);
+        assert_eq!(counter.events, 3);
+        assert_eq!(counter.first_event_ms, Some(120));
+        assert_eq!(counter.output_tokens, Some(42));
+
+        let mut gemini = SseCounter::new();
+        gemini.feed(b"data: {\"candidates\":[],\"usageMetadata\":{\"candidatesTokenCount\":7}}\r\n\r\n", 5);
+        assert_eq!(gemini.output_tokens, Some(7));
+    }
+
+    #[test]
+    fn test_stats_roundtrip() {
+        let stats = StreamStats::finish(200, 1200, 10, Some(50));
+        assert_eq!(stats.tokens_per_sec, Some(50.0));
+        let sse = format!("data: {{}}\n\ndata: [DONE]\n\n{}", stats.to_comment());
+        assert_eq!(StreamStats::from_sse(&sse), Some(stats.clone()));
+
+        let mut headers = HeaderMap::new();
+        let plain = StreamStats::finish(800, 800, 0, Some(40));
+        plain.write_headers(&mut headers);
+        assert_eq!(headers.get(TOKENS_PER_SEC_HEADER).unwrap(), "50");
+        assert_eq!(StreamStats::from_headers(&headers), Some(plain));
+        assert_eq!(StreamStats::finish(0, 0, 0, Some(3)).tokens_per_sec, None);
+    }
+}
@@ -0,0 +1,259 @@
+// 流式响应落盘 (stream tee)
+// 开启后每个 SSE 响应连同其请求体实时追加到 JSONL 文件，与 SQLite 请求历史相互独立:
+// 进程崩溃或流被中途掐断时，已收到的分块也已经在磁盘上，可用于排查与回放。
+// 每行一条记录: request (请求体) -> chunk (原样的 SSE 分块) ... -> end / error。
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+use crate::proxy::server::AppState;
+
+const DEFAULT_DIR_NAME: &str = "stream_tee";
+
+/// 置位后写入任务在下一条记录前关闭全部文件句柄 (文件被清理或擦除后重新创建)
+static CLOSE_FILES: AtomicBool = AtomicBool::new(false);
+
+/// 后台写入任务: 按目录 + 日期追加行，文件句柄复用
+fn writer() -> &'static mpsc::UnboundedSender<(PathBuf, String)> {
+    static TX: OnceLock<mpsc::UnboundedSender<(PathBuf, String)>> = OnceLock::new();
+    TX.get_or_init(|| {
+        let (tx, mut rx) = mpsc::unbounded_channel::<(PathBuf, String)>();
+        tokio::spawn(async move {
+            let mut files: HashMap<PathBuf, std::fs::File> = HashMap::new();
+            while let Some((dir, line)) = rx.recv().await {
+                if CLOSE_FILES.swap(false, Ordering::SeqCst) {
+                    files.clear();
+                }
+                let path = file_path(&dir, chrono::Local::now().date_naive());
+                if !files.contains_key(&path) {
+                    // 日期切换后旧文件不再写入，直接关闭
+                    files.retain(|p, _| p.parent() != Some(dir.as_path()));
+                    if let Err(e) = std::fs::create_dir_all(&dir) {
+                        tracing::warn!("[StreamTee] 无法创建目录 {:?}: {}", dir, e);
+                        continue;
+                    }
+                    match std::fs::OpenOptions::new().create(true).append(true).open(&path) {
+                        Ok(f) => {
+                            files.insert(path.clone(), f);
+                        }
+                        Err(e) => {
+                            tracing::warn!("[StreamTee] 无法打开 {:?}: {}", path, e);
+                            continue;
+                        }
+                    }
+                }
+                if let Some(file) = files.get_mut(&path) {
+                    if let Err(e) = writeln!(file, "{}", line) {
+                        tracing::warn!("[StreamTee] 写入失败 {:?}: {}", path, e);
+                        files.remove(&path);
+                    }
+                }
+            }
+        });
+        tx
+    })
+}
+
+fn file_path(dir: &Path, date: chrono::NaiveDate) -> PathBuf {
+    dir.join(format!("stream-{}.jsonl", date.format("%Y-%m-%d")))
+}
+
+fn resolve_dir(configured: &str) -> Option<PathBuf> {
+    if !configured.trim().is_empty() {
+        return Some(PathBuf::from(configured.trim()));
+    }
+    crate::modules::account::get_data_dir().ok().map(|d| d.join(DEFAULT_DIR_NAME))
+}
+
+/// 从文件名解析日期 (仅识别 stream-YYYY-MM-DD.jsonl)
+fn file_date(path: &Path) -> Option<chrono::NaiveDate> {
+    let name = path.file_name()?.to_str()?;
+    let date = name.strip_prefix("stream-")?.strip_suffix(".jsonl")?;
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// 配置目录与默认目录中的全部落盘文件及其日期 (供保留策略与擦除使用)
+pub fn list_files(configured: &str) -> Vec<(PathBuf, chrono::NaiveDate)> {
+    let mut dirs: Vec<PathBuf> = [resolve_dir(configured), resolve_dir("")].into_iter().flatten().collect();
+    dirs.dedup();
+    let mut files = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if let Some(date) = file_date(&path) {
+                files.push((path, date));
+            }
+        }
+    }
+    files
+}
+
+/// 让写入任务关闭已打开的文件，之后的记录写入重新创建的文件
+pub fn close_files() {
+    CLOSE_FILES.store(true, Ordering::SeqCst);
+}
+
+fn now_rfc3339() -> String {
+    chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// 请求记录: 请求体能解析为 JSON 时原样嵌入，否则按字符串保存
+fn request_record(id: &str, method: &str, uri: &str, body: &[u8]) -> Value {
+    let parsed: Option<Value> = serde_json::from_slice(body).ok();
+    let model = parsed
+        .as_ref()
+        .and_then(|v| v.get("model"))
+        .and_then(|m| m.as_str())
+        .map(|s| s.to_string())
+        .or_else(|| {
+            uri.split("/v1beta/models/")
+                .nth(1)
+                .and_then(|s| s.split(':').next())
+                .map(|s| s.to_string())
+        });
+    json!({
+        "type": "request",
+        "id": id,
+        "ts": now_rfc3339(),
+        "method": method,
+        "url": uri,
+        "model": model,
+        "body": parsed.unwrap_or_else(|| Value::String(String::from_utf8_lossy(body).into_owned())),
+    })
+}
+
+fn chunk_record(id: &str, offset_ms: u128, data: &[u8]) -> Value {
+    json!({
+        "type": "chunk",
+        "id": id,
+        "ts": now_rfc3339(),
+        "offset_ms": offset_ms,
+        "data": String::from_utf8_lossy(data),
+    })
+}
+
+pub async fn stream_tee_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = state.stream_tee.read().await.clone();
+    if !config.enabled || request.method() != axum::http::Method::POST {
+        return next.run(request).await;
+    }
+    let Some(dir) = resolve_dir(&config.directory) else {
+        return next.run(request).await;
+    };
+
+    let method = request.method().to_string();
+    let uri = request.uri().to_string();
+    let (parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(e) => {
+            use crate::proxy::common::streaming_json::{is_length_limit_error, payload_too_large_response};
+            if is_length_limit_error(&e) {
+                return payload_too_large_response(state.max_body_bytes);
+            }
+            return (axum::http::StatusCode::BAD_REQUEST, format!("Failed to read request body: {}", e)).into_response();
+        }
+    };
+    let request = Request::from_parts(parts, Body::from(bytes.clone()));
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+    let is_sse = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.contains("text/event-stream"))
+        .unwrap_or(false);
+    if !is_sse {
+        return response;
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let status = response.status().as_u16();
+    let tx = writer();
+    let _ = tx.send((dir.clone(), request_record(&id, &method, &uri, &bytes).to_string()));
+
+    let (parts, body) = response.into_parts();
+    let mut upstream = body.into_data_stream();
+    let stream = async_stream::stream! {
+        let mut total_bytes: usize = 0;
+        let mut failed = false;
+        while let Some(chunk) = upstream.next().await {
+            match chunk {
+                Ok(data) => {
+                    total_bytes += data.len();
+                    let _ = tx.send((dir.clone(), chunk_record(&id, start.elapsed().as_millis(), &data).to_string()));
+                    yield Ok::<_, axum::Error>(data);
+                }
+                Err(e) => {
+                    failed = true;
+                    let _ = tx.send((dir.clone(), json!({
+                        "type": "error",
+                        "id": id,
+                        "ts": now_rfc3339(),
+                        "duration_ms": start.elapsed().as_millis(),
+                        "error": e.to_string(),
+                    }).to_string()));
+                    yield Err(e);
+                    break;
+                }
+            }
+        }
+        if !failed {
+            let _ = tx.send((dir.clone(), json!({
+                "type": "end",
+                "id": id,
+                "ts": now_rfc3339(),
+                "duration_ms": start.elapsed().as_millis(),
+                "status": status,
+                "bytes": total_bytes,
+            }).to_string()));
+        }
+    };
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_and_file_naming() {
+        let body = br#"{"model":"gemini-3-flash","stream":true,"messages":[]}"#;
+        let rec = request_record("abc", "POST", "/v1/chat/completions", body);
+        assert_eq!(rec["type"], "request");
+        assert_eq!(rec["model"], "gemini-3-flash");
+        assert_eq!(rec["body"]["stream"], true);
+
+        let rec = request_record("abc", "POST", "/v1beta/models/gemini-2.5-pro:streamGenerateContent?alt=sse", b"not json");
+        assert_eq!(rec["model"], "gemini-2.5-pro");
+        assert_eq!(rec["body"], "not json");
+
+        let chunk = chunk_record("abc", 42, b"data: {\"x\":1}\n\n");
+        assert_eq!(chunk["offset_ms"], 42);
+        assert_eq!(chunk["data"], "data: {\"x\":1}\n\n");
+
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 3, 7).unwrap();
+        assert_eq!(file_path(Path::new("/tmp/tee"), date), PathBuf::from("/tmp/tee/stream-2026-03-07.jsonl"));
+        assert_eq!(resolve_dir(" /var/tee "), Some(PathBuf::from("/var/tee")));
+        assert_eq!(file_date(&file_path(Path::new("/tmp/tee"), date)), Some(date));
+        assert_eq!(file_date(Path::new("/tmp/tee/stream-latest.jsonl")), None);
+        assert_eq!(file_date(Path::new("/tmp/tee/notes-2026-03-07.jsonl")), None);
+    }
+}
@@ -0,0 +1,589 @@
+// Token 预算预检中间件
+// 转发上游前在本地估算 prompt token，与目标模型上下文窗口以及 API Key 额度比较，
+// 超出时直接返回 400，避免完整往返后才被 Gemini 拒绝。
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::{json, Value};
+
+use crate::proxy::common::model_mapping::{map_claude_model_to_gemini, wildcard_match};
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::config::TokenBudgetConfig;
+use crate::proxy::server::AppState;
+
+/// 与 ContextManager 保持一致: 约 3.5 字节 / token
+const BYTES_PER_TOKEN: f64 = 3.5;
+/// Gemini 对单张图片 (及其他内联二进制) 的固定计费
+const TOKENS_PER_INLINE_MEDIA: u64 = 258;
+
+/// 自动升级时标注原目标模型的响应头 (实际使用的模型见 X-Mapped-Model)
+pub const UPGRADED_FROM_HEADER: &str = "x-model-upgraded-from";
+
+/// 不计入 prompt 的字段 (签名、ID、生成参数等)
+const SKIP_KEYS: &[&str] = &[
+    "model",
+    "signature",
+    "thoughtSignature",
+    "thought_signature",
+    "id",
+    "tool_call_id",
+    "tool_use_id",
+    "stream",
+    "stream_options",
+    "generationConfig",
+    "safetySettings",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Protocol {
+    OpenAI,
+    Claude,
+    Gemini,
+}
+
+pub(crate) fn detect_protocol(path: &str) -> Option<Protocol> {
+    match path {
+        "/v1/chat/completions" | "/v1/completions" | "/v1/responses" => Some(Protocol::OpenAI),
+        "/v1/messages" => Some(Protocol::Claude),
+        p if p.starts_with("/v1beta/models/")
+            && (p.ends_with(":generateContent") || p.ends_with(":streamGenerateContent")) =>
+        {
+            Some(Protocol::Gemini)
+        }
+        _ => None,
+    }
+}
+
+/// 请求模型名: 优先取 body.model，Gemini 原生路径取 URL 中的模型段
+pub(crate) fn extract_model(path: &str, body: &Value) -> Option<String> {
+    if let Some(m) = body.get("model").and_then(|v| v.as_str()) {
+        return Some(m.to_string());
+    }
+    path.strip_prefix("/v1beta/models/")
+        .and_then(|rest| rest.split(':').next())
+        .map(|m| m.to_string())
+}
+
+/// 与 resolve_model_route 相同的优先级，但不输出路由日志
+pub(crate) fn resolve_target_model(model: &str, custom_mapping: &std::collections::HashMap<String, String>) -> String {
+    if let Some(target) = custom_mapping.get(model) {
+        return target.clone();
+    }
+    for (pattern, target) in custom_mapping.iter() {
+        if pattern.contains('*') && wildcard_match(pattern, model) {
+            return target.clone();
+        }
+    }
+    map_claude_model_to_gemini(model)
+}
+
+/// 目标模型的输入上下文窗口 (预算配置中的覆盖优先于模型登记表)，未知模型返回 None (不做窗口检查)
+fn context_window(model: &str, config: &TokenBudgetConfig) -> Option<u64> {
+    if let Some(limit) = config.context_windows.get(model) {
+        return Some(*limit);
+    }
+    for (pattern, limit) in config.context_windows.iter() {
+        if pattern.contains('*') && wildcard_match(pattern, model) {
+            return Some(*limit);
+        }
+    }
+
+    crate::proxy::mappers::model_limits::resolve(model).context_window
+}
+
+/// 粗略估算 prompt token 数
+pub fn estimate_prompt_tokens(body: &Value) -> u64 {
+    fn walk(value: &Value, key: Option<&str>) -> f64 {
+        match value {
+            Value::String(s) => {
+                if matches!(key, Some("data")) || s.starts_with("data:") {
+                    TOKENS_PER_INLINE_MEDIA as f64
+                } else {
+                    s.len() as f64 / BYTES_PER_TOKEN
+                }
+            }
+            Value::Array(arr) => arr.iter().map(|v| walk(v, None)).sum(),
+            Value::Object(obj) => obj
+                .iter()
+                .filter(|(k, _)| !SKIP_KEYS.contains(&k.as_str()))
+                .map(|(k, v)| walk(v, Some(k.as_str())))
+                .sum(),
+            _ => 0.0,
+        }
+    }
+    walk(body, None).ceil() as u64
+}
+
+fn requested_output_tokens(body: &Value) -> Option<u64> {
+    ["max_completion_tokens", "max_tokens", "max_output_tokens"]
+        .iter()
+        .find_map(|k| body.get(*k).and_then(|v| v.as_u64()))
+        .or_else(|| {
+            body.get("generationConfig")
+                .and_then(|g| g.get("maxOutputTokens"))
+                .and_then(|v| v.as_u64())
+        })
+}
+
+pub(crate) fn request_api_key(request: &Request) -> Option<String> {
+    api_key_from_parts(request.headers(), request.uri())
+}
+
+/// 供无法拿到完整 Request 的 handler 使用 (如 fanout 的 Json 提取器)
+pub(crate) fn api_key_from_parts(headers: &HeaderMap, uri: &Uri) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.strip_prefix("Bearer ").unwrap_or(s))
+        .or_else(|| headers.get("x-api-key").and_then(|h| h.to_str().ok()))
+        .or_else(|| headers.get("x-goog-api-key").and_then(|h| h.to_str().ok()))
+        .map(|s| s.trim().to_string())
+        .or_else(|| {
+            uri.query().and_then(|q| {
+                q.split('&')
+                    .find_map(|pair| pair.strip_prefix("key="))
+                    .map(|k| k.to_string())
+            })
+        })
+}
+
+/// 当前 Key 的额度: key_budgets 优先，其次 max_prompt_tokens；0 表示不限制
+fn budget_for_key(config: &TokenBudgetConfig, api_key: Option<&str>) -> Option<(u64, &'static str)> {
+    if let Some(limit) = api_key.and_then(|k| config.key_budgets.get(k)) {
+        return (*limit > 0).then_some((*limit, "key_budget"));
+    }
+    (config.max_prompt_tokens > 0).then_some((config.max_prompt_tokens, "max_prompt_tokens"))
+}
+
+#[derive(Debug)]
+struct Rejection {
+    code: &'static str,
+    message: String,
+    details: Value,
+}
+
+fn error_response(protocol: Protocol, rejection: Rejection) -> Response {
+    let body = match protocol {
+        Protocol::OpenAI => json!({
+            "error": {
+                "message": rejection.message,
+                "type": "invalid_request_error",
+                "param": "messages",
+                "code": rejection.code,
+                "details": rejection.details,
+            }
+        }),
+        Protocol::Claude => json!({
+            "type": "error",
+            "error": {
+                "type": "invalid_request_error",
+                "message": rejection.message,
+                "details": rejection.details,
+            }
+        }),
+        Protocol::Gemini => json!({
+            "error": {
+                "code": 400,
+                "message": rejection.message,
+                "status": "INVALID_ARGUMENT",
+                "details": [rejection.details],
+            }
+        }),
+    };
+    (StatusCode::BAD_REQUEST, Json(body)).into_response()
+}
+
+/// 估算值超过窗口的 (100 + tolerance)% 才视为超限
+fn window_threshold(limit: u64, config: &TokenBudgetConfig) -> u64 {
+    limit + limit * config.tolerance_percent as u64 / 100
+}
+
+/// 内置升级路线 (按顺序匹配，越具体的规则越靠前)
+const BUILTIN_UPGRADES: &[(&str, &str)] = &[
+    ("gemini-2.5-flash-lite*", "gemini-2.5-flash"),
+    ("gemini-2.5-flash*", "gemini-2.5-pro"),
+    ("gemini-3-flash*", "gemini-3-pro-high"),
+    ("gemini-3-pro-low", "gemini-3-pro-high"),
+];
+
+fn next_upgrade(model: &str, config: &TokenBudgetConfig) -> Option<String> {
+    if let Some(next) = config.upgrade_routes.get(model) {
+        return Some(next.clone());
+    }
+    for (pattern, next) in config.upgrade_routes.iter() {
+        if pattern.contains('*') && wildcard_match(pattern, model) {
+            return Some(next.clone());
+        }
+    }
+    BUILTIN_UPGRADES
+        .iter()
+        .find(|(pattern, _)| wildcard_match(pattern, model))
+        .map(|(_, next)| next.to_string())
+}
+
+/// 沿升级路线寻找第一个能容纳估算 token 的模型
+fn find_upgrade(target_model: &str, estimated: u64, config: &TokenBudgetConfig) -> Option<(String, u64)> {
+    let mut current = target_model.to_string();
+    let mut visited = vec![current.clone()];
+    while let Some(next) = next_upgrade(&current, config) {
+        if visited.contains(&next) {
+            break;
+        }
+        if let Some(limit) = context_window(&next, config) {
+            if estimated <= window_threshold(limit, config) {
+                return Some((next, limit));
+            }
+        }
+        visited.push(next.clone());
+        current = next;
+    }
+    None
+}
+
+fn auto_upgrade_enabled(config: &TokenBudgetConfig, api_key: Option<&str>) -> bool {
+    api_key
+        .and_then(|k| config.auto_upgrade_keys.get(k).copied())
+        .unwrap_or(config.auto_upgrade)
+}
+
+#[derive(Debug)]
+enum Decision {
+    Pass,
+    /// 改用更大窗口的模型: (模型名, 窗口)
+    Upgrade(String, u64),
+    Reject(Rejection),
+}
+
+fn check_budget(
+    protocol: Protocol,
+    model: &str,
+    target_model: &str,
+    body: &Value,
+    config: &TokenBudgetConfig,
+    budget: Option<(u64, &'static str)>,
+    allow_upgrade: bool,
+) -> Decision {
+    let estimated = estimate_prompt_tokens(body);
+    let num_ctx = context_window(target_model, config);
+    let details = json!({
+        "model": model,
+        "target_model": target_model,
+        "estimated_prompt_tokens": estimated,
+        "num_ctx": num_ctx,
+        "max_output_tokens": requested_output_tokens(body),
+        "budget": budget.map(|(limit, _)| limit),
+        "budget_source": budget.map(|(_, source)| source),
+        "estimation": "approximate",
+    });
+
+    if let Some((limit, _)) = budget {
+        if estimated > limit {
+            return Decision::Reject(Rejection {
+                code: "token_budget_exceeded",
+                message: format!(
+                    "Request is estimated at ~{} prompt tokens, exceeding the configured budget of {} tokens for this API key.",
+                    estimated, limit
+                ),
+                details,
+            });
+        }
+    }
+
+    if let Some(limit) = num_ctx {
+        if estimated > window_threshold(limit, config) {
+            if allow_upgrade {
+                if let Some((upgraded, upgraded_limit)) = find_upgrade(target_model, estimated, config) {
+                    return Decision::Upgrade(upgraded, upgraded_limit);
+                }
+            }
+            // Claude 客户端依赖 "prompt is too long" 字样触发自动压缩
+            let message = match protocol {
+                Protocol::Claude => format!(
+                    "prompt is too long: ~{} tokens > {} maximum (model {}, num_ctx={})",
+                    estimated, limit, target_model, limit
+                ),
+                _ => format!(
+                    "This model's maximum context length is {} tokens (num_ctx={}, model {}). However, your request is estimated at ~{} tokens. Please reduce the length of the messages.",
+                    limit, limit, target_model, estimated
+                ),
+            };
+            return Decision::Reject(Rejection { code: "context_length_exceeded", message, details });
+        }
+    }
+
+    Decision::Pass
+}
+
+/// 把请求改写为升级后的模型: body.model 或 Gemini 原生路径中的模型段
+pub(crate) fn rewrite_model(parts: &mut axum::http::request::Parts, json: &mut Value, upgraded: &str) -> bool {
+    if json.get("model").is_some() {
+        json["model"] = Value::String(upgraded.to_string());
+        return true;
+    }
+    let path = parts.uri.path();
+    let Some(rest) = path.strip_prefix("/v1beta/models/") else {
+        return false;
+    };
+    // 动作取最后一个冒号之后 (模型段本身可能含冒号，如 `preset:<name>`)
+    let Some((_, action)) = rest.rsplit_once(':') else {
+        return false;
+    };
+    let new_path = format!("/v1beta/models/{}:{}", upgraded, action);
+    let path_and_query = match parts.uri.query() {
+        Some(q) => format!("{}?{}", new_path, q),
+        None => new_path,
+    };
+    match path_and_query.parse::<axum::http::Uri>() {
+        Ok(uri) => {
+            parts.uri = uri;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+pub async fn token_budget_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(protocol) = detect_protocol(request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let config = state.token_budget.read().await.clone();
+    if !config.enabled {
+        return next.run(request).await;
+    }
+
+    let api_key = request_api_key(&request);
+    let budget = budget_for_key(&config, api_key.as_deref());
+    let allow_upgrade = auto_upgrade_enabled(&config, api_key.as_deref());
+    let path = request.uri().path().to_string();
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return payload_too_large_response(state.max_body_bytes),
+    };
+
+    // 请求体按字节估算的上界都不会超限时，无需解析 JSON
+    let upper_bound = (bytes.len() as f64 / BYTES_PER_TOKEN).ceil() as u64;
+    let min_override = config.context_windows.values().copied().min().unwrap_or(u64::MAX);
+    // 请求体上界低于登记表中最小的窗口时无需解析
+    let cheap_limit = crate::proxy::mappers::model_limits::min_context_window()
+        .min(min_override)
+        .min(budget.map(|(limit, _)| limit).unwrap_or(u64::MAX));
+    if upper_bound <= cheap_limit {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    }
+
+    let Ok(mut json) = serde_json::from_slice::<Value>(&bytes) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let Some(model) = extract_model(&path, &json) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let target_model = {
+        let mapping = state.custom_mapping.read().await;
+        resolve_target_model(&model, &mapping)
+    };
+
+    match check_budget(protocol, &model, &target_model, &json, &config, budget, allow_upgrade) {
+        Decision::Pass => next.run(Request::from_parts(parts, Body::from(bytes))).await,
+        Decision::Reject(rejection) => {
+            tracing::warn!(
+                "[TokenBudget] 拒绝请求 {} ({} -> {}): {}",
+                path,
+                model,
+                target_model,
+                rejection.message
+            );
+            error_response(protocol, rejection)
+        }
+        Decision::Upgrade(upgraded, limit) => {
+            if !rewrite_model(&mut parts, &mut json, &upgraded) {
+                return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+            }
+            tracing::info!(
+                "[TokenBudget] 上下文超出 {} 的窗口，自动升级到 {} (num_ctx={})",
+                target_model,
+                upgraded,
+                limit
+            );
+            let serialized = serde_json::to_vec(&json).unwrap_or_default();
+            parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+            let mut response = next.run(Request::from_parts(parts, Body::from(serialized))).await;
+
+            let headers = response.headers_mut();
+            if let Ok(v) = HeaderValue::from_str(&target_model) {
+                headers.insert(UPGRADED_FROM_HEADER, v);
+            }
+            if !headers.contains_key("X-Mapped-Model") {
+                if let Ok(v) = HeaderValue::from_str(&upgraded) {
+                    headers.insert("X-Mapped-Model", v);
+                }
+            }
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_counts_text_and_media() {
+        let body = json!({
+            "model": "gemini-2.5-flash",
+            "messages": [
+                {"role": "user", "content": "a".repeat(350)},
+                {"role": "user", "content": [
+                    {"type": "image_url", "image_url": {"url": format!("data:image/png;base64,{}", "A".repeat(100_000))}}
+                ]}
+            ]
+        });
+        let estimated = estimate_prompt_tokens(&body);
+        // 350 字节文本 ≈ 100 token，图片固定 258，外加少量角色/类型字段
+        assert!((358..380).contains(&estimated), "estimated = {}", estimated);
+    }
+
+    #[test]
+    fn test_context_window_lookup() {
+        let mut config = TokenBudgetConfig::default();
+        assert_eq!(context_window("gemini-2.5-flash", &config), Some(1_048_576));
+        assert_eq!(context_window("claude-sonnet-4-5", &config), Some(200_000));
+        assert_eq!(context_window("gemini-3-pro-image", &config), None);
+        assert_eq!(context_window("some-local-model", &config), None);
+
+        config.context_windows.insert("gemini-3-*".to_string(), 500_000);
+        assert_eq!(context_window("gemini-3-flash", &config), Some(500_000));
+    }
+
+    #[test]
+    fn test_extract_model_from_gemini_path() {
+        let body = json!({"contents": []});
+        assert_eq!(
+            extract_model("/v1beta/models/gemini-2.5-pro:streamGenerateContent", &body).as_deref(),
+            Some("gemini-2.5-pro")
+        );
+        assert_eq!(detect_protocol("/v1/messages/count_tokens"), None);
+    }
+
+    fn expect_reject(decision: Decision) -> Rejection {
+        match decision {
+            Decision::Reject(r) => r,
+            other => panic!("expected rejection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_check_budget_rejections() {
+        let config = TokenBudgetConfig {
+            context_windows: [("tiny-model".to_string(), 100)].into_iter().collect(),
+            ..TokenBudgetConfig::default()
+        };
+        let body = json!({"messages": [{"content": "x".repeat(3500)}]});
+
+        let rejection = expect_reject(check_budget(Protocol::Claude, "tiny", "tiny-model", &body, &config, None, false));
+        assert_eq!(rejection.code, "context_length_exceeded");
+        assert!(rejection.message.starts_with("prompt is too long"));
+        assert_eq!(rejection.details["num_ctx"], 100);
+
+        let rejection = expect_reject(check_budget(
+            Protocol::OpenAI,
+            "gemini-2.5-flash",
+            "gemini-2.5-flash",
+            &body,
+            &config,
+            Some((500, "key_budget")),
+            false,
+        ));
+        assert_eq!(rejection.code, "token_budget_exceeded");
+        assert_eq!(rejection.details["budget_source"], "key_budget");
+
+        assert!(matches!(
+            check_budget(Protocol::OpenAI, "m", "gemini-2.5-flash", &body, &config, None, false),
+            Decision::Pass
+        ));
+    }
+
+    #[test]
+    fn test_auto_upgrade_follows_routes() {
+        let mut config = TokenBudgetConfig {
+            context_windows: [
+                ("gemini-2.5-flash".to_string(), 100),
+                ("gemini-2.5-pro".to_string(), 2000),
+                ("small-model".to_string(), 100),
+            ]
+            .into_iter()
+            .collect(),
+            ..TokenBudgetConfig::default()
+        };
+        let body = json!({"messages": [{"content": "x".repeat(3500)}]});
+
+        // 内置路线 flash -> pro
+        match check_budget(Protocol::OpenAI, "m", "gemini-2.5-flash", &body, &config, None, true) {
+            Decision::Upgrade(model, limit) => {
+                assert_eq!(model, "gemini-2.5-pro");
+                assert_eq!(limit, 2000);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        // 未开启升级时仍然拒绝
+        expect_reject(check_budget(Protocol::OpenAI, "m", "gemini-2.5-flash", &body, &config, None, false));
+        // 没有可用路线时拒绝
+        expect_reject(check_budget(Protocol::OpenAI, "m", "small-model", &body, &config, None, true));
+
+        // 自定义路线可多跳，且出现环路时停止
+        config.upgrade_routes.insert("small-*".to_string(), "gemini-2.5-flash".to_string());
+        assert_eq!(find_upgrade("small-model", 1000, &config), Some(("gemini-2.5-pro".to_string(), 2000)));
+        config.upgrade_routes.insert("gemini-2.5-pro".to_string(), "small-model".to_string());
+        assert_eq!(find_upgrade("small-model", 10_000, &config), None);
+    }
+
+    #[test]
+    fn test_auto_upgrade_per_key() {
+        let mut config = TokenBudgetConfig { auto_upgrade: true, ..TokenBudgetConfig::default() };
+        config.auto_upgrade_keys.insert("sk-strict".to_string(), false);
+        assert!(auto_upgrade_enabled(&config, None));
+        assert!(!auto_upgrade_enabled(&config, Some("sk-strict")));
+        config.auto_upgrade = false;
+        config.auto_upgrade_keys.insert("sk-vip".to_string(), true);
+        assert!(auto_upgrade_enabled(&config, Some("sk-vip")));
+        assert!(!auto_upgrade_enabled(&config, Some("sk-other")));
+    }
+
+    #[test]
+    fn test_rewrite_model_in_gemini_path() {
+        let (mut parts, _) = Request::builder()
+            .uri("/v1beta/models/gemini-3-flash:streamGenerateContent?alt=sse")
+            .body(Body::empty())
+            .unwrap()
+            .into_parts();
+        let mut body = json!({"contents": []});
+        assert!(rewrite_model(&mut parts, &mut body, "gemini-3-pro-high"));
+        assert_eq!(parts.uri.to_string(), "/v1beta/models/gemini-3-pro-high:streamGenerateContent?alt=sse");
+
+        let mut body = json!({"model": "claude-sonnet-4-5"});
+        assert!(rewrite_model(&mut parts, &mut body, "gemini-2.5-pro"));
+        assert_eq!(body["model"], "gemini-2.5-pro");
+    }
+
+    #[test]
+    fn test_budget_for_key() {
+        let mut config = TokenBudgetConfig { max_prompt_tokens: 1000, ..TokenBudgetConfig::default() };
+        config.key_budgets.insert("sk-small".to_string(), 10);
+        config.key_budgets.insert("sk-unlimited".to_string(), 0);
+
+        assert_eq!(budget_for_key(&config, Some("sk-small")), Some((10, "key_budget")));
+        assert_eq!(budget_for_key(&config, Some("sk-unlimited")), None);
+        assert_eq!(budget_for_key(&config, Some("sk-other")), Some((1000, "max_prompt_tokens")));
+        assert_eq!(budget_for_key(&config, None), Some((1000, "max_prompt_tokens")));
+    }
+}
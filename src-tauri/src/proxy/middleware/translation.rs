@@ -0,0 +1,350 @@
+// 提示词自动翻译
+// 部分用户反馈英文提示词的工具调用遵循度明显更好。按 API Key 开启后:
+// 最后一条用户消息含非 ASCII 字母时，先用廉价模型检测语言并译为英文再发送；
+// 非流式回答再译回用户语言，流式回答无法整体翻译，改为在提示词末尾要求模型直接用用户语言作答。
+// 翻译失败时原样转发 (fail open)。翻译调用在进程内直接走 chat completions handler，不经过本中间件。
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use futures::future::join_all;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::proxy::config::TranslationConfig;
+use crate::proxy::middleware::best_of::{choices_of, content_text};
+use crate::proxy::middleware::token_budget::{detect_protocol, request_api_key, Protocol};
+use crate::proxy::server::AppState;
+
+/// 响应头: 检测到的用户语言 (已翻译时)
+pub const TRANSLATED_FROM_HEADER: &str = "x-ag-translated-from";
+/// 单次请求最多翻译的文本段数
+const MAX_SEGMENTS: usize = 8;
+const MAX_RESPONSE_BYTES: usize = 100 * 1024 * 1024;
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Detected {
+    language: String,
+    text: String,
+}
+
+fn enabled_for_key(config: &TranslationConfig, api_key: Option<&str>) -> bool {
+    api_key
+        .and_then(|k| config.keys.get(k).copied())
+        .unwrap_or(config.enabled)
+}
+
+/// 纯 ASCII 文本视为英文，不调用模型检测
+fn needs_translation(text: &str) -> bool {
+    text.chars().any(|c| c.is_alphabetic() && !c.is_ascii())
+}
+
+fn is_english(language: &str) -> bool {
+    let lang = language.trim().to_ascii_lowercase();
+    lang == "english" || lang == "en" || lang.starts_with("en-")
+}
+
+fn is_user(message: &Value) -> bool {
+    message.get("role").and_then(|r| r.as_str()).unwrap_or("user") == "user"
+}
+
+/// 最后一条消息为用户消息时其中的文本 (均为 Value::String 的可变引用)；
+/// 工具调用循环中 (最后一条是工具结果) 不翻译
+fn last_user_texts(protocol: Protocol, body: &mut Value) -> Vec<&mut Value> {
+    let (list, parts_key) = match protocol {
+        Protocol::OpenAI | Protocol::Claude => ("messages", "content"),
+        Protocol::Gemini => ("contents", "parts"),
+    };
+    let Some(message) = body
+        .get_mut(list)
+        .and_then(|m| m.as_array_mut())
+        .and_then(|m| m.last_mut())
+        .filter(|m| is_user(m))
+    else {
+        return Vec::new();
+    };
+    let Some(content) = message.get_mut(parts_key) else {
+        return Vec::new();
+    };
+    if content.is_string() {
+        return vec![content];
+    }
+    content
+        .as_array_mut()
+        .map(|parts| {
+            parts
+                .iter_mut()
+                .filter(|p| p.get("type").map_or(true, |t| t == "text"))
+                .filter_map(|p| p.get_mut("text"))
+                .filter(|t| t.is_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 非流式回答中的文本 (思维内容不翻译)
+fn response_texts(protocol: Protocol, body: &mut Value) -> Vec<&mut Value> {
+    match protocol {
+        Protocol::OpenAI => body
+            .get_mut("choices")
+            .and_then(|c| c.as_array_mut())
+            .map(|choices| {
+                choices
+                    .iter_mut()
+                    .filter_map(|c| c.pointer_mut("/message/content"))
+                    .filter(|t| t.is_string())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        Protocol::Claude => body
+            .get_mut("content")
+            .and_then(|c| c.as_array_mut())
+            .map(|blocks| {
+                blocks
+                    .iter_mut()
+                    .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("text"))
+                    .filter_map(|b| b.get_mut("text"))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        Protocol::Gemini => body
+            .get_mut("candidates")
+            .and_then(|c| c.as_array_mut())
+            .map(|candidates| {
+                candidates
+                    .iter_mut()
+                    .filter_map(|c| c.pointer_mut("/content/parts").and_then(|p| p.as_array_mut()))
+                    .flat_map(|parts| parts.iter_mut())
+                    .filter(|p| !p.get("thought").and_then(|t| t.as_bool()).unwrap_or(false))
+                    .filter_map(|p| p.get_mut("text"))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn to_english_prompt(text: &str) -> String {
+    format!(
+        "Detect the language of the text inside <text> and translate it into English. \
+Keep code blocks, inline code, identifiers, file paths, URLs and placeholders like [EMAIL_1] unchanged. \
+Reply with JSON only: {{\"language\": \"<source language name in English>\", \"text\": \"<English translation>\"}}. \
+If the text is already English, return it unchanged with \"language\": \"English\".\n\n<text>\n{}\n</text>",
+        text
+    )
+}
+
+fn from_english_prompt(text: &str, language: &str) -> String {
+    format!(
+        "Translate the text inside <text> from English into {}. \
+Keep Markdown formatting, code blocks, inline code, identifiers, file paths, URLs and placeholders like [EMAIL_1] unchanged. \
+Reply with the translation only.\n\n<text>\n{}\n</text>",
+        language, text
+    )
+}
+
+fn answer_in_instruction(language: &str) -> String {
+    format!("\n\n(Please answer in {}.)", language)
+}
+
+/// 模型偶尔会把 JSON 包在代码块里
+fn parse_detected(raw: &str) -> Option<Detected> {
+    let trimmed = raw.trim();
+    let inner = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|s| s.strip_suffix("```"))
+        .unwrap_or(trimmed);
+    serde_json::from_str(inner.trim()).ok()
+}
+
+/// 调用翻译模型一次，返回正文
+async fn complete(state: &AppState, model: &str, prompt: String) -> Option<String> {
+    let body = json!({
+        "model": model,
+        "stream": false,
+        "temperature": 0,
+        "messages": [{ "role": "user", "content": prompt }],
+    });
+    let (status, _, response) = crate::proxy::batch::dispatch(state, "/v1/chat/completions", body).await;
+    if !(200..300).contains(&status) {
+        tracing::warn!("[Translation] 翻译模型 {} 返回 {}", model, status);
+        return None;
+    }
+    choices_of(&response)
+        .first()
+        .and_then(|c| c.pointer("/message/content"))
+        .map(content_text)
+        .filter(|t| !t.trim().is_empty())
+}
+
+pub async fn translation_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let Some(protocol) = detect_protocol(&path) else {
+        return next.run(request).await;
+    };
+    // OpenAI 协议仅处理 chat completions (legacy completions / responses 的输入格式不同)
+    if protocol == Protocol::OpenAI && path != "/v1/chat/completions" {
+        return next.run(request).await;
+    }
+    let config = state.experimental.read().await.translation.clone();
+    if !enabled_for_key(&config, request_api_key(&request).as_deref()) {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return crate::proxy::common::streaming_json::payload_too_large_response(state.max_body_bytes),
+    };
+    let Ok(mut json) = serde_json::from_slice::<Value>(&bytes) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let is_stream = path.ends_with(":streamGenerateContent")
+        || json.get("stream").and_then(|s| s.as_bool()).unwrap_or(false);
+
+    let mut slots = last_user_texts(protocol, &mut json);
+    slots.retain(|t| t.as_str().is_some_and(needs_translation));
+    slots.truncate(MAX_SEGMENTS);
+    if slots.is_empty() {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    }
+
+    let results = join_all(slots.iter().map(|slot| {
+        let prompt = to_english_prompt(slot.as_str().unwrap_or_default());
+        let state = &state;
+        let model = config.model.as_str();
+        async move { complete(state, model, prompt).await.and_then(|raw| parse_detected(&raw)) }
+    }))
+    .await;
+
+    let mut language = None;
+    for (slot, detected) in slots.iter_mut().zip(results) {
+        let Some(detected) = detected else { continue };
+        if is_english(&detected.language) || detected.text.trim().is_empty() {
+            continue;
+        }
+        **slot = Value::String(detected.text);
+        language.get_or_insert(detected.language);
+    }
+    let Some(language) = language else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+
+    let back_translate = !is_stream && config.translate_responses;
+    if !back_translate {
+        if let Some(Value::String(last)) = slots.last_mut().map(|s| &mut **s) {
+            last.push_str(&answer_in_instruction(&language));
+        }
+    }
+    drop(slots);
+    tracing::info!("[Translation] {} 提示词已由 {} 译为英文", path, language);
+
+    let serialized = serde_json::to_vec(&json).unwrap_or_default();
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+    let mut response = next.run(Request::from_parts(parts, Body::from(serialized))).await;
+    if let Ok(v) = HeaderValue::from_str(&language) {
+        response.headers_mut().insert(TRANSLATED_FROM_HEADER, v);
+    }
+    if !back_translate || !response.status().is_success() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_RESPONSE_BYTES).await {
+        Ok(b) => b,
+        Err(_) => return Response::from_parts(parts, Body::empty()),
+    };
+    let Ok(mut answer) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let mut texts = response_texts(protocol, &mut answer);
+    texts.retain(|t| t.as_str().is_some_and(|s| !s.trim().is_empty()));
+    texts.truncate(MAX_SEGMENTS);
+    let translated = join_all(texts.iter().map(|text| {
+        let prompt = from_english_prompt(text.as_str().unwrap_or_default(), &language);
+        let state = &state;
+        let model = config.model.as_str();
+        async move { complete(state, model, prompt).await }
+    }))
+    .await;
+    for (text, result) in texts.iter_mut().zip(translated) {
+        if let Some(result) = result {
+            **text = Value::String(result);
+        }
+    }
+    drop(texts);
+
+    let serialized = serde_json::to_vec(&answer).unwrap_or_default();
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+    Response::from_parts(parts, Body::from(serialized))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_user_texts_per_protocol() {
+        let mut openai = json!({ "messages": [
+            { "role": "user", "content": "旧问题" },
+            { "role": "assistant", "content": "answer" },
+            { "role": "user", "content": [{ "type": "text", "text": "新问题" }, { "type": "image_url", "image_url": {} }] }
+        ]});
+        let texts = last_user_texts(Protocol::OpenAI, &mut openai);
+        assert_eq!(texts.len(), 1);
+        assert_eq!(*texts[0], "新问题");
+
+        let mut claude = json!({ "messages": [
+            { "role": "user", "content": [{ "type": "tool_result", "content": "x" }] }
+        ]});
+        assert!(last_user_texts(Protocol::Claude, &mut claude).is_empty());
+        let mut tool_turn = json!({ "messages": [{ "role": "user", "content": "查询" }, { "role": "tool", "content": "{}" }] });
+        assert!(last_user_texts(Protocol::OpenAI, &mut tool_turn).is_empty());
+
+        let mut gemini = json!({ "contents": [{ "parts": [{ "text": "Привет" }] }] });
+        let texts = last_user_texts(Protocol::Gemini, &mut gemini);
+        *texts.into_iter().next().unwrap() = json!("Hello");
+        assert_eq!(gemini["contents"][0]["parts"][0]["text"], "Hello");
+    }
+
+    #[test]
+    fn test_response_texts_skip_thoughts() {
+        let mut gemini = json!({ "candidates": [{ "content": { "parts": [
+            { "text": "thinking", "thought": true }, { "text": "Answer" }
+        ]}}]});
+        let texts = response_texts(Protocol::Gemini, &mut gemini);
+        assert_eq!(texts.len(), 1);
+        assert_eq!(*texts[0], "Answer");
+
+        let mut claude = json!({ "content": [{ "type": "thinking", "thinking": "t" }, { "type": "text", "text": "Hi" }] });
+        assert_eq!(response_texts(Protocol::Claude, &mut claude).len(), 1);
+    }
+
+    #[test]
+    fn test_detection_helpers() {
+        assert!(!needs_translation("Refactor src/main.rs, please!"));
+        assert!(needs_translation("重构 main.rs"));
+        assert!(needs_translation("¿Puedes explicar esto?"));
+        assert!(is_english("English") && is_english("en-US") && !is_english("Chinese"));
+
+        let fenced = "```json\n{\"language\": \"Japanese\", \"text\": \"Hello\"}\n```";
+        assert_eq!(
+            parse_detected(fenced),
+            Some(Detected { language: "Japanese".to_string(), text: "Hello".to_string() })
+        );
+        assert_eq!(parse_detected("not json"), None);
+
+        let mut config = TranslationConfig::default();
+        config.keys.insert("sk-cn".to_string(), true);
+        assert!(enabled_for_key(&config, Some("sk-cn")));
+        assert!(!enabled_for_key(&config, Some("sk-other")));
+        config.enabled = true;
+        config.keys.insert("sk-en".to_string(), false);
+        assert!(!enabled_for_key(&config, Some("sk-en")));
+        assert!(enabled_for_key(&config, None));
+    }
+}
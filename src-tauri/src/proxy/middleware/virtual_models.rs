@@ -0,0 +1,255 @@
+// 虚拟模型中间件 (Chat Completions)
+// 请求模型命中配置的虚拟模型时，按声明的步骤在代理内依次调用:
+// pre 步骤改写最后一条用户消息 (如先用 flash 翻译)，main 步骤基于改写后的完整对话作答，
+// post 步骤再改写回答。每一步都是同一路由上的内部请求，仍经过分流/预检/降级链。
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::{json, Value};
+
+use crate::proxy::common::http::invalid_request;
+use crate::proxy::common::streaming_json::payload_too_large_response;
+use crate::proxy::config::{VirtualModel, VirtualModelStep, VirtualStepStage};
+use crate::proxy::middleware::best_of::{add_usage, choices_of, clone_parts, content_text, run_inner};
+use crate::proxy::server::AppState;
+
+pub const VIRTUAL_MODEL_HEADER: &str = "x-virtual-model";
+
+/// 按阶段拆分步骤；未声明 main 时最后一步视为 main
+fn split_steps(model: &VirtualModel) -> (Vec<&VirtualModelStep>, Option<&VirtualModelStep>, Vec<&VirtualModelStep>) {
+    let explicit_main = model.steps.iter().position(|s| s.stage == VirtualStepStage::Main);
+    let main_idx = explicit_main.or_else(|| model.steps.len().checked_sub(1));
+    let mut pre = Vec::new();
+    let mut post = Vec::new();
+    for (i, step) in model.steps.iter().enumerate() {
+        if Some(i) == main_idx {
+            continue;
+        }
+        match step.stage {
+            VirtualStepStage::Post => post.push(step),
+            _ => pre.push(step),
+        }
+    }
+    (pre, main_idx.map(|i| &model.steps[i]), post)
+}
+
+fn render_prompt(step: &VirtualModelStep, input: &str) -> String {
+    match &step.prompt {
+        Some(template) if !template.is_empty() => template.replace("{{input}}", input),
+        _ => input.to_string(),
+    }
+}
+
+fn last_user_index(messages: &[Value]) -> Option<usize> {
+    messages.iter().rposition(|m| m.get("role").and_then(|r| r.as_str()) == Some("user"))
+}
+
+/// 替换消息中的文本 (保留图片等非文本 part)
+fn replace_text(message: &mut Value, text: String) {
+    match message.get_mut("content") {
+        Some(Value::Array(parts)) => {
+            parts.retain(|p| p.get("type").and_then(|t| t.as_str()) != Some("text"));
+            parts.insert(0, json!({ "type": "text", "text": text }));
+        }
+        _ => message["content"] = json!(text),
+    }
+}
+
+/// 单轮变换步骤的请求体 (pre / post)
+fn transform_body(step: &VirtualModelStep, input: &str) -> Value {
+    let mut messages = Vec::new();
+    if let Some(system) = &step.system_prompt {
+        messages.push(json!({ "role": "system", "content": system }));
+    }
+    messages.push(json!({ "role": "user", "content": render_prompt(step, input) }));
+    json!({ "model": step.model, "stream": false, "messages": messages })
+}
+
+/// main 步骤的请求体: 保留客户端参数，替换模型与 (改写后的) 最后一条用户消息
+fn main_body(step: &VirtualModelStep, client_body: &Value, user_text: Option<String>) -> Value {
+    let mut body = client_body.clone();
+    body["model"] = json!(step.model);
+    if let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) {
+        if let (Some(idx), Some(text)) = (last_user_index(messages), user_text) {
+            replace_text(&mut messages[idx], text);
+        }
+        if let Some(system) = &step.system_prompt {
+            messages.insert(0, json!({ "role": "system", "content": system }));
+        }
+    }
+    body
+}
+
+fn answer_text(response: &Value) -> Option<String> {
+    choices_of(response)
+        .first()
+        .and_then(|c| c.pointer("/message/content").cloned())
+        .map(|c| content_text(&c))
+}
+
+pub async fn virtual_models_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.uri().path() != "/v1/chat/completions" {
+        return next.run(request).await;
+    }
+    let models = state.experimental.read().await.virtual_models.clone();
+    if !models.iter().any(|m| m.enabled) {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes: Bytes = match to_bytes(body, state.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return payload_too_large_response(state.max_body_bytes),
+    };
+    let matched = serde_json::from_slice::<Value>(&bytes).ok().and_then(|body| {
+        let name = body.get("model")?.as_str()?.to_string();
+        let model = models.iter().find(|m| m.enabled && m.name == name)?.clone();
+        Some((body, model))
+    });
+    let Some((client_body, virtual_model)) = matched else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    let (pre, main, post) = split_steps(&virtual_model);
+    let Some(main) = main else {
+        return invalid_request(format!("Virtual model '{}' has no steps", virtual_model.name), Some("model"));
+    };
+    let wants_stream = client_body.get("stream").and_then(|s| s.as_bool()).unwrap_or(false);
+    if wants_stream && !post.is_empty() {
+        return invalid_request("Virtual models with post-processing steps cannot be used together with stream", Some("model"));
+    }
+
+    let mut usage = json!({});
+
+    // 1. pre: 依次改写最后一条用户消息
+    let messages = client_body.get("messages").and_then(|m| m.as_array()).cloned().unwrap_or_default();
+    let mut user_text = last_user_index(&messages).map(|i| content_text(messages[i].get("content").unwrap_or(&Value::Null)));
+    for step in &pre {
+        let Some(input) = user_text.clone() else { break };
+        let (status, response) = run_inner(&next, &parts, &transform_body(step, &input)).await;
+        if !status.is_success() {
+            tracing::warn!("[VirtualModel] {} 前处理步骤 ({}) 失败: HTTP {}", virtual_model.name, step.model, status);
+            return (status, Json(response)).into_response();
+        }
+        add_usage(&mut usage, response.get("usage"));
+        user_text = answer_text(&response).or(Some(input));
+    }
+    let user_text = if pre.is_empty() { None } else { user_text };
+    let main_request = main_body(main, &client_body, user_text);
+    tracing::info!(
+        "[VirtualModel] {}: {} 个前处理步骤 -> {} -> {} 个后处理步骤",
+        virtual_model.name,
+        pre.len(),
+        main.model,
+        post.len()
+    );
+
+    // 2. main: 无后处理时直接透传 (支持流式；流式响应无法并入前处理步骤的 usage)
+    if post.is_empty() && (wants_stream || pre.is_empty()) {
+        let mut main_parts = clone_parts(&parts);
+        let serialized = serde_json::to_vec(&main_request).unwrap_or_default();
+        main_parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(serialized.len()));
+        let mut response = next.run(Request::from_parts(main_parts, Body::from(serialized))).await;
+        if let Ok(v) = HeaderValue::from_str(&virtual_model.name) {
+            response.headers_mut().insert(VIRTUAL_MODEL_HEADER, v);
+        }
+        return response;
+    }
+
+    let (status, mut result) = run_inner(&next, &parts, &main_request).await;
+    if !status.is_success() {
+        return (status, Json(result)).into_response();
+    }
+    add_usage(&mut usage, result.get("usage"));
+
+    // 3. post: 依次改写回答 (失败时保留上一步结果)
+    if let Some(mut answer) = answer_text(&result) {
+        for step in &post {
+            let (status, response) = run_inner(&next, &parts, &transform_body(step, &answer)).await;
+            if !status.is_success() {
+                tracing::warn!("[VirtualModel] {} 后处理步骤 ({}) 失败: HTTP {}，保留上一步结果", virtual_model.name, step.model, status);
+                break;
+            }
+            add_usage(&mut usage, response.get("usage"));
+            if let Some(text) = answer_text(&response) {
+                answer = text;
+            }
+        }
+        result["choices"][0]["message"]["content"] = json!(answer);
+    }
+
+    result["model"] = json!(virtual_model.name);
+    if usage.as_object().map(|u| !u.is_empty()).unwrap_or(false) {
+        result["usage"] = usage;
+    }
+    let mut response = (StatusCode::OK, Json(result)).into_response();
+    if let Ok(v) = HeaderValue::from_str(&virtual_model.name) {
+        response.headers_mut().insert(VIRTUAL_MODEL_HEADER, v);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(stage: VirtualStepStage, model: &str, prompt: Option<&str>) -> VirtualModelStep {
+        VirtualModelStep {
+            stage,
+            model: model.to_string(),
+            system_prompt: None,
+            prompt: prompt.map(|p| p.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_split_steps_defaults_last_step_to_main() {
+        let model = VirtualModel {
+            name: "translate-then-answer".to_string(),
+            enabled: true,
+            steps: vec![
+                step(VirtualStepStage::Pre, "gemini-2.5-flash", Some("Translate to English:\n{{input}}")),
+                step(VirtualStepStage::Pre, "gemini-2.5-pro", None),
+            ],
+        };
+        let (pre, main, post) = split_steps(&model);
+        assert_eq!(pre.len(), 1);
+        assert_eq!(main.unwrap().model, "gemini-2.5-pro");
+        assert!(post.is_empty());
+        assert_eq!(render_prompt(pre[0], "你好"), "Translate to English:\n你好");
+    }
+
+    #[test]
+    fn test_main_body_replaces_last_user_text_and_keeps_images() {
+        let client = json!({
+            "model": "translate-then-answer",
+            "temperature": 0.3,
+            "messages": [
+                { "role": "user", "content": "first" },
+                { "role": "assistant", "content": "ok" },
+                { "role": "user", "content": [
+                    { "type": "text", "text": "这是什么" },
+                    { "type": "image_url", "image_url": { "url": "data:image/png;base64,AA" } }
+                ]}
+            ]
+        });
+        let mut main = step(VirtualStepStage::Main, "gemini-2.5-pro", None);
+        main.system_prompt = Some("Answer in English.".to_string());
+        let body = main_body(&main, &client, Some("What is this".to_string()));
+        assert_eq!(body["model"], "gemini-2.5-pro");
+        assert_eq!(body["temperature"], client["temperature"]);
+        assert_eq!(body["messages"][0]["role"], "system");
+        let last = &body["messages"][3]["content"];
+        assert_eq!(last[0]["text"], "What is this");
+        assert_eq!(last[1]["type"], "image_url");
+        assert_eq!(body["messages"][1]["content"], "first");
+    }
+}
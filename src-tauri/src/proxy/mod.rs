@@ -23,6 +23,22 @@ pub mod session_manager;   // 会话指纹管理
 pub mod audio;             // 音频处理模块
 pub mod signature_cache;   // Signature Cache (v3.3.16)
 pub mod cli_sync;          // CLI 配置同步 (v3.3.35)
+pub mod batch;             // Batch API 后台执行
+pub mod openapi;           // OpenAPI 文档与 API 浏览器
+pub mod ab_test;           // A/B 分流实验
+pub mod tool_call_ids;     // 工具调用 ID 映射表
+pub mod inflight;          // 在途请求登记与取消
+pub mod quota_learning;    // 429 配额窗口学习
+pub mod usage_ledger;      // 账号每日 token 用量 (公平调度)
+pub mod presets;           // 提示词预设 (preset:<name> 伪模型)
+pub mod profiles;          // 配置档案 (整套设置切换 / X-AG-Profile)
+pub mod state_journal;     // 调度状态崩溃恢复日志
+pub mod state_store;       // 外部状态存储 (多副本共享账号/限流状态)
+pub mod local_socket;      // 本地套接字 / 命名管道监听
+pub mod mdns;              // 局域网 mDNS 服务广播
+pub mod telemetry;         // OpenTelemetry 链路追踪导出 (OTLP/HTTP)
+pub mod latency;           // 请求延迟统计与延迟预算
+pub mod circuit_breaker;   // 按 (账号, 端点) 的熔断器
 
 
 pub use config::ProxyConfig;
@@ -33,6 +49,7 @@ pub use token_manager::TokenManager;
 pub use server::AxumServer;
 pub use security::ProxySecurityConfig;
 pub use signature_cache::SignatureCache;
+pub use tool_call_ids::ToolCallIdTable;
 
 #[cfg(test)]
 pub mod tests;
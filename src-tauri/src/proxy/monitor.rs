@@ -21,6 +21,26 @@ pub struct ProxyRequestLog {
     pub input_tokens: Option<u32>,
     pub output_tokens: Option<u32>,
     pub protocol: Option<String>,     // 协议类型: "openai", "anthropic", "gemini"
+    #[serde(default)]
+    pub session_id: Option<String>,   // 会话指纹 (用于日志按对话分组)
+    #[serde(default)]
+    pub notes: Option<String>,        // 处理备注 (如 JSON 修复结果)，来自 X-AG-Notes 响应头
+    #[serde(default)]
+    pub tags: Option<String>,         // 请求标签 (X-AG-Tag 请求头，规范化为 "key=value,key2")
+    #[serde(default)]
+    pub ttft_ms: Option<u64>,         // 首个 token 耗时 (非流式为完整响应耗时)
+    #[serde(default)]
+    pub tokens_per_sec: Option<f64>,  // 输出速率 (首 token 之后的生成阶段)
+    #[serde(default)]
+    pub chunk_count: Option<u32>,     // SSE 事件数
+    #[serde(default)]
+    pub message_id: Option<String>,   // 会话树节点 (截至最后一条消息的历史哈希)，重放同一提示词得到同一节点
+    #[serde(default)]
+    pub parent_message_id: Option<String>, // 父节点 (上一轮助手回复之前的历史哈希)，编辑后重发形成兄弟分支
+    #[serde(default)]
+    pub error_code: Option<String>,   // 错误指纹识别出的错误码 (X-AG-Error-Code)
+    #[serde(default)]
+    pub error_hint: Option<String>,   // 对应的处理建议
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -35,6 +55,8 @@ pub struct ProxyMonitor {
     pub stats: RwLock<ProxyStats>,
     pub max_logs: usize,
     pub enabled: AtomicBool,
+    /// 最近一分钟的请求时间戳 (毫秒)，不受 enabled 影响，供托盘显示每分钟请求数
+    recent_requests: std::sync::Mutex<VecDeque<i64>>,
     app_handle: Option<tauri::AppHandle>,
 }
 
@@ -45,37 +67,48 @@ impl ProxyMonitor {
             tracing::error!("Failed to initialize proxy DB: {}", e);
         }
 
-        // Auto cleanup old logs (keep last 30 days)
-        tokio::spawn(async {
-            match crate::modules::proxy_db::cleanup_old_logs(30) {
-                Ok(deleted) => {
-                    if deleted > 0 {
-                        tracing::info!("Auto cleanup: removed {} old logs (>30 days)", deleted);
-                    }
-                }
-                Err(e) => {
-                    tracing::error!("Failed to cleanup old logs: {}", e);
-                }
-            }
-        });
+        // 过期日志由 modules::retention 的后台任务按保留策略清理
 
         Self {
             logs: RwLock::new(VecDeque::with_capacity(max_logs)),
             stats: RwLock::new(ProxyStats::default()),
             max_logs,
             enabled: AtomicBool::new(false), // Default to disabled
+            recent_requests: std::sync::Mutex::new(VecDeque::new()),
             app_handle,
         }
     }
 
     pub fn set_enabled(&self, enabled: bool) {
         self.enabled.store(enabled, Ordering::Relaxed);
+        // 监控开启后公平调度改用实际 usage
+        crate::proxy::usage_ledger::DailyUsageLedger::global().set_actual_source(enabled);
     }
 
     pub fn is_enabled(&self) -> bool {
         self.enabled.load(Ordering::Relaxed)
     }
 
+    /// 记录一次请求 (仅计数，用于每分钟请求数)
+    pub fn record_hit(&self) {
+        let now = chrono::Utc::now().timestamp_millis();
+        if let Ok(mut recent) = self.recent_requests.lock() {
+            recent.push_back(now);
+            while recent.front().is_some_and(|t| now - t > 60_000) {
+                recent.pop_front();
+            }
+        }
+    }
+
+    /// 最近 60 秒内的请求数
+    pub fn requests_per_minute(&self) -> usize {
+        let now = chrono::Utc::now().timestamp_millis();
+        self.recent_requests
+            .lock()
+            .map(|recent| recent.iter().filter(|t| now - **t <= 60_000).count())
+            .unwrap_or(0)
+    }
+
     pub async fn log_request(&self, log: ProxyRequestLog) {
         if !self.is_enabled() {
             return;
@@ -101,6 +134,13 @@ impl ProxyMonitor {
             logs.push_front(log.clone());
         }
 
+        if let (Some(account), Some(input), Some(output)) = (&log.account_email, log.input_tokens, log.output_tokens) {
+            crate::proxy::usage_ledger::DailyUsageLedger::global().record_actual(account, input as u64 + output as u64);
+        }
+
+        // 多副本部署时镜像到共享存储
+        crate::proxy::state_store::record_history(&log);
+
         // Save to DB
         let log_to_save = log.clone();
         tokio::spawn(async move {
@@ -115,7 +155,7 @@ impl ProxyMonitor {
                 log_to_save.output_tokens,
             ) {
                 let model = log_to_save.model.clone().unwrap_or_else(|| "unknown".to_string());
-                if let Err(e) = crate::modules::token_stats::record_usage(account, &model, input, output) {
+                if let Err(e) = crate::modules::token_stats::record_usage(account, &model, input, output, log_to_save.tags.as_deref()) {
                     tracing::debug!("Failed to record token stats: {}", e);
                 }
             }
@@ -139,6 +179,16 @@ impl ProxyMonitor {
                 input_tokens: log.input_tokens,
                 output_tokens: log.output_tokens,
                 protocol: log.protocol.clone(),
+                session_id: log.session_id.clone(),
+                notes: log.notes.clone(),
+                tags: log.tags.clone(),
+                ttft_ms: log.ttft_ms,
+                tokens_per_sec: log.tokens_per_sec,
+                chunk_count: log.chunk_count,
+                message_id: log.message_id.clone(),
+                parent_message_id: log.parent_message_id.clone(),
+                error_code: log.error_code.clone(),
+                error_hint: log.error_hint.clone(),
             };
             let _ = app.emit("proxy://request", &log_summary);
         }
@@ -0,0 +1,595 @@
+// OpenAPI 3.1 文档与内置 API 浏览器 (/openapi.json, /docs)
+// ENDPOINTS 是文档的唯一数据源；测试会对照 server.rs 中注册的路由，新增路由未写文档时测试失败。
+
+use axum::{
+    extract::Host,
+    http::header,
+    response::{Html, IntoResponse, Json, Response},
+};
+use serde_json::{json, Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Body {
+    None,
+    Json(&'static str),
+    Multipart(&'static str),
+    /// multipart/form-data 与 JSON 均可 (同一 schema)
+    MultipartOrJson(&'static str),
+}
+
+struct EndpointDoc {
+    method: &'static str,
+    /// axum 路由路径 (`:param` 形式)
+    route: &'static str,
+    /// 文档展示路径，None 时由 route 转换
+    display: Option<&'static str>,
+    tag: &'static str,
+    summary: &'static str,
+    body: Body,
+    /// 支持 SSE 流式响应
+    streaming: bool,
+}
+
+const fn ep(method: &'static str, route: &'static str, tag: &'static str, summary: &'static str, body: Body) -> EndpointDoc {
+    EndpointDoc { method, route, display: None, tag, summary, body, streaming: false }
+}
+
+const fn stream(mut doc: EndpointDoc) -> EndpointDoc {
+    doc.streaming = true;
+    doc
+}
+
+const fn display(mut doc: EndpointDoc, path: &'static str) -> EndpointDoc {
+    doc.display = Some(path);
+    doc
+}
+
+const ENDPOINTS: &[EndpointDoc] = &[
+    // OpenAI
+    ep("get", "/v1/models", "OpenAI", "List available models", Body::None),
+    stream(ep("post", "/v1/chat/completions", "OpenAI", "Create a chat completion", Body::Json("ChatCompletionRequest"))),
+    stream(ep("post", "/v1/completions", "OpenAI", "Create a legacy text completion", Body::Json("CompletionRequest"))),
+    stream(ep("post", "/v1/responses", "OpenAI", "Create a response (Responses API / Codex CLI)", Body::Json("ResponsesRequest"))),
+    ep("post", "/v1/images/generations", "OpenAI", "Generate images", Body::Json("ImageGenerationRequest")),
+    ep("post", "/v1/images/edits", "OpenAI", "Edit one or more images (multipart upload or JSON with base64 / data URLs)", Body::MultipartOrJson("ImageEditRequest")),
+    ep("post", "/v1/audio/transcriptions", "OpenAI", "Transcribe audio", Body::Multipart("TranscriptionRequest")),
+    ep("post", "/v1/audio/speech", "OpenAI", "Synthesize speech", Body::Json("SpeechRequest")),
+    ep("post", "/v1/moderations", "OpenAI", "Classify content against moderation categories", Body::Json("ModerationRequest")),
+    stream(ep("post", "/v1/summarize", "Utility", "Summarize arbitrarily long text with chunked map-reduce (non-standard; streams by default)", Body::Json("SummarizeRequest"))),
+    ep("post", "/v1/ocr", "Utility", "Extract text blocks from an image via a Gemini vision prompt (non-standard)", Body::Json("OcrRequest")),
+    ep("post", "/v1/rerank", "Utility", "Score documents against a query and return them by relevance (Cohere / Jina compatible)", Body::Json("RerankRequest")),
+    ep("post", "/v2/rerank", "Utility", "Alias of /v1/rerank for Cohere v2 clients", Body::Json("RerankRequest")),
+    stream(ep("post", "/v1/fanout", "Utility", "Send one chat request to several models / accounts in parallel; multiplex all results or race for the first token (non-standard)", Body::Json("FanoutRequest"))),
+    // Batch
+    ep("get", "/v1/files", "Batch", "List uploaded files", Body::None),
+    ep("post", "/v1/files", "Batch", "Upload a JSONL batch input file", Body::Multipart("FileUploadRequest")),
+    ep("get", "/v1/files/:id", "Batch", "Retrieve file metadata", Body::None),
+    ep("delete", "/v1/files/:id", "Batch", "Delete a file", Body::None),
+    ep("get", "/v1/files/:id/content", "Batch", "Download file content (partial results while a batch runs)", Body::None),
+    ep("get", "/v1/batches", "Batch", "List batches", Body::None),
+    ep("post", "/v1/batches", "Batch", "Create a batch", Body::Json("CreateBatchRequest")),
+    ep("get", "/v1/batches/:id", "Batch", "Retrieve a batch", Body::None),
+    ep("post", "/v1/batches/:id/cancel", "Batch", "Cancel a batch", Body::None),
+    // Anthropic
+    stream(ep("post", "/v1/messages", "Anthropic", "Create a message", Body::Json("MessagesRequest"))),
+    ep("post", "/v1/messages/count_tokens", "Anthropic", "Count tokens for a message request", Body::Json("MessagesRequest")),
+    ep("get", "/v1/models/claude", "Anthropic", "List Claude-compatible models", Body::None),
+    // Gemini
+    ep("get", "/v1beta/models", "Gemini", "List Gemini models", Body::None),
+    ep("get", "/v1beta/models/:model", "Gemini", "Get model information", Body::None),
+    display(
+        ep("post", "/v1beta/models/:model", "Gemini", "generateContent / streamGenerateContent (`{model}` is `name:method`)", Body::Json("GenerateContentRequest")),
+        "/v1beta/models/{model}:generateContent",
+    ),
+    ep("post", "/v1beta/models/:model/countTokens", "Gemini", "Count tokens", Body::Json("GenerateContentRequest")),
+    // z.ai MCP
+    ep("post", "/mcp/web_search_prime/mcp", "MCP", "z.ai web search MCP (reverse proxy)", Body::Json("JsonRpcRequest")),
+    ep("post", "/mcp/web_reader/mcp", "MCP", "z.ai web reader MCP (reverse proxy)", Body::Json("JsonRpcRequest")),
+    ep("post", "/mcp/zai-mcp-server/mcp", "MCP", "Built-in z.ai vision MCP server", Body::Json("JsonRpcRequest")),
+    // Utility
+    ep("post", "/v1/models/detect", "Utility", "Resolve model mapping and capabilities", Body::Json("DetectModelRequest")),
+    ep("post", "/v1/cancel/:request_id", "Utility", "Cancel an in-flight generation by its X-AG-Request-Id (only with the API key that started it)", Body::None),
+    ep("post", "/internal/warmup", "Utility", "Warm up an account/model pair", Body::Json("WarmupRequest")),
+    ep("post", "/v1/api/event_logging", "Utility", "Telemetry sink (always 200)", Body::None),
+    ep("post", "/v1/api/event_logging/batch", "Utility", "Telemetry sink (always 200)", Body::None),
+    ep("get", "/healthz", "Utility", "Liveness check (process up, version, uptime)", Body::None),
+    ep("get", "/readyz", "Utility", "Readiness check: a usable account and a reachable upstream (503 when not ready)", Body::None),
+    ep("get", "/openapi.json", "Utility", "This OpenAPI document", Body::None),
+    ep("get", "/docs", "Utility", "Interactive API explorer", Body::None),
+    // Admin
+    ep("get", "/admin/accounts", "Admin", "List accounts", Body::None),
+    ep("post", "/admin/accounts", "Admin", "Add an account by refresh token", Body::Json("AddAccountRequest")),
+    ep("post", "/admin/accounts/reorder", "Admin", "Reorder accounts", Body::Json("ReorderAccountsRequest")),
+    ep("post", "/admin/accounts/bulk", "Admin", "Run a bulk action across accounts (refresh tokens, verify projects, pause/resume by tag, purge failed)", Body::Json("BulkAction")),
+    ep("delete", "/admin/accounts/:id", "Admin", "Delete an account", Body::None),
+    ep("post", "/admin/accounts/:id/status", "Admin", "Enable or disable an account", Body::Json("AccountStatusRequest")),
+    ep("post", "/admin/accounts/:id/pause", "Admin", "Pause an account for a number of minutes or until resumed (body optional)", Body::Json("PauseAccountRequest")),
+    ep("post", "/admin/accounts/:id/resume", "Admin", "Resume a paused account", Body::None),
+    ep("get", "/admin/stats", "Admin", "Request statistics", Body::None),
+    ep("get", "/admin/inflight", "Admin", "In-flight and queued requests", Body::None),
+    ep("get", "/admin/circuits", "Admin", "Per account/endpoint circuit breaker state and counters", Body::None),
+    ep("post", "/admin/circuits/reset", "Admin", "Close circuit breakers (one account or all)", Body::Json("ResetCircuitsRequest")),
+    ep("get", "/admin/quota-profiles", "Admin", "Per-account quota windows learned from 429s", Body::None),
+    ep("get", "/admin/config", "Admin", "Get proxy configuration", Body::None),
+    ep("put", "/admin/config", "Admin", "Update proxy configuration", Body::Json("ProxyConfig")),
+];
+
+/// `:param` -> `{param}`
+fn to_openapi_path(route: &str) -> String {
+    route
+        .split('/')
+        .map(|seg| match seg.strip_prefix(':') {
+            Some(name) => format!("{{{}}}", name),
+            None => seg.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn path_params(path: &str) -> Vec<Value> {
+    path.split(|c| c == '{' || c == '}')
+        .skip(1)
+        .step_by(2)
+        .map(|name| json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } }))
+        .collect()
+}
+
+fn operation(doc: &EndpointDoc, path: &str) -> Value {
+    let mut op = json!({
+        "tags": [doc.tag],
+        "summary": doc.summary,
+        "operationId": format!("{}_{}", doc.method, path.trim_matches('/').replace(['/', '{', '}', ':', '-', '.'], "_")),
+        "responses": {
+            "200": {
+                "description": "Success",
+                "content": { "application/json": { "schema": { "type": "object" } } }
+            },
+            "400": { "$ref": "#/components/responses/Error" },
+            "401": { "$ref": "#/components/responses/Error" },
+            "413": { "$ref": "#/components/responses/Error" },
+            "429": { "$ref": "#/components/responses/Error" }
+        }
+    });
+    if doc.streaming {
+        op["responses"]["200"]["content"]["text/event-stream"] = json!({
+            "schema": { "type": "string", "description": "Server-sent events when `stream: true`" }
+        });
+    }
+
+    let params = path_params(path);
+    if !params.is_empty() {
+        op["parameters"] = Value::Array(params);
+    }
+
+    let content: &[&str] = match doc.body {
+        Body::None => &[],
+        Body::Json(_) => &["application/json"],
+        Body::Multipart(_) => &["multipart/form-data"],
+        Body::MultipartOrJson(_) => &["multipart/form-data", "application/json"],
+    };
+    if let Body::Json(schema) | Body::Multipart(schema) | Body::MultipartOrJson(schema) = doc.body {
+        let reference = json!({ "schema": { "$ref": format!("#/components/schemas/{}", schema) } });
+        let content: serde_json::Map<String, Value> = content.iter().map(|mime| (mime.to_string(), reference.clone())).collect();
+        op["requestBody"] = json!({ "required": true, "content": content });
+    }
+    if matches!(doc.route, "/healthz" | "/readyz" | "/openapi.json" | "/docs") {
+        op["security"] = json!([]);
+    }
+    op
+}
+
+fn message_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["role"],
+        "properties": {
+            "role": { "type": "string", "enum": ["system", "developer", "user", "assistant", "tool"] },
+            "content": {
+                "description": "Text or an array of content parts (text / image_url)",
+                "oneOf": [{ "type": "string" }, { "type": "array", "items": { "type": "object" } }, { "type": "null" }]
+            },
+            "tool_calls": { "type": "array", "items": { "type": "object" } },
+            "tool_call_id": { "type": "string" }
+        }
+    })
+}
+
+fn schemas() -> Value {
+    json!({
+        "Error": {
+            "type": "object",
+            "properties": {
+                "error": {
+                    "type": "object",
+                    "properties": {
+                        "message": { "type": "string" },
+                        "type": { "type": "string" },
+                        "code": { "type": ["string", "null"] }
+                    }
+                }
+            }
+        },
+        "ChatMessage": message_schema(),
+        "ChatCompletionRequest": {
+            "type": "object",
+            "required": ["model", "messages"],
+            "properties": {
+                "model": { "type": "string", "examples": ["gemini-2.5-flash"] },
+                "messages": { "type": "array", "items": { "$ref": "#/components/schemas/ChatMessage" } },
+                "stream": { "type": "boolean", "default": false },
+                "temperature": { "type": "number" },
+                "top_p": { "type": "number" },
+                "max_tokens": { "type": "integer" },
+                "stop": { "oneOf": [{ "type": "string" }, { "type": "array", "items": { "type": "string" } }] },
+                "tools": { "type": "array", "items": { "type": "object" } },
+                "tool_choice": {},
+                "response_format": { "type": "object" },
+                "reasoning_effort": { "type": "string", "enum": ["low", "medium", "high"] },
+                "best_of": { "type": "integer", "minimum": 1, "description": "Generate several candidates and return the one picked by the judge model (non-streaming only)" },
+                "user": { "type": "string", "description": "Also used as conversation id when no X-Conversation-Id is sent" },
+                "conversation_id": { "type": "string" },
+                "seed": { "type": "integer" },
+                "size": { "type": "string", "description": "Image models only: mapped to imageConfig.aspectRatio" },
+                "aspect_ratio": { "type": "string", "description": "Image models only (vendor extension), e.g. 16:9" },
+                "image_size": { "type": "string", "enum": ["1K", "2K", "4K"], "description": "Image models only (vendor extension)" },
+                "negative_prompt": { "type": "string", "description": "Image models only (vendor extension)" }
+            }
+        },
+        "CompletionRequest": {
+            "type": "object",
+            "required": ["model", "prompt"],
+            "properties": {
+                "model": { "type": "string" },
+                "prompt": { "oneOf": [{ "type": "string" }, { "type": "array", "items": { "type": "string" } }] },
+                "suffix": { "type": "string", "description": "Text after the cursor; enables fill-in-the-middle" },
+                "echo": { "type": "boolean", "description": "Prepend the prompt to the completion" },
+                "stream": { "type": "boolean" },
+                "max_tokens": { "type": "integer" },
+                "temperature": { "type": "number" },
+                "stop": { "oneOf": [{ "type": "string" }, { "type": "array", "items": { "type": "string" } }] }
+            }
+        },
+        "ResponsesRequest": {
+            "type": "object",
+            "required": ["model"],
+            "properties": {
+                "model": { "type": "string" },
+                "instructions": { "type": "string" },
+                "input": { "oneOf": [{ "type": "string" }, { "type": "array", "items": { "type": "object" } }] },
+                "stream": { "type": "boolean" },
+                "tools": { "type": "array", "items": { "type": "object" } }
+            }
+        },
+        "ImageGenerationRequest": {
+            "type": "object",
+            "required": ["prompt"],
+            "properties": {
+                "prompt": { "type": "string" },
+                "model": { "type": "string", "default": "gemini-3-pro-image", "description": "Suffixes such as -2k / -4k / -16x9 select imageConfig" },
+                "n": { "type": "integer", "default": 1, "minimum": 1, "maximum": 10 },
+                "size": { "type": "string", "examples": ["1024x1024"], "description": "Mapped to the closest supported aspect ratio" },
+                "quality": { "type": "string" },
+                "response_format": { "type": "string", "enum": ["b64_json", "url"] },
+                "aspect_ratio": { "type": "string", "enum": ["1:1", "2:3", "3:2", "3:4", "4:3", "4:5", "5:4", "9:16", "16:9", "21:9"], "description": "Vendor extension; overrides size" },
+                "image_size": { "type": "string", "enum": ["1K", "2K", "4K"], "description": "Vendor extension" },
+                "negative_prompt": { "type": "string", "description": "Vendor extension; appended as an instruction" },
+                "seed": { "type": "integer", "description": "Vendor extension; image i uses seed + i and is echoed on each data item" }
+            }
+        },
+        "ImageEditRequest": {
+            "type": "object",
+            "required": ["image", "prompt"],
+            "properties": {
+                "image": {
+                    "description": "Image file(s) (multipart, repeat `image[]` for several) or base64 / data URL string(s) (JSON)",
+                    "oneOf": [{ "type": "string" }, { "type": "array", "items": { "type": "string" } }]
+                },
+                "mask": { "type": "string", "description": "Optional mask; transparent areas mark the region to edit" },
+                "prompt": { "type": "string" },
+                "model": { "type": "string", "default": "gemini-3-pro-image", "description": "Suffixes such as -2k / -4k / -16x9 select imageConfig" },
+                "n": { "type": "integer", "default": 1 },
+                "size": { "type": "string", "description": "Mapped to an aspect ratio; omit to keep the input's aspect ratio" },
+                "response_format": { "type": "string", "enum": ["b64_json", "url"], "default": "b64_json" },
+                "aspect_ratio": { "type": "string", "description": "Vendor extension; overrides size" },
+                "image_size": { "type": "string", "enum": ["1K", "2K", "4K"] },
+                "negative_prompt": { "type": "string" }
+            }
+        },
+        "TranscriptionRequest": {
+            "type": "object",
+            "required": ["file"],
+            "properties": {
+                "file": { "type": "string", "format": "binary" },
+                "model": { "type": "string", "examples": ["whisper-1"] },
+                "prompt": { "type": "string" },
+                "language": { "type": "string" },
+                "temperature": { "type": "number" },
+                "response_format": { "type": "string", "enum": ["json", "text", "verbose_json", "srt", "vtt"], "default": "json" }
+            }
+        },
+        "SpeechRequest": {
+            "type": "object",
+            "required": ["input"],
+            "properties": {
+                "model": { "type": "string", "examples": ["tts-1"] },
+                "input": { "type": "string" },
+                "voice": { "type": "string", "examples": ["alloy", "Kore"] },
+                "instructions": { "type": "string" },
+                "speed": { "type": "number" },
+                "response_format": { "type": "string", "enum": ["mp3", "opus", "aac", "flac", "wav", "pcm", "ogg"], "default": "mp3" }
+            }
+        },
+        "ModerationRequest": {
+            "type": "object",
+            "required": ["input"],
+            "properties": {
+                "model": { "type": "string", "examples": ["omni-moderation-latest"] },
+                "input": { "oneOf": [{ "type": "string" }, { "type": "array", "items": {} }] }
+            }
+        },
+        "SummarizeRequest": {
+            "type": "object",
+            "required": ["input"],
+            "properties": {
+                "input": {
+                    "description": "Text to summarize, or an array of documents joined with blank lines",
+                    "oneOf": [{ "type": "string" }, { "type": "array", "items": { "type": "string" } }]
+                },
+                "model": { "type": "string", "default": "gemini-2.5-flash" },
+                "instructions": { "type": "string", "description": "Extra guidance applied to every map and reduce step" },
+                "chunk_tokens": { "type": "integer", "default": 100000, "minimum": 1000, "description": "Approximate tokens per chunk" },
+                "stream": { "type": "boolean", "default": true, "description": "Progress is reported as SSE comments (`: map 3/10`) before the merged summary streams as chat.completion.chunk events" }
+            }
+        },
+        "OcrRequest": {
+            "type": "object",
+            "required": ["image"],
+            "properties": {
+                "image": { "type": "string", "description": "Data URL, http(s) URL or bare base64 image bytes" },
+                "model": { "type": "string", "default": "gemini-2.5-flash" },
+                "language": { "type": "string", "description": "Optional hint for the expected text language" }
+            }
+        },
+        "RerankRequest": {
+            "type": "object",
+            "required": ["query", "documents"],
+            "properties": {
+                "query": { "type": "string" },
+                "documents": {
+                    "type": "array",
+                    "description": "Strings, `{text}` objects, or other JSON objects (scored as JSON text)",
+                    "items": { "oneOf": [{ "type": "string" }, { "type": "object" }] }
+                },
+                "model": { "type": "string", "default": "gemini-2.5-flash", "description": "Non-Gemini names (e.g. rerank-english-v3.0) use the default model" },
+                "top_n": { "type": "integer", "minimum": 1 },
+                "return_documents": { "type": "boolean", "default": true },
+                "max_tokens_per_doc": { "type": "integer", "default": 4096, "description": "Longer documents are truncated (local token estimate)" }
+            }
+        },
+        "FanoutRequest": {
+            "type": "object",
+            "required": ["messages", "fanout"],
+            "description": "A chat completion request plus the fan-out fields below",
+            "properties": {
+                "model": { "type": "string", "description": "Default model for the targets" },
+                "messages": { "type": "array", "items": { "type": "object" } },
+                "fanout": {
+                    "description": "Number of parallel requests on `model` (each on a different account), or a list of targets",
+                    "oneOf": [
+                        { "type": "integer", "minimum": 2, "maximum": 8 },
+                        {
+                            "type": "array",
+                            "maxItems": 8,
+                            "items": {
+                                "oneOf": [
+                                    { "type": "string", "description": "Model name" },
+                                    {
+                                        "type": "object",
+                                        "properties": {
+                                            "model": { "type": "string" },
+                                            "account": { "type": "string", "description": "Account id or email" }
+                                        }
+                                    }
+                                ]
+                            }
+                        }
+                    ]
+                },
+                "race": { "type": "boolean", "default": false, "description": "Return only the first target to produce a token and cancel the others" },
+                "stream": { "type": "boolean", "default": false }
+            }
+        },
+        "FileUploadRequest": {
+            "type": "object",
+            "required": ["file"],
+            "properties": {
+                "file": { "type": "string", "format": "binary" },
+                "purpose": { "type": "string", "default": "batch" }
+            }
+        },
+        "CreateBatchRequest": {
+            "type": "object",
+            "required": ["input_file_id", "endpoint"],
+            "properties": {
+                "input_file_id": { "type": "string" },
+                "endpoint": { "type": "string", "enum": crate::proxy::batch::SUPPORTED_ENDPOINTS },
+                "completion_window": { "type": "string", "default": "24h" },
+                "metadata": { "type": "object", "additionalProperties": { "type": "string" } }
+            }
+        },
+        "MessagesRequest": {
+            "type": "object",
+            "required": ["model", "messages"],
+            "properties": {
+                "model": { "type": "string", "examples": ["claude-sonnet-4-5"] },
+                "system": { "oneOf": [{ "type": "string" }, { "type": "array", "items": { "type": "object" } }] },
+                "messages": { "type": "array", "items": { "type": "object" } },
+                "max_tokens": { "type": "integer" },
+                "stream": { "type": "boolean" },
+                "temperature": { "type": "number" },
+                "tools": { "type": "array", "items": { "type": "object" } },
+                "thinking": { "type": "object" },
+                "metadata": { "type": "object" }
+            }
+        },
+        "GenerateContentRequest": {
+            "type": "object",
+            "required": ["contents"],
+            "properties": {
+                "contents": { "type": "array", "items": { "type": "object" } },
+                "systemInstruction": { "type": "object" },
+                "generationConfig": { "type": "object" },
+                "tools": { "type": "array", "items": { "type": "object" } },
+                "safetySettings": { "type": "array", "items": { "type": "object" } }
+            }
+        },
+        "JsonRpcRequest": {
+            "type": "object",
+            "properties": {
+                "jsonrpc": { "type": "string", "const": "2.0" },
+                "id": {},
+                "method": { "type": "string" },
+                "params": { "type": "object" }
+            }
+        },
+        "DetectModelRequest": {
+            "type": "object",
+            "required": ["model"],
+            "properties": { "model": { "type": "string" } }
+        },
+        "WarmupRequest": {
+            "type": "object",
+            "properties": { "email": { "type": "string" }, "model": { "type": "string" } }
+        },
+        "AddAccountRequest": {
+            "type": "object",
+            "required": ["refresh_token"],
+            "properties": { "refresh_token": { "type": "string" } }
+        },
+        "ReorderAccountsRequest": {
+            "type": "object",
+            "required": ["account_ids"],
+            "properties": { "account_ids": { "type": "array", "items": { "type": "string" } } }
+        },
+        "BulkAction": {
+            "type": "object",
+            "required": ["action"],
+            "properties": {
+                "action": { "type": "string", "enum": ["refresh_tokens", "verify_projects", "pause_tag", "resume_tag", "purge_failed"] },
+                "tag": { "type": "string", "description": "pause_tag / resume_tag: accounts carrying this tag" },
+                "minutes": { "type": "integer", "minimum": 1, "description": "pause_tag: pause duration; omit to pause until resumed" },
+                "reason": { "type": "string", "description": "pause_tag" },
+                "older_than_days": { "type": "integer", "default": 7, "description": "purge_failed: delete accounts disabled for longer than this" },
+                "dry_run": { "type": "boolean", "default": false, "description": "purge_failed: only report what would be deleted" }
+            }
+        },
+        "AccountStatusRequest": {
+            "type": "object",
+            "required": ["disabled"],
+            "properties": { "disabled": { "type": "boolean" }, "reason": { "type": "string" } }
+        },
+        "PauseAccountRequest": {
+            "type": "object",
+            "properties": {
+                "minutes": { "type": "integer", "minimum": 1, "description": "Pause duration; omit to pause until resumed" },
+                "reason": { "type": "string" }
+            }
+        },
+        "ResetCircuitsRequest": {
+            "type": "object",
+            "properties": { "account": { "type": "string", "description": "Account email; omit to reset all" } }
+        },
+        "ProxyConfig": { "type": "object", "description": "Full proxy configuration object (see GET /admin/config)" }
+    })
+}
+
+/// 生成 OpenAPI 3.1 文档
+pub fn build_spec(server_url: Option<&str>) -> Value {
+    let mut paths = Map::new();
+    for doc in ENDPOINTS {
+        let path = doc.display.map(str::to_string).unwrap_or_else(|| to_openapi_path(doc.route));
+        let entry = paths.entry(path.clone()).or_insert_with(|| json!({}));
+        entry[doc.method] = operation(doc, &path);
+    }
+
+    let mut spec = json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Antigravity Tools API Proxy",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "OpenAI, Anthropic and Gemini compatible endpoints served by the local proxy."
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas(),
+            "responses": {
+                "Error": {
+                    "description": "Error",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } }
+                }
+            },
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer" },
+                "apiKeyHeader": { "type": "apiKey", "in": "header", "name": "x-api-key" }
+            }
+        },
+        "security": [{ "bearerAuth": [] }, { "apiKeyHeader": [] }]
+    });
+    if let Some(url) = server_url {
+        spec["servers"] = json!([{ "url": url }]);
+    }
+    spec
+}
+
+/// GET /openapi.json
+pub async fn handle_openapi_json(Host(host): Host) -> Response {
+    Json(build_spec(Some(&format!("http://{}", host)))).into_response()
+}
+
+/// GET /docs
+pub async fn handle_docs() -> Response {
+    (
+        [(header::CACHE_CONTROL, "no-cache")],
+        Html(include_str!("openapi_explorer.html")),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_conversion() {
+        assert_eq!(to_openapi_path("/v1/batches/:id/cancel"), "/v1/batches/{id}/cancel");
+        assert_eq!(path_params("/v1/files/{id}/content").len(), 1);
+        assert!(path_params("/v1/models").is_empty());
+    }
+
+    #[test]
+    fn test_spec_references_resolve() {
+        let spec = build_spec(None);
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        for doc in ENDPOINTS {
+            if let Body::Json(name) | Body::Multipart(name) | Body::MultipartOrJson(name) = doc.body {
+                assert!(schemas.contains_key(name), "missing schema {}", name);
+            }
+        }
+        assert_eq!(spec["paths"]["/v1/batches/{id}"]["get"]["parameters"][0]["name"], "id");
+        assert!(spec["paths"]["/v1/chat/completions"]["post"]["responses"]["200"]["content"]["text/event-stream"].is_object());
+    }
+
+    /// server.rs 中的每个路由都必须有文档，反之亦然
+    #[test]
+    fn test_every_route_is_documented() {
+        let source = include_str!("server.rs");
+        let re = regex::Regex::new(r#"\.route\(\s*"([^"]+)""#).unwrap();
+        let routed: std::collections::HashSet<&str> = re.captures_iter(source).map(|c| c.get(1).unwrap().as_str()).collect();
+        let documented: std::collections::HashSet<&str> = ENDPOINTS.iter().map(|d| d.route).collect();
+
+        let undocumented: Vec<_> = routed.difference(&documented).collect();
+        assert!(undocumented.is_empty(), "routes without OpenAPI docs: {:?}", undocumented);
+        let stale: Vec<_> = documented.difference(&routed).collect();
+        assert!(stale.is_empty(), "documented routes that are not registered: {:?}", stale);
+    }
+}
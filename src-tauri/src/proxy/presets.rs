@@ -0,0 +1,190 @@
+// 提示词预设 (Prompt Presets)
+// 命名模板: 系统提示词 / 温度 / 实际模型 / 注入工具。客户端以伪模型名 `preset:<name>` 选用，
+// 使无法自定义参数的客户端也能获得统一调校过的行为。请求改写见 middleware::presets。
+// 预设保存在数据目录的 prompt_presets.json，通过 Tauri 命令管理。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// 客户端选择预设的伪模型名前缀
+pub const PRESET_MODEL_PREFIX: &str = "preset:";
+
+const PRESETS_FILE: &str = "prompt_presets.json";
+
+/// 注入的工具定义 (与协议无关，按请求协议转换为 OpenAI / Claude / Gemini 格式)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PresetTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// JSON Schema
+    #[serde(default = "default_parameters")]
+    pub parameters: Value,
+}
+
+fn default_parameters() -> Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PromptPreset {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// 实际请求的模型 (仍经过自定义映射)
+    pub model: String,
+    /// 插入在客户端系统提示词之前
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// 覆盖客户端的 temperature
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// 追加到请求的工具 (与客户端工具同名时跳过)
+    #[serde(default)]
+    pub tools: Vec<PresetTool>,
+}
+
+impl PromptPreset {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty()
+            || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err("preset_name_invalid: use letters, digits, '-', '_' or '.'".to_string());
+        }
+        if self.model.trim().is_empty() {
+            return Err("preset_model_required".to_string());
+        }
+        if self.model.starts_with(PRESET_MODEL_PREFIX) {
+            return Err("preset_model_cannot_reference_preset".to_string());
+        }
+        if let Some(t) = self.temperature {
+            if !(0.0..=2.0).contains(&t) {
+                return Err("preset_temperature_out_of_range".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct PresetStore {
+    presets: RwLock<Vec<PromptPreset>>,
+}
+
+fn presets_path() -> Result<std::path::PathBuf, String> {
+    Ok(crate::modules::account::get_data_dir()?.join(PRESETS_FILE))
+}
+
+fn load_from_disk() -> Vec<PromptPreset> {
+    let Ok(path) = presets_path() else {
+        return Vec::new();
+    };
+    if !path.exists() {
+        return Vec::new();
+    }
+    match std::fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|s| {
+        serde_json::from_str::<Vec<PromptPreset>>(&s).map_err(|e| e.to_string())
+    }) {
+        Ok(presets) => presets,
+        Err(e) => {
+            tracing::warn!("[Presets] 读取 {} 失败: {}", PRESETS_FILE, e);
+            Vec::new()
+        }
+    }
+}
+
+impl PresetStore {
+    /// Global singleton instance (首次使用时从磁盘加载)
+    pub fn global() -> Arc<Self> {
+        static INSTANCE: OnceLock<Arc<PresetStore>> = OnceLock::new();
+        INSTANCE
+            .get_or_init(|| {
+                Arc::new(PresetStore {
+                    presets: RwLock::new(load_from_disk()),
+                })
+            })
+            .clone()
+    }
+
+    pub fn list(&self) -> Vec<PromptPreset> {
+        self.presets.read().map(|p| p.clone()).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.presets.read().map(|p| p.is_empty()).unwrap_or(true)
+    }
+
+    pub fn get(&self, name: &str) -> Option<PromptPreset> {
+        self.presets.read().ok()?.iter().find(|p| p.name == name).cloned()
+    }
+
+    fn persist(presets: &[PromptPreset]) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(presets).map_err(|e| format!("failed_to_serialize_presets: {}", e))?;
+        std::fs::write(presets_path()?, content).map_err(|e| format!("failed_to_save_presets: {}", e))
+    }
+
+    /// 新增或按名称覆盖预设
+    pub fn save(&self, preset: PromptPreset) -> Result<(), String> {
+        preset.validate()?;
+        let mut presets = self.presets.write().map_err(|_| "preset_store_poisoned".to_string())?;
+        let mut next = presets.clone();
+        match next.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => next.push(preset),
+        }
+        Self::persist(&next)?;
+        *presets = next;
+        Ok(())
+    }
+
+    /// 删除预设，返回是否存在
+    pub fn delete(&self, name: &str) -> Result<bool, String> {
+        let mut presets = self.presets.write().map_err(|_| "preset_store_poisoned".to_string())?;
+        let before = presets.len();
+        let next: Vec<PromptPreset> = presets.iter().filter(|p| p.name != name).cloned().collect();
+        if next.len() == before {
+            return Ok(false);
+        }
+        Self::persist(&next)?;
+        *presets = next;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(name: &str, model: &str) -> PromptPreset {
+        PromptPreset {
+            name: name.to_string(),
+            description: None,
+            model: model.to_string(),
+            system_prompt: None,
+            temperature: None,
+            tools: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_preset_validation() {
+        assert!(preset("code-review", "gemini-2.5-pro").validate().is_ok());
+        assert!(preset("code review", "gemini-2.5-pro").validate().is_err());
+        assert!(preset("", "gemini-2.5-pro").validate().is_err());
+        assert!(preset("loop", "preset:loop").validate().is_err());
+        assert!(preset("x", " ").validate().is_err());
+
+        let mut hot = preset("hot", "gemini-2.5-flash");
+        hot.temperature = Some(3.0);
+        assert!(hot.validate().is_err());
+    }
+
+    #[test]
+    fn test_store_is_empty() {
+        let store = PresetStore { presets: RwLock::new(Vec::new()) };
+        assert!(store.is_empty());
+        store.presets.write().unwrap().push(preset("code-review", "gemini-2.5-pro"));
+        assert!(!store.is_empty());
+        assert!(store.get("code-review").is_some());
+    }
+}
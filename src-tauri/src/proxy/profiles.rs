@@ -0,0 +1,243 @@
+// 配置档案 (Config Profiles)
+// 一个档案是完整的反代配置快照 (模型映射、注入/实验性设置、端口等) + 可用账号子集 + 可选系统提示词，
+// 用于在 "编码 Agent 模式" / "纯聊天模式" 等整套设置之间一键切换:
+// - 激活档案: 用快照替换当前反代配置并热更新 (端口/监听地址变化时重启)，账号子集与系统提示词对所有请求生效
+// - 请求头 `X-AG-Profile: <name>`: 仅对该请求套用档案的模型映射、系统提示词与账号子集 (见 middleware::profile)
+// 档案保存在数据目录的 config_profiles.json，通过 Tauri 命令管理。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::proxy::ProxyConfig;
+
+const PROFILES_FILE: &str = "config_profiles.json";
+
+tokio::task_local! {
+    /// 当前请求通过请求头选用的档案
+    static REQUEST_PROFILE: Arc<ConfigProfile>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigProfile {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// 反代配置快照 (激活时替换当前配置，API Key 与自启设置除外)
+    pub proxy: ProxyConfig,
+    /// 可用账号 ID，为空表示全部账号
+    #[serde(default)]
+    pub account_ids: Vec<String>,
+    /// 插入在客户端系统提示词之前
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+}
+
+impl ConfigProfile {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty()
+            || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err("profile_name_invalid: use letters, digits, '-', '_' or '.'".to_string());
+        }
+        if self.proxy.port == 0 {
+            return Err("profile_port_invalid".to_string());
+        }
+        Ok(())
+    }
+
+    /// 账号子集 (None 表示不限制)
+    pub fn allowed_accounts(&self) -> Option<HashSet<String>> {
+        if self.account_ids.is_empty() {
+            None
+        } else {
+            Some(self.account_ids.iter().cloned().collect())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileList {
+    /// 当前激活的档案
+    #[serde(default)]
+    pub active: Option<String>,
+    #[serde(default)]
+    pub profiles: Vec<ConfigProfile>,
+}
+
+pub struct ProfileStore {
+    data: RwLock<ProfileList>,
+}
+
+fn profiles_path() -> Result<std::path::PathBuf, String> {
+    Ok(crate::modules::account::get_data_dir()?.join(PROFILES_FILE))
+}
+
+fn load_from_disk() -> ProfileList {
+    let Ok(path) = profiles_path() else {
+        return ProfileList::default();
+    };
+    if !path.exists() {
+        return ProfileList::default();
+    }
+    match std::fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|s| {
+        serde_json::from_str::<ProfileList>(&s).map_err(|e| e.to_string())
+    }) {
+        Ok(mut list) => {
+            // 激活的档案已被删除时视为未激活
+            if list.active.as_ref().is_some_and(|a| !list.profiles.iter().any(|p| &p.name == a)) {
+                list.active = None;
+            }
+            list
+        }
+        Err(e) => {
+            tracing::warn!("[Profiles] 读取 {} 失败: {}", PROFILES_FILE, e);
+            ProfileList::default()
+        }
+    }
+}
+
+impl ProfileStore {
+    /// Global singleton instance (首次使用时从磁盘加载)
+    pub fn global() -> Arc<Self> {
+        static INSTANCE: OnceLock<Arc<ProfileStore>> = OnceLock::new();
+        INSTANCE
+            .get_or_init(|| {
+                Arc::new(ProfileStore {
+                    data: RwLock::new(load_from_disk()),
+                })
+            })
+            .clone()
+    }
+
+    pub fn list(&self) -> ProfileList {
+        self.data.read().map(|d| d.clone()).unwrap_or_default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<ConfigProfile> {
+        self.data.read().ok()?.profiles.iter().find(|p| p.name == name).cloned()
+    }
+
+    pub fn active(&self) -> Option<ConfigProfile> {
+        let data = self.data.read().ok()?;
+        let name = data.active.as_ref()?;
+        data.profiles.iter().find(|p| &p.name == name).cloned()
+    }
+
+    fn persist(data: &ProfileList) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(data).map_err(|e| format!("failed_to_serialize_profiles: {}", e))?;
+        std::fs::write(profiles_path()?, content).map_err(|e| format!("failed_to_save_profiles: {}", e))
+    }
+
+    /// 新增或覆盖同名档案
+    pub fn save(&self, profile: ConfigProfile) -> Result<(), String> {
+        profile.validate()?;
+        let mut data = self.data.write().map_err(|_| "profile_store_poisoned".to_string())?;
+        match data.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => data.profiles.push(profile),
+        }
+        Self::persist(&data)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<bool, String> {
+        let mut data = self.data.write().map_err(|_| "profile_store_poisoned".to_string())?;
+        let before = data.profiles.len();
+        data.profiles.retain(|p| p.name != name);
+        if data.profiles.len() == before {
+            return Ok(false);
+        }
+        if data.active.as_deref() == Some(name) {
+            data.active = None;
+        }
+        Self::persist(&data)?;
+        Ok(true)
+    }
+
+    /// 记录激活的档案 (None 表示取消激活)
+    pub fn set_active(&self, name: Option<&str>) -> Result<(), String> {
+        let mut data = self.data.write().map_err(|_| "profile_store_poisoned".to_string())?;
+        if let Some(name) = name {
+            if !data.profiles.iter().any(|p| p.name == name) {
+                return Err(format!("profile_not_found: {}", name));
+            }
+        }
+        data.active = name.map(|n| n.to_string());
+        Self::persist(&data)
+    }
+}
+
+/// 在请求档案作用域内执行
+pub async fn scope<F: std::future::Future>(profile: Arc<ConfigProfile>, fut: F) -> F::Output {
+    REQUEST_PROFILE.scope(profile, fut).await
+}
+
+/// 当前请求应使用的账号子集: 请求头选用的档案优先，其次为激活的档案 (None 表示不限制)
+pub fn allowed_accounts() -> Option<HashSet<String>> {
+    match REQUEST_PROFILE.try_with(|p| p.allowed_accounts()) {
+        Ok(allowed) => allowed,
+        Err(_) => ProfileStore::global().active().and_then(|p| p.allowed_accounts()),
+    }
+}
+
+/// 由当前配置生成激活后的配置: 使用档案快照，但保留 API Key、自启设置与日志格式 (切换模式不应让已配置的客户端/日志采集失效)
+pub fn apply_to(current: &ProxyConfig, profile: &ConfigProfile) -> ProxyConfig {
+    let mut next = profile.proxy.clone();
+    next.api_key = current.api_key.clone();
+    next.auto_start = current.auto_start;
+    next.enabled = current.enabled;
+    next.log_format = current.log_format;
+    next
+}
+
+/// 切换后是否需要重启监听器 (其余设置均可热更新)
+pub fn needs_restart(current: &ProxyConfig, next: &ProxyConfig) -> bool {
+    current.port != next.port
+        || current.allow_lan_access != next.allow_lan_access
+        || current.listen_ipv4 != next.listen_ipv4
+        || current.listen_ipv6 != next.listen_ipv6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str, account_ids: Vec<&str>) -> ConfigProfile {
+        let mut proxy = ProxyConfig::default();
+        proxy.port = 9000;
+        proxy.api_key = "sk-profile".to_string();
+        ConfigProfile {
+            name: name.to_string(),
+            description: None,
+            proxy,
+            account_ids: account_ids.into_iter().map(|s| s.to_string()).collect(),
+            system_prompt: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_and_apply() {
+        assert!(profile("coding-agent", vec![]).validate().is_ok());
+        assert!(profile("clean chat", vec![]).validate().is_err());
+
+        let mut current = ProxyConfig::default();
+        current.api_key = "sk-current".to_string();
+        let p = profile("coding-agent", vec![]);
+        let next = apply_to(&current, &p);
+        assert_eq!(next.port, 9000);
+        assert_eq!(next.api_key, "sk-current");
+        assert!(needs_restart(&current, &next));
+    }
+
+    #[tokio::test]
+    async fn test_request_profile_accounts() {
+        let p = Arc::new(profile("chat", vec!["acc-1", "acc-2"]));
+        let allowed = scope(p, async { allowed_accounts() }).await.unwrap();
+        assert!(allowed.contains("acc-1"));
+        assert_eq!(allowed.len(), 2);
+
+        // 请求档案未限制账号时，即使存在激活档案也不限制
+        let open = Arc::new(profile("all", vec![]));
+        assert!(scope(open, async { allowed_accounts() }).await.is_none());
+    }
+}
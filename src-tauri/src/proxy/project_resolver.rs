@@ -3,6 +3,18 @@ use serde_json::Value;
 /// 使用 Antigravity 的 loadCodeAssist API 获取 project_id
 /// 这是获取 cloudaicompanionProject 的正确方式
 pub async fn fetch_project_id(access_token: &str) -> Result<String, String> {
+    if let Some(project_id) = fetch_official_project_id(access_token).await? {
+        return Ok(project_id);
+    }
+
+    // 如果没有返回 project_id，说明账号无资格，使用内置随机生成逻辑作为兜底
+    let mock_id = generate_mock_project_id();
+    tracing::warn!("账号无资格获取官方 cloudaicompanionProject，将使用随机生成的 Project ID 作为兜底: {}", mock_id);
+    Ok(mock_id)
+}
+
+/// 只查询官方分配的 cloudaicompanionProject (账号无资格时返回 None，不生成兜底 ID)
+pub async fn fetch_official_project_id(access_token: &str) -> Result<Option<String>, String> {
     let url = "https://cloudcode-pa.googleapis.com/v1internal:loadCodeAssist";
     
     let request_body = serde_json::json!({
@@ -34,15 +46,9 @@ pub async fn fetch_project_id(access_token: &str) -> Result<String, String> {
         .map_err(|e| format!("解析响应失败: {}", e))?;
     
     // 提取 cloudaicompanionProject
-    if let Some(project_id) = data.get("cloudaicompanionProject")
-        .and_then(|v| v.as_str()) {
-        return Ok(project_id.to_string());
-    }
-    
-    // 如果没有返回 project_id，说明账号无资格，使用内置随机生成逻辑作为兜底
-    let mock_id = generate_mock_project_id();
-    tracing::warn!("账号无资格获取官方 cloudaicompanionProject，将使用随机生成的 Project ID 作为兜底: {}", mock_id);
-    Ok(mock_id)
+    Ok(data.get("cloudaicompanionProject")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string()))
 }
 
 /// 生成随机 project_id（当无法从 API 获取时使用）
@@ -68,3 +74,40 @@ pub fn generate_mock_project_id() -> String {
     
     format!("{}-{}-{}", adj, noun, random_num)
 }
+
+/// [NEW] 判断上游错误是否由 project_id 失效/不匹配引起
+/// 典型场景: 403 PERMISSION_DENIED 提及 project，或 400/404 提示 project 不存在/不匹配
+pub fn is_project_error(status_code: u16, error_text: &str) -> bool {
+    if !matches!(status_code, 400 | 403 | 404) {
+        return false;
+    }
+    let lower = error_text.to_lowercase();
+    if !lower.contains("project") {
+        return false;
+    }
+    (status_code == 403 && lower.contains("permission_denied"))
+        || lower.contains("not found")
+        || lower.contains("mismatch")
+        || lower.contains("invalid project")
+        || lower.contains("does not have permission")
+        || lower.contains("has not been used")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_project_error() {
+        assert!(is_project_error(
+            403,
+            r#"{"error":{"code":403,"message":"Permission denied on resource project useful-fuze-abc12.","status":"PERMISSION_DENIED"}}"#
+        ));
+        assert!(is_project_error(404, "Requested project not found"));
+        assert!(is_project_error(400, "Project mismatch for the current user"));
+        // 与 project 无关的 403/400 不应触发修复
+        assert!(!is_project_error(403, r#"{"status":"PERMISSION_DENIED","message":"The caller does not have permission"}"#));
+        assert!(!is_project_error(400, "Invalid `signature` in `thinking` block"));
+        assert!(!is_project_error(429, "Quota exceeded for project"));
+    }
+}
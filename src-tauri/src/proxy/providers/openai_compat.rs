@@ -0,0 +1,174 @@
+// OpenAI 兼容上游 (OpenRouter / llama.cpp / vLLM ...)
+// 模型映射表决定每个模型由哪类后端服务: 目标写成 `<name>:<model>` 或模型命中该上游的 `models` 列表时，
+// OpenAI 协议请求原样转发到该上游，其余仍走 Google 账号池。
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::time::Duration;
+
+use crate::proxy::common::model_mapping::{lookup_custom_mapping, wildcard_match};
+use crate::proxy::config::OpenAICompatBackend;
+use crate::proxy::server::AppState;
+
+/// 按映射表 / 上游模型列表解析路由目标，返回 (上游, 上游模型名)
+pub fn resolve_backend<'a>(
+    backends: &'a [OpenAICompatBackend],
+    original_model: &str,
+    custom_mapping: &HashMap<String, String>,
+) -> Option<(&'a OpenAICompatBackend, String)> {
+    let enabled = || backends.iter().filter(|b| b.enabled && !b.name.is_empty());
+
+    if let Some(target) = lookup_custom_mapping(original_model, custom_mapping) {
+        if let Some((prefix, model)) = target.split_once(':') {
+            if let Some(backend) = enabled().find(|b| b.name == prefix) {
+                return Some((backend, model.to_string()));
+            }
+        }
+        // 映射到了其他模型: 以映射结果继续匹配上游模型列表
+        return enabled()
+            .find(|b| b.models.iter().any(|p| wildcard_match(p, &target)))
+            .map(|b| (b, target));
+    }
+
+    enabled()
+        .find(|b| b.models.iter().any(|p| wildcard_match(p, original_model)))
+        .map(|b| (b, original_model.to_string()))
+}
+
+/// /v1/models 中列出的上游模型 (不含通配符规则)
+pub fn listed_models(backends: &[OpenAICompatBackend]) -> Vec<String> {
+    backends
+        .iter()
+        .filter(|b| b.enabled)
+        .flat_map(|b| b.models.iter())
+        .filter(|m| !m.contains('*'))
+        .cloned()
+        .collect()
+}
+
+fn build_client(
+    upstream_proxy: &crate::proxy::config::UpstreamProxyConfig,
+    timeout_secs: u64,
+) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder().timeout(Duration::from_secs(timeout_secs.max(5)));
+    if upstream_proxy.enabled && !upstream_proxy.url.is_empty() {
+        let proxy = reqwest::Proxy::all(&upstream_proxy.url)
+            .map_err(|e| format!("Invalid upstream proxy url: {}", e))?;
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .tcp_nodelay(true)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+/// 转发 OpenAI 协议请求 (path 如 `/chat/completions`)，响应 (含 SSE) 原样流式返回
+pub async fn forward_openai_json(
+    state: &AppState,
+    backend: &OpenAICompatBackend,
+    path: &str,
+    upstream_model: &str,
+    mut body: Value,
+) -> Response {
+    body["model"] = Value::String(upstream_model.to_string());
+    let url = format!("{}/{}", backend.base_url.trim_end_matches('/'), path.trim_start_matches('/'));
+
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    let client = match build_client(&upstream_proxy, state.request_timeout) {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+
+    let mut req = client
+        .post(&url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&body).unwrap_or_default());
+    if !backend.api_key.trim().is_empty() {
+        req = req.bearer_auth(backend.api_key.trim());
+    }
+    for (name, value) in &backend.headers {
+        req = req.header(name.as_str(), value.as_str());
+    }
+
+    tracing::debug!("[OpenAI-Compat] {} -> {} ({})", upstream_model, url, backend.name);
+    let resp = match req.send().await {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                format!("Upstream '{}' request failed: {}", backend.name, e),
+            )
+                .into_response();
+        }
+    };
+
+    let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let mut out = Response::builder().status(status);
+    if let Some(ct) = resp.headers().get(header::CONTENT_TYPE) {
+        out = out.header(header::CONTENT_TYPE, ct.clone());
+    }
+    // 请求历史中以 `<name>` 标识该上游 (代替账号邮箱)
+    if let Ok(v) = HeaderValue::from_str(&format!("openai-compat:{}", backend.name)) {
+        out = out.header("X-Account-Email", v);
+    }
+    if let Ok(v) = HeaderValue::from_str(upstream_model) {
+        out = out.header("X-Mapped-Model", v);
+    }
+
+    let stream = resp.bytes_stream().map(|chunk| match chunk {
+        Ok(b) => Ok::<Bytes, std::io::Error>(b),
+        Err(e) => Ok(Bytes::from(format!("Upstream stream error: {}", e))),
+    });
+    out.body(Body::from_stream(stream)).unwrap_or_else(|_| {
+        (StatusCode::INTERNAL_SERVER_ERROR, "Failed to build response").into_response()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(name: &str, models: &[&str]) -> OpenAICompatBackend {
+        OpenAICompatBackend {
+            enabled: true,
+            name: name.to_string(),
+            base_url: "http://127.0.0.1:8081/v1".to_string(),
+            api_key: String::new(),
+            models: models.iter().map(|m| m.to_string()).collect(),
+            headers: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_backend_by_mapping_and_model_list() {
+        let backends = vec![backend("openrouter", &[]), backend("local", &["llama-*", "qwen2.5-coder"])];
+        let mut mapping = HashMap::new();
+        mapping.insert("gpt-4o-mini".to_string(), "openrouter:openai/gpt-4o-mini".to_string());
+        mapping.insert("fast".to_string(), "qwen2.5-coder".to_string());
+        mapping.insert("gpt-4o".to_string(), "gemini-3-flash".to_string());
+
+        let (b, m) = resolve_backend(&backends, "gpt-4o-mini", &mapping).unwrap();
+        assert_eq!((b.name.as_str(), m.as_str()), ("openrouter", "openai/gpt-4o-mini"));
+
+        let (b, m) = resolve_backend(&backends, "llama-3.1-8b", &mapping).unwrap();
+        assert_eq!((b.name.as_str(), m.as_str()), ("local", "llama-3.1-8b"));
+
+        let (b, m) = resolve_backend(&backends, "fast", &mapping).unwrap();
+        assert_eq!((b.name.as_str(), m.as_str()), ("local", "qwen2.5-coder"));
+
+        // 映射到 Google 模型 / 未知模型仍走账号池
+        assert!(resolve_backend(&backends, "gpt-4o", &mapping).is_none());
+        assert!(resolve_backend(&backends, "gemini-3-pro-high", &mapping).is_none());
+
+        let mut disabled = backends.clone();
+        disabled[0].enabled = false;
+        assert!(resolve_backend(&disabled, "gpt-4o-mini", &mapping).is_none());
+        assert_eq!(listed_models(&backends), vec!["qwen2.5-coder".to_string()]);
+    }
+}
@@ -0,0 +1,203 @@
+// 配额窗口学习
+// 不再假设固定的限流规则，而是根据每个账号实际观察到的 429 学习其配额行为：
+// - 每个窗口内成功请求数 (429 前累计的成功次数)
+// - 恢复时间 (429 到下一次成功的间隔)
+// 调度器据此估算账号剩余余量 (headroom)，同等级账号中优先使用余量更大的账号。
+
+use crate::proxy::rate_limit::RateLimitReason;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::time::Instant;
+
+/// EWMA 平滑系数 (新样本权重)
+const EWMA_ALPHA: f64 = 0.3;
+/// 恢复时间样本上限，超出视为账号闲置而非真实恢复时间
+const MAX_RECOVERY_SAMPLE_SECS: f64 = 6.0 * 3600.0;
+
+#[derive(Debug, Default)]
+struct QuotaState {
+    hits: u32,
+    /// 估算的每窗口可用请求数
+    window_requests: Option<f64>,
+    /// 估算的恢复时间(秒)
+    recovery_secs: Option<f64>,
+    /// 估算的完整窗口周期(秒) = 窗口开始到 429 + 恢复时间
+    window_secs: Option<f64>,
+    requests_in_window: u32,
+    window_start: Option<Instant>,
+    limited_since: Option<Instant>,
+    /// 最近一次 429 的窗口时长 (用于计算 window_secs)
+    last_window_elapsed: Option<f64>,
+    last_hit_at: Option<i64>,
+}
+
+fn ewma(prev: Option<f64>, sample: f64) -> f64 {
+    match prev {
+        Some(p) => p * (1.0 - EWMA_ALPHA) + sample * EWMA_ALPHA,
+        None => sample,
+    }
+}
+
+impl QuotaState {
+    /// 窗口是否已按学习到的周期自然重置
+    fn window_expired(&self) -> bool {
+        match (self.window_start, self.window_secs) {
+            (Some(start), Some(secs)) => start.elapsed().as_secs_f64() >= secs,
+            _ => false,
+        }
+    }
+
+    fn headroom(&self) -> f64 {
+        if let Some(since) = self.limited_since {
+            // 仍处于学习到的恢复期内
+            let recovery = self.recovery_secs.unwrap_or(0.0);
+            if since.elapsed().as_secs_f64() < recovery {
+                return 0.0;
+            }
+            return 1.0;
+        }
+        let Some(limit) = self.window_requests.filter(|l| *l >= 1.0) else {
+            return 1.0;
+        };
+        if self.window_expired() {
+            return 1.0;
+        }
+        (1.0 - self.requests_in_window as f64 / limit).clamp(0.0, 1.0)
+    }
+}
+
+/// 对外展示的学习结果
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaProfile {
+    pub account_id: String,
+    pub rate_limit_hits: u32,
+    pub window_requests: Option<f64>,
+    pub recovery_secs: Option<f64>,
+    pub window_secs: Option<f64>,
+    pub requests_in_window: u32,
+    pub headroom: f64,
+    pub last_hit_at: Option<i64>,
+}
+
+#[derive(Default)]
+pub struct QuotaLearner {
+    states: DashMap<String, QuotaState>,
+}
+
+impl QuotaLearner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一次成功请求；若账号此前处于限流状态，同时得到一个恢复时间样本
+    pub fn record_success(&self, account_id: &str) {
+        let mut state = self.states.entry(account_id.to_string()).or_default();
+        if let Some(since) = state.limited_since.take() {
+            let recovery = since.elapsed().as_secs_f64();
+            if recovery <= MAX_RECOVERY_SAMPLE_SECS {
+                state.recovery_secs = Some(ewma(state.recovery_secs, recovery));
+                if let Some(elapsed) = state.last_window_elapsed.take() {
+                    state.window_secs = Some(ewma(state.window_secs, elapsed + recovery));
+                }
+            }
+            state.requests_in_window = 0;
+            state.window_start = Some(Instant::now());
+        } else if state.window_start.is_none() || state.window_expired() {
+            state.requests_in_window = 0;
+            state.window_start = Some(Instant::now());
+        }
+        state.requests_in_window = state.requests_in_window.saturating_add(1);
+    }
+
+    /// 记录一次 429；限流期间的重复 429 不重复采样
+    pub fn record_rate_limit(&self, account_id: &str, reason: RateLimitReason) {
+        // 容量不足/服务端错误与账号配额无关
+        if matches!(reason, RateLimitReason::ModelCapacityExhausted | RateLimitReason::ServerError) {
+            return;
+        }
+        let mut state = self.states.entry(account_id.to_string()).or_default();
+        state.last_hit_at = Some(chrono::Utc::now().timestamp());
+        if state.limited_since.is_some() {
+            return;
+        }
+        state.hits += 1;
+        if state.requests_in_window > 0 {
+            state.window_requests = Some(ewma(state.window_requests, state.requests_in_window as f64));
+        }
+        state.last_window_elapsed = state.window_start.map(|s| s.elapsed().as_secs_f64());
+        state.limited_since = Some(Instant::now());
+        tracing::debug!(
+            "[QuotaLearning] 账号 {} 第 {} 次 429: 本窗口 {} 次请求, 估算窗口容量 {:?}",
+            account_id,
+            state.hits,
+            state.requests_in_window,
+            state.window_requests
+        );
+    }
+
+    /// 账号剩余余量估算 (0.0 ~ 1.0)，无观测数据时视为 1.0
+    pub fn headroom(&self, account_id: &str) -> f64 {
+        self.states.get(account_id).map_or(1.0, |s| s.headroom())
+    }
+
+    pub fn profiles(&self) -> Vec<QuotaProfile> {
+        let mut list: Vec<QuotaProfile> = self
+            .states
+            .iter()
+            .map(|e| QuotaProfile {
+                account_id: e.key().clone(),
+                rate_limit_hits: e.hits,
+                window_requests: e.window_requests,
+                recovery_secs: e.recovery_secs,
+                window_secs: e.window_secs,
+                requests_in_window: e.requests_in_window,
+                headroom: e.headroom(),
+                last_hit_at: e.last_hit_at,
+            })
+            .collect();
+        list.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+        list
+    }
+
+    pub fn forget(&self, account_id: &str) {
+        self.states.remove(account_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_learns_window_size_and_headroom() {
+        let learner = QuotaLearner::new();
+        assert_eq!(learner.headroom("acc"), 1.0);
+
+        for _ in 0..10 {
+            learner.record_success("acc");
+        }
+        learner.record_rate_limit("acc", RateLimitReason::RateLimitExceeded);
+        // 重复 429 不重复计数
+        learner.record_rate_limit("acc", RateLimitReason::RateLimitExceeded);
+        let profile = &learner.profiles()[0];
+        assert_eq!(profile.rate_limit_hits, 1);
+        assert_eq!(profile.window_requests, Some(10.0));
+
+        // 恢复后新窗口内已用 5 次，余量约一半
+        for _ in 0..5 {
+            learner.record_success("acc");
+        }
+        assert!((learner.headroom("acc") - 0.5).abs() < 1e-9);
+        assert!(learner.profiles()[0].recovery_secs.is_some());
+    }
+
+    #[test]
+    fn test_ignores_capacity_errors() {
+        let learner = QuotaLearner::new();
+        learner.record_success("acc");
+        learner.record_rate_limit("acc", RateLimitReason::ModelCapacityExhausted);
+        learner.record_rate_limit("acc", RateLimitReason::ServerError);
+        assert_eq!(learner.profiles()[0].rate_limit_hits, 0);
+        assert_eq!(learner.headroom("acc"), 1.0);
+    }
+}
@@ -1,9 +1,12 @@
 use dashmap::DashMap;
 use std::time::{SystemTime, Duration};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::proxy::state_journal::{JournalRecord, JournalState, LockoutEntry, StateJournal};
 
 /// 限流原因类型
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum RateLimitReason {
     /// 配额耗尽 (QUOTA_EXHAUSTED)
     QuotaExhausted,
@@ -72,13 +75,72 @@ impl RateLimitTracker {
     /// 当账号成功完成请求后调用此方法，将其失败计数归零，
     /// 这样下次失败时会从最短的锁定时间（60秒）开始。
     pub fn mark_success(&self, account_id: &str) {
-        if self.failure_counts.remove(account_id).is_some() {
+        let had_failures = self.failure_counts.remove(account_id).is_some();
+        if had_failures {
             tracing::debug!("账号 {} 请求成功，已重置失败计数", account_id);
         }
         // 同时清除限流记录（如果有）
-        self.limits.remove(account_id);
+        if self.limits.remove(account_id).is_some() || had_failures {
+            StateJournal::global().append(JournalRecord::ClearAccount { account_id: account_id.to_string() });
+        }
+    }
+
+    /// 记录一条限流到状态日志 (崩溃后可恢复冷却)
+    fn journal_lockout(account_id: &str, info: &RateLimitInfo) {
+        StateJournal::global().append(JournalRecord::Lockout {
+            account_id: account_id.to_string(),
+            reset_at: to_unix_secs(info.reset_time),
+            reason: info.reason,
+            model: info.model.clone(),
+        });
+    }
+
+    /// 从状态日志恢复未过期的限流与失败计数
+    pub fn restore_from_journal(&self, state: &JournalState) -> usize {
+        let now = SystemTime::now();
+        let mut restored = 0;
+        for (account_id, lockout) in &state.lockouts {
+            let reset_time = from_unix_secs(lockout.reset_at);
+            if reset_time <= now || self.limits.contains_key(account_id) {
+                continue;
+            }
+            self.limits.insert(
+                account_id.clone(),
+                RateLimitInfo {
+                    reset_time,
+                    retry_after_sec: reset_time.duration_since(now).map(|d| d.as_secs()).unwrap_or(0),
+                    detected_at: now,
+                    reason: lockout.reason,
+                    model: lockout.model.clone(),
+                },
+            );
+            restored += 1;
+        }
+        for (account_id, (count, at)) in &state.failures {
+            let at = from_unix_secs(*at);
+            if now.duration_since(at).map(|d| d.as_secs()).unwrap_or(0) <= FAILURE_COUNT_EXPIRY_SECONDS {
+                self.failure_counts.entry(account_id.clone()).or_insert((*count, at));
+            }
+        }
+        restored
     }
     
+    /// 当前未过期的限流冷却 (供多副本共享状态)
+    pub fn active_lockouts(&self) -> Vec<(String, LockoutEntry)> {
+        let now = SystemTime::now();
+        self.limits
+            .iter()
+            .filter(|e| e.value().reset_time > now)
+            .map(|e| {
+                let info = e.value();
+                (
+                    e.key().clone(),
+                    LockoutEntry { reset_at: to_unix_secs(info.reset_time), reason: info.reason, model: info.model.clone() },
+                )
+            })
+            .collect()
+    }
+
     /// 精确锁定账号到指定时间点
     /// 
     /// 使用账号配额中的 reset_time 来精确锁定账号,
@@ -101,6 +163,7 @@ impl RateLimitTracker {
             model: model.clone(),  // 🆕 支持模型级别限流
         };
         
+        Self::journal_lockout(account_id, &info);
         self.limits.insert(account_id.to_string(), info);
         
         if let Some(m) = &model {
@@ -205,6 +268,11 @@ impl RateLimitTracker {
                     }
                     entry.0 += 1;
                     entry.1 = now;
+                    StateJournal::global().append(JournalRecord::Failures {
+                        account_id: account_id.to_string(),
+                        count: entry.0,
+                        at: to_unix_secs(now),
+                    });
                     entry.0
                 };
                 
@@ -266,6 +334,7 @@ impl RateLimitTracker {
         };
         
         // 存储
+        Self::journal_lockout(account_id, &info);
         self.limits.insert(account_id.to_string(), info.clone());
         
         tracing::warn!(
@@ -279,6 +348,11 @@ impl RateLimitTracker {
         Some(info)
     }
     
+    /// 判断 429 的限流原因 (供配额学习区分账号配额与容量不足)
+    pub fn classify_reason(&self, body: &str) -> RateLimitReason {
+        self.parse_rate_limit_reason(body)
+    }
+
     /// 解析限流原因类型
     fn parse_rate_limit_reason(&self, body: &str) -> RateLimitReason {
         // 尝试从 JSON 中提取 reason 字段
@@ -500,7 +574,11 @@ impl RateLimitTracker {
     /// 清除指定账号的限流记录
     #[allow(dead_code)]
     pub fn clear(&self, account_id: &str) -> bool {
-        self.limits.remove(account_id).is_some()
+        let removed = self.limits.remove(account_id).is_some();
+        if removed {
+            StateJournal::global().append(JournalRecord::ClearLockout { account_id: account_id.to_string() });
+        }
+        removed
     }
     
     /// 清除所有限流记录 (乐观重置策略)
@@ -510,10 +588,19 @@ impl RateLimitTracker {
     pub fn clear_all(&self) {
         let count = self.limits.len();
         self.limits.clear();
+        StateJournal::global().append(JournalRecord::ClearAllLockouts);
         tracing::warn!("🔄 Optimistic reset: Cleared all {} rate limit record(s)", count);
     }
 }
 
+fn to_unix_secs(t: SystemTime) -> i64 {
+    t.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+fn from_unix_secs(secs: i64) -> SystemTime {
+    std::time::UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)
+}
+
 impl Default for RateLimitTracker {
     fn default() -> Self {
         Self::new()
@@ -585,4 +672,28 @@ mod tests {
         // 应该被识别为 RateLimitExceeded，而不是 QuotaExhausted
         assert_eq!(reason, RateLimitReason::RateLimitExceeded);
     }
+
+    #[test]
+    fn test_restore_from_journal_skips_expired() {
+        use crate::proxy::state_journal::LockoutEntry;
+        let tracker = RateLimitTracker::new();
+        let now = to_unix_secs(SystemTime::now());
+        let mut state = JournalState::default();
+        state.lockouts.insert(
+            "active".into(),
+            LockoutEntry { reset_at: now + 120, reason: RateLimitReason::QuotaExhausted, model: None },
+        );
+        state.lockouts.insert(
+            "expired".into(),
+            LockoutEntry { reset_at: now - 10, reason: RateLimitReason::Unknown, model: None },
+        );
+        state.failures.insert("active".into(), (2, now - 30));
+        state.failures.insert("stale".into(), (5, now - 7200));
+
+        assert_eq!(tracker.restore_from_journal(&state), 1);
+        assert!(tracker.get_remaining_wait("active") > 100);
+        assert_eq!(tracker.get_remaining_wait("expired"), 0);
+        assert_eq!(tracker.failure_counts.get("active").map(|e| e.0), Some(2));
+        assert!(tracker.failure_counts.get("stale").is_none());
+    }
 }
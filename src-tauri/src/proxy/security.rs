@@ -4,7 +4,12 @@ use crate::proxy::config::{ProxyAuthMode, ProxyConfig};
 pub struct ProxySecurityConfig {
     pub auth_mode: ProxyAuthMode,
     pub api_key: String,
+    /// /admin/* 专用密钥，须非空且不同于 api_key
+    pub admin_api_key: String,
     pub allow_lan_access: bool,
+    pub admin_api_enabled: bool,
+    /// 本地套接字连接免 API Key
+    pub local_socket_trusted: bool,
 }
 
 impl ProxySecurityConfig {
@@ -12,7 +17,10 @@ impl ProxySecurityConfig {
         Self {
             auth_mode: config.auth_mode.clone(),
             api_key: config.api_key.clone(),
+            admin_api_key: config.admin_api_key.clone(),
             allow_lan_access: config.allow_lan_access,
+            admin_api_enabled: config.enable_admin_api,
+            local_socket_trusted: config.local_socket.enabled && config.local_socket.trust_peers,
         }
     }
 
@@ -39,7 +47,10 @@ mod tests {
         let s = ProxySecurityConfig {
             auth_mode: ProxyAuthMode::Auto,
             api_key: "sk-test".to_string(),
+            admin_api_key: String::new(),
             allow_lan_access: false,
+            admin_api_enabled: false,
+            local_socket_trusted: false,
         };
         assert!(matches!(s.effective_auth_mode(), ProxyAuthMode::Off));
     }
@@ -49,7 +60,10 @@ mod tests {
         let s = ProxySecurityConfig {
             auth_mode: ProxyAuthMode::Auto,
             api_key: "sk-test".to_string(),
+            admin_api_key: String::new(),
             allow_lan_access: true,
+            admin_api_enabled: false,
+            local_socket_trusted: false,
         };
         assert!(matches!(
             s.effective_auth_mode(),
@@ -1,6 +1,6 @@
 use crate::proxy::TokenManager;
 use axum::{
-    extract::DefaultBodyLimit,
+    extract::{DefaultBodyLimit, State},
     http::StatusCode,
     response::{IntoResponse, Json, Response},
     routing::{any, get, post},
@@ -26,20 +26,69 @@ pub struct AppState {
     pub upstream_proxy: Arc<tokio::sync::RwLock<crate::proxy::config::UpstreamProxyConfig>>,
     pub upstream: Arc<crate::proxy::upstream::client::UpstreamClient>,
     pub zai: Arc<RwLock<crate::proxy::ZaiConfig>>,
+    /// OpenAI 兼容上游列表
+    pub openai_backends: Arc<RwLock<Vec<crate::proxy::config::OpenAICompatBackend>>>,
+    /// Vertex AI 服务账号上游
+    pub vertex: Arc<RwLock<crate::proxy::config::VertexConfig>>,
+    /// AI Studio API Key 账号路由配置
+    pub ai_studio: Arc<RwLock<crate::proxy::config::AiStudioConfig>>,
     pub provider_rr: Arc<AtomicUsize>,
     pub zai_vision_mcp: Arc<crate::proxy::zai_vision_mcp::ZaiVisionMcpState>,
     pub monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
     pub experimental: Arc<RwLock<crate::proxy::config::ExperimentalConfig>>,
+    /// 请求体大小上限(字节)
+    pub max_body_bytes: usize,
+    /// Batch API 任务管理
+    pub batches: Arc<crate::proxy::batch::BatchManager>,
+    /// 扩展钩子配置
+    pub hooks: Arc<RwLock<crate::proxy::config::HooksConfig>>,
+    /// 出站敏感信息脱敏配置
+    pub pii_scrub: Arc<RwLock<crate::proxy::config::PiiScrubConfig>>,
+    /// 流式响应落盘配置
+    pub stream_tee: Arc<RwLock<crate::proxy::config::StreamTeeConfig>>,
+    /// Token 预算预检配置
+    pub token_budget: Arc<RwLock<crate::proxy::config::TokenBudgetConfig>>,
+    /// 模型降级链配置
+    pub model_fallback: Arc<RwLock<crate::proxy::config::ModelFallbackConfig>>,
+    /// A/B 分流实验配置
+    pub ab_test: Arc<RwLock<crate::proxy::config::AbTestConfig>>,
+    /// 延迟预算配置
+    pub latency_budget: Arc<RwLock<crate::proxy::config::LatencyBudgetConfig>>,
+    /// 竞速模式配置
+    pub racing: Arc<RwLock<crate::proxy::config::RacingConfig>>,
+    /// 按请求固定账号配置
+    pub account_pinning: Arc<RwLock<crate::proxy::config::AccountPinningConfig>>,
+    /// 在途请求登记 (取消 API)
+    pub inflight: Arc<crate::proxy::inflight::InflightRegistry>,
+    /// 生效中的完整反代配置 (headless 下来自 --config / 环境变量 / 命令行，可能与桌面端 gui_config.json 不同)
+    pub proxy_config: Arc<RwLock<crate::proxy::config::ProxyConfig>>,
+    /// 管理 API 修改配置后是否写回 gui_config.json (headless 模式下为 false)
+    pub persist_config: bool,
 }
 
 /// Axum 服务器实例
 pub struct AxumServer {
-    shutdown_tx: Option<oneshot::Sender<()>>,
+    /// 关闭信号，携带在途连接的排空超时
+    shutdown_tx: Option<oneshot::Sender<std::time::Duration>>,
     custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
     proxy_state: Arc<tokio::sync::RwLock<crate::proxy::config::UpstreamProxyConfig>>,
     security_state: Arc<RwLock<crate::proxy::ProxySecurityConfig>>,
     zai_state: Arc<RwLock<crate::proxy::ZaiConfig>>,
+    openai_backends: Arc<RwLock<Vec<crate::proxy::config::OpenAICompatBackend>>>,
+    vertex: Arc<RwLock<crate::proxy::config::VertexConfig>>,
+    ai_studio: Arc<RwLock<crate::proxy::config::AiStudioConfig>>,
     experimental: Arc<RwLock<crate::proxy::config::ExperimentalConfig>>,
+    upstream: Arc<crate::proxy::upstream::client::UpstreamClient>,
+    hooks: Arc<RwLock<crate::proxy::config::HooksConfig>>,
+    pii_scrub: Arc<RwLock<crate::proxy::config::PiiScrubConfig>>,
+    stream_tee: Arc<RwLock<crate::proxy::config::StreamTeeConfig>>,
+    token_budget: Arc<RwLock<crate::proxy::config::TokenBudgetConfig>>,
+    model_fallback: Arc<RwLock<crate::proxy::config::ModelFallbackConfig>>,
+    ab_test: Arc<RwLock<crate::proxy::config::AbTestConfig>>,
+    latency_budget: Arc<RwLock<crate::proxy::config::LatencyBudgetConfig>>,
+    racing: Arc<RwLock<crate::proxy::config::RacingConfig>>,
+    account_pinning: Arc<RwLock<crate::proxy::config::AccountPinningConfig>>,
+    proxy_config: Arc<RwLock<crate::proxy::config::ProxyConfig>>,
 }
 
 impl AxumServer {
@@ -70,14 +119,102 @@ impl AxumServer {
         tracing::info!("z.ai 配置已热更新");
     }
 
+    pub async fn update_openai_backends(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut backends = self.openai_backends.write().await;
+        *backends = config.openai_backends.clone();
+        tracing::info!("OpenAI 兼容上游配置已热更新");
+    }
+
+    pub async fn update_vertex(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut vertex = self.vertex.write().await;
+        *vertex = config.vertex.clone();
+        tracing::info!("Vertex AI 配置已热更新");
+    }
+
+    pub async fn update_ai_studio(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut ai_studio = self.ai_studio.write().await;
+        *ai_studio = config.ai_studio.clone();
+        tracing::info!("AI Studio API Key 配置已热更新");
+    }
+
     pub async fn update_experimental(&self, config: &crate::proxy::config::ProxyConfig) {
         let mut exp = self.experimental.write().await;
         *exp = config.experimental.clone();
+        crate::proxy::mappers::model_limits::set_overrides(config.experimental.model_limits.overrides.clone());
+        crate::proxy::mappers::quirks::set_overrides(config.experimental.quirks.overrides.clone());
         tracing::info!("实验性配置已热更新");
     }
+
+    pub async fn update_hooks(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut hooks = self.hooks.write().await;
+        *hooks = config.hooks.clone();
+        tracing::info!("扩展钩子配置已热更新");
+    }
+
+    pub async fn update_pii_scrub(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut pii = self.pii_scrub.write().await;
+        *pii = config.pii_scrub.clone();
+        tracing::info!("敏感信息脱敏配置已热更新");
+    }
+
+    pub async fn update_stream_tee(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut tee = self.stream_tee.write().await;
+        *tee = config.stream_tee.clone();
+        tracing::info!("流式响应落盘配置已热更新");
+    }
+
+    pub async fn update_token_budget(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut budget = self.token_budget.write().await;
+        *budget = config.token_budget.clone();
+        tracing::info!("Token 预算配置已热更新");
+    }
+
+    pub async fn update_model_fallback(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut fallback = self.model_fallback.write().await;
+        *fallback = config.model_fallback.clone();
+        tracing::info!("模型降级链配置已热更新");
+    }
+
+    pub async fn update_ab_test(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut ab = self.ab_test.write().await;
+        *ab = config.ab_test.clone();
+        tracing::info!("A/B 实验配置已热更新");
+    }
+
+    pub async fn update_latency_budget(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut latency = self.latency_budget.write().await;
+        *latency = config.latency_budget.clone();
+        tracing::info!("延迟预算配置已热更新");
+    }
+
+    pub async fn update_racing(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut racing = self.racing.write().await;
+        *racing = config.racing.clone();
+        tracing::info!("竞速模式配置已热更新");
+    }
+
+    pub async fn update_account_pinning(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut pinning = self.account_pinning.write().await;
+        *pinning = config.account_pinning.clone();
+        tracing::info!("账号固定配置已热更新");
+    }
+
+    pub async fn update_proxy_config(&self, config: &crate::proxy::config::ProxyConfig) {
+        *self.proxy_config.write().await = config.clone();
+    }
+
+    /// 生效中的完整反代配置 (含管理 API 的修改)
+    pub async fn proxy_config(&self) -> crate::proxy::config::ProxyConfig {
+        self.proxy_config.read().await.clone()
+    }
+
+    pub fn update_upstream_endpoints(&self, config: &crate::proxy::config::ProxyConfig) {
+        self.upstream.set_endpoints(config.upstream_endpoints.clone());
+        tracing::info!("上游端点配置已热更新");
+    }
     /// 启动 Axum 服务器
     pub async fn start(
-        host: String,
+        listen_addrs: Vec<crate::proxy::config::ListenAddr>,
         port: u16,
         token_manager: Arc<TokenManager>,
         custom_mapping: std::collections::HashMap<String, String>,
@@ -85,18 +222,61 @@ impl AxumServer {
         upstream_proxy: crate::proxy::config::UpstreamProxyConfig,
         security_config: crate::proxy::ProxySecurityConfig,
         zai_config: crate::proxy::ZaiConfig,
+        openai_backends: Vec<crate::proxy::config::OpenAICompatBackend>,
+        vertex_config: crate::proxy::config::VertexConfig,
+        ai_studio_config: crate::proxy::config::AiStudioConfig,
         monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
         experimental_config: crate::proxy::config::ExperimentalConfig,
-
+        upstream_endpoints: crate::proxy::config::UpstreamEndpointsConfig,
+        upstream_pool: crate::proxy::config::UpstreamPoolConfig,
+        max_body_bytes: usize,
+        cors_config: crate::proxy::config::CorsConfig,
+        hooks_config: crate::proxy::config::HooksConfig,
+        pii_scrub_config: crate::proxy::config::PiiScrubConfig,
+        stream_tee_config: crate::proxy::config::StreamTeeConfig,
+        token_budget_config: crate::proxy::config::TokenBudgetConfig,
+        model_fallback_config: crate::proxy::config::ModelFallbackConfig,
+        ab_test_config: crate::proxy::config::AbTestConfig,
+        latency_budget_config: crate::proxy::config::LatencyBudgetConfig,
+        racing_config: crate::proxy::config::RacingConfig,
+        account_pinning_config: crate::proxy::config::AccountPinningConfig,
+        local_socket_config: crate::proxy::config::LocalSocketConfig,
+        proxy_config: crate::proxy::config::ProxyConfig,
+        persist_config: bool,
     ) -> Result<(Self, tokio::task::JoinHandle<()>), String> {
+        PROCESS_STARTED.get_or_init(std::time::Instant::now);
         let custom_mapping_state = Arc::new(tokio::sync::RwLock::new(custom_mapping));
 	        let proxy_state = Arc::new(tokio::sync::RwLock::new(upstream_proxy.clone()));
 	        let security_state = Arc::new(RwLock::new(security_config));
 	        let zai_state = Arc::new(RwLock::new(zai_config));
+	        let openai_backends_state = Arc::new(RwLock::new(openai_backends));
+	        let vertex_state = Arc::new(RwLock::new(vertex_config));
+	        let ai_studio_state = Arc::new(RwLock::new(ai_studio_config));
 	        let provider_rr = Arc::new(AtomicUsize::new(0));
 	        let zai_vision_mcp_state =
 	            Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
+	        crate::proxy::mappers::model_limits::set_overrides(experimental_config.model_limits.overrides.clone());
+	        crate::proxy::mappers::quirks::set_overrides(experimental_config.quirks.overrides.clone());
 	        let experimental_state = Arc::new(RwLock::new(experimental_config));
+	        let hooks_state = Arc::new(RwLock::new(hooks_config));
+	        let pii_scrub_state = Arc::new(RwLock::new(pii_scrub_config));
+	        let stream_tee_state = Arc::new(RwLock::new(stream_tee_config));
+	        let token_budget_state = Arc::new(RwLock::new(token_budget_config));
+	        let model_fallback_state = Arc::new(RwLock::new(model_fallback_config));
+	        let ab_test_state = Arc::new(RwLock::new(ab_test_config));
+	        let latency_budget_state = Arc::new(RwLock::new(latency_budget_config));
+	        let racing_state = Arc::new(RwLock::new(racing_config));
+	        let account_pinning_state = Arc::new(RwLock::new(account_pinning_config));
+	        let proxy_config_state = Arc::new(RwLock::new(proxy_config));
+	        let upstream_client = Arc::new(crate::proxy::upstream::client::UpstreamClient::new(
+	            Some(upstream_proxy.clone()),
+	            &upstream_pool,
+	        ));
+	        upstream_client.set_endpoints(upstream_endpoints);
+	        if upstream_pool.prewarm {
+	            let client = upstream_client.clone();
+	            tokio::spawn(async move { client.prewarm().await });
+	        }
 
 	        let state = AppState {
 	            token_manager: token_manager.clone(),
@@ -106,15 +286,32 @@ impl AxumServer {
                 std::collections::HashMap::new(),
             )),
             upstream_proxy: proxy_state.clone(),
-            upstream: Arc::new(crate::proxy::upstream::client::UpstreamClient::new(Some(
-                upstream_proxy.clone(),
-            ))),
+            upstream: upstream_client.clone(),
             zai: zai_state.clone(),
+            openai_backends: openai_backends_state.clone(),
+            vertex: vertex_state.clone(),
+            ai_studio: ai_studio_state.clone(),
             provider_rr: provider_rr.clone(),
             zai_vision_mcp: zai_vision_mcp_state,
             monitor: monitor.clone(),
             experimental: experimental_state.clone(),
+            max_body_bytes,
+            batches: crate::proxy::batch::BatchManager::global(),
+            hooks: hooks_state.clone(),
+            pii_scrub: pii_scrub_state.clone(),
+            stream_tee: stream_tee_state.clone(),
+            token_budget: token_budget_state.clone(),
+            model_fallback: model_fallback_state.clone(),
+            ab_test: ab_test_state.clone(),
+            latency_budget: latency_budget_state.clone(),
+            racing: racing_state.clone(),
+            account_pinning: account_pinning_state.clone(),
+            inflight: crate::proxy::inflight::InflightRegistry::global(),
+            proxy_config: proxy_config_state.clone(),
+            persist_config,
         };
+        // 恢复上次未跑完的批任务
+        state.batches.resume_pending(state.clone());
 
 
         // 构建路由 - 使用新架构的 handlers！
@@ -144,6 +341,35 @@ impl AxumServer {
                 "/v1/audio/transcriptions",
                 post(handlers::audio::handle_audio_transcription),
             ) // 音频转录 API
+            .route(
+                "/v1/audio/speech",
+                post(handlers::audio::handle_audio_speech),
+            ) // 语音合成 API
+            .route(
+                "/v1/moderations",
+                post(handlers::moderations::handle_moderations),
+            ) // 内容审核 API
+            .route("/v1/summarize", post(handlers::summarize::handle_summarize)) // 长文本摘要 (非标准)
+            .route("/v1/ocr", post(handlers::ocr::handle_ocr)) // 图片 OCR (非标准)
+            .route("/v1/rerank", post(handlers::rerank::handle_rerank)) // 文档重排序 (Cohere / Jina 兼容)
+            .route("/v2/rerank", post(handlers::rerank::handle_rerank))
+            .route("/v1/fanout", post(handlers::fanout::handle_fanout)) // 并行扇出 / 竞速 (非标准)
+            // Batch API
+            .route(
+                "/v1/files",
+                get(handlers::batches::handle_list_files).post(handlers::batches::handle_upload_file),
+            )
+            .route(
+                "/v1/files/:id",
+                get(handlers::batches::handle_get_file).delete(handlers::batches::handle_delete_file),
+            )
+            .route("/v1/files/:id/content", get(handlers::batches::handle_file_content))
+            .route(
+                "/v1/batches",
+                get(handlers::batches::handle_list_batches).post(handlers::batches::handle_create_batch),
+            )
+            .route("/v1/batches/:id", get(handlers::batches::handle_get_batch))
+            .route("/v1/batches/:id/cancel", post(handlers::batches::handle_cancel_batch))
             // Claude Protocol
             .route("/v1/messages", post(handlers::claude::handle_messages))
             .route(
@@ -179,30 +405,110 @@ impl AxumServer {
                 post(handlers::gemini::handle_count_tokens),
             ) // Specific route priority
             .route("/v1/models/detect", post(handlers::common::handle_detect_model))
+            .route("/v1/cancel/:request_id", post(handlers::common::handle_cancel_request))
             .route("/internal/warmup", post(handlers::warmup::handle_warmup)) // 内部预热端点
             .route("/v1/api/event_logging/batch", post(silent_ok_handler))
             .route("/v1/api/event_logging", post(silent_ok_handler))
+            // Admin API (远程管理 headless 实例)
+            .route(
+                "/admin/accounts",
+                get(handlers::admin::handle_list_accounts).post(handlers::admin::handle_add_account),
+            )
+            .route("/admin/accounts/reorder", post(handlers::admin::handle_reorder_accounts))
+            .route("/admin/accounts/bulk", post(handlers::admin::handle_bulk_accounts))
+            .route("/admin/accounts/:id", axum::routing::delete(handlers::admin::handle_delete_account))
+            .route("/admin/accounts/:id/status", post(handlers::admin::handle_set_account_status))
+            .route("/admin/accounts/:id/pause", post(handlers::admin::handle_pause_account))
+            .route("/admin/accounts/:id/resume", post(handlers::admin::handle_resume_account))
+            .route("/admin/stats", get(handlers::admin::handle_stats))
+            .route("/admin/inflight", get(handlers::admin::handle_inflight))
+            .route("/admin/circuits", get(handlers::admin::handle_circuits))
+            .route("/admin/circuits/reset", post(handlers::admin::handle_reset_circuits))
+            .route("/admin/quota-profiles", get(handlers::admin::handle_quota_profiles))
+            .route(
+                "/admin/config",
+                get(handlers::admin::handle_get_config).put(handlers::admin::handle_update_config),
+            )
             .route("/healthz", get(health_check_handler))
-            .layer(DefaultBodyLimit::max(100 * 1024 * 1024))
+            .route("/readyz", get(readiness_handler))
+            .route("/openapi.json", get(crate::proxy::openapi::handle_openapi_json))
+            .route("/docs", get(crate::proxy::openapi::handle_docs))
+            .layer(DefaultBodyLimit::max(max_body_bytes))
+            // 按 API Key 解析流式平滑速率，供 handler 包装上游流
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::stream_pacing_middleware))
+            // 按 API Key 匹配响应改写规则
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::response_rewrite_middleware))
+            // 降级链位于预检内层: 每个备用模型都重新经过 handler 的完整重试逻辑
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::model_fallback_middleware))
+            // 预检位于 monitor 内层，被拒绝的请求同样会记录到监控
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::token_budget_middleware))
+            // 竞速位于延迟预算内层: 各路使用降级后的模型，并共享同一预算作用域
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::racing_middleware))
+            // 延迟预算位于分流内层: 按实际分到的模型判断是否降级，降级后的模型仍经过预检
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::latency_budget_middleware))
+            // 分流位于预检外层: 预检按实际分到的模型检查窗口
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::ab_routing_middleware))
+            // best_of 的每个候选与裁判请求都会经过内层的分流/预检/降级链
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::best_of_middleware))
+            // 虚拟模型的每个步骤都是内部请求，main 步骤仍可使用 best_of
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::virtual_models_middleware))
+            // 预设位于 best_of 外层: 候选与裁判请求都已是预设展开后的实际模型
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::presets_middleware))
+            // 档案位于预设外层: 档案映射的目标可以是 preset:<name>
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::profile_middleware))
+            // 账号固定在档案外层: 模型后缀先被剥离，之后的映射与调度都在固定作用域内
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::account_pinning_middleware))
+            // 统计位于 monitor 内侧: monitor 从统计注释行 / 响应头写入请求历史
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::stream_stats_middleware))
+            // 错误解释位于 monitor 内侧: 请求历史记录的是附加了错误码与建议的错误体
+            .layer(axum::middleware::from_fn(crate::proxy::middleware::error_explain_middleware))
             .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::monitor::monitor_middleware))
+            // 落盘与 monitor 同层: 记录的同样是已脱敏的内容
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::stream_tee_middleware))
+            // 翻译位于脱敏内侧: 翻译模型只看到已脱敏的文本，历史记录的是译后的实际请求
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::translation_middleware))
+            // 本地文件内联同样位于翻译外侧，翻译模型不会收到附件内容
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::local_files_middleware))
+            // 图片提取位于翻译外侧: 翻译模型只看到占位符而不是整段 base64
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::markdown_images_middleware))
+            // 钩子位于脱敏内侧: Webhook / 外部程序收到的是已脱敏的请求与尚未还原的响应
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::hooks_middleware))
+            // 脱敏位于 monitor 外层: 监控记录的是实际发往上游的 (已脱敏) 内容
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::pii_scrub_middleware))
+            // 下游压缩: 按 Accept-Encoding 压缩响应 (默认谓词已排除 text/event-stream 与小于 32 字节的响应)
+            // 放在 monitor 外层，监控记录的始终是未压缩内容
+            .layer(tower_http::compression::CompressionLayer::new())
+            .layer(tower_http::decompression::RequestDecompressionLayer::new())
+            // 请求 ID 位于压缩/钩子外层: 取消时整个内层处理链 (含重试与降级) 一并中止
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::request_id_middleware))
+            .layer(axum::middleware::from_fn(crate::proxy::middleware::conversation_middleware))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::body_limit_middleware))
             .layer(TraceLayer::new_for_http())
             .layer(axum::middleware::from_fn_with_state(
                 security_state.clone(),
                 crate::proxy::middleware::auth_middleware,
             ))
-            .layer(crate::proxy::middleware::cors_layer())
+            .layer(crate::proxy::middleware::cors_layer(&cors_config))
             .with_state(state);
 
-        // 绑定地址
-        let addr = format!("{}:{}", host, port);
-        let listener = tokio::net::TcpListener::bind(&addr)
-            .await
-            .map_err(|e| format!("地址 {} 绑定失败: {}", addr, e))?;
-
-        tracing::info!("反代服务器启动在 http://{}", addr);
+        // 绑定地址 (IPv4 / IPv6 / 双栈)
+        let listeners = bind_listeners(&listen_addrs, port)?;
+        for listener in &listeners {
+            if let Ok(addr) = listener.local_addr() {
+                tracing::info!("反代服务器启动在 http://{}", addr);
+            }
+        }
+        let mut local_listener = if local_socket_config.enabled {
+            let path = crate::proxy::local_socket::resolve_path(&local_socket_config.path)?;
+            let listener = crate::proxy::local_socket::LocalListener::bind(path)?;
+            tracing::info!("反代服务器本地套接字: {}", listener.path().display());
+            Some(listener)
+        } else {
+            None
+        };
 
         // 创建关闭通道
-        let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<std::time::Duration>();
 
         let server_instance = Self {
             shutdown_tx: Some(shutdown_tx),
@@ -210,68 +516,272 @@ impl AxumServer {
             proxy_state,
             security_state,
             zai_state,
+            openai_backends: openai_backends_state,
+            vertex: vertex_state,
+            ai_studio: ai_studio_state,
             experimental: experimental_state.clone(),
+            upstream: upstream_client,
+            hooks: hooks_state,
+            pii_scrub: pii_scrub_state,
+            stream_tee: stream_tee_state,
+            token_budget: token_budget_state,
+            model_fallback: model_fallback_state,
+            ab_test: ab_test_state,
+            latency_budget: latency_budget_state,
+            racing: racing_state,
+            account_pinning: account_pinning_state,
+            proxy_config: proxy_config_state,
         };
 
+        // 本地套接字连接带上标记 (最外层)，供鉴权中间件识别
+        let local_app = app.clone().layer(axum::Extension(crate::proxy::local_socket::LocalSocketPeer));
+
         // 在新任务中启动服务器
         let handle = tokio::spawn(async move {
-            use hyper::server::conn::http1;
-            use hyper_util::rt::TokioIo;
-            use hyper_util::service::TowerToHyperService;
+            // 在途连接集合 + 排空信号 (用于优雅停机)
+            let mut connections = tokio::task::JoinSet::new();
+            let (drain_tx, drain_rx) = tokio::sync::watch::channel(false);
 
-            loop {
+            let drain_timeout = loop {
+                let accept = futures::future::select_all(
+                    listeners.iter().map(|listener| Box::pin(listener.accept())),
+                );
                 tokio::select! {
-                    res = listener.accept() => {
+                    (res, _, _) = accept => {
                         match res {
                             Ok((stream, _)) => {
-                                let io = TokioIo::new(stream);
-                                let service = TowerToHyperService::new(app.clone());
-
-                                tokio::task::spawn(async move {
-                                    if let Err(err) = http1::Builder::new()
-                                        .serve_connection(io, service)
-                                        .with_upgrades() // 支持 WebSocket (如果以后需要)
-                                        .await
-                                    {
-                                        debug!("连接处理结束或出错: {:?}", err);
-                                    }
-                                });
+                                connections.spawn(serve_connection(stream, app.clone(), drain_rx.clone()));
                             }
                             Err(e) => {
                                 error!("接收连接失败: {:?}", e);
                             }
                         }
                     }
-                    _ = &mut shutdown_rx => {
+                    res = crate::proxy::local_socket::accept_optional(&mut local_listener) => {
+                        match res {
+                            Ok(stream) => {
+                                connections.spawn(serve_connection(stream, local_app.clone(), drain_rx.clone()));
+                            }
+                            Err(e) => {
+                                error!("接收本地套接字连接失败: {:?}", e);
+                            }
+                        }
+                    }
+                    // 回收已结束的连接任务
+                    Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                    timeout = &mut shutdown_rx => {
                         tracing::info!("反代服务器停止监听");
-                        break;
+                        break timeout.unwrap_or_default();
                     }
                 }
+            };
+
+            // 关闭监听端口，通知所有连接进入排空状态
+            drop(listeners);
+            drop(local_listener);
+            let _ = drain_tx.send(true);
+
+            let in_flight = connections.len();
+            if in_flight > 0 {
+                tracing::info!("等待 {} 个在途连接完成 (最长 {:?})", in_flight, drain_timeout);
+                let drained = tokio::time::timeout(drain_timeout, async {
+                    while connections.join_next().await.is_some() {}
+                })
+                .await;
+                if drained.is_err() {
+                    tracing::warn!("排空超时，强制断开剩余 {} 个连接", connections.len());
+                    connections.abort_all();
+                    while connections.join_next().await.is_some() {}
+                }
             }
+            tracing::info!("反代服务器已完全停止");
         });
 
         Ok((server_instance, handle))
     }
 
     /// 停止服务器
-    pub fn stop(mut self) {
+    /// 立即停止监听新连接，在途请求/流最多等待 `drain_timeout` 后强制断开
+    pub fn stop(mut self, drain_timeout: std::time::Duration) {
         if let Some(tx) = self.shutdown_tx.take() {
-            let _ = tx.send(());
+            let _ = tx.send(drain_timeout);
         }
     }
 }
 
 // ===== API 处理器 (旧代码已移除，由 src/proxy/handlers/* 接管) =====
 
-/// 健康检查处理器
+/// 进程启动时间 (健康检查中的 uptime)
+static PROCESS_STARTED: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+
+/// 健康检查处理器 (进程存活)
 async fn health_check_handler() -> Response {
     Json(serde_json::json!({
-        "status": "ok"
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_secs": PROCESS_STARTED.get_or_init(std::time::Instant::now).elapsed().as_secs()
     }))
     .into_response()
 }
 
+/// 就绪检查处理器: 至少一个 Token 有效且可调度的账号，且至少一个上游端点可达
+async fn readiness_handler(State(state): State<AppState>) -> Response {
+    let accounts = state.token_manager.readiness();
+    let endpoints = state.upstream.probe_reachability().await;
+
+    let mut reasons = Vec::new();
+    if accounts.available == 0 {
+        reasons.push(if accounts.total == 0 {
+            "no_accounts"
+        } else if accounts.valid_token == 0 {
+            "no_valid_token"
+        } else if accounts.paused == accounts.valid_token {
+            "all_accounts_paused"
+        } else {
+            "all_accounts_unavailable"
+        });
+    }
+    if !endpoints.iter().any(|e| e.reachable) {
+        reasons.push("upstream_unreachable");
+    }
+
+    let ready = reasons.is_empty();
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (
+        status,
+        Json(serde_json::json!({
+            "status": if ready { "ready" } else { "not_ready" },
+            "reasons": reasons,
+            "accounts": accounts,
+            "upstream": { "endpoints": endpoints }
+        })),
+    )
+        .into_response()
+}
+
 /// 静默成功处理器 (用于拦截遥测日志等)
 async fn silent_ok_handler() -> Response {
     StatusCode::OK.into_response()
 }
+
+/// 处理单个 HTTP/1 连接；收到排空信号后停止接收新请求，等待当前响应(含流式)结束
+async fn serve_connection<I, S>(io: I, service: S, mut drain_rx: tokio::sync::watch::Receiver<bool>)
+where
+    I: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    S: tower::Service<axum::http::Request<hyper::body::Incoming>, Response = Response, Error = std::convert::Infallible>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    use hyper::server::conn::http1;
+    use hyper_util::rt::TokioIo;
+    use hyper_util::service::TowerToHyperService;
+
+    let conn = http1::Builder::new()
+        .serve_connection(TokioIo::new(io), TowerToHyperService::new(service))
+        .with_upgrades(); // 支持 WebSocket (如果以后需要)
+    tokio::pin!(conn);
+
+    let result = tokio::select! {
+        res = conn.as_mut() => res,
+        _ = drain_rx.changed() => {
+            conn.as_mut().graceful_shutdown();
+            conn.await
+        }
+    };
+    if let Err(err) = result {
+        debug!("连接处理结束或出错: {:?}", err);
+    }
+}
+
+/// 绑定所有监听地址；某个地址族不可用 (如系统禁用 IPv6) 时跳过并告警，全部失败才报错
+fn bind_listeners(
+    addrs: &[crate::proxy::config::ListenAddr],
+    port: u16,
+) -> Result<Vec<tokio::net::TcpListener>, String> {
+    let mut listeners = Vec::new();
+    let mut errors = Vec::new();
+    for addr in addrs {
+        match bind_listener(addr, port) {
+            Ok(listener) => listeners.push(listener),
+            Err(e) if addr.dual_stack => {
+                // 双栈不可用时退回到 IPv4 全部地址
+                tracing::warn!("双栈地址 [{}]:{} 绑定失败 ({}), 改为监听 0.0.0.0", addr.ip, port, e);
+                let fallback = crate::proxy::config::ListenAddr {
+                    ip: std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+                    dual_stack: false,
+                };
+                match bind_listener(&fallback, port) {
+                    Ok(listener) => listeners.push(listener),
+                    Err(e) => errors.push(format!("0.0.0.0:{}: {}", port, e)),
+                }
+            }
+            Err(e) => {
+                tracing::warn!("地址 {} 绑定失败: {}", std::net::SocketAddr::new(addr.ip, port), e);
+                errors.push(format!("{}: {}", std::net::SocketAddr::new(addr.ip, port), e));
+            }
+        }
+    }
+    if listeners.is_empty() {
+        return Err(format!("地址绑定失败: {}", errors.join("; ")));
+    }
+    Ok(listeners)
+}
+
+fn bind_listener(addr: &crate::proxy::config::ListenAddr, port: u16) -> std::io::Result<tokio::net::TcpListener> {
+    use socket2::{Domain, Protocol, Socket, Type};
+    let sock_addr = std::net::SocketAddr::new(addr.ip, port);
+    let socket = Socket::new(Domain::for_address(sock_addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.ip.is_ipv6() {
+        // 显式设置，避免依赖系统默认值 (Windows / BSD 默认仅 IPv6，Linux 默认双栈)
+        socket.set_only_v6(!addr.dual_stack)?;
+    }
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&sock_addr.into())?;
+    socket.listen(1024)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::config::ListenAddr;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    #[tokio::test]
+    async fn test_bind_listeners_skips_unavailable_family() {
+        let addrs = [
+            ListenAddr { ip: IpAddr::V4(Ipv4Addr::LOCALHOST), dual_stack: false },
+            ListenAddr { ip: IpAddr::V6(Ipv6Addr::LOCALHOST), dual_stack: false },
+        ];
+        // IPv6 可能在测试环境中被禁用，但 IPv4 回环地址必须可用
+        let listeners = bind_listeners(&addrs, 0).unwrap();
+        assert!(listeners.iter().any(|l| l.local_addr().unwrap().is_ipv4()));
+    }
+
+    #[test]
+    fn test_listen_addresses_per_family() {
+        let mut config = crate::proxy::config::ProxyConfig::default();
+        assert_eq!(config.listen_addresses().len(), 2);
+
+        config.allow_lan_access = true;
+        assert_eq!(
+            config.listen_addresses(),
+            vec![ListenAddr { ip: IpAddr::V6(Ipv6Addr::UNSPECIFIED), dual_stack: true }]
+        );
+
+        config.listen_ipv6 = false;
+        assert_eq!(config.listen_addresses()[0].ip, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        // 两个地址族都关闭时回退为 IPv4
+        config.listen_ipv4 = false;
+        config.allow_lan_access = false;
+        assert_eq!(
+            config.listen_addresses(),
+            vec![ListenAddr { ip: IpAddr::V4(Ipv4Addr::LOCALHOST), dual_stack: false }]
+        );
+    }
+}
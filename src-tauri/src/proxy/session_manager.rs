@@ -2,11 +2,83 @@ use sha2::{Sha256, Digest};
 use crate::proxy::mappers::claude::models::{ClaudeRequest, MessageContent};
 use crate::proxy::mappers::openai::models::{OpenAIRequest, OpenAIContent};
 use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+/// 客户端显式会话 ID 的最大长度，超出部分改为哈希
+const MAX_EXPLICIT_ID_LEN: usize = 128;
+
+/// 请求级会话上下文 (由 conversation_middleware 建立，贯穿整个请求处理)
+#[derive(Debug, Default)]
+struct ConversationContext {
+    /// 客户端通过 X-Conversation-Id 头显式指定的会话 ID
+    explicit_id: Option<String>,
+    /// 本次请求最终解析出的会话指纹 (供日志分组)
+    resolved_id: Option<String>,
+}
+
+tokio::task_local! {
+    static CONVERSATION: Arc<Mutex<ConversationContext>>;
+}
 
 /// 会话管理器工具
 pub struct SessionManager;
 
 impl SessionManager {
+    /// 在会话上下文中执行请求处理
+    pub async fn scope<F: std::future::Future>(explicit_id: Option<String>, fut: F) -> F::Output {
+        let ctx = ConversationContext {
+            explicit_id: explicit_id.as_deref().and_then(Self::normalize_explicit_id),
+            resolved_id: None,
+        };
+        CONVERSATION.scope(Arc::new(Mutex::new(ctx)), fut).await
+    }
+
+    /// 当前请求解析出的会话 ID (需在 scope 内调用)
+    pub fn current_conversation_id() -> Option<String> {
+        CONVERSATION
+            .try_with(|ctx| ctx.lock().ok().and_then(|c| c.resolved_id.clone()))
+            .ok()
+            .flatten()
+    }
+
+    fn explicit_from_scope() -> Option<String> {
+        CONVERSATION
+            .try_with(|ctx| ctx.lock().ok().and_then(|c| c.explicit_id.clone()))
+            .ok()
+            .flatten()
+    }
+
+    fn record(session_id: &str) {
+        let _ = CONVERSATION.try_with(|ctx| {
+            if let Ok(mut c) = ctx.lock() {
+                c.resolved_id = Some(session_id.to_string());
+            }
+        });
+    }
+
+    /// 规范化客户端提供的会话 ID: 去除首尾空白，过长时哈希
+    pub fn normalize_explicit_id(raw: &str) -> Option<String> {
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
+        if trimmed.len() <= MAX_EXPLICIT_ID_LEN {
+            return Some(trimmed.to_string());
+        }
+        let hash = format!("{:x}", Sha256::digest(trimmed.as_bytes()));
+        Some(format!("cid-{}", &hash[..16]))
+    }
+
+    /// 显式会话 ID 优先级: X-Conversation-Id 头 > 请求体字段
+    fn resolve_explicit(candidates: &[Option<&str>]) -> Option<String> {
+        Self::explicit_from_scope().or_else(|| {
+            candidates
+                .iter()
+                .flatten()
+                .find_map(|raw| Self::normalize_explicit_id(raw))
+        })
+    }
+
     /// 根据 Claude 请求生成稳定的会话指纹 (Session Fingerprint)
     /// 
     /// 设计理念:
@@ -18,11 +90,19 @@ impl SessionManager {
     /// 1. metadata.user_id (客户端显式提供)
     /// 2. 第一条用户消息的 SHA256 哈希
     pub fn extract_session_id(request: &ClaudeRequest) -> String {
+        // 0. X-Conversation-Id 头
+        if let Some(cid) = Self::resolve_explicit(&[]) {
+            tracing::debug!("[SessionManager] Using explicit conversation id: {}", cid);
+            Self::record(&cid);
+            return cid;
+        }
+
         // 1. 优先使用 metadata 中的 user_id
         if let Some(metadata) = &request.metadata {
             if let Some(user_id) = &metadata.user_id {
                 if !user_id.is_empty() && !user_id.contains("session-") {
                     tracing::debug!("[SessionManager] Using explicit user_id: {}", user_id);
+                    Self::record(user_id);
                     return user_id.clone();
                 }
             }
@@ -73,11 +153,19 @@ impl SessionManager {
             content_found,
             request.model
         );
+        Self::record(&sid);
         sid
     }
 
     /// 根据 OpenAI 请求生成稳定的会话指纹
+    /// 优先级: X-Conversation-Id 头 > conversation_id 字段 > user 字段 > 首条用户消息哈希
     pub fn extract_openai_session_id(request: &OpenAIRequest) -> String {
+        if let Some(cid) = Self::resolve_explicit(&[request.conversation_id.as_deref(), request.user.as_deref()]) {
+            tracing::debug!("[SessionManager-OpenAI] Using explicit conversation id: {}", cid);
+            Self::record(&cid);
+            return cid;
+        }
+
         let mut hasher = Sha256::new();
 
         let mut content_found = false;
@@ -115,11 +203,18 @@ impl SessionManager {
         let hash = format!("{:x}", hasher.finalize());
         let sid = format!("sid-{}", &hash[..16]);
         tracing::debug!("[SessionManager-OpenAI] Generated fingerprint: {}", sid);
+        Self::record(&sid);
         sid
     }
 
     /// 根据 Gemini 原生请求 (JSON) 生成稳定的会话指纹
     pub fn extract_gemini_session_id(request: &Value, _model_name: &str) -> String {
+        if let Some(cid) = Self::resolve_explicit(&[]) {
+            tracing::debug!("[SessionManager-Gemini] Using explicit conversation id: {}", cid);
+            Self::record(&cid);
+            return cid;
+        }
+
         let mut hasher = Sha256::new();
 
         let mut content_found = false;
@@ -154,6 +249,51 @@ impl SessionManager {
         let hash = format!("{:x}", hasher.finalize());
         let sid = format!("sid-{}", &hash[..16]);
         tracing::debug!("[SessionManager-Gemini] Generated fingerprint: {}", sid);
+        Self::record(&sid);
         sid
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn openai_request(conversation_id: Option<&str>, user: Option<&str>) -> OpenAIRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "Please refactor the session manager module" }],
+            "conversation_id": conversation_id,
+            "user": user,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_explicit_conversation_id_priority() {
+        // 请求体字段: conversation_id > user > 内容哈希
+        let req = openai_request(Some("conv-1"), Some("user-1"));
+        assert_eq!(SessionManager::extract_openai_session_id(&req), "conv-1");
+        let req = openai_request(None, Some("user-1"));
+        assert_eq!(SessionManager::extract_openai_session_id(&req), "user-1");
+        let req = openai_request(None, None);
+        assert!(SessionManager::extract_openai_session_id(&req).starts_with("sid-"));
+
+        // X-Conversation-Id 头优先于请求体，并记录到当前作用域
+        let req = openai_request(Some("conv-1"), None);
+        let (sid, recorded) = SessionManager::scope(Some(" header-id ".to_string()), async {
+            let sid = SessionManager::extract_openai_session_id(&req);
+            (sid, SessionManager::current_conversation_id())
+        })
+        .await;
+        assert_eq!(sid, "header-id");
+        assert_eq!(recorded.as_deref(), Some("header-id"));
+    }
+
+    #[test]
+    fn test_normalize_explicit_id() {
+        assert_eq!(SessionManager::normalize_explicit_id("   "), None);
+        let long = "x".repeat(500);
+        let normalized = SessionManager::normalize_explicit_id(&long).unwrap();
+        assert!(normalized.starts_with("cid-") && normalized.len() == 20);
+    }
+}
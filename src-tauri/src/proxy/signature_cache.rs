@@ -2,6 +2,8 @@ use std::collections::HashMap;
 use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, SystemTime};
 
+use crate::proxy::state_journal::{JournalRecord, JournalState, SignatureLayer, StampedValue, StateJournal};
+
 // Node.js proxy uses 2 hours TTL
 const SIGNATURE_TTL: Duration = Duration::from_secs(2 * 60 * 60);
 const MIN_SIGNATURE_LENGTH: usize = 50;
@@ -26,6 +28,13 @@ impl<T> CacheEntry<T> {
         }
     }
 
+    fn restored(data: T, unix_secs: i64) -> Self {
+        Self {
+            data,
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(unix_secs.max(0) as u64),
+        }
+    }
+
     fn is_expired(&self) -> bool {
         self.timestamp.elapsed().unwrap_or(Duration::ZERO) > SIGNATURE_TTL
     }
@@ -76,6 +85,7 @@ impl SignatureCache {
         
         if let Ok(mut cache) = self.tool_signatures.lock() {
             tracing::debug!("[SignatureCache] Caching tool signature for id: {}", tool_use_id);
+            journal_signature(SignatureLayer::Tool, tool_use_id, &signature);
             cache.insert(tool_use_id.to_string(), CacheEntry::new(signature));
             
             // Clean up expired entries when limit is reached
@@ -111,6 +121,7 @@ impl SignatureCache {
 
         if let Ok(mut cache) = self.thinking_families.lock() {
             tracing::debug!("[SignatureCache] Caching thinking family for sig (len={}): {}", signature.len(), family);
+            journal_signature(SignatureLayer::Family, &signature, &family);
             cache.insert(signature, CacheEntry::new(family));
             
             if cache.len() > FAMILY_CACHE_LIMIT {
@@ -167,6 +178,7 @@ impl SignatureCache {
                     session_id,
                     signature.len()
                 );
+                journal_signature(SignatureLayer::Session, session_id, &signature);
                 cache.insert(session_id.to_string(), CacheEntry::new(signature));
             }
 
@@ -207,8 +219,7 @@ impl SignatureCache {
         None
     }
 
-    /// Clear all caches (for testing or manual reset)
-    #[allow(dead_code)] // Used in tests
+    /// Clear all caches (tests and panic wipe)
     pub fn clear(&self) {
         if let Ok(mut cache) = self.tool_signatures.lock() {
             cache.clear();
@@ -219,9 +230,38 @@ impl SignatureCache {
         if let Ok(mut cache) = self.session_signatures.lock() {
             cache.clear();
         }
+        StateJournal::global().append(JournalRecord::ClearSignatures);
+    }
+
+    /// Restore unexpired entries from the state journal, keeping their original timestamps
+    pub fn restore(&self, state: &JournalState) {
+        let layers = [
+            (&self.tool_signatures, &state.tool_signatures),
+            (&self.thinking_families, &state.signature_families),
+            (&self.session_signatures, &state.session_signatures),
+        ];
+        for (cache, entries) in layers {
+            if let Ok(mut cache) = cache.lock() {
+                for (key, StampedValue { value, at }) in entries {
+                    let entry = CacheEntry::restored(value.clone(), *at);
+                    if !entry.is_expired() {
+                        cache.entry(key.clone()).or_insert(entry);
+                    }
+                }
+            }
+        }
     }
 }
 
+fn journal_signature(layer: SignatureLayer, key: &str, value: &str) {
+    StateJournal::global().append(JournalRecord::Signature {
+        layer,
+        key: key.to_string(),
+        value: value.to_string(),
+        at: crate::proxy::state_journal::now_secs(),
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -302,4 +342,19 @@ mod tests {
         assert!(cache.get_signature_family(&sig).is_none());
         assert!(cache.get_session_signature("sid-1").is_none());
     }
+
+    #[test]
+    fn test_restore_from_journal() {
+        let cache = SignatureCache::new();
+        let now = crate::proxy::state_journal::now_secs();
+        let mut state = JournalState::default();
+        state.session_signatures.insert("sid-live".into(), StampedValue { value: "s".repeat(60), at: now - 60 });
+        state.session_signatures.insert("sid-old".into(), StampedValue { value: "o".repeat(60), at: now - 3 * 3600 });
+        state.tool_signatures.insert("tool_1".into(), StampedValue { value: "t".repeat(60), at: now });
+
+        cache.restore(&state);
+        assert_eq!(cache.get_session_signature("sid-live"), Some("s".repeat(60)));
+        assert!(cache.get_session_signature("sid-old").is_none());
+        assert_eq!(cache.get_tool_signature("tool_1"), Some("t".repeat(60)));
+    }
 }
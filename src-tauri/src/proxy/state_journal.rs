@@ -0,0 +1,370 @@
+// 崩溃安全的调度状态日志 (Write-Ahead Journal)
+// 限流冷却、连续失败计数、公平调度的估算用量与签名缓存原本只在内存中，应用崩溃或被强制退出后全部丢失，
+// 已冷却的账号会被立即重新选中并再次耗尽配额。
+// 每次状态变更先以一行 JSON 追加写入 journal.log；启动时加载 snapshot.json 并重放日志恢复状态。
+// 日志超过阈值或停机时压缩为新快照 (临时文件 + fsync + rename，任意时刻磁盘上都有一份完整状态)。
+// 仅在反代启动后启用，单元测试中的状态变更不会落盘。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::proxy::rate_limit::RateLimitReason;
+
+const JOURNAL_DIR: &str = "state_journal";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const JOURNAL_FILE: &str = "journal.log";
+/// 日志记录数超过该值时压缩为快照
+const COMPACT_THRESHOLD: usize = 10_000;
+/// 与 RateLimitTracker 的失败计数过期时间一致
+const FAILURE_EXPIRY_SECS: i64 = 3600;
+/// 与 SignatureCache 的 TTL 一致
+const SIGNATURE_TTL_SECS: i64 = 2 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureLayer {
+    Tool,
+    Family,
+    Session,
+}
+
+/// 单条状态变更
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JournalRecord {
+    Lockout {
+        account_id: String,
+        reset_at: i64,
+        reason: RateLimitReason,
+        #[serde(default)]
+        model: Option<String>,
+    },
+    /// 账号请求成功: 清除限流与失败计数
+    ClearAccount { account_id: String },
+    ClearLockout { account_id: String },
+    ClearAllLockouts,
+    Failures { account_id: String, count: u32, at: i64 },
+    UsageEstimate { day: String, account: String, tokens: u64 },
+    Signature { layer: SignatureLayer, key: String, value: String, at: i64 },
+    ClearSignatures,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LockoutEntry {
+    pub reset_at: i64,
+    pub reason: RateLimitReason,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StampedValue {
+    pub value: String,
+    pub at: i64,
+}
+
+/// 重放后的完整状态 (同时也是快照格式)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JournalState {
+    #[serde(default)]
+    pub lockouts: HashMap<String, LockoutEntry>,
+    #[serde(default)]
+    pub failures: HashMap<String, (u32, i64)>,
+    #[serde(default)]
+    pub usage_day: String,
+    #[serde(default)]
+    pub usage_estimates: HashMap<String, u64>,
+    #[serde(default)]
+    pub tool_signatures: HashMap<String, StampedValue>,
+    #[serde(default)]
+    pub signature_families: HashMap<String, StampedValue>,
+    #[serde(default)]
+    pub session_signatures: HashMap<String, StampedValue>,
+}
+
+impl JournalState {
+    fn signatures_mut(&mut self, layer: SignatureLayer) -> &mut HashMap<String, StampedValue> {
+        match layer {
+            SignatureLayer::Tool => &mut self.tool_signatures,
+            SignatureLayer::Family => &mut self.signature_families,
+            SignatureLayer::Session => &mut self.session_signatures,
+        }
+    }
+
+    fn apply(&mut self, record: &JournalRecord) {
+        match record {
+            JournalRecord::Lockout { account_id, reset_at, reason, model } => {
+                self.lockouts.insert(
+                    account_id.clone(),
+                    LockoutEntry { reset_at: *reset_at, reason: *reason, model: model.clone() },
+                );
+            }
+            JournalRecord::ClearAccount { account_id } => {
+                self.lockouts.remove(account_id);
+                self.failures.remove(account_id);
+            }
+            JournalRecord::ClearLockout { account_id } => {
+                self.lockouts.remove(account_id);
+            }
+            JournalRecord::ClearAllLockouts => self.lockouts.clear(),
+            JournalRecord::Failures { account_id, count, at } => {
+                self.failures.insert(account_id.clone(), (*count, *at));
+            }
+            JournalRecord::UsageEstimate { day, account, tokens } => {
+                if *day != self.usage_day {
+                    self.usage_day = day.clone();
+                    self.usage_estimates.clear();
+                }
+                *self.usage_estimates.entry(account.clone()).or_insert(0) += tokens;
+            }
+            JournalRecord::Signature { layer, key, value, at } => {
+                self.signatures_mut(*layer)
+                    .insert(key.clone(), StampedValue { value: value.clone(), at: *at });
+            }
+            JournalRecord::ClearSignatures => {
+                self.tool_signatures.clear();
+                self.signature_families.clear();
+                self.session_signatures.clear();
+            }
+        }
+    }
+
+    /// 丢弃已过期的状态 (解除的限流、过期的失败计数、非当日用量、超过 TTL 的签名)
+    fn prune(&mut self, now: i64, today: &str) {
+        self.lockouts.retain(|_, l| l.reset_at > now);
+        self.failures.retain(|_, (_, at)| now - *at <= FAILURE_EXPIRY_SECS);
+        if self.usage_day != today {
+            self.usage_day = today.to_string();
+            self.usage_estimates.clear();
+        }
+        for layer in [SignatureLayer::Tool, SignatureLayer::Family, SignatureLayer::Session] {
+            self.signatures_mut(layer).retain(|_, s| now - s.at <= SIGNATURE_TTL_SECS);
+        }
+    }
+}
+
+/// 由快照与日志内容重放出状态；无法解析的行 (如崩溃时写了一半的末行) 直接跳过
+fn replay(snapshot: Option<&str>, journal: &str) -> JournalState {
+    let mut state = snapshot
+        .and_then(|s| serde_json::from_str::<JournalState>(s).ok())
+        .unwrap_or_default();
+    for line in journal.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str::<JournalRecord>(line) {
+            Ok(record) => state.apply(&record),
+            Err(e) => tracing::debug!("[StateJournal] 跳过损坏的日志行: {}", e),
+        }
+    }
+    state
+}
+
+pub fn now_secs() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn today() -> String {
+    chrono::Utc::now().format("%Y-%m-%d").to_string()
+}
+
+struct JournalInner {
+    dir: Option<PathBuf>,
+    file: Option<File>,
+    state: JournalState,
+    records: usize,
+}
+
+pub struct StateJournal {
+    enabled: AtomicBool,
+    inner: Mutex<JournalInner>,
+}
+
+impl StateJournal {
+    /// Global singleton instance
+    pub fn global() -> &'static StateJournal {
+        static INSTANCE: OnceLock<StateJournal> = OnceLock::new();
+        INSTANCE.get_or_init(|| StateJournal {
+            enabled: AtomicBool::new(false),
+            inner: Mutex::new(JournalInner { dir: None, file: None, state: JournalState::default(), records: 0 }),
+        })
+    }
+
+    /// 加载快照并重放日志，压缩后开始记录。返回恢复出的状态，以及是否为本进程首次打开
+    /// (进程级缓存只需在首次打开时恢复，反代重启时只需恢复新建的 TokenManager)
+    pub fn open(&self, data_dir: &Path) -> Result<(JournalState, bool), String> {
+        let mut inner = self.inner.lock().map_err(|_| "state_journal_poisoned".to_string())?;
+        if self.enabled.load(Ordering::SeqCst) {
+            inner.state.prune(now_secs(), &today());
+            return Ok((inner.state.clone(), false));
+        }
+
+        let dir = data_dir.join(JOURNAL_DIR);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create journal directory: {}", e))?;
+        let snapshot = std::fs::read_to_string(dir.join(SNAPSHOT_FILE)).ok();
+        let journal = std::fs::read_to_string(dir.join(JOURNAL_FILE)).unwrap_or_default();
+        let mut state = replay(snapshot.as_deref(), &journal);
+        state.prune(now_secs(), &today());
+
+        inner.dir = Some(dir);
+        inner.state = state;
+        Self::compact_locked(&mut inner)?;
+        self.enabled.store(true, Ordering::SeqCst);
+
+        tracing::info!(
+            "[StateJournal] 已恢复 {} 个限流冷却、{} 个失败计数、{} 个签名",
+            inner.state.lockouts.len(),
+            inner.state.failures.len(),
+            inner.state.tool_signatures.len() + inner.state.signature_families.len() + inner.state.session_signatures.len()
+        );
+        Ok((inner.state.clone(), true))
+    }
+
+    /// 追加一条状态变更 (未启用时忽略)
+    pub fn append(&self, record: JournalRecord) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        let Ok(mut inner) = self.inner.lock() else { return };
+        inner.state.apply(&record);
+        let Ok(mut line) = serde_json::to_string(&record) else { return };
+        line.push('\n');
+        // 整行一次写入，崩溃时最多留下一行残缺记录 (重放时跳过)
+        if let Some(file) = inner.file.as_mut() {
+            if let Err(e) = file.write_all(line.as_bytes()) {
+                tracing::warn!("[StateJournal] 写入日志失败: {}", e);
+                return;
+            }
+        }
+        inner.records += 1;
+        if inner.records >= COMPACT_THRESHOLD {
+            if let Err(e) = Self::compact_locked(&mut inner) {
+                tracing::warn!("[StateJournal] 压缩日志失败: {}", e);
+            }
+        }
+    }
+
+    /// 把当前状态写成快照并清空日志 (停机时调用)
+    pub fn compact(&self) {
+        if !self.enabled.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Ok(mut inner) = self.inner.lock() {
+            if let Err(e) = Self::compact_locked(&mut inner) {
+                tracing::warn!("[StateJournal] 压缩日志失败: {}", e);
+            }
+        }
+    }
+
+    fn compact_locked(inner: &mut JournalInner) -> Result<(), String> {
+        let Some(dir) = inner.dir.clone() else {
+            return Ok(());
+        };
+        inner.state.prune(now_secs(), &today());
+        let content = serde_json::to_vec(&inner.state).map_err(|e| format!("Failed to serialize snapshot: {}", e))?;
+
+        // 先让新快照完整落盘，再截断日志；任一步崩溃都能从 (旧快照 + 日志) 或 (新快照 + 日志) 恢复
+        let tmp = dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        {
+            let mut file = File::create(&tmp).map_err(|e| format!("Failed to write snapshot: {}", e))?;
+            file.write_all(&content).map_err(|e| format!("Failed to write snapshot: {}", e))?;
+            file.sync_all().map_err(|e| format!("Failed to sync snapshot: {}", e))?;
+        }
+        std::fs::rename(&tmp, dir.join(SNAPSHOT_FILE)).map_err(|e| format!("Failed to replace snapshot: {}", e))?;
+
+        inner.file = None;
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(dir.join(JOURNAL_FILE))
+            .map_err(|e| format!("Failed to open journal: {}", e))?;
+        drop(file);
+        inner.file = Some(
+            OpenOptions::new()
+                .append(true)
+                .open(dir.join(JOURNAL_FILE))
+                .map_err(|e| format!("Failed to open journal: {}", e))?,
+        );
+        inner.records = 0;
+        Ok(())
+    }
+}
+
+/// 反代启动时调用: 恢复限流冷却到新的 TokenManager，首次打开时同时恢复估算用量与签名缓存
+pub fn open_and_restore(data_dir: &Path, token_manager: &crate::proxy::TokenManager) {
+    match StateJournal::global().open(data_dir) {
+        Ok((state, first_open)) => {
+            token_manager.restore_journaled_state(&state);
+            if first_open {
+                crate::proxy::usage_ledger::DailyUsageLedger::global()
+                    .restore_estimates(&state.usage_day, &state.usage_estimates);
+                crate::proxy::signature_cache::SignatureCache::global().restore(&state);
+            }
+        }
+        Err(e) => tracing::warn!("[StateJournal] 恢复调度状态失败，将从空状态开始: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lockout(account: &str, reset_at: i64) -> JournalRecord {
+        JournalRecord::Lockout {
+            account_id: account.to_string(),
+            reset_at,
+            reason: RateLimitReason::QuotaExhausted,
+            model: None,
+        }
+    }
+
+    #[test]
+    fn test_replay_skips_torn_line_and_applies_clears() {
+        let journal = [
+            serde_json::to_string(&lockout("a", 2_000)).unwrap(),
+            serde_json::to_string(&lockout("b", 2_000)).unwrap(),
+            serde_json::to_string(&JournalRecord::Failures { account_id: "a".into(), count: 3, at: 900 }).unwrap(),
+            serde_json::to_string(&JournalRecord::ClearAccount { account_id: "b".into() }).unwrap(),
+            "{\"op\":\"lockout\",\"account_id\":\"c\",\"res".to_string(),
+        ]
+        .join("\n");
+        let state = replay(None, &journal);
+        assert_eq!(state.lockouts.len(), 1);
+        assert_eq!(state.lockouts["a"].reason, RateLimitReason::QuotaExhausted);
+        assert_eq!(state.failures["a"], (3, 900));
+    }
+
+    #[test]
+    fn test_replay_on_top_of_snapshot_and_prune() {
+        let mut snapshot = JournalState::default();
+        snapshot.apply(&lockout("expired", 500));
+        snapshot.apply(&lockout("active", 5_000));
+        snapshot.apply(&JournalRecord::UsageEstimate { day: "2026-01-01".into(), account: "a@x".into(), tokens: 10 });
+        let journal = [
+            serde_json::to_string(&JournalRecord::UsageEstimate { day: "2026-01-01".into(), account: "a@x".into(), tokens: 5 }).unwrap(),
+            serde_json::to_string(&JournalRecord::Signature {
+                layer: SignatureLayer::Session,
+                key: "sid-1".into(),
+                value: "sig".into(),
+                at: 1_000 - SIGNATURE_TTL_SECS - 1,
+            })
+            .unwrap(),
+        ]
+        .join("\n");
+        let mut state = replay(Some(&serde_json::to_string(&snapshot).unwrap()), &journal);
+        assert_eq!(state.usage_estimates["a@x"], 15);
+        assert_eq!(state.session_signatures.len(), 1);
+
+        state.prune(1_000, "2026-01-01");
+        assert!(state.lockouts.contains_key("active"));
+        assert!(!state.lockouts.contains_key("expired"));
+        assert_eq!(state.usage_estimates["a@x"], 15);
+        assert!(state.session_signatures.is_empty());
+
+        state.prune(1_000, "2026-01-02");
+        assert!(state.usage_estimates.is_empty());
+    }
+}
@@ -0,0 +1,795 @@
+// 外部状态存储 (多副本部署)
+// 本地数据目录仍是账号与调度器的工作副本；配置了共享后端 (目录 / SQLite / PostgreSQL) 时，后台按间隔双向同步:
+// - accounts: 账号文件 (含刷新后的 Token 与配额)，删除以墓碑记录传播，同一账号并发修改时后写入者生效
+// - lockouts: 限流冷却 (到期自然失效，成功请求清除冷却不会传播)
+// - history: 请求历史只追加镜像，不回拉到本地
+// 记录带存储分配的单调版本号与副本 ID: 拉取按版本号增量读取 (不依赖各副本的时钟)，并跳过本副本写入的记录。
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tokio::time::{self, Duration};
+
+use crate::proxy::config::{StateStoreBackend, StateStoreConfig};
+use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::state_journal::{JournalState, LockoutEntry};
+use crate::proxy::TokenManager;
+
+const NS_ACCOUNTS: &str = "accounts";
+const NS_LOCKOUTS: &str = "lockouts";
+const NS_HISTORY: &str = "history";
+/// 共享目录锁的等待上限
+const DIR_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// 超过该时长的目录锁视为持有者已崩溃 (正常持有时间为毫秒级)
+const DIR_LOCK_STALE: Duration = Duration::from_secs(30);
+/// PostgreSQL 写入事务的咨询锁 ("agstate")，保证版本号按提交顺序可见
+const PG_VERSION_LOCK: i64 = 0x0061_6773_7461_7465;
+/// 待镜像的请求历史上限 (存储不可用时丢弃最旧的记录)
+const MAX_PENDING_HISTORY: usize = 5_000;
+
+const SQL_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS ag_state (
+    namespace TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT,
+    updated_at BIGINT NOT NULL,
+    origin TEXT NOT NULL,
+    version BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (namespace, key)
+);";
+/// 旧版本创建的表没有 version 列 (SQLite 不支持 ADD COLUMN IF NOT EXISTS，重复添加的错误被忽略)
+const SQL_ADD_VERSION_SQLITE: &str = "ALTER TABLE ag_state ADD COLUMN version BIGINT NOT NULL DEFAULT 0";
+const SQL_ADD_VERSION_POSTGRES: &str = "ALTER TABLE ag_state ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 0;
+CREATE SEQUENCE IF NOT EXISTS ag_state_version_seq;";
+const SQL_INDEX: &str = "CREATE INDEX IF NOT EXISTS ag_state_version ON ag_state (namespace, version);";
+
+/// 版本号由存储在写入时分配，按到达存储的顺序后写入者生效 (不比较各副本的墙钟时间)
+const SQL_UPSERT_SQLITE: &str = "INSERT INTO ag_state (namespace, key, value, updated_at, origin, version) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+    ON CONFLICT (namespace, key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at, origin = excluded.origin, version = excluded.version";
+const SQL_UPSERT_POSTGRES: &str = "INSERT INTO ag_state (namespace, key, value, updated_at, origin, version)
+    VALUES ($1, $2, $3, $4, $5, nextval('ag_state_version_seq'))
+    ON CONFLICT (namespace, key) DO UPDATE SET value = EXCLUDED.value, updated_at = EXCLUDED.updated_at, origin = EXCLUDED.origin, version = EXCLUDED.version";
+
+static HISTORY_ENABLED: AtomicBool = AtomicBool::new(false);
+static PENDING_HISTORY: Mutex<VecDeque<ProxyRequestLog>> = Mutex::new(VecDeque::new());
+static SYNC_TASK: Mutex<Option<tokio::task::JoinHandle<()>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StoredEntry {
+    pub key: String,
+    /// None 表示已删除 (墓碑)
+    pub value: Option<String>,
+    /// 写入副本的本地时间 (毫秒，仅供参考，各副本时钟可能有偏差)
+    pub updated_at: i64,
+    /// 写入的副本
+    pub origin: String,
+    /// 存储分配的单调版本号 (写入时忽略调用方的值)
+    #[serde(default)]
+    pub version: i64,
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// 本进程的副本 ID (每次启动重新生成)
+fn replica_id() -> &'static str {
+    static ID: OnceLock<String> = OnceLock::new();
+    ID.get_or_init(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// 文件名安全的键 (原始键保存在记录内)
+fn file_key(key: &str) -> String {
+    key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@') { c } else { '_' })
+        .collect()
+}
+
+/// 共享目录: `<path>/<namespace>/<key>.json`，版本计数器为 `<path>/.version`
+struct DirStore {
+    root: PathBuf,
+}
+
+/// 共享目录上的独占锁 (独占创建 `<path>/.lock`)，释放时删除
+struct DirLock {
+    path: PathBuf,
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl DirStore {
+    /// 目录没有原子计数器: 写入与读取都在锁内进行，版本号分配与可见顺序一致
+    fn lock(&self) -> Result<DirLock, String> {
+        std::fs::create_dir_all(&self.root).map_err(|e| format!("Failed to create {}: {}", self.root.display(), e))?;
+        let path = self.root.join(".lock");
+        let deadline = Instant::now() + DIR_LOCK_TIMEOUT;
+        loop {
+            match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(DirLock { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = std::fs::metadata(&path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|t| t.elapsed().ok())
+                        .is_some_and(|age| age > DIR_LOCK_STALE);
+                    if stale {
+                        tracing::warn!("[StateStore] 清理残留的目录锁 {}", path.display());
+                        let _ = std::fs::remove_file(&path);
+                        continue;
+                    }
+                    if Instant::now() > deadline {
+                        return Err(format!("Timed out waiting for {}", path.display()));
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                }
+                Err(e) => return Err(format!("Failed to lock {}: {}", path.display(), e)),
+            }
+        }
+    }
+
+    fn put_many(&self, namespace: &str, entries: &[StoredEntry]) -> Result<(), String> {
+        let _lock = self.lock()?;
+        let dir = self.root.join(namespace);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let counter = self.root.join(".version");
+        let mut version: i64 =
+            std::fs::read_to_string(&counter).ok().and_then(|v| v.trim().parse().ok()).unwrap_or(0);
+        for entry in entries {
+            version += 1;
+            let entry = StoredEntry { version, ..entry.clone() };
+            let path = dir.join(format!("{}.json", file_key(&entry.key)));
+            let content = serde_json::to_vec(&entry).map_err(|e| e.to_string())?;
+            Self::write_atomic(&path, &content)?;
+        }
+        Self::write_atomic(&counter, version.to_string().as_bytes())
+    }
+
+    /// 临时文件 + rename，其他副本不会读到半截文件
+    fn write_atomic(path: &std::path::Path, content: &[u8]) -> Result<(), String> {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let tmp = path.with_file_name(format!(".{}.{}.tmp", name, replica_id()));
+        std::fs::write(&tmp, content).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        std::fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+    }
+
+    fn read(path: &std::path::Path) -> Option<StoredEntry> {
+        serde_json::from_slice(&std::fs::read(path).ok()?).ok()
+    }
+
+    fn changed_since(&self, namespace: &str, since: i64) -> Result<Vec<StoredEntry>, String> {
+        let _lock = self.lock()?;
+        let dir = self.root.join(namespace);
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            return Ok(Vec::new());
+        };
+        Ok(entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("json"))
+            .filter_map(|p| Self::read(&p))
+            .filter(|e| e.version > since)
+            .collect())
+    }
+
+    fn delete_namespace(&self, namespace: &str) -> Result<usize, String> {
+        let dir = self.root.join(namespace);
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            return Ok(0);
+        };
+        let mut deleted = 0;
+        for path in entries.flatten().map(|e| e.path()).filter(|p| p.is_file()) {
+            std::fs::remove_file(&path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
+}
+
+/// 挂载卷上的 SQLite 文件
+struct SqliteStore {
+    path: PathBuf,
+}
+
+impl SqliteStore {
+    fn connect(&self) -> Result<rusqlite::Connection, String> {
+        let conn = rusqlite::Connection::open(&self.path)
+            .map_err(|e| format!("Failed to open {}: {}", self.path.display(), e))?;
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "busy_timeout", 5000).map_err(|e| e.to_string())?;
+        conn.execute_batch(SQL_SCHEMA).map_err(|e| e.to_string())?;
+        if let Err(e) = conn.execute(SQL_ADD_VERSION_SQLITE, []) {
+            if !e.to_string().contains("duplicate column") {
+                return Err(e.to_string());
+            }
+        }
+        conn.execute_batch(SQL_INDEX).map_err(|e| e.to_string())?;
+        Ok(conn)
+    }
+
+    fn put_many(&self, namespace: &str, entries: &[StoredEntry]) -> Result<(), String> {
+        let mut conn = self.connect()?;
+        // IMMEDIATE: 事务开始即持有写锁，max(version) + 1 不会被其他副本并发分配
+        let tx = conn
+            .transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+            .map_err(|e| e.to_string())?;
+        let mut version: i64 = tx
+            .query_row("SELECT COALESCE(MAX(version), 0) FROM ag_state", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        for entry in entries {
+            version += 1;
+            tx.execute(
+                SQL_UPSERT_SQLITE,
+                rusqlite::params![namespace, entry.key, entry.value, entry.updated_at, entry.origin, version],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn changed_since(&self, namespace: &str, since: i64) -> Result<Vec<StoredEntry>, String> {
+        let conn = self.connect()?;
+        let mut stmt = conn
+            .prepare("SELECT key, value, updated_at, origin, version FROM ag_state WHERE namespace = ?1 AND version > ?2")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(rusqlite::params![namespace, since], |row| {
+                Ok(StoredEntry {
+                    key: row.get(0)?,
+                    value: row.get(1)?,
+                    updated_at: row.get(2)?,
+                    origin: row.get(3)?,
+                    version: row.get(4)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    fn delete_namespace(&self, namespace: &str) -> Result<usize, String> {
+        let conn = self.connect()?;
+        conn.execute("DELETE FROM ag_state WHERE namespace = ?1", rusqlite::params![namespace])
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// PostgreSQL (断线后下次访问时自动重连)
+struct PostgresStore {
+    url: String,
+    client: tokio::sync::Mutex<Option<tokio_postgres::Client>>,
+}
+
+impl PostgresStore {
+    async fn connected(&self) -> Result<tokio::sync::MutexGuard<'_, Option<tokio_postgres::Client>>, String> {
+        let mut guard = self.client.lock().await;
+        if guard.as_ref().map_or(true, |c| c.is_closed()) {
+            // 按连接串的 sslmode 协商 TLS: prefer (默认) 服务端支持时加密，require 强制加密；证书按系统信任库校验
+            let tls = native_tls::TlsConnector::new().map_err(|e| format!("Failed to initialize TLS: {}", e))?;
+            let (client, connection) = tokio_postgres::connect(&self.url, postgres_native_tls::MakeTlsConnector::new(tls))
+                .await
+                .map_err(|e| format!("Failed to connect to PostgreSQL: {}", e))?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    tracing::warn!("[StateStore] PostgreSQL 连接断开: {}", e);
+                }
+            });
+            for sql in [SQL_SCHEMA, SQL_ADD_VERSION_POSTGRES, SQL_INDEX] {
+                client.batch_execute(sql).await.map_err(|e| format!("Failed to create schema: {}", e))?;
+            }
+            *guard = Some(client);
+        }
+        Ok(guard)
+    }
+
+    async fn put_many(&self, namespace: &str, entries: &[StoredEntry]) -> Result<(), String> {
+        let mut guard = self.connected().await?;
+        let client = guard.as_mut().ok_or("PostgreSQL client unavailable")?;
+        let tx = client.transaction().await.map_err(|e| e.to_string())?;
+        // 序列值按调用顺序分配但可能乱序提交: 串行化写入事务，读到某个版本时更小的版本都已可见
+        tx.execute("SELECT pg_advisory_xact_lock($1)", &[&PG_VERSION_LOCK]).await.map_err(|e| e.to_string())?;
+        for entry in entries {
+            tx.execute(SQL_UPSERT_POSTGRES, &[&namespace, &entry.key, &entry.value, &entry.updated_at, &entry.origin])
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    async fn changed_since(&self, namespace: &str, since: i64) -> Result<Vec<StoredEntry>, String> {
+        let guard = self.connected().await?;
+        let client = guard.as_ref().ok_or("PostgreSQL client unavailable")?;
+        let rows = client
+            .query(
+                "SELECT key, value, updated_at, origin, version FROM ag_state WHERE namespace = $1 AND version > $2",
+                &[&namespace, &since],
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(rows
+            .iter()
+            .map(|row| StoredEntry {
+                key: row.get(0),
+                value: row.get(1),
+                updated_at: row.get(2),
+                origin: row.get(3),
+                version: row.get(4),
+            })
+            .collect())
+    }
+
+    async fn delete_namespace(&self, namespace: &str) -> Result<usize, String> {
+        let guard = self.connected().await?;
+        let client = guard.as_ref().ok_or("PostgreSQL client unavailable")?;
+        let n = client
+            .execute("DELETE FROM ag_state WHERE namespace = $1", &[&namespace])
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(n as usize)
+    }
+}
+
+enum Backend {
+    Dir(DirStore),
+    Sqlite(SqliteStore),
+    Postgres(PostgresStore),
+}
+
+impl Backend {
+    /// local 后端返回 None
+    fn from_config(config: &StateStoreConfig) -> Result<Option<Backend>, String> {
+        let path = config.path.trim();
+        let url = config.url.trim();
+        Ok(match config.backend {
+            StateStoreBackend::Local => None,
+            StateStoreBackend::Path if path.is_empty() => return Err("state_store.path is required for the path backend".into()),
+            StateStoreBackend::Path => Some(Backend::Dir(DirStore { root: PathBuf::from(path) })),
+            StateStoreBackend::Sqlite if path.is_empty() => return Err("state_store.path is required for the sqlite backend".into()),
+            StateStoreBackend::Sqlite => Some(Backend::Sqlite(SqliteStore { path: PathBuf::from(path) })),
+            StateStoreBackend::Postgres if url.is_empty() => return Err("state_store.url is required for the postgres backend".into()),
+            StateStoreBackend::Postgres => Some(Backend::Postgres(PostgresStore {
+                url: url.to_string(),
+                client: tokio::sync::Mutex::new(None),
+            })),
+        })
+    }
+
+    async fn put_many(&self, namespace: &str, entries: &[StoredEntry]) -> Result<(), String> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        match self {
+            Backend::Dir(store) => store.put_many(namespace, entries),
+            Backend::Sqlite(store) => store.put_many(namespace, entries),
+            Backend::Postgres(store) => store.put_many(namespace, entries).await,
+        }
+    }
+
+    /// 版本号大于 since 的记录
+    async fn changed_since(&self, namespace: &str, since: i64) -> Result<Vec<StoredEntry>, String> {
+        match self {
+            Backend::Dir(store) => store.changed_since(namespace, since),
+            Backend::Sqlite(store) => store.changed_since(namespace, since),
+            Backend::Postgres(store) => store.changed_since(namespace, since).await,
+        }
+    }
+
+    async fn delete_namespace(&self, namespace: &str) -> Result<usize, String> {
+        match self {
+            Backend::Dir(store) => store.delete_namespace(namespace),
+            Backend::Sqlite(store) => store.delete_namespace(namespace),
+            Backend::Postgres(store) => store.delete_namespace(namespace).await,
+        }
+    }
+}
+
+fn fingerprint(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 本地账号文件: 账号 ID -> 文件内容 (与 TokenManager 一致，直接扫描 accounts 目录)
+fn local_accounts() -> HashMap<String, String> {
+    let Ok(dir) = crate::modules::account::get_accounts_dir() else {
+        return HashMap::new();
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return HashMap::new();
+    };
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("json"))
+        .filter_map(|p| {
+            let id = p.file_stem()?.to_str()?.to_string();
+            Some((id, std::fs::read_to_string(&p).ok()?))
+        })
+        .collect()
+}
+
+fn read_local_account(id: &str) -> Option<String> {
+    let dir = crate::modules::account::get_accounts_dir().ok()?;
+    std::fs::read_to_string(dir.join(format!("{}.json", id))).ok()
+}
+
+/// 与共享存储的同步状态
+pub struct StateSync {
+    backend: Backend,
+    mirror_history: bool,
+    interval: Duration,
+    /// 账号 ID -> 最近一次推送/拉取后的本地文件指纹
+    account_fingerprints: HashMap<String, u64>,
+    /// 账号 ID -> 已推送/已拉取的冷却到期时间
+    known_lockouts: HashMap<String, i64>,
+    /// 已拉取的最大版本号
+    accounts_watermark: i64,
+    lockouts_watermark: i64,
+    /// 是否已成功拉取过一次账号 (之前的推送会覆盖其他副本的更新)
+    seeded: bool,
+}
+
+impl StateSync {
+    /// 推送本地新增/修改/删除的账号
+    async fn push_accounts(&mut self) -> Result<usize, String> {
+        let local = local_accounts();
+        let now = now_ms();
+        let mut entries = Vec::new();
+        for (id, content) in &local {
+            let fp = fingerprint(content);
+            if self.account_fingerprints.get(id) != Some(&fp) {
+                entries.push(StoredEntry { key: id.clone(), value: Some(content.clone()), updated_at: now, origin: replica_id().to_string(), version: 0 });
+                self.account_fingerprints.insert(id.clone(), fp);
+            }
+        }
+        let deleted: Vec<String> = self.account_fingerprints.keys().filter(|id| !local.contains_key(*id)).cloned().collect();
+        for id in deleted {
+            self.account_fingerprints.remove(&id);
+            entries.push(StoredEntry { key: id, value: None, updated_at: now, origin: replica_id().to_string(), version: 0 });
+        }
+        self.backend.put_many(NS_ACCOUNTS, &entries).await?;
+        Ok(entries.len())
+    }
+
+    /// 拉取其他副本的账号变更，返回写入本地的数量
+    async fn pull_accounts(&mut self) -> Result<usize, String> {
+        let rows = self.backend.changed_since(NS_ACCOUNTS, self.accounts_watermark).await?;
+        let mut changed = 0;
+        for row in rows {
+            self.accounts_watermark = self.accounts_watermark.max(row.version);
+            if row.origin == replica_id() {
+                continue;
+            }
+            match row.value {
+                Some(content) => {
+                    let account: crate::models::Account = match serde_json::from_str(&content) {
+                        Ok(a) => a,
+                        Err(e) => {
+                            tracing::warn!("[StateStore] 跳过无法解析的账号 {}: {}", row.key, e);
+                            continue;
+                        }
+                    };
+                    crate::modules::account::import_replicated_account(&account)?;
+                    if let Some(written) = read_local_account(&account.id) {
+                        self.account_fingerprints.insert(account.id.clone(), fingerprint(&written));
+                    }
+                    changed += 1;
+                }
+                None => {
+                    self.account_fingerprints.remove(&row.key);
+                    if read_local_account(&row.key).is_some() {
+                        let _ = crate::modules::account::delete_account(&row.key);
+                        changed += 1;
+                    }
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    async fn sync_lockouts(&mut self, token_manager: &TokenManager) -> Result<(), String> {
+        let now = now_ms();
+        let mut entries = Vec::new();
+        for (id, lockout) in token_manager.active_lockouts() {
+            if self.known_lockouts.get(&id) == Some(&lockout.reset_at) {
+                continue;
+            }
+            self.known_lockouts.insert(id.clone(), lockout.reset_at);
+            let value = serde_json::to_string(&lockout).map_err(|e| e.to_string())?;
+            entries.push(StoredEntry { key: id, value: Some(value), updated_at: now, origin: replica_id().to_string(), version: 0 });
+        }
+        self.backend.put_many(NS_LOCKOUTS, &entries).await?;
+
+        let rows = self.backend.changed_since(NS_LOCKOUTS, self.lockouts_watermark).await?;
+        let now_secs = now / 1000;
+        let mut state = JournalState::default();
+        for row in rows {
+            self.lockouts_watermark = self.lockouts_watermark.max(row.version);
+            if row.origin == replica_id() {
+                continue;
+            }
+            let Some(lockout) = row.value.and_then(|v| serde_json::from_str::<LockoutEntry>(&v).ok()) else {
+                continue;
+            };
+            if lockout.reset_at <= now_secs || self.known_lockouts.get(&row.key) == Some(&lockout.reset_at) {
+                continue;
+            }
+            self.known_lockouts.insert(row.key.clone(), lockout.reset_at);
+            state.lockouts.insert(row.key, lockout);
+        }
+        if !state.lockouts.is_empty() {
+            token_manager.restore_journaled_state(&state);
+        }
+        self.known_lockouts.retain(|_, reset_at| *reset_at > now_secs);
+        Ok(())
+    }
+
+    async fn push_history(&self) -> Result<(), String> {
+        let logs: Vec<ProxyRequestLog> = match PENDING_HISTORY.lock() {
+            Ok(mut pending) => pending.drain(..).collect(),
+            Err(_) => return Ok(()),
+        };
+        let entries: Vec<StoredEntry> = logs
+            .iter()
+            .filter_map(|log| {
+                Some(StoredEntry {
+                    key: log.id.clone(),
+                    value: Some(serde_json::to_string(log).ok()?),
+                    updated_at: log.timestamp,
+                    origin: replica_id().to_string(),
+                    version: 0,
+                })
+            })
+            .collect();
+        if let Err(e) = self.backend.put_many(NS_HISTORY, &entries).await {
+            // 放回队列，下次重试
+            if let Ok(mut pending) = PENDING_HISTORY.lock() {
+                for log in logs.into_iter().rev() {
+                    if pending.len() >= MAX_PENDING_HISTORY {
+                        break;
+                    }
+                    pending.push_front(log);
+                }
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn tick(&mut self, token_manager: &TokenManager) {
+        // 先拉取再推送: 拉取时记录远端版本的指纹，推送只包含本地真正发生的变更
+        match self.pull_accounts().await {
+            Ok(changed) => {
+                self.seeded = true;
+                if changed > 0 {
+                    tracing::info!("[StateStore] 已同步其他副本的 {} 个账号变更", changed);
+                    if let Err(e) = token_manager.reload_all_accounts().await {
+                        tracing::warn!("[StateStore] 重新加载账号失败: {}", e);
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("[StateStore] 拉取账号失败: {}", e),
+        }
+        // 从未成功拉取过 (启动时存储不可用) 时不推送，否则本地的旧账号会以当前时间覆盖其他副本更新的记录
+        if self.seeded {
+            if let Err(e) = self.push_accounts().await {
+                tracing::warn!("[StateStore] 推送账号失败: {}", e);
+            }
+        }
+        if let Err(e) = self.sync_lockouts(token_manager).await {
+            tracing::warn!("[StateStore] 同步限流状态失败: {}", e);
+        }
+        if self.mirror_history {
+            if let Err(e) = self.push_history().await {
+                tracing::warn!("[StateStore] 镜像请求历史失败: {}", e);
+            }
+        }
+    }
+
+    /// 账号加载后启动后台同步 (替换之前的同步任务)
+    pub fn start(mut self, token_manager: Arc<TokenManager>) {
+        HISTORY_ENABLED.store(self.mirror_history, Ordering::SeqCst);
+        let handle = tokio::spawn(async move {
+            let mut interval = time::interval(self.interval);
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                self.tick(&token_manager).await;
+            }
+        });
+        if let Ok(mut task) = SYNC_TASK.lock() {
+            if let Some(previous) = task.replace(handle) {
+                previous.abort();
+            }
+        }
+    }
+}
+
+/// 反代启动前调用: 应用请求历史库位置；配置了共享后端时先拉取账号 (新副本本地可能还没有任何账号)
+pub async fn prepare(config: &StateStoreConfig) -> Result<Option<StateSync>, String> {
+    let history_path = config.history_db_path.trim();
+    crate::modules::proxy_db::set_db_path_override((!history_path.is_empty()).then(|| PathBuf::from(history_path)));
+    if !history_path.is_empty() {
+        crate::modules::proxy_db::init_db()?;
+    }
+
+    let Some(backend) = Backend::from_config(config)? else {
+        return Ok(None);
+    };
+    let mut sync = StateSync {
+        backend,
+        mirror_history: config.mirror_history,
+        interval: Duration::from_secs(config.sync_interval_secs.max(1)),
+        account_fingerprints: HashMap::new(),
+        known_lockouts: HashMap::new(),
+        // 升级前写入的记录版本号为 0，新副本需要读到它们
+        accounts_watermark: -1,
+        lockouts_watermark: -1,
+        seeded: false,
+    };
+    // 存储暂不可用时仍以本地状态启动，由后台同步重试 (成功拉取前不推送本地账号)
+    match sync.pull_accounts().await {
+        Ok(n) => {
+            sync.seeded = true;
+            tracing::info!("[StateStore] 已连接 {:?} 状态存储，拉取 {} 个账号", config.backend, n);
+        }
+        Err(e) => tracing::warn!("[StateStore] 连接状态存储失败，先使用本地状态: {}", e),
+    }
+    Ok(Some(sync))
+}
+
+/// 停止后台同步 (反代停止时调用)
+pub fn stop() {
+    HISTORY_ENABLED.store(false, Ordering::SeqCst);
+    if let Ok(mut task) = SYNC_TASK.lock() {
+        if let Some(handle) = task.take() {
+            handle.abort();
+        }
+    }
+}
+
+/// 紧急擦除: 丢弃待镜像的请求历史并删除共享存储中的 history 命名空间 (所有副本镜像的记录)，返回删除数
+/// local 后端返回 0
+pub async fn wipe_history(config: &StateStoreConfig) -> Result<usize, String> {
+    if let Ok(mut pending) = PENDING_HISTORY.lock() {
+        pending.clear();
+    }
+    match Backend::from_config(config)? {
+        Some(backend) => backend.delete_namespace(NS_HISTORY).await,
+        None => Ok(0),
+    }
+}
+
+/// 记录一条待镜像的请求历史 (未启用镜像时忽略)
+pub fn record_history(log: &ProxyRequestLog) {
+    if !HISTORY_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(mut pending) = PENDING_HISTORY.lock() {
+        if pending.len() >= MAX_PENDING_HISTORY {
+            pending.pop_front();
+        }
+        pending.push_back(log.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, value: Option<&str>, updated_at: i64) -> StoredEntry {
+        replica_entry("replica-a", key, value, updated_at)
+    }
+
+    fn replica_entry(origin: &str, key: &str, value: Option<&str>, updated_at: i64) -> StoredEntry {
+        StoredEntry { key: key.to_string(), value: value.map(|v| v.to_string()), updated_at, origin: origin.to_string(), version: 0 }
+    }
+
+    fn temp_backends() -> (PathBuf, [Backend; 2]) {
+        let dir = std::env::temp_dir().join(format!("ag_state_store_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let backends = [
+            Backend::Dir(DirStore { root: dir.join("shared") }),
+            Backend::Sqlite(SqliteStore { path: dir.join("state.db") }),
+        ];
+        (dir, backends)
+    }
+
+    #[tokio::test]
+    async fn test_backends_keep_newest_entry() {
+        let (dir, backends) = temp_backends();
+        for backend in &backends {
+            backend.put_many(NS_ACCOUNTS, &[entry("acc/1", Some("v1"), 200)]).await.unwrap();
+            // 后写入者生效，与调用方填写的时间无关
+            backend.put_many(NS_ACCOUNTS, &[entry("acc/1", Some("v2"), 100)]).await.unwrap();
+            backend.put_many(NS_ACCOUNTS, &[entry("acc-2", None, 300)]).await.unwrap();
+
+            let mut rows = backend.changed_since(NS_ACCOUNTS, -1).await.unwrap();
+            rows.sort_by_key(|r| r.version);
+            assert_eq!(rows.len(), 2);
+            assert_eq!(rows[0].key, "acc/1");
+            assert_eq!(rows[0].value.as_deref(), Some("v2"));
+            assert_eq!(rows[1].value, None);
+            assert!(rows[0].version < rows[1].version);
+            assert_eq!(backend.changed_since(NS_ACCOUNTS, rows[0].version).await.unwrap().len(), 1);
+            assert!(backend.changed_since(NS_LOCKOUTS, -1).await.unwrap().is_empty());
+
+            // 擦除只影响指定命名空间
+            backend.put_many(NS_HISTORY, &[entry("req-1", Some("{}"), 100), entry("req-2", Some("{}"), 100)]).await.unwrap();
+            assert_eq!(backend.delete_namespace(NS_HISTORY).await.unwrap(), 2);
+            assert!(backend.changed_since(NS_HISTORY, -1).await.unwrap().is_empty());
+            assert_eq!(backend.changed_since(NS_ACCOUNTS, -1).await.unwrap().len(), 2);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_versions_ignore_replica_clock_skew() {
+        let (dir, backends) = temp_backends();
+        let now = now_ms();
+        for backend in &backends {
+            // 副本 A 的时钟快 10 分钟；写入后 A 的水位停在自己这条记录
+            backend.put_many(NS_ACCOUNTS, &[replica_entry("replica-a", "acc-1", Some("from-a"), now + 600_000)]).await.unwrap();
+            let watermark_a = backend.changed_since(NS_ACCOUNTS, -1).await.unwrap().iter().map(|r| r.version).max().unwrap();
+
+            // 副本 B 的时钟慢 5 分钟: 墙钟时间早于 A 的记录，但写入更晚
+            let skewed = now - 300_000;
+            backend
+                .put_many(
+                    NS_ACCOUNTS,
+                    &[replica_entry("replica-b", "acc-1", Some("from-b"), skewed), replica_entry("replica-b", "acc-2", Some("new"), skewed)],
+                )
+                .await
+                .unwrap();
+
+            // A 按版本号拉取，能看到 B 的修改与新增 (按时间过滤时会被回看窗口漏掉)
+            let mut rows = backend.changed_since(NS_ACCOUNTS, watermark_a).await.unwrap();
+            rows.sort_by(|a, b| a.key.cmp(&b.key));
+            assert_eq!(rows.len(), 2);
+            assert_eq!((rows[0].key.as_str(), rows[0].value.as_deref()), ("acc-1", Some("from-b")));
+            assert_eq!(rows[0].origin, "replica-b");
+            assert_eq!(rows[1].key, "acc-2");
+            assert!(rows.iter().all(|r| r.version > watermark_a));
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sqlite_migrates_tables_without_version() {
+        let dir = std::env::temp_dir().join(format!("ag_state_store_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.db");
+        // 旧版本创建的表与记录
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE ag_state (namespace TEXT NOT NULL, key TEXT NOT NULL, value TEXT, updated_at BIGINT NOT NULL, origin TEXT NOT NULL, PRIMARY KEY (namespace, key));
+             INSERT INTO ag_state VALUES ('accounts', 'legacy', '{}', 100, 'old-replica');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let store = SqliteStore { path };
+        store.put_many(NS_ACCOUNTS, &[entry("acc-1", Some("v1"), 200)]).unwrap();
+        let mut rows = store.changed_since(NS_ACCOUNTS, -1).unwrap();
+        rows.sort_by_key(|r| r.version);
+        assert_eq!((rows[0].key.as_str(), rows[0].version), ("legacy", 0));
+        assert_eq!((rows[1].key.as_str(), rows[1].version), ("acc-1", 1));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_backend_requires_location() {
+        let mut config = StateStoreConfig::default();
+        assert!(Backend::from_config(&config).unwrap().is_none());
+        config.backend = StateStoreBackend::Postgres;
+        assert!(Backend::from_config(&config).is_err());
+        config.url = "postgres://localhost/antigravity".to_string();
+        assert!(matches!(Backend::from_config(&config), Ok(Some(Backend::Postgres(_)))));
+    }
+}
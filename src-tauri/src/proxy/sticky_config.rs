@@ -9,6 +9,8 @@ pub enum SchedulingMode {
     Balance,
     /// 性能优先 (Performance-first): 纯轮询模式 (Round-robin)，账号负载最均衡，但不利用缓存
     PerformanceFirst,
+    /// 公平模式 (Fairness): 按账号当日累计 token 用量均衡分配，保留会话粘性但不做 60s 全局锁定
+    Fairness,
 }
 
 impl Default for SchedulingMode {
@@ -0,0 +1,477 @@
+// OpenTelemetry 链路追踪导出
+// 请求管道的关键阶段 (transform / schedule / upstream / map_back) 以 target = "ag_otel" 的 tracing span 标记，
+// 本模块的 Layer 将其转换为 OTLP span，按批以 OTLP/HTTP JSON 发送到采集端 (Jaeger / OTel Collector 的 4318 端口)。
+// - 根 span 为 request_id 中间件创建的 `request`；客户端携带 W3C `traceparent` 时接入调用方的链路
+// - span 字段即属性；`otel.name` / `otel.kind` / `otel.status_code` / `otel.status_message` 沿用 tracing-opentelemetry 的约定
+// - span 内的 ERROR 事件将 span 标记为错误
+// - 过滤器动态读取开关，未启用时这些 span 不会被创建 (文本/JSON 日志也不会显示它们)
+
+use futures::{Stream, StreamExt};
+use serde_json::{json, Map, Value};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::proxy::config::TelemetryConfig;
+
+/// 管道阶段 span 的 target
+pub const TARGET: &str = "ag_otel";
+
+/// 待发送队列上限 (采集端不可用时丢弃最旧的 span)
+const MAX_QUEUE: usize = 4096;
+const BATCH_SIZE: usize = 512;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// OTLP SpanKind
+const KIND_INTERNAL: u8 = 1;
+const KIND_SERVER: u8 = 2;
+const KIND_CLIENT: u8 = 3;
+
+/// OTLP StatusCode
+const STATUS_UNSET: u8 = 0;
+const STATUS_OK: u8 = 1;
+const STATUS_ERROR: u8 = 2;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+struct Exporter {
+    config: RwLock<TelemetryConfig>,
+    queue: Mutex<Vec<Value>>,
+    started: AtomicBool,
+}
+
+fn exporter() -> &'static Exporter {
+    static INSTANCE: OnceLock<Exporter> = OnceLock::new();
+    INSTANCE.get_or_init(|| Exporter {
+        config: RwLock::new(TelemetryConfig::default()),
+        queue: Mutex::new(Vec::new()),
+        started: AtomicBool::new(false),
+    })
+}
+
+/// 应用配置 (反代启动与保存配置时调用，需在 tokio 运行时内)
+pub fn configure(config: &TelemetryConfig) {
+    let exporter = exporter();
+    if let Ok(mut current) = exporter.config.write() {
+        *current = config.clone();
+    }
+    let enabled = config.enabled && !config.endpoint.trim().is_empty();
+    if ENABLED.swap(enabled, Ordering::Relaxed) != enabled {
+        if enabled {
+            tracing::info!("[Telemetry] OTLP 链路导出已启用: {}", config.endpoint);
+        } else {
+            tracing::info!("[Telemetry] OTLP 链路导出已关闭");
+        }
+    }
+    if !enabled {
+        if let Ok(mut queue) = exporter.queue.lock() {
+            queue.clear();
+        }
+        return;
+    }
+    if !exporter.started.swap(true, Ordering::SeqCst) {
+        tokio::spawn(flush_loop());
+    }
+}
+
+fn enqueue(span: Value) {
+    if let Ok(mut queue) = exporter().queue.lock() {
+        if queue.len() >= MAX_QUEUE {
+            queue.remove(0);
+        }
+        queue.push(span);
+    }
+}
+
+fn drain_batch() -> Vec<Value> {
+    match exporter().queue.lock() {
+        Ok(mut queue) => {
+            let n = queue.len().min(BATCH_SIZE);
+            queue.drain(..n).collect()
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+async fn flush_loop() {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default();
+    // 采集端持续不可用时只记录一次警告
+    let mut failing = false;
+    loop {
+        tokio::time::sleep(FLUSH_INTERVAL).await;
+        if !is_enabled() {
+            continue;
+        }
+        loop {
+            let batch = drain_batch();
+            if batch.is_empty() {
+                break;
+            }
+            let config = match exporter().config.read() {
+                Ok(c) => c.clone(),
+                Err(_) => break,
+            };
+            match send(&client, &config, batch).await {
+                Ok(()) => {
+                    if failing {
+                        tracing::info!("[Telemetry] OTLP 采集端已恢复");
+                    }
+                    failing = false;
+                }
+                Err(e) => {
+                    if !failing {
+                        tracing::warn!("[Telemetry] 发送链路数据失败 (将丢弃本批次): {}", e);
+                    }
+                    failing = true;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn send(client: &reqwest::Client, config: &TelemetryConfig, spans: Vec<Value>) -> Result<(), String> {
+    let mut request = client.post(&config.endpoint).json(&export_request(&config.service_name, spans));
+    for (name, value) in &config.headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    Ok(())
+}
+
+/// OTLP ExportTraceServiceRequest (JSON 编码)
+fn export_request(service_name: &str, spans: Vec<Value>) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    { "key": "service.name", "value": { "stringValue": service_name } },
+                    { "key": "service.version", "value": { "stringValue": env!("CARGO_PKG_VERSION") } }
+                ]
+            },
+            "scopeSpans": [{
+                "scope": { "name": "antigravity-proxy", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans
+            }]
+        }]
+    })
+}
+
+fn any_value(value: &Value) -> Value {
+    match value {
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "intValue": n.to_string() }),
+        Value::Number(n) => json!({ "doubleValue": n.as_f64().unwrap_or_default() }),
+        Value::String(s) => json!({ "stringValue": s }),
+        other => json!({ "stringValue": other.to_string() }),
+    }
+}
+
+fn now_unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn random_hex(bytes: usize) -> String {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    id[..bytes * 2].to_string()
+}
+
+/// 解析 W3C traceparent: `00-<trace-id>-<parent-id>-<flags>`，返回 (trace_id, parent_span_id, sampled)
+fn parse_traceparent(value: &str) -> Option<(String, String, bool)> {
+    let parts: Vec<&str> = value.trim().split('-').collect();
+    if parts.len() != 4 || parts[0] != "00" {
+        return None;
+    }
+    let is_hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit());
+    let (trace_id, span_id, flags) = (parts[1], parts[2], parts[3]);
+    if !is_hex(trace_id, 32) || !is_hex(span_id, 16) || !is_hex(flags, 2) {
+        return None;
+    }
+    if trace_id.bytes().all(|b| b == b'0') || span_id.bytes().all(|b| b == b'0') {
+        return None;
+    }
+    let sampled = u8::from_str_radix(flags, 16).map(|f| f & 0x01 == 1).unwrap_or(false);
+    Some((trace_id.to_ascii_lowercase(), span_id.to_ascii_lowercase(), sampled))
+}
+
+fn should_sample(ratio: f64) -> bool {
+    if ratio >= 1.0 {
+        true
+    } else if ratio <= 0.0 {
+        false
+    } else {
+        rand::random::<f64>() < ratio
+    }
+}
+
+fn sample_ratio() -> f64 {
+    exporter().config.read().map(|c| c.sample_ratio).unwrap_or(1.0)
+}
+
+struct FieldVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
+
+/// 记录在 tracing span 扩展中的 OTLP span 数据
+struct SpanData {
+    trace_id: String,
+    span_id: String,
+    parent_span_id: Option<String>,
+    name: String,
+    start_ns: u64,
+    attributes: Map<String, Value>,
+    /// ERROR 事件的消息 (未通过 otel.status_code 显式设置状态时使用)
+    error: Option<String>,
+    sampled: bool,
+}
+
+impl SpanData {
+    fn into_otlp(mut self, end_ns: u64) -> Value {
+        let kind = match self.attributes.remove("otel.kind").as_ref().and_then(|v| v.as_str()) {
+            Some("server") => KIND_SERVER,
+            Some("client") => KIND_CLIENT,
+            _ => KIND_INTERNAL,
+        };
+        if let Some(name) = self.attributes.remove("otel.name") {
+            if let Some(name) = name.as_str().filter(|n| !n.is_empty()) {
+                self.name = name.to_string();
+            }
+        }
+        let explicit_status = self.attributes.remove("otel.status_code");
+        let status_message = self.attributes.remove("otel.status_message");
+        let (code, message) = match explicit_status.as_ref().and_then(|v| v.as_str()).map(|s| s.to_ascii_lowercase()) {
+            Some(s) if s == "error" => (
+                STATUS_ERROR,
+                status_message.and_then(|m| m.as_str().map(|s| s.to_string())).or(self.error),
+            ),
+            Some(s) if s == "ok" => (STATUS_OK, None),
+            _ => match self.error {
+                Some(message) => (STATUS_ERROR, Some(message)),
+                None => (STATUS_UNSET, None),
+            },
+        };
+        let attributes: Vec<Value> = self
+            .attributes
+            .iter()
+            .map(|(k, v)| json!({ "key": k, "value": any_value(v) }))
+            .collect();
+        let mut span = json!({
+            "traceId": self.trace_id,
+            "spanId": self.span_id,
+            "name": self.name,
+            "kind": kind,
+            "startTimeUnixNano": self.start_ns.to_string(),
+            "endTimeUnixNano": end_ns.max(self.start_ns).to_string(),
+            "attributes": attributes,
+            "status": { "code": code },
+        });
+        if let Some(parent) = self.parent_span_id {
+            span["parentSpanId"] = Value::String(parent);
+        }
+        if let Some(message) = message {
+            span["status"]["message"] = Value::String(message);
+        }
+        span
+    }
+}
+
+/// 将 target = "ag_otel" 的 span 转换为 OTLP span
+pub struct TelemetryLayer;
+
+impl<S> Layer<S> for TelemetryLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut attributes = Map::new();
+        attrs.record(&mut FieldVisitor(&mut attributes));
+        let traceparent = attributes.remove("traceparent");
+
+        let parent = span.parent().and_then(|p| {
+            p.extensions()
+                .get::<SpanData>()
+                .map(|d| (d.trace_id.clone(), d.span_id.clone(), d.sampled))
+        });
+        let (trace_id, parent_span_id, sampled) = match parent {
+            Some((trace_id, span_id, sampled)) => (trace_id, Some(span_id), sampled),
+            // 根 span: 优先接入调用方的链路 (采样决定随调用方)
+            None => match traceparent.as_ref().and_then(|v| v.as_str()).and_then(parse_traceparent) {
+                Some((trace_id, span_id, sampled)) => (trace_id, Some(span_id), sampled),
+                None => (random_hex(16), None, should_sample(sample_ratio())),
+            },
+        };
+
+        span.extensions_mut().insert(SpanData {
+            trace_id,
+            span_id: random_hex(8),
+            parent_span_id,
+            name: span.name().to_string(),
+            start_ns: now_unix_nanos(),
+            attributes,
+            error: None,
+            sampled,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>() {
+            values.record(&mut FieldVisitor(&mut data.attributes));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let mut fields = Map::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>() {
+            let message = fields
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("error")
+                .to_string();
+            data.error = Some(message);
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let data = span.extensions_mut().remove::<SpanData>();
+        if let Some(data) = data.filter(|d| d.sampled) {
+            if is_enabled() {
+                enqueue(data.into_otlp(now_unix_nanos()));
+            }
+        }
+    }
+}
+
+/// 链路导出层: 仅接收管道阶段 span 与 ERROR 事件，且只在导出启用时创建 span
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    TelemetryLayer.with_filter(filter::dynamic_filter_fn(|meta, _| {
+        if !is_enabled() {
+            return false;
+        }
+        if meta.is_span() {
+            meta.target() == TARGET
+        } else {
+            *meta.level() == Level::ERROR
+        }
+    }))
+}
+
+/// 让 span 随流一起存活 (流结束并被丢弃时关闭)，用于流式响应的 map_back 阶段
+pub fn traced_stream<S>(span: tracing::Span, stream: S) -> Pin<Box<dyn Stream<Item = S::Item> + Send>>
+where
+    S: Stream + Send + 'static,
+{
+    Box::pin(stream.map(move |item| {
+        let _ = &span;
+        item
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_traceparent() {
+        let (trace_id, span_id, sampled) =
+            parse_traceparent("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01").unwrap();
+        assert_eq!(trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(span_id, "00f067aa0ba902b7");
+        assert!(sampled);
+        assert!(!parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").unwrap().2);
+        assert!(parse_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none());
+        assert!(parse_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none());
+        assert!(parse_traceparent("garbage").is_none());
+    }
+
+    #[test]
+    fn test_span_into_otlp() {
+        let mut attributes = Map::new();
+        attributes.insert("otel.kind".to_string(), "client".into());
+        attributes.insert("rpc.method".to_string(), "streamGenerateContent".into());
+        attributes.insert("http.status_code".to_string(), 429u64.into());
+        let span = SpanData {
+            trace_id: "a".repeat(32),
+            span_id: "b".repeat(16),
+            parent_span_id: Some("c".repeat(16)),
+            name: "upstream".to_string(),
+            start_ns: 100,
+            attributes,
+            error: Some("HTTP 429".to_string()),
+            sampled: true,
+        }
+        .into_otlp(250);
+        assert_eq!(span["kind"], KIND_CLIENT);
+        assert_eq!(span["parentSpanId"], "c".repeat(16));
+        assert_eq!(span["endTimeUnixNano"], "250");
+        assert_eq!(span["status"]["code"], STATUS_ERROR);
+        assert_eq!(span["status"]["message"], "HTTP 429");
+        let attrs = span["attributes"].as_array().unwrap();
+        assert_eq!(attrs.len(), 2);
+        assert!(attrs.iter().any(|a| a["key"] == "http.status_code" && a["value"]["intValue"] == "429"));
+    }
+}
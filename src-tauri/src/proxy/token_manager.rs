@@ -5,6 +5,7 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
+use crate::proxy::quota_learning::{QuotaLearner, QuotaProfile};
 use crate::proxy::rate_limit::RateLimitTracker;
 use crate::proxy::sticky_config::StickySessionConfig;
 
@@ -21,15 +22,63 @@ pub struct ProxyToken {
     pub subscription_tier: Option<String>, // "FREE" | "PRO" | "ULTRA"
     pub remaining_quota: Option<i32>, // [FIX #563] Remaining quota for priority sorting
     pub protected_models: HashSet<String>, // [NEW #621]
+    pub usage_schedule: Option<crate::models::UsageSchedule>, // 账号可用时间窗口
+    pub paused_until: Option<i64>, // 临时暂停到期时间 (i64::MAX 表示直到手动恢复)
+}
+
+/// 账号池就绪状态 (/readyz)
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct AccountReadiness {
+    pub total: usize,
+    pub valid_token: usize,
+    pub available: usize,
+    /// 暂停中或不在可用时间窗口内的账号数
+    pub paused: usize,
+}
+
+/// 所有账号都不在可用时间窗口内时的错误 (区别于配额耗尽)
+pub const NO_ELIGIBLE_ACCOUNT_ERROR: &str = "No eligible account: all accounts are paused or outside their usage schedule";
+
+tokio::task_local! {
+    /// 当前请求固定使用的账号 ID (并行扇出等需要逐个指定账号的场景)
+    static PINNED_ACCOUNT: String;
+}
+
+/// 在固定账号作用域内执行: 调度只会选择该账号 (不可用时直接报错而不是换号)
+pub async fn pin_account<F: std::future::Future>(account_id: String, fut: F) -> F::Output {
+    PINNED_ACCOUNT.scope(account_id, fut).await
+}
+
+pub(crate) fn pinned_account() -> Option<String> {
+    PINNED_ACCOUNT.try_with(|id| id.clone()).ok()
+}
+
+impl ProxyToken {
+    /// 当前时间是否在账号的可用时间窗口内
+    pub fn in_schedule(&self) -> bool {
+        self.usage_schedule.as_ref().map_or(true, |s| s.allows_now())
+    }
+
+    /// 是否处于临时暂停中 (到期后自动恢复，无需重新加载账号池)
+    pub fn is_paused(&self) -> bool {
+        self.paused_until.is_some_and(|until| chrono::Utc::now().timestamp() < until)
+    }
+
+    /// 未暂停且在可用时间窗口内
+    pub fn is_schedulable(&self) -> bool {
+        !self.is_paused() && self.in_schedule()
+    }
 }
 
 
 pub struct TokenManager {
     tokens: Arc<DashMap<String, ProxyToken>>,  // account_id -> ProxyToken
+    api_keys: Arc<DashMap<String, crate::proxy::upstream::aistudio::ApiKeyAccount>>, // AI Studio API Key 账号 (不参与 OAuth 轮换)
     current_index: Arc<AtomicUsize>,
     last_used_account: Arc<tokio::sync::Mutex<Option<(String, std::time::Instant)>>>,
     data_dir: PathBuf,
     rate_limit_tracker: Arc<RateLimitTracker>,  // 新增: 限流跟踪器
+    quota_learner: Arc<QuotaLearner>, // 根据 429 学习各账号的配额窗口
     sticky_config: Arc<tokio::sync::RwLock<StickySessionConfig>>, // 新增：调度配置
     session_accounts: Arc<DashMap<String, String>>, // 新增：会话与账号映射 (SessionID -> AccountID)
     preferred_account_id: Arc<tokio::sync::RwLock<Option<String>>>, // [FIX #820] 优先使用的账号ID（固定账号模式）
@@ -40,10 +89,12 @@ impl TokenManager {
     pub fn new(data_dir: PathBuf) -> Self {
         Self {
             tokens: Arc::new(DashMap::new()),
+            api_keys: Arc::new(DashMap::new()),
             current_index: Arc::new(AtomicUsize::new(0)),
             last_used_account: Arc::new(tokio::sync::Mutex::new(None)),
             data_dir,
             rate_limit_tracker: Arc::new(RateLimitTracker::new()),
+            quota_learner: Arc::new(QuotaLearner::new()),
             sticky_config: Arc::new(tokio::sync::RwLock::new(StickySessionConfig::default())),
             session_accounts: Arc::new(DashMap::new()),
             preferred_account_id: Arc::new(tokio::sync::RwLock::new(None)), // [FIX #820]
@@ -76,6 +127,7 @@ impl TokenManager {
 
         // Reload should reflect current on-disk state (accounts can be added/removed/disabled).
         self.tokens.clear();
+        self.api_keys.clear();
         self.current_index.store(0, Ordering::SeqCst);
         {
             let mut last_used = self.last_used_account.lock().await;
@@ -157,6 +209,27 @@ impl TokenManager {
             return Ok(None);
         }
 
+        // AI Studio API Key 账号单独登记，不进入 OAuth 账号池
+        if account.get("kind").and_then(|v| v.as_str()) == Some("api_key") {
+            let proxy_disabled = account.get("proxy_disabled").and_then(|v| v.as_bool()).unwrap_or(false);
+            if let (false, Some(id), Some(email), Some(key)) = (
+                proxy_disabled,
+                account["id"].as_str(),
+                account["email"].as_str(),
+                account["token"]["access_token"].as_str(),
+            ) {
+                self.api_keys.insert(
+                    id.to_string(),
+                    crate::proxy::upstream::aistudio::ApiKeyAccount {
+                        account_id: id.to_string(),
+                        email: email.to_string(),
+                        api_key: key.to_string(),
+                    },
+                );
+            }
+            return Ok(None);
+        }
+
         // 【新增】配额保护检查 - 在检查 proxy_disabled 之前执行
         // 这样可以在加载时自动恢复配额已恢复的账号
         if self.check_and_protect_quota(&mut account, path).await {
@@ -234,6 +307,18 @@ impl TokenManager {
                     .collect()
             })
             .unwrap_or_default();
+
+        let usage_schedule = account.get("usage_schedule")
+            .filter(|v| !v.is_null())
+            .and_then(|v| serde_json::from_value::<crate::models::UsageSchedule>(v.clone()).ok())
+            .filter(|s| !s.windows.is_empty());
+
+        // 暂停的账号仍加载 (固定账号时给出明确错误)，调度时跳过；已到期的暂停忽略
+        let now = chrono::Utc::now().timestamp();
+        let paused_until = account.get("paused_at")
+            .filter(|v| !v.is_null())
+            .map(|_| account.get("paused_until").and_then(|v| v.as_i64()).unwrap_or(i64::MAX))
+            .filter(|until| *until > now);
         
         Ok(Some(ProxyToken {
             account_id,
@@ -247,6 +332,8 @@ impl TokenManager {
             subscription_tier,
             remaining_quota,
             protected_models,
+            usage_schedule,
+            paused_until,
         }))
     }
 
@@ -479,12 +566,48 @@ impl TokenManager {
         session_id: Option<&str>,
         target_model: &str,
     ) -> Result<(String, String, String), String> {
+        use tracing::Instrument;
+        // 链路追踪: 调度阶段 (含限流等待)
+        let span = tracing::info_span!(
+            target: crate::proxy::telemetry::TARGET,
+            "schedule",
+            ag.quota_group = quota_group,
+            ag.model = target_model,
+            ag.force_rotate = force_rotate,
+            ag.account = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+            otel.status_message = tracing::field::Empty,
+        );
         // 【优化 Issue #284】添加 5 秒超时，防止死锁
         let timeout_duration = std::time::Duration::from_secs(5);
-        match tokio::time::timeout(timeout_duration, self.get_token_internal(quota_group, force_rotate, session_id, target_model)).await {
-            Ok(result) => result,
+        let result = match tokio::time::timeout(
+            timeout_duration,
+            self.get_token_internal(quota_group, force_rotate, session_id, target_model).instrument(span.clone()),
+        )
+        .await
+        {
+            Ok(result) => {
+                // 回填在途请求视图 (/admin/inflight) 的模型与账号
+                if let Ok((access_token, _, email)) = &result {
+                    crate::proxy::circuit_breaker::CircuitBreakers::global().bind_token(access_token, email);
+                    crate::proxy::inflight::note_assignment(target_model, email);
+                    crate::proxy::usage_ledger::DailyUsageLedger::global()
+                        .charge_estimate(email, crate::proxy::inflight::current_request_bytes());
+                }
+                result
+            }
             Err(_) => Err("Token acquisition timeout (5s) - system too busy or deadlock detected".to_string()),
+        };
+        match &result {
+            Ok((_, _, email)) => {
+                span.record("ag.account", email.as_str());
+            }
+            Err(e) => {
+                span.record("otel.status_code", "error");
+                span.record("otel.status_message", e.as_str());
+            }
         }
+        result
     }
 
     /// 内部实现：获取 Token 的核心逻辑
@@ -496,11 +619,48 @@ impl TokenManager {
         target_model: &str,
     ) -> Result<(String, String, String), String> {
         let mut tokens_snapshot: Vec<ProxyToken> = self.tokens.iter().map(|e| e.value().clone()).collect();
-        let total = tokens_snapshot.len();
-        if total == 0 {
+        if tokens_snapshot.is_empty() {
             return Err("Token pool is empty".to_string());
         }
 
+        // 跳过暂停中或不在可用时间窗口内的账号 (维护模式 / quiet hours)
+        let before_schedule = tokens_snapshot.len();
+        tokens_snapshot.retain(|t| t.is_schedulable());
+        if tokens_snapshot.is_empty() {
+            tracing::warn!("[Schedule] 全部 {} 个账号均处于暂停中或不在可用时间窗口内", before_schedule);
+            return Err(NO_ELIGIBLE_ACCOUNT_ERROR.to_string());
+        }
+        if tokens_snapshot.len() < before_schedule {
+            tracing::debug!("[Schedule] {} 个账号暂停中或不在可用时间窗口内，已跳过", before_schedule - tokens_snapshot.len());
+        }
+        // 配置档案限定的账号子集 (请求头选用的档案或当前激活的档案)
+        if let Some(allowed) = crate::proxy::profiles::allowed_accounts() {
+            tokens_snapshot.retain(|t| allowed.contains(&t.account_id));
+            if tokens_snapshot.is_empty() {
+                tracing::warn!("[Profiles] 档案限定的账号均不可用");
+                return Err(NO_ELIGIBLE_ACCOUNT_ERROR.to_string());
+            }
+        }
+        if let Some(pinned) = pinned_account() {
+            tokens_snapshot.retain(|t| t.account_id == pinned);
+            if tokens_snapshot.is_empty() {
+                return Err(format!("Pinned account {} is not available", pinned));
+            }
+        }
+        // 所有端点均处于熔断中的账号直接跳过 (全部熔断时保留，由半开探测兜底)
+        let breakers = crate::proxy::circuit_breaker::CircuitBreakers::global();
+        if tokens_snapshot.iter().any(|t| breakers.is_account_open(&t.email)) {
+            let healthy: Vec<ProxyToken> =
+                tokens_snapshot.iter().filter(|t| !breakers.is_account_open(&t.email)).cloned().collect();
+            if healthy.is_empty() {
+                tracing::warn!("[CircuitBreaker] 候选账号均处于熔断中");
+            } else {
+                tracing::debug!("[CircuitBreaker] 跳过 {} 个熔断中的账号", tokens_snapshot.len() - healthy.len());
+                tokens_snapshot = healthy;
+            }
+        }
+        let total = tokens_snapshot.len();
+
         // ===== 【优化】根据订阅等级和剩余配额排序 =====
         // [FIX #563] 优先级: ULTRA > PRO > FREE, 同tier内优先高配额账号
         // 理由: ULTRA/PRO 重置快，优先消耗；FREE 重置慢，用于兜底
@@ -521,26 +681,50 @@ impl TokenManager {
                 return tier_cmp;
             }
             
-            // [FIX #563] Second: compare by remaining quota percentage (higher is better)
+            // Second: learned headroom from observed 429s (quartiles, higher is better)
+            // 接近学习到的窗口上限的账号排到同等级后面，避免反复撞 429
+            let headroom_bucket = |t: &ProxyToken| (self.quota_learner.headroom(&t.account_id) * 4.0).ceil() as i32;
+            let headroom_cmp = headroom_bucket(b).cmp(&headroom_bucket(a));
+            if headroom_cmp != std::cmp::Ordering::Equal {
+                return headroom_cmp;
+            }
+
+            // [FIX #563] Third: compare by remaining quota percentage (higher is better)
             // Accounts with unknown/zero percentage go last within their tier
             let quota_a = a.remaining_quota.unwrap_or(0);
             let quota_b = b.remaining_quota.unwrap_or(0);
             quota_b.cmp(&quota_a)  // Descending: higher percentage first
         });
         
+        // 0. 读取当前调度配置
+        let scheduling = self.sticky_config.read().await.clone();
+        use crate::proxy::sticky_config::SchedulingMode;
+        let fairness = scheduling.mode == SchedulingMode::Fairness;
+
+        // 公平模式: 当日累计 token 最少的账号优先 (稳定排序，同用量时保留上面的等级/配额顺序)
+        if fairness {
+            let ledger = crate::proxy::usage_ledger::DailyUsageLedger::global();
+            tokens_snapshot.sort_by_cached_key(|t| ledger.tokens_today(&t.email));
+        }
+
+        // 延迟预算: 历史更快的账号优先 (稳定排序，无历史数据的账号排在已知满足预算的账号之后)
+        let latency_budget = crate::proxy::latency::current_budget();
+        let latency_tracker = crate::proxy::latency::LatencyTracker::global();
+        if let Some(budget) = &latency_budget {
+            tokens_snapshot.sort_by_cached_key(|t| latency_tracker.rank(&t.email, target_model, budget));
+        }
+        // 公平模式与延迟预算都按排序结果从头选择，不做轮询与 60s 锁定
+        let ordered = fairness || latency_budget.is_some();
+
         // 【调试日志】打印排序后的账号顺序
         tracing::info!(
             "🔄 [Token Rotation] Accounts: {:?}",
             tokens_snapshot.iter().map(|t| format!(
-                "{}(protected={:?})", 
-                t.email, t.protected_models
+                "{}(protected={:?}, headroom={:.2})", 
+                t.email, t.protected_models, self.quota_learner.headroom(&t.account_id)
             )).collect::<Vec<_>>()
         );
 
-        // 0. 读取当前调度配置
-        let scheduling = self.sticky_config.read().await.clone();
-        use crate::proxy::sticky_config::SchedulingMode;
-        
         // 【新增】检查配额保护是否启用（如果关闭，则忽略 protected_models 检查）
         let quota_protection_enabled = crate::modules::config::load_app_config()
             .map(|cfg| cfg.quota_protection.enabled)
@@ -586,6 +770,11 @@ impl TokenManager {
                             }
                             Err(e) => {
                                 tracing::warn!("Preferred account token refresh failed: {}", e);
+                                crate::modules::notifications::notify(
+                                    crate::modules::notifications::NotificationEvent::TokenRefreshFailed,
+                                    Some(&token.email),
+                                    format!("Token refresh failed for {}: {}", token.email, e),
+                                );
                                 // 继续使用旧 token，让后续逻辑处理失败
                             }
                         }
@@ -663,6 +852,9 @@ impl TokenManager {
                                 bound_token.email, reset_sec
                             );
                             self.session_accounts.remove(sid);
+                        } else if latency_budget.as_ref().is_some_and(|b| !latency_tracker.within_budget(&bound_token.email, target_model, b)) {
+                            // 绑定账号历史延迟超出本次预算: 本次不复用，但保留绑定
+                            tracing::debug!("Sticky Session: Bound account {} exceeds latency budget, picking a faster account for this request", bound_token.email);
                         } else if !attempted.contains(&bound_id) && !(quota_protection_enabled && bound_token.protected_models.contains(&normalized_target)) {
                             // 3. 账号可用且未被标记为尝试失败，优先复用
                             tracing::debug!("Sticky Session: Successfully reusing bound account {} for session {}", bound_token.email, sid);
@@ -683,7 +875,7 @@ impl TokenManager {
             // 【修复】性能优先模式应跳过 60s 锁定；
             if target_token.is_none() && !rotate && quota_group != "image_gen" && scheduling.mode != SchedulingMode::PerformanceFirst {
                 // 【优化】使用预先获取的快照，不再在循环内加锁
-                if let Some((account_id, last_time)) = &last_used_account_id {
+                if let Some((account_id, last_time)) = last_used_account_id.as_ref().filter(|_| !ordered) {
                     // [FIX #3] 60s 锁定逻辑应检查 `attempted` 集合，避免重复尝试失败的账号
                     if last_time.elapsed().as_secs() < 60 && !attempted.contains(account_id) {
                         if let Some(found) = tokens_snapshot.iter().find(|t| &t.account_id == account_id) {
@@ -704,7 +896,8 @@ impl TokenManager {
                 
                 // 若无锁定，则轮询选择新账号
                 if target_token.is_none() {
-                    let start_idx = self.current_index.fetch_add(1, Ordering::SeqCst) % total;
+                    // 公平模式从用量最少的账号开始，延迟预算从历史最快的账号开始
+                    let start_idx = if ordered { 0 } else { self.current_index.fetch_add(1, Ordering::SeqCst) % total };
                     for offset in 0..total {
                         let idx = (start_idx + offset) % total;
                         let candidate = &tokens_snapshot[idx];
@@ -739,7 +932,7 @@ impl TokenManager {
                 }
             } else if target_token.is_none() {
                 // 模式 C: 纯轮询模式 (Round-robin) 或强制轮换
-                let start_idx = self.current_index.fetch_add(1, Ordering::SeqCst) % total;
+                let start_idx = if ordered { 0 } else { self.current_index.fetch_add(1, Ordering::SeqCst) % total };
                 tracing::info!("🔄 [Mode C] Round-robin from idx {}, total: {}", start_idx, total);
                 for offset in 0..total {
                     let idx = (start_idx + offset) % total;
@@ -820,18 +1013,18 @@ impl TokenManager {
                                     t.clone()
                                 } else {
                                     // 所有策略都失败,返回错误
-                                    return Err(
+                                    return Err(self.all_accounts_unavailable(
                                         "All accounts failed after optimistic reset. Please check account health.".to_string()
-                                    );
+                                    ));
                                 }
                             }
                         } else {
                             // 等待时间 > 2秒,正常返回错误
-                            return Err(format!("All accounts are currently limited. Please wait {}s.", wait_sec));
+                            return Err(self.all_accounts_unavailable(format!("All accounts are currently limited. Please wait {}s.", wait_sec)));
                         }
                     } else {
                         // 无限流记录但仍无可用账号,可能是其他问题
-                        return Err("All accounts failed or unhealthy.".to_string());
+                        return Err(self.all_accounts_unavailable("All accounts failed or unhealthy.".to_string()));
                     }
                 }
             };
@@ -866,6 +1059,11 @@ impl TokenManager {
                     }
                     Err(e) => {
                         tracing::error!("Token 刷新失败 ({}): {}，尝试下一个账号", token.email, e);
+                        crate::modules::notifications::notify(
+                            crate::modules::notifications::NotificationEvent::TokenRefreshFailed,
+                            Some(&token.email),
+                            format!("Token refresh failed for {}: {}", token.email, e),
+                        );
                         if e.contains("\"invalid_grant\"") || e.contains("invalid_grant") {
                             tracing::error!(
                                 "Disabling account due to invalid_grant ({}): refresh_token likely revoked/expired",
@@ -963,6 +1161,7 @@ impl TokenManager {
         
         // 【修复 Issue #3】从内存中移除禁用的账号，防止被60s锁定逻辑继续使用
         self.tokens.remove(account_id);
+        self.quota_learner.forget(account_id);
 
         tracing::warn!("Account disabled: {} ({:?})", account_id, path);
         Ok(())
@@ -987,6 +1186,36 @@ impl TokenManager {
         tracing::debug!("已保存 project_id 到账号 {}", account_id);
         Ok(())
     }
+
+    /// [NEW] 修复失效的 project_id
+    /// 上游返回 PERMISSION_DENIED / project 不匹配时，重新走 loadCodeAssist 发现流程并写回账号文件
+    /// 返回新的 project_id；若发现结果与当前一致则视为无法修复
+    pub async fn repair_project_id(&self, email: &str) -> Result<String, String> {
+        let (account_id, access_token, old_project) = self
+            .tokens
+            .iter()
+            .find(|e| e.value().email == email)
+            .map(|e| (e.key().clone(), e.value().access_token.clone(), e.value().project_id.clone()))
+            .ok_or_else(|| format!("账号不存在: {}", email))?;
+
+        let new_project = crate::proxy::project_resolver::fetch_project_id(&access_token).await?;
+        if old_project.as_deref() == Some(new_project.as_str()) {
+            return Err(format!("project_id 未变化 ({})，无法自动修复", new_project));
+        }
+
+        if let Some(mut entry) = self.tokens.get_mut(&account_id) {
+            entry.project_id = Some(new_project.clone());
+        }
+        self.save_project_id(&account_id, &new_project).await?;
+
+        tracing::info!(
+            "🔧 已修复账号 {} 的 project_id: {} -> {}",
+            email,
+            old_project.as_deref().unwrap_or("<none>"),
+            new_project
+        );
+        Ok(new_project)
+    }
     
     /// 保存刷新后的 token 到账号文件
     async fn save_refreshed_token(&self, account_id: &str, token_response: &crate::modules::oauth::TokenResponse) -> Result<(), String> {
@@ -1016,6 +1245,68 @@ impl TokenManager {
         self.tokens.len()
     }
 
+    /// 已加载的 AI Studio API Key 账号
+    pub fn api_key_accounts(&self) -> Vec<crate::proxy::upstream::aistudio::ApiKeyAccount> {
+        let mut keys: Vec<_> = self.api_keys.iter().map(|e| e.value().clone()).collect();
+        keys.sort_by(|a, b| a.email.cmp(&b.email));
+        keys
+    }
+
+    /// 从状态日志恢复崩溃前的限流冷却与失败计数
+    pub fn restore_journaled_state(&self, state: &crate::proxy::state_journal::JournalState) {
+        let restored = self.rate_limit_tracker.restore_from_journal(state);
+        if restored > 0 {
+            tracing::info!("[StateJournal] 已恢复 {} 个账号的限流冷却", restored);
+        }
+    }
+
+    /// 当前未过期的限流冷却 (外部状态存储同步)
+    pub fn active_lockouts(&self) -> Vec<(String, crate::proxy::state_journal::LockoutEntry)> {
+        self.rate_limit_tracker.active_lockouts()
+    }
+
+    /// 就绪检查: 账号总数 / Token 有效 (未过期或可刷新) / 当前可调度 (有效、未暂停、在时间窗口内且未限流)
+    pub fn readiness(&self) -> AccountReadiness {
+        let now = chrono::Utc::now().timestamp();
+        let mut readiness = AccountReadiness {
+            total: self.tokens.len(),
+            ..Default::default()
+        };
+        for entry in self.tokens.iter() {
+            let token = entry.value();
+            let token_valid = token.timestamp > now || !token.refresh_token.trim().is_empty();
+            if !token_valid {
+                continue;
+            }
+            readiness.valid_token += 1;
+            if !token.is_schedulable() {
+                readiness.paused += 1;
+            } else if !self.is_rate_limited_by_account_id(&token.account_id) {
+                readiness.available += 1;
+            }
+        }
+        readiness
+    }
+
+    /// 已加载的全部账号，返回 (账号 ID, 邮箱)
+    pub fn all_accounts(&self) -> Vec<(String, String)> {
+        self.tokens.iter().map(|e| (e.key().clone(), e.value().email.clone())).collect()
+    }
+
+    /// 当前可调度的账号 (未暂停、在时间窗口内、未限流、未被档案排除)，按邮箱排序，返回 (账号 ID, 邮箱)
+    pub fn schedulable_accounts(&self) -> Vec<(String, String)> {
+        let allowed = crate::proxy::profiles::allowed_accounts();
+        let mut accounts: Vec<(String, String)> = self
+            .tokens
+            .iter()
+            .filter(|e| e.value().is_schedulable() && !self.is_rate_limited_by_account_id(e.key()))
+            .filter(|e| allowed.as_ref().is_none_or(|a| a.contains(e.key())))
+            .map(|e| (e.key().clone(), e.value().email.clone()))
+            .collect();
+        accounts.sort_by(|a, b| a.1.cmp(&b.1));
+        accounts
+    }
+
     /// 通过 email 获取指定账号的 Token（用于预热等需要指定账号的场景）
     /// 此方法会自动刷新过期的 token
     pub async fn get_token_by_email(&self, email: &str) -> Result<(String, String, String), String> {
@@ -1097,6 +1388,7 @@ impl TokenManager {
     ) {
         // 【替代方案】转换 email -> account_id
         let key = self.email_to_account_id(email).unwrap_or_else(|| email.to_string());
+        self.learn_rate_limit(&key, email, status, error_body);
         self.rate_limit_tracker.parse_from_error(
             &key,
             status,
@@ -1105,8 +1397,46 @@ impl TokenManager {
             None,
         );
     }
+
+    /// 将 429 计入配额学习 (按 account_id 归档)，配额耗尽时发送通知
+    fn learn_rate_limit(&self, key: &str, email: &str, status: u16, error_body: &str) {
+        if status == 429 {
+            let reason = self.rate_limit_tracker.classify_reason(error_body);
+            self.quota_learner.record_rate_limit(key, reason);
+            if reason == crate::proxy::rate_limit::RateLimitReason::QuotaExhausted {
+                crate::modules::notifications::notify(
+                    crate::modules::notifications::NotificationEvent::QuotaExhausted,
+                    Some(email),
+                    format!("Account {} has exhausted its quota", email),
+                );
+            }
+        }
+    }
+
+    /// 所有账号均不可用: 发送通知并原样返回错误信息
+    fn all_accounts_unavailable(&self, message: String) -> String {
+        crate::modules::notifications::notify(
+            crate::modules::notifications::NotificationEvent::AllAccountsUnhealthy,
+            None,
+            message.clone(),
+        );
+        message
+    }
+
+    /// 各账号学习到的配额窗口
+    pub fn quota_profiles(&self) -> Vec<QuotaProfile> {
+        self.quota_learner.profiles()
+    }
     
 
+    /// 结合限流冷却与熔断状态推导账号状态
+    pub fn account_status(&self, account: &crate::models::Account) -> crate::models::AccountStatus {
+        let now = chrono::Utc::now().timestamp();
+        let cooldown_until = self.rate_limit_tracker.get_reset_seconds(&account.id).map(|secs| now + secs as i64);
+        let circuit_open = crate::proxy::circuit_breaker::CircuitBreakers::global().is_account_open(&account.email);
+        account.status(now, cooldown_until, circuit_open)
+    }
+
     /// 检查账号是否在限流中 (直接使用 account_id)
     pub fn is_rate_limited_by_account_id(&self, account_id: &str) -> bool {
         self.rate_limit_tracker.is_rate_limited(account_id)
@@ -1144,6 +1474,8 @@ impl TokenManager {
     /// 下次失败时从最短的锁定时间开始（智能限流）。
     pub fn mark_account_success(&self, account_id: &str) {
         self.rate_limit_tracker.mark_success(account_id);
+        let key = self.email_to_account_id(account_id).unwrap_or_else(|| account_id.to_string());
+        self.quota_learner.record_success(&key);
     }
     
     /// 检查是否有可用的 Google 账号
@@ -1176,6 +1508,11 @@ impl TokenManager {
         for entry in self.tokens.iter() {
             let token = entry.value();
             
+            // 0. 检查是否暂停或不在可用时间窗口内
+            if !token.is_schedulable() {
+                continue;
+            }
+
             // 1. 检查是否被限流
             if self.is_rate_limited_by_account_id(&token.account_id) {
                 tracing::debug!(
@@ -1357,6 +1694,9 @@ impl TokenManager {
         error_body: &str,
         model: Option<&str>,  // 🆕 新增模型参数
     ) {
+        let learn_key = self.email_to_account_id(account_id).unwrap_or_else(|| account_id.to_string());
+        self.learn_rate_limit(&learn_key, account_id, status, error_body);
+
         // 检查 API 是否返回了精确的重试时间
         let has_explicit_retry_time = retry_after_header.is_some() || 
             error_body.contains("quotaResetDelay");
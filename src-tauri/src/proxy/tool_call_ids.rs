@@ -0,0 +1,173 @@
+// 工具调用 ID 映射表
+// Gemini 的 functionCall 通常不带 ID，OpenAI 协议却要求每个 tool_call 有 `id` 并在后续的
+// tool 消息中通过 `tool_call_id` 引用。这里按会话维护 ID ↔ (函数名, 参数哈希) 的映射：
+// - 响应侧为同一会话生成确定性的 ID (相同调用在重放/重试时得到相同 ID，重复调用依次编号)
+// - 请求侧据此还原 tool 消息对应的函数名，保证 functionResponse 与 Gemini 看到的 functionCall 一致
+
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+const TABLE_TTL: Duration = Duration::from_secs(2 * 60 * 60);
+const CONVERSATION_LIMIT: usize = 1000;
+const CALLS_PER_CONVERSATION_LIMIT: usize = 2000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCallRecord {
+    pub name: String,
+    pub args_hash: u64,
+}
+
+#[derive(Default)]
+struct ConversationTable {
+    by_id: HashMap<String, ToolCallRecord>,
+    /// (函数名, 参数哈希) -> 已分配次数，用于区分同一会话中的重复调用
+    occurrences: HashMap<(String, u64), u32>,
+    touched: Option<SystemTime>,
+}
+
+impl ConversationTable {
+    fn is_expired(&self) -> bool {
+        self.touched
+            .and_then(|t| t.elapsed().ok())
+            .map(|e| e > TABLE_TTL)
+            .unwrap_or(false)
+    }
+}
+
+/// 参数按规范化 JSON 计算哈希 (字符串形式的 arguments 先解析，避免空白差异)
+pub fn hash_args(args: &Value) -> u64 {
+    let normalized = match args {
+        Value::String(s) => serde_json::from_str::<Value>(s).unwrap_or_else(|_| args.clone()),
+        other => other.clone(),
+    };
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(&normalized).unwrap_or_default().hash(&mut hasher);
+    hasher.finish()
+}
+
+pub struct ToolCallIdTable {
+    conversations: Mutex<HashMap<String, ConversationTable>>,
+}
+
+impl ToolCallIdTable {
+    fn new() -> Self {
+        Self { conversations: Mutex::new(HashMap::new()) }
+    }
+
+    /// Global singleton instance
+    pub fn global() -> &'static ToolCallIdTable {
+        static INSTANCE: OnceLock<ToolCallIdTable> = OnceLock::new();
+        INSTANCE.get_or_init(ToolCallIdTable::new)
+    }
+
+    fn with_conversation<R>(&self, session_id: &str, f: impl FnOnce(&mut ConversationTable) -> R) -> Option<R> {
+        let mut conversations = self.conversations.lock().ok()?;
+        if conversations.len() >= CONVERSATION_LIMIT && !conversations.contains_key(session_id) {
+            conversations.retain(|_, t| !t.is_expired());
+            if conversations.len() >= CONVERSATION_LIMIT {
+                let oldest = conversations
+                    .iter()
+                    .min_by_key(|(_, t)| t.touched)
+                    .map(|(k, _)| k.clone());
+                if let Some(k) = oldest {
+                    conversations.remove(&k);
+                }
+            }
+        }
+        let table = conversations.entry(session_id.to_string()).or_default();
+        if table.is_expired() {
+            *table = ConversationTable::default();
+        }
+        table.touched = Some(SystemTime::now());
+        Some(f(table))
+    }
+
+    /// 为上游返回的 functionCall 分配 ID；上游自带 ID 时沿用
+    pub fn assign(&self, session_id: &str, name: &str, args: &Value, upstream_id: Option<&str>) -> String {
+        let args_hash = hash_args(args);
+        let record = ToolCallRecord { name: name.to_string(), args_hash };
+        let upstream_id = upstream_id.filter(|id| !id.is_empty()).map(|id| id.to_string());
+
+        self.with_conversation(session_id, |table| {
+            let id = match upstream_id {
+                Some(id) => id,
+                None => {
+                    let seq = table.occurrences.entry((name.to_string(), args_hash)).or_insert(0);
+                    let mut hasher = DefaultHasher::new();
+                    session_id.hash(&mut hasher);
+                    name.hash(&mut hasher);
+                    args_hash.hash(&mut hasher);
+                    seq.hash(&mut hasher);
+                    *seq += 1;
+                    format!("call_{:016x}", hasher.finish())
+                }
+            };
+            if table.by_id.len() < CALLS_PER_CONVERSATION_LIMIT {
+                table.by_id.insert(id.clone(), record.clone());
+            }
+            id
+        })
+        .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple()))
+    }
+
+    /// 登记客户端历史消息中的 tool_call (客户端可能自行生成或改写 ID)
+    pub fn register(&self, session_id: &str, id: &str, name: &str, args: &Value) {
+        if id.is_empty() {
+            return;
+        }
+        let record = ToolCallRecord { name: name.to_string(), args_hash: hash_args(args) };
+        self.with_conversation(session_id, |table| {
+            if table.by_id.len() < CALLS_PER_CONVERSATION_LIMIT || table.by_id.contains_key(id) {
+                table.by_id.insert(id.to_string(), record);
+            }
+        });
+    }
+
+    pub fn resolve(&self, session_id: &str, id: &str) -> Option<ToolCallRecord> {
+        let conversations = self.conversations.lock().ok()?;
+        let table = conversations.get(session_id).filter(|t| !t.is_expired())?;
+        table.by_id.get(id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_assign_is_deterministic_and_distinguishes_repeats() {
+        let table = ToolCallIdTable::new();
+        let args = json!({"path": "a.txt"});
+        let first = table.assign("sid-1", "read_file", &args, None);
+        let second = table.assign("sid-1", "read_file", &args, None);
+        assert_ne!(first, second);
+        assert!(first.starts_with("call_"));
+
+        // 新进程状态下重放同一会话得到相同序列
+        let replay = ToolCallIdTable::new();
+        assert_eq!(replay.assign("sid-1", "read_file", &args, None), first);
+        assert_eq!(replay.assign("sid-1", "read_file", &args, None), second);
+
+        // 不同会话互不影响
+        assert_ne!(table.assign("sid-2", "read_file", &args, None), first);
+    }
+
+    #[test]
+    fn test_upstream_id_and_resolve() {
+        let table = ToolCallIdTable::new();
+        let id = table.assign("sid-1", "search", &json!({"q": "x"}), Some("gem-123"));
+        assert_eq!(id, "gem-123");
+        assert_eq!(table.resolve("sid-1", "gem-123").unwrap().name, "search");
+        assert!(table.resolve("sid-2", "gem-123").is_none());
+
+        // 字符串形式的 arguments 与对象形式哈希一致
+        table.register("sid-1", "client-id", "search", &json!("{ \"q\" : \"x\" }"));
+        let record = table.resolve("sid-1", "client-id").unwrap();
+        assert_eq!(record.args_hash, hash_args(&json!({"q": "x"})));
+    }
+}
@@ -0,0 +1,322 @@
+// AI Studio API Key 上游
+// 账号列表中的 API Key 类型账号直接调用 generativelanguage.googleapis.com (标准 Gemini 协议)，
+// 与 OAuth 账号池分开调度: 每个 Key 单独统计当日请求数，429 后按 retryDelay 冷却。
+
+use chrono::{DateTime, Duration as ChronoDuration, FixedOffset, NaiveDate, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use crate::models::QuotaData;
+use crate::proxy::common::http::build_client;
+use crate::proxy::common::model_mapping::{lookup_custom_mapping, wildcard_match};
+use crate::proxy::config::{AiStudioConfig, UpstreamProxyConfig};
+
+const BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+/// 映射目标前缀
+pub const ROUTE_PREFIX: &str = "aistudio:";
+/// 单次请求最多尝试的 Key 数
+const MAX_KEY_ATTEMPTS: usize = 3;
+/// 429 未携带 retryDelay 时的冷却时长
+const DEFAULT_COOLDOWN_SECS: i64 = 60;
+/// Key 无效 / 被禁用 (400 API_KEY_INVALID / 403) 时的冷却时长
+const INVALID_KEY_COOLDOWN_SECS: i64 = 3600;
+/// 配额显示用的模型名 (账号列表中的配额条)
+pub const QUOTA_NAME: &str = "ai-studio-requests";
+
+#[derive(Debug, Clone)]
+pub struct ApiKeyAccount {
+    pub account_id: String,
+    pub email: String,
+    pub api_key: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct KeyUsage {
+    day: Option<NaiveDate>,
+    requests: u32,
+    cooldown_until: i64,
+}
+
+fn usage_map() -> &'static Mutex<HashMap<String, KeyUsage>> {
+    static USAGE: OnceLock<Mutex<HashMap<String, KeyUsage>>> = OnceLock::new();
+    USAGE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 免费额度按太平洋时间零点重置 (固定 UTC-8，忽略夏令时)
+fn pacific() -> FixedOffset {
+    FixedOffset::west_opt(8 * 3600).expect("valid offset")
+}
+
+fn quota_day(now: DateTime<Utc>) -> NaiveDate {
+    now.with_timezone(&pacific()).date_naive()
+}
+
+fn next_reset(now: DateTime<Utc>) -> DateTime<Utc> {
+    let tomorrow = quota_day(now) + ChronoDuration::days(1);
+    tomorrow
+        .and_hms_opt(0, 0, 0)
+        .and_then(|t| t.and_local_timezone(pacific()).single())
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or(now)
+}
+
+impl KeyUsage {
+    fn requests_on(&self, day: NaiveDate) -> u32 {
+        if self.day == Some(day) {
+            self.requests
+        } else {
+            0
+        }
+    }
+
+    fn available(&self, now: DateTime<Utc>, daily_limit: u32) -> bool {
+        self.cooldown_until <= now.timestamp()
+            && (daily_limit == 0 || self.requests_on(quota_day(now)) < daily_limit)
+    }
+}
+
+/// 可用的 Key，按当日请求数升序 (优先使用剩余额度多的 Key)
+fn pick_order(keys: &[ApiKeyAccount], usage: &HashMap<String, KeyUsage>, daily_limit: u32, now: DateTime<Utc>) -> Vec<ApiKeyAccount> {
+    let day = quota_day(now);
+    let mut candidates: Vec<(u32, &ApiKeyAccount)> = keys
+        .iter()
+        .filter_map(|k| {
+            let u = usage.get(&k.account_id).cloned().unwrap_or_default();
+            u.available(now, daily_limit).then(|| (u.requests_on(day), k))
+        })
+        .collect();
+    candidates.sort_by_key(|(requests, _)| *requests);
+    candidates.into_iter().map(|(_, k)| k.clone()).collect()
+}
+
+fn record_request(account_id: &str) {
+    let now = Utc::now();
+    if let Ok(mut map) = usage_map().lock() {
+        let entry = map.entry(account_id.to_string()).or_default();
+        let day = quota_day(now);
+        if entry.day != Some(day) {
+            entry.day = Some(day);
+            entry.requests = 0;
+        }
+        entry.requests += 1;
+    }
+}
+
+fn set_cooldown(account_id: &str, secs: i64) {
+    if let Ok(mut map) = usage_map().lock() {
+        map.entry(account_id.to_string()).or_default().cooldown_until = Utc::now().timestamp() + secs;
+    }
+}
+
+/// 账号列表中显示的配额 (本地统计的当日剩余请求比例)
+pub fn quota_snapshot(account_id: &str, daily_limit: u32) -> QuotaData {
+    let now = Utc::now();
+    let usage = usage_map()
+        .lock()
+        .ok()
+        .and_then(|m| m.get(account_id).cloned())
+        .unwrap_or_default();
+    let percentage = if daily_limit == 0 {
+        100
+    } else {
+        let used = usage.requests_on(quota_day(now)).min(daily_limit);
+        (100 - used * 100 / daily_limit) as i32
+    };
+    let mut quota = QuotaData::new();
+    quota.add_model(QUOTA_NAME.to_string(), percentage, next_reset(now).to_rfc3339());
+    quota.subscription_tier = Some("API_KEY".to_string());
+    quota
+}
+
+/// 模型是否显式路由到 API Key 账号，返回 AI Studio 侧模型名
+pub fn resolve_model(config: &AiStudioConfig, original_model: &str, custom_mapping: &HashMap<String, String>) -> Option<String> {
+    let target = lookup_custom_mapping(original_model, custom_mapping).unwrap_or_else(|| original_model.to_string());
+    if let Some(model) = target.strip_prefix(ROUTE_PREFIX) {
+        return Some(model.to_string());
+    }
+    config
+        .models
+        .iter()
+        .any(|p| wildcard_match(p, &target))
+        .then_some(target)
+}
+
+/// AI Studio 只提供 Gemini 模型 (Claude 等 Antigravity 专属模型无法回退)
+pub fn supports_model(model: &str) -> bool {
+    model.starts_with("gemini-")
+}
+
+/// Antigravity 内部模型名 -> AI Studio 公开模型名 (+ 由 -high / -low 后缀推导的 thinkingLevel)
+pub fn public_model_name(model: &str) -> (String, Option<&'static str>) {
+    let mut name = model.trim_end_matches("-thinking").to_string();
+    let mut level = None;
+    for (suffix, lvl) in [("-high", "high"), ("-low", "low")] {
+        if let Some(stripped) = name.strip_suffix(suffix) {
+            name = stripped.to_string();
+            level = Some(lvl);
+            break;
+        }
+    }
+    // Gemini 3 系列在 AI Studio 上仍以 -preview 发布
+    if name.starts_with("gemini-3") && !name.contains("-preview") {
+        name.push_str("-preview");
+    }
+    (name, level)
+}
+
+/// 请求体兼容处理: 去掉 v1internal 专用字段，补充 thinkingLevel
+fn apply_quirks(request: &mut Value, thinking_level: Option<&str>) {
+    let Some(obj) = request.as_object_mut() else {
+        return;
+    };
+    obj.remove("sessionId");
+    obj.remove("model");
+    // labels 仅 Vertex 支持，AI Studio 会返回 400
+    obj.remove("labels");
+    if let Some(level) = thinking_level {
+        let gen = obj.entry("generationConfig").or_insert_with(|| serde_json::json!({}));
+        if let Some(gen) = gen.as_object_mut() {
+            let thinking = gen.entry("thinkingConfig").or_insert_with(|| serde_json::json!({}));
+            if let Some(thinking) = thinking.as_object_mut() {
+                if !thinking.contains_key("thinkingBudget") {
+                    thinking.entry("thinkingLevel").or_insert_with(|| Value::String(level.to_string()));
+                }
+            }
+        }
+    }
+}
+
+/// 调用 generateContent / streamGenerateContent，429 / 无效 Key 时换下一个 Key。
+/// request 为标准 Gemini 请求体；成功时返回 (响应, 使用的账号标识)，失败时返回 (HTTP 状态, 错误信息)
+pub async fn call_generate(
+    keys: &[ApiKeyAccount],
+    config: &AiStudioConfig,
+    upstream_proxy: &UpstreamProxyConfig,
+    timeout_secs: u64,
+    model: &str,
+    stream: bool,
+    mut request: Value,
+) -> Result<(reqwest::Response, String), (u16, String)> {
+    let candidates = {
+        let usage = usage_map().lock().map(|m| m.clone()).unwrap_or_default();
+        pick_order(keys, &usage, config.daily_request_limit, Utc::now())
+    };
+    if candidates.is_empty() {
+        return Err((429, "No AI Studio API key available (all keys cooling down or over the daily limit)".to_string()));
+    }
+
+    let (public_model, thinking_level) = public_model_name(model);
+    apply_quirks(&mut request, thinking_level);
+    let client = build_client(upstream_proxy, timeout_secs).map_err(|e| (500, e))?;
+    let method = if stream { "streamGenerateContent?alt=sse" } else { "generateContent" };
+    let url = format!("{}/{}:{}", BASE_URL, public_model, method);
+
+    let mut last_error = (502, String::new());
+    for key in candidates.iter().take(MAX_KEY_ATTEMPTS) {
+        tracing::debug!("[AIStudio] {} -> {} ({})", model, public_model, key.email);
+        let resp = match client
+            .post(&url)
+            .header("x-goog-api-key", key.api_key.as_str())
+            .json(&request)
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                last_error = (502, format!("AI Studio request failed: {}", e));
+                continue;
+            }
+        };
+        let status = resp.status().as_u16();
+        match status {
+            429 => {
+                let text = resp.text().await.unwrap_or_default();
+                let secs = crate::proxy::upstream::retry::parse_retry_delay(&text)
+                    .map(|ms| (ms / 1000) as i64 + 1)
+                    .unwrap_or(DEFAULT_COOLDOWN_SECS);
+                tracing::warn!("[AIStudio] {} rate limited, cooling down {}s", key.email, secs);
+                set_cooldown(&key.account_id, secs);
+                last_error = (429, text);
+            }
+            400 | 403 => {
+                let text = resp.text().await.unwrap_or_default();
+                record_request(&key.account_id);
+                // 请求本身的 400 (参数错误) 直接返回，不换 Key
+                if status == 400 && !text.contains("API_KEY_INVALID") {
+                    return Err((400, text));
+                }
+                tracing::warn!("[AIStudio] {} rejected (HTTP {}), disabling for {}s", key.email, status, INVALID_KEY_COOLDOWN_SECS);
+                set_cooldown(&key.account_id, INVALID_KEY_COOLDOWN_SECS);
+                last_error = (status, text);
+            }
+            _ => {
+                record_request(&key.account_id);
+                return Ok((resp, key.email.clone()));
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/// 添加账号前校验 Key 是否有效 (列出一个模型)
+pub async fn verify_key(api_key: &str, upstream_proxy: &UpstreamProxyConfig) -> Result<(), String> {
+    let client = build_client(upstream_proxy, 15)?;
+    let resp = client
+        .get(format!("{}?pageSize=1", BASE_URL))
+        .header("x-goog-api-key", api_key.trim())
+        .send()
+        .await
+        .map_err(|e| format!("AI Studio request failed: {}", e))?;
+    if resp.status().is_success() {
+        return Ok(());
+    }
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    Err(format!("API key rejected (HTTP {}): {}", status, text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn key(id: &str) -> ApiKeyAccount {
+        ApiKeyAccount { account_id: id.to_string(), email: format!("{}@key", id), api_key: format!("AIza-{}", id) }
+    }
+
+    #[test]
+    fn test_public_model_name_and_quirks() {
+        assert_eq!(public_model_name("gemini-3-pro-high"), ("gemini-3-pro-preview".to_string(), Some("high")));
+        assert_eq!(public_model_name("gemini-3-flash"), ("gemini-3-flash-preview".to_string(), None));
+        assert_eq!(public_model_name("gemini-2.5-flash-thinking"), ("gemini-2.5-flash".to_string(), None));
+        assert!(!supports_model("claude-sonnet-4-5"));
+
+        let mut request = serde_json::json!({ "contents": [], "sessionId": "s", "labels": {} });
+        apply_quirks(&mut request, Some("low"));
+        assert_eq!(
+            request,
+            serde_json::json!({ "contents": [], "generationConfig": { "thinkingConfig": { "thinkingLevel": "low" } } })
+        );
+    }
+
+    #[test]
+    fn test_pick_order_respects_limit_and_cooldown() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 2, 20, 0, 0).unwrap();
+        let day = quota_day(now);
+        let mut usage = HashMap::new();
+        usage.insert("a".to_string(), KeyUsage { day: Some(day), requests: 5, cooldown_until: 0 });
+        usage.insert("b".to_string(), KeyUsage { day: Some(day), requests: 1, cooldown_until: 0 });
+        usage.insert("c".to_string(), KeyUsage { day: None, requests: 0, cooldown_until: now.timestamp() + 30 });
+        // 前一天的计数不影响今天
+        usage.insert("d".to_string(), KeyUsage { day: day.pred_opt(), requests: 99, cooldown_until: 0 });
+
+        let keys = vec![key("a"), key("b"), key("c"), key("d")];
+        let ids = |v: Vec<ApiKeyAccount>| v.into_iter().map(|k| k.account_id).collect::<Vec<_>>();
+        assert_eq!(ids(pick_order(&keys, &usage, 0, now)), vec!["d", "b", "a"]);
+        assert_eq!(ids(pick_order(&keys, &usage, 5, now)), vec!["d", "b"]);
+
+        // 下一次重置: 太平洋时间 03-03 零点 (UTC 08:00)
+        assert_eq!(next_reset(now), Utc.with_ymd_and_hms(2026, 3, 3, 8, 0, 0).unwrap());
+    }
+}
@@ -1,9 +1,13 @@
 // 上游客户端实现
 // 基于高性能通讯接口封装
 
+use crate::proxy::config::{EndpointSelectionStrategy, UpstreamEndpointsConfig, UpstreamPoolConfig};
+use dashmap::DashMap;
 use reqwest::{header, Client, Response, StatusCode};
 use serde_json::Value;
+use std::time::Instant;
 use tokio::time::Duration;
+use tracing::Instrument;
 
 // Cloud Code v1internal endpoints (fallback order: prod → daily)
 // 优先使用稳定的 prod 端点，避免影响缓存命中率
@@ -14,21 +18,69 @@ const V1_INTERNAL_BASE_URL_FALLBACKS: [&str; 2] = [
     V1_INTERNAL_BASE_URL_DAILY,  // 备用测试环境（新功能）
 ];
 
+/// 单个端点的健康状态 (延迟 EWMA + 故障冷却)
+#[derive(Debug, Clone, Default)]
+struct EndpointHealth {
+    /// 响应头延迟的指数移动平均 (ms)
+    ewma_ms: Option<f64>,
+    /// 在此之前视为不健康，排到末尾
+    unhealthy_until: Option<Instant>,
+}
+
+/// 就绪探测结果的缓存时间，避免容器健康检查频繁请求上游
+const REACHABILITY_CACHE_SECS: u64 = 30;
+/// 就绪探测的单端点超时
+const REACHABILITY_TIMEOUT_SECS: u64 = 5;
+
+/// 单个端点的可达性探测结果 (/readyz)
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EndpointProbe {
+    pub url: String,
+    pub reachable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 pub struct UpstreamClient {
     http_client: Client,
+    endpoints: std::sync::RwLock<UpstreamEndpointsConfig>,
+    health: DashMap<String, EndpointHealth>,
+    reachability: std::sync::Mutex<Option<(Instant, Vec<EndpointProbe>)>>,
 }
 
 impl UpstreamClient {
-    pub fn new(proxy_config: Option<crate::proxy::config::UpstreamProxyConfig>) -> Self {
+    /// 按上游代理与连接池配置创建客户端
+    pub fn new(
+        proxy_config: Option<crate::proxy::config::UpstreamProxyConfig>,
+        pool: &UpstreamPoolConfig,
+    ) -> Self {
         let mut builder = Client::builder()
             // Connection settings (优化连接复用，减少建立开销)
             .connect_timeout(Duration::from_secs(20))
-            .pool_max_idle_per_host(16)                  // 每主机最多 16 个空闲连接
-            .pool_idle_timeout(Duration::from_secs(90))  // 空闲连接保持 90 秒
-            .tcp_keepalive(Duration::from_secs(60))      // TCP 保活探测 60 秒
+            .pool_max_idle_per_host(pool.max_idle_per_host)
+            .pool_idle_timeout(Duration::from_secs(pool.idle_timeout_secs))
             .timeout(Duration::from_secs(600))
             .user_agent("antigravity/1.11.9 windows/amd64");
 
+        if pool.tcp_keepalive_secs > 0 {
+            builder = builder.tcp_keepalive(Duration::from_secs(pool.tcp_keepalive_secs));
+        }
+
+        if pool.http2 {
+            // HTTP/2 多路复用: 通过 PING 保活让空闲连接保持常热
+            builder = builder.http2_adaptive_window(true);
+            if pool.http2_keepalive_interval_secs > 0 {
+                builder = builder
+                    .http2_keep_alive_interval(Duration::from_secs(pool.http2_keepalive_interval_secs))
+                    .http2_keep_alive_timeout(Duration::from_secs(20))
+                    .http2_keep_alive_while_idle(true);
+            }
+        } else {
+            builder = builder.http1_only();
+        }
+
         if let Some(config) = proxy_config {
             if config.enabled && !config.url.is_empty() {
                 if let Ok(proxy) = reqwest::Proxy::all(&config.url) {
@@ -40,7 +92,84 @@ impl UpstreamClient {
 
         let http_client = builder.build().expect("Failed to create HTTP client");
 
-        Self { http_client }
+        Self {
+            http_client,
+            endpoints: std::sync::RwLock::new(UpstreamEndpointsConfig::default()),
+            health: DashMap::new(),
+            reachability: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// 探测各端点是否可达 (任何 HTTP 状态码都视为可达)，结果缓存 30 秒
+    pub async fn probe_reachability(&self) -> Vec<EndpointProbe> {
+        if let Ok(cache) = self.reachability.lock() {
+            if let Some((at, probes)) = cache.as_ref() {
+                if at.elapsed() < Duration::from_secs(REACHABILITY_CACHE_SECS) {
+                    return probes.clone();
+                }
+            }
+        }
+
+        let (endpoints, _, _) = self.endpoint_plan();
+        let probes = futures::future::join_all(endpoints.into_iter().map(|url| async move {
+            let start = Instant::now();
+            let result = self
+                .http_client
+                .get(&url)
+                .timeout(Duration::from_secs(REACHABILITY_TIMEOUT_SECS))
+                .send()
+                .await;
+            match result {
+                Ok(_) => EndpointProbe {
+                    url,
+                    reachable: true,
+                    latency_ms: Some(start.elapsed().as_millis() as u64),
+                    error: None,
+                },
+                Err(e) => EndpointProbe {
+                    url,
+                    reachable: false,
+                    latency_ms: None,
+                    error: Some(e.to_string()),
+                },
+            }
+        }))
+        .await;
+
+        if let Ok(mut cache) = self.reachability.lock() {
+            *cache = Some((Instant::now(), probes.clone()));
+        }
+        probes
+    }
+
+    /// 预热连接: 向每个端点发起一次轻量请求以完成 DNS/TCP/TLS 握手，连接随后留在池中复用
+    pub async fn prewarm(&self) {
+        let (endpoints, _, _) = self.endpoint_plan();
+        for base_url in endpoints {
+            let start = Instant::now();
+            // 任何 HTTP 状态码都说明连接已建立，结果本身无意义
+            match self.http_client.get(&base_url).send().await {
+                Ok(resp) => tracing::debug!(
+                    "Upstream connection prewarmed | Endpoint: {} | {:?} | {}ms",
+                    base_url,
+                    resp.version(),
+                    start.elapsed().as_millis()
+                ),
+                Err(e) => tracing::debug!("Upstream prewarm failed for {}: {}", base_url, e),
+            }
+        }
+    }
+
+    /// 设置端点配置 (启动时及配置热更新时调用)
+    pub fn set_endpoints(&self, config: UpstreamEndpointsConfig) {
+        crate::proxy::circuit_breaker::CircuitBreakers::global().set_config(config.circuit_breaker.clone());
+        if let Ok(mut guard) = self.endpoints.write() {
+            *guard = config;
+        }
+        self.health.clear();
+        if let Ok(mut cache) = self.reachability.lock() {
+            *cache = None;
+        }
     }
 
     /// 构建 v1internal URL
@@ -68,79 +197,153 @@ impl UpstreamClient {
             || status.is_server_error()
     }
 
-    /// 调用 v1internal API（基础方法）
-    /// 
-    /// 发起基础网络请求，支持多端点自动 Fallback
-    pub async fn call_v1_internal(
-        &self,
-        method: &str,
-        access_token: &str,
-        body: Value,
-        query_string: Option<&str>,
-    ) -> Result<Response, String> {
-        self.call_v1_internal_with_headers(method, access_token, body, query_string, std::collections::HashMap::new()).await
+    /// 端点自身故障 (而非账号限流) 才计入冷却
+    fn is_endpoint_failure(status: StatusCode) -> bool {
+        status == StatusCode::REQUEST_TIMEOUT || status.is_server_error()
     }
 
-    /// [FIX #765] 调用 v1internal API，支持透传额外的 Headers
-    pub async fn call_v1_internal_with_headers(
+    /// 按策略排列端点：健康端点在前 (Ordered 保持配置顺序 / Latency 按 EWMA 升序)，冷却中的端点排到末尾兜底
+    fn order_endpoints(
+        base_urls: &[String],
+        strategy: EndpointSelectionStrategy,
+        health: &std::collections::HashMap<String, EndpointHealth>,
+        now: Instant,
+    ) -> Vec<String> {
+        let is_cooling = |url: &String| {
+            health
+                .get(url)
+                .and_then(|h| h.unhealthy_until)
+                .map(|until| until > now)
+                .unwrap_or(false)
+        };
+
+        let (mut healthy, cooling): (Vec<String>, Vec<String>) =
+            base_urls.iter().cloned().partition(|u| !is_cooling(u));
+
+        if strategy == EndpointSelectionStrategy::Latency {
+            // 未测量过的端点视为 0ms，保证每个端点至少被探测一次
+            let latency = |u: &String| health.get(u).and_then(|h| h.ewma_ms).unwrap_or(0.0);
+            healthy.sort_by(|a, b| latency(a).partial_cmp(&latency(b)).unwrap_or(std::cmp::Ordering::Equal));
+        }
+
+        healthy.extend(cooling);
+        healthy
+    }
+
+    /// 当前请求应依次尝试的端点，以及响应头超时
+    fn endpoint_plan(&self) -> (Vec<String>, Option<Duration>, Duration) {
+        let config = self
+            .endpoints
+            .read()
+            .map(|c| c.clone())
+            .unwrap_or_default();
+
+        let manifest_urls = crate::proxy::mappers::quirks::manifest_endpoints();
+        let base_urls: Vec<String> = if !config.base_urls.is_empty() {
+            config
+                .base_urls
+                .iter()
+                .map(|u| u.trim().trim_end_matches('/').to_string())
+                .filter(|u| !u.is_empty())
+                .collect()
+        } else if !manifest_urls.is_empty() {
+            // quirk 清单下发的端点 (如新的 API 版本)
+            manifest_urls.iter().map(|u| u.trim_end_matches('/').to_string()).collect()
+        } else {
+            V1_INTERNAL_BASE_URL_FALLBACKS.iter().map(|s| s.to_string()).collect()
+        };
+
+        let snapshot: std::collections::HashMap<String, EndpointHealth> = self
+            .health
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect();
+        let ordered = Self::order_endpoints(&base_urls, config.strategy, &snapshot, Instant::now());
+
+        let header_timeout = (config.header_timeout_secs > 0)
+            .then(|| Duration::from_secs(config.header_timeout_secs));
+        (ordered, header_timeout, Duration::from_secs(config.failure_cooldown_secs))
+    }
+
+    fn record_success(&self, base_url: &str, elapsed: Duration) {
+        let sample = elapsed.as_millis() as f64;
+        let mut entry = self.health.entry(base_url.to_string()).or_default();
+        entry.ewma_ms = Some(match entry.ewma_ms {
+            Some(prev) => prev * 0.7 + sample * 0.3,
+            None => sample,
+        });
+        entry.unhealthy_until = None;
+    }
+
+    fn record_failure(&self, base_url: &str, cooldown: Duration) {
+        let mut entry = self.health.entry(base_url.to_string()).or_default();
+        entry.unhealthy_until = Some(Instant::now() + cooldown);
+    }
+
+    /// 按端点计划发送请求，5xx/超时/网络错误时自动切换到下一个端点
+    /// 已知所属账号时经过 (账号, 端点) 熔断器: 熔断中的端点直接跳过
+    async fn send_with_failover<B: serde::Serialize + ?Sized + Sync>(
         &self,
         method: &str,
-        access_token: &str,
-        body: Value,
         query_string: Option<&str>,
-        extra_headers: std::collections::HashMap<String, String>,
+        headers: header::HeaderMap,
+        body: &B,
+        account: Option<&str>,
     ) -> Result<Response, String> {
-        // 构建 Headers (所有端点复用)
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::CONTENT_TYPE,
-            header::HeaderValue::from_static("application/json"),
-        );
-        headers.insert(
-            header::AUTHORIZATION,
-            header::HeaderValue::from_str(&format!("Bearer {}", access_token))
-                .map_err(|e| e.to_string())?,
-        );
-        headers.insert(
-            header::USER_AGENT,
-            header::HeaderValue::from_static("antigravity/1.11.9 windows/amd64"),
-        );
-
-        // 注入额外的 Headers (如 anthropic-beta)
-        for (k, v) in extra_headers {
-            if let Ok(hk) = header::HeaderName::from_bytes(k.as_bytes()) {
-                if let Ok(hv) = header::HeaderValue::from_str(&v) {
-                    headers.insert(hk, hv);
-                }
-            }
-        }
-
+        let (endpoints, header_timeout, cooldown) = self.endpoint_plan();
+        let total = endpoints.len();
         let mut last_err: Option<String> = None;
+        let breakers = crate::proxy::circuit_breaker::CircuitBreakers::global();
 
         // 遍历所有端点，失败时自动切换
-        for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
+        for (idx, base_url) in endpoints.iter().enumerate() {
             let url = Self::build_url(base_url, method, query_string);
-            let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
+            let has_next = idx + 1 < total;
 
-            let response = self
+            if let Some(account) = account {
+                if !breakers.try_acquire(account, base_url) {
+                    tracing::debug!("Circuit open for {} at {}, skipping endpoint", account, base_url);
+                    last_err = Some(format!("Circuit open for {} at {}", account, base_url));
+                    continue;
+                }
+            }
+
+            let start = Instant::now();
+            let request = self
                 .http_client
                 .post(&url)
                 .headers(headers.clone())
-                .json(&body)
-                .send()
-                .await;
+                .json(body)
+                .send();
+
+            let response = match header_timeout {
+                Some(limit) => match tokio::time::timeout(limit, request).await {
+                    Ok(r) => r.map_err(|e| e.to_string()),
+                    Err(_) => Err(format!("no response headers within {}s", limit.as_secs())),
+                },
+                None => request.await.map_err(|e| e.to_string()),
+            };
 
             match response {
                 Ok(resp) => {
                     let status = resp.status();
+                    if let Some(account) = account {
+                        let code = status.as_u16();
+                        if crate::proxy::circuit_breaker::is_breaker_failure(code) {
+                            breakers.record_failure(account, base_url, &format!("HTTP {}", code));
+                        } else if !crate::proxy::circuit_breaker::is_breaker_neutral(code) {
+                            breakers.record_success(account, base_url);
+                        }
+                    }
                     if status.is_success() {
+                        self.record_success(base_url, start.elapsed());
                         if idx > 0 {
                             tracing::info!(
                                 "✓ Upstream fallback succeeded | Endpoint: {} | Status: {} | Attempt: {}/{}",
                                 base_url,
                                 status,
                                 idx + 1,
-                                V1_INTERNAL_BASE_URL_FALLBACKS.len()
+                                total
                             );
                         } else {
                             tracing::debug!("✓ Upstream request succeeded | Endpoint: {} | Status: {}", base_url, status);
@@ -148,6 +351,10 @@ impl UpstreamClient {
                         return Ok(resp);
                     }
 
+                    if Self::is_endpoint_failure(status) {
+                        self.record_failure(base_url, cooldown);
+                    }
+
                     // 如果有下一个端点且当前错误可重试，则切换
                     if has_next && Self::should_try_next_endpoint(status) {
                         tracing::warn!(
@@ -164,6 +371,10 @@ impl UpstreamClient {
                     return Ok(resp);
                 }
                 Err(e) => {
+                    self.record_failure(base_url, cooldown);
+                    if let Some(account) = account {
+                        breakers.record_failure(account, base_url, &e);
+                    }
                     let msg = format!("HTTP request failed at {}: {}", base_url, e);
                     tracing::debug!("{}", msg);
                     last_err = Some(msg);
@@ -180,6 +391,85 @@ impl UpstreamClient {
         Err(last_err.unwrap_or_else(|| "All endpoints failed".to_string()))
     }
 
+    /// 调用 v1internal API（基础方法）
+    /// 
+    /// 发起基础网络请求，支持多端点自动 Fallback
+    /// body 可传 `Value` 或 `&Value` (重试时复用同一请求体，避免克隆)
+    pub async fn call_v1_internal<B: serde::Serialize + Send + Sync>(
+        &self,
+        method: &str,
+        access_token: &str,
+        body: B,
+        query_string: Option<&str>,
+    ) -> Result<Response, String> {
+        self.call_v1_internal_with_headers(method, access_token, body, query_string, std::collections::HashMap::new()).await
+    }
+
+    /// [FIX #765] 调用 v1internal API，支持透传额外的 Headers
+    pub async fn call_v1_internal_with_headers<B: serde::Serialize + Send + Sync>(
+        &self,
+        method: &str,
+        access_token: &str,
+        body: B,
+        query_string: Option<&str>,
+        extra_headers: std::collections::HashMap<String, String>,
+    ) -> Result<Response, String> {
+        // 构建 Headers (所有端点复用)
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_str(&format!("Bearer {}", access_token))
+                .map_err(|e| e.to_string())?,
+        );
+        headers.insert(
+            header::USER_AGENT,
+            header::HeaderValue::from_static("antigravity/1.11.9 windows/amd64"),
+        );
+
+        // 注入额外的 Headers (如 anthropic-beta)
+        for (k, v) in extra_headers {
+            if let Ok(hk) = header::HeaderName::from_bytes(k.as_bytes()) {
+                if let Ok(hv) = header::HeaderValue::from_str(&v) {
+                    headers.insert(hk, hv);
+                }
+            }
+        }
+
+        // 链路追踪: 上游调用 (到收到响应头为止，含端点切换)
+        let span = tracing::info_span!(
+            target: crate::proxy::telemetry::TARGET,
+            "upstream",
+            otel.kind = "client",
+            otel.status_code = tracing::field::Empty,
+            otel.status_message = tracing::field::Empty,
+            rpc.method = method,
+            http.status_code = tracing::field::Empty,
+        );
+        let account = crate::proxy::circuit_breaker::CircuitBreakers::global().account_for_token(access_token);
+        let result = self
+            .send_with_failover(method, query_string, headers, &body, account.as_deref())
+            .instrument(span.clone())
+            .await;
+        match &result {
+            Ok(response) => {
+                span.record("http.status_code", response.status().as_u16());
+                if !response.status().is_success() {
+                    span.record("otel.status_code", "error");
+                    span.record("otel.status_message", response.status().to_string().as_str());
+                }
+            }
+            Err(e) => {
+                span.record("otel.status_code", "error");
+                span.record("otel.status_message", e.as_str());
+            }
+        }
+        result
+    }
+
     /// 调用 v1internal API（带 429 重试,支持闭包）
     /// 
     /// 带容错和重试的核心请求逻辑
@@ -219,70 +509,16 @@ impl UpstreamClient {
             header::HeaderValue::from_static("antigravity/1.11.9 windows/amd64"),
         );
 
-        let mut last_err: Option<String> = None;
-
-        // 遍历所有端点，失败时自动切换
-        for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
-            let url = Self::build_url(base_url, "fetchAvailableModels", None);
-
-            let response = self
-                .http_client
-                .post(&url)
-                .headers(headers.clone())
-                .json(&serde_json::json!({}))
-                .send()
-                .await;
-
-            match response {
-                Ok(resp) => {
-                    let status = resp.status();
-                    if status.is_success() {
-                        if idx > 0 {
-                            tracing::info!(
-                                "✓ Upstream fallback succeeded for fetchAvailableModels | Endpoint: {} | Status: {}",
-                                base_url,
-                                status
-                            );
-                        } else {
-                            tracing::debug!("✓ fetchAvailableModels succeeded | Endpoint: {}", base_url);
-                        }
-                        let json: Value = resp
-                            .json()
-                            .await
-                            .map_err(|e| format!("Parse json failed: {}", e))?;
-                        return Ok(json);
-                    }
-
-                    // 如果有下一个端点且当前错误可重试，则切换
-                    let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
-                    if has_next && Self::should_try_next_endpoint(status) {
-                        tracing::warn!(
-                            "fetchAvailableModels returned {} at {}, trying next endpoint",
-                            status,
-                            base_url
-                        );
-                        last_err = Some(format!("Upstream error: {}", status));
-                        continue;
-                    }
-
-                    // 不可重试的错误或已是最后一个端点
-                    return Err(format!("Upstream error: {}", status));
-                }
-                Err(e) => {
-                    let msg = format!("Request failed at {}: {}", base_url, e);
-                    tracing::debug!("{}", msg);
-                    last_err = Some(msg);
-
-                    // 如果是最后一个端点，退出循环
-                    if idx + 1 >= V1_INTERNAL_BASE_URL_FALLBACKS.len() {
-                        break;
-                    }
-                    continue;
-                }
-            }
+        let resp = self
+            .send_with_failover("fetchAvailableModels", None, headers, &serde_json::json!({}), None)
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(format!("Upstream error: {}", status));
         }
-
-        Err(last_err.unwrap_or_else(|| "All endpoints failed".to_string()))
+        resp.json()
+            .await
+            .map_err(|e| format!("Parse json failed: {}", e))
     }
 }
 
@@ -307,4 +543,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_order_endpoints() {
+        let a = "https://a.example/v1internal".to_string();
+        let b = "https://b.example/v1internal".to_string();
+        let c = "https://c.example/v1internal".to_string();
+        let urls = vec![a.clone(), b.clone(), c.clone()];
+        let now = Instant::now();
+
+        let mut health = std::collections::HashMap::new();
+        health.insert(a.clone(), EndpointHealth { ewma_ms: Some(300.0), unhealthy_until: None });
+        health.insert(b.clone(), EndpointHealth { ewma_ms: Some(50.0), unhealthy_until: Some(now + Duration::from_secs(30)) });
+        health.insert(c.clone(), EndpointHealth { ewma_ms: Some(100.0), unhealthy_until: None });
+
+        // Ordered: 保持配置顺序，冷却中的端点排到末尾
+        let ordered = UpstreamClient::order_endpoints(&urls, EndpointSelectionStrategy::Ordered, &health, now);
+        assert_eq!(ordered, vec![a.clone(), c.clone(), b.clone()]);
+
+        // Latency: 健康端点按延迟升序
+        let ordered = UpstreamClient::order_endpoints(&urls, EndpointSelectionStrategy::Latency, &health, now);
+        assert_eq!(ordered, vec![c.clone(), a.clone(), b.clone()]);
+
+        // 冷却过期后恢复
+        let later = now + Duration::from_secs(60);
+        let ordered = UpstreamClient::order_endpoints(&urls, EndpointSelectionStrategy::Latency, &health, later);
+        assert_eq!(ordered, vec![b, c, a]);
+    }
 }
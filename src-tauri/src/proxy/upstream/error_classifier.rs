@@ -0,0 +1,187 @@
+// 上游错误指纹识别
+// 把原始的 Gemini / Cloud Code 错误负载归类为已知场景，给出机器可读的错误码与面向用户的处理建议。
+// handler 常把上游状态码包装成 503 "Last error: HTTP 403 ..."，因此按错误文本匹配而不依赖最终状态码。
+
+/// 已知错误场景
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorCase {
+    /// 机器可读的错误码
+    pub code: &'static str,
+    /// 处理建议
+    pub hint: &'static str,
+}
+
+pub const OAUTH_EXPIRED: ErrorCase = ErrorCase {
+    code: "oauth_expired",
+    hint: "The account's OAuth token is expired or revoked. Refresh the account in Accounts; if that fails, sign in to the account again.",
+};
+
+pub const WRONG_PROJECT: ErrorCase = ErrorCase {
+    code: "wrong_project",
+    hint: "The account's Cloud project is wrong or has no access to the Code Assist API. Refresh the account so the project is discovered again, or check its project_id.",
+};
+
+pub const MODEL_NOT_ALLOWED: ErrorCase = ErrorCase {
+    code: "model_not_allowed",
+    hint: "This model is not available for the account's subscription tier. Map it to a model the tier supports, or restrict it to Pro / Ultra accounts.",
+};
+
+pub const THOUGHT_SIGNATURE_MISSING: ErrorCase = ErrorCase {
+    code: "thought_signature_missing",
+    hint: "The upstream needs the thought signature from the previous tool-calling turn. Resend the conversation with thinking blocks unmodified, or start a new conversation.",
+};
+
+pub const REGION_BLOCKED: ErrorCase = ErrorCase {
+    code: "region_blocked",
+    hint: "Google rejected the request because of the location of the outgoing IP. Route upstream traffic through a proxy in a supported region (Settings → Upstream proxy).",
+};
+
+pub const QUOTA_EXHAUSTED: ErrorCase = ErrorCase {
+    code: "quota_exhausted",
+    hint: "The account's quota for this model is used up. Wait for the quota to reset, add more accounts, or switch to another model.",
+};
+
+pub const NO_CAPACITY: ErrorCase = ErrorCase {
+    code: "no_capacity",
+    hint: "The upstream has no capacity for this model right now. Retry shortly, or configure a fallback model chain.",
+};
+
+pub const CONTEXT_TOO_LONG: ErrorCase = ErrorCase {
+    code: "context_too_long",
+    hint: "The request exceeds the model's context window. Shorten the conversation, or use a model with a larger context.",
+};
+
+pub const ALL_CASES: &[ErrorCase] = &[
+    OAUTH_EXPIRED,
+    WRONG_PROJECT,
+    MODEL_NOT_ALLOWED,
+    THOUGHT_SIGNATURE_MISSING,
+    REGION_BLOCKED,
+    QUOTA_EXHAUSTED,
+    NO_CAPACITY,
+    CONTEXT_TOO_LONG,
+];
+
+fn contains_any(text: &str, markers: &[&str]) -> bool {
+    markers.iter().any(|m| text.contains(m))
+}
+
+/// 按错误文本识别场景 (无法识别时返回 None)
+/// 顺序从特征最明确的场景开始: 签名 / 地区错误的文本中也可能出现 project 或 permission
+pub fn classify(error_text: &str) -> Option<ErrorCase> {
+    let lower = error_text.to_lowercase();
+    if contains_any(&lower, &["thought_signature", "thought signature", "thoughtsignature"]) {
+        return Some(THOUGHT_SIGNATURE_MISSING);
+    }
+    if contains_any(
+        &lower,
+        &["user location is not supported", "location is not supported", "unsupported_country", "not available in your country"],
+    ) {
+        return Some(REGION_BLOCKED);
+    }
+    if contains_any(
+        &lower,
+        &[
+            "invalid_grant",
+            "invalid authentication credentials",
+            "expected oauth 2 access token",
+            "token has been expired or revoked",
+            "unauthenticated",
+        ],
+    ) {
+        return Some(OAUTH_EXPIRED);
+    }
+    if contains_any(
+        &lower,
+        &[
+            "not available for your",
+            "not allowed for your",
+            "not supported for your",
+            "does not have access to model",
+            "model is not available for",
+            "requires a paid",
+            "upgrade your plan",
+        ],
+    ) {
+        return Some(MODEL_NOT_ALLOWED);
+    }
+    // 与 project_resolver::is_project_error 的判断一致，但不限制状态码
+    if lower.contains("project")
+        && contains_any(
+            &lower,
+            &["permission_denied", "permission denied", "not found", "mismatch", "invalid project", "does not have permission", "has not been used", "consumer_invalid"],
+        )
+    {
+        return Some(WRONG_PROJECT);
+    }
+    if contains_any(&lower, &["quota_exhausted", "resource_exhausted", "quota exceeded", "exhausted your capacity"]) {
+        return Some(QUOTA_EXHAUSTED);
+    }
+    if contains_any(&lower, &["no capacity available", "model_capacity_exhausted", "overloaded"]) {
+        return Some(NO_CAPACITY);
+    }
+    if contains_any(
+        &lower,
+        &["exceeds the maximum number of tokens", "input token count", "context length", "prompt is too long", "context_length_exceeded"],
+    ) {
+        return Some(CONTEXT_TOO_LONG);
+    }
+    None
+}
+
+/// 按错误码查找场景 (日志回读时使用)
+pub fn case_for_code(code: &str) -> Option<ErrorCase> {
+    ALL_CASES.iter().find(|c| c.code == code).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_known_payloads() {
+        let cases = [
+            (
+                r#"{"error":{"code":401,"message":"Request had invalid authentication credentials. Expected OAuth 2 access token, login cookie or other valid authentication credential.","status":"UNAUTHENTICATED"}}"#,
+                OAUTH_EXPIRED,
+            ),
+            ("Token error: Token refresh failed: invalid_grant", OAUTH_EXPIRED),
+            (
+                r#"{"error":{"code":403,"message":"Permission denied on resource project useful-fuze-abc12.","status":"PERMISSION_DENIED"}}"#,
+                WRONG_PROJECT,
+            ),
+            (
+                r#"{"error":{"code":403,"message":"The model gemini-3-pro-high is not available for your current tier.","status":"PERMISSION_DENIED"}}"#,
+                MODEL_NOT_ALLOWED,
+            ),
+            (
+                r#"{"error":{"code":400,"message":"Function call is missing a thought_signature in functionCall parts.","status":"INVALID_ARGUMENT"}}"#,
+                THOUGHT_SIGNATURE_MISSING,
+            ),
+            (
+                r#"{"error":{"code":400,"message":"User location is not supported for the API use.","status":"FAILED_PRECONDITION"}}"#,
+                REGION_BLOCKED,
+            ),
+            (r#"{"error":{"code":429,"message":"QUOTA_EXHAUSTED","status":"RESOURCE_EXHAUSTED"}}"#, QUOTA_EXHAUSTED),
+            (r#"{"error":{"code":503,"message":"No capacity available for model"}}"#, NO_CAPACITY),
+            (
+                "All accounts exhausted. Last error: HTTP 400: The input token count (1200000) exceeds the maximum number of tokens allowed (1048576).",
+                CONTEXT_TOO_LONG,
+            ),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(classify(text), Some(expected), "{}", text);
+        }
+        assert_eq!(classify(r#"{"error":{"code":400,"message":"Invalid JSON payload received."}}"#), None);
+    }
+
+    #[test]
+    fn test_case_for_code() {
+        assert_eq!(case_for_code("region_blocked"), Some(REGION_BLOCKED));
+        assert_eq!(case_for_code("nope"), None);
+        let mut codes: Vec<&str> = ALL_CASES.iter().map(|c| c.code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), ALL_CASES.len());
+    }
+}
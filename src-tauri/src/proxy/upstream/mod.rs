@@ -3,4 +3,9 @@
 
 pub mod client;
 pub mod retry;
+pub mod error_classifier;
 pub mod models;
+pub mod resume;
+pub mod sse_rewrite;
+pub mod vertex;
+pub mod aistudio;
@@ -0,0 +1,212 @@
+// 流式响应断线续传
+// 上游 SSE 在生成中途断开时，把已收到的正文作为 model 前缀、追加一条 "continue" 指令重新请求，
+// 并把续写内容拼接进同一个下游流，使长输出在网络抖动时仍能完整返回。
+// 工作在 Gemini v1internal 流层面，对 Claude / OpenAI / Gemini 各协议的 mapper 透明。
+
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use super::client::UpstreamClient;
+
+pub type GeminiByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>;
+
+/// 续写指令 (要求模型从断点处无缝继续，不重复已输出内容)
+const CONTINUE_PROMPT: &str = "Your previous response was cut off by a network interruption. \
+Continue exactly where it stopped, without repeating any text that was already written and without any preamble.";
+
+/// 跟踪已转发的 SSE 数据，用于判断是否正常结束与构建续写前缀
+#[derive(Default)]
+struct StreamProgress {
+    /// 已收到的正文 (不含 thought)
+    text: String,
+    finished: bool,
+    /// 出现函数调用时不续传 (无法安全拼接)
+    has_function_call: bool,
+}
+
+impl StreamProgress {
+    fn observe_line(&mut self, line: &str) {
+        let Some(data) = line.trim().strip_prefix("data:") else {
+            return;
+        };
+        let Ok(value) = serde_json::from_str::<Value>(data.trim()) else {
+            return;
+        };
+        let inner = value.get("response").unwrap_or(&value);
+        let Some(candidate) = inner.get("candidates").and_then(|c| c.get(0)) else {
+            return;
+        };
+        if candidate.get("finishReason").and_then(|f| f.as_str()).is_some() {
+            self.finished = true;
+        }
+        let parts = candidate
+            .get("content")
+            .and_then(|c| c.get("parts"))
+            .and_then(|p| p.as_array());
+        for part in parts.into_iter().flatten() {
+            if part.get("functionCall").is_some() {
+                self.has_function_call = true;
+            }
+            if part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false) {
+                continue;
+            }
+            if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                self.text.push_str(text);
+            }
+        }
+    }
+
+    fn resumable(&self) -> bool {
+        !self.finished && !self.has_function_call && !self.text.is_empty()
+    }
+}
+
+/// 在原请求末尾追加已生成的 model 前缀与续写指令
+pub fn build_continuation_body(body: &Value, partial_text: &str) -> Option<Value> {
+    let mut next = body.clone();
+    let contents = next.get_mut("request")?.get_mut("contents")?.as_array_mut()?;
+    contents.push(json!({ "role": "model", "parts": [{ "text": partial_text }] }));
+    contents.push(json!({ "role": "user", "parts": [{ "text": CONTINUE_PROMPT }] }));
+    Some(next)
+}
+
+/// 包装上游流：中途断开 (传输错误或未收到 finishReason 即结束) 时最多续传 `max_resumes` 次。
+/// 只向下游转发完整的 SSE 行，避免断点处的半行数据与续写流拼接出错。
+pub fn resumable_stream(
+    initial: GeminiByteStream,
+    upstream: Arc<UpstreamClient>,
+    access_token: String,
+    body: Value,
+    max_resumes: u32,
+    trace_id: String,
+) -> GeminiByteStream {
+    if max_resumes == 0 {
+        return initial;
+    }
+    Box::pin(async_stream::stream! {
+        let mut current = initial;
+        let mut progress = StreamProgress::default();
+        let mut buffer = BytesMut::new();
+        let mut resumes = 0u32;
+
+        loop {
+            let mut failure: Option<reqwest::Error> = None;
+            while let Some(item) = current.next().await {
+                match item {
+                    Ok(chunk) => {
+                        buffer.extend_from_slice(&chunk);
+                        if let Some(pos) = buffer.iter().rposition(|b| *b == b'\n') {
+                            let complete = buffer.split_to(pos + 1).freeze();
+                            for line in String::from_utf8_lossy(&complete).lines() {
+                                progress.observe_line(line);
+                            }
+                            yield Ok(complete);
+                        }
+                    }
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
+                    }
+                }
+            }
+
+            // 丢弃断点处不完整的半行；正常结束时原样转发剩余数据
+            let dropped = failure.is_some() || !progress.finished;
+            if !dropped || !progress.resumable() || resumes >= max_resumes {
+                if !buffer.is_empty() {
+                    let rest = buffer.split().freeze();
+                    for line in String::from_utf8_lossy(&rest).lines() {
+                        progress.observe_line(line);
+                    }
+                    yield Ok(rest);
+                }
+                if let Some(e) = failure {
+                    yield Err(e);
+                }
+                break;
+            }
+            buffer.clear();
+            resumes += 1;
+
+            let Some(next_body) = build_continuation_body(&body, &progress.text) else {
+                if let Some(e) = failure {
+                    yield Err(e);
+                }
+                break;
+            };
+            tracing::warn!(
+                "[{}] 上游流在生成中途断开 (已收到 {} 字符)，第 {}/{} 次续传",
+                trace_id,
+                progress.text.len(),
+                resumes,
+                max_resumes
+            );
+
+            match upstream
+                .call_v1_internal("streamGenerateContent", &access_token, &next_body, Some("alt=sse"))
+                .await
+            {
+                Ok(resp) if resp.status().is_success() => {
+                    current = Box::pin(resp.bytes_stream());
+                }
+                Ok(resp) => {
+                    tracing::warn!("[{}] 续传请求失败: HTTP {}", trace_id, resp.status());
+                    if let Some(e) = failure {
+                        yield Err(e);
+                    }
+                    break;
+                }
+                Err(e) => {
+                    tracing::warn!("[{}] 续传请求失败: {}", trace_id, e);
+                    if let Some(e) = failure {
+                        yield Err(e);
+                    }
+                    break;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_tracks_text_and_finish() {
+        let mut progress = StreamProgress::default();
+        progress.observe_line(r#"data: {"response":{"candidates":[{"content":{"parts":[{"text":"think","thought":true}]}}]}}"#);
+        progress.observe_line(r#"data: {"response":{"candidates":[{"content":{"parts":[{"text":"Hello, "}]}}]}}"#);
+        progress.observe_line(r#"data: {"response":{"candidates":[{"content":{"parts":[{"text":"world"}]}}]}}"#);
+        assert_eq!(progress.text, "Hello, world");
+        assert!(progress.resumable());
+
+        progress.observe_line(r#"data: {"response":{"candidates":[{"content":{"parts":[]},"finishReason":"STOP"}]}}"#);
+        assert!(!progress.resumable());
+    }
+
+    #[test]
+    fn test_function_call_is_not_resumable() {
+        let mut progress = StreamProgress::default();
+        progress.observe_line(r#"data: {"candidates":[{"content":{"parts":[{"text":"Let me check"},{"functionCall":{"name":"ls","args":{}}}]}}]}"#);
+        assert!(!progress.resumable());
+    }
+
+    #[test]
+    fn test_build_continuation_body() {
+        let body = json!({
+            "model": "gemini-2.5-pro",
+            "request": { "contents": [{ "role": "user", "parts": [{ "text": "write an essay" }] }] }
+        });
+        let next = build_continuation_body(&body, "Once upon").unwrap();
+        let contents = next["request"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["text"], "Once upon");
+        assert_eq!(contents[2]["role"], "user");
+        assert!(build_continuation_body(&json!({}), "x").is_none());
+    }
+}
@@ -0,0 +1,82 @@
+// Gemini SSE 分块改写
+// 按完整行解析上游 `data:` 分块并交给改写器就地修改，供预填充去重、本地停止序列等
+// 与协议无关的后处理复用 (位于各协议 mapper 之前)。
+
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use serde_json::Value;
+
+use super::resume::GeminiByteStream;
+
+pub trait SseRewriter: Send + 'static {
+    /// 改写单个 data 分块；返回 false 时在该分块之后结束流
+    fn rewrite(&mut self, event: &mut Value) -> bool;
+
+    /// 上游正常结束时追加的分块 (如暂存未放行的文本)
+    fn finish(&mut self) -> Option<Value> {
+        None
+    }
+}
+
+/// 逐行改写 SSE 流；只解析完整行，保证断点处的半行不会被误解析
+pub fn rewrite_sse_stream<R: SseRewriter>(stream: GeminiByteStream, rewriter: R) -> GeminiByteStream {
+    Box::pin(async_stream::stream! {
+        let mut stream = stream;
+        let mut rewriter = rewriter;
+        let mut buffer = BytesMut::new();
+        while let Some(item) = stream.next().await {
+            match item {
+                Ok(chunk) => {
+                    buffer.extend_from_slice(&chunk);
+                    let Some(pos) = buffer.iter().rposition(|b| *b == b'\n') else {
+                        continue;
+                    };
+                    let complete = buffer.split_to(pos + 1).freeze();
+                    let mut out = String::with_capacity(complete.len());
+                    let mut keep_going = true;
+                    for line in String::from_utf8_lossy(&complete).split_inclusive('\n') {
+                        let parsed = line
+                            .trim()
+                            .strip_prefix("data:")
+                            .and_then(|data| serde_json::from_str::<Value>(data.trim()).ok());
+                        match parsed {
+                            Some(mut event) => {
+                                keep_going = rewriter.rewrite(&mut event);
+                                out.push_str(&format!("data: {}\n", event));
+                                if !keep_going {
+                                    out.push('\n');
+                                    break;
+                                }
+                            }
+                            None => out.push_str(line),
+                        }
+                    }
+                    yield Ok(Bytes::from(out));
+                    if !keep_going {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            }
+        }
+        if !buffer.is_empty() {
+            yield Ok(buffer.freeze());
+        }
+        if let Some(event) = rewriter.finish() {
+            yield Ok(Bytes::from(format!("data: {}\n\n", event)));
+        }
+    })
+}
+
+/// 取出分块中的首个候选 (兼容 v1internal 的 `response` 包装)
+pub fn candidate_mut(event: &mut Value) -> Option<&mut Value> {
+    let inner = if event.get("response").is_some() {
+        &mut event["response"]
+    } else {
+        event
+    };
+    inner.get_mut("candidates")?.get_mut(0)
+}
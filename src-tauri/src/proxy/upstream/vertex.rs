@@ -0,0 +1,228 @@
+// Vertex AI 服务账号上游
+// 使用服务账号 JSON 密钥签发 JWT (RS256) 换取 access token，直接调用 Vertex generateContent 端点，
+// 让有 GCP 额度的用户在同一个反代下与 Antigravity 账号混用。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tokio::sync::Mutex;
+
+use crate::proxy::common::http::build_client;
+use crate::proxy::common::model_mapping::{lookup_custom_mapping, wildcard_match};
+use crate::proxy::config::{UpstreamProxyConfig, VertexConfig};
+
+const TOKEN_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+/// access token 有效期 1 小时，提前 5 分钟刷新
+const TOKEN_LIFETIME_SECS: i64 = 3600;
+const TOKEN_REFRESH_MARGIN_SECS: i64 = 300;
+
+/// 映射目标前缀
+pub const ROUTE_PREFIX: &str = "vertex:";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceAccountKey {
+    pub client_email: String,
+    pub private_key: String,
+    #[serde(default)]
+    pub project_id: String,
+    #[serde(default)]
+    pub token_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+struct JwtClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<i64>,
+}
+
+struct CachedToken {
+    access_token: String,
+    expires_at: i64,
+}
+
+/// 按 client_email 缓存 access token
+fn token_cache() -> &'static Mutex<HashMap<String, CachedToken>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, CachedToken>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn load_key(path: &str) -> Result<ServiceAccountKey, String> {
+    let content = std::fs::read_to_string(path.trim())
+        .map_err(|e| format!("无法读取服务账号密钥 {}: {}", path, e))?;
+    let key: ServiceAccountKey =
+        serde_json::from_str(&content).map_err(|e| format!("服务账号密钥格式无效: {}", e))?;
+    if key.client_email.is_empty() || key.private_key.is_empty() {
+        return Err("服务账号密钥缺少 client_email / private_key".to_string());
+    }
+    Ok(key)
+}
+
+/// 模型是否由 Vertex 服务，返回 Vertex 侧模型名
+pub fn resolve_model(config: &VertexConfig, original_model: &str, custom_mapping: &HashMap<String, String>) -> Option<String> {
+    if !config.enabled {
+        return None;
+    }
+    let target = lookup_custom_mapping(original_model, custom_mapping).unwrap_or_else(|| original_model.to_string());
+    if let Some(model) = target.strip_prefix(ROUTE_PREFIX) {
+        return Some(model.to_string());
+    }
+    config
+        .models
+        .iter()
+        .any(|p| wildcard_match(p, &target))
+        .then_some(target)
+}
+
+pub fn endpoint_url(location: &str, project: &str, model: &str, method: &str) -> String {
+    let location = if location.trim().is_empty() { "us-central1" } else { location.trim() };
+    let host = if location == "global" {
+        "aiplatform.googleapis.com".to_string()
+    } else {
+        format!("{}-aiplatform.googleapis.com", location)
+    };
+    format!(
+        "https://{}/v1/projects/{}/locations/{}/publishers/google/models/{}:{}",
+        host, project, location, model, method
+    )
+}
+
+fn sign_assertion(key: &ServiceAccountKey, token_uri: &str, now: i64) -> Result<String, String> {
+    let claims = JwtClaims {
+        iss: &key.client_email,
+        scope: TOKEN_SCOPE,
+        aud: token_uri,
+        iat: now,
+        exp: now + TOKEN_LIFETIME_SECS,
+    };
+    let encoding_key = jsonwebtoken::EncodingKey::from_rsa_pem(key.private_key.as_bytes())
+        .map_err(|e| format!("服务账号私钥无效: {}", e))?;
+    jsonwebtoken::encode(&jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256), &claims, &encoding_key)
+        .map_err(|e| format!("JWT 签名失败: {}", e))
+}
+
+async fn access_token(client: &reqwest::Client, key: &ServiceAccountKey) -> Result<String, String> {
+    let now = chrono::Utc::now().timestamp();
+    let mut cache = token_cache().lock().await;
+    if let Some(cached) = cache.get(&key.client_email) {
+        if cached.expires_at - TOKEN_REFRESH_MARGIN_SECS > now {
+            return Ok(cached.access_token.clone());
+        }
+    }
+
+    let token_uri = if key.token_uri.is_empty() { DEFAULT_TOKEN_URI } else { key.token_uri.as_str() };
+    let assertion = sign_assertion(key, token_uri, now)?;
+    let resp = client
+        .post(token_uri)
+        .form(&[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", assertion.as_str()),
+        ])
+        .send()
+        .await
+        .map_err(|e| format!("Vertex token 请求失败: {}", e))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("Vertex token 交换失败 (HTTP {}): {}", status, text));
+    }
+    let token: TokenResponse = resp.json().await.map_err(|e| format!("Vertex token 响应解析失败: {}", e))?;
+    cache.insert(
+        key.client_email.clone(),
+        CachedToken {
+            access_token: token.access_token.clone(),
+            expires_at: now + token.expires_in.unwrap_or(TOKEN_LIFETIME_SECS),
+        },
+    );
+    tracing::info!("[Vertex] 已获取 {} 的 access token", key.client_email);
+    Ok(token.access_token)
+}
+
+/// v1internal 专用字段 Vertex 不接受，发送前移除
+fn strip_internal_fields(request: &mut Value) {
+    if let Some(obj) = request.as_object_mut() {
+        obj.remove("sessionId");
+        obj.remove("model");
+    }
+}
+
+/// 调用 Vertex generateContent / streamGenerateContent；request 为标准 Gemini 请求体 (非 v1internal 包装)
+pub async fn call_generate(
+    config: &VertexConfig,
+    upstream_proxy: &UpstreamProxyConfig,
+    timeout_secs: u64,
+    model: &str,
+    stream: bool,
+    mut request: Value,
+) -> Result<reqwest::Response, String> {
+    let key = load_key(&config.credentials_path)?;
+    let project = if config.project_id.trim().is_empty() { key.project_id.clone() } else { config.project_id.trim().to_string() };
+    if project.is_empty() {
+        return Err("未配置 Vertex 项目 ID (密钥中也没有 project_id)".to_string());
+    }
+
+    let client = build_client(upstream_proxy, timeout_secs)?;
+    let token = access_token(&client, &key).await?;
+    let method = if stream { "streamGenerateContent" } else { "generateContent" };
+    let mut url = endpoint_url(&config.location, &project, model, method);
+    if stream {
+        url.push_str("?alt=sse");
+    }
+    strip_internal_fields(&mut request);
+
+    tracing::debug!("[Vertex] {} -> {}", model, url);
+    client
+        .post(&url)
+        .bearer_auth(token)
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| format!("Vertex 请求失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_model_and_endpoint() {
+        let mut config = VertexConfig {
+            enabled: true,
+            models: vec!["gemini-2.5-pro*".to_string()],
+            ..Default::default()
+        };
+        let mut mapping = HashMap::new();
+        mapping.insert("big".to_string(), "vertex:gemini-2.5-pro".to_string());
+        mapping.insert("gpt-4o".to_string(), "gemini-3-flash".to_string());
+
+        assert_eq!(resolve_model(&config, "big", &mapping).as_deref(), Some("gemini-2.5-pro"));
+        assert_eq!(resolve_model(&config, "gemini-2.5-pro-002", &mapping).as_deref(), Some("gemini-2.5-pro-002"));
+        assert_eq!(resolve_model(&config, "gpt-4o", &mapping), None);
+        config.enabled = false;
+        assert_eq!(resolve_model(&config, "big", &mapping), None);
+
+        assert_eq!(
+            endpoint_url("europe-west4", "my-proj", "gemini-2.5-pro", "streamGenerateContent"),
+            "https://europe-west4-aiplatform.googleapis.com/v1/projects/my-proj/locations/europe-west4/publishers/google/models/gemini-2.5-pro:streamGenerateContent"
+        );
+        assert!(endpoint_url("global", "p", "m", "generateContent").starts_with("https://aiplatform.googleapis.com/v1/projects/p/locations/global/"));
+    }
+
+    #[test]
+    fn test_strip_internal_fields() {
+        let mut request = serde_json::json!({ "contents": [], "sessionId": "abc", "model": "x" });
+        strip_internal_fields(&mut request);
+        assert_eq!(request, serde_json::json!({ "contents": [] }));
+    }
+}
@@ -0,0 +1,140 @@
+// 账号每日 token 用量账本
+// 供公平调度模式 (Fairness) 使用：按当日 (UTC) 累计 token 而非请求数均衡各账号负载，
+// 避免某个账号承接全部长上下文请求。
+// - 启动时从 token_stats 数据库补齐当日已有用量
+// - 监控开启时记录响应中的实际 usage
+// - 监控关闭时 (无实际 usage) 按请求体大小估算输入 token 在分配账号时计入，估算值记入状态日志
+
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// 与 token_budget 估算保持一致: 约 3.5 字节 / token
+const BYTES_PER_TOKEN: f64 = 3.5;
+
+pub struct DailyUsageLedger {
+    /// 当前统计日 (UTC, "YYYY-MM-DD")
+    day: Mutex<String>,
+    usage: DashMap<String, u64>,
+    /// 是否有实际 usage 来源 (监控已开启)
+    actual_source: AtomicBool,
+}
+
+fn today() -> String {
+    chrono::Utc::now().format("%Y-%m-%d").to_string()
+}
+
+impl DailyUsageLedger {
+    fn new() -> Self {
+        Self {
+            day: Mutex::new(today()),
+            usage: DashMap::new(),
+            actual_source: AtomicBool::new(false),
+        }
+    }
+
+    /// Global singleton instance (首次使用时从 token_stats 补齐当日用量)
+    pub fn global() -> Arc<Self> {
+        static INSTANCE: OnceLock<Arc<DailyUsageLedger>> = OnceLock::new();
+        INSTANCE
+            .get_or_init(|| {
+                let ledger = DailyUsageLedger::new();
+                match crate::modules::token_stats::get_today_account_stats() {
+                    Ok(stats) => {
+                        for s in stats {
+                            ledger.usage.insert(s.account_email, s.total_tokens);
+                        }
+                    }
+                    Err(e) => tracing::debug!("[Fairness] 读取当日 token 用量失败: {}", e),
+                }
+                Arc::new(ledger)
+            })
+            .clone()
+    }
+
+    /// 跨日时清空账本
+    fn roll_over(&self) {
+        let now = today();
+        if let Ok(mut day) = self.day.lock() {
+            if *day != now {
+                *day = now;
+                self.usage.clear();
+            }
+        }
+    }
+
+    pub fn set_actual_source(&self, enabled: bool) {
+        self.actual_source.store(enabled, Ordering::Relaxed);
+    }
+
+    /// 记录响应中的实际 token 用量
+    pub fn record_actual(&self, account: &str, tokens: u64) {
+        self.roll_over();
+        *self.usage.entry(account.to_string()).or_insert(0) += tokens;
+    }
+
+    /// 分配账号时按请求体大小估算计入 (已有实际 usage 来源时跳过，避免重复计数)
+    pub fn charge_estimate(&self, account: &str, request_bytes: Option<u64>) {
+        if self.actual_source.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some(bytes) = request_bytes else {
+            return;
+        };
+        self.roll_over();
+        let tokens = (bytes as f64 / BYTES_PER_TOKEN).ceil() as u64;
+        *self.usage.entry(account.to_string()).or_insert(0) += tokens;
+        // 估算值不在 token_stats 中，写入状态日志以便崩溃后恢复
+        crate::proxy::state_journal::StateJournal::global().append(
+            crate::proxy::state_journal::JournalRecord::UsageEstimate {
+                day: today(),
+                account: account.to_string(),
+                tokens,
+            },
+        );
+    }
+
+    /// 从状态日志恢复当日的估算用量
+    pub fn restore_estimates(&self, day: &str, estimates: &std::collections::HashMap<String, u64>) {
+        self.roll_over();
+        if day != today() {
+            return;
+        }
+        for (account, tokens) in estimates {
+            *self.usage.entry(account.clone()).or_insert(0) += tokens;
+        }
+    }
+
+    pub fn tokens_today(&self, account: &str) -> u64 {
+        self.roll_over();
+        self.usage.get(account).map(|v| *v).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_skipped_when_actual_usage_available() {
+        let ledger = DailyUsageLedger::new();
+        ledger.charge_estimate("a@example.com", Some(3500));
+        assert_eq!(ledger.tokens_today("a@example.com"), 1000);
+        ledger.charge_estimate("a@example.com", None);
+        assert_eq!(ledger.tokens_today("a@example.com"), 1000);
+
+        ledger.set_actual_source(true);
+        ledger.charge_estimate("a@example.com", Some(3500));
+        ledger.record_actual("a@example.com", 250);
+        assert_eq!(ledger.tokens_today("a@example.com"), 1250);
+        assert_eq!(ledger.tokens_today("b@example.com"), 0);
+    }
+
+    #[test]
+    fn test_roll_over_clears_previous_day() {
+        let ledger = DailyUsageLedger::new();
+        ledger.record_actual("a@example.com", 500);
+        *ledger.day.lock().unwrap() = "2000-01-01".to_string();
+        assert_eq!(ledger.tokens_today("a@example.com"), 0);
+    }
+}
@@ -1,5 +1,7 @@
+use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
 #[derive(Debug, Clone, Default)]
@@ -11,6 +13,82 @@ pub struct ZaiVisionMcpState {
 struct ZaiVisionSession {
     #[allow(dead_code)]
     created_at: std::time::Instant,
+    tool_loop: ToolLoopGuard,
+}
+
+/// 工具循环保护的上限 (0 = 不限制)
+#[derive(Debug, Clone, Copy)]
+pub struct ToolLoopLimits {
+    pub max_calls: u32,
+    pub max_total: Duration,
+    pub max_repeats: u32,
+}
+
+impl ToolLoopLimits {
+    pub fn from_config(mcp: &crate::proxy::config::ZaiMcpConfig) -> Self {
+        Self {
+            max_calls: mcp.max_tool_calls,
+            max_total: Duration::from_secs(mcp.tool_loop_timeout_secs),
+            max_repeats: mcp.max_repeated_calls,
+        }
+    }
+}
+
+/// 会话内的工具调用统计；一旦触发中止，后续调用均直接返回中止结果
+#[derive(Debug, Clone, Default)]
+struct ToolLoopGuard {
+    calls: u32,
+    elapsed: Duration,
+    last_signature: Option<String>,
+    repeats: u32,
+    aborted: Option<String>,
+}
+
+impl ToolLoopGuard {
+    /// 登记一次调用，返回本次允许的剩余耗时 (None = 不限制)；超限时返回中止原因
+    fn begin(&mut self, tool_name: &str, arguments: &Value, limits: &ToolLoopLimits) -> Result<Option<Duration>, String> {
+        if let Some(reason) = &self.aborted {
+            return Err(reason.clone());
+        }
+        let signature = format!("{}:{}", tool_name, arguments);
+        if self.last_signature.as_deref() == Some(signature.as_str()) {
+            self.repeats += 1;
+        } else {
+            self.last_signature = Some(signature);
+            self.repeats = 1;
+        }
+
+        let reason = if limits.max_calls > 0 && self.calls >= limits.max_calls {
+            Some(format!("max_tool_calls: reached the limit of {} tool calls", limits.max_calls))
+        } else if limits.max_repeats > 0 && self.repeats > limits.max_repeats {
+            Some(format!(
+                "repeated_call: '{}' was called {} times in a row with identical arguments",
+                tool_name, self.repeats
+            ))
+        } else if !limits.max_total.is_zero() && self.elapsed >= limits.max_total {
+            Some(format!("timeout: cumulative tool time exceeded {}s", limits.max_total.as_secs()))
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            self.aborted = Some(reason.clone());
+            return Err(reason);
+        }
+        self.calls += 1;
+        Ok((!limits.max_total.is_zero()).then(|| limits.max_total - self.elapsed))
+    }
+}
+
+/// 结构化的 "loop aborted" 工具结果 (MCP tools/call result)
+pub fn loop_aborted_result(reason: &str) -> Value {
+    json!({
+        "content": [{
+            "type": "text",
+            "text": format!("Tool loop aborted: {}. Stop calling tools and answer with the information gathered so far.", reason)
+        }],
+        "structuredContent": { "status": "loop_aborted", "reason": reason },
+        "isError": true
+    })
 }
 
 impl ZaiVisionMcpState {
@@ -25,6 +103,7 @@ impl ZaiVisionMcpState {
             session_id.clone(),
             ZaiVisionSession {
                 created_at: std::time::Instant::now(),
+                tool_loop: ToolLoopGuard::default(),
             },
         );
         session_id
@@ -39,5 +118,74 @@ impl ZaiVisionMcpState {
         let mut sessions = self.sessions.lock().await;
         sessions.remove(session_id);
     }
+
+    /// 工具调用前检查循环保护，返回本次调用可用的剩余耗时
+    pub async fn begin_tool_call(
+        &self,
+        session_id: &str,
+        tool_name: &str,
+        arguments: &Value,
+        limits: &ToolLoopLimits,
+    ) -> Result<Option<Duration>, String> {
+        let mut sessions = self.sessions.lock().await;
+        match sessions.get_mut(session_id) {
+            Some(session) => session.tool_loop.begin(tool_name, arguments, limits),
+            None => Ok(None),
+        }
+    }
+
+    /// 累计工具执行耗时
+    pub async fn finish_tool_call(&self, session_id: &str, elapsed: Duration) {
+        let mut sessions = self.sessions.lock().await;
+        if let Some(session) = sessions.get_mut(session_id) {
+            session.tool_loop.elapsed += elapsed;
+        }
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max_calls: u32, max_repeats: u32, max_total_secs: u64) -> ToolLoopLimits {
+        ToolLoopLimits {
+            max_calls,
+            max_total: Duration::from_secs(max_total_secs),
+            max_repeats,
+        }
+    }
+
+    #[test]
+    fn test_repeated_identical_calls_abort_loop() {
+        let mut guard = ToolLoopGuard::default();
+        let l = limits(0, 2, 0);
+        let args = json!({ "query": "rust" });
+        assert!(guard.begin("webSearchPrime", &args, &l).is_ok());
+        assert!(guard.begin("webSearchPrime", &args, &l).is_ok());
+        let err = guard.begin("webSearchPrime", &args, &l).unwrap_err();
+        assert!(err.starts_with("repeated_call"));
+        // 中止后即便参数不同也不再执行
+        assert!(guard.begin("webReader", &json!({ "url": "https://a" }), &l).is_err());
+    }
+
+    #[test]
+    fn test_max_calls_and_cumulative_timeout() {
+        let mut guard = ToolLoopGuard::default();
+        let l = limits(2, 0, 0);
+        assert!(guard.begin("a", &json!(1), &l).is_ok());
+        assert!(guard.begin("a", &json!(2), &l).is_ok());
+        assert!(guard.begin("a", &json!(3), &l).unwrap_err().starts_with("max_tool_calls"));
+
+        let mut guard = ToolLoopGuard::default();
+        let l = limits(0, 0, 10);
+        assert_eq!(guard.begin("a", &json!(1), &l), Ok(Some(Duration::from_secs(10))));
+        guard.elapsed = Duration::from_secs(7);
+        assert_eq!(guard.begin("a", &json!(2), &l), Ok(Some(Duration::from_secs(3))));
+        guard.elapsed = Duration::from_secs(10);
+        assert!(guard.begin("a", &json!(3), &l).unwrap_err().starts_with("timeout"));
+
+        let result = loop_aborted_result("timeout: cumulative tool time exceeded 10s");
+        assert_eq!(result["structuredContent"]["status"], "loop_aborted");
+        assert_eq!(result["isError"], true);
+    }
+}
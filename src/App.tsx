@@ -87,6 +87,13 @@ function App() {
       })
     );
 
+    // 监听托盘暂停/恢复账号事件
+    unlistenPromises.push(
+      listen('tray://account-paused', () => {
+        fetchAccounts();
+      })
+    );
+
     // Cleanup
     return () => {
       Promise.all(unlistenPromises).then(unlisteners => {
@@ -120,7 +127,12 @@ function App() {
 
     // Delay check to avoid blocking initial render
     const timer = setTimeout(checkUpdates, 2000);
-    return () => clearTimeout(timer);
+    // 后台定期检查 (长时间运行时) 发现新版本
+    const unlistenAvailable = listen('update://available', () => setShowUpdateNotification(true));
+    return () => {
+      clearTimeout(timer);
+      unlistenAvailable.then(unlisten => unlisten());
+    };
   }, []);
 
   return (
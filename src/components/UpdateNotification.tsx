@@ -1,9 +1,8 @@
 import React, { useEffect, useState } from 'react';
-import { X, Download, Sparkles, ArrowRight, Loader2, CheckCircle } from 'lucide-react';
+import { X, Download, Sparkles, ArrowRight, Loader2, CheckCircle, Clock } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { useTranslation } from 'react-i18next';
-import { check } from '@tauri-apps/plugin-updater';
-import { relaunch } from '@tauri-apps/plugin-process';
 
 interface UpdateInfo {
   has_update: boolean;
@@ -12,7 +11,12 @@ interface UpdateInfo {
   download_url: string;
 }
 
-type UpdateState = 'checking' | 'available' | 'downloading' | 'ready' | 'none';
+type UpdateState = 'checking' | 'available' | 'downloading' | 'ready' | 'deferred' | 'none';
+
+interface DownloadProgress {
+  downloaded: number;
+  total: number | null;
+}
 
 interface UpdateNotificationProps {
   onClose: () => void;
@@ -46,42 +50,46 @@ export const UpdateNotification: React.FC<UpdateNotificationProps> = ({ onClose
     }
   };
 
-  const handleAutoUpdate = async () => {
+  // 后端按所选通道下载并校验签名，安装可立即进行或推迟到退出时
+  const downloadUpdate = async () => {
     setUpdateState('downloading');
-    try {
-      const update = await check();
-      if (update) {
-        let downloaded = 0;
-        let contentLength = 0;
-        
-        await update.downloadAndInstall((event) => {
-          switch (event.event) {
-            case 'Started':
-              contentLength = event.data.contentLength || 0;
-              break;
-            case 'Progress':
-              downloaded += event.data.chunkLength;
-              if (contentLength > 0) {
-                setDownloadProgress(Math.round((downloaded / contentLength) * 100));
-              }
-              break;
-            case 'Finished':
-              setUpdateState('ready');
-              break;
-          }
-        });
-
-        setUpdateState('ready');
-        setTimeout(async () => {
-          await relaunch();
-        }, 1500);
+    const unlisten = await listen<DownloadProgress>('update://progress', (event) => {
+      const { downloaded, total } = event.payload;
+      if (total) {
+        setDownloadProgress(Math.round((downloaded / total) * 100));
       }
+    });
+    try {
+      await invoke('download_update');
+    } finally {
+      unlisten();
+    }
+  };
+
+  const handleAutoUpdate = async () => {
+    try {
+      await downloadUpdate();
+      setUpdateState('ready');
+      setTimeout(async () => {
+        await invoke('install_pending_update', { restart: true });
+      }, 1500);
     } catch (error) {
       console.error('Auto update failed:', error);
       handleManualDownload();
     }
   };
 
+  const handleInstallLater = async () => {
+    try {
+      await downloadUpdate();
+      setUpdateState('deferred');
+      setTimeout(handleClose, 2500);
+    } catch (error) {
+      console.error('Deferred update download failed:', error);
+      handleManualDownload();
+    }
+  };
+
   const handleManualDownload = () => {
     if (updateInfo?.download_url) {
       window.open(updateInfo.download_url, '_blank');
@@ -164,6 +172,7 @@ export const UpdateNotification: React.FC<UpdateNotificationProps> = ({ onClose
             <p className="text-sm text-gray-600 dark:text-gray-300 leading-relaxed">
               {updateState === 'downloading' && t('update_notification.downloading', '正在下载更新...')}
               {updateState === 'ready' && t('update_notification.restarting', '即将重启应用...')}
+              {updateState === 'deferred' && t('update_notification.deferred', '更新已下载，将在退出时安装')}
               {updateState === 'available' && updateInfo && t('update_notification.message', { current: updateInfo.current_version })}
             </p>
           </div>
@@ -201,6 +210,19 @@ export const UpdateNotification: React.FC<UpdateNotificationProps> = ({ onClose
                 <ArrowRight className="w-4 h-4 opacity-0 -translate-x-2 group-hover/btn:opacity-100 group-hover/btn:translate-x-0 transition-all duration-300" />
                 <div className="absolute inset-0 -translate-x-full group-hover/btn:animate-[shimmer_1.5s_infinite] bg-gradient-to-r from-transparent via-white/20 to-transparent z-20 pointer-events-none" />
               </button>
+              <button
+                onClick={handleInstallLater}
+                title={t('update_notification.install_later', '退出时安装')}
+                className="
+                  px-3 rounded-xl
+                  border border-gray-200 dark:border-white/10
+                  text-gray-600 dark:text-gray-300
+                  hover:bg-black/5 dark:hover:bg-white/10
+                  transition-all duration-200
+                "
+              >
+                <Clock className="w-4 h-4" />
+              </button>
             </div>
           )}
 
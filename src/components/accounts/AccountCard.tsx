@@ -1,6 +1,6 @@
-import { ArrowRightLeft, RefreshCw, Trash2, Download, Info, Lock, Ban, Diamond, Gem, Circle, Clock, ToggleLeft, ToggleRight, Fingerprint, Sparkles } from 'lucide-react';
-import { Account } from '../../types/account';
-import { getQuotaColor, formatTimeRemaining, getTimeRemainingColor } from '../../utils/format';
+import { ArrowRightLeft, RefreshCw, Trash2, Download, Info, Lock, Ban, Diamond, Gem, Circle, Clock, ToggleLeft, ToggleRight, Fingerprint, Sparkles, KeyRound, Pause, Play } from 'lucide-react';
+import { Account, isAccountPaused } from '../../types/account';
+import { getQuotaColor, formatTimeRemaining, getTimeRemainingColor, formatDate } from '../../utils/format';
 import { cn } from '../../utils/cn';
 import { useTranslation } from 'react-i18next';
 
@@ -18,17 +18,23 @@ interface AccountCardProps {
     onExport: () => void;
     onDelete: () => void;
     onToggleProxy: () => void;
+    onTogglePause?: () => void;
     onWarmup?: () => void;
 }
 
 
-function AccountCard({ account, selected, onSelect, isCurrent, isRefreshing, isSwitching = false, onSwitch, onRefresh, onViewDetails, onExport, onDelete, onToggleProxy, onViewDevice, onWarmup }: AccountCardProps) {
+function AccountCard({ account, selected, onSelect, isCurrent, isRefreshing, isSwitching = false, onSwitch, onRefresh, onViewDetails, onExport, onDelete, onToggleProxy, onTogglePause, onViewDevice, onWarmup }: AccountCardProps) {
     const { t } = useTranslation();
     const geminiProModel = account.quota?.models.find(m => m.name === 'gemini-3-pro-high');
     const geminiFlashModel = account.quota?.models.find(m => m.name === 'gemini-3-flash');
     const geminiImageModel = account.quota?.models.find(m => m.name === 'gemini-3-pro-image');
     const claudeModel = account.quota?.models.find(m => m.name === 'claude-sonnet-4-5-thinking');
     const isDisabled = Boolean(account.disabled);
+    const isPaused = isAccountPaused(account);
+    const pausedTitle = [
+        account.paused_reason,
+        account.paused_until ? t('accounts.paused_until', { time: formatDate(account.paused_until) }) : t('accounts.paused_indefinitely'),
+    ].filter(Boolean).join(' · ');
 
     const getColorClass = (percentage: number) => {
         const color = getQuotaColor(percentage);
@@ -90,6 +96,15 @@ function AccountCard({ account, selected, onSelect, isCurrent, isRefreshing, isS
                                     {t('accounts.disabled').toUpperCase()}
                                 </span>
                             )}
+                            {isPaused && (
+                                <span
+                                    className="px-1.5 py-0.5 rounded-md bg-amber-100 dark:bg-amber-900/40 text-amber-700 dark:text-amber-300 text-[9px] font-bold flex items-center gap-1 shadow-sm border border-amber-200/50"
+                                    title={pausedTitle}
+                                >
+                                    <Pause className="w-2.5 h-2.5" />
+                                    {t('accounts.paused').toUpperCase()}
+                                </span>
+                            )}
                             {account.quota?.is_forbidden && (
                                 <span className="px-1.5 py-0.5 rounded-md bg-red-100 dark:bg-red-900/40 text-red-600 dark:text-red-400 text-[9px] font-bold flex items-center gap-1 shadow-sm border border-red-200/50" title={t('accounts.forbidden_tooltip')}>
                                     <Lock className="w-2.5 h-2.5" />
@@ -99,7 +114,14 @@ function AccountCard({ account, selected, onSelect, isCurrent, isRefreshing, isS
                             {/* 订阅类型徽章 */}
                             {account.quota?.subscription_tier && (() => {
                                 const tier = account.quota.subscription_tier.toLowerCase();
-                                if (tier.includes('ultra')) {
+                                if (tier === 'api_key') {
+                                    return (
+                                        <span className="flex items-center gap-1 px-1.5 py-0.5 rounded-md bg-amber-100 dark:bg-amber-900/30 text-amber-700 dark:text-amber-400 text-[9px] font-bold shadow-sm border border-amber-200 dark:border-amber-800/40">
+                                            <KeyRound className="w-2.5 h-2.5" />
+                                            API KEY
+                                        </span>
+                                    );
+                                } else if (tier.includes('ultra')) {
                                     return (
                                         <span className="flex items-center gap-1 px-1.5 py-0.5 rounded-md bg-gradient-to-r from-purple-600 to-pink-600 text-white text-[9px] font-bold shadow-sm">
                                             <Gem className="w-2.5 h-2.5 fill-current" />
@@ -329,6 +351,19 @@ function AccountCard({ account, selected, onSelect, isCurrent, isRefreshing, isS
                             <ToggleLeft className="w-3.5 h-3.5" />
                         )}
                     </button>
+                    {onTogglePause && (
+                        <button
+                            className={cn(
+                                "p-1.5 rounded-lg transition-all text-gray-400",
+                                isPaused ? "hover:text-green-600 hover:bg-green-50" : "hover:text-amber-600 hover:bg-amber-50"
+                            )}
+                            onClick={(e) => { e.stopPropagation(); onTogglePause(); }}
+                            title={isPaused ? t('accounts.resume') : t('accounts.pause_one_hour')}
+                            disabled={isDisabled}
+                        >
+                            {isPaused ? <Play className="w-3.5 h-3.5" /> : <Pause className="w-3.5 h-3.5" />}
+                        </button>
+                    )}
                     <button
                         className="p-1.5 text-gray-400 hover:text-red-600 hover:bg-red-50 rounded-lg transition-all"
                         onClick={(e) => { e.stopPropagation(); onDelete(); }}
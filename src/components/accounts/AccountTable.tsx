@@ -39,11 +39,13 @@ import {
     ToggleLeft,
     ToggleRight,
     Sparkles,
+    Pause,
+    Play,
 } from 'lucide-react';
-import { Account } from '../../types/account';
+import { Account, isAccountPaused } from '../../types/account';
 import { useTranslation } from 'react-i18next';
 import { cn } from '../../utils/cn';
-import { getQuotaColor, formatTimeRemaining, getTimeRemainingColor } from '../../utils/format';
+import { getQuotaColor, formatTimeRemaining, getTimeRemainingColor, formatDate } from '../../utils/format';
 import { useConfigStore } from '../../stores/useConfigStore';
 
 // ============================================================================
@@ -65,6 +67,7 @@ interface AccountTableProps {
     onExport: (accountId: string) => void;
     onDelete: (accountId: string) => void;
     onToggleProxy: (accountId: string) => void;
+    onTogglePause?: (accountId: string) => void;
     onWarmup?: (accountId: string) => void;
     /** 拖拽排序回调，当用户完成拖拽时触发 */
     onReorder?: (accountIds: string[]) => void;
@@ -85,6 +88,7 @@ interface SortableRowProps {
     onExport: () => void;
     onDelete: () => void;
     onToggleProxy: () => void;
+    onTogglePause?: () => void;
     onWarmup?: () => void;
 }
 
@@ -100,6 +104,7 @@ interface AccountRowContentProps {
     onExport: () => void;
     onDelete: () => void;
     onToggleProxy: () => void;
+    onTogglePause?: () => void;
     onWarmup?: () => void;
 }
 
@@ -208,6 +213,7 @@ function SortableAccountRow({
     onExport,
     onDelete,
     onToggleProxy,
+    onTogglePause,
     onWarmup,
 }: SortableRowProps) {
     const { t } = useTranslation();
@@ -271,6 +277,7 @@ function SortableAccountRow({
                 onExport={onExport}
                 onDelete={onDelete}
                 onToggleProxy={onToggleProxy}
+                onTogglePause={onTogglePause}
                 onWarmup={onWarmup}
             />
         </tr>
@@ -293,6 +300,7 @@ function AccountRowContent({
     onExport,
     onDelete,
     onToggleProxy,
+    onTogglePause,
     onWarmup,
 }: AccountRowContentProps) {
     const { t } = useTranslation();
@@ -309,6 +317,11 @@ function AccountRowContent({
     // 获取要显示的模型列表
     const pinnedModels = config?.pinned_quota_models?.models || Object.keys(MODEL_CONFIG);
     const isDisabled = Boolean(account.disabled);
+    const isPaused = isAccountPaused(account);
+    const pausedTitle = [
+        account.paused_reason,
+        account.paused_until ? t('accounts.paused_until', { time: formatDate(account.paused_until) }) : t('accounts.paused_indefinitely'),
+    ].filter(Boolean).join(' · ');
 
     return (
         <>
@@ -349,6 +362,16 @@ function AccountRowContent({
                             </span>
                         )}
 
+                        {isPaused && (
+                            <span
+                                className="px-2 py-0.5 rounded-md bg-amber-100 dark:bg-amber-900/50 text-amber-700 dark:text-amber-300 text-[10px] font-bold flex items-center gap-1 shadow-sm border border-amber-200/50"
+                                title={pausedTitle}
+                            >
+                                <Pause className="w-2.5 h-2.5" />
+                                <span>{t('accounts.paused')}</span>
+                            </span>
+                        )}
+
                         {account.quota?.is_forbidden && (
                             <span className="px-2 py-0.5 rounded-md bg-red-100 dark:bg-red-900/50 text-red-600 dark:text-red-400 text-[10px] font-bold flex items-center gap-1 shadow-sm border border-red-200/50" title={t('accounts.forbidden_tooltip')}>
                                 <Lock className="w-2.5 h-2.5" />
@@ -526,6 +549,21 @@ function AccountRowContent({
                             <ToggleLeft className="w-3.5 h-3.5" />
                         )}
                     </button>
+                    {onTogglePause && (
+                        <button
+                            className={cn(
+                                "p-1.5 rounded-lg transition-all text-gray-500 dark:text-gray-400",
+                                isPaused
+                                    ? "hover:text-green-600 dark:hover:text-green-400 hover:bg-green-50 dark:hover:bg-green-900/30"
+                                    : "hover:text-amber-600 dark:hover:text-amber-400 hover:bg-amber-50 dark:hover:bg-amber-900/30"
+                            )}
+                            onClick={(e) => { e.stopPropagation(); onTogglePause(); }}
+                            title={isPaused ? t('accounts.resume') : t('accounts.pause_one_hour')}
+                            disabled={isDisabled}
+                        >
+                            {isPaused ? <Play className="w-3.5 h-3.5" /> : <Pause className="w-3.5 h-3.5" />}
+                        </button>
+                    )}
                     <button
                         className="p-1.5 text-gray-500 dark:text-gray-400 hover:text-red-600 dark:hover:text-red-400 hover:bg-red-50 dark:hover:bg-red-900/30 rounded-lg transition-all"
                         onClick={(e) => { e.stopPropagation(); onDelete(); }}
@@ -562,6 +600,7 @@ function AccountTable({
     onExport,
     onDelete,
     onToggleProxy,
+    onTogglePause,
     onReorder,
 }: AccountTableProps) {
     const { t } = useTranslation();
@@ -654,6 +693,7 @@ function AccountTable({
                                     onExport={() => onExport(account.id)}
                                     onDelete={() => onDelete(account.id)}
                                     onToggleProxy={() => onToggleProxy(account.id)}
+                                    onTogglePause={onTogglePause ? () => onTogglePause(account.id) : undefined}
                                 />
                             ))}
                         </tbody>
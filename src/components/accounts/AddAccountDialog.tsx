@@ -25,7 +25,7 @@ function AddAccountDialog({ onAdd }: AddAccountDialogProps) {
     const [status, setStatus] = useState<Status>('idle');
     const [message, setMessage] = useState('');
 
-    const { startOAuthLogin, completeOAuthLogin, cancelOAuthLogin, importFromDb, importV1Accounts, importFromCustomDb } = useAccountStore();
+    const { startOAuthLogin, completeOAuthLogin, cancelOAuthLogin, importFromDb, importV1Accounts, importFromCustomDb, addApiKeyAccount } = useAccountStore();
 
     const oauthUrlRef = useRef(oauthUrl);
     const statusRef = useRef(status);
@@ -221,8 +221,14 @@ function AddAccountDialog({ onAdd }: AddAccountDialogProps) {
 
         // 去重
         tokens = [...new Set(tokens)];
-
-        if (tokens.length === 0) {
+        // AI Studio API Key (AIza...) 作为 API Key 类型账号添加
+        const apiKeys = [...new Set(input.match(/AIza[0-9A-Za-z_\-]{35}/g) || [])];
+        const items = [
+            ...tokens.map(value => ({ value, isApiKey: false })),
+            ...apiKeys.map(value => ({ value, isApiKey: true })),
+        ];
+
+        if (items.length === 0) {
             setStatus('error');
             setMessage(t('accounts.add.token.error_token')); // 或者提示"未找到有效 Token"
             return;
@@ -232,12 +238,16 @@ function AddAccountDialog({ onAdd }: AddAccountDialogProps) {
         let successCount = 0;
         let failCount = 0;
 
-        for (let i = 0; i < tokens.length; i++) {
-            const currentToken = tokens[i];
-            setMessage(t('accounts.add.token.batch_progress', { current: i + 1, total: tokens.length }));
+        for (let i = 0; i < items.length; i++) {
+            const item = items[i];
+            setMessage(t('accounts.add.token.batch_progress', { current: i + 1, total: items.length }));
 
             try {
-                await onAdd("", currentToken);
+                if (item.isApiKey) {
+                    await addApiKeyAccount(item.value);
+                } else {
+                    await onAdd("", item.value);
+                }
                 successCount++;
             } catch (error) {
                 console.error(`Failed to add token ${i + 1}:`, error);
@@ -248,7 +258,7 @@ function AddAccountDialog({ onAdd }: AddAccountDialogProps) {
         }
 
         // 4. 结果反馈
-        if (successCount === tokens.length) {
+        if (successCount === items.length) {
             setStatus('success');
             setMessage(t('accounts.add.token.batch_success', { count: successCount }));
             setTimeout(() => {
@@ -484,6 +494,9 @@ function AddAccountDialog({ onAdd }: AddAccountDialogProps) {
                                         <p className="text-[10px] text-gray-400 mt-2">
                                             {t('accounts.add.token.hint')}
                                         </p>
+                                        <p className="text-[10px] text-gray-400 mt-1">
+                                            {t('accounts.add.token.api_key_hint')}
+                                        </p>
                                     </div>
                                 </div>
                             )}
@@ -2,6 +2,7 @@
 import { useEffect } from 'react';
 import { useConfigStore } from '../../stores/useConfigStore';
 import { getCurrentWindow } from '@tauri-apps/api/window';
+import { invoke } from '@tauri-apps/api/core';
 
 // Detect if running on Linux platform
 const isLinux = navigator.userAgent.toLowerCase().includes('linux');
@@ -13,6 +14,9 @@ export default function ThemeManager() {
     useEffect(() => {
         const init = async () => {
             await loadConfig();
+            // 开机自启 (--minimized) 时保持隐藏，只留托盘
+            const minimized = await invoke<boolean>('is_launched_minimized').catch(() => false);
+            if (minimized) return;
             // Show window after a short delay to ensure React has painted
             setTimeout(async () => {
                 await getCurrentWindow().show();
@@ -0,0 +1,110 @@
+import { useEffect, useState } from 'react';
+import { useTranslation } from 'react-i18next';
+import { Check, Loader2, Minus, Radar, X } from 'lucide-react';
+import { CapabilityProbeReport, ProbedCapabilities } from '../../types/config';
+import { getCapabilityProbes, probeModelCapabilities } from '../../services/configService';
+import { showToast } from '../common/ToastContainer';
+
+interface CapabilityProbePanelProps {
+    probeOnAdd: boolean;
+    onProbeOnAddChange: (value: boolean) => void;
+}
+
+const FEATURES: (keyof ProbedCapabilities)[] = ['tools', 'images', 'system_instruction', 'thinking', 'json_mode'];
+
+const FeatureCell = ({ value }: { value: boolean | null }) => {
+    if (value === true) return <Check size={14} className="text-green-500 mx-auto" />;
+    if (value === false) return <X size={14} className="text-red-500 mx-auto" />;
+    return <Minus size={14} className="text-gray-300 mx-auto" />;
+};
+
+export const CapabilityProbePanel = ({ probeOnAdd, onProbeOnAddChange }: CapabilityProbePanelProps) => {
+    const { t } = useTranslation();
+    const [report, setReport] = useState<CapabilityProbeReport | null>(null);
+    const [probing, setProbing] = useState(false);
+
+    useEffect(() => {
+        getCapabilityProbes().then(setReport).catch(() => setReport(null));
+    }, []);
+
+    const handleProbe = async () => {
+        setProbing(true);
+        try {
+            const next = await probeModelCapabilities();
+            setReport(next);
+            showToast(t('proxy.config.experimental.capability_probe_done', { count: next.results.length }), 'success');
+        } catch (error) {
+            showToast(`${t('common.error')}: ${error}`, 'error');
+        } finally {
+            setProbing(false);
+        }
+    };
+
+    const models = Object.keys(report?.merged ?? {});
+    const unavailable = report?.results.filter(r => r.error).length ?? 0;
+
+    return (
+        <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-xl border border-gray-100 dark:border-base-300 space-y-3">
+            <div className="flex items-center justify-between">
+                <div className="space-y-1">
+                    <span className="text-sm font-bold text-gray-900 dark:text-base-content">
+                        {t('proxy.config.experimental.capability_probe')}
+                    </span>
+                    <p className="text-[10px] text-gray-500 dark:text-gray-400 max-w-lg">
+                        {t('proxy.config.experimental.capability_probe_desc')}
+                    </p>
+                </div>
+                <button
+                    className="px-3 py-1.5 text-xs font-medium bg-purple-50 dark:bg-purple-900/20 text-purple-600 dark:text-purple-400 rounded-lg border border-purple-100 dark:border-purple-800/30 hover:bg-purple-100 flex items-center gap-1.5 disabled:opacity-50"
+                    onClick={handleProbe}
+                    disabled={probing}
+                >
+                    {probing ? <Loader2 size={14} className="animate-spin" /> : <Radar size={14} />}
+                    {t('proxy.config.experimental.capability_probe_run')}
+                </button>
+            </div>
+
+            <label className="flex items-center gap-2 text-xs text-gray-600 dark:text-gray-400 cursor-pointer">
+                <input
+                    type="checkbox"
+                    className="checkbox checkbox-xs"
+                    checked={probeOnAdd}
+                    onChange={(e) => onProbeOnAddChange(e.target.checked)}
+                />
+                {t('proxy.config.experimental.capability_probe_on_add')}
+            </label>
+
+            {models.length > 0 && (
+                <div className="overflow-x-auto">
+                    <table className="w-full text-xs">
+                        <thead>
+                            <tr className="text-gray-500 dark:text-gray-400">
+                                <th className="text-left font-medium py-1">{t('proxy.config.experimental.capability_probe_model')}</th>
+                                {FEATURES.map(f => (
+                                    <th key={f} className="font-medium py-1 font-mono">{f}</th>
+                                ))}
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {models.map(model => (
+                                <tr key={model} className="border-t border-gray-100 dark:border-base-300">
+                                    <td className="py-1 font-mono text-gray-700 dark:text-gray-300">{model}</td>
+                                    {FEATURES.map(f => (
+                                        <td key={f} className="py-1">
+                                            <FeatureCell value={report!.merged[model][f]} />
+                                        </td>
+                                    ))}
+                                </tr>
+                            ))}
+                        </tbody>
+                    </table>
+                    {unavailable > 0 && (
+                        <p className="mt-2 text-[10px] text-gray-400">
+                            {t('proxy.config.experimental.capability_probe_unavailable', { count: unavailable })}
+                        </p>
+                    )}
+                </div>
+            )}
+        </div>
+    );
+};
@@ -0,0 +1,70 @@
+import { useTranslation } from 'react-i18next';
+import { Image } from 'lucide-react';
+import { ImageOutputConfig, ImageOutputMode } from '../../types/config';
+
+interface ImageOutputSettingsProps {
+    config: ImageOutputConfig;
+    onChange: (config: ImageOutputConfig) => void;
+}
+
+const MODES: ImageOutputMode[] = ['markdown', 'images', 'both'];
+
+export const DEFAULT_IMAGE_OUTPUT_CONFIG: ImageOutputConfig = {
+    mode: 'markdown',
+    save_dir: '',
+    link_saved_files: false,
+};
+
+export const ImageOutputSettings = ({ config, onChange }: ImageOutputSettingsProps) => {
+    const { t } = useTranslation();
+
+    return (
+        <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-xl border border-gray-100 dark:border-base-300 space-y-3">
+            <div className="space-y-1">
+                <span className="text-sm font-bold text-gray-900 dark:text-base-content flex items-center gap-2">
+                    <Image size={14} className="text-pink-500" />
+                    {t('proxy.config.experimental.image_output')}
+                </span>
+                <p className="text-[10px] text-gray-500 dark:text-gray-400 max-w-lg">
+                    {t('proxy.config.experimental.image_output_desc')}
+                </p>
+            </div>
+
+            <div className="grid grid-cols-1 sm:grid-cols-2 gap-2">
+                <label className="space-y-1">
+                    <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.image_output_mode')}</span>
+                    <select
+                        className="select select-sm select-bordered text-xs w-full"
+                        value={config.mode}
+                        onChange={(e) => onChange({ ...config, mode: e.target.value as ImageOutputMode })}
+                    >
+                        {MODES.map(mode => (
+                            <option key={mode} value={mode}>
+                                {t(`proxy.config.experimental.image_output_mode_${mode}`)}
+                            </option>
+                        ))}
+                    </select>
+                </label>
+                <label className="space-y-1">
+                    <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.image_output_save_dir')}</span>
+                    <input
+                        className="w-full px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                        placeholder="/home/me/Pictures/antigravity"
+                        defaultValue={config.save_dir}
+                        onBlur={(e) => onChange({ ...config, save_dir: e.target.value.trim() })}
+                    />
+                </label>
+                <label className="flex items-center gap-2 text-xs text-gray-700 dark:text-gray-300 sm:col-span-2">
+                    <input
+                        type="checkbox"
+                        className="checkbox checkbox-sm"
+                        checked={config.link_saved_files}
+                        disabled={!config.save_dir}
+                        onChange={(e) => onChange({ ...config, link_saved_files: e.target.checked })}
+                    />
+                    {t('proxy.config.experimental.image_output_link_saved')}
+                </label>
+            </div>
+        </div>
+    );
+};
@@ -0,0 +1,77 @@
+import { useTranslation } from 'react-i18next';
+import { FolderOpen } from 'lucide-react';
+import { LocalFilesConfig } from '../../types/config';
+
+interface LocalFilesSettingsProps {
+    config: LocalFilesConfig;
+    onChange: (config: LocalFilesConfig) => void;
+}
+
+const splitLines = (value: string) => value.split(/[\n,]/).map(v => v.trim()).filter(Boolean);
+
+export const DEFAULT_LOCAL_FILES_CONFIG: LocalFilesConfig = {
+    enabled: false,
+    roots: [],
+    trusted_keys: [],
+    max_file_mb: 20,
+};
+
+export const LocalFilesSettings = ({ config, onChange }: LocalFilesSettingsProps) => {
+    const { t } = useTranslation();
+
+    return (
+        <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-xl border border-gray-100 dark:border-base-300 space-y-3">
+            <div className="flex items-center justify-between">
+                <div className="space-y-1">
+                    <span className="text-sm font-bold text-gray-900 dark:text-base-content flex items-center gap-2">
+                        <FolderOpen size={14} className="text-amber-500" />
+                        {t('proxy.config.experimental.local_files')}
+                    </span>
+                    <p className="text-[10px] text-gray-500 dark:text-gray-400 max-w-lg">
+                        {t('proxy.config.experimental.local_files_desc')}
+                    </p>
+                </div>
+                <label className="relative inline-flex items-center cursor-pointer">
+                    <input
+                        type="checkbox"
+                        className="sr-only peer"
+                        checked={config.enabled}
+                        onChange={(e) => onChange({ ...config, enabled: e.target.checked })}
+                    />
+                    <div className="w-11 h-6 bg-gray-200 dark:bg-base-300 peer-focus:outline-none rounded-full peer peer-checked:after:translate-x-full peer-checked:after:border-white after:content-[''] after:absolute after:top-[2px] after:left-[2px] after:bg-white after:border-gray-300 after:border after:rounded-full after:h-5 after:w-5 after:transition-all peer-checked:bg-amber-500 shadow-inner"></div>
+                </label>
+            </div>
+
+            <div className="grid grid-cols-1 sm:grid-cols-2 gap-2">
+                <label className="space-y-1 sm:col-span-2">
+                    <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.local_files_roots')}</span>
+                    <textarea
+                        rows={2}
+                        className="w-full px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                        placeholder="/home/me/screenshots"
+                        defaultValue={config.roots.join('\n')}
+                        onBlur={(e) => onChange({ ...config, roots: splitLines(e.target.value) })}
+                    />
+                </label>
+                <label className="space-y-1">
+                    <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.local_files_trusted_keys')}</span>
+                    <input
+                        className="w-full px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                        defaultValue={config.trusted_keys.join(', ')}
+                        onBlur={(e) => onChange({ ...config, trusted_keys: splitLines(e.target.value) })}
+                    />
+                </label>
+                <label className="space-y-1">
+                    <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.local_files_max_mb')}</span>
+                    <input
+                        type="number"
+                        min={1}
+                        className="w-full px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                        value={config.max_file_mb}
+                        onChange={(e) => onChange({ ...config, max_file_mb: Math.max(1, parseInt(e.target.value) || 1) })}
+                    />
+                </label>
+            </div>
+        </div>
+    );
+};
@@ -0,0 +1,175 @@
+import { useEffect, useState } from 'react';
+import { useTranslation } from 'react-i18next';
+import { Loader2, Plus, RefreshCw, Ruler, Trash2 } from 'lucide-react';
+import { ModelLimitRule, ModelLimitsConfig, ModelLimitsReport } from '../../types/config';
+import { getModelLimits, refreshModelLimitsManifest } from '../../services/configService';
+import { showToast } from '../common/ToastContainer';
+
+interface ModelLimitsSettingsProps {
+    config: ModelLimitsConfig;
+    onChange: (config: ModelLimitsConfig) => void;
+}
+
+export const DEFAULT_MODEL_LIMITS_CONFIG: ModelLimitsConfig = {
+    overrides: [],
+    manifest_url: '',
+    clamp_max_output: true,
+};
+
+const parseLimit = (value: string): number | null => {
+    const n = parseInt(value, 10);
+    return Number.isFinite(n) && n > 0 ? n : null;
+};
+
+const formatLimit = (value?: number | null) => (value ? value.toLocaleString() : '—');
+
+export const ModelLimitsSettings = ({ config, onChange }: ModelLimitsSettingsProps) => {
+    const { t } = useTranslation();
+    const [report, setReport] = useState<ModelLimitsReport | null>(null);
+    const [refreshing, setRefreshing] = useState(false);
+
+    useEffect(() => {
+        getModelLimits().then(setReport).catch(() => setReport(null));
+    }, []);
+
+    const updateOverride = (index: number, updates: Partial<ModelLimitRule>) => {
+        const overrides = config.overrides.map((rule, i) => (i === index ? { ...rule, ...updates } : rule));
+        onChange({ ...config, overrides });
+    };
+
+    const handleRefresh = async () => {
+        setRefreshing(true);
+        try {
+            const next = await refreshModelLimitsManifest(config.manifest_url || undefined);
+            setReport(next);
+            showToast(t('proxy.config.experimental.model_limits_refreshed', { count: next.manifest.length }), 'success');
+        } catch (error) {
+            showToast(`${t('common.error')}: ${error}`, 'error');
+        } finally {
+            setRefreshing(false);
+        }
+    };
+
+    const referenceRules = [...(report?.manifest ?? []), ...(report?.builtin ?? [])];
+
+    return (
+        <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-xl border border-gray-100 dark:border-base-300 space-y-3">
+            <div className="space-y-1">
+                <span className="text-sm font-bold text-gray-900 dark:text-base-content flex items-center gap-2">
+                    <Ruler size={14} className="text-teal-500" />
+                    {t('proxy.config.experimental.model_limits')}
+                </span>
+                <p className="text-[10px] text-gray-500 dark:text-gray-400 max-w-lg">
+                    {t('proxy.config.experimental.model_limits_desc')}
+                </p>
+            </div>
+
+            <label className="flex items-center gap-2 text-xs text-gray-700 dark:text-gray-300">
+                <input
+                    type="checkbox"
+                    className="checkbox checkbox-sm"
+                    checked={config.clamp_max_output}
+                    onChange={(e) => onChange({ ...config, clamp_max_output: e.target.checked })}
+                />
+                {t('proxy.config.experimental.model_limits_clamp')}
+            </label>
+
+            <div className="space-y-1">
+                <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.model_limits_manifest_url')}</span>
+                <div className="flex gap-2">
+                    <input
+                        className="flex-1 px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                        placeholder="https://example.com/model-limits.json"
+                        defaultValue={config.manifest_url}
+                        onBlur={(e) => onChange({ ...config, manifest_url: e.target.value.trim() })}
+                    />
+                    <button
+                        className="px-3 py-1 text-xs font-medium bg-teal-50 dark:bg-teal-900/20 text-teal-600 dark:text-teal-400 rounded-lg border border-teal-100 dark:border-teal-800/30 hover:bg-teal-100 flex items-center gap-1.5 disabled:opacity-50"
+                        onClick={handleRefresh}
+                        disabled={refreshing || !config.manifest_url}
+                    >
+                        {refreshing ? <Loader2 size={14} className="animate-spin" /> : <RefreshCw size={14} />}
+                        {t('proxy.config.experimental.model_limits_refresh')}
+                    </button>
+                </div>
+                {report?.fetched_at && (
+                    <p className="text-[10px] text-gray-400">
+                        {t('proxy.config.experimental.model_limits_fetched', {
+                            count: report.manifest.length,
+                            time: new Date(report.fetched_at * 1000).toLocaleString(),
+                        })}
+                    </p>
+                )}
+            </div>
+
+            <div className="space-y-1">
+                <div className="flex items-center justify-between">
+                    <span className="text-xs font-medium text-gray-700 dark:text-gray-300">{t('proxy.config.experimental.model_limits_overrides')}</span>
+                    <button
+                        className="btn btn-ghost btn-xs gap-1"
+                        onClick={() => onChange({ ...config, overrides: [...config.overrides, { model: '', context_window: null, max_output_tokens: null }] })}
+                    >
+                        <Plus size={12} />
+                        {t('proxy.config.experimental.model_limits_add')}
+                    </button>
+                </div>
+                {config.overrides.map((rule, index) => (
+                    <div key={index} className="grid grid-cols-[1fr_7rem_7rem_auto] gap-2 items-center">
+                        <input
+                            className="px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                            placeholder="gemini-3-pro-*"
+                            defaultValue={rule.model}
+                            onBlur={(e) => updateOverride(index, { model: e.target.value.trim() })}
+                        />
+                        <input
+                            type="number"
+                            min={1}
+                            className="px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                            placeholder={t('proxy.config.experimental.model_limits_context')}
+                            defaultValue={rule.context_window ?? ''}
+                            onBlur={(e) => updateOverride(index, { context_window: parseLimit(e.target.value) })}
+                        />
+                        <input
+                            type="number"
+                            min={1}
+                            className="px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                            placeholder={t('proxy.config.experimental.model_limits_max_output')}
+                            defaultValue={rule.max_output_tokens ?? ''}
+                            onBlur={(e) => updateOverride(index, { max_output_tokens: parseLimit(e.target.value) })}
+                        />
+                        <button
+                            className="btn btn-ghost btn-xs text-red-500"
+                            onClick={() => onChange({ ...config, overrides: config.overrides.filter((_, i) => i !== index) })}
+                        >
+                            <Trash2 size={12} />
+                        </button>
+                    </div>
+                ))}
+            </div>
+
+            {referenceRules.length > 0 && (
+                <details className="text-xs">
+                    <summary className="cursor-pointer text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.model_limits_reference')}</summary>
+                    <table className="w-full mt-2">
+                        <thead>
+                            <tr className="text-gray-500 dark:text-gray-400">
+                                <th className="text-left font-medium py-1">{t('proxy.config.experimental.model_limits_model')}</th>
+                                <th className="text-right font-medium py-1">{t('proxy.config.experimental.model_limits_context')}</th>
+                                <th className="text-right font-medium py-1">{t('proxy.config.experimental.model_limits_max_output')}</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {referenceRules.map((rule, index) => (
+                                <tr key={`${rule.model}-${index}`} className="border-t border-gray-100 dark:border-base-300">
+                                    <td className="py-1 font-mono text-gray-700 dark:text-gray-300">{rule.model}</td>
+                                    <td className="py-1 font-mono text-right">{formatLimit(rule.context_window)}</td>
+                                    <td className="py-1 font-mono text-right">{formatLimit(rule.max_output_tokens)}</td>
+                                </tr>
+                            ))}
+                        </tbody>
+                    </table>
+                </details>
+            )}
+        </div>
+    );
+};
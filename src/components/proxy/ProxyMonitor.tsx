@@ -1,5 +1,6 @@
 import React, { useEffect, useState, useRef } from 'react';
 import { listen } from '@tauri-apps/api/event';
+import { save } from '@tauri-apps/plugin-dialog';
 import ModalDialog from '../common/ModalDialog';
 import { useTranslation } from 'react-i18next';
 import { request as invoke } from '../../utils/request';
@@ -7,6 +8,7 @@ import { Trash2, Search, X, Copy, CheckCircle, ChevronLeft, ChevronRight, Refres
 
 import { AppConfig } from '../../types/config';
 import { formatCompactNumber } from '../../utils/format';
+import { showToast } from '../common/ToastContainer';
 
 
 interface ProxyRequestLog {
@@ -25,6 +27,16 @@ interface ProxyRequestLog {
     output_tokens?: number;
     account_email?: string;
     protocol?: string;  // "openai" | "anthropic" | "gemini"
+    session_id?: string;
+    notes?: string;
+    tags?: string;
+    ttft_ms?: number;
+    tokens_per_sec?: number;
+    chunk_count?: number;
+    message_id?: string;
+    parent_message_id?: string;
+    error_code?: string;
+    error_hint?: string;
 }
 
 interface ProxyStats {
@@ -154,6 +166,21 @@ export const ProxyMonitor: React.FC<ProxyMonitorProps> = ({ className }) => {
     const [loading, setLoading] = useState(false);
     const [loadingDetail, setLoadingDetail] = useState(false);
 
+    const exportTranscript = async (sessionId: string, format: 'markdown' | 'jsonl') => {
+        const extension = format === 'markdown' ? 'md' : 'jsonl';
+        try {
+            const path = await save({
+                filters: [{ name: format === 'markdown' ? 'Markdown' : 'JSONL', extensions: [extension] }],
+                defaultPath: `conversation_${sessionId}.${extension}`
+            });
+            if (!path) return;
+            const count = await invoke<number>('export_conversation_transcript', { sessionId, format, filePath: path });
+            showToast(t('monitor.details.export_transcript_success', { count }), 'success');
+        } catch (error) {
+            showToast(`${t('monitor.details.export_transcript_error')}: ${error}`, 'error');
+        }
+    };
+
     const loadData = async (page = 1, searchFilter = filter) => {
         if (loading) return;
         setLoading(true);
@@ -554,6 +581,20 @@ export const ProxyMonitor: React.FC<ProxyMonitorProps> = ({ className }) => {
                                             <span className="text-green-700 dark:text-green-300 bg-green-100 dark:bg-green-900/40 px-2.5 py-1 rounded-md border border-green-200 dark:border-green-800/50 font-bold">Out: {formatCompactNumber(selectedLog.output_tokens ?? 0)}</span>
                                         </div>
                                     </div>
+                                    {selectedLog.ttft_ms != null && (
+                                        <div className="space-y-1.5">
+                                            <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest">{t('monitor.details.stream_stats')}</span>
+                                            <div className="font-mono text-[11px] flex flex-wrap gap-2">
+                                                <span className="text-gray-700 dark:text-gray-300 bg-gray-100 dark:bg-slate-700 px-2.5 py-1 rounded-md font-bold">{t('monitor.details.ttft')}: {selectedLog.ttft_ms}ms</span>
+                                                {selectedLog.tokens_per_sec != null && (
+                                                    <span className="text-gray-700 dark:text-gray-300 bg-gray-100 dark:bg-slate-700 px-2.5 py-1 rounded-md font-bold">{selectedLog.tokens_per_sec} tok/s</span>
+                                                )}
+                                                {selectedLog.chunk_count != null && (
+                                                    <span className="text-gray-700 dark:text-gray-300 bg-gray-100 dark:bg-slate-700 px-2.5 py-1 rounded-md font-bold">{t('monitor.details.chunks', { count: selectedLog.chunk_count })}</span>
+                                                )}
+                                            </div>
+                                        </div>
+                                    )}
                                 </div>
                                 <div className="mt-5 pt-5 border-t border-gray-200 dark:border-slate-700">
                                     <div className="grid grid-cols-1 sm:grid-cols-2 lg:grid-cols-3 gap-5">
@@ -582,12 +623,69 @@ export const ProxyMonitor: React.FC<ProxyMonitorProps> = ({ className }) => {
                                         )}
                                     </div>
                                 </div>
+                                {selectedLog.error_code && (
+                                    <div className="mt-5 pt-5 border-t border-gray-200 dark:border-slate-700">
+                                        <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest mb-2">{t('monitor.details.error_diagnosis')}</span>
+                                        <span className="badge badge-sm badge-error text-white border-none font-mono">{selectedLog.error_code}</span>
+                                        {selectedLog.error_hint && (
+                                            <p className="mt-2 text-xs text-gray-700 dark:text-gray-300">{selectedLog.error_hint}</p>
+                                        )}
+                                    </div>
+                                )}
                                 {selectedLog.account_email && (
                                     <div className="mt-5 pt-5 border-t border-gray-200 dark:border-slate-700">
                                         <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest mb-2">{t('monitor.details.account_used')}</span>
                                         <span className="font-mono font-semibold text-gray-900 dark:text-white text-xs">{selectedLog.account_email}</span>
                                     </div>
                                 )}
+                                {selectedLog.tags && (
+                                    <div className="mt-5 pt-5 border-t border-gray-200 dark:border-slate-700">
+                                        <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest mb-2">{t('monitor.details.tags')}</span>
+                                        <div className="flex flex-wrap gap-1">
+                                            {selectedLog.tags.split(',').map((tag) => (
+                                                <button
+                                                    key={tag}
+                                                    type="button"
+                                                    className="badge badge-sm badge-outline font-mono"
+                                                    onClick={() => { setFilter(tag); setSelectedLog(null); }}
+                                                >
+                                                    {tag}
+                                                </button>
+                                            ))}
+                                        </div>
+                                    </div>
+                                )}
+                                {selectedLog.session_id && (
+                                    <div className="mt-5 pt-5 border-t border-gray-200 dark:border-slate-700 flex items-center justify-between gap-2">
+                                        <div>
+                                            <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest mb-2">{t('monitor.details.session')}</span>
+                                            <span className="font-mono font-semibold text-gray-900 dark:text-white text-xs break-all">{selectedLog.session_id}</span>
+                                        </div>
+                                        <div className="flex items-center gap-1 shrink-0">
+                                            <button
+                                                type="button"
+                                                className="btn btn-ghost btn-xs"
+                                                onClick={() => { setFilter(selectedLog.session_id!); setSelectedLog(null); }}
+                                            >
+                                                {t('monitor.details.show_conversation')}
+                                            </button>
+                                            <button
+                                                type="button"
+                                                className="btn btn-ghost btn-xs"
+                                                onClick={() => exportTranscript(selectedLog.session_id!, 'markdown')}
+                                            >
+                                                {t('monitor.details.export_markdown')}
+                                            </button>
+                                            <button
+                                                type="button"
+                                                className="btn btn-ghost btn-xs"
+                                                onClick={() => exportTranscript(selectedLog.session_id!, 'jsonl')}
+                                            >
+                                                {t('monitor.details.export_jsonl')}
+                                            </button>
+                                        </div>
+                                    </div>
+                                )}
                             </div>
 
                             {/* Payloads */}
@@ -0,0 +1,143 @@
+import { useEffect, useState } from 'react';
+import { useTranslation } from 'react-i18next';
+import { FileCheck, Loader2, RefreshCw } from 'lucide-react';
+import { ModelQuirk, QuirkManifestReport, QuirksConfig } from '../../types/config';
+import { getQuirkManifest, refreshQuirkManifest } from '../../services/configService';
+import { showToast } from '../common/ToastContainer';
+
+interface QuirksSettingsProps {
+    config: QuirksConfig;
+    onChange: (config: QuirksConfig) => void;
+}
+
+export const DEFAULT_QUIRKS_CONFIG: QuirksConfig = {
+    manifest_url: '',
+    public_key: '',
+    refresh_interval_hours: 24,
+    overrides: [],
+};
+
+const OVERRIDES_PLACEHOLDER = `[
+  { "model": "gemini-3-*", "requires_thought_signature": true },
+  { "model": "gemini-9-pro", "strip_thought_parts": true, "schema_drop_keywords": ["pattern"] }
+]`;
+
+export const QuirksSettings = ({ config, onChange }: QuirksSettingsProps) => {
+    const { t } = useTranslation();
+    const [report, setReport] = useState<QuirkManifestReport | null>(null);
+    const [refreshing, setRefreshing] = useState(false);
+    const [overridesError, setOverridesError] = useState<string | null>(null);
+
+    useEffect(() => {
+        getQuirkManifest().then(setReport).catch(() => setReport(null));
+    }, []);
+
+    const handleRefresh = async () => {
+        setRefreshing(true);
+        try {
+            const next = await refreshQuirkManifest();
+            setReport(next);
+            showToast(t('proxy.config.experimental.quirks_refreshed', { version: next.manifest?.version ?? 0 }), 'success');
+        } catch (error) {
+            showToast(`${t('common.error')}: ${error}`, 'error');
+        } finally {
+            setRefreshing(false);
+        }
+    };
+
+    const handleOverridesBlur = (text: string) => {
+        if (!text.trim()) {
+            setOverridesError(null);
+            onChange({ ...config, overrides: [] });
+            return;
+        }
+        try {
+            const parsed = JSON.parse(text);
+            if (!Array.isArray(parsed) || parsed.some((q: ModelQuirk) => typeof q?.model !== 'string' || !q.model.trim())) {
+                throw new Error(t('proxy.config.experimental.quirks_overrides_invalid'));
+            }
+            setOverridesError(null);
+            onChange({ ...config, overrides: parsed });
+        } catch (error) {
+            setOverridesError(error instanceof Error ? error.message : String(error));
+        }
+    };
+
+    const manifest = report?.manifest;
+
+    return (
+        <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-xl border border-gray-100 dark:border-base-300 space-y-3">
+            <div className="flex items-center justify-between">
+                <div className="space-y-1">
+                    <span className="text-sm font-bold text-gray-900 dark:text-base-content flex items-center gap-2">
+                        <FileCheck size={14} className="text-amber-500" />
+                        {t('proxy.config.experimental.quirks')}
+                    </span>
+                    <p className="text-[10px] text-gray-500 dark:text-gray-400 max-w-lg">
+                        {t('proxy.config.experimental.quirks_desc')}
+                    </p>
+                </div>
+                <button
+                    className="px-3 py-1.5 text-xs font-medium bg-amber-50 dark:bg-amber-900/20 text-amber-600 dark:text-amber-400 rounded-lg border border-amber-100 dark:border-amber-800/30 hover:bg-amber-100 flex items-center gap-1.5 disabled:opacity-50"
+                    onClick={handleRefresh}
+                    disabled={refreshing || !config.manifest_url || !config.public_key}
+                >
+                    {refreshing ? <Loader2 size={14} className="animate-spin" /> : <RefreshCw size={14} />}
+                    {t('proxy.config.experimental.quirks_refresh')}
+                </button>
+            </div>
+
+            <div className="grid grid-cols-1 sm:grid-cols-[1fr_1fr_8rem] gap-2">
+                <label className="space-y-1">
+                    <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.quirks_manifest_url')}</span>
+                    <input
+                        className="w-full px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                        placeholder="https://example.com/quirks.json"
+                        defaultValue={config.manifest_url}
+                        onBlur={(e) => onChange({ ...config, manifest_url: e.target.value.trim() })}
+                    />
+                </label>
+                <label className="space-y-1">
+                    <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.quirks_public_key')}</span>
+                    <input
+                        className="w-full px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                        placeholder="Ed25519 (base64url)"
+                        defaultValue={config.public_key}
+                        onBlur={(e) => onChange({ ...config, public_key: e.target.value.trim() })}
+                    />
+                </label>
+                <label className="space-y-1">
+                    <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.quirks_interval')}</span>
+                    <input
+                        type="number"
+                        min={0}
+                        className="w-full px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                        defaultValue={config.refresh_interval_hours}
+                        onBlur={(e) => onChange({ ...config, refresh_interval_hours: Math.max(0, parseInt(e.target.value, 10) || 0) })}
+                    />
+                </label>
+            </div>
+
+            <p className="text-[10px] text-gray-400">
+                {manifest && report?.fetched_at
+                    ? t('proxy.config.experimental.quirks_status', {
+                        version: manifest.version,
+                        count: manifest.models.length,
+                        time: new Date(report.fetched_at * 1000).toLocaleString(),
+                    })
+                    : t('proxy.config.experimental.quirks_status_none')}
+            </p>
+
+            <label className="space-y-1 block">
+                <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.quirks_overrides')}</span>
+                <textarea
+                    className="w-full h-24 px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                    placeholder={OVERRIDES_PLACEHOLDER}
+                    defaultValue={config.overrides.length > 0 ? JSON.stringify(config.overrides, null, 2) : ''}
+                    onBlur={(e) => handleOverridesBlur(e.target.value)}
+                />
+                {overridesError && <span className="text-[10px] text-red-500">{overridesError}</span>}
+            </label>
+        </div>
+    );
+};
@@ -0,0 +1,119 @@
+import { useTranslation } from 'react-i18next';
+import { Plus, Trash2 } from 'lucide-react';
+import { ResponseRewriteAction, ResponseRewriteRule } from '../../types/config';
+
+interface ResponseRewriteRulesProps {
+    rules: ResponseRewriteRule[];
+    onChange: (rules: ResponseRewriteRule[]) => void;
+}
+
+const ACTIONS: ResponseRewriteAction[] = ['regex_replace', 'strip_phrase', 'strip_json_fences', 'normalize_newlines'];
+
+const splitList = (value: string) => value.split(',').map(v => v.trim()).filter(Boolean);
+
+export const ResponseRewriteRules = ({ rules, onChange }: ResponseRewriteRulesProps) => {
+    const { t } = useTranslation();
+
+    const updateRule = (index: number, patch: Partial<ResponseRewriteRule>) => {
+        onChange(rules.map((r, i) => (i === index ? { ...r, ...patch } : r)));
+    };
+
+    const addRule = () => {
+        onChange([
+            ...rules,
+            { enabled: true, name: `rule-${rules.length + 1}`, action: 'strip_phrase', pattern: '', replacement: '', api_keys: [], models: [] },
+        ]);
+    };
+
+    return (
+        <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-xl border border-gray-100 dark:border-base-300 space-y-3">
+            <div className="space-y-1">
+                <span className="text-sm font-bold text-gray-900 dark:text-base-content">
+                    {t('proxy.config.experimental.response_rewrites')}
+                </span>
+                <p className="text-[10px] text-gray-500 dark:text-gray-400 max-w-lg">
+                    {t('proxy.config.experimental.response_rewrites_desc')}
+                </p>
+            </div>
+
+            {rules.map((rule, index) => {
+                const needsPattern = rule.action === 'regex_replace' || rule.action === 'strip_phrase';
+                return (
+                    <div key={index} className="p-3 rounded-lg bg-white dark:bg-base-100 border border-gray-100 dark:border-base-300 space-y-2">
+                        <div className="flex items-center gap-2">
+                            <input
+                                type="checkbox"
+                                className="checkbox checkbox-sm"
+                                checked={rule.enabled}
+                                onChange={(e) => updateRule(index, { enabled: e.target.checked })}
+                            />
+                            <input
+                                className="w-32 px-2 py-1 text-sm bg-gray-50 dark:bg-base-200 border border-gray-200 dark:border-base-300 rounded"
+                                value={rule.name}
+                                onChange={(e) => updateRule(index, { name: e.target.value })}
+                            />
+                            <select
+                                className="select select-sm select-bordered text-xs"
+                                value={rule.action}
+                                onChange={(e) => updateRule(index, { action: e.target.value as ResponseRewriteAction })}
+                            >
+                                {ACTIONS.map(action => (
+                                    <option key={action} value={action}>
+                                        {t(`proxy.config.experimental.rewrite_action_${action}`)}
+                                    </option>
+                                ))}
+                            </select>
+                            <div className="flex-1" />
+                            <button
+                                className="p-1 text-gray-400 hover:text-red-500"
+                                onClick={() => onChange(rules.filter((_, i) => i !== index))}
+                            >
+                                <Trash2 size={16} />
+                            </button>
+                        </div>
+                        {needsPattern && (
+                            <div className="flex items-center gap-2">
+                                <input
+                                    className="flex-1 px-2 py-1 text-xs font-mono bg-gray-50 dark:bg-base-200 border border-gray-200 dark:border-base-300 rounded"
+                                    placeholder={rule.action === 'regex_replace' ? t('proxy.config.experimental.rewrite_pattern_regex') : t('proxy.config.experimental.rewrite_pattern_phrase')}
+                                    value={rule.pattern}
+                                    onChange={(e) => updateRule(index, { pattern: e.target.value })}
+                                />
+                                {rule.action === 'regex_replace' && (
+                                    <input
+                                        className="flex-1 px-2 py-1 text-xs font-mono bg-gray-50 dark:bg-base-200 border border-gray-200 dark:border-base-300 rounded"
+                                        placeholder={t('proxy.config.experimental.rewrite_replacement')}
+                                        value={rule.replacement}
+                                        onChange={(e) => updateRule(index, { replacement: e.target.value })}
+                                    />
+                                )}
+                            </div>
+                        )}
+                        <div className="flex items-center gap-2">
+                            <input
+                                className="flex-1 px-2 py-1 text-xs font-mono bg-gray-50 dark:bg-base-200 border border-gray-200 dark:border-base-300 rounded"
+                                placeholder={t('proxy.config.experimental.rewrite_api_keys')}
+                                value={rule.api_keys.join(', ')}
+                                onChange={(e) => updateRule(index, { api_keys: splitList(e.target.value) })}
+                            />
+                            <input
+                                className="flex-1 px-2 py-1 text-xs font-mono bg-gray-50 dark:bg-base-200 border border-gray-200 dark:border-base-300 rounded"
+                                placeholder={t('proxy.config.experimental.rewrite_models')}
+                                value={rule.models.join(', ')}
+                                onChange={(e) => updateRule(index, { models: splitList(e.target.value) })}
+                            />
+                        </div>
+                    </div>
+                );
+            })}
+
+            <button
+                className="px-3 py-1.5 text-xs font-medium text-gray-600 dark:text-gray-300 border border-dashed border-gray-300 dark:border-base-300 rounded-lg hover:border-purple-400 hover:text-purple-600 flex items-center gap-1.5"
+                onClick={addRule}
+            >
+                <Plus size={14} />
+                {t('proxy.config.experimental.rewrite_add')}
+            </button>
+        </div>
+    );
+};
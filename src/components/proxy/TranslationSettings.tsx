@@ -0,0 +1,95 @@
+import { useTranslation } from 'react-i18next';
+import { Languages } from 'lucide-react';
+import { TranslationConfig } from '../../types/config';
+
+interface TranslationSettingsProps {
+    config: TranslationConfig;
+    onChange: (config: TranslationConfig) => void;
+}
+
+const splitList = (value: string) => value.split(',').map(v => v.trim()).filter(Boolean);
+
+export const DEFAULT_TRANSLATION_CONFIG: TranslationConfig = {
+    enabled: false,
+    keys: {},
+    model: 'gemini-2.5-flash',
+    translate_responses: true,
+};
+
+export const TranslationSettings = ({ config, onChange }: TranslationSettingsProps) => {
+    const { t } = useTranslation();
+
+    const keysWith = (value: boolean) =>
+        Object.entries(config.keys).filter(([, v]) => v === value).map(([k]) => k).join(', ');
+
+    // 同一个 Key 同时出现在两个列表时以最后编辑的列表为准
+    const setKeys = (value: boolean, raw: string) => {
+        const keys: Record<string, boolean> = {};
+        Object.entries(config.keys).forEach(([k, v]) => {
+            if (v !== value) keys[k] = v;
+        });
+        splitList(raw).forEach(k => { keys[k] = value; });
+        onChange({ ...config, keys });
+    };
+
+    return (
+        <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-xl border border-gray-100 dark:border-base-300 space-y-3">
+            <div className="flex items-center justify-between">
+                <div className="space-y-1">
+                    <span className="text-sm font-bold text-gray-900 dark:text-base-content flex items-center gap-2">
+                        <Languages size={14} className="text-purple-500" />
+                        {t('proxy.config.experimental.translation')}
+                    </span>
+                    <p className="text-[10px] text-gray-500 dark:text-gray-400 max-w-lg">
+                        {t('proxy.config.experimental.translation_desc')}
+                    </p>
+                </div>
+                <label className="relative inline-flex items-center cursor-pointer">
+                    <input
+                        type="checkbox"
+                        className="sr-only peer"
+                        checked={config.enabled}
+                        onChange={(e) => onChange({ ...config, enabled: e.target.checked })}
+                    />
+                    <div className="w-11 h-6 bg-gray-200 dark:bg-base-300 peer-focus:outline-none rounded-full peer peer-checked:after:translate-x-full peer-checked:after:border-white after:content-[''] after:absolute after:top-[2px] after:left-[2px] after:bg-white after:border-gray-300 after:border after:rounded-full after:h-5 after:w-5 after:transition-all peer-checked:bg-purple-500 shadow-inner"></div>
+                </label>
+            </div>
+
+            <div className="grid grid-cols-1 sm:grid-cols-2 gap-2">
+                <label className="space-y-1">
+                    <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.translation_model')}</span>
+                    <input
+                        className="w-full px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                        value={config.model}
+                        onChange={(e) => onChange({ ...config, model: e.target.value })}
+                    />
+                </label>
+                <label className="flex items-center gap-2 text-xs text-gray-700 dark:text-gray-300 sm:pt-4">
+                    <input
+                        type="checkbox"
+                        className="checkbox checkbox-sm"
+                        checked={config.translate_responses}
+                        onChange={(e) => onChange({ ...config, translate_responses: e.target.checked })}
+                    />
+                    {t('proxy.config.experimental.translation_responses')}
+                </label>
+                <label className="space-y-1">
+                    <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.translation_keys_on')}</span>
+                    <input
+                        className="w-full px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                        defaultValue={keysWith(true)}
+                        onBlur={(e) => setKeys(true, e.target.value)}
+                    />
+                </label>
+                <label className="space-y-1">
+                    <span className="text-[10px] text-gray-500 dark:text-gray-400">{t('proxy.config.experimental.translation_keys_off')}</span>
+                    <input
+                        className="w-full px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                        defaultValue={keysWith(false)}
+                        onBlur={(e) => setKeys(false, e.target.value)}
+                    />
+                </label>
+            </div>
+        </div>
+    );
+};
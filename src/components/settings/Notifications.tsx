@@ -0,0 +1,244 @@
+import { useState } from 'react';
+import { Bell, Plus, Trash2, Send, FileText } from 'lucide-react';
+import { useTranslation } from 'react-i18next';
+import { DailyReportConfig, NotificationConfig, NotificationEventName, WebhookConfig } from '../../types/config';
+import { generateDailyReport, sendTestNotification } from '../../services/configService';
+import { showToast } from '../common/ToastContainer';
+
+interface NotificationsProps {
+    config?: NotificationConfig;
+    onChange: (config: NotificationConfig) => void;
+}
+
+const DEFAULT_DAILY_REPORT: DailyReportConfig = {
+    enabled: false,
+    hour: 9,
+    desktop: true,
+    report_path: '',
+};
+
+const DEFAULT_CONFIG: NotificationConfig = {
+    desktop: false,
+    webhooks: [],
+    cooldown_secs: 600,
+    daily_report: DEFAULT_DAILY_REPORT,
+};
+
+// Discord 风格示例 (占位符由后端替换，这里不能写进翻译文本，否则会被 i18next 插值)
+const TEMPLATE_EXAMPLE = '{"content":"**{{title}}** {{account}}: {{message}}"}';
+
+const EVENTS: NotificationEventName[] = ['quota_exhausted', 'token_refresh_failed', 'all_accounts_unhealthy'];
+
+const Notifications = ({ config, onChange }: NotificationsProps) => {
+    const { t } = useTranslation();
+    const current = config ?? DEFAULT_CONFIG;
+    const dailyReport = current.daily_report ?? DEFAULT_DAILY_REPORT;
+    const [isTesting, setIsTesting] = useState(false);
+    const [isGenerating, setIsGenerating] = useState(false);
+
+    const updateDailyReport = (patch: Partial<DailyReportConfig>) => {
+        onChange({ ...current, daily_report: { ...dailyReport, ...patch } });
+    };
+
+    const handleGenerateReport = async () => {
+        setIsGenerating(true);
+        try {
+            await generateDailyReport(dailyReport, true);
+            showToast(t('settings.notifications.daily_report_generated'), 'success');
+        } catch (error) {
+            showToast(`${t('common.error')}: ${error}`, 'error');
+        } finally {
+            setIsGenerating(false);
+        }
+    };
+
+    const updateWebhook = (index: number, patch: Partial<WebhookConfig>) => {
+        const webhooks = current.webhooks.map((w, i) => (i === index ? { ...w, ...patch } : w));
+        onChange({ ...current, webhooks });
+    };
+
+    const addWebhook = () => {
+        onChange({
+            ...current,
+            webhooks: [...current.webhooks, { enabled: true, name: `webhook-${current.webhooks.length + 1}`, url: '', events: [], template: '' }],
+        });
+    };
+
+    const removeWebhook = (index: number) => {
+        onChange({ ...current, webhooks: current.webhooks.filter((_, i) => i !== index) });
+    };
+
+    const toggleEvent = (index: number, event: NotificationEventName) => {
+        const events = current.webhooks[index].events;
+        updateWebhook(index, { events: events.includes(event) ? events.filter(e => e !== event) : [...events, event] });
+    };
+
+    const handleTest = async () => {
+        setIsTesting(true);
+        try {
+            const sent = await sendTestNotification(current);
+            showToast(t('settings.notifications.test_success', { count: sent }), 'success');
+        } catch (error) {
+            showToast(`${t('settings.notifications.test_failed')}: ${error}`, 'error');
+        } finally {
+            setIsTesting(false);
+        }
+    };
+
+    return (
+        <div className="animate-in fade-in duration-500">
+            <div className="flex items-center justify-between">
+                <div className="flex items-center gap-4">
+                    <div className="w-10 h-10 rounded-xl bg-amber-50 dark:bg-amber-900/20 flex items-center justify-center text-amber-500 group-hover:bg-amber-500 group-hover:text-white transition-all duration-300">
+                        <Bell size={20} />
+                    </div>
+                    <div>
+                        <div className="font-bold text-gray-900 dark:text-gray-100">{t('settings.notifications.title')}</div>
+                        <p className="text-xs text-gray-500 dark:text-gray-400 mt-0.5">{t('settings.notifications.desc')}</p>
+                    </div>
+                </div>
+                <button
+                    className="px-3 py-1.5 text-xs font-medium bg-amber-50 dark:bg-amber-900/20 text-amber-600 dark:text-amber-400 rounded-lg border border-amber-100 dark:border-amber-800/30 hover:bg-amber-100 flex items-center gap-1.5 disabled:opacity-50"
+                    onClick={handleTest}
+                    disabled={isTesting}
+                >
+                    <Send size={14} />
+                    {t('settings.notifications.test')}
+                </button>
+            </div>
+
+            <div className="mt-5 pt-5 border-t border-gray-50 dark:border-base-300 space-y-4">
+                <div className="flex items-center gap-6">
+                    <label className="flex items-center gap-2 text-sm text-gray-700 dark:text-gray-300 cursor-pointer">
+                        <input
+                            type="checkbox"
+                            className="checkbox checkbox-sm"
+                            checked={current.desktop}
+                            onChange={(e) => onChange({ ...current, desktop: e.target.checked })}
+                        />
+                        {t('settings.notifications.desktop')}
+                    </label>
+                    <label className="flex items-center gap-2 text-xs font-bold text-gray-500 dark:text-gray-400 uppercase tracking-wider">
+                        {t('settings.notifications.cooldown')}
+                        <input
+                            type="number"
+                            className="w-24 px-3 py-1.5 bg-gray-50 dark:bg-base-200 border border-gray-100 dark:border-base-300 rounded-lg focus:ring-2 focus:ring-amber-500 outline-none text-sm font-bold text-amber-600 dark:text-amber-400"
+                            min="0"
+                            value={current.cooldown_secs}
+                            onChange={(e) => onChange({ ...current, cooldown_secs: Math.max(0, parseInt(e.target.value) || 0) })}
+                        />
+                    </label>
+                </div>
+
+                {current.webhooks.map((webhook, index) => (
+                    <div key={index} className="p-3 rounded-lg bg-gray-50 dark:bg-base-200 border border-gray-100 dark:border-base-300 space-y-2">
+                        <div className="flex items-center gap-2">
+                            <input
+                                type="checkbox"
+                                className="checkbox checkbox-sm"
+                                checked={webhook.enabled}
+                                onChange={(e) => updateWebhook(index, { enabled: e.target.checked })}
+                            />
+                            <input
+                                className="w-32 px-2 py-1 text-sm bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                                value={webhook.name}
+                                onChange={(e) => updateWebhook(index, { name: e.target.value })}
+                            />
+                            <input
+                                className="flex-1 px-2 py-1 text-sm font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                                placeholder="https://ntfy.sh/my-topic"
+                                value={webhook.url}
+                                onChange={(e) => updateWebhook(index, { url: e.target.value })}
+                            />
+                            <button className="p-1 text-gray-400 hover:text-red-500" onClick={() => removeWebhook(index)}>
+                                <Trash2 size={16} />
+                            </button>
+                        </div>
+                        <div className="flex flex-wrap items-center gap-3 text-xs text-gray-600 dark:text-gray-400">
+                            <span>{t('settings.notifications.events')}</span>
+                            {EVENTS.map(event => (
+                                <label key={event} className="flex items-center gap-1 cursor-pointer">
+                                    <input
+                                        type="checkbox"
+                                        className="checkbox checkbox-xs"
+                                        checked={webhook.events.includes(event)}
+                                        onChange={() => toggleEvent(index, event)}
+                                    />
+                                    {t(`settings.notifications.event_${event}`)}
+                                </label>
+                            ))}
+                        </div>
+                        <textarea
+                            className="w-full px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                            rows={2}
+                            placeholder={t('settings.notifications.template_placeholder', { example: TEMPLATE_EXAMPLE, interpolation: { escapeValue: false } })}
+                            value={webhook.template}
+                            onChange={(e) => updateWebhook(index, { template: e.target.value })}
+                        />
+                    </div>
+                ))}
+
+                <div className="p-3 rounded-lg bg-gray-50 dark:bg-base-200 border border-gray-100 dark:border-base-300 space-y-2">
+                    <div className="flex items-center justify-between">
+                        <label className="flex items-center gap-2 text-sm font-medium text-gray-700 dark:text-gray-300 cursor-pointer">
+                            <input
+                                type="checkbox"
+                                className="checkbox checkbox-sm"
+                                checked={dailyReport.enabled}
+                                onChange={(e) => updateDailyReport({ enabled: e.target.checked })}
+                            />
+                            <FileText size={14} />
+                            {t('settings.notifications.daily_report')}
+                        </label>
+                        <button
+                            className="px-2 py-1 text-xs text-gray-600 dark:text-gray-300 hover:text-amber-600 disabled:opacity-50"
+                            onClick={handleGenerateReport}
+                            disabled={isGenerating}
+                        >
+                            {t('settings.notifications.daily_report_now')}
+                        </button>
+                    </div>
+                    <p className="text-xs text-gray-500 dark:text-gray-400">{t('settings.notifications.daily_report_desc')}</p>
+                    <div className="flex items-center gap-3 text-xs text-gray-600 dark:text-gray-400">
+                        <label className="flex items-center gap-1.5">
+                            {t('settings.notifications.daily_report_hour')}
+                            <input
+                                type="number"
+                                className="w-16 px-2 py-1 text-sm bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                                min="0"
+                                max="23"
+                                value={dailyReport.hour}
+                                onChange={(e) => updateDailyReport({ hour: Math.max(0, Math.min(23, parseInt(e.target.value) || 0)) })}
+                            />
+                        </label>
+                        <label className="flex items-center gap-1.5 cursor-pointer">
+                            <input
+                                type="checkbox"
+                                className="checkbox checkbox-xs"
+                                checked={dailyReport.desktop}
+                                onChange={(e) => updateDailyReport({ desktop: e.target.checked })}
+                            />
+                            {t('settings.notifications.desktop')}
+                        </label>
+                        <input
+                            className="flex-1 px-2 py-1 text-xs font-mono bg-white dark:bg-base-100 border border-gray-200 dark:border-base-300 rounded"
+                            placeholder={t('settings.notifications.daily_report_path_placeholder')}
+                            value={dailyReport.report_path}
+                            onChange={(e) => updateDailyReport({ report_path: e.target.value })}
+                        />
+                    </div>
+                </div>
+
+                <button
+                    className="px-3 py-1.5 text-xs font-medium text-gray-600 dark:text-gray-300 border border-dashed border-gray-300 dark:border-base-300 rounded-lg hover:border-amber-400 hover:text-amber-600 flex items-center gap-1.5"
+                    onClick={addWebhook}
+                >
+                    <Plus size={14} />
+                    {t('settings.notifications.add_webhook')}
+                </button>
+            </div>
+        </div>
+    );
+};
+
+export default Notifications;
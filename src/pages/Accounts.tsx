@@ -13,7 +13,7 @@ import AddAccountDialog from '../components/accounts/AddAccountDialog';
 import ModalDialog from '../components/common/ModalDialog';
 import Pagination from '../components/common/Pagination';
 import { showToast } from '../components/common/ToastContainer';
-import { Account } from '../types/account';
+import { Account, isAccountPaused } from '../types/account';
 import { cn } from '../utils/cn';
 
 // ... (省略中间代码)
@@ -37,6 +37,8 @@ function Accounts() {
         loading,
         refreshQuota,
         toggleProxyStatus,
+        pauseAccount,
+        resumeAccount,
         reorderAccounts,
         warmUpAccounts,
         warmUpAccount,
@@ -374,6 +376,24 @@ function Accounts() {
         }
     };
 
+    // 临时暂停 1 小时 / 恢复 (维护模式，到期自动恢复)
+    const handleTogglePause = async (accountId: string) => {
+        const account = accounts.find(a => a.id === accountId);
+        if (!account) return;
+        try {
+            if (isAccountPaused(account)) {
+                await resumeAccount(accountId);
+                showToast(t('accounts.toast.resumed'), 'success');
+            } else {
+                await pauseAccount(accountId, 60, t('accounts.pause_reason_manual'));
+                showToast(t('accounts.toast.paused'), 'success');
+            }
+        } catch (error) {
+            console.error('[Accounts] Toggle pause failed:', error);
+            showToast(`${t('common.error')}: ${error}`, 'error');
+        }
+    };
+
     const handleBatchToggleProxy = async (enable: boolean) => {
         if (selectedIds.size === 0) return;
 
@@ -825,6 +845,7 @@ function Accounts() {
                                 onExport={handleExportOne}
                                 onDelete={handleDelete}
                                 onToggleProxy={(id) => handleToggleProxy(id, !!accounts.find(a => a.id === id)?.proxy_disabled)}
+                                onTogglePause={handleTogglePause}
                                 onReorder={reorderAccounts}
                                 onWarmup={handleWarmup}
                             />
@@ -846,6 +867,7 @@ function Accounts() {
                             onExport={handleExportOne}
                             onDelete={handleDelete}
                             onToggleProxy={(id) => handleToggleProxy(id, !!accounts.find(a => a.id === id)?.proxy_disabled)}
+                            onTogglePause={handleTogglePause}
                             onWarmup={handleWarmup}
                         />
                     </div>
@@ -20,9 +20,11 @@ import {
     Code,
     Check,
     X,
-    Edit2
+    Edit2,
+    Globe,
+    KeyRound
 } from 'lucide-react';
-import { AppConfig, ProxyConfig, StickySessionConfig, ExperimentalConfig } from '../types/config';
+import { AppConfig, ProxyConfig, StickySessionConfig, ExperimentalConfig, OpenAICompatBackend, VertexConfig, AiStudioConfig, CapabilityGating } from '../types/config';
 import HelpTooltip from '../components/common/HelpTooltip';
 import ModalDialog from '../components/common/ModalDialog';
 import { showToast } from '../components/common/ToastContainer';
@@ -30,6 +32,13 @@ import { cn } from '../utils/cn';
 import { useProxyModels } from '../hooks/useProxyModels';
 import GroupedSelect, { SelectOption } from '../components/common/GroupedSelect';
 import { CliSyncCard } from '../components/proxy/CliSyncCard';
+import { CapabilityProbePanel } from '../components/proxy/CapabilityProbePanel';
+import { ResponseRewriteRules } from '../components/proxy/ResponseRewriteRules';
+import { DEFAULT_TRANSLATION_CONFIG, TranslationSettings } from '../components/proxy/TranslationSettings';
+import { DEFAULT_LOCAL_FILES_CONFIG, LocalFilesSettings } from '../components/proxy/LocalFilesSettings';
+import { DEFAULT_IMAGE_OUTPUT_CONFIG, ImageOutputSettings } from '../components/proxy/ImageOutputSettings';
+import { DEFAULT_MODEL_LIMITS_CONFIG, ModelLimitsSettings } from '../components/proxy/ModelLimitsSettings';
+import { DEFAULT_QUIRKS_CONFIG, QuirksSettings } from '../components/proxy/QuirksSettings';
 
 interface ProxyStatus {
     running: boolean;
@@ -516,6 +525,37 @@ export default function ApiProxy() {
         saveConfig(newConfig);
     };
 
+    const updateOpenAIBackends = (backends: OpenAICompatBackend[]) => {
+        if (!appConfig) return;
+        saveConfig({
+            ...appConfig,
+            proxy: { ...appConfig.proxy, openai_backends: backends }
+        });
+    };
+
+    const updateOpenAIBackend = (index: number, updates: Partial<OpenAICompatBackend>) => {
+        const backends = appConfig?.proxy.openai_backends || [];
+        updateOpenAIBackends(backends.map((b, i) => (i === index ? { ...b, ...updates } : b)));
+    };
+
+    const updateVertexConfig = (updates: Partial<VertexConfig>) => {
+        if (!appConfig) return;
+        const current: VertexConfig = appConfig.proxy.vertex || { enabled: false, credentials_path: '', project_id: '', location: 'us-central1', models: [] };
+        saveConfig({
+            ...appConfig,
+            proxy: { ...appConfig.proxy, vertex: { ...current, ...updates } }
+        });
+    };
+
+    const updateAiStudioConfig = (updates: Partial<AiStudioConfig>) => {
+        if (!appConfig) return;
+        const current: AiStudioConfig = appConfig.proxy.ai_studio || { fallback: true, models: [], daily_request_limit: 0 };
+        saveConfig({
+            ...appConfig,
+            proxy: { ...appConfig.proxy, ai_studio: { ...current, ...updates } }
+        });
+    };
+
     const handleToggle = async () => {
         if (!appConfig) return;
         setLoading(true);
@@ -866,6 +906,40 @@ print(response.text)`;
                                                 {t('proxy.config.allow_lan_access_warning')}
                                             </p>
                                         )}
+                                        {(appConfig.proxy.allow_lan_access || false) && (
+                                            <label className="inline-flex items-center gap-1.5 text-[11px] text-gray-600 dark:text-gray-400 cursor-pointer">
+                                                <input
+                                                    type="checkbox"
+                                                    className="checkbox checkbox-xs"
+                                                    checked={appConfig.proxy.mdns_advertise ?? true}
+                                                    onChange={(e) => updateProxyConfig({ mdns_advertise: e.target.checked })}
+                                                />
+                                                {t('proxy.config.mdns_advertise')}
+                                                <HelpTooltip
+                                                    text={t('proxy.config.mdns_advertise_hint')}
+                                                    ariaLabel={t('proxy.config.mdns_advertise')}
+                                                    placement="right"
+                                                />
+                                            </label>
+                                        )}
+                                        <div className="flex items-center gap-4 pt-1">
+                                            {(['listen_ipv4', 'listen_ipv6'] as const).map((key) => (
+                                                <label key={key} className="inline-flex items-center gap-1.5 text-[11px] text-gray-600 dark:text-gray-400 cursor-pointer">
+                                                    <input
+                                                        type="checkbox"
+                                                        className="checkbox checkbox-xs"
+                                                        checked={appConfig.proxy[key] ?? true}
+                                                        onChange={(e) => updateProxyConfig({ [key]: e.target.checked })}
+                                                    />
+                                                    {t(`proxy.config.${key}`)}
+                                                </label>
+                                            ))}
+                                            <HelpTooltip
+                                                text={t('proxy.config.listen_family_hint')}
+                                                ariaLabel={t('proxy.config.listen_ipv6')}
+                                                placement="right"
+                                            />
+                                        </div>
                                         {status.running && (
                                             <p className="text-[10px] text-blue-600 dark:text-blue-400">
                                                 {t('proxy.config.allow_lan_access_restart_hint')}
@@ -1194,6 +1268,186 @@ print(response.text)`;
                                 </div>
                             </CollapsibleCard>
 
+                            {/* OpenAI-compatible upstreams */}
+                            <CollapsibleCard
+                                title={t('proxy.config.openai_backends.title')}
+                                icon={<Globe size={18} className="text-emerald-500" />}
+                            >
+                                <div className="space-y-3">
+                                    <p className="text-[11px] text-gray-500 dark:text-gray-400">
+                                        {t('proxy.config.openai_backends.desc')}
+                                    </p>
+                                    {(appConfig.proxy.openai_backends || []).map((backend, index) => (
+                                        <div key={index} className="p-3 rounded-lg border border-gray-100 dark:border-base-200 space-y-2">
+                                            <div className="flex items-center gap-2">
+                                                <input
+                                                    type="checkbox"
+                                                    className="toggle toggle-xs"
+                                                    checked={backend.enabled}
+                                                    onChange={(e) => updateOpenAIBackend(index, { enabled: e.target.checked })}
+                                                />
+                                                <input
+                                                    type="text"
+                                                    value={backend.name}
+                                                    onChange={(e) => updateOpenAIBackend(index, { name: e.target.value.replace(/[:\s]/g, '') })}
+                                                    placeholder="openrouter"
+                                                    className="input input-xs input-bordered w-32 font-mono"
+                                                />
+                                                <input
+                                                    type="text"
+                                                    value={backend.base_url}
+                                                    onChange={(e) => updateOpenAIBackend(index, { base_url: e.target.value })}
+                                                    placeholder="https://openrouter.ai/api/v1"
+                                                    className="input input-xs input-bordered flex-1 font-mono"
+                                                />
+                                                <button
+                                                    className="btn btn-ghost btn-xs text-gray-400 hover:text-red-500"
+                                                    onClick={() => updateOpenAIBackends((appConfig.proxy.openai_backends || []).filter((_, i) => i !== index))}
+                                                >
+                                                    <Trash2 size={14} />
+                                                </button>
+                                            </div>
+                                            <div className="grid grid-cols-1 md:grid-cols-2 gap-2">
+                                                <input
+                                                    type="password"
+                                                    value={backend.api_key}
+                                                    onChange={(e) => updateOpenAIBackend(index, { api_key: e.target.value })}
+                                                    placeholder={t('proxy.config.openai_backends.api_key_placeholder')}
+                                                    className="input input-xs input-bordered w-full font-mono"
+                                                />
+                                                <input
+                                                    type="text"
+                                                    value={backend.models.join(', ')}
+                                                    onChange={(e) => updateOpenAIBackend(index, { models: e.target.value.split(',').map(m => m.trim()).filter(Boolean) })}
+                                                    placeholder={t('proxy.config.openai_backends.models_placeholder')}
+                                                    className="input input-xs input-bordered w-full font-mono"
+                                                />
+                                            </div>
+                                        </div>
+                                    ))}
+                                    <button
+                                        className="btn btn-xs btn-outline gap-1"
+                                        onClick={() => updateOpenAIBackends([
+                                            ...(appConfig.proxy.openai_backends || []),
+                                            { enabled: true, name: `backend${(appConfig.proxy.openai_backends || []).length + 1}`, base_url: '', api_key: '', models: [] }
+                                        ])}
+                                    >
+                                        <Plus size={12} />
+                                        {t('proxy.config.openai_backends.add')}
+                                    </button>
+                                </div>
+                            </CollapsibleCard>
+
+                            {/* Vertex AI (Service Account) */}
+                            <CollapsibleCard
+                                title={t('proxy.config.vertex.title')}
+                                icon={<Layers size={18} className="text-blue-500" />}
+                                enabled={!!appConfig.proxy.vertex?.enabled}
+                                onToggle={(checked) => updateVertexConfig({ enabled: checked })}
+                            >
+                                <div className="space-y-3">
+                                    <p className="text-[11px] text-gray-500 dark:text-gray-400">
+                                        {t('proxy.config.vertex.desc')}
+                                    </p>
+                                    <div className="space-y-1">
+                                        <label className="text-[11px] font-medium text-gray-500 dark:text-gray-400">
+                                            {t('proxy.config.vertex.credentials_path')}
+                                        </label>
+                                        <input
+                                            type="text"
+                                            value={appConfig.proxy.vertex?.credentials_path || ''}
+                                            onChange={(e) => updateVertexConfig({ credentials_path: e.target.value })}
+                                            placeholder="/path/to/service-account.json"
+                                            className="input input-sm input-bordered w-full font-mono text-xs"
+                                        />
+                                    </div>
+                                    <div className="grid grid-cols-1 md:grid-cols-3 gap-3">
+                                        <div className="space-y-1">
+                                            <label className="text-[11px] font-medium text-gray-500 dark:text-gray-400">
+                                                {t('proxy.config.vertex.project_id')}
+                                            </label>
+                                            <input
+                                                type="text"
+                                                value={appConfig.proxy.vertex?.project_id || ''}
+                                                onChange={(e) => updateVertexConfig({ project_id: e.target.value })}
+                                                placeholder={t('proxy.config.vertex.project_id_placeholder')}
+                                                className="input input-sm input-bordered w-full font-mono text-xs"
+                                            />
+                                        </div>
+                                        <div className="space-y-1">
+                                            <label className="text-[11px] font-medium text-gray-500 dark:text-gray-400">
+                                                {t('proxy.config.vertex.location')}
+                                            </label>
+                                            <input
+                                                type="text"
+                                                value={appConfig.proxy.vertex?.location || 'us-central1'}
+                                                onChange={(e) => updateVertexConfig({ location: e.target.value })}
+                                                className="input input-sm input-bordered w-full font-mono text-xs"
+                                            />
+                                        </div>
+                                        <div className="space-y-1">
+                                            <label className="text-[11px] font-medium text-gray-500 dark:text-gray-400">
+                                                {t('proxy.config.vertex.models')}
+                                            </label>
+                                            <input
+                                                type="text"
+                                                value={(appConfig.proxy.vertex?.models || []).join(', ')}
+                                                onChange={(e) => updateVertexConfig({ models: e.target.value.split(',').map(m => m.trim()).filter(Boolean) })}
+                                                placeholder="gemini-2.5-pro*"
+                                                className="input input-sm input-bordered w-full font-mono text-xs"
+                                            />
+                                        </div>
+                                    </div>
+                                </div>
+                            </CollapsibleCard>
+
+                            {/* AI Studio API Keys */}
+                            <CollapsibleCard
+                                title={t('proxy.config.ai_studio.title')}
+                                icon={<KeyRound size={18} className="text-amber-500" />}
+                            >
+                                <div className="space-y-3">
+                                    <p className="text-[11px] text-gray-500 dark:text-gray-400">
+                                        {t('proxy.config.ai_studio.desc')}
+                                    </p>
+                                    <label className="flex items-center gap-2 text-xs text-gray-700 dark:text-gray-300 cursor-pointer">
+                                        <input
+                                            type="checkbox"
+                                            className="checkbox checkbox-sm"
+                                            checked={appConfig.proxy.ai_studio?.fallback ?? true}
+                                            onChange={(e) => updateAiStudioConfig({ fallback: e.target.checked })}
+                                        />
+                                        {t('proxy.config.ai_studio.fallback')}
+                                    </label>
+                                    <div className="grid grid-cols-1 md:grid-cols-2 gap-3">
+                                        <div className="space-y-1">
+                                            <label className="text-[11px] font-medium text-gray-500 dark:text-gray-400">
+                                                {t('proxy.config.ai_studio.daily_limit')}
+                                            </label>
+                                            <input
+                                                type="number"
+                                                min={0}
+                                                value={appConfig.proxy.ai_studio?.daily_request_limit ?? 0}
+                                                onChange={(e) => updateAiStudioConfig({ daily_request_limit: Math.max(0, parseInt(e.target.value) || 0) })}
+                                                className="input input-sm input-bordered w-full font-mono text-xs"
+                                            />
+                                        </div>
+                                        <div className="space-y-1">
+                                            <label className="text-[11px] font-medium text-gray-500 dark:text-gray-400">
+                                                {t('proxy.config.ai_studio.models')}
+                                            </label>
+                                            <input
+                                                type="text"
+                                                value={(appConfig.proxy.ai_studio?.models || []).join(', ')}
+                                                onChange={(e) => updateAiStudioConfig({ models: e.target.value.split(',').map(m => m.trim()).filter(Boolean) })}
+                                                placeholder="gemini-2.5-flash*"
+                                                className="input input-sm input-bordered w-full font-mono text-xs"
+                                            />
+                                        </div>
+                                    </div>
+                                </div>
+                            </CollapsibleCard>
+
                             {/* MCP System */}
                             <CollapsibleCard
                                 title={t('proxy.config.zai.mcp.title')}
@@ -1281,7 +1535,7 @@ print(response.text)`;
                                                 </button>
                                             </div>
                                             <div className="grid grid-cols-1 gap-2">
-                                                {(['CacheFirst', 'Balance', 'PerformanceFirst'] as const).map(mode => (
+                                                {(['CacheFirst', 'Balance', 'PerformanceFirst', 'Fairness'] as const).map(mode => (
                                                     <label
                                                         key={mode}
                                                         className={`flex items-start gap-3 p-3 rounded-xl border cursor-pointer transition-all duration-200 ${(appConfig.proxy.scheduling?.mode || 'Balance') === mode
@@ -1426,6 +1680,134 @@ print(response.text)`;
                                             <div className="w-11 h-6 bg-gray-200 dark:bg-base-300 peer-focus:outline-none rounded-full peer peer-checked:after:translate-x-full peer-checked:after:border-white after:content-[''] after:absolute after:top-[2px] after:left-[2px] after:bg-white after:border-gray-300 after:border after:rounded-full after:h-5 after:w-5 after:transition-all peer-checked:bg-purple-500 shadow-inner"></div>
                                         </label>
                                     </div>
+
+                                    <div className="flex items-center justify-between p-4 bg-gray-50 dark:bg-base-200 rounded-xl border border-gray-100 dark:border-base-300">
+                                        <div className="space-y-1">
+                                            <div className="flex items-center gap-2">
+                                                <span className="text-sm font-bold text-gray-900 dark:text-base-content">
+                                                    {t('proxy.config.experimental.capability_gating')}
+                                                </span>
+                                                <HelpTooltip text={t('proxy.config.experimental.capability_gating_tooltip')} />
+                                            </div>
+                                            <p className="text-[10px] text-gray-500 dark:text-gray-400 max-w-lg">
+                                                {t('proxy.config.experimental.capability_gating_tooltip')}
+                                            </p>
+                                        </div>
+                                        <select
+                                            className="select select-sm select-bordered text-xs"
+                                            value={appConfig.proxy.experimental?.capability_gating || 'degrade'}
+                                            onChange={(e) => updateExperimentalConfig({ capability_gating: e.target.value as CapabilityGating })}
+                                        >
+                                            <option value="degrade">{t('proxy.config.experimental.capability_gating_degrade')}</option>
+                                            <option value="reject">{t('proxy.config.experimental.capability_gating_reject')}</option>
+                                            <option value="off">{t('proxy.config.experimental.capability_gating_off')}</option>
+                                        </select>
+                                    </div>
+
+                                    <div className="flex items-center justify-between p-4 bg-gray-50 dark:bg-base-200 rounded-xl border border-gray-100 dark:border-base-300">
+                                        <div className="space-y-1">
+                                            <div className="flex items-center gap-2">
+                                                <span className="text-sm font-bold text-gray-900 dark:text-base-content">
+                                                    {t('proxy.config.experimental.stream_stats')}
+                                                </span>
+                                                <HelpTooltip text={t('proxy.config.experimental.stream_stats_tooltip')} />
+                                            </div>
+                                            <p className="text-[10px] text-gray-500 dark:text-gray-400 max-w-lg">
+                                                {t('proxy.config.experimental.stream_stats_tooltip')}
+                                            </p>
+                                        </div>
+                                        <label className="relative inline-flex items-center cursor-pointer">
+                                            <input
+                                                type="checkbox"
+                                                className="sr-only peer"
+                                                checked={appConfig.proxy.experimental?.stream_stats ?? true}
+                                                onChange={(e) => updateExperimentalConfig({ stream_stats: e.target.checked })}
+                                            />
+                                            <div className="w-11 h-6 bg-gray-200 dark:bg-base-300 peer-focus:outline-none rounded-full peer peer-checked:after:translate-x-full peer-checked:after:border-white after:content-[''] after:absolute after:top-[2px] after:left-[2px] after:bg-white after:border-gray-300 after:border after:rounded-full after:h-5 after:w-5 after:transition-all peer-checked:bg-purple-500 shadow-inner"></div>
+                                        </label>
+                                    </div>
+
+                                    <div className="flex items-center justify-between p-4 bg-gray-50 dark:bg-base-200 rounded-xl border border-gray-100 dark:border-base-300">
+                                        <div className="space-y-1">
+                                            <div className="flex items-center gap-2">
+                                                <span className="text-sm font-bold text-gray-900 dark:text-base-content">
+                                                    {t('proxy.config.experimental.markdown_images')}
+                                                </span>
+                                                <HelpTooltip text={t('proxy.config.experimental.markdown_images_tooltip')} />
+                                            </div>
+                                            <p className="text-[10px] text-gray-500 dark:text-gray-400 max-w-lg">
+                                                {t('proxy.config.experimental.markdown_images_tooltip')}
+                                            </p>
+                                        </div>
+                                        <label className="relative inline-flex items-center cursor-pointer">
+                                            <input
+                                                type="checkbox"
+                                                className="sr-only peer"
+                                                checked={appConfig.proxy.experimental?.markdown_images ?? false}
+                                                onChange={(e) => updateExperimentalConfig({ markdown_images: e.target.checked })}
+                                            />
+                                            <div className="w-11 h-6 bg-gray-200 dark:bg-base-300 peer-focus:outline-none rounded-full peer peer-checked:after:translate-x-full peer-checked:after:border-white after:content-[''] after:absolute after:top-[2px] after:left-[2px] after:bg-white after:border-gray-300 after:border after:rounded-full after:h-5 after:w-5 after:transition-all peer-checked:bg-purple-500 shadow-inner"></div>
+                                        </label>
+                                    </div>
+
+                                    <div className="flex items-center justify-between p-4 bg-gray-50 dark:bg-base-200 rounded-xl border border-gray-100 dark:border-base-300">
+                                        <div className="space-y-1">
+                                            <div className="flex items-center gap-2">
+                                                <span className="text-sm font-bold text-gray-900 dark:text-base-content">
+                                                    {t('proxy.config.experimental.markdown_images_local_files')}
+                                                </span>
+                                                <HelpTooltip text={t('proxy.config.experimental.markdown_images_local_files_tooltip')} />
+                                            </div>
+                                            <p className="text-[10px] text-gray-500 dark:text-gray-400 max-w-lg">
+                                                {t('proxy.config.experimental.markdown_images_local_files_tooltip')}
+                                            </p>
+                                        </div>
+                                        <label className="relative inline-flex items-center cursor-pointer">
+                                            <input
+                                                type="checkbox"
+                                                className="sr-only peer"
+                                                checked={appConfig.proxy.experimental?.markdown_images_local_files ?? false}
+                                                disabled={!(appConfig.proxy.experimental?.markdown_images ?? false)}
+                                                onChange={(e) => updateExperimentalConfig({ markdown_images_local_files: e.target.checked })}
+                                            />
+                                            <div className="w-11 h-6 bg-gray-200 dark:bg-base-300 peer-focus:outline-none rounded-full peer peer-checked:after:translate-x-full peer-checked:after:border-white after:content-[''] after:absolute after:top-[2px] after:left-[2px] after:bg-white after:border-gray-300 after:border after:rounded-full after:h-5 after:w-5 after:transition-all peer-checked:bg-purple-500 shadow-inner"></div>
+                                        </label>
+                                    </div>
+
+                                    <CapabilityProbePanel
+                                        probeOnAdd={appConfig.proxy.experimental?.capability_probe_on_add ?? true}
+                                        onProbeOnAddChange={(value) => updateExperimentalConfig({ capability_probe_on_add: value })}
+                                    />
+
+                                    <ResponseRewriteRules
+                                        rules={appConfig.proxy.experimental?.response_rewrites ?? []}
+                                        onChange={(rules) => updateExperimentalConfig({ response_rewrites: rules })}
+                                    />
+
+                                    <TranslationSettings
+                                        config={appConfig.proxy.experimental?.translation ?? DEFAULT_TRANSLATION_CONFIG}
+                                        onChange={(translation) => updateExperimentalConfig({ translation })}
+                                    />
+
+                                    <LocalFilesSettings
+                                        config={appConfig.proxy.experimental?.local_files ?? DEFAULT_LOCAL_FILES_CONFIG}
+                                        onChange={(local_files) => updateExperimentalConfig({ local_files })}
+                                    />
+
+                                    <ImageOutputSettings
+                                        config={appConfig.proxy.experimental?.image_output ?? DEFAULT_IMAGE_OUTPUT_CONFIG}
+                                        onChange={(image_output) => updateExperimentalConfig({ image_output })}
+                                    />
+
+                                    <ModelLimitsSettings
+                                        config={appConfig.proxy.experimental?.model_limits ?? DEFAULT_MODEL_LIMITS_CONFIG}
+                                        onChange={(model_limits) => updateExperimentalConfig({ model_limits })}
+                                    />
+
+                                    <QuirksSettings
+                                        config={appConfig.proxy.experimental?.quirks ?? DEFAULT_QUIRKS_CONFIG}
+                                        onChange={(quirks) => updateExperimentalConfig({ quirks })}
+                                    />
                                 </div>
                             </CollapsibleCard>
                         </div>
@@ -1,18 +1,27 @@
 import { useState, useEffect } from 'react';
-import { Save, Github, User, MessageCircle, ExternalLink, RefreshCw, Sparkles, Heart, Coffee } from 'lucide-react';
+import { Save, Github, User, MessageCircle, ExternalLink, RefreshCw, Sparkles, Heart, Coffee, FileText, Activity } from 'lucide-react';
 import { request as invoke } from '../utils/request';
 import { open } from '@tauri-apps/plugin-dialog';
 import { useConfigStore } from '../stores/useConfigStore';
-import { AppConfig } from '../types/config';
+import { AppConfig, LaunchMode, LogFormat, TelemetryConfig, UpdateChannel, UpdateSettings } from '../types/config';
 import ModalDialog from '../components/common/ModalDialog';
 import { showToast } from '../components/common/ToastContainer';
 import QuotaProtection from '../components/settings/QuotaProtection';
 import SmartWarmup from '../components/settings/SmartWarmup';
 import PinnedQuotaModels from '../components/settings/PinnedQuotaModels';
+import Notifications from '../components/settings/Notifications';
 
 import { useTranslation } from 'react-i18next';
 
 
+const DEFAULT_TELEMETRY: TelemetryConfig = {
+    enabled: false,
+    endpoint: 'http://127.0.0.1:4318/v1/traces',
+    service_name: 'antigravity-proxy',
+    sample_ratio: 1,
+    headers: {},
+};
+
 function Settings() {
     const { t } = useTranslation();
     const { config, loadConfig, saveConfig } = useConfigStore();
@@ -52,6 +61,7 @@ function Settings() {
 
     // Dialog state
     // Dialog state
+    const [launchMode, setLaunchMode] = useState<LaunchMode>('off');
     const [isClearLogsOpen, setIsClearLogsOpen] = useState(false);
     const [isSupportModalOpen, setIsSupportModalOpen] = useState(false);
     const [dataDirPath, setDataDirPath] = useState<string>('~/.antigravity_tools/');
@@ -73,6 +83,20 @@ function Settings() {
     const [httpApiPortInput, setHttpApiPortInput] = useState('19527');
     const [httpApiSettingsChanged, setHttpApiSettingsChanged] = useState(false);
 
+    // 保存更新设置 (未传入的字段沿用当前表单值)
+    const saveUpdateSettings = async (patch: Partial<UpdateSettings>) => {
+        const settings: UpdateSettings = {
+            auto_check: formData.auto_check_update ?? true,
+            last_check_time: 0,
+            check_interval_hours: formData.update_check_interval ?? 24,
+            channel: formData.update_channel ?? 'stable',
+            auto_download: formData.update_auto_download ?? false,
+            install_on_quit: formData.update_install_on_quit ?? true,
+            ...patch
+        };
+        await invoke('save_update_settings', { settings });
+    };
+
     useEffect(() => {
         loadConfig();
 
@@ -82,20 +106,24 @@ function Settings() {
             .catch(err => console.error('Failed to get data dir:', err));
 
         // 加载更新设置
-        invoke<{ auto_check: boolean; last_check_time: number; check_interval_hours: number }>('get_update_settings')
+        invoke<UpdateSettings>('get_update_settings')
             .then(settings => {
                 setFormData(prev => ({
                     ...prev,
                     auto_check_update: settings.auto_check,
-                    update_check_interval: settings.check_interval_hours
+                    update_check_interval: settings.check_interval_hours,
+                    update_channel: settings.channel,
+                    update_auto_download: settings.auto_download,
+                    update_install_on_quit: settings.install_on_quit
                 }));
             })
             .catch(err => console.error('Failed to load update settings:', err));
 
         // 获取真实的开机自启状态
-        invoke<boolean>('is_auto_launch_enabled')
-            .then(enabled => {
-                setFormData(prev => ({ ...prev, auto_launch: enabled }));
+        invoke<LaunchMode>('get_launch_mode')
+            .then(mode => {
+                setLaunchMode(mode);
+                setFormData(prev => ({ ...prev, auto_launch: mode !== 'off' }));
             })
             .catch(err => console.error('Failed to get auto launch status:', err));
 
@@ -322,22 +350,25 @@ function Settings() {
                                 <label className="block text-sm font-medium text-gray-900 dark:text-base-content mb-2">{t('settings.general.auto_launch')}</label>
                                 <select
                                     className="w-full px-4 py-4 border border-gray-200 dark:border-base-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-transparent text-gray-900 dark:text-base-content bg-gray-50 dark:bg-base-200"
-                                    value={formData.auto_launch ? 'enabled' : 'disabled'}
+                                    value={launchMode}
                                     onChange={async (e) => {
-                                        const enabled = e.target.value === 'enabled';
                                         try {
-                                            await invoke('toggle_auto_launch', { enable: enabled });
-                                            setFormData({ ...formData, auto_launch: enabled });
-                                            showToast(enabled ? t('settings.general.auto_launch_enabled') : t('settings.general.auto_launch_disabled'), 'success');
+                                            const mode = await invoke<LaunchMode>('set_launch_mode', { mode: e.target.value as LaunchMode });
+                                            setLaunchMode(mode);
+                                            setFormData({ ...formData, auto_launch: mode !== 'off' });
+                                            showToast(mode !== 'off' ? t('settings.general.auto_launch_enabled') : t('settings.general.auto_launch_disabled'), 'success');
                                         } catch (error) {
                                             showToast(`${t('common.error')}: ${error}`, 'error');
                                         }
                                     }}
                                 >
-                                    <option value="disabled">{t('settings.general.auto_launch_disabled')}</option>
-                                    <option value="enabled">{t('settings.general.auto_launch_enabled')}</option>
+                                    <option value="off">{t('settings.general.auto_launch_disabled')}</option>
+                                    <option value="app">{t('settings.general.auto_launch_enabled')}</option>
+                                    <option value="headless">{t('settings.general.auto_launch_headless')}</option>
                                 </select>
-                                <p className="text-sm text-gray-500 dark:text-gray-400 mt-2">{t('settings.general.auto_launch_desc')}</p>
+                                <p className="text-sm text-gray-500 dark:text-gray-400 mt-2">
+                                    {t(launchMode === 'headless' ? 'settings.general.auto_launch_headless_desc' : 'settings.general.auto_launch_desc')}
+                                </p>
                             </div>
 
                             {/* 自动检查更新 */}
@@ -354,13 +385,7 @@ function Settings() {
                                         onChange={async (e) => {
                                             const enabled = e.target.checked;
                                             try {
-                                                await invoke('save_update_settings', {
-                                                    settings: {
-                                                        auto_check: enabled,
-                                                        last_check_time: 0,
-                                                        check_interval_hours: formData.update_check_interval ?? 24
-                                                    }
-                                                });
+                                                await saveUpdateSettings({ auto_check: enabled });
                                                 setFormData({ ...formData, auto_check_update: enabled });
                                                 showToast(enabled ? t('settings.general.auto_check_update_enabled') : t('settings.general.auto_check_update_disabled'), 'success');
                                             } catch (error) {
@@ -385,13 +410,7 @@ function Settings() {
                                         onChange={(e) => setFormData({ ...formData, update_check_interval: parseInt(e.target.value) })}
                                         onBlur={async () => {
                                             try {
-                                                await invoke('save_update_settings', {
-                                                    settings: {
-                                                        auto_check: formData.auto_check_update ?? true,
-                                                        last_check_time: 0,
-                                                        check_interval_hours: formData.update_check_interval ?? 24
-                                                    }
-                                                });
+                                                await saveUpdateSettings({});
                                                 showToast(t('settings.general.update_check_interval_saved'), 'success');
                                             } catch (error) {
                                                 showToast(`${t('common.error')}: ${error}`, 'error');
@@ -401,6 +420,61 @@ function Settings() {
                                     <p className="text-sm text-gray-500 dark:text-gray-400 mt-2">{t('settings.general.update_check_interval_desc')}</p>
                                 </div>
                             )}
+
+                            {/* 更新通道与推迟安装 */}
+                            <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-lg border border-gray-100 dark:border-base-300 space-y-3">
+                                <div className="flex items-center justify-between">
+                                    <div>
+                                        <div className="font-medium text-gray-900 dark:text-base-content">{t('settings.general.update_channel')}</div>
+                                        <p className="text-sm text-gray-600 dark:text-gray-400 mt-1">{t('settings.general.update_channel_desc')}</p>
+                                    </div>
+                                    <select
+                                        className="select select-sm select-bordered"
+                                        value={formData.update_channel ?? 'stable'}
+                                        onChange={async (e) => {
+                                            const channel = e.target.value as UpdateChannel;
+                                            try {
+                                                await saveUpdateSettings({ channel });
+                                                setFormData({ ...formData, update_channel: channel });
+                                                showToast(t('settings.general.update_settings_saved'), 'success');
+                                            } catch (error) {
+                                                showToast(`${t('common.error')}: ${error}`, 'error');
+                                            }
+                                        }}
+                                    >
+                                        <option value="stable">{t('settings.general.update_channel_stable')}</option>
+                                        <option value="beta">{t('settings.general.update_channel_beta')}</option>
+                                    </select>
+                                </div>
+                                {([
+                                    ['update_auto_download', 'auto_download', false],
+                                    ['update_install_on_quit', 'install_on_quit', true],
+                                ] as const).map(([formKey, settingKey, fallback]) => (
+                                    <label key={formKey} className="flex items-start gap-2 cursor-pointer">
+                                        <input
+                                            type="checkbox"
+                                            className="checkbox checkbox-sm mt-0.5"
+                                            checked={formData[formKey] ?? fallback}
+                                            onChange={async (e) => {
+                                                const enabled = e.target.checked;
+                                                try {
+                                                    await saveUpdateSettings({ [settingKey]: enabled });
+                                                    setFormData({ ...formData, [formKey]: enabled });
+                                                    showToast(t('settings.general.update_settings_saved'), 'success');
+                                                } catch (error) {
+                                                    showToast(`${t('common.error')}: ${error}`, 'error');
+                                                }
+                                            }}
+                                        />
+                                        <span>
+                                            <span className="text-sm text-gray-900 dark:text-base-content">{t(`settings.general.${formKey}`)}</span>
+                                            {formKey === 'update_auto_download' && (
+                                                <p className="text-xs text-gray-500 dark:text-gray-400">{t('settings.general.update_auto_download_desc')}</p>
+                                            )}
+                                        </span>
+                                    </label>
+                                ))}
+                            </div>
                         </div>
                     )}
 
@@ -510,6 +584,17 @@ function Settings() {
                                     })}
                                 />
                             </div>
+
+                            {/* 账号事件通知 (Notifications) */}
+                            <div className="group bg-white dark:bg-base-100 rounded-xl p-5 border border-gray-100 dark:border-base-200 hover:border-amber-200 transition-all duration-300 shadow-sm">
+                                <Notifications
+                                    config={formData.notifications}
+                                    onChange={(newConfig) => setFormData({
+                                        ...formData,
+                                        notifications: newConfig
+                                    })}
+                                />
+                            </div>
                         </div>
                     )}
 
@@ -728,6 +813,18 @@ function Settings() {
                                 <div className="badge badge-primary badge-outline gap-2 font-mono">
                                     v3.3.45
                                 </div>
+                                <div className="mb-3">
+                                    <label className="block text-sm font-medium text-gray-900 dark:text-base-content mb-2">{t('settings.advanced.log_format')}</label>
+                                    <select
+                                        className="w-full px-4 py-2 border border-gray-200 dark:border-base-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-transparent text-gray-900 dark:text-base-content bg-gray-50 dark:bg-base-200"
+                                        value={formData.proxy.log_format ?? 'text'}
+                                        onChange={(e) => setFormData({ ...formData, proxy: { ...formData.proxy, log_format: e.target.value as LogFormat } })}
+                                    >
+                                        <option value="text">{t('settings.advanced.log_format_text')}</option>
+                                        <option value="json">{t('settings.advanced.log_format_json')}</option>
+                                    </select>
+                                    <p className="text-sm text-gray-500 dark:text-gray-400 mt-2">{t('settings.advanced.log_format_desc')}</p>
+                                </div>
                                 <div className="flex items-center gap-4">
                                     <button
                                         className="px-4 py-2 border border-gray-300 dark:border-base-300 text-gray-700 dark:text-gray-300 rounded-lg hover:bg-gray-100 dark:hover:bg-base-200 transition-colors"
@@ -808,6 +905,186 @@ function Settings() {
                                     </div>
                                 </div>
                             </div>
+
+                            <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-lg border border-gray-100 dark:border-base-300">
+                                <h3 className="text-md font-semibold text-gray-900 dark:text-base-content mb-3 flex items-center gap-2">
+                                    <FileText size={18} className="text-blue-500" />
+                                    {t('proxy.config.stream_tee.title')}
+                                </h3>
+                                <p className="text-sm text-gray-600 dark:text-gray-400 mb-4">
+                                    {t('proxy.config.stream_tee.desc')}
+                                </p>
+
+                                <div className="space-y-4">
+                                    <div className="flex items-center">
+                                        <label className="flex items-center cursor-pointer gap-3">
+                                            <div className="relative">
+                                                <input
+                                                    type="checkbox"
+                                                    className="sr-only"
+                                                    checked={formData.proxy?.stream_tee?.enabled || false}
+                                                    onChange={(e) => setFormData({
+                                                        ...formData,
+                                                        proxy: {
+                                                            ...formData.proxy,
+                                                            stream_tee: {
+                                                                directory: '',
+                                                                ...formData.proxy.stream_tee,
+                                                                enabled: e.target.checked
+                                                            }
+                                                        }
+                                                    })}
+                                                />
+                                                <div className={`block w-14 h-8 rounded-full transition-colors ${formData.proxy?.stream_tee?.enabled ? 'bg-blue-500' : 'bg-gray-300 dark:bg-base-300'}`}></div>
+                                                <div className={`dot absolute left-1 top-1 bg-white w-6 h-6 rounded-full transition-transform ${formData.proxy?.stream_tee?.enabled ? 'transform translate-x-6' : ''}`}></div>
+                                            </div>
+                                            <span className="text-sm font-medium text-gray-900 dark:text-base-content">
+                                                {t('proxy.config.stream_tee.enable')}
+                                            </span>
+                                        </label>
+                                    </div>
+
+                                    <div>
+                                        <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
+                                            {t('proxy.config.stream_tee.directory')}
+                                        </label>
+                                        <input
+                                            type="text"
+                                            value={formData.proxy?.stream_tee?.directory || ''}
+                                            onChange={(e) => setFormData({
+                                                ...formData,
+                                                proxy: {
+                                                    ...formData.proxy,
+                                                    stream_tee: {
+                                                        enabled: false,
+                                                        ...formData.proxy.stream_tee,
+                                                        directory: e.target.value
+                                                    }
+                                                }
+                                            })}
+                                            placeholder={t('proxy.config.stream_tee.directory_placeholder')}
+                                            className="w-full px-4 py-4 border border-gray-200 dark:border-base-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-transparent text-gray-900 dark:text-base-content bg-gray-50 dark:bg-base-200"
+                                        />
+                                        <p className="mt-1 text-xs text-gray-500 dark:text-gray-400">
+                                            {t('proxy.config.stream_tee.tip')}
+                                        </p>
+                                    </div>
+                                </div>
+                            </div>
+
+                            <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-lg border border-gray-100 dark:border-base-300">
+                                <h3 className="text-md font-semibold text-gray-900 dark:text-base-content mb-3 flex items-center gap-2">
+                                    <Activity size={18} className="text-blue-500" />
+                                    {t('proxy.config.telemetry.title')}
+                                </h3>
+                                <p className="text-sm text-gray-600 dark:text-gray-400 mb-4">
+                                    {t('proxy.config.telemetry.desc')}
+                                </p>
+
+                                <div className="space-y-4">
+                                    <div className="flex items-center">
+                                        <label className="flex items-center cursor-pointer gap-3">
+                                            <div className="relative">
+                                                <input
+                                                    type="checkbox"
+                                                    className="sr-only"
+                                                    checked={formData.proxy?.telemetry?.enabled || false}
+                                                    onChange={(e) => setFormData({
+                                                        ...formData,
+                                                        proxy: {
+                                                            ...formData.proxy,
+                                                            telemetry: {
+                                                                ...DEFAULT_TELEMETRY,
+                                                                ...formData.proxy.telemetry,
+                                                                enabled: e.target.checked
+                                                            }
+                                                        }
+                                                    })}
+                                                />
+                                                <div className={`block w-14 h-8 rounded-full transition-colors ${formData.proxy?.telemetry?.enabled ? 'bg-blue-500' : 'bg-gray-300 dark:bg-base-300'}`}></div>
+                                                <div className={`dot absolute left-1 top-1 bg-white w-6 h-6 rounded-full transition-transform ${formData.proxy?.telemetry?.enabled ? 'transform translate-x-6' : ''}`}></div>
+                                            </div>
+                                            <span className="text-sm font-medium text-gray-900 dark:text-base-content">
+                                                {t('proxy.config.telemetry.enable')}
+                                            </span>
+                                        </label>
+                                    </div>
+
+                                    <div>
+                                        <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
+                                            {t('proxy.config.telemetry.endpoint')}
+                                        </label>
+                                        <input
+                                            type="text"
+                                            value={formData.proxy?.telemetry?.endpoint ?? DEFAULT_TELEMETRY.endpoint}
+                                            onChange={(e) => setFormData({
+                                                ...formData,
+                                                proxy: {
+                                                    ...formData.proxy,
+                                                    telemetry: {
+                                                        ...DEFAULT_TELEMETRY,
+                                                        ...formData.proxy.telemetry,
+                                                        endpoint: e.target.value
+                                                    }
+                                                }
+                                            })}
+                                            placeholder={DEFAULT_TELEMETRY.endpoint}
+                                            className="w-full px-4 py-4 border border-gray-200 dark:border-base-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-transparent text-gray-900 dark:text-base-content bg-gray-50 dark:bg-base-200"
+                                        />
+                                    </div>
+
+                                    <div className="grid grid-cols-2 gap-4">
+                                        <div>
+                                            <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
+                                                {t('proxy.config.telemetry.service_name')}
+                                            </label>
+                                            <input
+                                                type="text"
+                                                value={formData.proxy?.telemetry?.service_name ?? DEFAULT_TELEMETRY.service_name}
+                                                onChange={(e) => setFormData({
+                                                    ...formData,
+                                                    proxy: {
+                                                        ...formData.proxy,
+                                                        telemetry: {
+                                                            ...DEFAULT_TELEMETRY,
+                                                            ...formData.proxy.telemetry,
+                                                            service_name: e.target.value
+                                                        }
+                                                    }
+                                                })}
+                                                className="w-full px-4 py-4 border border-gray-200 dark:border-base-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-transparent text-gray-900 dark:text-base-content bg-gray-50 dark:bg-base-200"
+                                            />
+                                        </div>
+                                        <div>
+                                            <label className="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-1">
+                                                {t('proxy.config.telemetry.sample_ratio')}
+                                            </label>
+                                            <input
+                                                type="number"
+                                                min={0}
+                                                max={1}
+                                                step={0.05}
+                                                value={formData.proxy?.telemetry?.sample_ratio ?? DEFAULT_TELEMETRY.sample_ratio}
+                                                onChange={(e) => setFormData({
+                                                    ...formData,
+                                                    proxy: {
+                                                        ...formData.proxy,
+                                                        telemetry: {
+                                                            ...DEFAULT_TELEMETRY,
+                                                            ...formData.proxy.telemetry,
+                                                            sample_ratio: Math.min(1, Math.max(0, Number(e.target.value) || 0))
+                                                        }
+                                                    }
+                                                })}
+                                                className="w-full px-4 py-4 border border-gray-200 dark:border-base-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-transparent text-gray-900 dark:text-base-content bg-gray-50 dark:bg-base-200"
+                                            />
+                                        </div>
+                                    </div>
+                                    <p className="text-xs text-gray-500 dark:text-gray-400">
+                                        {t('proxy.config.telemetry.tip')}
+                                    </p>
+                                </div>
+                            </div>
                         </div>
                     )}
                     {activeTab === 'about' && (
@@ -2,7 +2,7 @@ import React, { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { useTranslation } from 'react-i18next';
 import { BarChart, Bar, XAxis, YAxis, CartesianGrid, Tooltip, ResponsiveContainer, PieChart, Pie, Cell } from 'recharts';
-import { Clock, Calendar, CalendarDays, Users, Zap, TrendingUp, RefreshCw } from 'lucide-react';
+import { Clock, Calendar, CalendarDays, Users, Zap, TrendingUp, RefreshCw, Tag } from 'lucide-react';
 
 interface TokenStatsAggregated {
     period: string;
@@ -20,6 +20,14 @@ interface AccountTokenStats {
     request_count: number;
 }
 
+interface TagTokenStats {
+    tag: string;
+    total_input_tokens: number;
+    total_output_tokens: number;
+    total_tokens: number;
+    request_count: number;
+}
+
 interface TokenStatsSummary {
     total_input_tokens: number;
     total_output_tokens: number;
@@ -69,6 +77,8 @@ const TokenStats: React.FC = () => {
     const [chartData, setChartData] = useState<TokenStatsAggregated[]>([]);
     const [accountData, setAccountData] = useState<AccountTokenStats[]>([]);
     const [summary, setSummary] = useState<TokenStatsSummary | null>(null);
+    const [tagData, setTagData] = useState<TagTokenStats[]>([]);
+    const [tagKey, setTagKey] = useState('');
     const [loading, setLoading] = useState(true);
 
     const fetchData = async () => {
@@ -112,6 +122,17 @@ const TokenStats: React.FC = () => {
         fetchData();
     }, [timeRange]);
 
+    // 按标签统计 (X-AG-Tag 请求头)，可按标签键过滤
+    useEffect(() => {
+        const hours = timeRange === 'hourly' ? 24 : timeRange === 'daily' ? 168 : 720;
+        const timer = setTimeout(() => {
+            invoke<TagTokenStats[]>('get_token_stats_by_tag', { hours, key: tagKey.trim() || null })
+                .then(setTagData)
+                .catch((error) => console.error('Failed to fetch tag stats:', error));
+        }, 300);
+        return () => clearTimeout(timer);
+    }, [timeRange, tagKey]);
+
     const pieData = accountData.slice(0, 8).map((account, index) => ({
         name: account.account_email.split('@')[0] + '...',
         value: account.total_tokens,
@@ -362,6 +383,56 @@ const TokenStats: React.FC = () => {
                         </div>
                     </div>
                 )}
+
+                <div className="bg-white dark:bg-base-100 rounded-xl p-5 shadow-sm border border-gray-100 dark:border-base-200">
+                    <div className="flex items-center justify-between mb-4 gap-3">
+                        <h2 className="text-base font-semibold text-gray-900 dark:text-base-content flex items-center gap-2">
+                            <Tag className="w-4 h-4 text-amber-500" />
+                            {t('token_stats.by_tag', '分标签统计')}
+                        </h2>
+                        <input
+                            type="text"
+                            value={tagKey}
+                            onChange={(e) => setTagKey(e.target.value)}
+                            placeholder={t('token_stats.tag_filter_placeholder', '按标签键过滤，如 project')}
+                            className="input input-xs input-bordered w-48 text-xs"
+                        />
+                    </div>
+                    {tagData.length > 0 ? (
+                        <div className="overflow-x-auto">
+                            <table className="w-full text-xs text-left">
+                                <thead>
+                                    <tr className="text-gray-400 dark:text-gray-500 font-medium border-b border-gray-100 dark:border-base-200">
+                                        <th className="py-2.5 px-2">{t('token_stats.tag', '标签')}</th>
+                                        <th className="py-2.5 px-2 text-right">{t('token_stats.requests', '请求数')}</th>
+                                        <th className="py-2.5 px-2 text-right">{t('token_stats.input', '输入')}</th>
+                                        <th className="py-2.5 px-2 text-right">{t('token_stats.output', '输出')}</th>
+                                        <th className="py-2.5 px-2 text-right">{t('token_stats.total', '合计')}</th>
+                                    </tr>
+                                </thead>
+                                <tbody className="divide-y divide-gray-50 dark:divide-base-200/50 text-gray-600 dark:text-gray-300">
+                                    {tagData.map((row) => (
+                                        <tr key={row.tag} className="hover:bg-gray-50/50 dark:hover:bg-base-200/30 transition-colors">
+                                            <td className="py-3 px-2 font-mono text-gray-700 dark:text-gray-300">{row.tag}</td>
+                                            <td className="py-3 px-2 text-right">{row.request_count.toLocaleString()}</td>
+                                            <td className="py-3 px-2 text-right text-emerald-600 dark:text-emerald-400">
+                                                {formatNumber(row.total_input_tokens)}
+                                            </td>
+                                            <td className="py-3 px-2 text-right text-indigo-600 dark:text-indigo-400">
+                                                {formatNumber(row.total_output_tokens)}
+                                            </td>
+                                            <td className="py-3 px-2 text-right font-bold text-gray-900 dark:text-base-content">
+                                                {formatNumber(row.total_tokens)}
+                                            </td>
+                                        </tr>
+                                    ))}
+                                </tbody>
+                            </table>
+                        </div>
+                    ) : (
+                        <p className="text-xs text-gray-400">{t('token_stats.no_tag_data', '暂无标签数据。请求时附带 X-AG-Tag: project=foo 请求头即可按标签归集用量。')}</p>
+                    )}
+                </div>
             </div>
         </div>
     );
@@ -1,6 +1,6 @@
 import i18n from '../i18n';
 import { request as invoke } from '../utils/request';
-import { Account, QuotaData, DeviceProfile, DeviceProfileVersion } from '../types/account';
+import { Account, AccountStatus, QuotaData, DeviceProfile, DeviceProfileVersion } from '../types/account';
 
 // 检查 Tauri 环境
 function ensureTauriEnvironment() {
@@ -23,6 +23,10 @@ export async function addAccount(email: string, refreshToken: string): Promise<A
     return await invoke('add_account', { email, refreshToken });
 }
 
+export async function addApiKeyAccount(apiKey: string, name?: string): Promise<Account> {
+    return await invoke('add_api_key_account', { apiKey, name });
+}
+
 export async function deleteAccount(accountId: string): Promise<void> {
     return await invoke('delete_account', { accountId });
 }
@@ -111,6 +115,22 @@ export async function toggleProxyStatus(accountId: string, enable: boolean, reas
     return await invoke('toggle_proxy_status', { accountId, enable, reason });
 }
 
+/**
+ * 临时暂停账号 (维护模式)
+ * @param minutes 暂停时长，省略表示直到手动恢复
+ */
+export async function pauseAccount(accountId: string, minutes?: number, reason?: string): Promise<void> {
+    return await invoke('pause_account', { accountId, minutes, reason });
+}
+
+export async function resumeAccount(accountId: string): Promise<void> {
+    return await invoke('resume_account', { accountId });
+}
+
+export async function getAccountStates(): Promise<AccountStatus[]> {
+    return await invoke('get_account_states');
+}
+
 /**
  * 重新排序账号列表
  * @param accountIds 按新顺序排列的账号ID数组
@@ -1,5 +1,5 @@
 import { request as invoke } from '../utils/request';
-import { AppConfig } from '../types/config';
+import { AppConfig, CapabilityProbeReport, ClientConfigKind, ClientConfigOptions, ConfigProfile, ConfigProfileList, DailyReportConfig, DiagnosticsReport, GeneratedClientConfig, ModelLimitRule, ModelLimitsReport, NotificationConfig, PromptPreset, QuirkManifestReport, SchedulingMode, SetupOptions, SetupPortCheck, SetupResult, TrayProxyState } from '../types/config';
 
 export async function loadConfig(): Promise<AppConfig> {
     return await invoke('load_config');
@@ -8,3 +8,99 @@ export async function loadConfig(): Promise<AppConfig> {
 export async function saveConfig(config: AppConfig): Promise<void> {
     return await invoke('save_config', { config });
 }
+
+export async function listPromptPresets(): Promise<PromptPreset[]> {
+    return await invoke('list_prompt_presets');
+}
+
+export async function savePromptPreset(preset: PromptPreset): Promise<void> {
+    return await invoke('save_prompt_preset', { preset });
+}
+
+export async function deletePromptPreset(name: string): Promise<boolean> {
+    return await invoke('delete_prompt_preset', { name });
+}
+
+export async function listConfigProfiles(): Promise<ConfigProfileList> {
+    return await invoke('list_config_profiles');
+}
+
+export async function saveConfigProfile(profile: ConfigProfile): Promise<void> {
+    return await invoke('save_config_profile', { profile });
+}
+
+export async function deleteConfigProfile(name: string): Promise<boolean> {
+    return await invoke('delete_config_profile', { name });
+}
+
+export async function activateConfigProfile(name: string | null): Promise<void> {
+    return await invoke('activate_config_profile', { name });
+}
+
+export async function runDiagnostics(createBundle: boolean): Promise<DiagnosticsReport> {
+    return await invoke('run_diagnostics', { createBundle });
+}
+
+export async function checkSetupPort(port: number): Promise<SetupPortCheck> {
+    return await invoke('check_setup_port', { port });
+}
+
+export async function runSetupWizard(options?: SetupOptions): Promise<SetupResult> {
+    return await invoke('run_setup_wizard', { options });
+}
+
+export async function generateClientConfigs(options?: ClientConfigOptions): Promise<GeneratedClientConfig[]> {
+    return await invoke('generate_client_configs', { options });
+}
+
+export async function exportClientConfig(kind: ClientConfigKind, filePath: string, options?: ClientConfigOptions): Promise<void> {
+    return await invoke('export_client_config', { kind, options, filePath });
+}
+
+export async function getTrayProxyState(): Promise<TrayProxyState> {
+    return await invoke('get_tray_proxy_state');
+}
+
+export async function setTrayProxyRunning(running: boolean): Promise<TrayProxyState> {
+    return await invoke('set_tray_proxy_running', { running });
+}
+
+export async function setTraySchedulingMode(mode: SchedulingMode): Promise<TrayProxyState> {
+    return await invoke('set_tray_scheduling_mode', { mode });
+}
+
+export async function sendTestNotification(config: NotificationConfig): Promise<number> {
+    return await invoke('send_test_notification', { config });
+}
+
+export async function generateDailyReport(config: DailyReportConfig, deliver: boolean, date?: string): Promise<string> {
+    return await invoke('generate_daily_report', { config, date, deliver });
+}
+
+export async function getCapabilityProbes(): Promise<CapabilityProbeReport> {
+    return await invoke('get_capability_probes');
+}
+
+export async function probeModelCapabilities(accountIds: string[] = [], models: string[] = []): Promise<CapabilityProbeReport> {
+    return await invoke('probe_model_capabilities', { accountIds, models });
+}
+
+export async function getModelLimits(): Promise<ModelLimitsReport> {
+    return await invoke('get_model_limits');
+}
+
+export async function setModelLimitOverrides(overrides: ModelLimitRule[]): Promise<ModelLimitsReport> {
+    return await invoke('set_model_limit_overrides', { overrides });
+}
+
+export async function refreshModelLimitsManifest(url?: string): Promise<ModelLimitsReport> {
+    return await invoke('refresh_model_limits_manifest', { url });
+}
+
+export async function getQuirkManifest(): Promise<QuirkManifestReport> {
+    return await invoke('get_quirk_manifest');
+}
+
+export async function refreshQuirkManifest(): Promise<QuirkManifestReport> {
+    return await invoke('refresh_quirk_manifest');
+}
@@ -12,6 +12,7 @@ interface AccountState {
     fetchAccounts: () => Promise<void>;
     fetchCurrentAccount: () => Promise<void>;
     addAccount: (email: string, refreshToken: string) => Promise<void>;
+    addApiKeyAccount: (apiKey: string, name?: string) => Promise<void>;
     deleteAccount: (accountId: string) => Promise<void>;
     deleteAccounts: (accountIds: string[]) => Promise<void>;
     switchAccount: (accountId: string) => Promise<void>;
@@ -28,6 +29,8 @@ interface AccountState {
     importFromCustomDb: (path: string) => Promise<void>;
     syncAccountFromDb: () => Promise<void>;
     toggleProxyStatus: (accountId: string, enable: boolean, reason?: string) => Promise<void>;
+    pauseAccount: (accountId: string, minutes?: number, reason?: string) => Promise<void>;
+    resumeAccount: (accountId: string) => Promise<void>;
     warmUpAccounts: () => Promise<string>;
     warmUpAccount: (accountId: string) => Promise<string>;
 }
@@ -72,6 +75,18 @@ export const useAccountStore = create<AccountState>((set, get) => ({
         }
     },
 
+    addApiKeyAccount: async (apiKey: string, name?: string) => {
+        set({ loading: true, error: null });
+        try {
+            await accountService.addApiKeyAccount(apiKey, name);
+            await get().fetchAccounts();
+            set({ loading: false });
+        } catch (error) {
+            set({ error: String(error), loading: false });
+            throw error;
+        }
+    },
+
     deleteAccount: async (accountId: string) => {
         set({ loading: true, error: null });
         try {
@@ -269,6 +284,26 @@ export const useAccountStore = create<AccountState>((set, get) => ({
         }
     },
 
+    pauseAccount: async (accountId: string, minutes?: number, reason?: string) => {
+        try {
+            await accountService.pauseAccount(accountId, minutes, reason);
+            await get().fetchAccounts();
+        } catch (error) {
+            console.error('[AccountStore] Pause account failed:', error);
+            throw error;
+        }
+    },
+
+    resumeAccount: async (accountId: string) => {
+        try {
+            await accountService.resumeAccount(accountId);
+            await get().fetchAccounts();
+        } catch (error) {
+            console.error('[AccountStore] Resume account failed:', error);
+            throw error;
+        }
+    },
+
     warmUpAccounts: async () => {
         set({ loading: true, error: null });
         try {
@@ -2,6 +2,7 @@ export interface Account {
     id: string;
     email: string;
     name?: string;
+    kind?: 'oauth' | 'api_key'; // api_key: AI Studio API Key (存于 token.access_token)
     token: TokenData;
     device_profile?: DeviceProfile;
     device_history?: DeviceProfileVersion[];
@@ -13,10 +14,41 @@ export interface Account {
     proxy_disabled_reason?: string;
     proxy_disabled_at?: number;
     protected_models?: string[];
+    last_warm_at?: number;
+    usage_schedule?: UsageSchedule;
+    tags?: string[]; // 按标签批量操作
+    paused_at?: number; // 临时暂停 (维护模式)
+    paused_until?: number; // 为空表示直到手动恢复
+    paused_reason?: string;
     created_at: number;
     last_used: number;
 }
 
+export type AccountStateKind = 'active' | 'paused' | 'cooldown' | 'disabled' | 'error';
+
+export interface AccountStatus {
+    account_id: string;
+    email: string;
+    state: AccountStateKind;
+    reason?: string;
+    until?: number;
+}
+
+/** 账号是否处于临时暂停中 (到期的暂停视为已恢复) */
+export function isAccountPaused(account: Account, now: number = Date.now() / 1000): boolean {
+    return account.paused_at != null && (account.paused_until == null || now < account.paused_until);
+}
+
+export interface UsageSchedule {
+    windows: ScheduleWindow[];
+}
+
+export interface ScheduleWindow {
+    days?: number[]; // 1 = Monday ... 7 = Sunday, empty = every day
+    start: string; // "HH:MM"
+    end: string; // "HH:MM", start > end spans midnight
+}
+
 export interface TokenData {
     access_token: string;
     refresh_token: string;
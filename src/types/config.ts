@@ -3,23 +3,240 @@ export interface UpstreamProxyConfig {
     url: string;
 }
 
+export type EndpointSelectionStrategy = 'ordered' | 'latency';
+
+export interface UpstreamEndpointsConfig {
+    base_urls: string[];
+    strategy: EndpointSelectionStrategy;
+    failure_cooldown_secs: number;
+    header_timeout_secs: number;
+    circuit_breaker?: CircuitBreakerConfig;
+}
+
+// 按 (账号, 端点) 的熔断器
+export interface CircuitBreakerConfig {
+    enabled: boolean;
+    failure_threshold: number;
+    open_secs: number;
+    max_open_secs: number;
+}
+
+export interface UpstreamPoolConfig {
+    http2: boolean;
+    max_idle_per_host: number;
+    idle_timeout_secs: number;
+    tcp_keepalive_secs: number;
+    http2_keepalive_interval_secs: number;
+    prewarm: boolean;
+}
+
+export interface CorsConfig {
+    enabled: boolean;
+    allowed_origins: string[];
+    allowed_headers: string[];
+    expose_headers: string[];
+    allow_credentials: boolean;
+    allow_private_network: boolean;
+    max_age_secs: number;
+}
+
+export interface LocalSocketConfig {
+    enabled: boolean;
+    path: string;
+    trust_peers: boolean;
+}
+
+// 外部状态存储: 多副本共享账号与限流状态 (修改后需重启反代服务)
+export type LogFormat = 'text' | 'json';
+
+export interface TelemetryConfig {
+    enabled: boolean;
+    endpoint: string; // OTLP/HTTP traces endpoint (JSON), e.g. Jaeger :4318/v1/traces
+    service_name: string;
+    sample_ratio: number; // 0.0 - 1.0; a client traceparent's sampled flag takes precedence
+    headers: Record<string, string>;
+}
+
+export type StateStoreBackend = 'local' | 'path' | 'sqlite' | 'postgres';
+
+export interface StateStoreConfig {
+    backend: StateStoreBackend;
+    path: string; // path: 共享目录; sqlite: 数据库文件
+    url: string; // postgres 连接串
+    sync_interval_secs: number;
+    mirror_history: boolean;
+    history_db_path: string; // 请求历史数据库位置，空 = 数据目录
+}
+
+export type HookKind = 'webhook' | 'command';
+export type HookStage = 'before' | 'after' | 'both';
+
+export interface HookConfig {
+    enabled: boolean;
+    name: string;
+    kind: HookKind;
+    target: string;
+    args: string[];
+    stage: HookStage;
+    paths: string[];
+    timeout_ms: number;
+    fail_open: boolean;
+}
+
+export interface HooksConfig {
+    enabled: boolean;
+    hooks: HookConfig[];
+}
+
+export interface PiiScrubConfig {
+    enabled: boolean;
+    mask_emails: boolean;
+    mask_api_keys: boolean;
+    mask_file_paths: boolean;
+    custom_patterns: string[];
+    unmask_responses: boolean;
+}
+
+export interface StreamTeeConfig {
+    enabled: boolean;
+    directory: string;
+}
+
+export interface RetentionConfig {
+    enabled: boolean;
+    max_age_days: number;
+    max_rows: number;
+    max_db_size_mb: number;
+    interval_minutes: number;
+}
+
+export interface TokenBudgetConfig {
+    enabled: boolean;
+    max_prompt_tokens: number;
+    key_budgets: Record<string, number>;
+    context_windows: Record<string, number>;
+    tolerance_percent: number;
+    auto_upgrade: boolean;
+    auto_upgrade_keys: Record<string, boolean>;
+    upgrade_routes: Record<string, string>;
+}
+
+export interface ModelFallbackConfig {
+    enabled: boolean;
+    chains: Record<string, string[]>;
+    trigger_statuses: number[];
+}
+
+export interface AbExperiment {
+    name: string;
+    enabled: boolean;
+    alias: string;
+    variant_model: string;
+    percentage: number;
+}
+
+export interface AbTestConfig {
+    enabled: boolean;
+    experiments: AbExperiment[];
+}
+
+export interface LatencyBudgetConfig {
+    enabled: boolean;
+    disable_thinking_below_ms: number;
+    downgrade_below_ms: number;
+    fast_model: string;
+}
+
+// 竞速模式: 命中规则的请求同时发往多个账号，采用最先输出 token 的一路
+export interface RaceRule {
+    enabled: boolean;
+    alias: string;
+    accounts: number;
+    streaming_only: boolean;
+}
+
+export interface RacingConfig {
+    enabled: boolean;
+    rules: RaceRule[];
+}
+
+export interface AccountPinningConfig {
+    enabled: boolean;
+    allow_model_suffix: boolean;
+    api_keys: string[];
+    aliases: Record<string, string>;
+}
+
 export interface ProxyConfig {
     enabled: boolean;
     allow_lan_access?: boolean;
+    listen_ipv4?: boolean;
+    listen_ipv6?: boolean;
+    mdns_advertise?: boolean;
     auth_mode?: 'off' | 'strict' | 'all_except_health' | 'auto';
+    enable_admin_api?: boolean;
+    admin_api_key?: string; // must be non-empty and differ from api_key
     port: number;
     api_key: string;
     auto_start: boolean;
     custom_mapping?: Record<string, string>;
     request_timeout: number;
+    max_request_body_mb?: number;
+    watchdog_enabled?: boolean; // restart the listener if it dies (default true)
     enable_logging: boolean;
+    log_format?: LogFormat; // console + app.log output format, applied on save
     upstream_proxy: UpstreamProxyConfig;
+    upstream_endpoints?: UpstreamEndpointsConfig;
+    upstream_pool?: UpstreamPoolConfig;
+    cors?: CorsConfig;
+    local_socket?: LocalSocketConfig;
+    state_store?: StateStoreConfig;
+    telemetry?: TelemetryConfig;
+    hooks?: HooksConfig;
+    pii_scrub?: PiiScrubConfig;
+    stream_tee?: StreamTeeConfig;
+    retention?: RetentionConfig;
+    token_budget?: TokenBudgetConfig;
+    model_fallback?: ModelFallbackConfig;
+    ab_test?: AbTestConfig;
+    latency_budget?: LatencyBudgetConfig;
+    racing?: RacingConfig;
+    account_pinning?: AccountPinningConfig;
     zai?: ZaiConfig;
+    openai_backends?: OpenAICompatBackend[];
+    vertex?: VertexConfig;
+    ai_studio?: AiStudioConfig;
     scheduling?: StickySessionConfig;
     experimental?: ExperimentalConfig;
 }
 
-export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst';
+// OpenAI 兼容上游 (映射目标写成 `<name>:<model>` 即路由到该上游)
+export interface OpenAICompatBackend {
+    enabled: boolean;
+    name: string;
+    base_url: string;
+    api_key: string;
+    models: string[]; // 直接由该上游服务的模型，支持 * 通配符
+    headers?: Record<string, string>;
+}
+
+// Vertex AI 服务账号上游 (映射目标写成 `vertex:<model>`)
+export interface VertexConfig {
+    enabled: boolean;
+    credentials_path: string; // 服务账号 JSON 密钥文件
+    project_id: string; // 为空时使用密钥中的 project_id
+    location: string; // us-central1 / global ...
+    models: string[];
+}
+
+// AI Studio API Key 账号路由 (映射目标写成 `aistudio:<model>`)
+export interface AiStudioConfig {
+    fallback: boolean; // OAuth 账号池无可用账号时回退到 API Key
+    models: string[];
+    daily_request_limit: number; // 每个 Key 每日请求上限，0 = 不限制
+}
+
+export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst' | 'Fairness';
 
 export interface StickySessionConfig {
     mode: SchedulingMode;
@@ -33,6 +250,9 @@ export interface ZaiMcpConfig {
     web_search_enabled: boolean;
     web_reader_enabled: boolean;
     vision_enabled: boolean;
+    max_tool_calls?: number; // 0 = unlimited
+    tool_loop_timeout_secs?: number; // 0 = unlimited
+    max_repeated_calls?: number; // 0 = disabled
 }
 
 export interface ZaiModelDefaults {
@@ -62,12 +282,253 @@ export interface QuotaProtectionConfig {
     monitored_models: string[];
 }
 
+export interface CloudSyncConfig {
+    enabled: boolean;
+    backend: 'webdav' | 's3';
+    webdav_url: string;
+    webdav_username: string;
+    webdav_password: string;
+    s3_endpoint: string;
+    s3_region: string;
+    s3_bucket: string;
+    s3_prefix: string;
+    s3_access_key: string;
+    s3_secret_key: string;
+    passphrase: string;
+}
+
+export interface KeepWarmConfig {
+    enabled: boolean;
+    interval_minutes: number;
+    model: string;
+}
+
 export interface PinnedQuotaModelsConfig {
     models: string[];
 }
 
+export type ImageOutputMode = 'markdown' | 'images' | 'both';
+
+export interface ImageOutputConfig {
+    mode: ImageOutputMode;
+    save_dir: string;
+    link_saved_files: boolean;
+}
+
+export interface LocalFilesConfig {
+    enabled: boolean;
+    roots: string[];
+    trusted_keys: string[];
+    max_file_mb: number;
+}
+
+export interface TranslationConfig {
+    enabled: boolean;
+    keys: Record<string, boolean>;
+    model: string;
+    translate_responses: boolean;
+}
+
 export interface ExperimentalConfig {
     enable_usage_scaling: boolean;
+    signature_degradation?: 'passthrough' | 'synthetic_thought' | 'drop_orphaned' | 'downgrade_model';
+    signature_fallback_model?: string;
+    enable_best_of?: boolean;
+    best_of_judge_model?: string;
+    best_of_max_candidates?: number;
+    enable_prompt_cache_emulation?: boolean;
+    function_response_rules?: FunctionResponseRule[];
+    function_response_auto_detect?: boolean;
+    function_response_structured_max_bytes?: number;
+    stream_resume_max_attempts?: number;
+    enable_json_output_repair?: boolean;
+    json_output_reprompt?: boolean;
+    stream_pacing_cps?: number;
+    stream_pacing_keys?: Record<string, number>;
+    virtual_models?: VirtualModel[];
+    capability_gating?: CapabilityGating;
+    capability_rules?: CapabilityRule[];
+    capability_probe_on_add?: boolean;
+    response_rewrites?: ResponseRewriteRule[];
+    stream_stats?: boolean;
+    translation?: TranslationConfig;
+    markdown_images?: boolean;
+    markdown_images_local_files?: boolean;
+    local_files?: LocalFilesConfig;
+    image_output?: ImageOutputConfig;
+    model_limits?: ModelLimitsConfig;
+    quirks?: QuirksConfig;
+    fim_templates?: FimTemplateRule[];
+    completion_truncation?: CompletionTruncationConfig;
+}
+
+export type ResponseRewriteAction = 'regex_replace' | 'strip_phrase' | 'strip_json_fences' | 'normalize_newlines';
+
+// 响应后处理规则 (流式与非流式均生效，思维内容不改写)
+export interface ResponseRewriteRule {
+    enabled: boolean;
+    name: string;
+    action: ResponseRewriteAction;
+    pattern: string; // regex_replace 的正则 / strip_phrase 的短语
+    replacement: string;
+    api_keys: string[]; // 为空 = 所有
+    models: string[]; // 客户端模型名，支持通配符；为空 = 所有
+}
+
+// 请求特性超出上游 / 模型能力时: 不检查 / 降级 / 拒绝 (400)
+export type CapabilityGating = 'off' | 'degrade' | 'reject';
+
+export interface CapabilityRule {
+    backend?: string; // antigravity / vertex / aistudio，为空表示任意
+    model: string; // 支持通配符
+    tools?: boolean;
+    images?: boolean;
+    system_instruction?: boolean;
+    thinking?: boolean;
+    json_mode?: boolean;
+}
+
+// 账号池实测能力 (null = 未能确定)
+export interface ProbedCapabilities {
+    tools: boolean | null;
+    images: boolean | null;
+    system_instruction: boolean | null;
+    thinking: boolean | null;
+    json_mode: boolean | null;
+}
+
+export interface CapabilityProbeResult {
+    account_id: string;
+    email: string;
+    tier: string | null;
+    model: string;
+    capabilities: ProbedCapabilities;
+    error: string | null; // 模型对该账号不可用
+    probed_at: number;
+}
+
+export interface CapabilityProbeReport {
+    results: CapabilityProbeResult[];
+    merged: Record<string, ProbedCapabilities>;
+}
+
+export interface ModelLimitRule {
+    model: string;
+    context_window?: number | null;
+    max_output_tokens?: number | null;
+}
+
+export interface ModelLimitsConfig {
+    overrides: ModelLimitRule[];
+    manifest_url: string;
+    clamp_max_output: boolean;
+}
+
+export interface ModelLimitsReport {
+    builtin: ModelLimitRule[];
+    manifest: ModelLimitRule[];
+    overrides: ModelLimitRule[];
+    manifest_url: string | null;
+    fetched_at: number | null;
+}
+
+export interface ModelQuirk {
+    model: string;
+    requires_thought_signature?: boolean | null;
+    strip_thought_parts?: boolean | null;
+    schema_drop_keywords?: string[];
+}
+
+export interface QuirksConfig {
+    manifest_url: string;
+    public_key: string;
+    refresh_interval_hours: number;
+    overrides: ModelQuirk[];
+}
+
+export interface QuirkManifest {
+    version: number;
+    models: ModelQuirk[];
+    endpoints: string[];
+}
+
+export interface QuirkManifestReport {
+    manifest: QuirkManifest | null;
+    manifest_url: string | null;
+    fetched_at: number | null;
+    overrides: ModelQuirk[];
+}
+
+export type VirtualStepStage = 'pre' | 'main' | 'post';
+
+export interface VirtualModelStep {
+    stage?: VirtualStepStage;
+    model: string;
+    system_prompt?: string;
+    prompt?: string; // `{{input}}` 替换为本步输入
+}
+
+export interface VirtualModel {
+    name: string;
+    enabled?: boolean;
+    steps: VirtualModelStep[];
+}
+
+export interface FunctionResponseRule {
+    model: string;
+    wrapper?: 'result' | 'content' | 'output' | 'inline';
+    structured?: boolean;
+}
+
+export interface FimTemplateRule {
+    model: string;
+    style?: 'instruction' | 'tokens';
+    template: string; // {prefix} / {suffix} placeholders
+    system?: string;
+    stop?: string[];
+}
+
+export interface CompletionTruncationConfig {
+    fim_max_tokens: number; // 0 = 不截断
+    prompt_max_tokens: number; // 0 = 不截断
+    prefix_percent: number;
+}
+
+// 账号事件通知
+export type NotificationEventName = 'quota_exhausted' | 'token_refresh_failed' | 'all_accounts_unhealthy';
+
+export interface WebhookConfig {
+    enabled: boolean;
+    name: string;
+    url: string;
+    events: NotificationEventName[]; // 为空表示全部事件
+    template: string; // 为空时发送默认 JSON；支持 {{event}} {{title}} {{message}} {{account}} {{timestamp}}
+    headers?: Record<string, string>;
+}
+
+export interface DailyReportConfig {
+    enabled: boolean;
+    hour: number; // 本地时间 0-23，之后生成前一天的报告
+    desktop: boolean;
+    report_path: string; // 追加 Markdown 报告的文件，为空不写入
+}
+
+export interface NotificationConfig {
+    desktop: boolean;
+    webhooks: WebhookConfig[];
+    cooldown_secs: number;
+    daily_report?: DailyReportConfig;
+}
+
+export type UpdateChannel = 'stable' | 'beta';
+
+export interface UpdateSettings {
+    auto_check: boolean;
+    last_check_time: number;
+    check_interval_hours: number;
+    channel: UpdateChannel;
+    auto_download: boolean;
+    install_on_quit: boolean;
 }
 
 export interface AppConfig {
@@ -83,10 +544,132 @@ export interface AppConfig {
     auto_launch?: boolean; // 开机自动启动
     auto_check_update?: boolean; // 自动检查更新
     update_check_interval?: number; // 更新检查间隔（小时）
+    update_channel?: UpdateChannel; // 更新通道
+    update_auto_download?: boolean; // 后台下载更新
+    update_install_on_quit?: boolean; // 退出时安装已下载的更新
     accounts_page_size?: number; // 账号列表每页显示数量,默认 0 表示自动计算
     scheduled_warmup: ScheduledWarmupConfig;
+    keep_warm?: KeepWarmConfig;
+    cloud_sync?: CloudSyncConfig;
+    notifications?: NotificationConfig; // 账号事件通知 (Webhook / 桌面)
     quota_protection: QuotaProtectionConfig; // [NEW] 配额保护配置
     pinned_quota_models: PinnedQuotaModelsConfig; // [NEW] 配额关注列表
     proxy: ProxyConfig;
 }
 
+
+// 提示词预设 (客户端以 `preset:<name>` 作为模型名选用)
+export interface PresetTool {
+    name: string;
+    description?: string;
+    parameters?: Record<string, unknown>;
+}
+
+export interface PromptPreset {
+    name: string;
+    description?: string;
+    model: string;
+    system_prompt?: string;
+    temperature?: number;
+    tools?: PresetTool[];
+}
+
+// 配置档案 (整套反代设置，激活切换或以 X-AG-Profile 请求头按请求选用)
+export interface ConfigProfile {
+    name: string;
+    description?: string;
+    proxy: ProxyConfig; // 激活时保留当前的 api_key / auto_start
+    account_ids: string[]; // 为空表示全部账号
+    system_prompt?: string;
+}
+
+export interface ConfigProfileList {
+    active?: string;
+    profiles: ConfigProfile[];
+}
+
+export type DiagnosticStatus = 'pass' | 'warn' | 'fail' | 'skipped';
+
+export interface DiagnosticCheck {
+    name: string; // port | dns | tls | clock_skew | tokens | generation
+    status: DiagnosticStatus;
+    message: string;
+    duration_ms: number;
+    details?: unknown;
+}
+
+export interface DiagnosticsReport {
+    generated_at: number;
+    app_version: string;
+    os: string;
+    arch: string;
+    checks: DiagnosticCheck[];
+    bundle_path: string | null; // redacted zip for bug reports
+}
+
+export interface SetupPortCheck {
+    port: number;
+    available: boolean; // free, or already used by this app's proxy
+    proxy_running: boolean;
+    suggested_port: number | null;
+    message: string;
+}
+
+export interface SetupOptions {
+    port?: number;
+    regenerate_api_key?: boolean;
+    model?: string;
+}
+
+export interface SetupProgress {
+    step: 'port' | 'api_key' | 'generation' | 'done';
+    status: DiagnosticStatus | null; // null while the step is running
+    message: string;
+}
+
+export interface SetupClientConfig {
+    client: string; // Cline | Cursor | SillyTavern
+    settings: [string, string][];
+    snippet: string;
+    note: string | null;
+}
+
+export interface SetupResult {
+    port: number;
+    base_url: string;
+    api_key: string;
+    api_key_created: boolean;
+    generation: DiagnosticCheck;
+    clients: SetupClientConfig[];
+}
+
+export type ClientConfigKind = 'cline' | 'codex' | 'litellm' | 'openai_env';
+
+export interface ClientConfigOptions {
+    models?: string[]; // first entry is the default model; empty = custom mapping aliases
+    base_url?: string; // defaults to http://127.0.0.1:<port>
+}
+
+export interface GeneratedClientConfig {
+    kind: ClientConfigKind;
+    file_name: string;
+    content: string;
+}
+
+// off: no auto-start; app: desktop app starts minimized to tray; headless: `serve` only, no window
+export type LaunchMode = 'off' | 'app' | 'headless';
+
+export interface TrayAccount {
+    id: string;
+    email: string;
+    paused: boolean; // 临时暂停或 proxy_disabled
+}
+
+// Pushed via `tray://proxy-state` whenever it changes
+export interface TrayProxyState {
+    running: boolean;
+    port: number;
+    requests_per_minute: number;
+    scheduling_mode: SchedulingMode;
+    accounts: TrayAccount[];
+}